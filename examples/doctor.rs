@@ -0,0 +1,32 @@
+//! 环境检查
+//!
+//! `cargo run --example doctor -- [--iq-dir <目录>] [--sensor <实体名称>]... [--skip <检查>]...`
+//!
+//! 检查名称为 `sensors`、`iq-files`、`sdk-version`、`dry-run`，有检查失败时以 1 退出。
+use rkaiq::doctor::{doctor_with, DoctorCheck, DoctorOptions};
+use std::env;
+use std::process;
+
+fn usage() -> ! {
+    eprintln!("usage: doctor [--iq-dir <dir>] [--sensor <entity>]... [--skip <check>]...");
+    process::exit(2);
+}
+
+fn main() {
+    let mut opts = DoctorOptions::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let val = args.next().unwrap_or_else(|| usage());
+        opts = match arg.as_str() {
+            "--iq-dir" => opts.iq_file_dir(val),
+            "--sensor" => opts.sensor(val),
+            "--skip" => opts.skip(DoctorCheck::from_name(&val).unwrap_or_else(|| usage())),
+            _ => usage(),
+        };
+    }
+    let report = doctor_with(&opts);
+    print!("{}", report);
+    if !report.is_ok() {
+        process::exit(1);
+    }
+}
@@ -79,6 +79,11 @@ fn main() {
     writeln!(wrapper, "#include <rk_aiq_user_api2_imgproc.h>").unwrap();
     writeln!(wrapper, "#include <rk_aiq_user_api2_sysctl.h>").unwrap();
     writeln!(wrapper, "#include <rk_aiq_user_api2_wrapper.h>").unwrap();
+    #[cfg(feature = "v5_0")]
+    {
+        writeln!(wrapper, "#include <rk_aiq_user_api2_acsm.h>").unwrap();
+        writeln!(wrapper, "#include <rk_aiq_user_api2_acgc.h>").unwrap();
+    }
 
     let defines = &[DEFAULT_ISP_HW_VER_DEF];
 
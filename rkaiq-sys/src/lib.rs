@@ -43,6 +43,34 @@ impl Default for rk_aiq_wb_scene_t {
     }
 }
 
+impl rk_aiq_wb_scene_t {
+    /// 场景的标称色温，单位：K。
+    pub fn nominal_ct(&self) -> u32 {
+        match self {
+            rk_aiq_wb_scene_t::RK_AIQ_WBCT_INCANDESCENT => 2800,
+            rk_aiq_wb_scene_t::RK_AIQ_WBCT_WARM_FLUORESCENT => 3000,
+            rk_aiq_wb_scene_t::RK_AIQ_WBCT_FLUORESCENT => 4000,
+            rk_aiq_wb_scene_t::RK_AIQ_WBCT_DAYLIGHT => 5500,
+            rk_aiq_wb_scene_t::RK_AIQ_WBCT_CLOUDY_DAYLIGHT => 6500,
+            rk_aiq_wb_scene_t::RK_AIQ_WBCT_SHADE => 7500,
+            rk_aiq_wb_scene_t::RK_AIQ_WBCT_TWILIGHT => 10000,
+        }
+    }
+}
+
+/// 按标称色温升序排列；色温相同时按枚举值排列，因此与 `PartialEq` 一致。
+impl PartialOrd for rk_aiq_wb_scene_t {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for rk_aiq_wb_scene_t {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.nominal_ct(), *self as u32).cmp(&(other.nominal_ct(), *other as u32))
+    }
+}
+
 impl fmt::Debug for rk_aiq_lens_info_t {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let len_name = unsafe { CStr::from_ptr(self.len_name.as_ptr()) };
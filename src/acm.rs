@@ -1,7 +1,21 @@
 use super::context::Context;
-use super::error::XCamError;
 use super::ffi;
-use super::types::XCamResult;
+use super::shared::SharedContext;
+use super::types::{Strength, XCamResult};
+use super::uapi::uapi_call;
+
+/// SDK 颜色调节等级的最大值。
+const LEVEL_MAX: u32 = 255;
+
+/// 将强度 [0,100] 按比例映射为 SDK 等级 [0,255]。
+fn strength_to_level(val: Strength) -> u32 {
+    (u32::from(val) * LEVEL_MAX + 50) / 100
+}
+
+/// 将 SDK 等级 [0,255] 按比例映射为强度 [0,100]。
+fn level_to_strength(val: u32) -> Strength {
+    Strength::saturating((val * 100 + LEVEL_MAX / 2) / LEVEL_MAX)
+}
 
 pub trait AutoColorManagment {
     /// 获取亮度等级。
@@ -16,11 +30,11 @@ pub trait AutoColorManagment {
     /// 设置对比度等级，范围：[0,255]。
     fn set_contrast(&self, val: u32) -> XCamResult<()>;
 
-    /// 获取饱和度等级。
-    fn get_saturation(&self) -> XCamResult<u32>;
+    /// 获取饱和度。
+    fn get_saturation(&self) -> XCamResult<Strength>;
 
-    /// 设置饱和度等级，范围：[0,255]。
-    fn set_saturation(&self, val: u32) -> XCamResult<()>;
+    /// 设置饱和度，按比例映射到 SDK 的饱和度等级 [0,255]，50 约对应默认等级 128。
+    fn set_saturation(&self, val: Strength) -> XCamResult<()>;
 
     /// 获取色度等级。
     fn get_hue(&self) -> XCamResult<u32>;
@@ -32,62 +46,87 @@ pub trait AutoColorManagment {
 impl AutoColorManagment for Context {
     fn get_brightness(&self) -> XCamResult<u32> {
         let mut val: u32 = 0;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getBrightness(
-                self.internal.as_ptr(),
-                &mut val,
-            ))
-            .ok()
-            .map(|_| val)
-        }
+        uapi_call!(ffi::rk_aiq_uapi_getBrightness(self, &mut val)).map(|_| val)
     }
 
     fn set_brightness(&self, val: u32) -> XCamResult<()> {
-        unsafe { XCamError::from(ffi::rk_aiq_uapi_setBrightness(self.internal.as_ptr(), val)).ok() }
+        uapi_call!(ffi::rk_aiq_uapi_setBrightness(self, val))
     }
 
     fn get_contrast(&self) -> XCamResult<u32> {
         let mut val: u32 = 0;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getContrast(
-                self.internal.as_ptr(),
-                &mut val,
-            ))
-            .ok()
-            .map(|_| val)
-        }
+        uapi_call!(ffi::rk_aiq_uapi_getContrast(self, &mut val)).map(|_| val)
     }
 
     fn set_contrast(&self, val: u32) -> XCamResult<()> {
-        unsafe { XCamError::from(ffi::rk_aiq_uapi_setContrast(self.internal.as_ptr(), val)).ok() }
+        uapi_call!(ffi::rk_aiq_uapi_setContrast(self, val))
     }
 
-    fn get_saturation(&self) -> XCamResult<u32> {
+    fn get_saturation(&self) -> XCamResult<Strength> {
         let mut val: u32 = 0;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getSaturation(
-                self.internal.as_ptr(),
-                &mut val,
-            ))
-            .ok()
-            .map(|_| val)
-        }
+        uapi_call!(ffi::rk_aiq_uapi_getSaturation(self, &mut val)).map(|_| level_to_strength(val))
     }
 
-    fn set_saturation(&self, val: u32) -> XCamResult<()> {
-        unsafe { XCamError::from(ffi::rk_aiq_uapi_setSaturation(self.internal.as_ptr(), val)).ok() }
+    fn set_saturation(&self, val: Strength) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setSaturation(self, strength_to_level(val)))
     }
 
     fn get_hue(&self) -> XCamResult<u32> {
         let mut val: u32 = 0;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getHue(self.internal.as_ptr(), &mut val))
-                .ok()
-                .map(|_| val)
-        }
+        uapi_call!(ffi::rk_aiq_uapi_getHue(self, &mut val)).map(|_| val)
+    }
+
+    fn set_hue(&self, val: u32) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setHue(self, val))
+    }
+}
+
+impl AutoColorManagment for SharedContext {
+    fn get_brightness(&self) -> XCamResult<u32> {
+        self.inner.get_brightness()
+    }
+
+    fn set_brightness(&self, val: u32) -> XCamResult<()> {
+        self.inner.set_brightness(val)
+    }
+
+    fn get_contrast(&self) -> XCamResult<u32> {
+        self.inner.get_contrast()
+    }
+
+    fn set_contrast(&self, val: u32) -> XCamResult<()> {
+        self.inner.set_contrast(val)
+    }
+
+    fn get_saturation(&self) -> XCamResult<Strength> {
+        self.inner.get_saturation()
+    }
+
+    fn set_saturation(&self, val: Strength) -> XCamResult<()> {
+        self.inner.set_saturation(val)
+    }
+
+    fn get_hue(&self) -> XCamResult<u32> {
+        self.inner.get_hue()
     }
 
     fn set_hue(&self, val: u32) -> XCamResult<()> {
-        unsafe { XCamError::from(ffi::rk_aiq_uapi_setHue(self.internal.as_ptr(), val)).ok() }
+        self.inner.set_hue(val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strength_level_mapping() {
+        assert_eq!(strength_to_level(Strength::MIN), 0);
+        assert_eq!(strength_to_level(Strength::MAX), LEVEL_MAX);
+        assert_eq!(strength_to_level(Strength::new(50).unwrap()), 128);
+        for v in 0..=100 {
+            let s = Strength::new(v).unwrap();
+            assert_eq!(level_to_strength(strength_to_level(s)), s);
+        }
     }
 }
@@ -0,0 +1,269 @@
+//! 画面调节
+//!
+//! 根据 AE 统计的直方图自动拉伸对比度（自动色阶），适用于文档拍摄等需要高对比度的场景。
+//! 取直方图的低、高百分位作为画面的暗部与亮部，计算将二者映射到完整输出范围所需的
+//! CPROC 亮度与对比度，并按强度在不调节与完全拉伸之间插值。
+//!
+//! 直方图统计的是 CPROC 之前的原始数据，调节结果不会反过来影响统计，因此反复调用不会振荡；
+//! 原始数据尚未经过伽马变换，映射只是近似。
+use super::acm::AutoColorManagment;
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::sysctl::SystemControl;
+use super::types::XCamResult;
+use std::time::Duration;
+
+/// 暗部百分位。
+pub const LOW_PERCENTILE: f32 = 0.01;
+/// 亮部百分位。
+pub const HIGH_PERCENTILE: f32 = 0.99;
+/// CPROC 亮度与对比度的中性等级，亮度无偏移、对比度为 1 倍。
+pub const CPROC_NEUTRAL: u32 = 128;
+/// CPROC 等级的最大值。
+const CPROC_MAX: u32 = 255;
+/// 等待 3A 统计信息的最长时间。
+const STATS_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// 一个描述 CPROC 亮度与对比度等级的类型。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CprocLevels {
+    /// 亮度等级，范围：[0,255]，128 为无偏移。
+    pub brightness: u32,
+    /// 对比度等级，范围：[0,255]，128 为 1 倍。
+    pub contrast: u32,
+}
+
+impl Default for CprocLevels {
+    fn default() -> Self {
+        Self {
+            brightness: CPROC_NEUTRAL,
+            contrast: CPROC_NEUTRAL,
+        }
+    }
+}
+
+/// 返回累计计数达到总数 `p` 比例的第一个直方图分组，直方图为空时返回 `None`。
+///
+/// `p` 限制在 [0,1] 内。
+pub fn histogram_percentile(hist: &[u32], p: f32) -> Option<usize> {
+    let total: u64 = hist.iter().map(|&n| u64::from(n)).sum();
+    if total == 0 {
+        return None;
+    }
+    let target = ((total as f64) * f64::from(p.clamp(0.0, 1.0)))
+        .ceil()
+        .max(1.0) as u64;
+    let mut acc = 0u64;
+    hist.iter().position(|&n| {
+        acc += u64::from(n);
+        acc >= target
+    })
+}
+
+/// 由暗部与亮部分组计算 CPROC 等级。
+///
+/// 分组按 `bins` 个等宽分组换算到 8 位取值 `lo`、`hi`。CPROC 以 128 为中心缩放再加偏移：
+/// `out = (in - 128) * k + 128 + d`，其中 `k = contrast / 128`，`d = brightness - 128`。
+/// 完全拉伸时 `lo` 映射到 0、`hi` 映射到 255；按 `strength` 在原值与目标之间插值得到
+/// 目标区间 `[lo', hi']`，再解出 `k = (hi' - lo') / (hi - lo)` 与 `d = lo' - 128 - (lo - 128) * k`。
+/// `strength` 为 0 时返回中性等级；结果限制在 [0,255] 内，对比度最高约 2 倍。
+pub fn levels_for_range(low: usize, high: usize, bins: usize, strength: f32) -> CprocLevels {
+    if bins == 0 || high <= low {
+        return CprocLevels::default();
+    }
+    let scale = 256.0 / bins as f32;
+    let lo = low as f32 * scale;
+    let hi = (high + 1) as f32 * scale - 1.0;
+    let s = strength.clamp(0.0, 1.0);
+    let target_lo = lo * (1.0 - s);
+    let target_hi = hi + (CPROC_MAX as f32 - hi) * s;
+    let k = (target_hi - target_lo) / (hi - lo);
+    let contrast = (k * CPROC_NEUTRAL as f32).round();
+    // 对比度被限制后按实际倍数计算偏移，使暗部仍落在目标位置
+    let contrast = contrast.clamp(0.0, CPROC_MAX as f32);
+    let k = contrast / CPROC_NEUTRAL as f32;
+    let d = target_lo - 128.0 - (lo - 128.0) * k;
+    let brightness = (d + CPROC_NEUTRAL as f32)
+        .round()
+        .clamp(0.0, CPROC_MAX as f32);
+    CprocLevels {
+        brightness: brightness as u32,
+        contrast: contrast as u32,
+    }
+}
+
+/// 由直方图计算自动色阶的 CPROC 等级，直方图为空时返回 `None`。
+pub fn auto_levels_for_histogram(hist: &[u32], strength: f32) -> Option<CprocLevels> {
+    let low = histogram_percentile(hist, LOW_PERCENTILE)?;
+    let high = histogram_percentile(hist, HIGH_PERCENTILE)?;
+    Some(levels_for_range(low, high, hist.len(), strength))
+}
+
+/// 按直方图设置自动色阶，首次设置前的 CPROC 等级保存在 `saved` 中。
+///
+/// `strength` 须为 [0,1] 内的有限值，否则返回 `XCAM_RETURN_ERROR_PARAM`；
+/// 直方图为空时返回 `XCamError::Busy`。
+pub(crate) fn apply_auto_levels<C: AutoColorManagment + ?Sized>(
+    ctx: &C,
+    saved: &mut Option<CprocLevels>,
+    hist: &[u32],
+    strength: f32,
+) -> XCamResult<()> {
+    if !(0.0..=1.0).contains(&strength) {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    let levels = auto_levels_for_histogram(hist, strength).ok_or(XCamError::Busy)?;
+    if saved.is_none() {
+        *saved = Some(CprocLevels {
+            brightness: ctx.get_brightness()?,
+            contrast: ctx.get_contrast()?,
+        });
+    }
+    ctx.set_brightness(levels.brightness)?;
+    ctx.set_contrast(levels.contrast)
+}
+
+/// 恢复 `saved` 中保存的 CPROC 等级，未设置过自动色阶时不做任何操作。
+///
+/// 恢复失败时保留 `saved`，以便重试。
+pub(crate) fn restore_levels<C: AutoColorManagment + ?Sized>(
+    ctx: &C,
+    saved: &mut Option<CprocLevels>,
+) -> XCamResult<()> {
+    if let Some(prior) = *saved {
+        ctx.set_brightness(prior.brightness)?;
+        ctx.set_contrast(prior.contrast)?;
+        *saved = None;
+    }
+    Ok(())
+}
+
+impl Context {
+    /// 按 AE 统计直方图自动调节亮度与对比度，`strength` 范围：[0,1]。
+    ///
+    /// 每次调用只根据最新一帧统计调节一次，场景变化时需要重新调用。首次调用前的亮度与
+    /// 对比度被保存，由 `disable_auto_levels` 恢复。
+    #[cfg(not(feature = "v1_0"))]
+    pub fn auto_levels(&self, strength: f32) -> XCamResult<()> {
+        let stats = self.get_3a_stats(STATS_TIMEOUT)?;
+        let hist = &stats.aec_stats.ae_data.chn[0].rawhist_big.bins;
+        apply_auto_levels(
+            self,
+            &mut self.auto_levels_prior.lock().unwrap(),
+            hist,
+            strength,
+        )
+    }
+
+    /// 按 AE 统计直方图自动调节亮度与对比度。
+    ///
+    /// 该版本 SDK 的统计信息不包含原始直方图，总是返回 `XCamError::Unsupported`。
+    #[cfg(feature = "v1_0")]
+    pub fn auto_levels(&self, _strength: f32) -> XCamResult<()> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 恢复首次调用 `auto_levels` 前的亮度与对比度。
+    pub fn disable_auto_levels(&self) -> XCamResult<()> {
+        restore_levels(self, &mut self.auto_levels_prior.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+
+    /// 在 `[lo, hi]` 分组内均匀分布的 256 分组直方图。
+    fn flat(lo: usize, hi: usize) -> Vec<u32> {
+        let mut hist = vec![0; 256];
+        hist[lo..=hi].iter_mut().for_each(|n| *n = 100);
+        hist
+    }
+
+    #[test]
+    fn test_histogram_percentile() {
+        assert_eq!(histogram_percentile(&[0; 16], 0.5), None);
+        assert_eq!(histogram_percentile(&[], 0.5), None);
+        let hist = flat(64, 191);
+        assert_eq!(histogram_percentile(&hist, 0.0), Some(64));
+        assert_eq!(histogram_percentile(&hist, 0.5), Some(127));
+        assert_eq!(histogram_percentile(&hist, 1.0), Some(191));
+        assert_eq!(histogram_percentile(&[0, 1, 0, 99], 0.01), Some(1));
+    }
+
+    #[test]
+    fn test_levels_for_range() {
+        // 已占满输出范围或强度为 0 时不调节。
+        assert_eq!(levels_for_range(0, 255, 256, 1.0), CprocLevels::default());
+        assert_eq!(levels_for_range(64, 191, 256, 0.0), CprocLevels::default());
+        assert_eq!(levels_for_range(10, 10, 256, 1.0), CprocLevels::default());
+
+        // [64,191] 拉伸到 [0,255]：约 2 倍对比度，以 128 为中心无需偏移。
+        let full = levels_for_range(64, 191, 256, 1.0);
+        assert_eq!(
+            full,
+            CprocLevels {
+                brightness: 128,
+                contrast: 255
+            }
+        );
+        let half = levels_for_range(64, 191, 256, 0.5);
+        assert!(half.contrast > 128 && half.contrast < full.contrast);
+
+        // 偏暗的画面需要提高亮度。
+        let dark = levels_for_range(0, 127, 256, 1.0);
+        assert_eq!(dark.contrast, 255);
+        assert!(dark.brightness > 128, "{:?}", dark);
+
+        // 分组数不同时按比例换算。
+        assert_eq!(levels_for_range(16, 47, 64, 1.0), full);
+    }
+
+    #[test]
+    fn test_auto_levels() {
+        let ctx = MockContext::new();
+        let mut saved = None;
+        ctx.state.borrow_mut().cproc = CprocLevels {
+            brightness: 100,
+            contrast: 140,
+        };
+        let param = || Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        assert_eq!(
+            apply_auto_levels(&ctx, &mut saved, &flat(64, 191), 1.5),
+            param()
+        );
+        assert_eq!(
+            apply_auto_levels(&ctx, &mut saved, &flat(64, 191), f32::NAN),
+            param()
+        );
+        assert_eq!(
+            apply_auto_levels(&ctx, &mut saved, &[0; 256], 1.0),
+            Err(XCamError::Busy)
+        );
+        assert_eq!(saved, None);
+
+        apply_auto_levels(&ctx, &mut saved, &flat(64, 191), 1.0).unwrap();
+        apply_auto_levels(&ctx, &mut saved, &flat(0, 127), 1.0).unwrap();
+        assert_eq!(ctx.state.borrow().cproc.contrast, 255);
+        assert_eq!(
+            saved,
+            Some(CprocLevels {
+                brightness: 100,
+                contrast: 140
+            })
+        );
+
+        restore_levels(&ctx, &mut saved).unwrap();
+        assert_eq!(saved, None);
+        assert_eq!(
+            ctx.state.borrow().cproc,
+            CprocLevels {
+                brightness: 100,
+                contrast: 140
+            }
+        );
+        restore_levels(&ctx, &mut saved).unwrap();
+    }
+}
@@ -1,7 +1,24 @@
-use super::context::Context;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::attrib::with_fields;
+use super::context::{self, Context};
+use super::display::enum_str;
 use super::error::XCamError;
 use super::ffi;
-use super::types::{AntiFlickerMode, ExpPwrLineFreq, OpMode, XCamResult};
+use super::luma::LumaMap;
+use super::misc::Miscellaneous;
+use super::shared::SharedContext;
+use super::sysctl::SystemControl;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::types::WorkingMode;
+use super::types::{
+    AntiFlickerMode, ExpPwrLineFreq, FrameRateInfo, OpMode, Rect, Roi, SensorDescriptor, XCamResult,
+};
+use super::uapi::uapi_call;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::uapi::uapi_get;
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 #[cfg(feature = "v1_0")]
 pub enum AeMode {
@@ -34,6 +51,13 @@ impl From<AeMode> for ffi::aeMode_t {
     }
 }
 
+#[cfg(feature = "v1_0")]
+enum_str!(AeMode, "AE mode" {
+    Auto => "auto",
+    IrisPrior => "iris_prior",
+    ShutterPrior => "shutter_prior",
+});
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AeMeasAreaType {
     Auto,
@@ -72,395 +96,2684 @@ impl From<AeMeasAreaType> for ffi::aeMeasAreaType_e {
     }
 }
 
-pub trait AutoExposure {
-    #[cfg(feature = "v1_0")]
-    fn get_ae_mode(&self) -> XCamResult<AeMode>;
-    #[cfg(feature = "v1_0")]
-    fn set_ae_mode(&self, mode: AeMode) -> XCamResult<()>;
-
-    fn get_exp_mode(&self) -> XCamResult<OpMode>;
-    fn set_exp_mode(&self, mode: OpMode) -> XCamResult<()>;
+enum_str!(AeMeasAreaType, "AE metering area" {
+    Auto => "auto",
+    Up => "up",
+    Bottom => "bottom",
+    Left => "left",
+    Right => "right",
+    Center => "center",
+});
 
-    fn get_exp_gain_range(&self) -> XCamResult<(f32, f32)>;
-    fn set_exp_gain_range(&self, min: f32, max: f32) -> XCamResult<()>;
+/// 一个代表线性曝光属性的类型。
+///
+/// 通过 `with_*` 方法修改个别字段，其余字段保持原值。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+#[derive(Copy, Clone, Default)]
+pub struct LinExpAttr(pub ffi::Uapi_LinExpAttrV2_t);
 
-    fn get_exp_time_range(&self) -> XCamResult<(f32, f32)>;
-    fn set_exp_time_range(&self, min: f32, max: f32) -> XCamResult<()>;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+with_fields!(LinExpAttr {
+    /// 设置目标亮度。
+    with_set_point: f32 => SetPoint;
+    /// 设置夜间目标亮度。
+    with_night_set_point: f32 => NightSetPoint;
+    /// 设置亮度进入收敛的容差。
+    with_tolerance_in: f32 => ToleranceIn;
+    /// 设置亮度退出收敛的容差。
+    with_tolerance_out: f32 => ToleranceOut;
+    /// 设置曝光补偿。
+    with_evbias: f32 => Evbias;
+    /// 设置动态目标亮度开关。
+    with_dy_set_point_en: bool => DySetPointEn;
+});
 
-    fn set_manual_exp(&self, gain: f32, time: f32) -> XCamResult<()>;
-    fn set_manual_exp_fps(&self, gain: f32, time_fps: usize) -> XCamResult<()>;
-    fn set_manual_exp_ms(&self, gain: f32, time_ms: usize) -> XCamResult<()>;
-    fn set_manual_exp_us(&self, gain: f32, time_us: usize) -> XCamResult<()>;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+impl LinExpAttr {
+    /// 同时设置进入与退出收敛的容差。
+    #[must_use]
+    pub fn with_tolerance(self, tolerance: f32) -> Self {
+        self.with_tolerance_in(tolerance)
+            .with_tolerance_out(tolerance)
+    }
+}
 
-    fn set_blc_mode(&self, enabled: bool, mode: AeMeasAreaType) -> XCamResult<()>;
-    fn set_blc_strength(&self, strength: i32) -> XCamResult<()>;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+impl From<ffi::Uapi_LinExpAttrV2_t> for LinExpAttr {
+    fn from(val: ffi::Uapi_LinExpAttrV2_t) -> Self {
+        Self(val)
+    }
+}
 
-    /// 强光抑制开关。
-    fn set_hlc_mode(&self, enabled: bool) -> XCamResult<()>;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+impl From<LinExpAttr> for ffi::Uapi_LinExpAttrV2_t {
+    fn from(val: LinExpAttr) -> Self {
+        val.0
+    }
+}
 
-    /// 设置强光抑制强度。
-    ///
-    /// # Parameters
-    /// * `strength` - 抑制强度，范围[1,100]。
-    fn set_hlc_strength(&self, strength: i32) -> XCamResult<()>;
+/// 一个描述当前曝光结果的类型。
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExpInfo {
+    /// 画面平均亮度，范围：[0,255]。
+    pub mean_luma: f32,
+    /// 当前总增益，单位：倍。
+    pub gain: f32,
+    /// 当前曝光时间，单位：秒。
+    pub time: f32,
+    /// 自动曝光是否已收敛。
+    pub converged: bool,
+    /// 由总增益换算的 ISO 值，换算方式与 `set_iso` 相同。
+    pub iso: u32,
+}
 
-    /// 获取当前暗区提升强度。
-    fn get_dark_area_boost_strth(&self) -> XCamResult<u32>;
+impl From<ffi::Uapi_ExpQueryInfo_t> for ExpInfo {
+    fn from(val: ffi::Uapi_ExpQueryInfo_t) -> Self {
+        let exp = val.CurExpInfo.LinearExp.exp_real_params;
+        let gain = exp.analog_gain * exp.digital_gain * exp.isp_dgain;
+        Self {
+            mean_luma: val.MeanLuma,
+            gain,
+            time: exp.integration_time,
+            converged: val.IsConverged,
+            iso: gain_to_iso(gain, DEFAULT_BASE_ISO),
+        }
+    }
+}
 
-    /// 设置暗区提升强度。
-    ///
-    /// # Parameters
-    /// * `level` - 暗区提升强度，范围[1,10]。
-    fn set_dark_area_boost_strth(&self, level: u32) -> XCamResult<()>;
+/// 输出形如 `1/120s gain 4.0 ISO 400`，曝光时间不短于 1 秒时输出为 `2.0s`。
+impl std::fmt::Display for ExpInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.time > 0.0 && self.time < 1.0 {
+            write!(f, "1/{:.0}s", 1.0 / self.time)?;
+        } else {
+            write!(f, "{:.1}s", self.time)?;
+        }
+        write!(f, " gain {:.1} ISO {}", self.gain, self.iso)
+    }
+}
 
-    /// 获取抗闪模式。
-    fn get_anti_flicker_mode(&self) -> XCamResult<AntiFlickerMode>;
+/// 一个描述当前帧传感器曝光时序的类型，用于外部闪光灯同步。
+///
+/// 传感器以卷帘方式逐行积分，第 0 行在帧起始（读出开始）前 `integration_lines` 行开始曝光。
+/// 以上一帧的帧起始为零点，本帧第 0 行的曝光开始于 `exposure_start_lines`，即
+/// `frame_length_lines - integration_lines` 行之后。行数取自 AIQ 交给传感器驱动的参数，
+/// 各版本均有；微秒值须由传感器描述得到像素时钟。数据缺失（值为 0）的项为 `None`。
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExposureTiming {
+    /// 查询时元数据回调报告的帧号，尚未收到回调时为 `None`。
+    pub frame_id: Option<u32>,
+    /// 积分时间，单位：行。
+    pub integration_lines: Option<u32>,
+    /// 曝光开始相对上一帧帧起始的延迟，单位：行。
+    pub exposure_start_lines: Option<u32>,
+    /// 行长，单位：像素时钟周期。
+    pub line_length_pixels: Option<u32>,
+    /// 帧长，单位：行。
+    pub frame_length_lines: Option<u32>,
+    /// 行周期，单位：微秒。
+    pub line_time_us: Option<f64>,
+    /// 积分时间，单位：微秒。
+    pub integration_us: Option<f64>,
+    /// 曝光开始相对上一帧帧起始的延迟，单位：微秒。
+    pub exposure_start_us: Option<f64>,
+    /// 帧长，单位：微秒。
+    pub frame_length_us: Option<f64>,
+}
 
-    /// 设置抗闪模式。
-    fn set_anti_flicker_mode<T: Into<AntiFlickerMode>>(&self, mode: T) -> XCamResult<()>;
+impl ExposureTiming {
+    /// 由传感器参数计算时序。
+    ///
+    /// 行长、帧长为 0 时取传感器描述中的标称值 `des`；
+    /// 像素时钟无效时微秒值为 `None`。
+    pub(crate) fn new(
+        frame_id: Option<u32>,
+        integration_lines: u32,
+        line_length_pixels: u32,
+        frame_length_lines: u32,
+        des: Option<&SensorDescriptor>,
+    ) -> Self {
+        let nonzero = |v: u32| (v > 0).then_some(v);
+        let integration_lines = nonzero(integration_lines);
+        let line_length_pixels = nonzero(line_length_pixels)
+            .or_else(|| des.and_then(|d| nonzero(d.pixel_periods_per_line as u32)));
+        let frame_length_lines = nonzero(frame_length_lines)
+            .or_else(|| des.and_then(|d| nonzero(d.line_periods_per_field as u32)));
+        let exposure_start_lines = integration_lines
+            .zip(frame_length_lines)
+            .map(|(i, f)| f.saturating_sub(i));
+        let pclk_mhz = des.map_or(0.0, |d| d.pixel_clock_freq_mhz as f64);
+        let line_time_us = line_length_pixels
+            .filter(|_| pclk_mhz.is_finite() && pclk_mhz > 0.0)
+            .map(|l| l as f64 / pclk_mhz);
+        let us = |lines: Option<u32>| line_time_us.zip(lines).map(|(t, n)| t * n as f64);
+        Self {
+            frame_id,
+            integration_lines,
+            exposure_start_lines,
+            line_length_pixels,
+            frame_length_lines,
+            line_time_us,
+            integration_us: us(integration_lines),
+            exposure_start_us: us(exposure_start_lines),
+            frame_length_us: us(frame_length_lines),
+        }
+    }
+}
 
-    /// 获取抗闪频率。
-    fn get_exp_pwr_line_freq_mode(&self) -> XCamResult<ExpPwrLineFreq>;
+/// 按帧号记录的曝光时序的最大帧数，见 `Context::get_exposure_timing_at`。
+pub const EXPOSURE_TIMING_HISTORY: usize = 16;
 
-    /// 设置抗闪频率。
-    fn set_exp_pwr_line_freq_mode<T: Into<ExpPwrLineFreq>>(&self, mode: T) -> XCamResult<()>;
+/// 按帧号记录最近若干帧的曝光时序，帧号相同时以最后一次读取为准。
+#[derive(Debug, Default)]
+pub(crate) struct TimingHistory {
+    entries: VecDeque<ExposureTiming>,
 }
 
-impl AutoExposure for Context {
-    #[cfg(feature = "v1_0")]
-    fn get_ae_mode(&self) -> XCamResult<AeMode> {
-        unsafe {
-            let mut mode = ffi::aeMode_t::default();
-            XCamError::from(ffi::rk_aiq_uapi_getAeMode(
-                self.internal.as_ptr(),
-                &mut mode,
-            ))
-            .ok()
-            .map(|_| mode)
+impl TimingHistory {
+    /// 记录一帧的时序，没有帧号时忽略。
+    pub(crate) fn record(&mut self, timing: ExposureTiming) {
+        let Some(frame_id) = timing.frame_id else {
+            return;
+        };
+        self.entries.retain(|t| t.frame_id != Some(frame_id));
+        if self.entries.len() >= EXPOSURE_TIMING_HISTORY {
+            self.entries.pop_front();
         }
+        self.entries.push_back(timing);
     }
 
-    #[cfg(feature = "v1_0")]
-    fn set_ae_mode(&self, mode: AeMode) -> XCamResult<()> {
-        unsafe { XCamError::from(ffi::rk_aiq_uapi_getAeMode(self.internal.as_ptr(), &mode)).ok() }
+    /// 查找帧号为 `frame_id` 的时序。
+    pub(crate) fn get(&self, frame_id: u32) -> Option<ExposureTiming> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|t| t.frame_id == Some(frame_id))
+            .copied()
     }
+}
 
-    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
-    fn get_exp_mode(&self) -> XCamResult<OpMode> {
-        unsafe {
-            let mut mode = ffi::opMode_t::OP_INVAL;
-            XCamError::from(ffi::rk_aiq_uapi_getExpMode(
-                self.internal.as_ptr(),
-                &mut mode,
-            ))
-            .ok()
-            .map(|_| mode.into())
-        }
-    }
+/// 默认基准 ISO，即总增益为 1 倍时对应的 ISO 值。
+pub const DEFAULT_BASE_ISO: u32 = 100;
 
-    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
-    fn get_exp_mode(&self) -> XCamResult<OpMode> {
-        unsafe {
-            let mut mode = ffi::opMode_t::OP_INVAL;
-            XCamError::from(ffi::rk_aiq_uapi2_getExpMode(
-                self.internal.as_ptr(),
-                &mut mode,
-            ))
-            .ok()
-            .map(|_| mode.into())
-        }
+/// 检查基准 ISO，为 0 时返回 `XCAM_RETURN_ERROR_PARAM`。
+pub(crate) fn check_base_iso(base_iso: u32) -> XCamResult<()> {
+    if base_iso == 0 {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
     }
+    Ok(())
+}
 
-    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
-    fn set_exp_mode(&self, mode: OpMode) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setExpMode(
-                self.internal.as_ptr(),
-                mode.into(),
-            ))
-            .ok()
-        }
-    }
+/// 将总增益换算为 ISO 值：`ISO = 基准 ISO × 总增益`。
+pub fn gain_to_iso(gain: f32, base_iso: u32) -> u32 {
+    (gain * base_iso as f32).round() as u32
+}
 
-    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
-    fn set_exp_mode(&self, mode: OpMode) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setExpMode(
-                self.internal.as_ptr(),
-                mode.into(),
-            ))
-            .ok()
-        }
+/// 将 ISO 值换算为 `(模拟增益, 数字增益)`。
+///
+/// 总增益为 `ISO / 基准 ISO`，优先使用模拟增益，超出 `analog_max` 的部分由数字增益补足。
+/// 总增益小于 1 或大于 `analog_max × digital_max` 时返回错误。
+pub fn iso_to_gains(
+    iso: u32,
+    base_iso: u32,
+    analog_max: f32,
+    digital_max: f32,
+) -> XCamResult<(f32, f32)> {
+    if base_iso == 0 || analog_max < 1.0 || digital_max < 1.0 {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
     }
+    let total = iso as f32 / base_iso as f32;
+    if !(1.0..=analog_max * digital_max).contains(&total) {
+        return Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+    }
+    let analog = total.min(analog_max);
+    Ok((analog, (total / analog).min(digital_max)))
+}
 
-    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
-    fn get_exp_gain_range(&self) -> XCamResult<(f32, f32)> {
-        unsafe {
-            let mut range = ffi::paRange_t::default();
-            XCamError::from(ffi::rk_aiq_uapi_getExpGainRange(
-                self.internal.as_ptr(),
-                &mut range,
-            ))
-            .ok()
-            .map(|_| (range.min, range.max))
-        }
+/// 由传感器描述计算可用的曝光时间，单位：秒，按升序排列。
+///
+/// 传感器按行积分，曝光时间只能取行周期（`行长 / 像素时钟`）的整数倍；
+/// 积分行数的范围为 [`coarse_integration_time_min`, 帧长 − `coarse_integration_time_max_margin`]。
+/// 描述中的时钟或行长无效时返回 `XCamError::Unsupported`。
+pub fn exposure_steps(des: &SensorDescriptor) -> XCamResult<Vec<f32>> {
+    let pclk = des.pixel_clock_freq_mhz as f64 * 1e6;
+    let line_time = des.pixel_periods_per_line as f64 / pclk;
+    let min_lines = des.coarse_integration_time_min.max(1);
+    let max_lines =
+        (des.line_periods_per_field as u32).saturating_sub(des.coarse_integration_time_max_margin);
+    if !(line_time.is_finite() && line_time > 0.0) || max_lines < min_lines {
+        return Err(XCamError::Unsupported);
     }
+    Ok((min_lines..=max_lines)
+        .map(|n| (n as f64 * line_time) as f32)
+        .collect())
+}
 
-    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
-    fn get_exp_gain_range(&self) -> XCamResult<(f32, f32)> {
-        unsafe {
-            let mut range = ffi::paRange_t::default();
-            XCamError::from(ffi::rk_aiq_uapi2_getExpGainRange(
-                self.internal.as_ptr(),
-                &mut range,
-            ))
-            .ok()
-            .map(|_| (range.min, range.max))
-        }
+/// 在升序排列的 `steps` 中查找最接近 `v` 的值，距离相等时取较小者。
+///
+/// `steps` 为空或 `v` 为 NaN 时返回 `None`。
+pub fn nearest_step(steps: &[f32], v: f32) -> Option<f32> {
+    if v.is_nan() {
+        return None;
+    }
+    let i = steps.partition_point(|&s| s < v);
+    let above = steps.get(i).copied();
+    let below = i.checked_sub(1).map(|j| steps[j]);
+    match (below, above) {
+        (Some(b), Some(a)) => Some(if a - v < v - b { a } else { b }),
+        (b, a) => b.or(a),
     }
+}
 
-    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
-    fn set_exp_gain_range(&self, min: f32, max: f32) -> XCamResult<()> {
-        let mut range = ffi::paRange_t { min, max };
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setExpGainRange(
-                self.internal.as_ptr(),
-                &mut range,
-            ))
-            .ok()
-        }
+/// 将曝光时间转换为 SDK 使用的秒数。
+///
+/// SDK 以 `f32` 表示秒数，约有 7 位有效数字：1 秒以内的曝光时间可精确到约 0.1 微秒，
+/// 更长的曝光时间精度随之降低，亚微秒级的差别可能在转换中丢失。
+pub fn duration_to_secs(d: Duration) -> f32 {
+    d.as_secs_f64() as f32
+}
+
+/// ISP 数字增益的最大值，单位：倍。
+pub const ISP_DGAIN_MAX: f32 = 8.0;
+
+/// 检查手动模拟增益与数字增益的取值。
+///
+/// 两者均须为有限值，模拟增益范围为 [1, `analog_max`]，数字增益范围为 [1, `ISP_DGAIN_MAX`]。
+pub(crate) fn check_manual_gains(analog: f32, digital: f32, analog_max: f32) -> XCamResult<()> {
+    if !(analog.is_finite() && digital.is_finite()) {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
     }
+    if !(1.0..=analog_max).contains(&analog) || !(1.0..=ISP_DGAIN_MAX).contains(&digital) {
+        return Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+    }
+    Ok(())
+}
 
-    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
-    fn set_exp_gain_range(&self, min: f32, max: f32) -> XCamResult<()> {
-        let mut range = ffi::paRange_t { min, max };
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setExpGainRange(
-                self.internal.as_ptr(),
-                &mut range,
-            ))
-            .ok()
-        }
+/// 以手动模拟增益 `analog`、ISP 数字增益 `digital` 修改曝光属性，曝光时间固定为 `time`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn apply_manual_gains(
+    sw_attr: &mut ffi::Uapi_ExpSwAttrV2_t,
+    analog: f32,
+    digital: f32,
+    time: f32,
+) {
+    sw_attr.Enable = 1;
+    sw_attr.AecOpType = ffi::RKAiqOPMode_e::RK_AIQ_OP_MODE_MANUAL;
+    let linear = &mut sw_attr.stManual.LinearAE;
+    linear.ManualTimeEn = true;
+    linear.ManualGainEn = true;
+    linear.ManualIspDgainEn = true;
+    linear.TimeValue = time;
+    linear.GainValue = analog;
+    linear.IspDGainValue = digital;
+}
+
+/// 检查 ISP 数字增益上限的取值。
+///
+/// 须为有限值，范围为 [1, `ISP_DGAIN_MAX`]。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn check_digital_gain_cap(cap: f32) -> XCamResult<()> {
+    if !cap.is_finite() {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
     }
+    if !(1.0..=ISP_DGAIN_MAX).contains(&cap) {
+        return Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+    }
+    Ok(())
+}
 
-    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
-    fn get_exp_time_range(&self) -> XCamResult<(f32, f32)> {
-        unsafe {
-            let mut range = ffi::paRange_t::default();
-            XCamError::from(ffi::rk_aiq_uapi_getExpTimeRange(
-                self.internal.as_ptr(),
-                &mut range,
-            ))
-            .ok()
-            .map(|_| (range.min, range.max))
-        }
+/// 以曝光属性的高级范围设置 ISP 数字增益上限 `cap`，下限随之不超过上限。
+///
+/// 高级范围未启用时，启用后其余范围须有效：以 `ranges` 读取当前的
+/// `(曝光时间范围, 增益范围)` 填充，ISP 数字增益下限取 1。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn apply_digital_gain_cap<F>(
+    sw_attr: &mut ffi::Uapi_ExpSwAttrV2_t,
+    cap: f32,
+    ranges: F,
+) -> XCamResult<()>
+where
+    F: FnOnce() -> XCamResult<((f32, f32), (f32, f32))>,
+{
+    check_digital_gain_cap(cap)?;
+    let advanced = &mut sw_attr.stAdvanced;
+    if !advanced.SetAeRangeEn {
+        let ((time_min, time_max), (gain_min, gain_max)) = ranges()?;
+        advanced.SetLinAeRange.stExpTimeRange.Min = time_min;
+        advanced.SetLinAeRange.stExpTimeRange.Max = time_max;
+        advanced.SetLinAeRange.stGainRange.Min = gain_min;
+        advanced.SetLinAeRange.stGainRange.Max = gain_max;
+        advanced.SetLinAeRange.stIspDGainRange.Min = 1.0;
+        advanced.SetAeRangeEn = true;
     }
+    let range = &mut advanced.SetLinAeRange.stIspDGainRange;
+    range.Max = cap;
+    range.Min = range.Min.min(cap);
+    Ok(())
+}
 
-    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
-    fn get_exp_time_range(&self) -> XCamResult<(f32, f32)> {
-        unsafe {
-            let mut range = ffi::paRange_t::default();
-            XCamError::from(ffi::rk_aiq_uapi2_getExpTimeRange(
-                self.internal.as_ptr(),
-                &mut range,
-            ))
-            .ok()
-            .map(|_| (range.min, range.max))
-        }
+/// 由曝光属性还原 ISP 数字增益上限，高级范围未启用时为 `None`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn digital_gain_cap_of(sw_attr: &ffi::Uapi_ExpSwAttrV2_t) -> Option<f32> {
+    let advanced = &sw_attr.stAdvanced;
+    advanced
+        .SetAeRangeEn
+        .then_some(advanced.SetLinAeRange.stIspDGainRange.Max)
+}
+
+/// AE 权重表中单个权重的最大值。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub const AE_WEIGHT_MAX: u8 = 32;
+
+/// AE 权重表的列数。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+const AE_GRID_COLS: usize = 15;
+/// AE 权重表的行数。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+const AE_GRID_ROWS: usize = 15;
+
+/// 返回 AE 权重表的 `(列数, 行数)`，权重按行优先排列。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub fn ae_grid_dimensions() -> (usize, usize) {
+    (AE_GRID_COLS, AE_GRID_ROWS)
+}
+
+/// 检查 AE 权重表：长度须等于 `ae_grid_dimensions` 的格数，否则返回 `XCAM_RETURN_ERROR_PARAM`；
+/// 权重范围为 [0, `AE_WEIGHT_MAX`]，否则返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn check_ae_weights(weights: &[u8]) -> XCamResult<()> {
+    let (cols, rows) = ae_grid_dimensions();
+    if weights.len() != cols * rows {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    if weights.iter().any(|&w| w > AE_WEIGHT_MAX) {
+        return Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
     }
+    Ok(())
+}
 
-    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
-    fn set_exp_time_range(&self, min: f32, max: f32) -> XCamResult<()> {
-        let mut range = ffi::paRange_t { min, max };
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setExpTimeRange(
-                self.internal.as_ptr(),
-                &mut range,
-            ))
-            .ok()
-        }
+/// 优先区域以外的 AE 权重，也是均匀权重表中的权重。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub const AE_BASE_WEIGHT: u8 = 1;
+
+/// 将优先区域栅格化到 `cols` 列 `rows` 行的 AE 权重表 `grid` 中，不分配内存。
+///
+/// 每格的权重由其被区域覆盖的面积比例 `c` 决定：`AE_BASE_WEIGHT + c * strength *
+/// (AE_WEIGHT_MAX - AE_BASE_WEIGHT)`，四舍五入。区域重叠时取覆盖比例最大的一个，
+/// 超出画面的部分被裁掉。`grid` 的长度须为 `cols * rows`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub fn rasterize_regions(
+    regions: &[Roi],
+    strength: f32,
+    cols: usize,
+    rows: usize,
+    grid: &mut [u8],
+) {
+    let (cw, ch) = (1.0 / cols as f32, 1.0 / rows as f32);
+    let span = f32::from(AE_WEIGHT_MAX - AE_BASE_WEIGHT) * strength.clamp(0.0, 1.0);
+    for (i, cell) in grid.iter_mut().enumerate().take(cols * rows) {
+        let (x0, y0) = ((i % cols) as f32 * cw, (i / cols) as f32 * ch);
+        let coverage = regions
+            .iter()
+            .map(|r| {
+                let w = ((r.x + r.width).min(x0 + cw) - r.x.max(x0)).max(0.0);
+                let h = ((r.y + r.height).min(y0 + ch) - r.y.max(y0)).max(0.0);
+                (w * h / (cw * ch)).min(1.0)
+            })
+            .fold(0.0, f32::max);
+        *cell = (f32::from(AE_BASE_WEIGHT) + coverage * span).round() as u8;
     }
+}
 
-    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
-    fn set_exp_time_range(&self, min: f32, max: f32) -> XCamResult<()> {
-        let mut range = ffi::paRange_t { min, max };
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setExpTimeRange(
-                self.internal.as_ptr(),
-                &mut range,
-            ))
-            .ok()
-        }
+/// 按优先区域设置 AE 权重表。
+///
+/// `strength` 须为 [0,1] 内的有限值，区域的坐标与尺寸须为有限值且尺寸不小于 0，
+/// 否则返回 `XCAM_RETURN_ERROR_PARAM`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn apply_priority_regions<C: AutoExposure + ?Sized>(
+    ctx: &C,
+    regions: &[Roi],
+    strength: f32,
+) -> XCamResult<()> {
+    let valid_roi = |r: &Roi| {
+        [r.x, r.y, r.width, r.height].iter().all(|v| v.is_finite())
+            && r.width >= 0.0
+            && r.height >= 0.0
+    };
+    if !(0.0..=1.0).contains(&strength) || !regions.iter().all(valid_roi) {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
     }
+    let mut grid = [AE_BASE_WEIGHT; AE_GRID_COLS * AE_GRID_ROWS];
+    rasterize_regions(regions, strength, AE_GRID_COLS, AE_GRID_ROWS, &mut grid);
+    ctx.set_ae_weights(&grid)
+}
 
-    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
-    fn set_manual_exp(&self, gain: f32, time: f32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setManualExp(
-                self.internal.as_ptr(),
-                gain,
-                time,
-            ))
-            .ok()
-        }
+/// 一个描述曝光路径节点的类型。
+///
+/// 曝光路径决定自动曝光在总曝光量增大时如何分配曝光时间与增益：
+/// 相邻节点之间先延长曝光时间，再提高增益。
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExpRoutePoint {
+    /// 曝光时间，单位：秒。
+    pub time: f32,
+    /// 传感器增益，单位：倍。
+    pub gain: f32,
+    /// HDR 模式下长帧与短帧曝光比的范围 `(min, max)`，线性模式下忽略。
+    ///
+    /// 写入 HDR 曝光路径时，长帧使用 `time`：HDR3 的中帧与短帧分别使用 `time / min` 与
+    /// `time / max`；HDR2 只有长短两帧，短帧使用 `time / max`，读回时上下限相同。
+    pub ratio: Option<(f32, f32)>,
+}
+
+/// 一个描述曝光路径无效或设置失败的枚举。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExpRouteError {
+    /// 路径为空。
+    Empty,
+    /// 节点数超过 SDK 路径数组的长度。
+    TooLong { len: usize, max: usize },
+    /// 节点的取值无效：曝光时间须大于 0，增益与曝光比须不小于 1 且曝光比下限不大于上限。
+    InvalidPoint { index: usize },
+    /// 节点的总曝光量（曝光时间与增益之积）不大于前一节点。
+    NotMonotonic { index: usize },
+    /// SDK 接口返回错误。
+    Sdk(XCamError),
+}
+
+impl From<XCamError> for ExpRouteError {
+    fn from(val: XCamError) -> Self {
+        Self::Sdk(val)
     }
+}
 
-    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
-    fn set_manual_exp(&self, gain: f32, time: f32) -> XCamResult<()> {
-        unsafe {
-            let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
-            use crate::ffi::RKAiqOPMode_e::RK_AIQ_OP_MODE_MANUAL;
-            ffi::rk_aiq_user_api2_ae_getExpSwAttr(self.internal.as_ptr(), &mut sw_attr);
-            sw_attr.Enable = 1;
-            sw_attr.AecOpType = RK_AIQ_OP_MODE_MANUAL;
-            // LinearAE
-            sw_attr.stManual.LinearAE.ManualGainEn = true;
-            sw_attr.stManual.LinearAE.ManualTimeEn = true;
-            sw_attr.stManual.LinearAE.GainValue = gain;
-            sw_attr.stManual.LinearAE.TimeValue = time;
-            let r1 = XCamError::from(ffi::rk_aiq_user_api2_ae_setExpSwAttr(
-                self.internal.as_ptr(),
-                sw_attr,
-            ));
-            r1.ok()
+impl std::fmt::Display for ExpRouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => f.write_str("exposure route is empty"),
+            Self::TooLong { len, max } => {
+                write!(
+                    f,
+                    "exposure route has {} points, at most {} allowed",
+                    len, max
+                )
+            }
+            Self::InvalidPoint { index } => write!(f, "exposure route point {} is invalid", index),
+            Self::NotMonotonic { index } => write!(
+                f,
+                "exposure route point {} does not increase total exposure",
+                index
+            ),
+            Self::Sdk(e) => write!(f, "{}", e),
         }
     }
+}
 
-    fn set_manual_exp_fps(&self, gain: f32, time_fps: usize) -> XCamResult<()> {
-        let time = 1.0 / (time_fps as f32);
-        self.set_manual_exp(gain, time)
-    }
+impl std::error::Error for ExpRouteError {}
 
-    fn set_manual_exp_ms(&self, gain: f32, time_ms: usize) -> XCamResult<()> {
-        let time = 1.0 / 1000.0 * (time_ms as f32);
-        self.set_manual_exp(gain, time)
+/// 检查曝光路径：节点数在 [1, `max`] 内，各节点取值有效，且总曝光量严格递增。
+pub fn check_exp_route(points: &[ExpRoutePoint], max: usize) -> Result<(), ExpRouteError> {
+    if points.is_empty() {
+        return Err(ExpRouteError::Empty);
     }
-
-    fn set_manual_exp_us(&self, gain: f32, time_us: usize) -> XCamResult<()> {
-        let time = 1.0 / 1000000.0 * (time_us as f32);
-        self.set_manual_exp(gain, time)
+    if points.len() > max {
+        return Err(ExpRouteError::TooLong {
+            len: points.len(),
+            max,
+        });
     }
-
-    fn set_blc_mode(&self, enabled: bool, mode: AeMeasAreaType) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setBLCMode(
-                self.internal.as_ptr(),
-                enabled,
-                mode.into(),
-            ))
-            .ok()
+    let mut last = 0.0;
+    for (index, p) in points.iter().enumerate() {
+        let ratio_valid = match p.ratio {
+            Some((lo, hi)) => lo.is_finite() && hi.is_finite() && 1.0 <= lo && lo <= hi,
+            None => true,
+        };
+        if !(p.time.is_finite() && p.time > 0.0 && p.gain.is_finite() && p.gain >= 1.0)
+            || !ratio_valid
+        {
+            return Err(ExpRouteError::InvalidPoint { index });
         }
+        let total = p.time * p.gain;
+        if total <= last {
+            return Err(ExpRouteError::NotMonotonic { index });
+        }
+        last = total;
     }
+    Ok(())
+}
 
-    fn set_blc_strength(&self, strength: i32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setBLCStrength(
-                self.internal.as_ptr(),
-                strength,
-            ))
-            .ok()
+/// 由曝光属性读取曝光路径。
+///
+/// `mode` 为 HDR 模式且 HDR 曝光路径与线性曝光路径节点数一致时，按该模式的帧布局换算各节点的
+/// 曝光比，否则 `ratio` 为 `None`，见 `ExpRoutePoint::ratio`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn read_exp_route(
+    sw_attr: &ffi::Uapi_ExpSwAttrV2_t,
+    mode: Option<WorkingMode>,
+) -> Vec<ExpRoutePoint> {
+    let lin = &sw_attr.stAuto.LinAeRoute;
+    let len = (lin.array_size.max(0) as usize).min(lin.TimeDot.len());
+    let ratios = if sw_attr.stAuto.HdrAeRoute.array_size == lin.array_size {
+        hdr_route_ratios(sw_attr, mode)
+    } else {
+        Vec::new()
+    };
+    (0..len)
+        .map(|i| ExpRoutePoint {
+            time: lin.TimeDot[i],
+            gain: lin.GainDot[i],
+            ratio: ratios.get(i).copied(),
+        })
+        .collect()
+}
+
+/// 按 `mode` 的帧布局由 HDR 曝光路径换算各节点的曝光比，非 HDR 模式为空。
+///
+/// HDR2 的长帧为 Frm1、短帧为 Frm0，曝光比上下限相同；HDR3 的长、中、短帧依次为 Frm2、Frm1、Frm0。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+fn hdr_route_ratios(
+    sw_attr: &ffi::Uapi_ExpSwAttrV2_t,
+    mode: Option<WorkingMode>,
+) -> Vec<(f32, f32)> {
+    let hdr = &sw_attr.stAuto.HdrAeRoute;
+    let len = (hdr.array_size.max(0) as usize).min(hdr.Frm0TimeDot.len());
+    (0..len)
+        .filter_map(|i| match mode {
+            Some(WorkingMode::IspHdr2) => {
+                let r = hdr.Frm1TimeDot[i] / hdr.Frm0TimeDot[i];
+                Some((r, r))
+            }
+            Some(WorkingMode::IspHdr3) => {
+                let long = hdr.Frm2TimeDot[i];
+                Some((long / hdr.Frm1TimeDot[i], long / hdr.Frm0TimeDot[i]))
+            }
+            Some(WorkingMode::Normal) | None => None,
+        })
+        .collect()
+}
+
+/// 以 `points` 替换曝光属性中的曝光路径，`points` 须已经 `check_exp_route` 检查。
+///
+/// HDR 模式下总是同时重写 HDR 曝光路径，使两条路径节点数一致：未给出 `ratio` 的节点沿用原 HDR
+/// 路径中同一位置（超出时为最后一个节点）的曝光比，原路径为空时取 1。线性模式下忽略 `ratio`；
+/// `mode` 为 `None` 时无法确定帧布局，给出 `ratio` 返回 `XCAM_RETURN_ERROR_ORDER`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn write_exp_route(
+    sw_attr: &mut ffi::Uapi_ExpSwAttrV2_t,
+    mode: Option<WorkingMode>,
+    points: &[ExpRoutePoint],
+) -> Result<(), ExpRouteError> {
+    let hdr_mode = match mode {
+        Some(WorkingMode::IspHdr2) | Some(WorkingMode::IspHdr3) => true,
+        Some(WorkingMode::Normal) => false,
+        None if points.iter().any(|p| p.ratio.is_some()) => {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER).into());
         }
+        None => false,
+    };
+    let old_ratios = hdr_route_ratios(sw_attr, mode);
+    let lin = &mut sw_attr.stAuto.LinAeRoute;
+    for (i, p) in points.iter().enumerate() {
+        lin.TimeDot[i] = p.time;
+        lin.GainDot[i] = p.gain;
+        lin.IspDGainDot[i] = 1.0;
+    }
+    lin.array_size = points.len() as i32;
+    if !hdr_mode {
+        return Ok(());
     }
+    let hdr = &mut sw_attr.stAuto.HdrAeRoute;
+    for (i, p) in points.iter().enumerate() {
+        let (min, max) = p
+            .ratio
+            .or_else(|| old_ratios.get(i).or(old_ratios.last()).copied())
+            .unwrap_or((1.0, 1.0));
+        let (short, middle, long) = if mode == Some(WorkingMode::IspHdr2) {
+            (p.time / max, p.time, p.time)
+        } else {
+            (p.time / max, p.time / min, p.time)
+        };
+        hdr.Frm0TimeDot[i] = short;
+        hdr.Frm1TimeDot[i] = middle;
+        hdr.Frm2TimeDot[i] = long;
+        hdr.Frm0GainDot[i] = p.gain;
+        hdr.Frm1GainDot[i] = p.gain;
+        hdr.Frm2GainDot[i] = p.gain;
+        hdr.Frm0IspDGainDot[i] = 1.0;
+        hdr.Frm1IspDGainDot[i] = 1.0;
+        hdr.Frm2IspDGainDot[i] = 1.0;
+    }
+    hdr.array_size = points.len() as i32;
+    Ok(())
+}
 
-    fn set_hlc_mode(&self, enabled: bool) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setHLCMode(self.internal.as_ptr(), enabled)).ok()
+/// 曝光量 `time * gain` 对应的光值（LV），按 f/1 光圈、增益 1 倍相当于 ISO 100 计算。
+///
+/// 场景越亮，达到目标亮度所需的曝光量越小，光值越大；曝光量每增加一倍，光值减 1。
+pub fn exposure_to_lv(time: f32, gain: f32) -> f32 {
+    -(time * gain).log2()
+}
+
+/// 按 APEX 公式计算曝光组合对应的曝光值（EV，按 ISO 100 归一化）：
+///
+/// `EV = log2(N² / t) − log2(ISO / 100)`
+///
+/// `time_s` 为曝光时间，单位：秒；`fnumber` 为光圈 f 值 `N`。f/1、1 秒、ISO 100 为 EV 0；
+/// 光圈收小一档（f 值乘以 √2）或曝光时间减半时 EV 加 1，ISO 加倍时 EV 减 1。
+/// f/1、增益 1 倍相当于 ISO 100 时与 `exposure_to_lv` 一致。
+pub fn exposure_value(time_s: f32, fnumber: f32, iso: u32) -> f32 {
+    (fnumber * fnumber / time_s).log2() - (iso as f32 / 100.0).log2()
+}
+
+/// `exposure_value` 的逆运算：给定光圈与 ISO，返回达到曝光值 `ev` 所需的曝光时间，单位：秒。
+///
+/// `t = N² × 100 / (ISO × 2^EV)`
+pub fn exposure_time_for_ev(ev: f32, fnumber: f32, iso: u32) -> f32 {
+    fnumber * fnumber * 100.0 / (iso as f32 * ev.exp2())
+}
+
+/// 一个描述按光值排列的曝光路径的类型：随场景变暗，各光值下曝光时间与增益的分配。
+///
+/// SDK 的曝光路径只记录曝光时间与增益，节点对应的光值由总曝光量决定，见 `lv`。
+/// 总曝光量严格递增的路径即按光值从亮到暗排列，检查同 `check_exp_route`。
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AeRoute {
+    /// 路径节点，同 `get_exposure_route`。
+    pub points: Vec<ExpRoutePoint>,
+}
+
+impl AeRoute {
+    /// 由 `(曝光时间, 增益)` 节点创建，不设置 HDR 曝光比。
+    pub fn from_exposures(exposures: &[(f32, f32)]) -> Self {
+        Self {
+            points: exposures
+                .iter()
+                .map(|&(time, gain)| ExpRoutePoint {
+                    time,
+                    gain,
+                    ratio: None,
+                })
+                .collect(),
         }
     }
 
-    fn set_hlc_strength(&self, strength: i32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setHLCStrength(
-                self.internal.as_ptr(),
-                strength,
-            ))
-            .ok()
-        }
+    /// 各节点对应的场景光值，按 `exposure_to_lv` 由总曝光量计算。
+    pub fn lv(&self) -> impl Iterator<Item = f32> + '_ {
+        self.points.iter().map(|p| exposure_to_lv(p.time, p.gain))
     }
+}
 
-    fn get_dark_area_boost_strth(&self) -> XCamResult<u32> {
-        let mut level: u32 = 0;
-        #[cfg(feature = "v2_0")]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getDarkAreaBoostStrth(
-                self.internal.as_ptr(),
-                &mut level,
-            ))
-            .ok()
-            .map(|_| level)
-        }
-        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_getDarkAreaBoostStrth(
-                self.internal.as_ptr(),
-                &mut level,
-            ))
-            .ok()
-            .map(|_| level)
-        }
+/// 一个描述增益上限的类型，单位：倍。
+///
+/// 总增益依次由传感器模拟增益、传感器数字增益与 ISP 数字增益补足，各自不超过对应的上限。
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GainCaps {
+    /// 传感器模拟增益上限。
+    pub analog: f32,
+    /// 传感器数字增益上限。
+    pub digital: f32,
+    /// ISP 数字增益上限。
+    pub isp_dgain: f32,
+}
+
+/// 一个描述增益分配策略的枚举。
+///
+/// 曝光路径的每个节点记录传感器增益与 ISP 数字增益，二者之积为节点的总增益。
+/// 策略保持各节点的总增益不变，只改变二者的分配。
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GainSplitPolicy {
+    /// 先用满传感器增益，再使用 ISP 数字增益。
+    AnalogFirst,
+    /// 传感器增益与 ISP 数字增益按对数平分总增益，ISP 数字增益达到上限后由传感器增益补足。
+    Balanced,
+    /// 按给定的上限依次分配。
+    Custom(GainCaps),
+}
+
+/// 两个增益视为相等的相对误差。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+const GAIN_SPLIT_EPSILON: f32 = 1e-3;
+
+/// 检查增益分配策略：上限须为有限值且不小于 1，否则返回 `XCAM_RETURN_ERROR_PARAM`；
+/// 传感器增益上限（模拟与数字上限之积）不得超过 `sensor_max`，ISP 数字增益上限不得超过
+/// `ISP_DGAIN_MAX`，否则返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn check_gain_split_policy(policy: GainSplitPolicy, sensor_max: f32) -> XCamResult<()> {
+    let GainSplitPolicy::Custom(caps) = policy else {
+        return Ok(());
+    };
+    let caps = [caps.analog, caps.digital, caps.isp_dgain];
+    if !caps.iter().all(|c| c.is_finite() && *c >= 1.0) {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    if caps[0] * caps[1] > sensor_max * (1.0 + GAIN_SPLIT_EPSILON) || caps[2] > ISP_DGAIN_MAX {
+        return Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
     }
+    Ok(())
+}
 
-    fn set_dark_area_boost_strth(&self, level: u32) -> XCamResult<()> {
-        #[cfg(feature = "v2_0")]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setDarkAreaBoostStrth(
-                self.internal.as_ptr(),
-                level,
-            ))
-            .ok()
+/// 按策略将总增益 `total` 分配为 `(传感器增益, ISP 数字增益)`。
+///
+/// `sensor_max` 为传感器可达的增益。总增益超出可分配的范围时按上限截断，小于 1 时按 1 处理。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn split_gain(policy: GainSplitPolicy, total: f32, sensor_max: f32) -> (f32, f32) {
+    let total = total.max(1.0);
+    match policy {
+        GainSplitPolicy::AnalogFirst => {
+            let sensor = total.min(sensor_max);
+            (sensor, (total / sensor).min(ISP_DGAIN_MAX))
         }
-        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setDarkAreaBoostStrth(
-                self.internal.as_ptr(),
-                level,
-            ))
-            .ok()
+        GainSplitPolicy::Balanced => {
+            let isp = total.sqrt().min(ISP_DGAIN_MAX);
+            ((total / isp).min(sensor_max), isp)
+        }
+        GainSplitPolicy::Custom(caps) => {
+            let sensor = total.min(caps.analog * caps.digital);
+            (sensor, (total / sensor).min(caps.isp_dgain))
         }
     }
+}
 
-    fn get_anti_flicker_mode(&self) -> XCamResult<AntiFlickerMode> {
-        let mut mode: AntiFlickerMode = Default::default();
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getAntiFlickerMode(
-                self.internal.as_ptr(),
-                &mut mode,
-            ))
-            .ok()
-            .map(|_| mode)
-        }
+/// 按策略重新分配线性曝光路径各节点的传感器增益与 ISP 数字增益。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn apply_gain_split_policy(
+    sw_attr: &mut ffi::Uapi_ExpSwAttrV2_t,
+    policy: GainSplitPolicy,
+    sensor_max: f32,
+) {
+    let lin = &mut sw_attr.stAuto.LinAeRoute;
+    let len = (lin.array_size.max(0) as usize).min(lin.GainDot.len());
+    for i in 0..len {
+        let total = lin.GainDot[i] * lin.IspDGainDot[i];
+        (lin.GainDot[i], lin.IspDGainDot[i]) = split_gain(policy, total, sensor_max);
     }
+}
 
-    fn set_anti_flicker_mode<T: Into<AntiFlickerMode>>(&self, mode: T) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setAntiFlickerMode(
-                self.internal.as_ptr(),
-                mode.into(),
-            ))
-            .ok()
-        }
+/// 由线性曝光路径还原生效的增益分配策略。
+///
+/// 各节点的分配与某个预设一致时返回该预设，否则返回以各节点最大值为上限的 `Custom`。
+/// 路径中传感器增益不区分模拟与数字部分，此时传感器增益上限全部计入 `analog`，`digital` 为 1。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn gain_split_policy_of(
+    sw_attr: &ffi::Uapi_ExpSwAttrV2_t,
+    sensor_max: f32,
+) -> GainSplitPolicy {
+    let lin = &sw_attr.stAuto.LinAeRoute;
+    let len = (lin.array_size.max(0) as usize).min(lin.GainDot.len());
+    let nodes: Vec<_> = (0..len)
+        .map(|i| (lin.GainDot[i], lin.IspDGainDot[i]))
+        .collect();
+    let close = |a: f32, b: f32| (a - b).abs() <= GAIN_SPLIT_EPSILON * a.abs().max(b.abs());
+    let matches = |policy| {
+        nodes.iter().all(|&(sensor, isp)| {
+            let (s, i) = split_gain(policy, sensor * isp, sensor_max);
+            close(s, sensor) && close(i, isp)
+        })
+    };
+    if matches(GainSplitPolicy::AnalogFirst) {
+        return GainSplitPolicy::AnalogFirst;
+    }
+    if matches(GainSplitPolicy::Balanced) {
+        return GainSplitPolicy::Balanced;
+    }
+    let max = |f: fn(&(f32, f32)) -> f32| nodes.iter().map(f).fold(1.0, f32::max);
+    GainSplitPolicy::Custom(GainCaps {
+        analog: max(|n| n.0),
+        digital: 1.0,
+        isp_dgain: max(|n| n.1),
+    })
+}
+
+/// 曝光补偿的最大绝对值，单位：EV。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub const EV_COMPENSATION_MAX: f32 = 3.0;
+
+/// 线性曝光属性中 `Evbias` 字段每 EV 对应的数值。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+const EVBIAS_PER_EV: f32 = 100.0;
+
+/// AE 死区的最大值，单位：亮度级。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub const AE_DEADBAND_MAX: u8 = 64;
+
+/// 将亮度死区换算为线性曝光属性的容差，容差为目标亮度 `set_point` 的百分比。
+///
+/// `luma_delta` 超出 `AE_DEADBAND_MAX` 时取该值，换算结果不超过 100%；目标亮度小于 1 时按 1 计算。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub fn deadband_to_tolerance(luma_delta: u8, set_point: f32) -> f32 {
+    let delta = luma_delta.min(AE_DEADBAND_MAX) as f32;
+    (delta / set_point.max(1.0) * 100.0).min(100.0)
+}
+
+/// 将线性曝光属性的容差换算为亮度死区，是 `deadband_to_tolerance` 的逆运算。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub fn tolerance_to_deadband(tolerance: f32, set_point: f32) -> u8 {
+    (tolerance * set_point.max(1.0) / 100.0)
+        .round()
+        .clamp(0.0, AE_DEADBAND_MAX as f32) as u8
+}
+
+/// 设置 AE 死区前的进入容差与本库写入的进入容差，见 `AutoExposure::set_ae_deadband`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) type SavedToleranceIn = (f32, f32);
+
+/// 设置 AE 死区，原进入容差保存在 `saved` 中。
+///
+/// 当前的进入容差与上次写入的不同时，视为已在别处修改，以当前值作为新的原值。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn apply_ae_deadband<C: AutoExposure + ?Sized>(
+    ctx: &C,
+    saved: &mut Option<SavedToleranceIn>,
+    luma_delta: u8,
+) -> XCamResult<()> {
+    let attr = ctx.get_lin_exp_attr()?;
+    let out = deadband_to_tolerance(luma_delta, attr.0.SetPoint);
+    let original = match *saved {
+        Some((original, written)) if written == attr.0.ToleranceIn => original,
+        _ => attr.0.ToleranceIn,
+    };
+    let tolerance_in = original.min(out);
+    ctx.set_lin_exp_attr(attr.with_tolerance_out(out).with_tolerance_in(tolerance_in))?;
+    *saved = Some((original, tolerance_in));
+    Ok(())
+}
+
+/// AE 快速区的退出阈值与进入阈值之比，见 `Context::set_ae_convergence`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub const AE_CONVERGENCE_HYSTERESIS: f32 = 0.5;
+
+/// AE 快速区阈值的最大值，单位：EV。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub const AE_FAST_THRESHOLD_MAX_EV: f32 = 8.0;
+
+/// 一个描述 AE 双速率收敛参数的类型，见 `Context::set_ae_convergence`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AeConvergence {
+    /// 稳态区的收敛速度，即每次调节消除的亮度误差比例，范围：(0,1]。
+    pub steady_speed: f32,
+    /// 进入快速区的亮度偏差，单位：EV，范围：(0, `AE_FAST_THRESHOLD_MAX_EV`]。
+    pub fast_threshold_ev: f32,
+    /// 快速区的收敛速度，范围：[`steady_speed`, 1]。
+    pub fast_speed: f32,
+}
+
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+impl AeConvergence {
+    /// 检查取值范围，超出时返回 `XCAM_RETURN_ERROR_PARAM`。
+    pub fn check(&self) -> XCamResult<()> {
+        let speed = |v: f32| v > 0.0 && v <= 1.0;
+        let valid = speed(self.steady_speed)
+            && speed(self.fast_speed)
+            && self.fast_speed >= self.steady_speed
+            && self.fast_threshold_ev > 0.0
+            && self.fast_threshold_ev <= AE_FAST_THRESHOLD_MAX_EV;
+        if valid {
+            Ok(())
+        } else {
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        }
+    }
+
+    /// 按亮度偏差 `delta_ev` 选择区域，返回是否处于快速区，`was_fast` 为当前所处的区域。
+    ///
+    /// 偏差的绝对值超过 `fast_threshold_ev` 时进入快速区，降至其 `AE_CONVERGENCE_HYSTERESIS`
+    /// 倍以下时回到稳态区，其间保持当前区域；偏差为 NaN 时视为稳态。
+    pub fn is_fast(&self, delta_ev: f32, was_fast: bool) -> bool {
+        let threshold = if was_fast {
+            self.fast_threshold_ev * AE_CONVERGENCE_HYSTERESIS
+        } else {
+            self.fast_threshold_ev
+        };
+        delta_ev.abs() > threshold
+    }
+
+    /// 区域对应的收敛速度。
+    pub fn speed(&self, fast: bool) -> f32 {
+        if fast {
+            self.fast_speed
+        } else {
+            self.steady_speed
+        }
+    }
+}
+
+/// 画面平均亮度 `mean_luma` 与目标亮度 `target` 之差，单位：EV，亮度小于 1 时按 1 计算。
+///
+/// 画面偏暗时为正，偏亮时为负。
+pub fn luma_delta_ev(mean_luma: f32, target: f32) -> f32 {
+    (target.max(1.0) / mean_luma.max(1.0)).log2()
+}
+
+/// 将收敛速度换算为 AE 速度属性的阻尼。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+fn speed_to_damp(speed: f32) -> f32 {
+    (1.0 - speed).clamp(0.0, 1.0)
+}
+
+/// `Context::set_ae_convergence` 设置的参数及当前所处的区域。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct AeConvergenceState {
+    conv: AeConvergence,
+    fast: bool,
+}
+
+/// 检查参数并以稳态速度开始，见 `Context::set_ae_convergence`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn set_ae_convergence_with<C: AutoExposure + ?Sized>(
+    ctx: &C,
+    state: &mut Option<AeConvergenceState>,
+    conv: AeConvergence,
+) -> XCamResult<()> {
+    conv.check()?;
+    ctx.set_ae_speed(conv.steady_speed)?;
+    *state = Some(AeConvergenceState { conv, fast: false });
+    Ok(())
+}
+
+/// 按当前亮度偏差选择区域，切换区域时写入对应的速度并返回 `true`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn tick_ae_convergence<C: AutoExposure + ?Sized>(
+    ctx: &C,
+    state: &mut Option<AeConvergenceState>,
+) -> XCamResult<bool> {
+    let Some(s) = state.as_mut() else {
+        return Ok(false);
+    };
+    let info = ctx.query_exp_info()?;
+    let target = ctx.get_ae_target_luma()?;
+    let fast = s
+        .conv
+        .is_fast(luma_delta_ev(info.mean_luma, f32::from(target)), s.fast);
+    if fast == s.fast {
+        return Ok(false);
+    }
+    ctx.set_ae_speed(s.conv.speed(fast))?;
+    s.fast = fast;
+    Ok(true)
+}
+
+/// 一个描述抗闪频率的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AntiFlickerFrequency {
+    /// 50Hz 电网。
+    Hz50,
+    /// 60Hz 电网。
+    Hz60,
+}
+
+enum_str!(AntiFlickerFrequency, "anti-flicker frequency" {
+    Hz50 => "50hz",
+    Hz60 => "60hz",
+});
+
+impl From<AntiFlickerFrequency> for ExpPwrLineFreq {
+    fn from(val: AntiFlickerFrequency) -> Self {
+        match val {
+            AntiFlickerFrequency::Hz50 => ExpPwrLineFreq::EXP_PWR_LINE_FREQ_50HZ,
+            AntiFlickerFrequency::Hz60 => ExpPwrLineFreq::EXP_PWR_LINE_FREQ_60HZ,
+        }
+    }
+}
+
+impl AntiFlickerFrequency {
+    /// 由 SDK 的抗闪频率转换，关闭时返回 `None`。
+    pub fn from_pwr_line_freq(freq: ExpPwrLineFreq) -> Option<Self> {
+        match freq {
+            ExpPwrLineFreq::EXP_PWR_LINE_FREQ_50HZ => Some(Self::Hz50),
+            ExpPwrLineFreq::EXP_PWR_LINE_FREQ_60HZ => Some(Self::Hz60),
+            _ => None,
+        }
+    }
+}
+
+/// 一个描述抗闪设置的枚举，合并了 SDK 的抗闪模式与抗闪频率。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AntiFlicker {
+    /// 关闭抗闪。
+    Off,
+    /// 由算法检测电网频率。
+    Auto,
+    /// 使用固定频率，跳过检测，例如沿用上次启动时检测到的频率。
+    Fixed(AntiFlickerFrequency),
+}
+
+/// 一个描述半自动曝光优先模式的枚举。
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExposurePriority {
+    /// 全自动曝光，曝光时间与增益均由算法控制。
+    Auto,
+    /// 快门优先：固定曝光时间，单位：秒，增益由算法控制。
+    ShutterPriority(f32),
+    /// 增益优先：固定总增益，单位：倍，曝光时间由算法控制。
+    GainPriority(f32),
+}
+
+/// 检查优先模式中固定的取值：曝光时间须大于 0，增益须不小于 1。
+pub(crate) fn check_exposure_priority(priority: ExposurePriority) -> XCamResult<()> {
+    let valid = match priority {
+        ExposurePriority::Auto => true,
+        ExposurePriority::ShutterPriority(time) => time.is_finite() && time > 0.0,
+        ExposurePriority::GainPriority(gain) => gain.is_finite() && gain >= 1.0,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+    }
+}
+
+/// 按优先模式修改曝光属性：固定的一项使用手动值，另一项交由算法控制。
+///
+/// 切换回全自动时关闭全部手动项。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn apply_exposure_priority(
+    sw_attr: &mut ffi::Uapi_ExpSwAttrV2_t,
+    priority: ExposurePriority,
+) {
+    use ffi::RKAiqOPMode_e::{RK_AIQ_OP_MODE_AUTO, RK_AIQ_OP_MODE_MANUAL};
+    sw_attr.Enable = 1;
+    let linear = &mut sw_attr.stManual.LinearAE;
+    linear.ManualIspDgainEn = false;
+    match priority {
+        ExposurePriority::Auto => {
+            linear.ManualTimeEn = false;
+            linear.ManualGainEn = false;
+            sw_attr.AecOpType = RK_AIQ_OP_MODE_AUTO;
+        }
+        ExposurePriority::ShutterPriority(time) => {
+            linear.ManualTimeEn = true;
+            linear.ManualGainEn = false;
+            linear.TimeValue = time;
+            sw_attr.AecOpType = RK_AIQ_OP_MODE_MANUAL;
+        }
+        ExposurePriority::GainPriority(gain) => {
+            linear.ManualTimeEn = false;
+            linear.ManualGainEn = true;
+            linear.GainValue = gain;
+            sw_attr.AecOpType = RK_AIQ_OP_MODE_MANUAL;
+        }
+    }
+}
+
+/// 被优先模式固定前的 `(增益范围, 曝光时间范围)`。
+#[cfg(any(test, feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+pub(crate) type SavedExpRanges = ((f32, f32), (f32, f32));
+
+/// 以曝光范围模拟优先模式。
+///
+/// 将固定的一项的范围收窄为单个值，另一项仍由自动曝光在原范围内调节。
+/// 收窄前的范围保存在 `saved` 中，切换模式或回到全自动时先恢复原范围。
+#[cfg(any(test, feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+pub(crate) fn emulate_exposure_priority<C: AutoExposure + ?Sized>(
+    ctx: &C,
+    saved: &mut Option<SavedExpRanges>,
+    priority: ExposurePriority,
+) -> XCamResult<()> {
+    check_exposure_priority(priority)?;
+    if let Some((gain, time)) = *saved {
+        ctx.set_exp_gain_range(gain.0, gain.1)?;
+        ctx.set_exp_time_range(time.0, time.1)?;
+        *saved = None;
+    }
+    ctx.set_exp_mode(OpMode::Auto)?;
+    let ranges = (ctx.get_exp_gain_range()?, ctx.get_exp_time_range()?);
+    match priority {
+        ExposurePriority::Auto => return Ok(()),
+        ExposurePriority::ShutterPriority(time) => ctx.set_exp_time_range(time, time)?,
+        ExposurePriority::GainPriority(gain) => ctx.set_exp_gain_range(gain, gain)?,
+    }
+    *saved = Some(ranges);
+    Ok(())
+}
+
+/// 单帧曝光结束后需要恢复的曝光状态。
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct OneShotExposure {
+    /// 设置前的曝光模式。
+    mode: OpMode,
+    /// 设置前为手动模式时的 `(增益, 曝光时间)`。
+    manual: Option<(f32, f32)>,
+    /// 帧号到达该值时恢复。
+    revert_at: u32,
+}
+
+/// 设置只作用一帧的手动曝光，恢复所需的状态保存在 `pending` 中。
+///
+/// 上一次单帧曝光尚未恢复时沿用其保存的原状态，并从当前帧重新计算恢复时机。
+/// 尚未收到任何帧时无法确定恢复时机，返回 `XCAM_RETURN_ERROR_ORDER`。
+pub(crate) fn begin_oneshot_exposure<C: AutoExposure + SystemControl + ?Sized>(
+    ctx: &C,
+    pending: &mut Option<OneShotExposure>,
+    time: Duration,
+    gain: f32,
+) -> XCamResult<()> {
+    let frame_id = ctx
+        .get_frame_id()
+        .ok_or(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))?;
+    let (mode, manual) = match *pending {
+        Some(shot) => (shot.mode, shot.manual),
+        None => {
+            let mode = ctx.get_exp_mode()?;
+            let manual = match mode {
+                OpMode::Manual => Some(ctx.get_effective_exposure()?),
+                _ => None,
+            };
+            (mode, manual)
+        }
+    };
+    ctx.set_manual_exp_dur(time, gain)?;
+    *pending = Some(OneShotExposure {
+        mode,
+        manual,
+        revert_at: frame_id.wrapping_add(1),
+    });
+    Ok(())
+}
+
+/// 检查单帧曝光是否到期，到期时恢复原曝光并返回 `true`。
+///
+/// 恢复失败时保留 `pending`，下次调用时重试。
+pub(crate) fn tick_oneshot_exposure<C: AutoExposure + SystemControl + ?Sized>(
+    ctx: &C,
+    pending: &mut Option<OneShotExposure>,
+) -> XCamResult<bool> {
+    let (Some(shot), Some(frame_id)) = (*pending, ctx.get_frame_id()) else {
+        return Ok(false);
+    };
+    // 按回绕差值比较，帧号回绕后仍能判断
+    if (frame_id.wrapping_sub(shot.revert_at) as i32) < 0 {
+        return Ok(false);
+    }
+    match shot.manual {
+        Some((gain, time)) => ctx.set_manual_exp(gain, time)?,
+        None => ctx.set_exp_mode(shot.mode)?,
+    }
+    *pending = None;
+    Ok(true)
+}
+
+/// 自动帧率每次调整的步长，单位：fps。
+pub const AUTO_FPS_STEP: f32 = 1.0;
+
+/// 自动帧率的回差：折算 ISO 低于暗光阈值的该比例时才提高帧率。
+pub const AUTO_FPS_HYSTERESIS: f32 = 0.7;
+
+/// `enable_auto_framerate` 设置的自动帧率状态。
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct AutoFrameRate {
+    min_fps: f32,
+    max_fps: f32,
+    dark_iso: u32,
+    /// 当前设置的帧率。
+    fps: f32,
+    /// 启用前的帧率设置，关闭时恢复。
+    prior: FrameRateInfo,
+}
+
+impl AutoFrameRate {
+    /// 由当前 ISO 决定下一帧率，见 `Context::enable_auto_framerate`。
+    pub(crate) fn next_fps(&self, iso: u32) -> f32 {
+        // 折算为最高帧率下的 ISO，使降低帧率本身带来的 ISO 下降不会立即触发回升。
+        let equiv = iso as f32 * self.max_fps / self.fps;
+        let dark = self.dark_iso as f32;
+        if equiv >= dark {
+            (self.fps - AUTO_FPS_STEP).max(self.min_fps)
+        } else if equiv < dark * AUTO_FPS_HYSTERESIS {
+            (self.fps + AUTO_FPS_STEP).min(self.max_fps)
+        } else {
+            self.fps
+        }
+    }
+}
+
+fn manual_frame_rate(fps: f32) -> FrameRateInfo {
+    FrameRateInfo {
+        mode: ffi::opMode_t::OP_MANUAL,
+        fps: (fps.round() as u32).max(1),
+    }
+}
+
+/// 启用自动帧率，以 `max_fps` 开始；已启用时沿用原先保存的帧率设置。
+pub(crate) fn enable_auto_framerate<C: Miscellaneous + ?Sized>(
+    ctx: &C,
+    state: &mut Option<AutoFrameRate>,
+    min_fps: f32,
+    max_fps: f32,
+    dark_iso: u32,
+) -> XCamResult<()> {
+    if !(min_fps.is_finite() && max_fps.is_finite() && min_fps > 0.0 && min_fps <= max_fps)
+        || dark_iso == 0
+    {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    let prior = match *state {
+        Some(s) => s.prior,
+        None => ctx.get_frame_rate()?,
+    };
+    ctx.set_frame_rate(manual_frame_rate(max_fps))?;
+    *state = Some(AutoFrameRate {
+        min_fps,
+        max_fps,
+        dark_iso,
+        fps: max_fps,
+        prior,
+    });
+    Ok(())
+}
+
+/// 按当前 ISO 调整一步帧率，写入新帧率时返回 `true`。
+pub(crate) fn tick_auto_framerate<C: AutoExposure + Miscellaneous + ?Sized>(
+    ctx: &C,
+    state: &mut Option<AutoFrameRate>,
+) -> XCamResult<bool> {
+    let Some(s) = state.as_mut() else {
+        return Ok(false);
+    };
+    let next = s.next_fps(ctx.get_iso()?);
+    let written = next.round() != s.fps.round();
+    if written {
+        ctx.set_frame_rate(manual_frame_rate(next))?;
+    }
+    s.fps = next;
+    Ok(written)
+}
+
+/// 关闭自动帧率并恢复启用前的帧率设置，未启用时不做任何事。
+pub(crate) fn disable_auto_framerate<C: Miscellaneous + ?Sized>(
+    ctx: &C,
+    state: &mut Option<AutoFrameRate>,
+) -> XCamResult<()> {
+    if let Some(s) = *state {
+        ctx.set_frame_rate(s.prior)?;
+        *state = None;
+    }
+    Ok(())
+}
+
+/// 将像素坐标的窗口换算为 `map` 的格坐标，向外取整，至少一格。
+fn window_cells(rect: Rect, frame: (u32, u32), map: &LumaMap) -> Rect {
+    let span = |start: i32, len: i32, frame: u32, cells: usize| {
+        let scale = cells as f32 / frame as f32;
+        let lo = (start as f32 * scale).floor() as i32;
+        let hi = ((start + len) as f32 * scale).ceil() as i32;
+        (lo, (hi - lo).max(1))
+    };
+    let (left, width) = span(rect.left, rect.width, frame.0, map.width);
+    let (top, height) = span(rect.top, rect.height, frame.1, map.height);
+    Rect {
+        left,
+        top,
+        width,
+        height,
+    }
+}
+
+/// 检查 `frame` 画面中像素坐标的窗口：尺寸须大于 0，否则返回 `XCAM_RETURN_ERROR_PARAM`；
+/// 超出画面时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+pub(crate) fn check_window(rect: Rect, frame: (u32, u32)) -> XCamResult<()> {
+    if rect.width <= 0 || rect.height <= 0 || frame.0 == 0 || frame.1 == 0 {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    let fits = |start: i32, len: i32, max: u32| {
+        start >= 0 && i64::from(start) + i64::from(len) <= i64::from(max)
+    };
+    if !fits(rect.left, rect.width, frame.0) || !fits(rect.top, rect.height, frame.1) {
+        return Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+    }
+    Ok(())
+}
+
+/// 检查统计窗口并写入，见 `Context::set_stats_window`。
+pub(crate) fn set_stats_window_with<C: AutoExposure + ?Sized>(
+    ctx: &C,
+    rect: Rect,
+    frame: (u32, u32),
+) -> XCamResult<()> {
+    check_window(rect, frame)?;
+    ctx.set_ae_stats_window(rect)
+}
+
+/// 检查 SDK 报告的曝光增益或时间范围 `(min, max)`：须为有限的正值且 `min` 不大于 `max`，
+/// 否则返回 `XCAM_RETURN_ERROR_PARAM`。
+fn check_exp_range((min, max): (f32, f32)) -> XCamResult<(f32, f32)> {
+    if !(min.is_finite() && max.is_finite() && 0.0 < min && min <= max) {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    Ok((min, max))
+}
+
+/// 按窗口测光计算手动曝光并设置，返回设置的 `(增益, 曝光时间)`。
+///
+/// `rect` 为 `frame` 画面中的像素坐标，尺寸须大于 0，否则返回 `XCAM_RETURN_ERROR_PARAM`；
+/// 超出画面时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。曝光增益或时间范围无效（见 `check_exp_range`）
+/// 时返回 `XCAM_RETURN_ERROR_PARAM`，不修改曝光。
+pub(crate) fn lock_ae_to_window_with<C: AutoExposure + SystemControl + ?Sized>(
+    ctx: &C,
+    rect: Rect,
+    frame: (u32, u32),
+) -> XCamResult<(f32, f32)> {
+    check_window(rect, frame)?;
+    let map = ctx.get_luma_map()?;
+    let window = map
+        .region_mean(window_cells(rect, frame, &map))
+        .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))?;
+    let target = map.mean();
+    let (gain, time) = ctx.get_effective_exposure()?;
+    let (gain_min, gain_max) = check_exp_range(ctx.get_exp_gain_range()?)?;
+    let (time_min, time_max) = check_exp_range(ctx.get_exp_time_range()?)?;
+    // 亮度为 0 的窗口按 1 计算，避免曝光量无穷大；随后由曝光范围限制。
+    let total = gain * time * target.max(1.0) / window.max(1.0);
+    let time = (total / gain_min).clamp(time_min, time_max);
+    let gain = (total / time).clamp(gain_min, gain_max);
+    ctx.set_manual_exp(gain, time)?;
+    Ok((gain, time))
+}
+
+pub trait AutoExposure {
+    #[cfg(feature = "v1_0")]
+    fn get_ae_mode(&self) -> XCamResult<AeMode>;
+    #[cfg(feature = "v1_0")]
+    fn set_ae_mode(&self, mode: AeMode) -> XCamResult<()>;
+
+    fn get_exp_mode(&self) -> XCamResult<OpMode>;
+    fn set_exp_mode(&self, mode: OpMode) -> XCamResult<()>;
+
+    fn get_exp_gain_range(&self) -> XCamResult<(f32, f32)>;
+    fn set_exp_gain_range(&self, min: f32, max: f32) -> XCamResult<()>;
+
+    fn get_exp_time_range(&self) -> XCamResult<(f32, f32)>;
+    fn set_exp_time_range(&self, min: f32, max: f32) -> XCamResult<()>;
+
+    fn set_manual_exp(&self, gain: f32, time: f32) -> XCamResult<()>;
+    fn set_manual_exp_fps(&self, gain: f32, time_fps: usize) -> XCamResult<()>;
+    fn set_manual_exp_ms(&self, gain: f32, time_ms: usize) -> XCamResult<()>;
+    fn set_manual_exp_us(&self, gain: f32, time_us: usize) -> XCamResult<()>;
+
+    /// 以 `Duration` 设置曝光时间范围，精度限制见 `duration_to_secs`。
+    fn set_exp_time_range_dur(&self, min: Duration, max: Duration) -> XCamResult<()> {
+        self.set_exp_time_range(duration_to_secs(min), duration_to_secs(max))
+    }
+
+    /// 以 `Duration` 设置手动曝光时间与增益，精度限制见 `duration_to_secs`。
+    fn set_manual_exp_dur(&self, time: Duration, gain: f32) -> XCamResult<()> {
+        self.set_manual_exp(gain, duration_to_secs(time))
+    }
+
+    fn set_blc_mode(&self, enabled: bool, mode: AeMeasAreaType) -> XCamResult<()>;
+    fn set_blc_strength(&self, strength: i32) -> XCamResult<()>;
+
+    /// 强光抑制开关。
+    fn set_hlc_mode(&self, enabled: bool) -> XCamResult<()>;
+
+    /// 设置强光抑制强度。
+    ///
+    /// # Parameters
+    /// * `strength` - 抑制强度，范围[1,100]。
+    fn set_hlc_strength(&self, strength: i32) -> XCamResult<()>;
+
+    /// 获取当前暗区提升强度。
+    fn get_dark_area_boost_strth(&self) -> XCamResult<u32>;
+
+    /// 设置暗区提升强度。
+    ///
+    /// # Parameters
+    /// * `level` - 暗区提升强度，范围[1,10]。
+    fn set_dark_area_boost_strth(&self, level: u32) -> XCamResult<()>;
+
+    /// 获取抗闪模式。
+    fn get_anti_flicker_mode(&self) -> XCamResult<AntiFlickerMode>;
+
+    /// 设置抗闪模式。
+    fn set_anti_flicker_mode<T: Into<AntiFlickerMode>>(&self, mode: T) -> XCamResult<()>;
+
+    /// 获取抗闪频率。
+    fn get_exp_pwr_line_freq_mode(&self) -> XCamResult<ExpPwrLineFreq>;
+
+    /// 设置抗闪频率。
+    fn set_exp_pwr_line_freq_mode<T: Into<ExpPwrLineFreq>>(&self, mode: T) -> XCamResult<()>;
+
+    /// 获取抗闪设置，见 `AntiFlicker`。
+    fn get_anti_flicker(&self) -> XCamResult<AntiFlicker> {
+        if matches!(
+            self.get_anti_flicker_mode()?,
+            AntiFlickerMode::ANTIFLICKER_AUTO_MODE
+        ) {
+            return Ok(AntiFlicker::Auto);
+        }
+        Ok(
+            match AntiFlickerFrequency::from_pwr_line_freq(self.get_exp_pwr_line_freq_mode()?) {
+                Some(freq) => AntiFlicker::Fixed(freq),
+                None => AntiFlicker::Off,
+            },
+        )
+    }
+
+    /// 一次设置抗闪模式与抗闪频率。
+    ///
+    /// `Fixed` 切换为普通模式并写入频率，可用 `get_detected_flicker` 保存的结果跳过下次启动时的检测；
+    /// `Off` 切换为普通模式并关闭抗闪频率；`Auto` 只切换抗闪模式。
+    fn set_anti_flicker(&self, mode: AntiFlicker) -> XCamResult<()> {
+        match mode {
+            AntiFlicker::Auto => self.set_anti_flicker_mode(AntiFlickerMode::ANTIFLICKER_AUTO_MODE),
+            AntiFlicker::Off => {
+                self.set_anti_flicker_mode(AntiFlickerMode::ANTIFLICKER_NORMAL_MODE)?;
+                self.set_exp_pwr_line_freq_mode(ExpPwrLineFreq::EXP_PWR_LINE_FREQ_DIS)
+            }
+            AntiFlicker::Fixed(freq) => {
+                self.set_anti_flicker_mode(AntiFlickerMode::ANTIFLICKER_NORMAL_MODE)?;
+                self.set_exp_pwr_line_freq_mode(freq)
+            }
+        }
+    }
+
+    /// 获取自动抗闪检测到的电网频率。
+    ///
+    /// 各版本 SDK 的曝光查询信息均不报告自动抗闪的检测结果，默认返回 `None`，
+    /// 不根据配置的频率推测；能够报告检测结果的实现应覆盖该方法。
+    fn get_detected_flicker(&self) -> XCamResult<Option<AntiFlickerFrequency>> {
+        Ok(None)
+    }
+
+    /// 查询当前曝光结果。
+    ///
+    /// # Note
+    ///
+    /// AE 处理一帧期间调用会阻塞，最长约一个帧周期。
+    fn query_exp_info(&self) -> XCamResult<ExpInfo>;
+
+    /// 获取当前实际生效的曝光 `(增益, 曝光时间)`，参数顺序与 `set_manual_exp` 相同。
+    fn get_effective_exposure(&self) -> XCamResult<(f32, f32)> {
+        self.query_exp_info().map(|info| (info.gain, info.time))
+    }
+
+    /// 将本摄像头当前生效的曝光作为手动曝光设置到 `other`，用于双目等多摄像头同步曝光。
+    ///
+    /// # Note
+    ///
+    /// SDK 没有跨上下文的硬件曝光同步接口（同步需通过 camgroup），此处为软件同步：
+    /// 读取的是本摄像头最近一帧的曝光，写入 `other` 后还需经传感器寄存器延迟 2 至 3 帧才生效，
+    /// 因此两者的曝光相差数帧，场景亮度变化时短时间内不一致。`other` 保持手动曝光，
+    /// 需要持续跟随时应每帧或按固定周期重复调用。
+    fn sync_exposure_with<O: AutoExposure + ?Sized>(&self, other: &O) -> XCamResult<()> {
+        let (gain, time) = self.get_effective_exposure()?;
+        other.set_manual_exp(gain, time)
+    }
+
+    /// 获取手动曝光的模拟增益与 ISP 数字增益。
+    ///
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn get_manual_gains(&self) -> XCamResult<(f32, f32)> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 分别设置手动曝光的模拟增益与 ISP 数字增益，曝光时间固定为当前生效的曝光时间。
+    ///
+    /// 总增益为两者之积。低照度下优先提高模拟增益、限制数字增益可以降低噪声。
+    /// `digital` 作用于 ISP 的数字增益，不是传感器的数字增益；传感器端的增益都由 `analog` 表示，
+    /// 由 SDK 按传感器的增益表拆分。
+    ///
+    /// # Parameters
+    /// * `analog` - 传感器增益，范围：[1, 传感器可达的增益上限]，见 `get_sensor_gain_range`，
+    ///   不受 `set_exp_gain_range` 收窄的影响。
+    /// * `digital` - ISP 数字增益，范围：[1, `ISP_DGAIN_MAX`]。
+    ///
+    /// # Note
+    ///
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn set_manual_gains(&self, analog: f32, digital: f32) -> XCamResult<()> {
+        check_manual_gains(analog, digital, self.get_sensor_gain_range()?.1)?;
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取 ISP 数字增益的全局上限，未通过 `set_global_digital_gain_cap` 设置时返回 `None`，
+    /// 此时上限由 IQ 文件决定。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_global_digital_gain_cap(&self) -> XCamResult<Option<f32>>;
+
+    /// 设置 ISP 数字增益的全局上限。
+    ///
+    /// 上限约束自动曝光可以使用的 ISP 数字增益，与曝光增益范围（传感器增益）相互独立。
+    /// ISP 数字增益放大噪声的程度高于传感器模拟增益，降低上限可以抑制低照度下的噪声，
+    /// 代价是画面更暗。通过曝光属性的高级范围设置实现，下限大于新上限时一并降低。
+    ///
+    /// # Parameters
+    /// * `cap` - 数字增益上限，范围：[1, `ISP_DGAIN_MAX`]。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_global_digital_gain_cap(&self, cap: f32) -> XCamResult<()>;
+
+    /// 获取 AE 权重表，长度为 `ae_grid_dimensions` 的格数。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ae_weights(&self) -> XCamResult<Vec<u8>>;
+
+    /// 以 `weights` 替换曝光属性中的 AE 权重表，用于按人脸等检测结果自定义测光区域。
+    ///
+    /// 权重表按行优先排列，先经 `check_ae_weights` 检查。权重表与测光模式共用，
+    /// 之后调用 `set_blc_mode` 等切换测光区域时会被覆盖。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_weights(&self, weights: &[u8]) -> XCamResult<()>;
+
+    /// 获取当前生效的曝光路径。
+    ///
+    /// 以 `prepare` 选择的 HDR 模式运行，且线性曝光路径与 HDR 曝光路径节点数一致时，按该模式的
+    /// 帧布局由 HDR 路径换算出各节点的曝光比范围，否则 `ratio` 为 `None`。
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn get_exposure_route(&self) -> XCamResult<Vec<ExpRoutePoint>> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 以 `points` 替换曝光属性中的曝光路径，无需修改 IQ 文件。
+    ///
+    /// 写入线性曝光路径；以 HDR 模式运行时一并重写 HDR 曝光路径，未给出 `ratio` 的节点沿用原有的
+    /// 曝光比。线性模式下忽略 `ratio`，尚未 `prepare` 时给出 `ratio` 返回 `XCAM_RETURN_ERROR_ORDER`。
+    /// ISP 数字增益节点固定为 1，光圈节点保持不变。路径先经 `check_exp_route` 检查。
+    ///
+    /// # Note
+    ///
+    /// 节点数上限为 SDK 曝光属性中路径数组的长度，随 SDK 头文件而定，
+    /// 超出时返回 `ExpRouteError::TooLong` 并报告上限。
+    /// 仅 v4_0、v5_0 支持，其余版本的曝光属性中路径不可在运行时修改，返回 `XCamError::Unsupported`。
+    fn set_exposure_route(&self, points: &[ExpRoutePoint]) -> Result<(), ExpRouteError> {
+        let _ = points;
+        Err(ExpRouteError::Sdk(XCamError::Unsupported))
+    }
+
+    /// 获取当前生效的曝光路径，按光值排列，见 `AeRoute`。
+    ///
+    /// 同 `get_exposure_route`。
+    fn get_ae_route(&self) -> XCamResult<AeRoute> {
+        self.get_exposure_route().map(|points| AeRoute { points })
+    }
+
+    /// 以 `route` 替换曝光路径，同 `set_exposure_route`。
+    fn set_ae_route(&self, route: &AeRoute) -> Result<(), ExpRouteError> {
+        self.set_exposure_route(&route.points)
+    }
+
+    /// 设置半自动曝光优先模式。
+    ///
+    /// 快门优先固定曝光时间、由算法调节增益，增益优先反之；`Auto` 回到全自动并清除固定值。
+    ///
+    /// # Note
+    ///
+    /// v4_0、v5_0 通过曝光属性的手动/自动分项原生支持：固定项使用手动值，另一项仍由算法控制。
+    /// v1_0 至 v3_0 没有分项接口，由本库将固定项的曝光范围收窄为单个值来模拟，
+    /// 并在切换模式或回到全自动时恢复原范围；模拟期间调用 `set_exp_gain_range`、
+    /// `set_exp_time_range` 修改的范围会在恢复时被覆盖。
+    fn set_exposure_priority(&self, priority: ExposurePriority) -> XCamResult<()>;
+
+    /// 获取生效的增益分配策略，由线性曝光路径还原，见 `GainSplitPolicy`。
+    ///
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn get_gain_split_policy(&self) -> XCamResult<GainSplitPolicy> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 设置增益分配策略，即曝光路径各节点中传感器增益与 ISP 数字增益的分配。
+    ///
+    /// 读取曝光属性，按策略改写线性曝光路径的增益节点后写回，各节点的总增益不变。
+    /// 传感器可达的增益取首次修改曝光增益范围前的上限，即标定值，不受 `set_exp_gain_range` 收窄；
+    /// 自定义上限先经检查，超出传感器或 ISP 的能力时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+    /// 之后调用 `set_exposure_route` 会将 ISP 数字增益节点重置为 1。
+    ///
+    /// # Note
+    ///
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn set_gain_split_policy(&self, policy: GainSplitPolicy) -> XCamResult<()> {
+        let _ = policy;
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取线性曝光属性。
+    ///
+    /// # Note
+    ///
+    /// 与 AE 分析线程共用配置锁，最长阻塞约一个帧周期。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_lin_exp_attr(&self) -> XCamResult<LinExpAttr>;
+
+    /// 设置线性曝光属性。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_lin_exp_attr<T: Into<LinExpAttr>>(&self, attr: T) -> XCamResult<()>;
+
+    /// 获取传感器支持的曝光时间表，单位：秒，按升序排列。
+    ///
+    /// 表由传感器描述中的像素时钟、行长与积分行数范围计算得出，见 `exposure_steps`。
+    /// 帧长随传感器模式与帧率变化，切换分辨率或帧率后应重新获取。
+    fn get_exposure_steps(&self) -> XCamResult<Vec<f32>> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取传感器在当前帧长下可达的曝光时间范围 `(min, max)`，单位：秒。
+    ///
+    /// 取自 `get_exposure_steps` 的首尾两项，不受 `set_exp_time_range` 收窄的影响。
+    fn get_sensor_exp_time_range(&self) -> XCamResult<(f32, f32)> {
+        let steps = self.get_exposure_steps()?;
+        match (steps.first(), steps.last()) {
+            (Some(&min), Some(&max)) => Ok((min, max)),
+            _ => Err(XCamError::Unsupported),
+        }
+    }
+
+    /// 获取传感器可达的增益范围 `(min, max)`，单位：倍，不受 `set_exp_gain_range` 收窄的影响。
+    ///
+    /// 传感器描述不含增益范围，下限取 1，上限取首次修改曝光增益范围前 SDK 报告的上限。
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn get_sensor_gain_range(&self) -> XCamResult<(f32, f32)> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取传感器支持的增益表。
+    ///
+    /// 增益与寄存器值的换算表位于 IQ 文件的传感器标定中，仅由 SDK 内部使用，
+    /// 没有查询接口，因此总是返回 `XCamError::Unsupported`。
+    fn get_gain_steps(&self) -> XCamResult<Vec<f32>> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 返回最接近 `t` 的可用曝光时间，单位：秒。
+    ///
+    /// 无法获取曝光时间表时原样返回 `t`。
+    fn snap_exposure(&self, t: f32) -> f32 {
+        self.get_exposure_steps()
+            .ok()
+            .and_then(|steps| nearest_step(&steps, t))
+            .unwrap_or(t)
+    }
+
+    /// 获取 ISO 换算所用的基准 ISO，未设置时为 `DEFAULT_BASE_ISO`。
+    fn get_base_iso(&self) -> u32 {
+        DEFAULT_BASE_ISO
+    }
+
+    /// 设置 ISO 换算所用的基准 ISO，影响 `get_iso`、`set_iso` 及曝光查询结果中的 ISO。
+    ///
+    /// 基准 ISO 通常取传感器在 1 倍增益下的标称感光度，为 0 时返回 `XCAM_RETURN_ERROR_PARAM`。
+    fn set_base_iso(&self, base_iso: u32) -> XCamResult<()> {
+        check_base_iso(base_iso)?;
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取模组镜头的固定光圈 f 值，用于 `exposure_value` 等计算。
+    ///
+    /// SDK 与 IQ 文件都不记录光圈，须先以 `Context::set_sensor_aperture` 按模组规格设置，
+    /// 未设置时返回 `XCamError::Unsupported`。
+    fn get_sensor_aperture(&self) -> XCamResult<f32> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取 AE 统计窗口，为输出画面中的像素坐标。
+    ///
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn get_ae_stats_window(&self) -> XCamResult<Rect> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 设置 AE 统计窗口，不检查窗口范围，通常应使用 `Context::set_stats_window`。
+    ///
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn set_ae_stats_window(&self, rect: Rect) -> XCamResult<()> {
+        let _ = rect;
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取 AE 的收敛速度，即每次调节消除的亮度误差比例，范围：(0,1]。
+    ///
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn get_ae_speed(&self) -> XCamResult<f32> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 设置 AE 的收敛速度，超出 (0,1] 时返回 `XCAM_RETURN_ERROR_PARAM`。
+    ///
+    /// 通过 AE 速度属性的阻尼实现，阻尼为 `1 - speed`，过曝与欠曝两个方向使用同一速度，
+    /// 并关闭 SDK 的动态阻尼。需要按亮度偏差切换速度时使用 `Context::set_ae_convergence`。
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn set_ae_speed(&self, speed: f32) -> XCamResult<()> {
+        let _ = speed;
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取当前 ISO 值。
+    fn get_iso(&self) -> XCamResult<u32> {
+        self.query_exp_info().map(|info| info.iso)
+    }
+
+    /// 以 ISO 值设置手动曝光增益，曝光时间保持不变。
+    ///
+    /// 换算方式见 `iso_to_gains`，模拟增益上限取传感器可达的增益上限，见 `get_sensor_gain_range`。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_iso(&self, iso: u32) -> XCamResult<()> {
+        let (_, analog_max) = self.get_sensor_gain_range()?;
+        let (analog, digital) = iso_to_gains(iso, self.get_base_iso(), analog_max, ISP_DGAIN_MAX)?;
+        self.set_manual_gains(analog, digital)
+    }
+
+    /// 获取曝光补偿，单位：EV。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_exposure_compensation(&self) -> XCamResult<f32> {
+        self.get_lin_exp_attr()
+            .map(|attr| attr.0.Evbias / EVBIAS_PER_EV)
+    }
+
+    /// 设置曝光补偿。
+    ///
+    /// 通过线性曝光属性的 `Evbias` 字段实现，1 EV 对应 `Evbias` 增加 100，
+    /// 即自动曝光目标亮度提高一倍。仅修改该字段，测光模式、测光区域等其余参数保持不变。
+    ///
+    /// # Parameters
+    /// * `ev` - 曝光补偿，单位：EV，超出 ±`EV_COMPENSATION_MAX` 时取边界值。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_exposure_compensation(&self, ev: f32) -> XCamResult<()> {
+        if ev.is_nan() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        let ev = ev.clamp(-EV_COMPENSATION_MAX, EV_COMPENSATION_MAX);
+        let attr = self.get_lin_exp_attr()?;
+        self.set_lin_exp_attr(attr.with_evbias(ev * EVBIAS_PER_EV))
+    }
+
+    /// 获取自动曝光的目标平均亮度，范围：[0,255]。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ae_target_luma(&self) -> XCamResult<u8> {
+        self.get_lin_exp_attr()
+            .map(|attr| attr.0.SetPoint.round().clamp(0.0, 255.0) as u8)
+    }
+
+    /// 设置自动曝光的目标平均亮度。
+    ///
+    /// 通过线性曝光属性的 `SetPoint` 字段实现，例如 102 约为满量程的 40%。
+    /// 目标亮度与曝光补偿叠加生效：实际目标约为 `目标亮度 × 2^EV`，
+    /// 因此设置目标亮度后仍保留的曝光补偿会继续作用于新的目标。
+    ///
+    /// 目标亮度过高或过低时，受曝光增益与曝光时间范围限制，自动曝光可能无法达到目标而停在范围边界。
+    ///
+    /// # Parameters
+    /// * `luma` - 目标平均亮度，范围：[0,255]。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_target_luma(&self, luma: u8) -> XCamResult<()> {
+        let attr = self.get_lin_exp_attr()?;
+        self.set_lin_exp_attr(attr.with_set_point(luma as f32))
+    }
+
+    /// 获取 AE 死区，单位：亮度级，由退出收敛的容差与目标亮度换算。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ae_deadband(&self) -> XCamResult<u8> {
+        self.get_lin_exp_attr()
+            .map(|attr| tolerance_to_deadband(attr.0.ToleranceOut, attr.0.SetPoint))
+    }
+
+    /// 设置 AE 死区：收敛后画面平均亮度偏离目标不超过 `luma_delta` 时不重新调节曝光。
+    ///
+    /// SDK 没有单独的死区参数，通过线性曝光属性的收敛容差实现：退出收敛的容差设为
+    /// `luma_delta` 对应的百分比，进入收敛的容差不大于退出容差，二者形成回差。
+    /// 死区越大，平坦场景中细微的亮度起伏越不会引起曝光抖动，但收敛后的亮度误差最大可达
+    /// `luma_delta`，缓慢的光照变化也要累积到超出死区才会跟随，之后曝光一次调节较大的幅度。
+    /// 目标亮度改变后死区按百分比保持，需要固定亮度级时应重新设置。
+    ///
+    /// # Parameters
+    /// * `luma_delta` - 死区，单位：亮度级，范围：[0, `AE_DEADBAND_MAX`]，超出时取边界值，
+    ///   且不超过目标亮度。
+    ///
+    /// # Note
+    ///
+    /// 进入收敛的容差取首次设置死区前的原值与退出容差中的较小者，死区再次放宽时恢复原值；
+    /// 期间以 `set_lin_exp_attr` 修改的进入容差作为新的原值。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_deadband(&self, luma_delta: u8) -> XCamResult<()>;
+}
+
+impl Context {
+    /// 传感器可达的增益上限。
+    ///
+    /// 传感器描述不含增益上限，取首次修改曝光增益范围前读到的上限并缓存，之后收窄范围不影响该值。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub(crate) fn sensor_gain_max(&self) -> XCamResult<f32> {
+        let mut cached = self.sensor_gain_max.lock().unwrap();
+        if let Some(max) = *cached {
+            return Ok(max);
+        }
+        let (_, max) = self.get_exp_gain_range()?;
+        Ok(*cached.insert(max))
+    }
+
+    /// 设置模组镜头的固定光圈 f 值，见 `get_sensor_aperture`。
+    ///
+    /// `fnumber` 须为正的有限值，否则返回 `XCAM_RETURN_ERROR_PARAM`。
+    pub fn set_sensor_aperture(&self, fnumber: f32) -> XCamResult<()> {
+        if !(fnumber.is_finite() && fnumber > 0.0) {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        self.aperture.store(fnumber.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 设置只作用一帧的手动曝光，用于与闪光灯同步的抓拍。
+    ///
+    /// 记录当前帧号后立即切换为手动曝光，之后由 `tick_oneshot_exposure` 在帧号前进一帧时
+    /// 恢复原曝光模式；原为手动曝光时恢复原增益与曝光时间。
+    ///
+    /// # Note
+    ///
+    /// 恢复只在调用 `tick_oneshot_exposure` 时进行，应在每帧的处理循环中调用。传感器曝光
+    /// 寄存器有 2 至 3 帧的生效延迟，设置与恢复同样延迟，因此单帧曝光出现在设置后第 2 至 3 帧；
+    /// 两次调用之间若跳过了帧，单帧曝光会持续多帧。
+    pub fn set_oneshot_exposure(&self, time: Duration, gain: f32) -> XCamResult<()> {
+        begin_oneshot_exposure(self, &mut self.oneshot_exp.lock().unwrap(), time, gain)
+    }
+
+    /// 单帧曝光到期时恢复原曝光，见 `set_oneshot_exposure`。
+    ///
+    /// 发生恢复时返回 `true`，没有待恢复的单帧曝光或尚未到期时返回 `false`。
+    pub fn tick_oneshot_exposure(&self) -> XCamResult<bool> {
+        tick_oneshot_exposure(self, &mut self.oneshot_exp.lock().unwrap())
+    }
+
+    /// 启用随光照调整的自动帧率：暗光下逐步降低帧率，使 AE 可以使用更长的曝光时间；
+    /// 光照恢复后逐步回升。以 `max_fps` 开始，帧率以手动模式设置。
+    ///
+    /// 每次调用 `tick_auto_framerate` 读取当前 ISO 并折算为最高帧率下的 ISO（乘以
+    /// `max_fps / 当前帧率`），达到 `dark_threshold_iso` 时降低 `AUTO_FPS_STEP`，低于其
+    /// `AUTO_FPS_HYSTERESIS` 倍时提高 `AUTO_FPS_STEP`，其间保持不变；折算与回差避免降低帧率后
+    /// ISO 随之下降而立即回升，造成帧率来回跳动。
+    ///
+    /// 已启用时以新参数重新开始，关闭时仍恢复首次启用前的帧率设置。
+    ///
+    /// # Note
+    ///
+    /// 调整只在调用 `tick_auto_framerate` 时进行，应在处理循环中按帧或以固定间隔调用；
+    /// 每次至多调整一步，从 `max_fps` 降到 `min_fps` 需要相应次数的调用。帧率为整数，
+    /// 写入时四舍五入。
+    ///
+    /// # Parameters
+    /// * `min_fps`、`max_fps` - 帧率范围，须大于 0 且 `min_fps` 不大于 `max_fps`，否则返回
+    ///   `XCAM_RETURN_ERROR_PARAM`。
+    /// * `dark_threshold_iso` - 暗光阈值，为 0 时返回 `XCAM_RETURN_ERROR_PARAM`。
+    pub fn enable_auto_framerate(
+        &self,
+        min_fps: f32,
+        max_fps: f32,
+        dark_threshold_iso: u32,
+    ) -> XCamResult<()> {
+        enable_auto_framerate(
+            self,
+            &mut self.auto_fps.lock().unwrap(),
+            min_fps,
+            max_fps,
+            dark_threshold_iso,
+        )
+    }
+
+    /// 按当前 ISO 调整一步帧率，见 `enable_auto_framerate`。
+    ///
+    /// 写入新帧率时返回 `true`，未启用或帧率不变时返回 `false`。
+    pub fn tick_auto_framerate(&self) -> XCamResult<bool> {
+        tick_auto_framerate(self, &mut self.auto_fps.lock().unwrap())
+    }
+
+    /// 关闭自动帧率，恢复启用前的帧率设置。
+    pub fn disable_auto_framerate(&self) -> XCamResult<()> {
+        disable_auto_framerate(self, &mut self.auto_fps.lock().unwrap())
+    }
+
+    /// 设置 AE 的双速率收敛：亮度偏差较小时以稳态速度平稳收敛，偏差较大时（如开灯）切换为快速。
+    ///
+    /// 分为两个区域：稳态区中画面亮度与目标亮度相差不超过 `fast_threshold_ev`，使用
+    /// `steady_speed`，避免收敛过程中亮度来回摆动；偏差超过阈值时进入快速区，使用 `fast_speed`，
+    /// 偏差降至阈值的 `AE_CONVERGENCE_HYSTERESIS` 倍以下后回到稳态区，回差避免在阈值附近反复切换。
+    /// 以稳态区开始，每次调用 `tick_ae_convergence` 按 `query_exp_info` 的平均亮度与
+    /// `get_ae_target_luma` 计算偏差（见 `luma_delta_ev`），切换区域时以 `set_ae_speed` 写入。
+    /// 再次调用时以新参数重新开始。
+    ///
+    /// # Note
+    ///
+    /// SDK 的动态阻尼只区分调节方向，没有可设置的偏差阈值，因此区域由本库选择，切换只在调用
+    /// `tick_ae_convergence` 时进行，应在每帧的处理循环中调用。仅 v4_0、v5_0 支持。
+    ///
+    /// # Parameters
+    /// * `steady_speed`、`fast_speed` - 收敛速度，即每次调节消除的亮度误差比例，范围：(0,1]，
+    ///   且 `fast_speed` 不小于 `steady_speed`。
+    /// * `fast_threshold_ev` - 进入快速区的亮度偏差，单位：EV，范围：(0, `AE_FAST_THRESHOLD_MAX_EV`]。
+    ///
+    /// 超出范围时返回 `XCAM_RETURN_ERROR_PARAM`。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub fn set_ae_convergence(
+        &self,
+        steady_speed: f32,
+        fast_threshold_ev: f32,
+        fast_speed: f32,
+    ) -> XCamResult<()> {
+        let conv = AeConvergence {
+            steady_speed,
+            fast_threshold_ev,
+            fast_speed,
+        };
+        set_ae_convergence_with(self, &mut self.ae_convergence.lock().unwrap(), conv)
+    }
+
+    /// 获取 `set_ae_convergence` 设置的参数，未设置时为 `None`。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub fn get_ae_convergence(&self) -> Option<AeConvergence> {
+        self.ae_convergence.lock().unwrap().map(|s| s.conv)
+    }
+
+    /// 按当前亮度偏差选择收敛区域，见 `set_ae_convergence`。
+    ///
+    /// 切换区域并写入速度时返回 `true`，未设置或区域不变时返回 `false`。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub fn tick_ae_convergence(&self) -> XCamResult<bool> {
+        tick_ae_convergence(self, &mut self.ae_convergence.lock().unwrap())
+    }
+
+    /// 按画面中的窗口测光并锁定曝光（点测光锁定）。
+    ///
+    /// 从 `get_luma_map` 的亮度图中取窗口的平均亮度，将当前曝光量按全图平均亮度与窗口亮度之比
+    /// 缩放，使窗口达到 AE 当前为全图收敛到的亮度；缩放后的曝光量先分配给曝光时间，再分配给
+    /// 增益，均限制在当前的曝光时间与增益范围内，以手动曝光写入。之后场景变化时曝光保持不变，
+    /// 直至以 `set_exp_mode(OpMode::Auto)` 恢复自动曝光。
+    ///
+    /// 与以 `set_manual_exp` 写入 `get_effective_exposure` 的结果、将曝光冻结在当前值的全局锁定
+    /// 不同，锁定的曝光按窗口而非全图测得：点击画面中的暗处或亮处，锁定后该处亮度正常。
+    /// 与 `set_priority_regions` 也不同，后者只调整测光权重，AE 仍随场景持续调整。
+    ///
+    /// # Note
+    ///
+    /// 亮度图为 15x15 网格，窗口向外扩展到整格，小于一格的窗口按所在的格测光。RAW 域亮度与
+    /// 曝光量近似成正比，isp_hw_v20 的 YUV 域亮度经过 gamma，缩放只是近似，可能需要多次调用。
+    ///
+    /// # Parameters
+    /// * `rect` - 测光窗口，为 `negotiated_format` 输出画面中的像素坐标；尺寸须大于 0，否则返回
+    ///   `XCAM_RETURN_ERROR_PARAM`，超出画面时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+    ///   尚未 `prepare` 时返回 `XCAM_RETURN_ERROR_ORDER`。
+    pub fn lock_ae_to_window(&self, rect: Rect) -> XCamResult<()> {
+        let format = self.negotiated_format()?;
+        lock_ae_to_window_with(self, rect, (format.width, format.height)).map(|_| ())
+    }
+
+    /// 设置 3A 统计的 AE 测量窗口，只在窗口内计算曝光统计。
+    ///
+    /// 缩小窗口可减少 ISP 统计与 AE 算法的运算量，代价是统计只反映窗口内的画面：窗口外的亮度
+    /// 变化不再影响曝光，较小的窗口中每个统计块覆盖的像素更少，测光也更容易受局部噪声与
+    /// 运动影响。白平衡统计窗口由 IQ 文件决定，不受本接口影响。
+    ///
+    /// # Parameters
+    /// * `rect` - 统计窗口，为 `negotiated_format` 输出画面中的像素坐标；尺寸须大于 0，否则返回
+    ///   `XCAM_RETURN_ERROR_PARAM`，超出画面时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+    ///   尚未 `prepare` 时返回 `XCAM_RETURN_ERROR_ORDER`。仅 v4_0、v5_0 支持。
+    pub fn set_stats_window(&self, rect: Rect) -> XCamResult<()> {
+        let format = self.negotiated_format()?;
+        set_stats_window_with(self, rect, (format.width, format.height))
+    }
+
+    /// 获取 3A 统计的 AE 测量窗口，见 `set_stats_window`。
+    pub fn get_stats_window(&self) -> XCamResult<Rect> {
+        self.get_ae_stats_window()
+    }
+
+    /// 获取当前帧的传感器曝光时序，见 `ExposureTiming`。
+    ///
+    /// 行数取自曝光查询结果中 AIQ 交给传感器驱动的参数（v1_0 至 v5_0 均有），只反映线性曝光；
+    /// 驱动未报告的行长、帧长取传感器描述中的标称值，描述读取失败时微秒值为 `None`。
+    /// 曝光参数在同一帧内读取，见 `read_in_frame`；读到的时序按帧号记录，
+    /// 之后可由 `get_exposure_timing_at` 按帧号取回。
+    pub fn get_exposure_timing(&self) -> XCamResult<ExposureTiming> {
+        let (sensor, frame_id) = context::read_in_frame(context::last_frame_id, || {
+            let mut info = ffi::Uapi_ExpQueryInfo_t::default();
+            #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+            uapi_call!(ffi::rk_aiq_user_api_ae_queryExpResInfo(self, &mut info))?;
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            uapi_call!(ffi::rk_aiq_user_api2_ae_queryExpResInfo(self, &mut info))?;
+            Ok(info.CurExpInfo.LinearExp.exp_sensor_params)
+        })?;
+        let mut des = SensorDescriptor::default();
+        let des = uapi_call!(ffi::rk_aiq_uapi2_sysctl_getSensorDiscrib(self, &mut des))
+            .ok()
+            .map(|_| des);
+        let timing = ExposureTiming::new(
+            frame_id,
+            sensor.coarse_integration_time as u32,
+            sensor.line_length_pixels as u32,
+            sensor.frame_length_lines as u32,
+            des.as_ref(),
+        );
+        self.exposure_timings.lock().unwrap().record(timing);
+        Ok(timing)
+    }
+
+    /// 获取帧号为 `frame_id` 的帧的曝光时序，用于按帧号计算闪光灯触发时刻。
+    ///
+    /// 只记录 `get_exposure_timing` 读到的帧，最多保留最近 `EXPOSURE_TIMING_HISTORY` 帧；
+    /// 该帧未被读取或已被挤出时返回 `None`。
+    pub fn get_exposure_timing_at(&self, frame_id: u32) -> Option<ExposureTiming> {
+        self.exposure_timings.lock().unwrap().get(frame_id)
+    }
+
+    /// 按优先区域（如人脸检测结果）设置 AE 权重表，使测光偏向这些区域。
+    ///
+    /// `strength` 为 0 时等同均匀权重，为 1 时完全覆盖的格取 `AE_WEIGHT_MAX`，
+    /// 栅格化方式见 `rasterize_regions`。不分配内存，可每隔数帧调用一次。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub fn set_priority_regions(&self, regions: &[Roi], strength: f32) -> XCamResult<()> {
+        apply_priority_regions(self, regions, strength)
+    }
+
+    /// 恢复均匀的 AE 权重表。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub fn clear_priority_regions(&self) -> XCamResult<()> {
+        apply_priority_regions(self, &[], 0.0)
+    }
+}
+
+impl AutoExposure for Context {
+    #[cfg(feature = "v1_0")]
+    fn get_ae_mode(&self) -> XCamResult<AeMode> {
+        let mut mode = ffi::aeMode_t::default();
+        uapi_call!(ffi::rk_aiq_uapi_getAeMode(self, &mut mode)).map(|_| mode)
+    }
+
+    #[cfg(feature = "v1_0")]
+    fn set_ae_mode(&self, mode: AeMode) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_getAeMode(self, &mode))
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn get_exp_mode(&self) -> XCamResult<OpMode> {
+        let mut mode = ffi::opMode_t::OP_INVAL;
+        uapi_call!(ffi::rk_aiq_uapi_getExpMode(self, &mut mode)).map(|_| mode.into())
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_exp_mode(&self) -> XCamResult<OpMode> {
+        let mut mode = ffi::opMode_t::OP_INVAL;
+        uapi_call!(ffi::rk_aiq_uapi2_getExpMode(self, &mut mode)).map(|_| mode.into())
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn set_exp_mode(&self, mode: OpMode) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setExpMode(self, mode.into()))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_exp_mode(&self, mode: OpMode) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi2_setExpMode(self, mode.into()))
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn get_exp_gain_range(&self) -> XCamResult<(f32, f32)> {
+        let mut range = ffi::paRange_t::default();
+        uapi_call!(ffi::rk_aiq_uapi_getExpGainRange(self, &mut range))
+            .map(|_| (range.min, range.max))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_exp_gain_range(&self) -> XCamResult<(f32, f32)> {
+        let mut range = ffi::paRange_t::default();
+        uapi_call!(ffi::rk_aiq_uapi2_getExpGainRange(self, &mut range))
+            .map(|_| (range.min, range.max))
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn set_exp_gain_range(&self, min: f32, max: f32) -> XCamResult<()> {
+        let mut range = ffi::paRange_t { min, max };
+        uapi_call!(ffi::rk_aiq_uapi_setExpGainRange(self, &mut range))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_exp_gain_range(&self, min: f32, max: f32) -> XCamResult<()> {
+        self.sensor_gain_max()?;
+        let mut range = ffi::paRange_t { min, max };
+        uapi_call!(ffi::rk_aiq_uapi2_setExpGainRange(self, &mut range))
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn get_exp_time_range(&self) -> XCamResult<(f32, f32)> {
+        let mut range = ffi::paRange_t::default();
+        uapi_call!(ffi::rk_aiq_uapi_getExpTimeRange(self, &mut range))
+            .map(|_| (range.min, range.max))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_exp_time_range(&self) -> XCamResult<(f32, f32)> {
+        let mut range = ffi::paRange_t::default();
+        uapi_call!(ffi::rk_aiq_uapi2_getExpTimeRange(self, &mut range))
+            .map(|_| (range.min, range.max))
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn set_exp_time_range(&self, min: f32, max: f32) -> XCamResult<()> {
+        let mut range = ffi::paRange_t { min, max };
+        uapi_call!(ffi::rk_aiq_uapi_setExpTimeRange(self, &mut range))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_exp_time_range(&self, min: f32, max: f32) -> XCamResult<()> {
+        let mut range = ffi::paRange_t { min, max };
+        uapi_call!(ffi::rk_aiq_uapi2_setExpTimeRange(self, &mut range))
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn set_manual_exp(&self, gain: f32, time: f32) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setManualExp(self, gain, time))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_manual_exp(&self, gain: f32, time: f32) -> XCamResult<()> {
+        use crate::ffi::RKAiqOPMode_e::RK_AIQ_OP_MODE_MANUAL;
+        let mut sw_attr =
+            uapi_get!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self) -> ffi::Uapi_ExpSwAttrV2_t)?;
+        sw_attr.Enable = 1;
+        sw_attr.AecOpType = RK_AIQ_OP_MODE_MANUAL;
+        // LinearAE
+        sw_attr.stManual.LinearAE.ManualGainEn = true;
+        sw_attr.stManual.LinearAE.ManualTimeEn = true;
+        sw_attr.stManual.LinearAE.GainValue = gain;
+        sw_attr.stManual.LinearAE.TimeValue = time;
+        uapi_call!(ffi::rk_aiq_user_api2_ae_setExpSwAttr(self, sw_attr))
+    }
+
+    fn set_manual_exp_fps(&self, gain: f32, time_fps: usize) -> XCamResult<()> {
+        let time = 1.0 / (time_fps as f32);
+        self.set_manual_exp(gain, time)
+    }
+
+    fn set_manual_exp_ms(&self, gain: f32, time_ms: usize) -> XCamResult<()> {
+        let time = 1.0 / 1000.0 * (time_ms as f32);
+        self.set_manual_exp(gain, time)
+    }
+
+    fn set_manual_exp_us(&self, gain: f32, time_us: usize) -> XCamResult<()> {
+        let time = 1.0 / 1000000.0 * (time_us as f32);
+        self.set_manual_exp(gain, time)
+    }
+
+    fn set_blc_mode(&self, enabled: bool, mode: AeMeasAreaType) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setBLCMode(self, enabled, mode.into()))
+    }
+
+    fn set_blc_strength(&self, strength: i32) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setBLCStrength(self, strength))
+    }
+
+    fn set_hlc_mode(&self, enabled: bool) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setHLCMode(self, enabled))
+    }
+
+    fn set_hlc_strength(&self, strength: i32) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setHLCStrength(self, strength))
+    }
+
+    #[cfg(feature = "v2_0")]
+    fn get_dark_area_boost_strth(&self) -> XCamResult<u32> {
+        let mut level: u32 = 0;
+        uapi_call!(ffi::rk_aiq_uapi_getDarkAreaBoostStrth(self, &mut level)).map(|_| level)
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn get_dark_area_boost_strth(&self) -> XCamResult<u32> {
+        let mut level: u32 = 0;
+        uapi_call!(ffi::rk_aiq_uapi2_getDarkAreaBoostStrth(self, &mut level)).map(|_| level)
+    }
+
+    #[cfg(feature = "v2_0")]
+    fn set_dark_area_boost_strth(&self, level: u32) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setDarkAreaBoostStrth(self, level))
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn set_dark_area_boost_strth(&self, level: u32) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi2_setDarkAreaBoostStrth(self, level))
+    }
+
+    fn get_anti_flicker_mode(&self) -> XCamResult<AntiFlickerMode> {
+        let mut mode: AntiFlickerMode = Default::default();
+        uapi_call!(ffi::rk_aiq_uapi_getAntiFlickerMode(self, &mut mode)).map(|_| mode)
+    }
+
+    fn set_anti_flicker_mode<T: Into<AntiFlickerMode>>(&self, mode: T) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setAntiFlickerMode(self, mode.into()))
+    }
+
+    fn get_exp_pwr_line_freq_mode(&self) -> XCamResult<ExpPwrLineFreq> {
+        let mut mode: ExpPwrLineFreq = Default::default();
+        uapi_call!(ffi::rk_aiq_uapi_getExpPwrLineFreqMode(self, &mut mode)).map(|_| mode)
+    }
+
+    fn set_exp_pwr_line_freq_mode<T: Into<ExpPwrLineFreq>>(&self, mode: T) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setExpPwrLineFreqMode(self, mode.into()))
+    }
+
+    fn get_base_iso(&self) -> u32 {
+        self.base_iso.load(Ordering::Relaxed)
+    }
+
+    fn set_base_iso(&self, base_iso: u32) -> XCamResult<()> {
+        check_base_iso(base_iso)?;
+        self.base_iso.store(base_iso, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn get_sensor_aperture(&self) -> XCamResult<f32> {
+        match self.aperture.load(Ordering::Relaxed) {
+            0 => Err(XCamError::Unsupported),
+            bits => Ok(f32::from_bits(bits)),
+        }
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ae_stats_window(&self) -> XCamResult<Rect> {
+        let win = uapi_get!(ffi::rk_aiq_user_api2_ae_getExpWinAttr(self) -> ffi::Uapi_ExpWin_t)?;
+        Ok(Rect {
+            left: win.Params.h_offs,
+            top: win.Params.v_offs,
+            width: win.Params.h_size,
+            height: win.Params.v_size,
+        })
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_stats_window(&self, rect: Rect) -> XCamResult<()> {
+        let mut win =
+            uapi_get!(ffi::rk_aiq_user_api2_ae_getExpWinAttr(self) -> ffi::Uapi_ExpWin_t)?;
+        win.Params.h_offs = rect.left;
+        win.Params.v_offs = rect.top;
+        win.Params.h_size = rect.width;
+        win.Params.v_size = rect.height;
+        uapi_call!(ffi::rk_aiq_user_api2_ae_setExpWinAttr(self, win))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ae_speed(&self) -> XCamResult<f32> {
+        let sw_attr =
+            uapi_get!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self) -> ffi::Uapi_ExpSwAttrV2_t)?;
+        Ok(1.0 - sw_attr.stAuto.stAeSpeed.DampOver)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_speed(&self, speed: f32) -> XCamResult<()> {
+        if !(speed > 0.0 && speed <= 1.0) {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        let mut sw_attr =
+            uapi_get!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self) -> ffi::Uapi_ExpSwAttrV2_t)?;
+        let damp = speed_to_damp(speed);
+        let ae_speed = &mut sw_attr.stAuto.stAeSpeed;
+        ae_speed.DyDampEn = false;
+        ae_speed.DampOver = damp;
+        ae_speed.DampUnder = damp;
+        uapi_call!(ffi::rk_aiq_user_api2_ae_setExpSwAttr(self, sw_attr))
+    }
+
+    fn get_exposure_steps(&self) -> XCamResult<Vec<f32>> {
+        let mut des = SensorDescriptor::default();
+        uapi_call!(ffi::rk_aiq_uapi2_sysctl_getSensorDiscrib(self, &mut des))?;
+        exposure_steps(&des)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_sensor_gain_range(&self) -> XCamResult<(f32, f32)> {
+        Ok((1.0, self.sensor_gain_max()?))
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn query_exp_info(&self) -> XCamResult<ExpInfo> {
+        let mut info = ffi::Uapi_ExpQueryInfo_t::default();
+        uapi_call!(ffi::rk_aiq_user_api_ae_queryExpResInfo(self, &mut info))?;
+        let mut info = ExpInfo::from(info);
+        info.iso = gain_to_iso(info.gain, self.get_base_iso());
+        Ok(info)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn query_exp_info(&self) -> XCamResult<ExpInfo> {
+        let mut info = ffi::Uapi_ExpQueryInfo_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_queryExpResInfo(self, &mut info))?;
+        let mut info = ExpInfo::from(info);
+        info.iso = gain_to_iso(info.gain, self.get_base_iso());
+        Ok(info)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_manual_gains(&self) -> XCamResult<(f32, f32)> {
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self, &mut sw_attr))?;
+        let linear = sw_attr.stManual.LinearAE;
+        Ok((linear.GainValue, linear.IspDGainValue))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_manual_gains(&self, analog: f32, digital: f32) -> XCamResult<()> {
+        check_manual_gains(analog, digital, self.sensor_gain_max()?)?;
+        let (_, time) = self.get_effective_exposure()?;
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self, &mut sw_attr))?;
+        apply_manual_gains(&mut sw_attr, analog, digital, time);
+        uapi_call!(ffi::rk_aiq_user_api2_ae_setExpSwAttr(self, sw_attr))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_global_digital_gain_cap(&self) -> XCamResult<Option<f32>> {
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self, &mut sw_attr))?;
+        Ok(digital_gain_cap_of(&sw_attr))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_global_digital_gain_cap(&self, cap: f32) -> XCamResult<()> {
+        check_digital_gain_cap(cap)?;
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self, &mut sw_attr))?;
+        apply_digital_gain_cap(&mut sw_attr, cap, || {
+            Ok((self.get_exp_time_range()?, self.get_exp_gain_range()?))
+        })?;
+        uapi_call!(ffi::rk_aiq_user_api2_ae_setExpSwAttr(self, sw_attr))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ae_weights(&self) -> XCamResult<Vec<u8>> {
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self, &mut sw_attr))?;
+        let (cols, rows) = ae_grid_dimensions();
+        Ok(sw_attr.GridWeights.uCoeff[..cols * rows].to_vec())
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_weights(&self, weights: &[u8]) -> XCamResult<()> {
+        check_ae_weights(weights)?;
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self, &mut sw_attr))?;
+        sw_attr.GridWeights.uCoeff[..weights.len()].copy_from_slice(weights);
+        uapi_call!(ffi::rk_aiq_user_api2_ae_setExpSwAttr(self, sw_attr))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_exposure_route(&self) -> XCamResult<Vec<ExpRoutePoint>> {
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self, &mut sw_attr))?;
+        let mode = self.prepared.lock().unwrap().map(|p| p.mode);
+        Ok(read_exp_route(&sw_attr, mode))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_exposure_route(&self, points: &[ExpRoutePoint]) -> Result<(), ExpRouteError> {
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        check_exp_route(points, sw_attr.stAuto.LinAeRoute.TimeDot.len())?;
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self, &mut sw_attr))?;
+        let mode = self.prepared.lock().unwrap().map(|p| p.mode);
+        write_exp_route(&mut sw_attr, mode, points)?;
+        uapi_call!(ffi::rk_aiq_user_api2_ae_setExpSwAttr(self, sw_attr))?;
+        Ok(())
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn set_exposure_priority(&self, priority: ExposurePriority) -> XCamResult<()> {
+        let mut saved = self.exp_priority_ranges.lock().unwrap();
+        emulate_exposure_priority(self, &mut saved, priority)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_exposure_priority(&self, priority: ExposurePriority) -> XCamResult<()> {
+        check_exposure_priority(priority)?;
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self, &mut sw_attr))?;
+        apply_exposure_priority(&mut sw_attr, priority);
+        uapi_call!(ffi::rk_aiq_user_api2_ae_setExpSwAttr(self, sw_attr))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_gain_split_policy(&self) -> XCamResult<GainSplitPolicy> {
+        let sensor_max = self.sensor_gain_max()?;
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self, &mut sw_attr))?;
+        Ok(gain_split_policy_of(&sw_attr, sensor_max))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_gain_split_policy(&self, policy: GainSplitPolicy) -> XCamResult<()> {
+        let sensor_max = self.sensor_gain_max()?;
+        check_gain_split_policy(policy, sensor_max)?;
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getExpSwAttr(self, &mut sw_attr))?;
+        apply_gain_split_policy(&mut sw_attr, policy, sensor_max);
+        uapi_call!(ffi::rk_aiq_user_api2_ae_setExpSwAttr(self, sw_attr))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_lin_exp_attr(&self) -> XCamResult<LinExpAttr> {
+        let mut attr = ffi::Uapi_LinExpAttrV2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_ae_getLinExpAttr(self, &mut attr)).map(|_| attr.into())
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_lin_exp_attr<T: Into<LinExpAttr>>(&self, attr: T) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_user_api2_ae_setLinExpAttr(self, attr.into().0))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_deadband(&self, luma_delta: u8) -> XCamResult<()> {
+        let mut saved = self.ae_deadband_tolerance_in.lock().unwrap();
+        apply_ae_deadband(self, &mut saved, luma_delta)
+    }
+}
+
+impl AutoExposure for SharedContext {
+    #[cfg(feature = "v1_0")]
+    fn get_ae_mode(&self) -> XCamResult<AeMode> {
+        self.inner.get_ae_mode()
+    }
+
+    #[cfg(feature = "v1_0")]
+    fn set_ae_mode(&self, mode: AeMode) -> XCamResult<()> {
+        self.inner.set_ae_mode(mode)
+    }
+
+    fn get_exp_mode(&self) -> XCamResult<OpMode> {
+        self.inner.get_exp_mode()
+    }
+
+    fn set_exp_mode(&self, mode: OpMode) -> XCamResult<()> {
+        self.inner.set_exp_mode(mode)
+    }
+
+    fn get_exp_gain_range(&self) -> XCamResult<(f32, f32)> {
+        self.inner.get_exp_gain_range()
+    }
+
+    fn set_exp_gain_range(&self, min: f32, max: f32) -> XCamResult<()> {
+        self.inner.set_exp_gain_range(min, max)
+    }
+
+    fn get_exp_time_range(&self) -> XCamResult<(f32, f32)> {
+        self.inner.get_exp_time_range()
+    }
+
+    fn set_exp_time_range(&self, min: f32, max: f32) -> XCamResult<()> {
+        self.inner.set_exp_time_range(min, max)
+    }
+
+    fn set_manual_exp(&self, gain: f32, time: f32) -> XCamResult<()> {
+        self.inner.set_manual_exp(gain, time)
+    }
+
+    fn set_manual_exp_fps(&self, gain: f32, time_fps: usize) -> XCamResult<()> {
+        self.inner.set_manual_exp_fps(gain, time_fps)
+    }
+
+    fn set_manual_exp_ms(&self, gain: f32, time_ms: usize) -> XCamResult<()> {
+        self.inner.set_manual_exp_ms(gain, time_ms)
+    }
+
+    fn set_manual_exp_us(&self, gain: f32, time_us: usize) -> XCamResult<()> {
+        self.inner.set_manual_exp_us(gain, time_us)
+    }
+
+    fn set_blc_mode(&self, enabled: bool, mode: AeMeasAreaType) -> XCamResult<()> {
+        self.inner.set_blc_mode(enabled, mode)
+    }
+
+    fn set_blc_strength(&self, strength: i32) -> XCamResult<()> {
+        self.inner.set_blc_strength(strength)
+    }
+
+    fn set_hlc_mode(&self, enabled: bool) -> XCamResult<()> {
+        self.inner.set_hlc_mode(enabled)
+    }
+
+    fn set_hlc_strength(&self, strength: i32) -> XCamResult<()> {
+        self.inner.set_hlc_strength(strength)
+    }
+
+    fn get_dark_area_boost_strth(&self) -> XCamResult<u32> {
+        self.inner.get_dark_area_boost_strth()
+    }
+
+    fn set_dark_area_boost_strth(&self, level: u32) -> XCamResult<()> {
+        self.inner.set_dark_area_boost_strth(level)
+    }
+
+    fn get_anti_flicker_mode(&self) -> XCamResult<AntiFlickerMode> {
+        self.inner.get_anti_flicker_mode()
+    }
+
+    fn set_anti_flicker_mode<T: Into<AntiFlickerMode>>(&self, mode: T) -> XCamResult<()> {
+        self.inner.set_anti_flicker_mode(mode)
     }
 
     fn get_exp_pwr_line_freq_mode(&self) -> XCamResult<ExpPwrLineFreq> {
-        let mut mode: ExpPwrLineFreq = Default::default();
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getExpPwrLineFreqMode(
-                self.internal.as_ptr(),
-                &mut mode,
-            ))
-            .ok()
-            .map(|_| mode)
-        }
+        self.inner.get_exp_pwr_line_freq_mode()
     }
 
     fn set_exp_pwr_line_freq_mode<T: Into<ExpPwrLineFreq>>(&self, mode: T) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setExpPwrLineFreqMode(
-                self.internal.as_ptr(),
-                mode.into(),
-            ))
-            .ok()
-        }
+        self.inner.set_exp_pwr_line_freq_mode(mode)
+    }
+
+    fn get_base_iso(&self) -> u32 {
+        self.inner.get_base_iso()
+    }
+
+    fn set_base_iso(&self, base_iso: u32) -> XCamResult<()> {
+        self.inner.set_base_iso(base_iso)
+    }
+
+    fn get_sensor_aperture(&self) -> XCamResult<f32> {
+        self.inner.get_sensor_aperture()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ae_stats_window(&self) -> XCamResult<Rect> {
+        self.inner.get_ae_stats_window()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_stats_window(&self, rect: Rect) -> XCamResult<()> {
+        self.inner.set_ae_stats_window(rect)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ae_speed(&self) -> XCamResult<f32> {
+        self.inner.get_ae_speed()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_speed(&self, speed: f32) -> XCamResult<()> {
+        self.inner.set_ae_speed(speed)
+    }
+
+    fn get_exposure_steps(&self) -> XCamResult<Vec<f32>> {
+        self.inner.get_exposure_steps()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_sensor_gain_range(&self) -> XCamResult<(f32, f32)> {
+        self.inner.get_sensor_gain_range()
+    }
+
+    fn query_exp_info(&self) -> XCamResult<ExpInfo> {
+        self.inner.query_exp_info()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_manual_gains(&self) -> XCamResult<(f32, f32)> {
+        self.inner.get_manual_gains()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_manual_gains(&self, analog: f32, digital: f32) -> XCamResult<()> {
+        self.inner.set_manual_gains(analog, digital)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_global_digital_gain_cap(&self) -> XCamResult<Option<f32>> {
+        self.inner.get_global_digital_gain_cap()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_global_digital_gain_cap(&self, cap: f32) -> XCamResult<()> {
+        self.inner.set_global_digital_gain_cap(cap)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ae_weights(&self) -> XCamResult<Vec<u8>> {
+        self.inner.get_ae_weights()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_weights(&self, weights: &[u8]) -> XCamResult<()> {
+        self.inner.set_ae_weights(weights)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_exposure_route(&self) -> XCamResult<Vec<ExpRoutePoint>> {
+        self.inner.get_exposure_route()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_exposure_route(&self, points: &[ExpRoutePoint]) -> Result<(), ExpRouteError> {
+        self.inner.set_exposure_route(points)
+    }
+
+    fn set_exposure_priority(&self, priority: ExposurePriority) -> XCamResult<()> {
+        self.inner.set_exposure_priority(priority)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_gain_split_policy(&self) -> XCamResult<GainSplitPolicy> {
+        self.inner.get_gain_split_policy()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_gain_split_policy(&self, policy: GainSplitPolicy) -> XCamResult<()> {
+        self.inner.set_gain_split_policy(policy)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_lin_exp_attr(&self) -> XCamResult<LinExpAttr> {
+        self.inner.get_lin_exp_attr()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_lin_exp_attr<T: Into<LinExpAttr>>(&self, attr: T) -> XCamResult<()> {
+        self.inner.set_lin_exp_attr(attr)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_deadband(&self, luma_delta: u8) -> XCamResult<()> {
+        self.inner.set_ae_deadband(luma_delta)
     }
 }
 
@@ -492,3 +2805,1161 @@ impl AutoExposure for Context {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod mock_tests {
+    use super::*;
+    use crate::mock::MockContext;
+
+    #[test]
+    fn test_check_manual_gains() {
+        let out_of_range = Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+        assert_eq!(check_manual_gains(16.0, 2.0, 64.0), Ok(()));
+        assert_eq!(check_manual_gains(1.0, ISP_DGAIN_MAX, 64.0), Ok(()));
+        // 参数顺序颠倒时数字增益超出上限。
+        assert_eq!(check_manual_gains(2.0, 16.0, 64.0), out_of_range);
+        assert_eq!(check_manual_gains(128.0, 1.0, 64.0), out_of_range);
+        assert_eq!(check_manual_gains(0.5, 1.0, 64.0), out_of_range);
+        assert_eq!(check_manual_gains(4.0, 0.0, 64.0), out_of_range);
+        assert_eq!(
+            check_manual_gains(f32::NAN, 1.0, 64.0),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+    }
+
+    #[test]
+    fn test_manual_gains_ignore_user_range() {
+        let ctx = MockContext::new();
+        // 用户收窄的增益范围不限制手动增益，上限取传感器可达的增益。
+        ctx.set_exp_gain_range(1.0, 8.0).unwrap();
+        assert_eq!(ctx.set_manual_gains(32.0, 1.0), Ok(()));
+        assert_eq!(ctx.state.borrow().manual_gains, (32.0, 1.0));
+        assert_eq!(
+            ctx.set_manual_gains(128.0, 1.0),
+            Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE
+            ))
+        );
+    }
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lin_exp_attr_builder_keeps_fields() {
+        let mut raw = ffi::Uapi_LinExpAttrV2_t::default();
+        raw.NightSetPoint = 20.0;
+        raw.Evbias = 10.0;
+        raw.DySetPointEn = true;
+        let attr = LinExpAttr::from(raw)
+            .with_set_point(40.0)
+            .with_tolerance(5.0);
+        assert_eq!(attr.0.SetPoint, 40.0);
+        assert_eq!(attr.0.ToleranceIn, 5.0);
+        assert_eq!(attr.0.ToleranceOut, 5.0);
+        assert_eq!(attr.0.NightSetPoint, 20.0);
+        assert_eq!(attr.0.Evbias, 10.0);
+        assert!(attr.0.DySetPointEn);
+    }
+
+    #[test]
+    fn test_exposure_compensation_keeps_metering() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        ctx.set_exp_mode(OpMode::Manual).unwrap();
+        ctx.set_blc_mode(true, AeMeasAreaType::Center).unwrap();
+        ctx.set_lin_exp_attr(LinExpAttr::default().with_set_point(40.0))
+            .unwrap();
+        let before = ctx.calls().len();
+
+        ctx.set_exposure_compensation(1.5).unwrap();
+        assert_eq!(ctx.get_exposure_compensation(), Ok(1.5));
+        ctx.set_exposure_compensation(-5.0).unwrap();
+        assert_eq!(ctx.get_exposure_compensation(), Ok(-EV_COMPENSATION_MAX));
+
+        let st = ctx.state.borrow();
+        assert_eq!(st.exp_mode, OpMode::Manual);
+        assert_eq!(st.blc_mode, (true, AeMeasAreaType::Center));
+        assert_eq!(st.lin_exp_attr.0.SetPoint, 40.0);
+        drop(st);
+        assert!(ctx.calls()[before..]
+            .iter()
+            .all(|c| *c == "get_lin_exp_attr" || *c == "set_lin_exp_attr"));
+    }
+
+    #[test]
+    fn test_iso_conversion() {
+        assert_eq!(iso_to_gains(100, 100, 16.0, 8.0), Ok((1.0, 1.0)));
+        assert_eq!(iso_to_gains(800, 100, 16.0, 8.0), Ok((8.0, 1.0)));
+        assert_eq!(iso_to_gains(6400, 100, 16.0, 8.0), Ok((16.0, 4.0)));
+        assert_eq!(iso_to_gains(1600, 200, 16.0, 8.0), Ok((8.0, 1.0)));
+        let out_of_range = Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+        assert_eq!(iso_to_gains(50, 100, 16.0, 8.0), out_of_range);
+        assert_eq!(iso_to_gains(12900, 100, 16.0, 8.0), out_of_range);
+        assert!(iso_to_gains(100, 0, 16.0, 8.0).is_err());
+        for iso in [100, 250, 1600, 6400, 12800] {
+            let (a, d) = iso_to_gains(iso, 100, 16.0, 8.0).unwrap();
+            assert_eq!(gain_to_iso(a * d, 100), iso);
+        }
+    }
+
+    #[test]
+    fn test_set_iso_matches_exp_info() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        ctx.set_iso(3200).unwrap();
+        let (a, d) = ctx.state.borrow().manual_gains;
+        assert_eq!((a, d), (32.0, 1.0));
+        ctx.state.borrow_mut().exp_info.gain = a * d;
+        assert_eq!(ctx.get_iso(), Ok(3200));
+    }
+
+    #[test]
+    fn test_base_iso() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        assert_eq!(ctx.get_base_iso(), DEFAULT_BASE_ISO);
+        assert_eq!(
+            ctx.set_base_iso(0),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        ctx.state.borrow_mut().exp_info.gain = 4.0;
+        assert_eq!(ctx.get_iso(), Ok(400));
+        assert_eq!(ctx.set_base_iso(50), Ok(()));
+        assert_eq!(ctx.get_base_iso(), 50);
+        assert_eq!(ctx.get_iso(), Ok(200));
+    }
+
+    #[test]
+    fn test_apply_exposure_priority() {
+        use ffi::RKAiqOPMode_e::{RK_AIQ_OP_MODE_AUTO, RK_AIQ_OP_MODE_MANUAL};
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        apply_exposure_priority(&mut sw_attr, ExposurePriority::ShutterPriority(0.01));
+        let linear = sw_attr.stManual.LinearAE;
+        assert_eq!(sw_attr.AecOpType, RK_AIQ_OP_MODE_MANUAL);
+        assert!(linear.ManualTimeEn && !linear.ManualGainEn);
+        assert_eq!(linear.TimeValue, 0.01);
+
+        apply_exposure_priority(&mut sw_attr, ExposurePriority::GainPriority(4.0));
+        let linear = sw_attr.stManual.LinearAE;
+        assert!(!linear.ManualTimeEn && linear.ManualGainEn);
+        assert_eq!(linear.GainValue, 4.0);
+
+        apply_exposure_priority(&mut sw_attr, ExposurePriority::Auto);
+        let linear = sw_attr.stManual.LinearAE;
+        assert_eq!(sw_attr.AecOpType, RK_AIQ_OP_MODE_AUTO);
+        assert!(!linear.ManualTimeEn && !linear.ManualGainEn && !linear.ManualIspDgainEn);
+    }
+
+    #[test]
+    fn test_write_exp_route_hdr_layout() {
+        let point = |time: f32, ratio| ExpRoutePoint {
+            time,
+            gain: 2.0,
+            ratio,
+        };
+        let points = [
+            point(0.01, Some((2.0, 8.0))),
+            point(0.04, Some((4.0, 16.0))),
+        ];
+
+        // HDR2 的长帧为 Frm1，短帧为 Frm0。
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        let mode = Some(WorkingMode::IspHdr2);
+        assert_eq!(write_exp_route(&mut sw_attr, mode, &points), Ok(()));
+        let hdr = &sw_attr.stAuto.HdrAeRoute;
+        assert_eq!((hdr.Frm0TimeDot[1], hdr.Frm1TimeDot[1]), (0.0025, 0.04));
+        assert_eq!(
+            read_exp_route(&sw_attr, mode)
+                .iter()
+                .map(|p| p.ratio)
+                .collect::<Vec<_>>(),
+            [Some((8.0, 8.0)), Some((16.0, 16.0))]
+        );
+
+        // HDR3 的长、中、短帧依次为 Frm2、Frm1、Frm0。
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        let mode = Some(WorkingMode::IspHdr3);
+        assert_eq!(write_exp_route(&mut sw_attr, mode, &points), Ok(()));
+        let hdr = &sw_attr.stAuto.HdrAeRoute;
+        assert_eq!(
+            (hdr.Frm0TimeDot[1], hdr.Frm1TimeDot[1], hdr.Frm2TimeDot[1]),
+            (0.0025, 0.01, 0.04)
+        );
+        assert_eq!(read_exp_route(&sw_attr, mode), points);
+
+        // 线性模式下不换算曝光比。
+        assert!(read_exp_route(&sw_attr, Some(WorkingMode::Normal))
+            .iter()
+            .all(|p| p.ratio.is_none()));
+    }
+
+    #[test]
+    fn test_write_exp_route_keeps_hdr_ratio() {
+        let mode = Some(WorkingMode::IspHdr3);
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        let points = [
+            ExpRoutePoint {
+                time: 0.01,
+                gain: 1.0,
+                ratio: Some((2.0, 4.0)),
+            },
+            ExpRoutePoint {
+                time: 0.02,
+                gain: 1.0,
+                ratio: Some((4.0, 8.0)),
+            },
+        ];
+        assert_eq!(write_exp_route(&mut sw_attr, mode, &points), Ok(()));
+
+        // 未给出曝光比的节点沿用原有的曝光比，HDR 路径与线性路径节点数保持一致。
+        let points = [0.01, 0.02, 0.04].map(|time| ExpRoutePoint {
+            time,
+            gain: 1.0,
+            ratio: None,
+        });
+        assert_eq!(write_exp_route(&mut sw_attr, mode, &points), Ok(()));
+        assert_eq!(sw_attr.stAuto.HdrAeRoute.array_size, 3);
+        assert_eq!(
+            read_exp_route(&sw_attr, mode)
+                .iter()
+                .map(|p| p.ratio)
+                .collect::<Vec<_>>(),
+            [Some((2.0, 4.0)), Some((4.0, 8.0)), Some((4.0, 8.0))]
+        );
+
+        // 尚未 prepare 时无法确定帧布局。
+        assert_eq!(
+            write_exp_route(
+                &mut sw_attr,
+                None,
+                &[ExpRoutePoint {
+                    ratio: Some((2.0, 2.0)),
+                    ..points[0]
+                }]
+            ),
+            Err(ExpRouteError::Sdk(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER
+            )))
+        );
+    }
+
+    #[test]
+    fn test_gain_split_policy() {
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        let lin = &mut sw_attr.stAuto.LinAeRoute;
+        for (i, gain) in [1.0, 16.0, 64.0, 256.0].into_iter().enumerate() {
+            lin.GainDot[i] = gain;
+            lin.IspDGainDot[i] = 1.0;
+        }
+        lin.array_size = 4;
+        let nodes = |sw_attr: &ffi::Uapi_ExpSwAttrV2_t| {
+            let lin = &sw_attr.stAuto.LinAeRoute;
+            (0..4)
+                .map(|i| (lin.GainDot[i], lin.IspDGainDot[i]))
+                .collect::<Vec<_>>()
+        };
+
+        // 传感器增益用满后才使用 ISP 数字增益，超出部分截断。
+        apply_gain_split_policy(&mut sw_attr, GainSplitPolicy::AnalogFirst, 64.0);
+        assert_eq!(
+            nodes(&sw_attr),
+            [(1.0, 1.0), (16.0, 1.0), (64.0, 1.0), (64.0, 4.0)]
+        );
+        assert_eq!(
+            gain_split_policy_of(&sw_attr, 64.0),
+            GainSplitPolicy::AnalogFirst
+        );
+
+        apply_gain_split_policy(&mut sw_attr, GainSplitPolicy::Balanced, 64.0);
+        assert_eq!(
+            nodes(&sw_attr),
+            [(1.0, 1.0), (4.0, 4.0), (8.0, 8.0), (32.0, 8.0)]
+        );
+        assert_eq!(
+            gain_split_policy_of(&sw_attr, 64.0),
+            GainSplitPolicy::Balanced
+        );
+
+        let caps = GainCaps {
+            analog: 8.0,
+            digital: 2.0,
+            isp_dgain: 2.0,
+        };
+        apply_gain_split_policy(&mut sw_attr, GainSplitPolicy::Custom(caps), 64.0);
+        assert_eq!(
+            nodes(&sw_attr),
+            [(1.0, 1.0), (16.0, 1.0), (16.0, 2.0), (16.0, 2.0)]
+        );
+        assert_eq!(
+            gain_split_policy_of(&sw_attr, 64.0),
+            GainSplitPolicy::Custom(GainCaps {
+                analog: 16.0,
+                digital: 1.0,
+                isp_dgain: 2.0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_gain_split_policy() {
+        let custom = |analog, digital, isp_dgain| {
+            check_gain_split_policy(
+                GainSplitPolicy::Custom(GainCaps {
+                    analog,
+                    digital,
+                    isp_dgain,
+                }),
+                64.0,
+            )
+        };
+        assert_eq!(
+            check_gain_split_policy(GainSplitPolicy::Balanced, 64.0),
+            Ok(())
+        );
+        assert_eq!(custom(16.0, 4.0, ISP_DGAIN_MAX), Ok(()));
+        let out_of_range = Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+        assert_eq!(custom(16.0, 8.0, 1.0), out_of_range);
+        assert_eq!(custom(1.0, 1.0, ISP_DGAIN_MAX * 2.0), out_of_range);
+        let param = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        assert_eq!(custom(0.5, 1.0, 1.0), param);
+        assert_eq!(custom(f32::NAN, 1.0, 1.0), param);
+    }
+
+    #[test]
+    fn test_emulated_exposure_priority_restores_ranges() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        let (gain_range, time_range) = ((1.0, 64.0), (0.0001, 1.0 / 30.0));
+        ctx.set_exposure_priority(ExposurePriority::ShutterPriority(0.01))
+            .unwrap();
+        {
+            let st = ctx.state.borrow();
+            assert_eq!(st.exp_mode, OpMode::Auto);
+            assert_eq!(st.exp_time_range, (0.01, 0.01));
+            assert_eq!(st.exp_gain_range, gain_range);
+        }
+        // 直接切换到增益优先时先恢复曝光时间范围。
+        ctx.set_exposure_priority(ExposurePriority::GainPriority(8.0))
+            .unwrap();
+        {
+            let st = ctx.state.borrow();
+            assert_eq!(st.exp_time_range, time_range);
+            assert_eq!(st.exp_gain_range, (8.0, 8.0));
+        }
+        ctx.set_exposure_priority(ExposurePriority::Auto).unwrap();
+        let st = ctx.state.borrow();
+        assert_eq!(st.exp_priority, ExposurePriority::Auto);
+        assert_eq!(st.exp_priority_ranges, None);
+        assert_eq!(st.exp_time_range, time_range);
+        assert_eq!(st.exp_gain_range, gain_range);
+        drop(st);
+
+        let param = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        assert_eq!(
+            ctx.set_exposure_priority(ExposurePriority::ShutterPriority(0.0)),
+            param
+        );
+        assert_eq!(
+            ctx.set_exposure_priority(ExposurePriority::GainPriority(f32::NAN)),
+            param
+        );
+    }
+
+    #[test]
+    fn test_anti_flicker_round_trip() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        for mode in [
+            AntiFlicker::Fixed(AntiFlickerFrequency::Hz60),
+            AntiFlicker::Auto,
+            AntiFlicker::Fixed(AntiFlickerFrequency::Hz50),
+            AntiFlicker::Off,
+        ] {
+            assert_eq!(ctx.set_anti_flicker(mode), Ok(()));
+            assert_eq!(ctx.get_anti_flicker(), Ok(mode));
+        }
+        assert!(matches!(
+            ctx.state.borrow().anti_flicker_mode,
+            AntiFlickerMode::ANTIFLICKER_NORMAL_MODE
+        ));
+        assert!(matches!(
+            ctx.state.borrow().pwr_line_freq,
+            ExpPwrLineFreq::EXP_PWR_LINE_FREQ_DIS
+        ));
+        // SDK 不报告检测结果。
+        assert_eq!(ctx.get_detected_flicker(), Ok(None));
+    }
+
+    #[test]
+    fn test_exposure_steps() {
+        let des = SensorDescriptor {
+            pixel_clock_freq_mhz: 100.0,
+            pixel_periods_per_line: 1000,
+            line_periods_per_field: 12,
+            coarse_integration_time_min: 2,
+            coarse_integration_time_max_margin: 4,
+            ..Default::default()
+        };
+        // 行周期 10us，积分 2..=8 行。
+        let steps = exposure_steps(&des).unwrap();
+        assert_eq!(steps.len(), 7);
+        assert!((steps[0] - 20e-6).abs() < 1e-9);
+        assert!((steps[6] - 80e-6).abs() < 1e-9);
+
+        let bad = SensorDescriptor {
+            pixel_clock_freq_mhz: 0.0,
+            ..des
+        };
+        assert_eq!(exposure_steps(&bad), Err(XCamError::Unsupported));
+    }
+
+    #[test]
+    fn test_exposure_timing() {
+        let des = SensorDescriptor {
+            pixel_clock_freq_mhz: 100.0,
+            pixel_periods_per_line: 1000,
+            line_periods_per_field: 1125,
+            ..Default::default()
+        };
+        // 行周期 10us，帧长 1200 行，积分 200 行。
+        let t = ExposureTiming::new(Some(9), 200, 1000, 1200, Some(&des));
+        assert_eq!(t.frame_id, Some(9));
+        assert_eq!(t.exposure_start_lines, Some(1000));
+        assert_eq!(t.frame_length_lines, Some(1200));
+        assert_eq!(t.line_time_us, Some(10.0));
+        assert_eq!(t.integration_us, Some(2000.0));
+        assert_eq!(t.exposure_start_us, Some(10000.0));
+        assert_eq!(t.frame_length_us, Some(12000.0));
+
+        // 驱动未报告时取标称值。
+        let t = ExposureTiming::new(None, 200, 0, 0, Some(&des));
+        assert_eq!(t.line_length_pixels, Some(1000));
+        assert_eq!(t.frame_length_lines, Some(1125));
+        assert_eq!(t.exposure_start_lines, Some(925));
+
+        // 没有像素时钟时只有行数。
+        let t = ExposureTiming::new(None, 200, 1000, 1200, None);
+        assert_eq!(t.exposure_start_lines, Some(1000));
+        assert_eq!(t.line_time_us, None);
+        assert_eq!(t.exposure_start_us, None);
+        assert_eq!(
+            ExposureTiming::new(None, 0, 0, 0, None),
+            ExposureTiming::default()
+        );
+    }
+
+    #[test]
+    fn test_timing_history() {
+        let timing = |frame_id, integration_lines| {
+            ExposureTiming::new(Some(frame_id), integration_lines, 1000, 1200, None)
+        };
+        let mut history = TimingHistory::default();
+        history.record(ExposureTiming::default());
+        assert!(history.entries.is_empty());
+        history.record(timing(1, 100));
+        history.record(timing(1, 200));
+        assert_eq!(history.get(1), Some(timing(1, 200)));
+        assert_eq!(history.entries.len(), 1);
+        for id in 2..=EXPOSURE_TIMING_HISTORY as u32 + 1 {
+            history.record(timing(id, 300));
+        }
+        assert_eq!(history.entries.len(), EXPOSURE_TIMING_HISTORY);
+        assert_eq!(history.get(1), None);
+        assert_eq!(history.get(2), Some(timing(2, 300)));
+    }
+
+    #[test]
+    fn test_snap_exposure() {
+        use crate::mock::MockContext;
+        let steps = [0.001, 0.002, 0.004, 0.008];
+        assert_eq!(nearest_step(&steps, 0.0029), Some(0.002));
+        assert_eq!(nearest_step(&steps, 0.0031), Some(0.004));
+        assert_eq!(nearest_step(&steps, 0.003), Some(0.002));
+        assert_eq!(nearest_step(&steps, 0.0), Some(0.001));
+        assert_eq!(nearest_step(&steps, 1.0), Some(0.008));
+        assert_eq!(nearest_step(&steps, f32::NAN), None);
+        assert_eq!(nearest_step(&[], 0.01), None);
+
+        let ctx = MockContext::new();
+        assert_eq!(ctx.snap_exposure(0.005), 0.005);
+        ctx.state.borrow_mut().exposure_steps = Some(steps.to_vec());
+        assert_eq!(ctx.snap_exposure(0.005), 0.004);
+        assert_eq!(ctx.snap_exposure(0.007), 0.008);
+    }
+
+    #[test]
+    fn test_duration_exposure() {
+        use crate::mock::MockContext;
+        let secs = duration_to_secs(Duration::from_micros(8333));
+        assert!((secs - 0.008333).abs() < 1e-9);
+        assert_eq!(duration_to_secs(Duration::ZERO), 0.0);
+
+        let ctx = MockContext::new();
+        ctx.set_manual_exp_dur(Duration::from_micros(8333), 4.0)
+            .unwrap();
+        assert_eq!(ctx.state.borrow().manual_exp, (4.0, secs));
+        ctx.set_exp_time_range_dur(Duration::from_micros(100), Duration::from_millis(40))
+            .unwrap();
+        let (min, max) = ctx.state.borrow().exp_time_range;
+        assert!((min - 0.0001).abs() < 1e-9);
+        assert!((max - 0.04).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_exp_info_display() {
+        let info = ExpInfo {
+            gain: 4.0,
+            time: 1.0 / 120.0,
+            iso: 400,
+            ..Default::default()
+        };
+        assert_eq!(info.to_string(), "1/120s gain 4.0 ISO 400");
+        let info = ExpInfo {
+            gain: 64.0,
+            time: 2.0,
+            iso: 6400,
+            ..Default::default()
+        };
+        assert_eq!(info.to_string(), "2.0s gain 64.0 ISO 6400");
+        assert_eq!("Center".parse(), Ok(AeMeasAreaType::Center));
+    }
+
+    #[test]
+    fn test_ae_target_luma() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        ctx.set_exposure_compensation(1.0).unwrap();
+        for luma in [0, 40, 102, 255] {
+            ctx.set_ae_target_luma(luma).unwrap();
+            assert_eq!(ctx.get_ae_target_luma(), Ok(luma));
+        }
+        // 目标亮度与曝光补偿互不覆盖。
+        assert_eq!(ctx.get_exposure_compensation(), Ok(1.0));
+        ctx.state.borrow_mut().lin_exp_attr.0.SetPoint = 300.0;
+        assert_eq!(ctx.get_ae_target_luma(), Ok(255));
+        ctx.state.borrow_mut().lin_exp_attr.0.SetPoint = -3.0;
+        assert_eq!(ctx.get_ae_target_luma(), Ok(0));
+    }
+
+    #[test]
+    fn test_ae_convergence_region() {
+        let conv = AeConvergence {
+            steady_speed: 0.2,
+            fast_threshold_ev: 1.0,
+            fast_speed: 0.8,
+        };
+        // 稳态区中超过阈值才进入快速区，两个方向对称。
+        assert!(!conv.is_fast(0.0, false));
+        assert!(!conv.is_fast(1.0, false));
+        assert!(conv.is_fast(1.5, false));
+        assert!(conv.is_fast(-1.5, false));
+        // 快速区中降至阈值的一半以下才回到稳态区。
+        assert!(conv.is_fast(0.8, true));
+        assert!(!conv.is_fast(0.4, true));
+        assert!(!conv.is_fast(f32::NAN, true));
+        assert_eq!(conv.speed(true), 0.8);
+        assert_eq!(conv.speed(false), 0.2);
+
+        assert_eq!(luma_delta_ev(25.0, 100.0), 2.0);
+        assert_eq!(luma_delta_ev(200.0, 100.0), -1.0);
+        assert_eq!(luma_delta_ev(0.0, 4.0), 2.0);
+    }
+
+    #[test]
+    fn test_set_ae_convergence() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        let mut state = None;
+        let conv = AeConvergence {
+            steady_speed: 0.25,
+            fast_threshold_ev: 1.0,
+            fast_speed: 0.75,
+        };
+        set_ae_convergence_with(&ctx, &mut state, conv).unwrap();
+        assert_eq!(state.map(|s| s.conv), Some(conv));
+        assert_eq!(ctx.get_ae_speed(), Ok(0.25));
+
+        let param = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        for (steady, threshold, fast) in [
+            (0.0, 1.0, 0.5),
+            (0.5, 1.0, 1.5),
+            (0.5, 1.0, 0.25),
+            (0.25, 0.0, 0.5),
+            (0.25, AE_FAST_THRESHOLD_MAX_EV + 1.0, 0.5),
+            (f32::NAN, 1.0, 0.5),
+        ] {
+            let bad = AeConvergence {
+                steady_speed: steady,
+                fast_threshold_ev: threshold,
+                fast_speed: fast,
+            };
+            assert_eq!(set_ae_convergence_with(&ctx, &mut state, bad), param);
+        }
+        // 无效参数不影响已设置的参数。
+        assert_eq!(state.map(|s| s.conv), Some(conv));
+
+        ctx.set_ae_target_luma(100).unwrap();
+        ctx.state.borrow_mut().exp_info.mean_luma = 20.0;
+        assert_eq!(tick_ae_convergence(&ctx, &mut state), Ok(true));
+        assert_eq!(ctx.get_ae_speed(), Ok(0.75));
+        ctx.state.borrow_mut().exp_info.mean_luma = 60.0;
+        assert_eq!(tick_ae_convergence(&ctx, &mut state), Ok(false));
+        ctx.state.borrow_mut().exp_info.mean_luma = 95.0;
+        assert_eq!(tick_ae_convergence(&ctx, &mut state), Ok(true));
+        assert_eq!(ctx.get_ae_speed(), Ok(0.25));
+        assert_eq!(tick_ae_convergence(&ctx, &mut None), Ok(false));
+    }
+
+    #[test]
+    fn test_ae_deadband() {
+        use crate::mock::MockContext;
+        assert_eq!(deadband_to_tolerance(5, 50.0), 10.0);
+        assert_eq!(deadband_to_tolerance(0, 50.0), 0.0);
+        assert_eq!(deadband_to_tolerance(200, 50.0), 100.0);
+        assert_eq!(deadband_to_tolerance(200, 255.0), 64.0 / 255.0 * 100.0);
+        assert_eq!(tolerance_to_deadband(10.0, 50.0), 5);
+
+        let ctx = MockContext::new();
+        ctx.set_lin_exp_attr(
+            LinExpAttr::default()
+                .with_set_point(100.0)
+                .with_tolerance(20.0),
+        )
+        .unwrap();
+        for delta in [0, 3, 10, AE_DEADBAND_MAX] {
+            ctx.set_ae_deadband(delta).unwrap();
+            assert_eq!(ctx.get_ae_deadband(), Ok(delta));
+        }
+        ctx.set_ae_deadband(u8::MAX).unwrap();
+        assert_eq!(ctx.get_ae_deadband(), Ok(AE_DEADBAND_MAX));
+        // 进入收敛的容差不大于退出容差，目标亮度保持不变。
+        ctx.set_ae_deadband(5).unwrap();
+        let attr = ctx.state.borrow().lin_exp_attr;
+        assert_eq!(attr.0.ToleranceOut, 5.0);
+        assert_eq!(attr.0.ToleranceIn, 5.0);
+        assert_eq!(attr.0.SetPoint, 100.0);
+
+        // 死区放宽后恢复原进入容差。
+        ctx.set_ae_deadband(0).unwrap();
+        assert_eq!(ctx.state.borrow().lin_exp_attr.0.ToleranceIn, 0.0);
+        ctx.set_ae_deadband(30).unwrap();
+        let attr = ctx.state.borrow().lin_exp_attr;
+        assert_eq!(attr.0.ToleranceOut, 30.0);
+        assert_eq!(attr.0.ToleranceIn, 20.0);
+
+        // 在别处修改的进入容差作为新的原值。
+        ctx.set_lin_exp_attr(attr.with_tolerance_in(12.0)).unwrap();
+        ctx.set_ae_deadband(10).unwrap();
+        ctx.set_ae_deadband(30).unwrap();
+        assert_eq!(ctx.state.borrow().lin_exp_attr.0.ToleranceIn, 12.0);
+
+        // 死区不超过目标亮度。
+        ctx.set_ae_target_luma(40).unwrap();
+        ctx.set_ae_deadband(60).unwrap();
+        assert_eq!(ctx.get_ae_deadband(), Ok(40));
+    }
+
+    #[test]
+    fn test_apply_manual_gains() {
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        apply_manual_gains(&mut sw_attr, 4.0, 2.0, 0.02);
+        assert_eq!(sw_attr.AecOpType, ffi::RKAiqOPMode_e::RK_AIQ_OP_MODE_MANUAL);
+        let linear = &sw_attr.stManual.LinearAE;
+        assert!(linear.ManualTimeEn && linear.ManualGainEn && linear.ManualIspDgainEn);
+        assert_eq!(linear.TimeValue, 0.02);
+        assert_eq!((linear.GainValue, linear.IspDGainValue), (4.0, 2.0));
+    }
+
+    #[test]
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn test_global_digital_gain_cap() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        assert_eq!(ctx.get_global_digital_gain_cap(), Ok(None));
+        assert_eq!(
+            ctx.set_global_digital_gain_cap(0.5),
+            Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE
+            ))
+        );
+        assert_eq!(
+            ctx.set_global_digital_gain_cap(f32::NAN),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        assert_eq!(ctx.get_global_digital_gain_cap(), Ok(None));
+        assert_eq!(ctx.set_global_digital_gain_cap(2.0), Ok(()));
+        assert_eq!(ctx.get_global_digital_gain_cap(), Ok(Some(2.0)));
+        assert_eq!(ctx.set_global_digital_gain_cap(1.0), Ok(()));
+        assert_eq!(ctx.get_global_digital_gain_cap(), Ok(Some(1.0)));
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    #[test]
+    fn test_apply_digital_gain_cap() {
+        let ranges = || Ok(((0.0001, 0.033), (1.0, 16.0)));
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        assert_eq!(digital_gain_cap_of(&sw_attr), None);
+
+        // 无效上限不读取范围，也不修改属性。
+        for cap in [0.5, ISP_DGAIN_MAX * 2.0, f32::NAN] {
+            let r = apply_digital_gain_cap(&mut sw_attr, cap, || -> XCamResult<_> {
+                panic!("ranges read for an invalid cap")
+            });
+            assert!(r.is_err());
+            assert_eq!(digital_gain_cap_of(&sw_attr), None);
+        }
+
+        // 首次设置时以当前范围填充高级范围。
+        assert_eq!(apply_digital_gain_cap(&mut sw_attr, 4.0, ranges), Ok(()));
+        let range = &sw_attr.stAdvanced.SetLinAeRange;
+        assert_eq!(
+            (range.stExpTimeRange.Min, range.stExpTimeRange.Max),
+            (0.0001, 0.033)
+        );
+        assert_eq!((range.stGainRange.Min, range.stGainRange.Max), (1.0, 16.0));
+        assert_eq!(
+            (range.stIspDGainRange.Min, range.stIspDGainRange.Max),
+            (1.0, 4.0)
+        );
+        assert_eq!(digital_gain_cap_of(&sw_attr), Some(4.0));
+
+        // 已启用时不再读取范围，下限不超过上限。
+        sw_attr.stAdvanced.SetLinAeRange.stIspDGainRange.Min = 2.0;
+        let r = apply_digital_gain_cap(&mut sw_attr, 1.5, || -> XCamResult<_> {
+            panic!("ranges read twice")
+        });
+        assert_eq!(r, Ok(()));
+        let range = &sw_attr.stAdvanced.SetLinAeRange.stIspDGainRange;
+        assert_eq!((range.Min, range.Max), (1.5, 1.5));
+        assert_eq!(sw_attr.stAdvanced.SetLinAeRange.stGainRange.Max, 16.0);
+
+        // 读取范围失败时不启用高级范围。
+        let mut sw_attr = ffi::Uapi_ExpSwAttrV2_t::default();
+        assert_eq!(
+            apply_digital_gain_cap(&mut sw_attr, 2.0, || Err(XCamError::Busy)),
+            Err(XCamError::Busy)
+        );
+        assert_eq!(digital_gain_cap_of(&sw_attr), None);
+    }
+
+    #[test]
+    fn test_ae_weights() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        let (cols, rows) = ae_grid_dimensions();
+        assert_eq!(ctx.get_ae_weights().map(|w| w.len()), Ok(cols * rows));
+
+        let mut weights = vec![1; cols * rows];
+        weights[rows / 2 * cols + cols / 2] = AE_WEIGHT_MAX;
+        assert_eq!(ctx.set_ae_weights(&weights), Ok(()));
+        assert_eq!(ctx.get_ae_weights(), Ok(weights.clone()));
+
+        assert_eq!(
+            ctx.set_ae_weights(&weights[1..]),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        weights[0] = AE_WEIGHT_MAX + 1;
+        assert_eq!(
+            ctx.set_ae_weights(&weights),
+            Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE
+            ))
+        );
+        assert_eq!(ctx.get_ae_weights().map(|w| w[0]), Ok(1));
+    }
+
+    #[test]
+    fn test_rasterize_regions() {
+        let (cols, rows) = (4, 4);
+        let mut grid = [0u8; 16];
+        rasterize_regions(&[], 1.0, cols, rows, &mut grid);
+        assert_eq!(grid, [AE_BASE_WEIGHT; 16]);
+
+        // 完全覆盖第 0 行第 1 格，半覆盖第 2 格。
+        let roi = Roi {
+            x: 0.25,
+            y: 0.0,
+            width: 0.375,
+            height: 0.25,
+        };
+        rasterize_regions(&[roi], 1.0, cols, rows, &mut grid);
+        assert_eq!(grid[..4], [1, AE_WEIGHT_MAX, 17, 1]);
+        assert!(grid[4..].iter().all(|&w| w == AE_BASE_WEIGHT));
+        rasterize_regions(&[roi], 0.5, cols, rows, &mut grid);
+        assert_eq!(grid[..4], [1, 17, 9, 1]);
+
+        // 重叠区域取覆盖比例最大者，不叠加。
+        let inner = Roi {
+            x: 0.5,
+            y: 0.0,
+            width: 0.0625,
+            height: 0.25,
+        };
+        rasterize_regions(&[inner, roi, roi], 1.0, cols, rows, &mut grid);
+        assert_eq!(grid[..4], [1, AE_WEIGHT_MAX, 17, 1]);
+
+        // 超出画面的部分被裁掉。
+        let outside = Roi {
+            x: 0.75,
+            y: 0.75,
+            width: 2.0,
+            height: 2.0,
+        };
+        rasterize_regions(&[outside], 1.0, cols, rows, &mut grid);
+        assert_eq!(grid[15], AE_WEIGHT_MAX);
+        assert_eq!(grid[..15], [AE_BASE_WEIGHT; 15]);
+    }
+
+    #[test]
+    fn test_priority_regions() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        let (cols, rows) = ae_grid_dimensions();
+        let face = Roi::from_pixels(640, 360, 640, 360, (1920, 1080));
+        apply_priority_regions(&ctx, &[face], 1.0).unwrap();
+        let weights = ctx.state.borrow().ae_weights.clone();
+        assert_eq!(weights[rows / 2 * cols + cols / 2], AE_WEIGHT_MAX);
+        assert_eq!(weights[0], AE_BASE_WEIGHT);
+
+        let param = || Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        assert_eq!(apply_priority_regions(&ctx, &[face], 1.5), param());
+        let bad = Roi {
+            width: f32::NAN,
+            ..face
+        };
+        assert_eq!(apply_priority_regions(&ctx, &[bad], 1.0), param());
+
+        apply_priority_regions(&ctx, &[], 0.0).unwrap();
+        assert!(ctx
+            .state
+            .borrow()
+            .ae_weights
+            .iter()
+            .all(|&w| w == AE_BASE_WEIGHT));
+    }
+
+    #[test]
+    fn test_exposure_route() {
+        use crate::mock::{MockContext, MOCK_EXP_ROUTE_LEN};
+        let point = |time, gain| ExpRoutePoint {
+            time,
+            gain,
+            ratio: None,
+        };
+        let route = [point(0.01, 1.0), point(0.03, 1.0), point(0.03, 4.0)];
+        assert_eq!(check_exp_route(&route, 3), Ok(()));
+        assert_eq!(check_exp_route(&[], 3), Err(ExpRouteError::Empty));
+        assert_eq!(
+            check_exp_route(&route, 2),
+            Err(ExpRouteError::TooLong { len: 3, max: 2 })
+        );
+        let flat = [point(0.01, 2.0), point(0.02, 1.0)];
+        assert_eq!(
+            check_exp_route(&flat, 3),
+            Err(ExpRouteError::NotMonotonic { index: 1 })
+        );
+        let bad_ratio = [ExpRoutePoint {
+            ratio: Some((8.0, 4.0)),
+            ..point(0.01, 1.0)
+        }];
+        assert_eq!(
+            check_exp_route(&bad_ratio, 3),
+            Err(ExpRouteError::InvalidPoint { index: 0 })
+        );
+        assert_eq!(
+            check_exp_route(&[point(f32::NAN, 1.0)], 3),
+            Err(ExpRouteError::InvalidPoint { index: 0 })
+        );
+
+        let ctx = MockContext::new();
+        assert_eq!(ctx.get_exposure_route(), Err(XCamError::Unsupported));
+        ctx.set_exposure_route(&route).unwrap();
+        assert_eq!(ctx.get_exposure_route().unwrap(), route);
+        let long = vec![point(0.01, 1.0); MOCK_EXP_ROUTE_LEN + 1];
+        assert_eq!(
+            ctx.set_exposure_route(&long),
+            Err(ExpRouteError::TooLong {
+                len: MOCK_EXP_ROUTE_LEN + 1,
+                max: MOCK_EXP_ROUTE_LEN,
+            })
+        );
+    }
+
+    #[test]
+    fn test_ae_route() {
+        use crate::mock::MockContext;
+        let route = AeRoute::from_exposures(&[(0.01, 1.0), (0.03, 1.0), (0.03, 4.0)]);
+        let lv: Vec<f32> = route.lv().collect();
+        assert_eq!(lv[0], -(0.01f32).log2());
+        assert!(lv[0] > lv[1] && lv[1] > lv[2]);
+
+        let ctx = MockContext::new();
+        ctx.set_ae_route(&route).unwrap();
+        assert_eq!(ctx.get_ae_route().unwrap(), route);
+        assert_eq!(ctx.calls(), ["set_exposure_route", "get_exposure_route"]);
+
+        // 总曝光量回落、即光值未按从亮到暗排列的路径被拒绝。
+        let bad = AeRoute::from_exposures(&[(0.01, 1.0), (0.03, 4.0), (0.02, 2.0)]);
+        assert_eq!(
+            ctx.set_ae_route(&bad),
+            Err(ExpRouteError::NotMonotonic { index: 2 })
+        );
+        assert_eq!(ctx.calls().len(), 2);
+    }
+
+    #[test]
+    fn test_set_stats_window() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        let rect = |left, top, width, height| Rect {
+            left,
+            top,
+            width,
+            height,
+        };
+        let frame = (1920, 1080);
+        set_stats_window_with(&ctx, rect(480, 270, 960, 540), frame).unwrap();
+        set_stats_window_with(&ctx, rect(0, 0, 1920, 1080), frame).unwrap();
+        assert_eq!(ctx.get_ae_stats_window(), Ok(rect(0, 0, 1920, 1080)));
+
+        let param = XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM);
+        let out = XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE);
+        for (r, err) in [
+            (rect(0, 0, 0, 540), &param),
+            (rect(0, 0, 960, -1), &param),
+            (rect(-1, 0, 960, 540), &out),
+            (rect(961, 0, 960, 540), &out),
+            (rect(0, 541, 960, 540), &out),
+            (rect(i32::MAX, 0, 1, 1), &out),
+        ] {
+            assert_eq!(set_stats_window_with(&ctx, r, frame).as_ref(), Err(err));
+        }
+        // 越界的窗口不写入。
+        assert_eq!(
+            ctx.calls()
+                .iter()
+                .filter(|c| **c == "set_ae_stats_window")
+                .count(),
+            2
+        );
+        assert_eq!(ctx.get_ae_stats_window(), Ok(rect(0, 0, 1920, 1080)));
+    }
+
+    #[test]
+    fn test_exposure_value() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+        assert_eq!(exposure_value(1.0, 1.0, 100), 0.0);
+        // f/8、1/125 秒、ISO 100 约为 EV 13，“阳光 16” 法则 f/16、1/100 秒约为 EV 14.6。
+        assert!(close(exposure_value(1.0 / 125.0, 8.0, 100), 12.966));
+        assert!(close(exposure_value(0.01, 16.0, 100), 14.644));
+        // 光圈收小一档、曝光时间减半各加 1，ISO 加倍减 1。
+        assert!(close(exposure_value(1.0, 2f32.sqrt(), 100), 1.0));
+        assert!(close(exposure_value(0.5, 1.0, 100), 1.0));
+        assert!(close(exposure_value(1.0, 1.0, 200), -1.0));
+        assert!(close(
+            exposure_value(0.02, 1.0, 400),
+            exposure_to_lv(0.02, 4.0)
+        ));
+
+        assert_eq!(exposure_time_for_ev(0.0, 1.0, 100), 1.0);
+        for (time, fnumber, iso) in [(1.0 / 125.0, 8.0, 100), (0.03, 2.0, 1600), (2.0, 1.4, 50)] {
+            let ev = exposure_value(time, fnumber, iso);
+            let back = exposure_time_for_ev(ev, fnumber, iso);
+            assert!((back - time).abs() / time < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_sync_exposure_with() {
+        use crate::mock::MockContext;
+        let left = MockContext::new();
+        let right = MockContext::new();
+        left.state.borrow_mut().exp_info = ExpInfo {
+            gain: 4.0,
+            time: 0.01,
+            ..Default::default()
+        };
+        left.sync_exposure_with(&right).unwrap();
+        assert_eq!(right.state.borrow().manual_exp, (4.0, 0.01));
+        assert!(left.calls().iter().all(|c| !c.starts_with("set_")));
+
+        left.fail("query_exp_info");
+        assert!(left.sync_exposure_with(&right).is_err());
+        assert_eq!(right.calls(), ["set_manual_exp"]);
+    }
+
+    #[test]
+    fn test_oneshot_exposure() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        let mut pending = None;
+        let flash = Duration::from_micros(500);
+        assert!(begin_oneshot_exposure(&ctx, &mut pending, flash, 2.0).is_err());
+        assert_eq!(pending, None);
+
+        ctx.state.borrow_mut().frame_id = Some(10);
+        begin_oneshot_exposure(&ctx, &mut pending, flash, 2.0).unwrap();
+        assert_eq!(ctx.state.borrow().manual_exp, (2.0, 0.0005));
+        assert!(!tick_oneshot_exposure(&ctx, &mut pending).unwrap());
+        assert!(pending.is_some());
+
+        ctx.state.borrow_mut().frame_id = Some(11);
+        assert!(tick_oneshot_exposure(&ctx, &mut pending).unwrap());
+        assert_eq!(ctx.calls().last(), Some(&"set_exp_mode"));
+        assert_eq!(ctx.state.borrow().exp_mode, OpMode::Auto);
+        assert_eq!(pending, None);
+        assert!(!tick_oneshot_exposure(&ctx, &mut pending).unwrap());
+
+        // 原为手动曝光时以原增益与时间恢复；帧号回绕后到期，恢复失败时保留待恢复状态
+        {
+            let mut state = ctx.state.borrow_mut();
+            state.exp_mode = OpMode::Manual;
+            state.exp_info = ExpInfo {
+                gain: 4.0,
+                time: 0.02,
+                ..Default::default()
+            };
+            state.frame_id = Some(u32::MAX);
+        }
+        begin_oneshot_exposure(&ctx, &mut pending, flash, 2.0).unwrap();
+        ctx.fail("set_manual_exp");
+        ctx.state.borrow_mut().frame_id = Some(0);
+        assert!(tick_oneshot_exposure(&ctx, &mut pending).is_err());
+        assert_eq!(pending.unwrap().manual, Some((4.0, 0.02)));
+    }
+
+    #[test]
+    fn test_lock_ae_to_window() {
+        use crate::mock::MockContext;
+        let rect = |left, top, width, height| Rect {
+            left,
+            top,
+            width,
+            height,
+        };
+        // 1500x1500 的画面对应 15x15 的亮度图，每格 100 像素；左上角 3x3 格为暗处。
+        let mut data = vec![100; 225];
+        for y in 0..3 {
+            for x in 0..3 {
+                data[y * 15 + x] = 25;
+            }
+        }
+        let frame = (1500, 1500);
+        let ctx = MockContext::new();
+        {
+            let mut s = ctx.state.borrow_mut();
+            s.luma_map = Some(LumaMap::from_luma(15, 15, data));
+            s.exp_info.gain = 2.0;
+            s.exp_info.time = 0.01;
+        }
+        // 全图平均亮度 97，窗口亮度 25：曝光量放大 97/25 倍，曝光时间先达到上限 1/30 s，
+        // 其余由增益补足。
+        let (gain, time) = lock_ae_to_window_with(&ctx, rect(0, 0, 300, 300), frame).unwrap();
+        let total = 2.0 * 0.01 * 97.0 / 25.0;
+        assert_eq!(time, 1.0 / 30.0);
+        assert!((gain - total / time).abs() < 1e-4);
+        assert_eq!(ctx.state.borrow().manual_exp, (gain, time));
+
+        // 小于一格的窗口按所在的格测光。
+        assert_eq!(
+            lock_ae_to_window_with(&ctx, rect(50, 50, 10, 10), frame),
+            Ok((gain, time))
+        );
+
+        let calls = ctx.calls().len();
+        let param = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        let range = Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+        assert_eq!(
+            lock_ae_to_window_with(&ctx, rect(0, 0, 0, 10), frame),
+            param
+        );
+        assert_eq!(
+            lock_ae_to_window_with(&ctx, rect(0, 0, 10, 10), (0, 0)),
+            param
+        );
+        assert_eq!(
+            lock_ae_to_window_with(&ctx, rect(1400, 0, 200, 10), frame),
+            range
+        );
+        assert_eq!(
+            lock_ae_to_window_with(&ctx, rect(-1, 0, 10, 10), frame),
+            range
+        );
+        assert_eq!(ctx.calls().len(), calls);
+
+        // 曝光范围颠倒或无效时不修改曝光。
+        let manual = ctx.state.borrow().manual_exp;
+        ctx.state.borrow_mut().exp_time_range = (1.0 / 30.0, 0.0001);
+        assert_eq!(
+            lock_ae_to_window_with(&ctx, rect(0, 0, 300, 300), frame),
+            param
+        );
+        ctx.state.borrow_mut().exp_time_range = (0.0001, 1.0 / 30.0);
+        ctx.state.borrow_mut().exp_gain_range = (f32::NAN, 64.0);
+        assert_eq!(
+            lock_ae_to_window_with(&ctx, rect(0, 0, 300, 300), frame),
+            param
+        );
+        assert_eq!(ctx.state.borrow().manual_exp, manual);
+    }
+
+    #[test]
+    fn test_auto_framerate() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        let mut state = None;
+        let set_iso = |iso: u32| ctx.state.borrow_mut().exp_info.gain = iso as f32 / 100.0;
+        let fps = || ctx.state.borrow().frame_rate.fps;
+        assert_eq!(
+            enable_auto_framerate(&ctx, &mut state, 30.0, 10.0, 1600),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        assert!(!tick_auto_framerate(&ctx, &mut state).unwrap());
+
+        enable_auto_framerate(&ctx, &mut state, 10.0, 30.0, 1600).unwrap();
+        assert_eq!(fps(), 30);
+        // 明亮时保持最高帧率。
+        set_iso(400);
+        assert!(!tick_auto_framerate(&ctx, &mut state).unwrap());
+        assert_eq!(fps(), 30);
+
+        // 暗光下每次降低一步，直至最低帧率。
+        set_iso(1600);
+        assert!(tick_auto_framerate(&ctx, &mut state).unwrap());
+        assert_eq!(fps(), 29);
+        set_iso(3200);
+        for _ in 0..30 {
+            tick_auto_framerate(&ctx, &mut state).unwrap();
+        }
+        assert_eq!(fps(), 10);
+
+        // 降低帧率后 ISO 随曝光时间延长而下降，折算后仍为暗光，不回升。
+        set_iso(600);
+        assert!(!tick_auto_framerate(&ctx, &mut state).unwrap());
+        // 回差区间内保持。
+        set_iso(500);
+        assert!(!tick_auto_framerate(&ctx, &mut state).unwrap());
+        assert_eq!(fps(), 10);
+        // 光照恢复后提高。
+        set_iso(300);
+        assert!(tick_auto_framerate(&ctx, &mut state).unwrap());
+        assert_eq!(fps(), 11);
+
+        disable_auto_framerate(&ctx, &mut state).unwrap();
+        assert_eq!(fps(), 0);
+        assert!(state.is_none());
+        assert!(!tick_auto_framerate(&ctx, &mut state).unwrap());
+    }
+}
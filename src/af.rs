@@ -1,36 +1,719 @@
 //! 自动聚焦
 //!
 //! AF 模块的功能是指调整相机镜头，使被拍物成像清晰的过程。
+use super::capability::Capabilities;
+use super::clock::{Clock, SystemClock};
 use super::context::Context;
+use super::convert;
+use super::display::enum_str;
 use super::error::XCamError;
 use super::ffi;
-use super::types::{OpMode, XCamResult};
+use super::shared::SharedContext;
+use super::sysctl::SystemControl;
+use super::types::{IspStats, OpMode, XCamResult};
+use super::uapi::{uapi_call, uapi_get};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// AF 统计网格的行数。
+pub const AF_ZONE_ROWS: usize = 15;
+/// AF 统计网格的列数。
+pub const AF_ZONE_COLS: usize = 15;
+/// AF 区域权重的最大值。
+pub const AF_ZONE_WEIGHT_MAX: u8 = 32;
+
+/// 读取清晰度时等待 3A 统计的超时。
+pub const FOCUS_STATS_TIMEOUT: Duration = Duration::from_millis(100);
+/// 对焦评分取最大值的窗口长度，单位：帧。
+pub const FOCUS_METER_WINDOW: usize = 150;
+/// 对焦马达的原点码值，即无穷远端。
+pub const FOCUS_HOME_CODE: u16 = 0;
+/// 对焦马达回到原点后等待稳定的时间，覆盖全行程移动与 VCM 振荡衰减。
+pub const FOCUS_HOME_SETTLE: Duration = Duration::from_millis(50);
+
+/// AF 区域权重表，与 AF 统计网格一一对应，按行排列，第 0 行为画面顶部。
+pub type AfZoneWeights = [[u8; AF_ZONE_COLS]; AF_ZONE_ROWS];
+
+/// 检查 AF 区域权重表的取值。
+///
+/// 每个权重的范围为 [0, `AF_ZONE_WEIGHT_MAX`]，且不能全部为 0。
+pub(crate) fn check_af_zone_weights(weights: &AfZoneWeights) -> XCamResult<()> {
+    if weights.iter().flatten().any(|&w| w > AF_ZONE_WEIGHT_MAX) {
+        return Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+    }
+    if weights.iter().flatten().all(|&w| w == 0) {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    Ok(())
+}
+
+/// 变焦标定表，每项为 `(变焦马达码值, 焦距)`，焦距单位：毫米。
+pub type ZoomCalibration = [(u16, f32)];
+
+/// 检查变焦标定表：至少两项，码值与焦距均严格递增，焦距须为大于 0 的有限值。
+pub(crate) fn check_zoom_calibration(table: &ZoomCalibration) -> XCamResult<()> {
+    let valid = table.len() >= 2
+        && table.iter().all(|&(_, mm)| mm.is_finite() && mm > 0.0)
+        && table.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1);
+    if valid {
+        Ok(())
+    } else {
+        Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+    }
+}
+
+/// 按标定表将变焦码值线性插值为焦距，超出标定范围时取端点焦距。
+///
+/// `table` 须已通过 `check_zoom_calibration` 检查。
+pub fn zoom_code_to_focal_length(table: &ZoomCalibration, code: u16) -> f32 {
+    let i = table.partition_point(|&(c, _)| c <= code);
+    match i {
+        0 => table[0].1,
+        i if i == table.len() => table[i - 1].1,
+        i => {
+            let (c0, f0) = table[i - 1];
+            let (c1, f1) = table[i];
+            f0 + (f1 - f0) * f32::from(code - c0) / f32::from(c1 - c0)
+        }
+    }
+}
+
+/// 按标定表将焦距线性插值为最接近的变焦码值，超出标定范围时返回 `None`。
+///
+/// `table` 须已通过 `check_zoom_calibration` 检查。
+pub fn focal_length_to_zoom_code(table: &ZoomCalibration, mm: f32) -> Option<u16> {
+    let first = table.first()?;
+    let last = table.last()?;
+    if !(first.1..=last.1).contains(&mm) {
+        return None;
+    }
+    let i = table.partition_point(|&(_, f)| f < mm).max(1);
+    let (c0, f0) = table[i - 1];
+    let (c1, f1) = table[i];
+    let code = f32::from(c0) + f32::from(c1 - c0) * (mm - f0) / (f1 - f0);
+    Some(code.round() as u16)
+}
+
+/// 从 3A 统计中取出 AF 主窗口的清晰度值。
+///
+/// 本帧的 AF 统计无效时返回 `XCAM_RETURN_ERROR_FAILED`。
+pub(crate) fn af_sharpness(stats: &IspStats) -> XCamResult<u32> {
+    if !stats.af_stats_valid {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED));
+    }
+    Ok(stats.af_stats.roia_sharpness)
+}
+
+/// 一个描述 AF 统计网格清晰度的类型。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AfGridStats {
+    /// 网格行数。
+    pub rows: usize,
+    /// 网格列数。
+    pub cols: usize,
+    /// 每个区域的清晰度值，按行排列，第 0 行为画面顶部，长度为 `rows × cols`。
+    pub values: Vec<u32>,
+}
+
+/// 从 3A 统计中取出 AF 网格的清晰度值。
+///
+/// 本帧的 AF 统计无效时返回 `XCAM_RETURN_ERROR_FAILED`。
+pub(crate) fn af_grid(stats: &IspStats) -> XCamResult<AfGridStats> {
+    if !stats.af_stats_valid {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED));
+    }
+    Ok(AfGridStats {
+        rows: AF_ZONE_ROWS,
+        cols: AF_ZONE_COLS,
+        values: stats.af_stats.global_sharpness[..AF_ZONE_ROWS * AF_ZONE_COLS].to_vec(),
+    })
+}
+
+/// 将网格清晰度线性映射到 [0,255]，最大值映射为 255，可直接作为峰值对焦叠加层的强度。
+///
+/// 全部为 0 时返回全 0。
+pub fn normalize_grid(values: &[u32]) -> Vec<u8> {
+    let max = values.iter().copied().max().unwrap_or(0) as u64;
+    values
+        .iter()
+        .map(|&v| match max {
+            0 => 0,
+            max => (v as u64 * 255 / max) as u8,
+        })
+        .collect()
+}
+
+/// 一个将清晰度值归一化为对焦评分的类型。
+///
+/// 评分为当前清晰度与最近 `FOCUS_METER_WINDOW` 帧内最大清晰度之比，范围：[0,1]。
+/// 清晰度的绝对值随场景内容与亮度变化，比值更适合作为手动对焦的指示。
+#[derive(Clone, Debug)]
+pub struct FocusMeter {
+    history: VecDeque<u32>,
+    window: usize,
+}
+
+impl FocusMeter {
+    /// 创建一个最大值窗口为 `window` 帧的评分器，`window` 至少为 1。
+    pub fn new(window: usize) -> Self {
+        let window = window.max(1);
+        Self {
+            history: VecDeque::with_capacity(window),
+            window,
+        }
+    }
+
+    /// 记录一帧的清晰度并返回评分。
+    pub fn update(&mut self, sharpness: u32) -> f32 {
+        if self.history.len() == self.window {
+            self.history.pop_front();
+        }
+        self.history.push_back(sharpness);
+        match self.history.iter().max() {
+            Some(&max) if max > 0 => sharpness as f32 / max as f32,
+            _ => 0.0,
+        }
+    }
+
+    /// 清除历史，例如切换场景之后。
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+}
+
+impl Default for FocusMeter {
+    fn default() -> Self {
+        Self::new(FOCUS_METER_WINDOW)
+    }
+}
+
+/// 一个描述自动对焦搜索状态的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AfSearchState {
+    /// 尚未开始搜索或状态无效。
+    Invalid,
+    /// 正在搜索。
+    Running,
+    /// 搜索完成，镜头已停在对焦位置。
+    Done,
+}
+
+impl From<ffi::RKAIQ_AF_SEARCH_STATE> for AfSearchState {
+    fn from(val: ffi::RKAIQ_AF_SEARCH_STATE) -> Self {
+        use ffi::RKAIQ_AF_SEARCH_STATE::*;
+        match val {
+            RK_AIQ_AF_SEARCH_RUNNING => Self::Running,
+            RK_AIQ_AF_SEARCH_END => Self::Done,
+            v => {
+                if v != RK_AIQ_AF_SEARCH_INVAL {
+                    convert::unmapped("RKAIQ_AF_SEARCH_STATE", v);
+                }
+                Self::Invalid
+            }
+        }
+    }
+}
+
+enum_str!(AfSearchState, "AF search state" {
+    Invalid => "invalid",
+    Running => "running",
+    Done => "done",
+});
+
+/// 切换到手动对焦，将马达移动到 `FOCUS_HOME_CODE` 并以 `clock` 等待 `FOCUS_HOME_SETTLE`。
+pub(crate) fn home_focus_with<C, K>(ctx: &C, clock: &K) -> XCamResult<()>
+where
+    C: AutoFocus + ?Sized,
+    K: Clock + ?Sized,
+{
+    if ctx.get_focus_mode()? != OpMode::Manual {
+        ctx.set_focus_mode(OpMode::Manual)?;
+    }
+    ctx.set_focus_position(FOCUS_HOME_CODE)?;
+    clock.sleep(FOCUS_HOME_SETTLE);
+    Ok(())
+}
+
+/// 逐个对焦位置测量清晰度的迭代器，由 `AutoFocus::focus_sweep_iter` 创建。
+///
+/// 每次调用 `next` 时才移动镜头并读取清晰度，产生 `(码值, 清晰度)`。遇到错误时产生该错误，
+/// 之后迭代结束。提前丢弃迭代器时镜头停留在最后设置的位置，不会恢复到扫描前的位置。
+pub struct FocusSweep<'a, C> {
+    ctx: &'a C,
+    next: Option<u16>,
+    end: u16,
+    step: u16,
+}
+
+impl<C: AutoFocus + SystemControl> Iterator for FocusSweep<'_, C> {
+    type Item = XCamResult<(u16, u32)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let code = self.next.take()?;
+        if self.step == 0 {
+            return Some(Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM,
+            )));
+        }
+        let result = self
+            .ctx
+            .set_focus_position(code)
+            .and_then(|_| self.ctx.get_focus_sharpness())
+            .map(|sharpness| (code, sharpness));
+        if result.is_ok() {
+            self.next = if code <= self.end {
+                code.checked_add(self.step).filter(|&c| c <= self.end)
+            } else {
+                code.checked_sub(self.step).filter(|&c| c >= self.end)
+            };
+        }
+        Some(result)
+    }
+}
 
 pub trait AutoFocus {
     fn get_focus_mode(&self) -> XCamResult<OpMode>;
     fn set_focus_mode(&self, mode: OpMode) -> XCamResult<()>;
+
+    /// 获取 AF 区域权重表。
+    fn get_af_zone_weights(&self) -> XCamResult<AfZoneWeights>;
+
+    /// 设置 AF 区域权重表，权重越大的区域对对焦结果的影响越大。
+    ///
+    /// # Parameters
+    /// * `weights` - `AF_ZONE_ROWS` 行 `AF_ZONE_COLS` 列的权重表，
+    ///   每个权重的范围为 [0, `AF_ZONE_WEIGHT_MAX`]，且不能全部为 0。
+    fn set_af_zone_weights(&self, weights: &AfZoneWeights) -> XCamResult<()>;
+
+    /// 获取自动对焦的搜索状态。
+    fn get_af_search_state(&self) -> XCamResult<AfSearchState>;
+
+    /// 获取对焦马达的当前码值。
+    ///
+    /// 默认返回 `XCamError::Unsupported`。
+    fn get_focus_position(&self) -> XCamResult<u16> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 将对焦马达移动到码值 `code`，码值超出 SDK 的取值范围时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+    ///
+    /// 仅在手动对焦模式下生效。默认返回 `XCamError::Unsupported`。
+    fn set_focus_position(&self, code: u16) -> XCamResult<()> {
+        let _ = code;
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取 AF 统计中主窗口的清晰度值，与对焦模式无关，AF 关闭时同样有效。
+    ///
+    /// # Note
+    ///
+    /// 清晰度取自 `get_3a_stats`，会消耗统计队列中的一帧，最多阻塞 `FOCUS_STATS_TIMEOUT`。
+    fn get_focus_sharpness(&self) -> XCamResult<u32>
+    where
+        Self: SystemControl + Sized,
+    {
+        af_sharpness(&self.get_3a_stats(FOCUS_STATS_TIMEOUT)?)
+    }
+
+    /// 获取 AF 统计网格每个区域的清晰度，用于峰值对焦显示，与对焦模式无关。
+    ///
+    /// 未出流时返回 `XCAM_RETURN_ERROR_ORDER`。
+    ///
+    /// # Note
+    ///
+    /// 网格取自 `get_3a_stats`，会消耗统计队列中的一帧，最多阻塞 `FOCUS_STATS_TIMEOUT`。
+    fn get_af_grid_stats(&self) -> XCamResult<AfGridStats>
+    where
+        Self: SystemControl + Sized,
+    {
+        if !self.is_streaming() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER));
+        }
+        af_grid(&self.get_3a_stats(FOCUS_STATS_TIMEOUT)?)
+    }
+
+    /// 以步长 `step` 从码值 `start` 扫描到 `end`（含），返回逐步测量清晰度的迭代器，见 `FocusSweep`。
+    ///
+    /// `start` 大于 `end` 时反向扫描，`step` 为 0 时迭代器只产生 `XCAM_RETURN_ERROR_PARAM`。
+    /// 适合边扫描边显示清晰度曲线；扫描前须切换到手动对焦模式。
+    ///
+    /// # Note
+    ///
+    /// 每一步通过 `get_focus_sharpness` 读取清晰度，消耗统计队列中的一帧。
+    fn focus_sweep_iter(&self, start: u16, end: u16, step: u16) -> FocusSweep<'_, Self>
+    where
+        Self: SystemControl + Sized,
+    {
+        FocusSweep {
+            ctx: self,
+            next: Some(start),
+            end,
+            step,
+        }
+    }
+
+    /// 扫描全部对焦位置后返回 `(码值, 清晰度)` 列表，遇到第一个错误即返回，见 `focus_sweep_iter`。
+    fn focus_sweep(&self, start: u16, end: u16, step: u16) -> XCamResult<Vec<(u16, u32)>>
+    where
+        Self: SystemControl + Sized,
+    {
+        self.focus_sweep_iter(start, end, step).collect()
+    }
+
+    /// 获取对焦评分，即当前清晰度与近期最大清晰度之比，范围：[0,1]，见 `FocusMeter`。
+    ///
+    /// 用于手动对焦的对焦指示，每帧调用一次时窗口约为 `FOCUS_METER_WINDOW` 帧。
+    fn get_focus_score(&self) -> XCamResult<f32>;
 }
 
 impl AutoFocus for Context {
     fn get_focus_mode(&self) -> XCamResult<OpMode> {
         let mut mode: ffi::opMode_t = ffi::opMode_t::OP_AUTO;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_getFocusMode(
-                self.internal.as_ptr(),
-                &mut mode,
-            ))
-            .ok()
-            .map(|_| mode.into())
+        uapi_call!(ffi::rk_aiq_uapi2_getFocusMode(self, &mut mode)).map(|_| mode.into())
+    }
+
+    fn set_focus_mode(&self, mode: OpMode) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi2_setFocusMode(self, mode.into()))
+    }
+
+    fn get_focus_position(&self) -> XCamResult<u16> {
+        let code = uapi_get!(ffi::rk_aiq_uapi2_getFocusPosition(self) -> i16)?;
+        Ok(code.max(0) as u16)
+    }
+
+    fn set_focus_position(&self, code: u16) -> XCamResult<()> {
+        let code = i16::try_from(code)
+            .map_err(|_| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE))?;
+        uapi_call!(ffi::rk_aiq_uapi2_setFocusPosition(self, code))
+    }
+
+    fn get_af_zone_weights(&self) -> XCamResult<AfZoneWeights> {
+        let mut attr = ffi::rk_aiq_af_attrib_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_af_GetAttrib(self, &mut attr))?;
+        let mut weights = [[0u8; AF_ZONE_COLS]; AF_ZONE_ROWS];
+        for (row, src) in weights
+            .iter_mut()
+            .zip(attr.zoneWeight.chunks_exact(AF_ZONE_COLS))
+        {
+            row.copy_from_slice(src);
         }
+        Ok(weights)
+    }
+
+    fn set_af_zone_weights(&self, weights: &AfZoneWeights) -> XCamResult<()> {
+        check_af_zone_weights(weights)?;
+        let mut attr = ffi::rk_aiq_af_attrib_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_af_GetAttrib(self, &mut attr))?;
+        for (dst, src) in attr
+            .zoneWeight
+            .chunks_exact_mut(AF_ZONE_COLS)
+            .zip(weights.iter())
+        {
+            dst.copy_from_slice(src);
+        }
+        uapi_call!(ffi::rk_aiq_user_api2_af_SetAttrib(self, attr))
+    }
+
+    fn get_af_search_state(&self) -> XCamResult<AfSearchState> {
+        let mut result = ffi::rk_aiq_af_result_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_af_GetSearchResult(self, &mut result))
+            .map(|_| result.stat.into())
+    }
+
+    fn get_focus_score(&self) -> XCamResult<f32> {
+        let sharpness = self.get_focus_sharpness()?;
+        Ok(self.focus_meter.lock().unwrap().update(sharpness))
+    }
+}
+
+impl AutoFocus for SharedContext {
+    fn get_focus_mode(&self) -> XCamResult<OpMode> {
+        self.inner.get_focus_mode()
     }
 
     fn set_focus_mode(&self, mode: OpMode) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setFocusMode(
-                self.internal.as_ptr(),
-                mode.into(),
+        self.inner.set_focus_mode(mode)
+    }
+
+    fn get_focus_position(&self) -> XCamResult<u16> {
+        self.inner.get_focus_position()
+    }
+
+    fn set_focus_position(&self, code: u16) -> XCamResult<()> {
+        self.inner.set_focus_position(code)
+    }
+
+    fn get_af_zone_weights(&self) -> XCamResult<AfZoneWeights> {
+        self.inner.get_af_zone_weights()
+    }
+
+    fn set_af_zone_weights(&self, weights: &AfZoneWeights) -> XCamResult<()> {
+        self.inner.set_af_zone_weights(weights)
+    }
+
+    fn get_af_search_state(&self) -> XCamResult<AfSearchState> {
+        self.inner.get_af_search_state()
+    }
+
+    fn get_focus_score(&self) -> XCamResult<f32> {
+        self.inner.get_focus_score()
+    }
+}
+
+impl Context {
+    /// 将对焦马达移动到原点 `FOCUS_HOME_CODE` 并等待 `FOCUS_HOME_SETTLE`，用于上电后标定前
+    /// 使镜头处于已知位置。
+    ///
+    /// 为使码值生效会切换到手动对焦模式，返回后保持手动模式。传感器没有对焦马达（定焦模组）
+    /// 时返回 `XCamError::Unsupported`，见 `Capability::LensVcm`。
+    pub fn home_focus(&self) -> XCamResult<()> {
+        self.require(Capabilities::LENS_VCM)?;
+        home_focus_with(self, &SystemClock)
+    }
+
+    /// 设置变焦标定表，见 `ZoomCalibration`，须按码值升序排列。
+    pub fn set_zoom_calibration(&self, table: &ZoomCalibration) -> XCamResult<()> {
+        check_zoom_calibration(table)?;
+        *self.zoom_calib.lock().unwrap() = table.to_vec();
+        Ok(())
+    }
+
+    /// 将变焦马达移动到焦距 `mm` 对应的码值，单位：毫米。
+    ///
+    /// 未设置标定表时返回 `XCAM_RETURN_ERROR_ORDER`；`mm` 非有限值时返回
+    /// `XCAM_RETURN_ERROR_PARAM`，超出标定范围时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+    pub fn set_zoom_focal_length(&self, mm: f32) -> XCamResult<()> {
+        if !mm.is_finite() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        let code = {
+            let table = self.zoom_calib.lock().unwrap();
+            if table.is_empty() {
+                return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER));
+            }
+            focal_length_to_zoom_code(&table, mm)
+                .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE))?
+        };
+        uapi_call!(ffi::rk_aiq_uapi2_setOpZoomPosition(self, i32::from(code)))
+    }
+
+    /// 按变焦马达的当前码值获取焦距，单位：毫米。
+    ///
+    /// 未设置标定表时返回 `XCAM_RETURN_ERROR_ORDER`。
+    pub fn get_focal_length(&self) -> XCamResult<f32> {
+        let table = self.zoom_calib.lock().unwrap();
+        if table.is_empty() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER));
+        }
+        let mut pos = 0;
+        uapi_call!(ffi::rk_aiq_uapi2_getOpZoomPosition(self, &mut pos))?;
+        let code = pos.clamp(0, i32::from(u16::MAX)) as u16;
+        Ok(zoom_code_to_focal_length(&table, code))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockClock, MockContext};
+
+    #[test]
+    fn test_af_zone_weights_bottom_weighted() {
+        let mut weights = [[1u8; AF_ZONE_COLS]; AF_ZONE_ROWS];
+        for row in weights.iter_mut().skip(AF_ZONE_ROWS * 2 / 3) {
+            row[AF_ZONE_COLS / 3..AF_ZONE_COLS * 2 / 3].fill(AF_ZONE_WEIGHT_MAX);
+        }
+        let ctx = MockContext::new();
+        ctx.set_af_zone_weights(&weights).unwrap();
+        assert_eq!(ctx.get_af_zone_weights(), Ok(weights));
+    }
+
+    #[test]
+    fn test_focus_meter() {
+        let mut meter = FocusMeter::new(8);
+        assert_eq!(meter.update(0), 0.0);
+        // 越过对焦点后回调，评分逐步接近 1。
+        assert_eq!(meter.update(1000), 1.0);
+        let scores: Vec<f32> = [200, 400, 600, 800, 990]
+            .iter()
+            .map(|&s| meter.update(s))
+            .collect();
+        assert!(scores.windows(2).all(|w| w[0] < w[1]));
+        assert!((scores[4] - 0.99).abs() < 1e-6);
+        // 峰值移出窗口后以新的最大值归一化。
+        for _ in 0..8 {
+            meter.update(500);
+        }
+        assert_eq!(meter.update(500), 1.0);
+        meter.reset();
+        assert_eq!(meter.update(10), 1.0);
+    }
+
+    #[test]
+    fn test_focus_sharpness_from_stats() {
+        let ctx = MockContext::new();
+        let mut stats = IspStats::default();
+        stats.af_stats.roia_sharpness = 300;
+        ctx.state.borrow_mut().stats = Some(stats);
+        assert_eq!(
+            ctx.get_focus_sharpness(),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))
+        );
+        stats.af_stats_valid = true;
+        ctx.state.borrow_mut().stats = Some(stats);
+        assert_eq!(ctx.get_focus_score(), Ok(1.0));
+        stats.af_stats.roia_sharpness = 150;
+        ctx.state.borrow_mut().stats = Some(stats);
+        assert_eq!(ctx.get_focus_score(), Ok(0.5));
+        assert_eq!(ctx.get_focus_sharpness(), Err(XCamError::Timeout));
+    }
+
+    #[test]
+    fn test_focus_sweep_iter() {
+        let ctx = MockContext::new();
+        let stats_with = |sharpness| {
+            let mut stats = IspStats::default();
+            stats.af_stats_valid = true;
+            stats.af_stats.roia_sharpness = sharpness;
+            ctx.state.borrow_mut().stats = Some(stats);
+        };
+        let mut sweep = ctx.focus_sweep_iter(100, 0, 40);
+        stats_with(300);
+        assert_eq!(sweep.next(), Some(Ok((100, 300))));
+        // 镜头只在调用 `next` 时移动。
+        assert_eq!(ctx.get_focus_position(), Ok(100));
+        stats_with(500);
+        assert_eq!(sweep.next(), Some(Ok((60, 500))));
+        drop(sweep);
+        assert_eq!(ctx.get_focus_position(), Ok(60));
+        let moves = ctx
+            .calls()
+            .iter()
+            .filter(|c| **c == "set_focus_position")
+            .count();
+        assert_eq!(moves, 2);
+
+        // 读取失败后迭代结束。
+        let mut sweep = ctx.focus_sweep_iter(0, 100, 50);
+        assert_eq!(sweep.next(), Some(Err(XCamError::Timeout)));
+        assert_eq!(sweep.next(), None);
+        assert_eq!(
+            ctx.focus_sweep(0, 10, 0),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        stats_with(10);
+        assert_eq!(ctx.focus_sweep(7, 7, 5), Ok(vec![(7, 10)]));
+    }
+
+    #[test]
+    fn test_af_grid_stats() {
+        let ctx = MockContext::new();
+        let mut stats = IspStats::default();
+        stats.af_stats_valid = true;
+        stats.af_stats.global_sharpness[AF_ZONE_COLS + 2] = 80;
+        stats.af_stats.global_sharpness[0] = 40;
+        ctx.state.borrow_mut().stats = Some(stats);
+        assert_eq!(
+            ctx.get_af_grid_stats(),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))
+        );
+        ctx.state.borrow_mut().streaming = true;
+        let grid = ctx.get_af_grid_stats().unwrap();
+        assert_eq!((grid.rows, grid.cols), (AF_ZONE_ROWS, AF_ZONE_COLS));
+        assert_eq!(grid.values.len(), AF_ZONE_ROWS * AF_ZONE_COLS);
+        let overlay = normalize_grid(&grid.values);
+        assert_eq!(overlay[AF_ZONE_COLS + 2], 255);
+        assert_eq!(overlay[0], 127);
+        assert_eq!(overlay[1], 0);
+        assert_eq!(normalize_grid(&[0, 0]), vec![0, 0]);
+        assert!(normalize_grid(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_zoom_calibration() {
+        let table = [(0, 4.0), (100, 8.0), (300, 12.0)];
+        assert_eq!(check_zoom_calibration(&table), Ok(()));
+        let param = || Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        assert_eq!(check_zoom_calibration(&table[..1]), param());
+        assert_eq!(check_zoom_calibration(&[(100, 8.0), (0, 4.0)]), param());
+        assert_eq!(check_zoom_calibration(&[(0, 4.0), (100, 4.0)]), param());
+        assert_eq!(check_zoom_calibration(&[(0, 0.0), (100, 4.0)]), param());
+        assert_eq!(
+            check_zoom_calibration(&[(0, 4.0), (100, f32::NAN)]),
+            param()
+        );
+
+        assert_eq!(zoom_code_to_focal_length(&table, 0), 4.0);
+        assert_eq!(zoom_code_to_focal_length(&table, 50), 6.0);
+        assert_eq!(zoom_code_to_focal_length(&table, 200), 10.0);
+        assert_eq!(zoom_code_to_focal_length(&table, 1000), 12.0);
+        let table = [(20, 4.0), (100, 8.0)];
+        assert_eq!(zoom_code_to_focal_length(&table, 0), 4.0);
+    }
+
+    #[test]
+    fn test_focal_length_to_zoom_code() {
+        let table = [(0, 4.0), (100, 8.0), (300, 12.0)];
+        assert_eq!(focal_length_to_zoom_code(&table, 4.0), Some(0));
+        assert_eq!(focal_length_to_zoom_code(&table, 8.0), Some(100));
+        assert_eq!(focal_length_to_zoom_code(&table, 12.0), Some(300));
+        // 6.01 mm 对应 50.25，取最接近的码值 50。
+        assert_eq!(focal_length_to_zoom_code(&table, 6.01), Some(50));
+        assert_eq!(focal_length_to_zoom_code(&table, 9.0), Some(150));
+        assert_eq!(focal_length_to_zoom_code(&table, 3.9), None);
+        assert_eq!(focal_length_to_zoom_code(&table, 12.5), None);
+        assert_eq!(focal_length_to_zoom_code(&table, f32::NAN), None);
+        for code in [0, 37, 100, 211, 300] {
+            let mm = zoom_code_to_focal_length(&table, code);
+            assert_eq!(focal_length_to_zoom_code(&table, mm), Some(code));
+        }
+    }
+
+    #[test]
+    fn test_af_zone_weights_bounds() {
+        let mut weights = [[0u8; AF_ZONE_COLS]; AF_ZONE_ROWS];
+        assert_eq!(
+            check_af_zone_weights(&weights),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        weights[0][0] = AF_ZONE_WEIGHT_MAX + 1;
+        let ctx = MockContext::new();
+        assert_eq!(
+            ctx.set_af_zone_weights(&weights),
+            Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE
             ))
-            .ok()
+        );
+        assert_eq!(
+            ctx.state.borrow().af_zone_weights,
+            [[1; AF_ZONE_COLS]; AF_ZONE_ROWS]
+        );
+    }
+
+    #[test]
+    fn test_home_focus() {
+        let ctx = MockContext::new();
+        let clock = MockClock::new();
+        {
+            let mut s = ctx.state.borrow_mut();
+            s.focus_mode = OpMode::Auto;
+            s.focus_position = 300;
         }
+        home_focus_with(&ctx, &clock).unwrap();
+        assert_eq!(ctx.state.borrow().focus_mode, OpMode::Manual);
+        assert_eq!(ctx.state.borrow().focus_position, FOCUS_HOME_CODE);
+        assert_eq!(clock.elapsed(), FOCUS_HOME_SETTLE);
+        assert_eq!(
+            ctx.calls(),
+            ["get_focus_mode", "set_focus_mode", "set_focus_position"]
+        );
+
+        // 移动失败时不等待。
+        let clock = MockClock::new();
+        ctx.fail("set_focus_position");
+        assert!(home_focus_with(&ctx, &clock).is_err());
+        assert_eq!(clock.elapsed(), Duration::ZERO);
     }
 }
@@ -0,0 +1,111 @@
+//! 分析器状态
+//!
+//! 用于排查“3A 停止更新”一类问题：对比 ISP 统计信息的帧号与 3A 分析器最近输出结果的帧号，
+//! 分析器停滞时前者继续增长而后者保持不变。
+use super::context::Context;
+use super::sysctl::SystemControl;
+use std::time::Duration;
+
+/// 一个描述分析器分组单帧处理耗时的类型。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupLatency {
+    /// 分组名称。
+    pub name: String,
+    /// 最近一帧的处理耗时。
+    pub latency: Duration,
+}
+
+/// 一个描述 3A 分析器运行状态的类型。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnalyzerStatus {
+    /// 是否已启动。
+    pub running: bool,
+    /// 分析器最近输出结果的帧号，取自元数据回调，尚未收到回调时为 `None`。
+    pub last_frame_id: Option<u32>,
+    /// 采集端最新统计信息的帧号，统计队列为空或获取失败时为 `None`。
+    pub capture_frame_id: Option<u32>,
+    /// 各分组的单帧处理耗时。
+    ///
+    /// 目前各 SDK 版本的公开接口均不报告分组耗时，总为 `None`。
+    pub group_latency: Option<Vec<GroupLatency>>,
+}
+
+impl AnalyzerStatus {
+    /// 查询分析器状态。
+    ///
+    /// # Note
+    ///
+    /// 采集端帧号通过 `try_get_3a_stats` 获取，会从统计队列中取走一帧统计信息。
+    pub fn query<C: SystemControl>(ctx: &C) -> Self {
+        Self {
+            running: ctx.is_streaming(),
+            last_frame_id: ctx.get_frame_id(),
+            capture_frame_id: ctx.try_get_3a_stats().ok().map(|stats| stats.frame_id),
+            group_latency: None,
+        }
+    }
+
+    /// 分析器落后于采集端的帧数，任一帧号未知时返回 `None`。
+    ///
+    /// 帧号回绕时按回绕后的差值计算。
+    pub fn stale_frames(&self) -> Option<u32> {
+        match (self.capture_frame_id, self.last_frame_id) {
+            (Some(capture), Some(last)) => Some(capture.wrapping_sub(last)),
+            _ => None,
+        }
+    }
+
+    /// 分析器是否正常运行。
+    ///
+    /// 已启动且落后采集端不超过 `max_stale_frames` 帧时返回 `true`；
+    /// 任一帧号未知时无法判断，返回 `false`。
+    pub fn healthy(&self, max_stale_frames: u32) -> bool {
+        self.running
+            && self
+                .stale_frames()
+                .is_some_and(|stale| stale <= max_stale_frames)
+    }
+}
+
+impl Context {
+    /// 查询 3A 分析器状态，见 `AnalyzerStatus::query`。
+    pub fn analyzer_status(&self) -> AnalyzerStatus {
+        AnalyzerStatus::query(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+    use crate::types::IspStats;
+
+    #[test]
+    fn test_analyzer_status() {
+        let ctx = MockContext::new();
+        let status = AnalyzerStatus::query(&ctx);
+        assert!(!status.running);
+        assert_eq!(status.capture_frame_id, None);
+        assert_eq!(status.group_latency, None);
+        assert!(!status.healthy(u32::MAX));
+
+        ctx.state.borrow_mut().streaming = true;
+        ctx.state.borrow_mut().frame_id = Some(100);
+        ctx.state.borrow_mut().stats = Some(IspStats {
+            frame_id: 102,
+            ..Default::default()
+        });
+        let status = AnalyzerStatus::query(&ctx);
+        assert_eq!(status.stale_frames(), Some(2));
+        assert!(status.healthy(2));
+        assert!(!status.healthy(1));
+
+        let status = AnalyzerStatus {
+            running: true,
+            last_frame_id: Some(u32::MAX),
+            capture_frame_id: Some(1),
+            group_latency: None,
+        };
+        assert_eq!(status.stale_frames(), Some(2));
+    }
+}
@@ -2,26 +2,30 @@
 //!
 //! 通过图像信息计算当前环境亮度。
 use super::context::Context;
-use super::error::XCamError;
 use super::ffi;
+use super::shared::SharedContext;
 use super::types::{AsdAttrib, XCamResult};
+use super::uapi::uapi_call;
 
 /// 一个描述环境光强检测的契定。
 pub trait AmbientDetection {
     /// 获取当前环境亮度的计算结果。
+    ///
+    /// # Note
+    ///
+    /// 结果随每帧统计更新，更新期间调用会阻塞，最长约一个帧周期。
     fn get_asd_attrib(&self) -> XCamResult<AsdAttrib>;
 }
 
 impl AmbientDetection for Context {
     fn get_asd_attrib(&self) -> XCamResult<AsdAttrib> {
         let mut attr: AsdAttrib = Default::default();
-        unsafe {
-            XCamError::from(ffi::rk_aiq_user_api_asd_GetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()
-            .map(|_| attr)
-        }
+        uapi_call!(ffi::rk_aiq_user_api_asd_GetAttrib(self, &mut attr)).map(|_| attr)
+    }
+}
+
+impl AmbientDetection for SharedContext {
+    fn get_asd_attrib(&self) -> XCamResult<AsdAttrib> {
+        self.inner.get_asd_attrib()
     }
 }
@@ -0,0 +1,69 @@
+//! 属性构建
+//!
+//! 为包装 SDK 属性结构体的类型生成 `with_*` 方法。每个方法只修改对应字段并返回自身，
+//! 配合“读取—修改—写回”的接口使用，未指定的字段保持原值：
+//!
+//! ```ignore
+//! ctx.set_lin_exp_attr(ctx.get_lin_exp_attr()?.with_set_point(40.0).with_tolerance(5.0))?;
+//! ```
+
+/// 为形如 `struct Foo(ffi::foo_t)` 的包装类型生成 `with_*` 方法。
+///
+/// 每一项写作 `方法名: 类型 => 字段路径;`，字段路径相对于内部的 SDK 结构体。
+macro_rules! with_fields {
+    ($ty:ty { $( $(#[$meta:meta])* $name:ident: $fty:ty => $($field:ident).+; )* }) => {
+        impl $ty {
+            $(
+                $(#[$meta])*
+                #[must_use]
+                pub fn $name(mut self, value: $fty) -> Self {
+                    self.0.$($field).+ = value;
+                    self
+                }
+            )*
+        }
+    };
+}
+
+pub(crate) use with_fields;
+
+#[cfg(test)]
+mod tests {
+    #[derive(Copy, Clone, Debug, Default, PartialEq)]
+    struct Inner {
+        level: u32,
+        ratio: f32,
+    }
+
+    #[derive(Copy, Clone, Debug, Default, PartialEq)]
+    struct Raw {
+        enable: bool,
+        strength: u32,
+        inner: Inner,
+    }
+
+    #[derive(Copy, Clone, Debug, Default, PartialEq)]
+    struct Demo(Raw);
+
+    with_fields!(Demo {
+        with_strength: u32 => strength;
+        with_ratio: f32 => inner.ratio;
+    });
+
+    #[test]
+    fn test_with_fields_keeps_other_fields() {
+        let raw = Raw {
+            enable: true,
+            strength: 10,
+            inner: Inner {
+                level: 7,
+                ratio: 0.5,
+            },
+        };
+        let demo = Demo(raw).with_strength(20).with_ratio(1.5);
+        assert!(demo.0.enable);
+        assert_eq!(demo.0.strength, 20);
+        assert_eq!(demo.0.inner.level, 7);
+        assert_eq!(demo.0.inner.ratio, 1.5);
+    }
+}
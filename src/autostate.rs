@@ -0,0 +1,230 @@
+//! 自动算法状态的迁移
+//!
+//! 并排演示“ISP 自动”与“固定参数”时，需要把一个上下文中自动算法收敛后的结果一次复制到
+//! 另一个上下文作为手动参数。`Context::export_auto_state` 读取各算法当前选定的值，
+//! `Context::import_as_manual` 将对应模块切换为手动并写入这些值。
+//!
+//! | 项 | 读取 | 写入 |
+//! | --- | --- | --- |
+//! | 白平衡增益 | `get_awb_snapshot` | `set_manual_wb` |
+//! | CCM | `get_ccm_query_info` | `set_manual_ccm` |
+//! | 曝光 | `query_exp_info` | `set_manual_exp` |
+//! | 降噪强度 | `get_effective_nr`，仅 v4_0、v5_0 | 不写入 |
+//!
+//! 写入顺序即上表顺序：自动 CCM 随白平衡色温插值，先固定白平衡才能使 CCM 的取值与导出时一致。
+//!
+//! 降噪强度只导出、不写入，导入时报告为 `FieldStatus::Unsupported`：`get_effective_nr` 读取的是
+//! 插值后的最终强度，而 `set_ms_nr_strength`、`set_mt_nr_strength` 设置的是作用于插值结果的
+//! 缩放，SDK 没有直接写入最终强度的接口。自动降噪随曝光增益对应的 ISO 插值，曝光固定后
+//! 两个上下文使用同一份标定时插值结果相同。
+use super::ae::AutoExposure;
+use super::awb::AutoWhiteBalance;
+use super::ccm::{Ccm, ColorCorrection};
+use super::context::Context;
+use super::error::XCamError;
+use super::nr::{NoiseRemoval, NrState};
+use super::types::{WbGain, XCamResult};
+
+/// 一个描述 `AutoState` 各项的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AutoStateField {
+    WbGain,
+    Ccm,
+    Exposure,
+    Nr,
+}
+
+/// 一个描述单项导入结果的枚举。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldStatus {
+    /// 已切换为手动并写入。
+    Applied,
+    /// 导出时该项缺失，未修改。
+    Missing,
+    /// 当前版本或 SDK 接口不支持写入该项，未修改。
+    Unsupported,
+}
+
+/// 自动算法当前选定的参数，见模块说明。
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AutoState {
+    /// 白平衡增益。
+    pub wb_gain: Option<WbGain>,
+    /// 当前生效的 CCM。
+    pub ccm: Option<Ccm>,
+    /// 曝光 `(增益, 曝光时间)`，参数顺序与 `set_manual_exp` 相同。
+    pub exposure: Option<(f32, f32)>,
+    /// 降噪强度。
+    pub nr: Option<NrState>,
+    /// 读取失败而缺失的项及其错误，当前版本不支持时为 `XCamError::Unsupported`。
+    pub missing: Vec<(AutoStateField, XCamError)>,
+}
+
+/// 读取 `ctx` 的自动算法状态，单项读取失败时记录在 `missing` 中。
+pub(crate) fn export_auto_state<C>(ctx: &C) -> AutoState
+where
+    C: AutoWhiteBalance + ColorCorrection + AutoExposure + NoiseRemoval + ?Sized,
+{
+    fn read<T>(
+        missing: &mut Vec<(AutoStateField, XCamError)>,
+        field: AutoStateField,
+        res: XCamResult<T>,
+    ) -> Option<T> {
+        res.map_err(|e| missing.push((field, e))).ok()
+    }
+
+    let mut missing = Vec::new();
+    let m = &mut missing;
+    let wb_gain = read(
+        m,
+        AutoStateField::WbGain,
+        ctx.get_awb_snapshot().map(|s| s.gain),
+    );
+    let ccm = read(
+        m,
+        AutoStateField::Ccm,
+        ctx.get_ccm_query_info().map(|i| i.ccm),
+    );
+    let exposure = read(
+        m,
+        AutoStateField::Exposure,
+        ctx.query_exp_info().map(|i| (i.gain, i.time)),
+    );
+    let nr = read(m, AutoStateField::Nr, ctx.get_effective_nr());
+    AutoState {
+        wb_gain,
+        ccm,
+        exposure,
+        nr,
+        missing,
+    }
+}
+
+/// 按模块说明的顺序将 `state` 作为手动参数写入 `ctx`，返回各项的结果。
+///
+/// 返回 `XCamError::Unsupported` 的项跳过；其余错误立即返回，此前已写入的项保持手动。
+pub(crate) fn import_as_manual<C>(
+    ctx: &C,
+    state: &AutoState,
+) -> XCamResult<Vec<(AutoStateField, FieldStatus)>>
+where
+    C: AutoWhiteBalance + ColorCorrection + AutoExposure + NoiseRemoval + ?Sized,
+{
+    fn apply<T>(val: Option<T>, f: impl FnOnce(T) -> XCamResult<()>) -> XCamResult<FieldStatus> {
+        let Some(val) = val else {
+            return Ok(FieldStatus::Missing);
+        };
+        match f(val) {
+            Ok(()) => Ok(FieldStatus::Applied),
+            Err(XCamError::Unsupported) => Ok(FieldStatus::Unsupported),
+            Err(e) => Err(e),
+        }
+    }
+
+    let mut report = Vec::with_capacity(4);
+    report.push((
+        AutoStateField::WbGain,
+        apply(state.wb_gain, |gain| ctx.set_manual_wb(gain).map(|_| ()))?,
+    ));
+    report.push((
+        AutoStateField::Ccm,
+        apply(state.ccm, |ccm| ctx.set_manual_ccm(&ccm))?,
+    ));
+    report.push((
+        AutoStateField::Exposure,
+        apply(state.exposure, |(gain, time)| {
+            ctx.set_manual_exp(gain, time)
+        })?,
+    ));
+    // 降噪的设置接口只接受缩放，无法写入导出的最终强度，见模块说明。
+    report.push((
+        AutoStateField::Nr,
+        apply(state.nr, |_| Err(XCamError::Unsupported))?,
+    ));
+    Ok(report)
+}
+
+impl Context {
+    /// 读取自动算法当前选定的白平衡增益、CCM、曝光与降噪强度，见模块说明。
+    ///
+    /// 各项依次读取，并非同一帧的快照；读取失败的项为 `None` 并记录在 `AutoState::missing` 中。
+    pub fn export_auto_state(&self) -> AutoState {
+        export_auto_state(self)
+    }
+
+    /// 将各模块切换为手动并写入 `state` 中的值，返回各项的结果，见模块说明。
+    ///
+    /// 缺失的项不修改；不支持写入的项（包括降噪强度）跳过并报告为 `FieldStatus::Unsupported`。
+    /// 其余错误立即返回，此前已写入的模块保持手动。
+    pub fn import_as_manual(
+        &self,
+        state: &AutoState,
+    ) -> XCamResult<Vec<(AutoStateField, FieldStatus)>> {
+        import_as_manual(self, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi;
+    use crate::mock::MockContext;
+    use crate::types::{OpMode, Strength};
+
+    #[test]
+    fn test_export_import_auto_state() {
+        let auto = MockContext::new();
+        {
+            let mut s = auto.state.borrow_mut();
+            s.wb_gain.rgain = 1.7;
+            s.exp_info.gain = 4.0;
+            s.exp_info.time = 0.02;
+            s.nr_status.spatial = (true, Strength::new(30).unwrap());
+        }
+        auto.fail("get_ccm_query_info");
+        let state = export_auto_state(&auto);
+        assert_eq!(state.wb_gain.unwrap().rgain, 1.7);
+        assert_eq!(state.exposure, Some((4.0, 0.02)));
+        assert_eq!(state.ccm, None);
+        assert_eq!(
+            state.missing,
+            [(
+                AutoStateField::Ccm,
+                XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED)
+            )]
+        );
+
+        let manual = MockContext::new();
+        let report = import_as_manual(&manual, &state).unwrap();
+        assert_eq!(
+            report,
+            [
+                (AutoStateField::WbGain, FieldStatus::Applied),
+                (AutoStateField::Ccm, FieldStatus::Missing),
+                (AutoStateField::Exposure, FieldStatus::Applied),
+                (AutoStateField::Nr, FieldStatus::Unsupported),
+            ]
+        );
+        let s = manual.state.borrow();
+        assert_eq!(s.wb_mode, OpMode::Manual);
+        assert_eq!(s.wb_gain.rgain, 1.7);
+        assert_eq!(s.manual_exp, (4.0, 0.02));
+        assert_eq!(s.manual_ccm, None);
+        // 降噪保持自动，不写入任何强度。
+        assert_eq!(s.nr_mode, OpMode::Auto);
+        assert!(!manual
+            .calls()
+            .iter()
+            .any(|c| c.starts_with("set_") && c.contains("nr")));
+    }
+
+    #[test]
+    fn test_import_as_manual_stops_on_error() {
+        let state = export_auto_state(&MockContext::new());
+        let manual = MockContext::new();
+        manual.fail("set_manual_exp");
+        assert!(import_as_manual(&manual, &state).is_err());
+        // 曝光之前的项已写入，之后的项未修改。
+        assert!(manual.calls().contains(&"set_manual_ccm"));
+    }
+}
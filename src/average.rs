@@ -0,0 +1,171 @@
+//! 统计信息的滑动平均
+//!
+//! 单帧 3A 统计噪声较大。`Context::set_stats_averaging_window` 设置窗口后，`get_3a_stats`
+//! 以及基于它的 `get_awb_region_stats`、`get_af_grid_stats` 等接口返回最近若干帧的平均值。
+//!
+//! 这是本库内部的软件平均，不修改 ISP 硬件统计的配置：每次调用 `get_3a_stats` 取得的一帧
+//! 放入环形缓冲区，返回缓冲区内各帧的平均。只有调用时取得的帧参与平均，因此需要按帧
+//! 持续调用；调用间隔较长时，窗口覆盖的时间相应变长。
+//!
+//! 参与平均的是本库解读的字段：AF 主窗口清晰度与网格清晰度、AWB 网格各区域的 RGB 累加值；
+//! 本帧统计无效的部分不放入缓冲区，也不修改。其余字段（包括 AE 统计）保持最新一帧的值。
+use super::af::{AF_ZONE_COLS, AF_ZONE_ROWS};
+use super::awb;
+use super::context::Context;
+use super::types::IspStats;
+use std::collections::VecDeque;
+
+/// 一个按元素计算最近若干组数值平均的环形缓冲区。
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StatsWindow {
+    frames: VecDeque<Vec<f64>>,
+    window: usize,
+}
+
+impl StatsWindow {
+    /// 设置窗口长度，0 与 1 均表示不平均；缩短时丢弃较旧的数据。
+    pub(crate) fn set_window(&mut self, window: usize) {
+        self.window = window;
+        while self.frames.len() > window.max(1) {
+            self.frames.pop_front();
+        }
+    }
+
+    /// 记录一组数值，返回窗口内各组按元素的平均。
+    ///
+    /// 长度与已记录的数据不同时（例如统计格式改变）先清空缓冲区。
+    pub(crate) fn push(&mut self, values: Vec<f64>) -> Vec<f64> {
+        if self.frames.front().is_some_and(|f| f.len() != values.len()) {
+            self.frames.clear();
+        }
+        if self.frames.len() >= self.window.max(1) {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(values);
+        let n = self.frames.len() as f64;
+        let mut sum = vec![0.0; self.frames[0].len()];
+        for frame in &self.frames {
+            for (s, v) in sum.iter_mut().zip(frame) {
+                *s += v;
+            }
+        }
+        sum.iter_mut().for_each(|s| *s /= n);
+        sum
+    }
+}
+
+/// `Context` 中 `get_3a_stats` 的平均状态。
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StatsAveraging {
+    window: usize,
+    af: StatsWindow,
+    awb: StatsWindow,
+}
+
+impl StatsAveraging {
+    pub(crate) fn set_window(&mut self, window: usize) {
+        self.window = window;
+        self.af.set_window(window);
+        self.awb.set_window(window);
+    }
+
+    /// 记录一帧统计，窗口大于 1 时将参与平均的字段替换为平均值。
+    pub(crate) fn apply(&mut self, mut stats: IspStats) -> IspStats {
+        if self.window <= 1 {
+            return stats;
+        }
+        if stats.af_stats_valid {
+            let avg = self.af.push(af_values(&stats));
+            set_af_values(&mut stats, &avg);
+        }
+        if let Some(values) = awb::awb_block_values(&stats) {
+            let avg = self.awb.push(values);
+            awb::set_awb_block_values(&mut stats, &avg);
+        }
+        stats
+    }
+}
+
+const AF_GRID_LEN: usize = AF_ZONE_ROWS * AF_ZONE_COLS;
+
+/// AF 主窗口清晰度与网格清晰度。
+fn af_values(stats: &IspStats) -> Vec<f64> {
+    let af = &stats.af_stats;
+    std::iter::once(af.roia_sharpness)
+        .chain(af.global_sharpness[..AF_GRID_LEN].iter().copied())
+        .map(f64::from)
+        .collect()
+}
+
+fn set_af_values(stats: &mut IspStats, values: &[f64]) {
+    let af = &mut stats.af_stats;
+    af.roia_sharpness = values[0].round() as u32;
+    for (dst, v) in af.global_sharpness[..AF_GRID_LEN]
+        .iter_mut()
+        .zip(&values[1..])
+    {
+        *dst = v.round() as u32;
+    }
+}
+
+impl Context {
+    /// 设置 3A 统计的平均窗口为 `frames` 帧，0 或 1 关闭平均，见模块说明。
+    ///
+    /// 缩短窗口时丢弃较旧的帧，关闭时清空缓冲区。
+    pub fn set_stats_averaging_window(&self, frames: u8) {
+        let mut avg = self.stats_avg.lock().unwrap();
+        if frames <= 1 {
+            *avg = StatsAveraging::default();
+        } else {
+            avg.set_window(usize::from(frames));
+        }
+    }
+
+    /// 3A 统计的平均窗口，未开启时为 1。
+    pub fn get_stats_averaging_window(&self) -> u8 {
+        self.stats_avg.lock().unwrap().window.max(1) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_window() {
+        let mut w = StatsWindow::default();
+        w.set_window(3);
+        assert_eq!(w.push(vec![3.0, 0.0]), [3.0, 0.0]);
+        assert_eq!(w.push(vec![6.0, 3.0]), [4.5, 1.5]);
+        assert_eq!(w.push(vec![9.0, 6.0]), [6.0, 3.0]);
+        // 超出窗口后最旧的一帧移出。
+        assert_eq!(w.push(vec![12.0, 0.0]), [9.0, 3.0]);
+        w.set_window(2);
+        assert_eq!(w.push(vec![0.0, 0.0]), [6.0, 0.0]);
+        // 长度改变时重新开始。
+        assert_eq!(w.push(vec![1.0]), [1.0]);
+    }
+
+    #[test]
+    fn test_stats_averaging_af() {
+        let mut avg = StatsAveraging::default();
+        let frame = |sharpness: u32, valid: bool| {
+            let mut stats = IspStats::default();
+            stats.af_stats_valid = valid;
+            stats.af_stats.roia_sharpness = sharpness;
+            stats.af_stats.global_sharpness[0] = sharpness * 2;
+            stats
+        };
+        // 未开启时原样返回。
+        assert_eq!(avg.apply(frame(100, true)).af_stats.roia_sharpness, 100);
+
+        avg.set_window(2);
+        assert_eq!(avg.apply(frame(100, true)).af_stats.roia_sharpness, 100);
+        let out = avg.apply(frame(200, true));
+        assert_eq!(out.af_stats.roia_sharpness, 150);
+        assert_eq!(out.af_stats.global_sharpness[0], 300);
+        // 无效的帧不参与平均，也不修改。
+        assert_eq!(avg.apply(frame(7, false)).af_stats.roia_sharpness, 7);
+        assert_eq!(avg.apply(frame(300, true)).af_stats.roia_sharpness, 250);
+    }
+}
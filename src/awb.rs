@@ -3,10 +3,20 @@
 //! AWB 模块的功能是通过改变拍摄设备的色彩通道的增益，The function of the module is to change the gain of the color channel of the shooting device
 //！对色温环境所造成的颜色偏差和拍摄设备本身所固有的色彩通道增益的偏差进行统一补偿，Uniformly compensate for the color deviation caused by the color temperature environment and the deviation of the color channel gain inherent in the shooting equipment itself
 //！从而让获得的图像能正确反映物体的真实色彩。This allows the image obtained to correctly reflect the true color of the object.
-use super::context::Context;
+use super::context::{self, Context};
+use super::display::enum_str;
 use super::error::XCamError;
 use super::ffi;
-use super::types::{OpMode, WbGain, WbScene, XCamResult};
+use super::shared::SharedContext;
+use super::sysctl::SystemControl;
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+use super::types::string_from_c_chars;
+use super::types::{IspStats, OpMode, WbGain, WbScene, XCamResult};
+use super::uapi::{uapi_call, uapi_get};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 /// 一个描述自动白平衡的契定。A convention describing automatic white balance.
 pub trait AutoWhiteBalance {
@@ -22,6 +32,117 @@ pub trait AutoWhiteBalance {
     /// 解锁已被锁定的白平衡参数。Unlock locked white balance parameters
     fn unlock_awb(&self) -> XCamResult<()>;
 
+    /// 按范围锁定白平衡。Lock white balance within the given scope.
+    ///
+    /// `LockScope::Full` 等同于 `lock_awb`。各版本 SDK 的 AWB 属性均不支持只锁定增益，
+    /// `LockScope::Gains` 为模拟实现：读取当前增益后切换到手动模式并写入该增益，
+    /// 之后仍可通过 `get_awb_snapshot` 轮询查询信息中的色温作为元数据。
+    /// 写入增益失败时恢复自动模式。
+    ///
+    /// `LockScope::Full` is `lock_awb`. No SDK version supports locking only the gains
+    /// in the AWB attributes, so `LockScope::Gains` is emulated: the current gains are read,
+    /// the module is switched to manual mode and the gains written back, while the CT in the
+    /// query info can still be polled through `get_awb_snapshot` for metadata. Auto mode is
+    /// restored if writing the gains fails.
+    fn lock_awb_partial(&self, scope: LockScope) -> XCamResult<()> {
+        match scope {
+            LockScope::Full => self.lock_awb(),
+            LockScope::Gains => {
+                let gain = self.get_awb_snapshot()?.gain;
+                self.set_wb_mode(OpMode::Manual)?;
+                self.set_mwb_gain(gain).map_err(|e| {
+                    let _ = self.set_wb_mode(OpMode::Auto);
+                    e
+                })
+            }
+        }
+    }
+
+    /// 解除 `lock_awb_partial` 的锁定。Release a lock taken by `lock_awb_partial`.
+    ///
+    /// `LockScope::Full` 等同于 `unlock_awb`，`LockScope::Gains` 恢复自动模式。
+    /// `LockScope::Full` is `unlock_awb`; `LockScope::Gains` restores auto mode.
+    fn unlock_awb_partial(&self, scope: LockScope) -> XCamResult<()> {
+        match scope {
+            LockScope::Full => self.unlock_awb(),
+            LockScope::Gains => self.set_wb_mode(OpMode::Auto),
+        }
+    }
+
+    /// 获取自动白平衡允许的色温范围 `(min, max)`，单位：K；未限制时返回 `None`。
+    /// Get the CT range `(min, max)` in Kelvin AWB is restricted to, or `None` if unrestricted.
+    ///
+    /// 仅 v4_0、v5_0 的 AWB 属性含色温范围，其余版本返回 `XCamError::Unsupported`。
+    /// Only the v4_0 and v5_0 AWB attributes carry a CT range; other versions return
+    /// `XCamError::Unsupported`.
+    fn get_awb_ct_range(&self) -> XCamResult<Option<(u32, u32)>> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 限制自动白平衡估计的色温范围，用于避免彩色墙面等干扰光源。
+    /// Restrict the CT range AWB may estimate, e.g. to ignore colored walls.
+    ///
+    /// 首次设置时保存标定文件中的原始范围，供 `reset_awb_ct_range` 恢复。
+    /// The calibration defaults are captured on the first call for `reset_awb_ct_range`.
+    ///
+    /// # Parameters
+    /// * `min_k` - 色温下限，单位：K。Lower bound in Kelvin.
+    /// * `max_k` - 色温上限，须大于 `min_k`，范围见 `check_awb_ct_range`。
+    ///   Upper bound in Kelvin, greater than `min_k`; see `check_awb_ct_range`.
+    fn set_awb_ct_range(&self, min_k: u32, max_k: u32) -> XCamResult<()> {
+        check_awb_ct_range(min_k, max_k)?;
+        Err(XCamError::Unsupported)
+    }
+
+    /// 恢复首次调用 `set_awb_ct_range` 前的标定色温范围，未设置过时不做任何操作。
+    /// Restore the calibration CT range captured by the first `set_awb_ct_range`;
+    /// does nothing if it was never called.
+    fn reset_awb_ct_range(&self) -> XCamResult<()> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取标定文件中的自动白平衡色温范围 `(min, max)`，单位：K；标定未限制时返回 `None`。
+    /// Get the calibrated AWB CT range `(min, max)` in Kelvin, or `None` if unrestricted.
+    ///
+    /// 不受 `set_awb_ct_range` 影响。仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    /// Unaffected by `set_awb_ct_range`. Only v4_0 and v5_0 are supported; other versions
+    /// return `XCamError::Unsupported`.
+    fn get_awb_calib_ct_range(&self) -> XCamResult<Option<(u32, u32)>> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取自动白平衡的收敛速度，范围：[0,1]。Get the AWB adaptation speed, in [0,1].
+    ///
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    /// Only v4_0 and v5_0 are supported; other versions return `XCamError::Unsupported`.
+    fn get_awb_speed(&self) -> XCamResult<f32> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 设置自动白平衡的收敛速度，通过 AWB 属性的阻尼系数实现。
+    /// Set the AWB adaptation speed through the damping factor of the AWB attributes.
+    ///
+    /// 每帧向目标增益移动剩余差值的 `speed` 倍：1 为立即跟随，数值越小过渡越平滑，
+    /// 摇镜头时色彩不再突变，但光源真正变化时也需要更多帧才能收敛；0 等同冻结增益。
+    /// 与 `lock_awb` 的完全冻结不同，较小的速度仍会缓慢跟随光源变化。
+    /// Each frame covers `speed` of the remaining distance to the target gain: 1 follows
+    /// immediately, lower values give smoother transitions while panning at the cost of
+    /// taking more frames to settle after a real lighting change; 0 freezes the gains.
+    /// Unlike the hard freeze of `lock_awb`, a small speed still follows the light slowly.
+    ///
+    /// 与非模拟的 `Context::set_awb_max_step` 写入同一阻尼系数，后设置者生效：
+    /// 设置速度后 `get_awb_max_step` 不再报告非模拟的步长限制。
+    /// Writes the same damping factor as a non-emulated `Context::set_awb_max_step`; the
+    /// last call wins, and `get_awb_max_step` stops reporting the non-emulated limit.
+    ///
+    /// # Parameters
+    /// * `speed` - 收敛速度，超出 [0,1] 时取边界值，NaN 返回 `XCAM_RETURN_ERROR_PARAM`。
+    ///   Adaptation speed, clamped to [0,1]; NaN returns `XCAM_RETURN_ERROR_PARAM`.
+    fn set_awb_speed(&self, speed: f32) -> XCamResult<()> {
+        clamp_awb_speed(speed)?;
+        Err(XCamError::Unsupported)
+    }
+
     /// 获取白平衡场景。Get the white balance scene
     fn get_mwb_scene(&self) -> XCamResult<WbScene>;
 
@@ -34,114 +155,1424 @@ pub trait AutoWhiteBalance {
     /// 设置白平衡增益系数。Set the white balance gain coefficient.
     fn set_mwb_gain<T: Into<WbGain>>(&self, gain: T) -> XCamResult<()>;
 
+    /// 只设置 R、B 增益，Gr、Gb 增益保持当前值。
+    /// Set only the R and B gains, keeping the current Gr and Gb gains.
+    ///
+    /// 画面亮度主要由绿色通道决定。自动白平衡的绿色增益不一定为 1，直接以 G 为 1 写入手动增益
+    /// 会使画面整体变亮或变暗；本方法先以 `get_mwb_gain` 读取当前增益，保留其中的绿色增益，
+    /// 再与 `rgain`、`bgain` 一起以 `set_mwb_gain` 写入，切换到手动增益时亮度不变。
+    ///
+    /// Image brightness follows mostly the green channel. The auto white balance green gain
+    /// is not necessarily 1, so forcing G to 1 in a manual gain brightens or darkens the whole
+    /// image. The current gain is read with `get_mwb_gain`, its green gains are kept and
+    /// written back together with `rgain` and `bgain` through `set_mwb_gain`, so brightness
+    /// does not change.
+    ///
+    /// # Parameters
+    /// * `rgain`、`bgain` - 须为正的有限值，否则返回 `XCAM_RETURN_ERROR_PARAM`。
+    ///   Must be positive and finite, otherwise `XCAM_RETURN_ERROR_PARAM` is returned.
+    fn set_mwb_rb_gain(&self, rgain: f32, bgain: f32) -> XCamResult<()> {
+        if ![rgain, bgain].iter().all(|v| v.is_finite() && *v > 0.0) {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        let current = self.get_mwb_gain()?;
+        self.set_mwb_gain(WbGain {
+            rgain,
+            bgain,
+            ..current
+        })
+    }
+
+    /// 切换到手动白平衡并写入增益，返回读回的实际增益。
+    /// Switch to manual white balance, apply the gain and return the gain read back.
+    ///
+    /// 依次读取当前模式、切换到 `OpMode::Manual`、写入增益并读回。读取当前模式之后的任一步
+    /// 失败时恢复原来的模式并返回该错误；恢复失败的错误被忽略。原来已是手动模式时只恢复模式，
+    /// 已写入的增益不会回退。
+    ///
+    /// The current mode is read, the module switched to `OpMode::Manual`, the gain written
+    /// and read back. If any step after reading the mode fails, the prior mode is restored
+    /// and that error returned; an error from the restore itself is ignored. Only the mode is
+    /// rolled back: if the prior mode was already manual, a gain that was written stays.
+    fn set_manual_wb(&self, gain: WbGain) -> XCamResult<WbGain> {
+        let prior = self.get_wb_mode()?;
+        let apply = || {
+            self.set_wb_mode(OpMode::Manual)?;
+            self.set_mwb_gain(gain)?;
+            self.get_mwb_gain()
+        };
+        apply().map_err(|e| {
+            let _ = self.set_wb_mode(prior);
+            e
+        })
+    }
+
     /// 获取白平衡色温参数。Get the white balance color temperature parameters
     fn get_mwb_ct(&self) -> XCamResult<u32>;
 
     /// 设置白平衡色温参数。Set the white balance color temperature parameters.
     fn set_mwb_ct(&self, ct: u32) -> XCamResult<()>;
+
+    /// 获取 AWB 属性中手动增益的平滑生效标志。
+    /// Get the smooth-apply flag of the manual gain from the AWB attributes.
+    ///
+    /// 本库绑定的各版本 AWB 属性均未暴露该标志，默认返回 `XCamError::Unsupported`，
+    /// 此时 `Context::set_mwb_apply_mode` 由本库模拟平滑过渡。
+    /// None of the AWB attributes bound by this crate expose the flag, so the default
+    /// returns `XCamError::Unsupported` and `Context::set_mwb_apply_mode` emulates the ramp.
+    fn get_mwb_smooth_flag(&self) -> XCamResult<bool> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 设置 AWB 属性中手动增益的平滑生效标志，见 `get_mwb_smooth_flag`。
+    /// Set the smooth-apply flag of the manual gain; see `get_mwb_smooth_flag`.
+    fn set_mwb_smooth_flag(&self, _smooth: bool) -> XCamResult<()> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取白平衡状态快照。Get a snapshot of the white balance state.
+    ///
+    /// 默认实现依次读取工作模式、增益系数与色温，不保证三者来自同一帧。
+    /// The default implementation reads mode, gain and CT one after another and
+    /// does not guarantee they belong to the same frame.
+    fn get_awb_snapshot(&self) -> XCamResult<AwbSnapshot> {
+        Ok(AwbSnapshot {
+            mode: self.get_wb_mode()?,
+            gain: self.get_mwb_gain()?,
+            ct: self.get_mwb_ct()?,
+            frame_id: None,
+        })
+    }
+
+    /// 获取自动白平衡估计的主光源。Get the dominant illuminant estimated by auto white balance.
+    ///
+    /// 仅 v3_0 及以上版本的 SDK 报告光源估计结果，其余版本返回 `XCamError::Unsupported`。
+    /// Only SDK v3_0 and later report the estimate; other versions return
+    /// `XCamError::Unsupported`.
+    fn get_awb_illuminant(&self) -> XCamResult<IlluminantEstimate> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取 AWB 统计网格各区域的 RGB 累加值，与白平衡模式无关。
+    /// Get the RGB sums of each region of the AWB statistics grid, regardless of the WB mode.
+    ///
+    /// 未出流时返回 `XCAM_RETURN_ERROR_ORDER`；仅 isp_hw_v30、isp_hw_v31 支持，其余返回
+    /// `XCamError::Unsupported`。开启 `set_stats_averaging_window` 后返回平均值。
+    /// Returns `XCAM_RETURN_ERROR_ORDER` when not streaming; only isp_hw_v30 and isp_hw_v31
+    /// are supported, others return `XCamError::Unsupported`. Averaged once
+    /// `set_stats_averaging_window` is enabled.
+    ///
+    /// # Note
+    ///
+    /// 统计取自 `get_3a_stats`，会消耗统计队列中的一帧，最多阻塞 `AWB_STATS_TIMEOUT`。
+    /// The stats come from `get_3a_stats`, consume one frame from the stats queue and block
+    /// for at most `AWB_STATS_TIMEOUT`.
+    fn get_awb_region_stats(&self) -> XCamResult<AwbRegionStats>
+    where
+        Self: SystemControl + Sized,
+    {
+        if !self.is_streaming() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER));
+        }
+        awb_regions(&self.get_3a_stats(AWB_STATS_TIMEOUT)?)
+    }
+}
+
+/// `get_awb_region_stats` 等待统计的最长时间。How long `get_awb_region_stats` waits for stats.
+pub const AWB_STATS_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// AWB 统计网格的行数。Rows of the AWB statistics grid.
+pub const AWB_ZONE_ROWS: usize = 15;
+
+/// AWB 统计网格的列数。Columns of the AWB statistics grid.
+pub const AWB_ZONE_COLS: usize = 15;
+
+/// 一个描述 AWB 统计网格的类型。The AWB statistics grid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AwbRegionStats {
+    /// 网格行数。Grid rows.
+    pub rows: usize,
+    /// 网格列数。Grid columns.
+    pub cols: usize,
+    /// 每个区域的 `[R, G, B]` 累加值，按行排列，长度为 `rows × cols`。
+    /// `[R, G, B]` sums of each region in row order, `rows × cols` long.
+    pub values: Vec<[u64; 3]>,
+}
+
+/// 从 3A 统计中取出 AWB 网格，本帧的 AWB 统计无效时返回 `XCAM_RETURN_ERROR_FAILED`。
+/// Extract the AWB grid from the 3A stats; fails if this frame's AWB stats are invalid.
+#[cfg(any(feature = "isp_hw_v30", feature = "isp_hw_v31"))]
+pub(crate) fn awb_regions(stats: &IspStats) -> XCamResult<AwbRegionStats> {
+    if !stats.awb_stats_valid {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED));
+    }
+    let blocks = unsafe { &stats.awb_stats_v3x.blockResult };
+    Ok(AwbRegionStats {
+        rows: AWB_ZONE_ROWS,
+        cols: AWB_ZONE_COLS,
+        values: blocks[..AWB_ZONE_ROWS * AWB_ZONE_COLS]
+            .iter()
+            .map(|b| [b.Rvalue as u64, b.Gvalue as u64, b.Bvalue as u64])
+            .collect(),
+    })
+}
+
+#[cfg(not(any(feature = "isp_hw_v30", feature = "isp_hw_v31")))]
+pub(crate) fn awb_regions(_stats: &IspStats) -> XCamResult<AwbRegionStats> {
+    Err(XCamError::Unsupported)
+}
+
+/// 参与 `get_3a_stats` 平均的 AWB 网格数值，本帧无效或不支持时为 `None`。
+/// The AWB grid values averaged by `get_3a_stats`, `None` if invalid or unsupported.
+pub(crate) fn awb_block_values(stats: &IspStats) -> Option<Vec<f64>> {
+    let regions = awb_regions(stats).ok()?;
+    Some(regions.values.iter().flatten().map(|&v| v as f64).collect())
+}
+
+/// 将 `awb_block_values` 形式的数值写回 3A 统计。
+/// Write values in the `awb_block_values` layout back into the 3A stats.
+#[cfg(any(feature = "isp_hw_v30", feature = "isp_hw_v31"))]
+pub(crate) fn set_awb_block_values(stats: &mut IspStats, values: &[f64]) {
+    let blocks = unsafe { &mut stats.awb_stats_v3x.blockResult };
+    for (b, v) in blocks.iter_mut().zip(values.chunks_exact(3)) {
+        b.Rvalue = v[0].round() as _;
+        b.Gvalue = v[1].round() as _;
+        b.Bvalue = v[2].round() as _;
+    }
+}
+
+#[cfg(not(any(feature = "isp_hw_v30", feature = "isp_hw_v31")))]
+pub(crate) fn set_awb_block_values(_stats: &mut IspStats, _values: &[f64]) {}
+
+/// 一个描述白平衡锁定范围的枚举。The scope of a white balance lock.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LockScope {
+    /// 只锁定增益，色温估计继续更新。Lock the gains only and keep the CT estimate updating.
+    Gains,
+    /// 锁定全部白平衡参数。Lock all white balance parameters.
+    Full,
+}
+
+/// 一个描述标准光源的枚举。A standard illuminant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Illuminant {
+    /// 地平线光，约 2300K。Horizon light, about 2300K.
+    Horizon,
+    /// 白炽灯，约 2856K。Incandescent (CIE A), about 2856K.
+    A,
+    /// 冷白荧光灯，约 4100K。Cool white fluorescent, about 4100K.
+    Cwf,
+    /// 三基色荧光灯，约 4000K。Tri-phosphor fluorescent, about 4000K.
+    Tl84,
+    /// 水平日光，约 5000K。Horizon daylight, about 5000K.
+    D50,
+    /// 正午日光，约 6500K。Noon daylight, about 6500K.
+    D65,
+    /// 北方阴天日光，约 7500K。North sky daylight, about 7500K.
+    D75,
+    /// 标定文件中的其它光源。Another illuminant from the calibration file.
+    Other,
+}
+
+impl Illuminant {
+    /// 由标定文件中的光源名称得到光源，名称不区分大小写。
+    /// Map a calibration illuminant name, case-insensitively.
+    pub fn from_name(name: &str) -> Self {
+        match name.trim().to_ascii_uppercase().as_str() {
+            "HZ" | "H" | "HORIZON" => Self::Horizon,
+            "A" => Self::A,
+            "CWF" => Self::Cwf,
+            "TL84" | "TL84F" => Self::Tl84,
+            "D50" => Self::D50,
+            "D65" => Self::D65,
+            "D75" => Self::D75,
+            _ => Self::Other,
+        }
+    }
+}
+
+enum_str!(Illuminant, "illuminant" {
+    Horizon => "horizon",
+    A => "a",
+    Cwf => "cwf",
+    Tl84 => "tl84",
+    D50 => "d50",
+    D65 => "d65",
+    D75 => "d75",
+    Other => "other",
+});
+
+/// 一个描述光源估计结果的类型。An illuminant estimate.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IlluminantEstimate {
+    /// 主光源。Dominant illuminant.
+    pub illuminant: Illuminant,
+    /// 主光源的概率，范围：[0,1]，SDK 未报告时为 `None`。
+    /// Probability of the dominant illuminant, `None` when not reported.
+    pub confidence: Option<f32>,
+}
+
+/// 从各光源的 `(名称, 概率)` 中选出概率最大者。Pick the most probable `(name, probability)`.
+///
+/// 忽略空名称与非有限的概率，没有可用的候选时返回 `None`。
+/// Empty names and non-finite probabilities are ignored; returns `None` without candidates.
+pub(crate) fn dominant_illuminant<'a, I>(candidates: I) -> Option<IlluminantEstimate>
+where
+    I: IntoIterator<Item = (&'a str, f32)>,
+{
+    candidates
+        .into_iter()
+        .filter(|(name, prob)| !name.trim().is_empty() && prob.is_finite())
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(name, prob)| IlluminantEstimate {
+            illuminant: Illuminant::from_name(name),
+            confidence: Some(prob.clamp(0.0, 1.0)),
+        })
+}
+
+/// 一个描述白平衡状态快照的类型。A snapshot of the white balance state.
+///
+/// 增益与色温尽量取自同一帧：读取前后帧号不同时会重新读取，多次重试后仍跨帧时返回最后一次结果。
+/// 工作模式由单独的接口读取，SDK 不保证它与增益、色温原子一致。
+/// Gain and CT are read from the same frame where possible: the read is retried when
+/// the frame id changes in between, and the last result is returned if it keeps
+/// straddling frames. The mode comes from a separate call and is not atomic with them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AwbSnapshot {
+    /// 白平衡工作模式。White balance working mode.
+    pub mode: OpMode,
+    /// 白平衡增益系数。White balance gains.
+    pub gain: WbGain,
+    /// 白平衡色温，单位：K。Color temperature in Kelvin.
+    pub ct: u32,
+    /// 读取结束时的帧号，尚未收到元数据回调时为 `None`。Frame id at the end of the read.
+    pub frame_id: Option<u32>,
+}
+
+/// 与 DNG `AsShotNeutral` 标签相互转换的契定。Conversion to and from the DNG `AsShotNeutral` tag.
+///
+/// `AsShotNeutral` 是中性灰在相机原始 RGB 空间中的坐标 `[R, G, B]`，单位与增益互为倒数：
+/// 某通道的白平衡增益为 `1 / neutral`，再以绿色通道归一化，即 `gain = neutral[1] / neutral[c]`，
+/// 两个绿色通道的增益均为 1。反向转换以两个绿色通道增益的平均值作为绿色增益，
+/// 得到的 `neutral[1]` 恒为 1。各分量须为正数，否则结果不是有限值。
+///
+/// `AsShotNeutral` is the coordinate `[R, G, B]` of neutral grey in camera raw space, which is
+/// the reciprocal of a gain: each channel gain is `1 / neutral` normalized to green, i.e.
+/// `gain = neutral[1] / neutral[c]`, so both green gains are 1. The inverse uses the mean of
+/// the two green gains as the green gain and always yields `neutral[1] == 1`. All components
+/// must be positive, otherwise the result is not finite.
+pub trait AsShotNeutral {
+    /// 由 `AsShotNeutral` 得到白平衡增益。Build white balance gains from `AsShotNeutral`.
+    fn from_as_shot_neutral(neutral: [f32; 3]) -> Self;
+
+    /// 将白平衡增益转换为 `AsShotNeutral`。Convert white balance gains to `AsShotNeutral`.
+    fn to_as_shot_neutral(&self) -> [f32; 3];
+}
+
+impl AsShotNeutral for WbGain {
+    fn from_as_shot_neutral(neutral: [f32; 3]) -> Self {
+        let [r, g, b] = neutral;
+        WbGain {
+            rgain: g / r,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: g / b,
+        }
+    }
+
+    fn to_as_shot_neutral(&self) -> [f32; 3] {
+        let g = (self.grgain + self.gbgain) / 2.0;
+        [g / self.rgain, 1.0, g / self.bgain]
+    }
+}
+
+/// 由画面中中性灰区域的测量值计算白平衡增益。Compute white balance gains from a neutral patch.
+///
+/// 灰色区域经白平衡后三个通道应相等。以绿色通道为基准，设区域的平均原始值为 `(r, g, b)`，
+/// 则 `rgain = g / r`、`bgain = g / b`，两个绿色通道的增益为 1，与以 `[r, g, b]` 作为
+/// `AsShotNeutral` 的结果相同。测量值应取自未饱和、未经白平衡的原始数据。
+/// 任一通道不是正的有限值时无法校正，返回全部为 1 的增益。
+///
+/// A grey patch has equal channels after white balance. Using green as the reference, for the
+/// mean raw values `(r, g, b)` of the patch, `rgain = g / r` and `bgain = g / b` while both green
+/// gains are 1, the same as using `[r, g, b]` as `AsShotNeutral`. Measure unsaturated raw values
+/// before white balance. If any channel is not a positive finite value, unity gains are returned.
+pub trait NeutralPatch {
+    /// 由中性灰区域的 R/G/B 平均值得到增益。Build gains from the mean R/G/B of a neutral patch.
+    fn from_neutral_patch(r: f32, g: f32, b: f32) -> Self;
+}
+
+impl NeutralPatch for WbGain {
+    fn from_neutral_patch(r: f32, g: f32, b: f32) -> Self {
+        if [r, g, b].iter().all(|c| c.is_finite() && *c > 0.0) {
+            WbGain::from_as_shot_neutral([r, g, b])
+        } else {
+            WbGain {
+                rgain: 1.0,
+                grgain: 1.0,
+                gbgain: 1.0,
+                bgain: 1.0,
+            }
+        }
+    }
+}
+
+/// 两组白平衡增益之间的距离。Distance between two sets of white balance gains.
+///
+/// 距离为对数增益空间中的欧氏距离，单位：档（EV，以 2 为底）。各组增益先以两个绿色通道增益的
+/// 几何平均归一化，因此整体缩放不影响距离；分量为 `log2(R/G)`、`log2(B/G)` 与绿色分离
+/// `log2(Gr/Gb)`。只有红色增益相差一倍时距离为 1，两组增益相同时为 0。
+/// 各增益须为正数，否则结果不是有限值。
+///
+/// The distance is the Euclidean distance in log-gain space, in stops (EV, base 2). Each set
+/// is first normalized by the geometric mean of its two green gains, so overall scaling does
+/// not count; the components are `log2(R/G)`, `log2(B/G)` and the green split `log2(Gr/Gb)`.
+/// Doubling only the red gain gives 1, identical gains give 0. All gains must be positive,
+/// otherwise the result is not finite.
+pub trait GainDistance {
+    /// 与 `other` 的距离，单位：档。Distance to `other`, in stops.
+    fn distance(&self, other: &Self) -> f32;
+}
+
+impl GainDistance for WbGain {
+    fn distance(&self, other: &Self) -> f32 {
+        let coords = |g: &WbGain| {
+            let green = (g.grgain.log2() + g.gbgain.log2()) / 2.0;
+            [
+                g.rgain.log2() - green,
+                g.bgain.log2() - green,
+                g.grgain.log2() - g.gbgain.log2(),
+            ]
+        };
+        let (a, b) = (coords(self), coords(other));
+        a.iter()
+            .zip(&b)
+            .map(|(x, y)| (x - y) * (x - y))
+            .sum::<f32>()
+            .sqrt()
+    }
+}
+
+/// 常见光源的典型白平衡增益。Typical white balance gains for common illuminants.
+///
+/// 返回的增益以绿色通道归一化，两个绿色通道的增益为 1，取值为常见 CMOS 传感器在该光源下的
+/// 大致水平，仅作为手动白平衡的初始值：增益取决于传感器的光谱响应与镜头、红外滤光片，
+/// 并未针对任何传感器标定，与实际所需的增益可能相差 10% 以上。需要准确的增益时以
+/// `NeutralPatch` 实测，或在该光源下待自动白平衡收敛后读取当前增益。`Illuminant::Other`
+/// 没有典型值，返回全部为 1 的增益。
+///
+/// | 光源 | `rgain` | `bgain` |
+/// | --- | --- | --- |
+/// | `Horizon` | 1.05 | 3.20 |
+/// | `A` | 1.25 | 2.70 |
+/// | `Tl84` | 1.55 | 2.10 |
+/// | `Cwf` | 1.60 | 2.00 |
+/// | `D50` | 1.85 | 1.70 |
+/// | `D65` | 2.05 | 1.50 |
+/// | `D75` | 2.15 | 1.40 |
+///
+/// The gains are normalized to green with both green gains at 1, at roughly the level of a
+/// common CMOS sensor under each illuminant. They are nominal starting points for manual white
+/// balance, not calibrated for any sensor: gains depend on the sensor's spectral response, the
+/// lens and the IR-cut filter, and may be off by more than 10%. For accurate gains measure a
+/// `NeutralPatch`, or read the current gains once auto white balance has converged under that
+/// illuminant. `Illuminant::Other` has no typical value and returns unity gains.
+pub trait IlluminantPreset {
+    /// 光源 `illum` 的典型增益。Typical gains for `illum`.
+    fn for_illuminant(illum: Illuminant) -> Self;
+}
+
+impl IlluminantPreset for WbGain {
+    fn for_illuminant(illum: Illuminant) -> Self {
+        let (rgain, bgain) = match illum {
+            Illuminant::Horizon => (1.05, 3.20),
+            Illuminant::A => (1.25, 2.70),
+            Illuminant::Tl84 => (1.55, 2.10),
+            Illuminant::Cwf => (1.60, 2.00),
+            Illuminant::D50 => (1.85, 1.70),
+            Illuminant::D65 => (2.05, 1.50),
+            Illuminant::D75 => (2.15, 1.40),
+            Illuminant::Other => (1.0, 1.0),
+        };
+        WbGain {
+            rgain,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain,
+        }
+    }
 }
 
 impl AutoWhiteBalance for Context {
     fn get_wb_mode(&self) -> XCamResult<OpMode> {
         let mut mode = ffi::opMode_t::OP_INVAL;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getWBMode(
-                self.internal.as_ptr(),
-                &mut mode,
-            ))
-            .ok()
-            .map(|_| mode.into())
-        }
+        uapi_call!(ffi::rk_aiq_uapi_getWBMode(self, &mut mode)).map(|_| mode.into())
     }
 
     #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
     fn set_wb_mode(&self, mode: OpMode) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setWBMode(
-                self.internal.as_ptr(),
-                mode.into(),
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi_setWBMode(self, mode.into()))
     }
 
     #[cfg(any(feature = "v4_0", feature = "v5_0"))]
     fn set_wb_mode(&self, mode: OpMode) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setWBMode(
-                self.internal.as_ptr(),
-                mode.into(),
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi2_setWBMode(self, mode.into()))
     }
 
     fn lock_awb(&self) -> XCamResult<()> {
-        unsafe { XCamError::from(ffi::rk_aiq_uapi_lockAWB(self.internal.as_ptr())).ok() }
+        uapi_call!(ffi::rk_aiq_uapi_lockAWB(self))
     }
 
     fn unlock_awb(&self) -> XCamResult<()> {
-        unsafe { XCamError::from(ffi::rk_aiq_uapi_unlockAWB(self.internal.as_ptr())).ok() }
+        uapi_call!(ffi::rk_aiq_uapi_unlockAWB(self))
     }
 
     fn get_mwb_scene(&self) -> XCamResult<WbScene> {
-        let mut scene: WbScene = Default::default();
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getMWBScene(
-                self.internal.as_ptr(),
-                &mut scene,
-            ))
-            .ok()
-            .map(|_| scene)
-        }
+        uapi_get!(ffi::rk_aiq_uapi_getMWBScene(self) -> WbScene)
     }
 
     fn set_mwb_scene<T: Into<WbScene>>(&self, scene: T) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setMWBScene(
-                self.internal.as_ptr(),
-                scene.into(),
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi_setMWBScene(self, scene.into()))
     }
 
     fn get_mwb_gain(&self) -> XCamResult<WbGain> {
-        let mut gain: WbGain = Default::default();
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getMWBGain(
-                self.internal.as_ptr(),
-                &mut gain,
-            ))
-            .ok()
-            .map(|_| gain)
-        }
+        uapi_get!(ffi::rk_aiq_uapi_getMWBGain(self) -> WbGain)
     }
 
     fn set_mwb_gain<T: Into<WbGain>>(&self, gain: T) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setMWBGain(
-                self.internal.as_ptr(),
-                &mut gain.into(),
-            ))
-            .ok()
-        }
+        let mut state = self.mwb_apply.lock().unwrap();
+        apply_mwb_gain(self, &mut state, gain.into(), Context::write_mwb_gain)
     }
 
     fn get_mwb_ct(&self) -> XCamResult<u32> {
-        let mut ct: u32 = 0;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getMWBCT(self.internal.as_ptr(), &mut ct))
-                .ok()
-                .map(|_| ct)
+        uapi_get!(ffi::rk_aiq_uapi_getMWBCT(self) -> u32)
+    }
+
+    fn set_mwb_ct(&self, ct: u32) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setMWBCT(self, ct))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_awb_ct_range(&self) -> XCamResult<Option<(u32, u32)>> {
+        self.read_awb_ct_limit().map(AwbCtLimit::range)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_awb_ct_range(&self, min_k: u32, max_k: u32) -> XCamResult<()> {
+        let mut defaults = self.awb_ct_defaults.lock().unwrap();
+        limit_awb_ct_range(
+            &mut defaults,
+            min_k,
+            max_k,
+            || self.read_awb_ct_limit(),
+            |limit| self.write_awb_ct_limit(limit),
+        )
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn reset_awb_ct_range(&self) -> XCamResult<()> {
+        let mut defaults = self.awb_ct_defaults.lock().unwrap();
+        restore_awb_ct_range(&mut defaults, |limit| self.write_awb_ct_limit(limit))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_awb_calib_ct_range(&self) -> XCamResult<Option<(u32, u32)>> {
+        let defaults = self.awb_ct_defaults.lock().unwrap();
+        match *defaults {
+            Some(limit) => Ok(limit.range()),
+            None => self.get_awb_ct_range(),
         }
     }
 
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_awb_speed(&self) -> XCamResult<f32> {
+        let attr = uapi_get!(
+            ffi::rk_aiq_user_api2_awbV30_GetAllAttrib(self) -> ffi::rk_aiq_uapiV2_wbV30_attrib_t
+        )?;
+        let damp = &attr.stAuto.dampFactor;
+        Ok((1.0 - (damp.dFMin + damp.dFMax) / 2.0).clamp(0.0, 1.0))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_awb_speed(&self, speed: f32) -> XCamResult<()> {
+        let mut state = self.awb_step.lock().unwrap();
+        self.set_awb_damping(clamp_awb_speed(speed)?)?;
+        release_damping_step(&mut state);
+        Ok(())
+    }
+
+    fn get_awb_snapshot(&self) -> XCamResult<AwbSnapshot> {
+        let ((mode, gain, ct), frame_id) = context::read_in_frame(context::last_frame_id, || {
+            let mode = self.get_wb_mode()?;
+            #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+            let (gain, ct) = {
+                let info = uapi_get!(
+                    ffi::rk_aiq_user_api2_awb_QueryWBInfo(self) -> ffi::rk_aiq_wb_querry_info_t
+                )?;
+                (info.gain, info.cctGloabl.CCT as u32)
+            };
+            #[cfg(not(any(feature = "v3_0", feature = "v4_0", feature = "v5_0")))]
+            let (gain, ct) = (self.get_mwb_gain()?, self.get_mwb_ct()?);
+            Ok((mode, gain, ct))
+        })?;
+        Ok(AwbSnapshot {
+            mode,
+            gain,
+            ct,
+            frame_id,
+        })
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn get_awb_illuminant(&self) -> XCamResult<IlluminantEstimate> {
+        let info = uapi_get!(
+            ffi::rk_aiq_user_api2_awb_QueryWBInfo(self) -> ffi::rk_aiq_wb_querry_info_t
+        )?;
+        let names: Vec<_> = info
+            .illInf
+            .iter()
+            .map(|ill| (string_from_c_chars(&ill.illName), ill.prob_final))
+            .collect();
+        Ok(
+            dominant_illuminant(names.iter().map(|(name, prob)| (name.as_str(), *prob))).unwrap_or(
+                IlluminantEstimate {
+                    illuminant: Illuminant::Other,
+                    confidence: None,
+                },
+            ),
+        )
+    }
+}
+
+impl AutoWhiteBalance for SharedContext {
+    fn get_wb_mode(&self) -> XCamResult<OpMode> {
+        self.inner.get_wb_mode()
+    }
+
+    fn set_wb_mode(&self, mode: OpMode) -> XCamResult<()> {
+        self.inner.set_wb_mode(mode)
+    }
+
+    fn lock_awb(&self) -> XCamResult<()> {
+        self.inner.lock_awb()
+    }
+
+    fn unlock_awb(&self) -> XCamResult<()> {
+        self.inner.unlock_awb()
+    }
+
+    fn get_mwb_scene(&self) -> XCamResult<WbScene> {
+        self.inner.get_mwb_scene()
+    }
+
+    fn set_mwb_scene<T: Into<WbScene>>(&self, scene: T) -> XCamResult<()> {
+        self.inner.set_mwb_scene(scene)
+    }
+
+    fn get_mwb_gain(&self) -> XCamResult<WbGain> {
+        self.inner.get_mwb_gain()
+    }
+
+    fn set_mwb_gain<T: Into<WbGain>>(&self, gain: T) -> XCamResult<()> {
+        self.inner.set_mwb_gain(gain)
+    }
+
+    fn get_mwb_ct(&self) -> XCamResult<u32> {
+        self.inner.get_mwb_ct()
+    }
+
     fn set_mwb_ct(&self, ct: u32) -> XCamResult<()> {
-        unsafe { XCamError::from(ffi::rk_aiq_uapi_setMWBCT(self.internal.as_ptr(), ct)).ok() }
+        self.inner.set_mwb_ct(ct)
     }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_awb_ct_range(&self) -> XCamResult<Option<(u32, u32)>> {
+        self.inner.get_awb_ct_range()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_awb_ct_range(&self, min_k: u32, max_k: u32) -> XCamResult<()> {
+        self.inner.set_awb_ct_range(min_k, max_k)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn reset_awb_ct_range(&self) -> XCamResult<()> {
+        self.inner.reset_awb_ct_range()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_awb_calib_ct_range(&self) -> XCamResult<Option<(u32, u32)>> {
+        self.inner.get_awb_calib_ct_range()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_awb_speed(&self) -> XCamResult<f32> {
+        self.inner.get_awb_speed()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_awb_speed(&self, speed: f32) -> XCamResult<()> {
+        self.inner.set_awb_speed(speed)
+    }
+
+    fn get_awb_snapshot(&self) -> XCamResult<AwbSnapshot> {
+        self.inner.get_awb_snapshot()
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn get_awb_illuminant(&self) -> XCamResult<IlluminantEstimate> {
+        self.inner.get_awb_illuminant()
+    }
+}
+
+/// 将收敛速度限制在 [0,1] 内，NaN 返回 `XCAM_RETURN_ERROR_PARAM`。
+/// Clamp an adaptation speed to [0,1]; NaN returns `XCAM_RETURN_ERROR_PARAM`.
+pub(crate) fn clamp_awb_speed(speed: f32) -> XCamResult<f32> {
+    if speed.is_nan() {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    Ok(speed.clamp(0.0, 1.0))
+}
+
+/// 色温范围限制允许的最低色温，单位：K。Lowest CT accepted for the range, in Kelvin.
+pub const AWB_CT_MIN: u32 = 1500;
+
+/// 色温范围限制允许的最高色温，单位：K。Highest CT accepted for the range, in Kelvin.
+pub const AWB_CT_MAX: u32 = 15000;
+
+/// 检查色温范围：`min_k < max_k`，否则返回 `XCAM_RETURN_ERROR_PARAM`；
+/// 两者均须在 [`AWB_CT_MIN`, `AWB_CT_MAX`] 内，否则返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+/// Check a CT range: `min_k < max_k` (else `XCAM_RETURN_ERROR_PARAM`), both within
+/// [`AWB_CT_MIN`, `AWB_CT_MAX`] (else `XCAM_RETURN_ERROR_OUTOFRANGE`).
+pub fn check_awb_ct_range(min_k: u32, max_k: u32) -> XCamResult<()> {
+    if min_k >= max_k {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    let valid = AWB_CT_MIN..=AWB_CT_MAX;
+    if !valid.contains(&min_k) || !valid.contains(&max_k) {
+        return Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+    }
+    Ok(())
+}
+
+/// 普朗克轨迹上色温 `k` 的 CIE 1931 色度坐标 `(x, y)`，使用 Kim 等人的三次样条近似。
+/// Chromaticity `(x, y)` of the Planckian locus at `k`, per the cubic spline of Kim et al.
+fn planckian_xy(k: f64) -> (f64, f64) {
+    let (t1, t2, t3) = (1e3 / k, 1e6 / (k * k), 1e9 / (k * k * k));
+    let x = if k <= 4000.0 {
+        -0.2661239 * t3 - 0.2343589 * t2 + 0.8776956 * t1 + 0.179910
+    } else {
+        -3.0258469 * t3 + 2.1070379 * t2 + 0.2226347 * t1 + 0.240390
+    };
+    let y = if k <= 2222.0 {
+        -1.1063814 * x * x * x - 1.34811020 * x * x + 2.18555832 * x - 0.20219683
+    } else if k <= 4000.0 {
+        -0.9549476 * x * x * x - 1.37418593 * x * x + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x * x * x - 5.87338670 * x * x + 3.75112997 * x - 0.37001483
+    };
+    (x, y)
+}
+
+/// 由色温计算近似的白平衡增益。Approximate white balance gains for a color temperature.
+///
+/// 以普朗克轨迹近似得到色温为 `k` 的黑体光源的色度，转换到 Bradford 锥体响应空间，
+/// 取使其各通道相等的增益并以绿色通道归一化，两个绿色通道的增益为 1。该模型不含传感器的
+/// 光谱响应，与标定过的传感器增益存在系统偏差，只适合作为相对参考。
+/// `k` 被限制在 [`AWB_CT_MIN`, `AWB_CT_MAX`] 内。
+///
+/// Chromaticity of a black body at `k` from the Planckian locus approximation, converted to
+/// Bradford cone space; the gains equalize the channels, normalized to green with both green
+/// gains at 1. The model ignores the sensor's spectral response and differs systematically from
+/// calibrated sensor gains, so treat it as a relative reference. `k` is clamped to
+/// [`AWB_CT_MIN`, `AWB_CT_MAX`].
+pub fn wb_gain_from_kelvin(k: u32) -> WbGain {
+    let (x, y) = planckian_xy(k.clamp(AWB_CT_MIN, AWB_CT_MAX) as f64);
+    let (cx, cy, cz) = (x / y, 1.0, (1.0 - x - y) / y);
+    let l = 0.8951 * cx + 0.2664 * cy - 0.1614 * cz;
+    let m = -0.7502 * cx + 1.7135 * cy + 0.0367 * cz;
+    let s = 0.0389 * cx - 0.0685 * cy + 1.0296 * cz;
+    WbGain {
+        rgain: (m / l) as f32,
+        grgain: 1.0,
+        gbgain: 1.0,
+        bgain: (m / s) as f32,
+    }
+}
+
+/// 由白平衡增益估计色温，单位：K，为 `wb_gain_from_kelvin` 的逆运算。
+/// Estimate the color temperature of white balance gains in Kelvin; the inverse of
+/// `wb_gain_from_kelvin`.
+///
+/// 只使用 `rgain / bgain`，与绿色增益及整体缩放无关。在 [`AWB_CT_MIN`, `AWB_CT_MAX`]
+/// 内以倒色温二分查找，结果被限制在该范围内；对 `wb_gain_from_kelvin` 的结果往返误差小于 0.5%。
+/// 对传感器实际使用的增益只是粗略估计，误差可达数百 K，需要标定过的色温时使用 `get_mwb_ct`。
+/// `rgain`、`bgain` 不是正的有限值时结果无意义。
+///
+/// Only `rgain / bgain` is used, so green gains and overall scaling do not matter. The CT is
+/// bisected in mired within [`AWB_CT_MIN`, `AWB_CT_MAX`] and clamped to it; the round trip
+/// through `wb_gain_from_kelvin` is within 0.5%. For gains actually used by a sensor this is
+/// only a rough estimate that may be off by hundreds of Kelvin; use `get_mwb_ct` for a calibrated
+/// CT. The result is meaningless unless `rgain` and `bgain` are positive and finite.
+pub fn kelvin_from_wb_gain(gain: &WbGain) -> u32 {
+    // rgain / bgain 随色温单调递增，倒色温越大色温越低
+    let target = gain.rgain / gain.bgain;
+    let ratio = |k: f64| {
+        let g = wb_gain_from_kelvin(k.round() as u32);
+        g.rgain / g.bgain
+    };
+    let (mut lo, mut hi) = (1e6 / AWB_CT_MAX as f64, 1e6 / AWB_CT_MIN as f64);
+    for _ in 0..32 {
+        let mid = (lo + hi) / 2.0;
+        if ratio(1e6 / mid) < target {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    ((1e6 / ((lo + hi) / 2.0)).round() as u32).clamp(AWB_CT_MIN, AWB_CT_MAX)
 }
 
+/// AWB 属性中的色温范围字段。The CT range fields of the AWB attributes.
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct AwbCtLimit {
+    pub enable: bool,
+    pub min: f32,
+    pub max: f32,
+}
+
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+impl AwbCtLimit {
+    /// 启用时的色温范围，四舍五入为整数开尔文。The enabled range, rounded to whole kelvin.
+    pub(crate) fn range(self) -> Option<(u32, u32)> {
+        self.enable
+            .then(|| (self.min.round() as u32, self.max.round() as u32))
+    }
+}
+
+/// 检查并写入色温范围，首次限制前以 `read` 读取原范围保存到 `defaults`；
+/// 写入失败时不保存。
+/// Check and write a CT range, saving the range from `read` into `defaults` before the
+/// first limit; nothing is saved if the write fails.
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn limit_awb_ct_range<R, W>(
+    defaults: &mut Option<AwbCtLimit>,
+    min_k: u32,
+    max_k: u32,
+    read: R,
+    write: W,
+) -> XCamResult<()>
+where
+    R: FnOnce() -> XCamResult<AwbCtLimit>,
+    W: FnOnce(AwbCtLimit) -> XCamResult<()>,
+{
+    check_awb_ct_range(min_k, max_k)?;
+    let current = read()?;
+    write(AwbCtLimit {
+        enable: true,
+        min: min_k as f32,
+        max: max_k as f32,
+    })?;
+    defaults.get_or_insert(current);
+    Ok(())
+}
+
+/// 以 `write` 恢复 `defaults` 中保存的色温范围并清除，未保存时不做任何操作。
+/// Restore the CT range saved in `defaults` with `write` and clear it; no-op if unsaved.
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn restore_awb_ct_range<W>(defaults: &mut Option<AwbCtLimit>, write: W) -> XCamResult<()>
+where
+    W: FnOnce(AwbCtLimit) -> XCamResult<()>,
+{
+    if let Some(limit) = *defaults {
+        write(limit)?;
+        *defaults = None;
+    }
+    Ok(())
+}
+
+/// 模拟步长限制时，无法获取帧周期时使用的更新间隔。
+/// Update interval of the emulated limiter when the frame period is unavailable.
+const STEP_LIMITER_FALLBACK_PERIOD: Duration = Duration::from_millis(33);
+
+/// 一个描述白平衡增益步长限制的类型。A limit on the per-frame AWB gain change.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AwbStepLimit {
+    /// 每帧增益变化的上限，以比例表示，取值 (0, 1]。
+    /// Max per-frame change as a fraction, in (0, 1].
+    pub per_frame_delta: f32,
+    /// 是否由本库模拟。Whether the limit is emulated by this crate.
+    pub emulated: bool,
+}
+
+pub(crate) struct AwbStepState {
+    limit: AwbStepLimit,
+    worker: Option<StepLimiter>,
+}
+
+/// 阻尼系数被 `set_awb_speed` 覆盖后，清除同样以阻尼系数实现的非模拟步长限制。
+/// Drop a non-emulated step limit once `set_awb_speed` has overwritten its damping factor.
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub(crate) fn release_damping_step(state: &mut Option<AwbStepState>) {
+    if matches!(state, Some(s) if !s.limit.emulated) {
+        *state = None;
+    }
+}
+
+/// 模拟步长限制的工作线程。Worker thread of the emulated limiter.
+struct StepLimiter {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl StepLimiter {
+    /// 启动工作线程，每次调用 `tick` 后等待其返回的间隔，`tick` 返回 `None` 时退出。
+    /// Start the worker: call `tick` and wait for the interval it returns; exit on `None`.
+    fn spawn<F>(mut tick: F) -> XCamResult<Self>
+    where
+        F: FnMut() -> Option<Duration> + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let handle = thread::Builder::new()
+            .name("rkaiq-awb-step".into())
+            .spawn(move || {
+                while !flag.load(Ordering::Acquire) {
+                    let Some(period) = tick() else { break };
+                    thread::park_timeout(period);
+                }
+            })
+            .map_err(|_| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_THREAD))?;
+        Ok(Self { stop, handle })
+    }
+
+    /// 通知工作线程退出并等待其结束。Signal the worker and wait for it to exit.
+    fn stop(self) {
+        self.stop.store(true, Ordering::Release);
+        self.handle.thread().unpark();
+        let _ = self.handle.join();
+    }
+}
+
+/// 模拟步长限制的起点：切换为手动白平衡前 AWB 算法输出的增益与估计的色温。
+/// Starting point of the emulated limiter: the algorithm's gain and CT before switching
+/// to manual white balance.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct StepAnchor {
+    pub gain: WbGain,
+    pub ct: u32,
+}
+
+/// 由 AWB 算法估计的色温 `ct` 得出目标增益。Target gain for the CT `ct` estimated by AWB.
+///
+/// 手动模式下算法不再输出增益，查询信息中的增益即为写入的手动增益，但色温估计仍随画面更新。
+/// 目标增益按 `wb_gain_from_kelvin` 在起点色温与 `ct` 之间的相对变化缩放起点增益，
+/// 在起点处与算法增益一致，色温偏离起点越远误差越大。
+/// In manual mode the algorithm outputs no gain and the queried gain is the manual gain
+/// just written, but the CT estimate keeps updating. The anchor gain is scaled by the
+/// relative change of `wb_gain_from_kelvin` between the anchor CT and `ct`: exact at the
+/// anchor, increasingly approximate away from it.
+pub(crate) fn estimate_step_target(anchor: StepAnchor, ct: u32) -> WbGain {
+    let (from, to) = (wb_gain_from_kelvin(anchor.ct), wb_gain_from_kelvin(ct));
+    WbGain {
+        rgain: anchor.gain.rgain * to.rgain / from.rgain,
+        grgain: anchor.gain.grgain * to.grgain / from.grgain,
+        gbgain: anchor.gain.gbgain * to.gbgain / from.gbgain,
+        bgain: anchor.gain.bgain * to.bgain / from.bgain,
+    }
+}
+
+/// 模拟步长限制的一次更新：读取算法估计的色温，将 `output` 向目标增益移动一步后写入。
+/// One update of the emulated limiter: read the estimated CT and move `output` one step
+/// towards the target gain, then write it.
+pub(crate) fn step_limiter_tick<C: AutoWhiteBalance + ?Sized>(
+    ctx: &C,
+    anchor: StepAnchor,
+    output: &mut WbGain,
+    per_frame_delta: f32,
+) -> XCamResult<()> {
+    let ct = ctx.get_awb_snapshot()?.ct;
+    let next = limit_gain_step(*output, estimate_step_target(anchor, ct), per_frame_delta);
+    ctx.set_mwb_gain(next)?;
+    *output = next;
+    Ok(())
+}
+
+/// 工作线程只持有弱引用，上下文释放后返回 `None` 使线程退出。
+/// The worker holds a weak reference and returns `None` to exit once the context is dropped.
+fn run_step_limiter(
+    ctx: &Weak<Context>,
+    anchor: StepAnchor,
+    output: &mut WbGain,
+    per_frame_delta: f32,
+) -> Option<Duration> {
+    let ctx = ctx.upgrade()?;
+    let _ = step_limiter_tick(&*ctx, anchor, output, per_frame_delta);
+    Some(ctx.frame_period().unwrap_or(STEP_LIMITER_FALLBACK_PERIOD))
+}
+
+/// 将 `current` 向 `target` 移动，每个通道的变化不超过当前增益的 `per_frame_delta` 倍。
+/// Move `current` towards `target`, changing each channel by at most
+/// `per_frame_delta` times its current gain.
+///
+/// 当前增益不是有限值时直接取目标值。A non-finite current gain jumps to the target.
+pub fn limit_gain_step(current: WbGain, target: WbGain, per_frame_delta: f32) -> WbGain {
+    let step = |c: f32, t: f32| {
+        if !c.is_finite() {
+            return t;
+        }
+        let d = c.abs() * per_frame_delta.max(0.0);
+        t.clamp(c - d, c + d)
+    };
+    WbGain {
+        rgain: step(current.rgain, target.rgain),
+        grgain: step(current.grgain, target.grgain),
+        gbgain: step(current.gbgain, target.gbgain),
+        bgain: step(current.bgain, target.bgain),
+    }
+}
+
+impl Context {
+    /// 读取 AWB 算法输出的增益与估计的色温，须在自动模式下调用。
+    /// Read the gain and CT estimated by the AWB algorithm; call in auto mode.
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn awb_step_anchor(&self) -> XCamResult<StepAnchor> {
+        self.get_awb_snapshot().map(|s| StepAnchor {
+            gain: s.gain,
+            ct: s.ct,
+        })
+    }
+
+    #[cfg(not(any(feature = "v3_0", feature = "v4_0", feature = "v5_0")))]
+    fn awb_step_anchor(&self) -> XCamResult<StepAnchor> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 通过 AWB 属性的阻尼系数限制增益变化。Limit the gain change via the AWB damping factor.
+    ///
+    /// SDK 每帧按 `新增益 = df × 旧增益 + (1 − df) × 目标增益` 平滑，
+    /// 取固定的 `df = 1 − per_frame_delta`；`get_awb_speed` 由 `df` 的上下限均值反算。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_awb_damping(&self, per_frame_delta: f32) -> XCamResult<()> {
+        let mut attr = uapi_get!(
+            ffi::rk_aiq_user_api2_awbV30_GetAllAttrib(self) -> ffi::rk_aiq_uapiV2_wbV30_attrib_t
+        )?;
+        let df = 1.0 - per_frame_delta;
+        attr.stAuto.dampFactor.dFStep = 0.0;
+        attr.stAuto.dampFactor.dFMin = df;
+        attr.stAuto.dampFactor.dFMax = df;
+        uapi_call!(ffi::rk_aiq_user_api2_awbV30_SetAllAttrib(self, attr); log)
+    }
+
+    #[cfg(not(any(feature = "v4_0", feature = "v5_0")))]
+    fn set_awb_damping(&self, _per_frame_delta: f32) -> XCamResult<()> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 读取 AWB 属性中的色温范围。Read the CT range from the AWB attributes.
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn read_awb_ct_limit(&self) -> XCamResult<AwbCtLimit> {
+        let attr = uapi_get!(
+            ffi::rk_aiq_user_api2_awbV30_GetAllAttrib(self) -> ffi::rk_aiq_uapiV2_wbV30_attrib_t
+        )?;
+        let range = &attr.stAuto.cctRange;
+        Ok(AwbCtLimit {
+            enable: range.enable,
+            min: range.minCct,
+            max: range.maxCct,
+        })
+    }
+
+    /// 写入 AWB 属性中的色温范围。Write the CT range into the AWB attributes.
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn write_awb_ct_limit(&self, limit: AwbCtLimit) -> XCamResult<()> {
+        let mut attr = uapi_get!(
+            ffi::rk_aiq_user_api2_awbV30_GetAllAttrib(self) -> ffi::rk_aiq_uapiV2_wbV30_attrib_t
+        )?;
+        attr.stAuto.cctRange.enable = limit.enable;
+        attr.stAuto.cctRange.minCct = limit.min;
+        attr.stAuto.cctRange.maxCct = limit.max;
+        uapi_call!(ffi::rk_aiq_user_api2_awbV30_SetAllAttrib(self, attr); log)
+    }
+
+    /// 启用并写入 AWB 增益偏移。Enable and write the AWB gain offset.
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn write_wb_gain_offset(&self, offset: [f32; 4]) -> XCamResult<()> {
+        let mut attr = uapi_get!(
+            ffi::rk_aiq_user_api2_awb_GetWbGainOffsetAttrib(self)
+                -> ffi::rk_aiq_uapiV2_wb_awb_wbGainOffset_t
+        )?;
+        attr.gainOffset.enable = true;
+        attr.gainOffset.offset = offset;
+        uapi_call!(ffi::rk_aiq_user_api2_awb_SetWbGainOffsetAttrib(self, attr); log)
+    }
+
+    /// 限制白平衡增益每帧的变化量，用于抑制混合光源下的来回跳变。
+    /// Limit the per-frame AWB gain change to stop oscillation under mixed lighting.
+    ///
+    /// * `emulated == false`：设置 AWB 属性的阻尼系数（v4_0、v5_0），
+    ///   每帧向目标增益移动剩余差值的 `per_frame_delta`；其余版本返回 `XCamError::Unsupported`。
+    ///   Sets the AWB damping factor (v4_0, v5_0): each frame covers `per_frame_delta`
+    ///   of the remaining difference; other versions return `XCamError::Unsupported`.
+    ///   与 `set_awb_speed(per_frame_delta)` 等效，两者后设置者生效。
+    ///   Equivalent to `set_awb_speed(per_frame_delta)`; the last call wins.
+    /// * `emulated == true`：切换为手动白平衡，由工作线程每帧读取 AWB 算法估计的色温，
+    ///   换算为目标增益（见 `estimate_step_target`），按 `limit_gain_step` 限幅后写入手动增益
+    ///   （v3_0 及以上）。以 `stop_awb_step_limiter` 停止。
+    ///   Switches to manual white balance; a worker reads the algorithm's CT estimate every
+    ///   frame, converts it to a target gain, clamps it with `limit_gain_step` and writes it
+    ///   as the manual gain (v3_0+). Stop it with `stop_awb_step_limiter`.
+    ///
+    /// 重复调用时先停止已有的模拟线程。Any running emulation is stopped first.
+    ///
+    /// # Parameters
+    /// * `per_frame_delta` - 每帧变化的上限，取值 (0, 1]。Max change per frame, in (0, 1].
+    /// * `emulated` - 是否由本库模拟。Whether to emulate the limit.
+    pub fn set_awb_max_step(
+        self: &Arc<Self>,
+        per_frame_delta: f32,
+        emulated: bool,
+    ) -> XCamResult<()> {
+        if !(per_frame_delta > 0.0 && per_frame_delta <= 1.0) {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        let mut state = self.awb_step.lock().unwrap();
+        if let Some(worker) = state.take().and_then(|s| s.worker) {
+            worker.stop();
+        }
+        let worker = if emulated {
+            let anchor = self.awb_step_anchor()?;
+            self.set_wb_mode(OpMode::Manual)?;
+            let ctx = Arc::downgrade(self);
+            let mut output = anchor.gain;
+            Some(StepLimiter::spawn(move || {
+                run_step_limiter(&ctx, anchor, &mut output, per_frame_delta)
+            })?)
+        } else {
+            self.set_awb_damping(per_frame_delta)?;
+            None
+        };
+        *state = Some(AwbStepState {
+            limit: AwbStepLimit {
+                per_frame_delta,
+                emulated,
+            },
+            worker,
+        });
+        Ok(())
+    }
+
+    /// 获取最近一次通过 `set_awb_max_step` 设置的步长限制。
+    /// Get the limit last set with `set_awb_max_step`.
+    pub fn get_awb_max_step(&self) -> Option<AwbStepLimit> {
+        self.awb_step.lock().unwrap().as_ref().map(|s| s.limit)
+    }
+
+    /// 停止模拟的步长限制并恢复自动白平衡，等待工作线程退出后返回。
+    /// Stop the emulated limiter, wait for its worker and restore auto white balance.
+    ///
+    /// 未运行模拟时不做任何操作。Does nothing unless the emulation is running.
+    pub fn stop_awb_step_limiter(&self) -> XCamResult<()> {
+        let mut state = self.awb_step.lock().unwrap();
+        if !matches!(state.as_ref(), Some(s) if s.worker.is_some()) {
+            return Ok(());
+        }
+        if let Some(worker) = state.take().and_then(|s| s.worker) {
+            worker.stop();
+        }
+        self.set_wb_mode(OpMode::Auto)
+    }
+}
+
+/// 由金样增益与本机实测增益计算出厂补偿系数。
+/// Compute the factory compensation ratio from golden and measured gains.
+///
+/// 每个通道的系数为 `measured / golden`，再除以两个绿色通道系数的均值，使绿色保持约为 1；
+/// 任一增益不是正的有限值时返回 `None`。
+/// Each channel is `measured / golden`, divided by the mean of the two green ratios
+/// so green stays near 1; returns `None` unless every gain is positive and finite.
+pub fn factory_wb_ratio(golden: WbGain, measured: WbGain) -> Option<WbGain> {
+    let channels = |g: WbGain| [g.rgain, g.grgain, g.gbgain, g.bgain];
+    let (golden, measured) = (channels(golden), channels(measured));
+    if !golden
+        .iter()
+        .chain(&measured)
+        .all(|v| v.is_finite() && *v > 0.0)
+    {
+        return None;
+    }
+    let r: [f32; 4] = std::array::from_fn(|i| measured[i] / golden[i]);
+    let green = (r[1] + r[2]) / 2.0;
+    Some(WbGain {
+        rgain: r[0] / green,
+        grgain: r[1] / green,
+        gbgain: r[2] / green,
+        bgain: r[3] / green,
+    })
+}
+
+/// 将补偿系数换算为 SDK 增益偏移，顺序为 `[r, gr, gb, b]`。
+/// Convert a compensation ratio to the SDK gain offset, ordered `[r, gr, gb, b]`.
+///
+/// SDK 的偏移与算法输出的增益相加，而补偿系数与之相乘，因此按金样增益换算：
+/// 在标定光源下二者一致，色温偏离标定光源越远误差越大。
+/// The SDK adds the offset to the algorithm's gains while the ratio multiplies them,
+/// so the offset is taken at the golden gains: exact under the calibration light,
+/// increasingly approximate away from it.
+pub fn wb_gain_offset(golden: WbGain, ratio: WbGain) -> [f32; 4] {
+    [
+        golden.rgain * (ratio.rgain - 1.0),
+        golden.grgain * (ratio.grgain - 1.0),
+        golden.gbgain * (ratio.gbgain - 1.0),
+        golden.bgain * (ratio.bgain - 1.0),
+    ]
+}
+
+/// 按补偿系数缩放手动白平衡增益，`installed` 为已安装的系数，先撤销再应用新的系数。
+/// Scale the manual white balance gain by `ratio`, undoing the `installed` ratio first.
+#[cfg(any(test, not(any(feature = "v4_0", feature = "v5_0"))))]
+pub(crate) fn scale_manual_gain<C: AutoWhiteBalance + ?Sized>(
+    ctx: &C,
+    installed: &mut Option<WbGain>,
+    ratio: WbGain,
+) -> XCamResult<()> {
+    let prior = installed.unwrap_or(WbGain {
+        rgain: 1.0,
+        grgain: 1.0,
+        gbgain: 1.0,
+        bgain: 1.0,
+    });
+    let gain = ctx.get_mwb_gain()?;
+    ctx.set_mwb_gain(WbGain {
+        rgain: gain.rgain / prior.rgain * ratio.rgain,
+        grgain: gain.grgain / prior.grgain * ratio.grgain,
+        gbgain: gain.gbgain / prior.gbgain * ratio.gbgain,
+        bgain: gain.bgain / prior.bgain * ratio.bgain,
+    })?;
+    *installed = Some(ratio);
+    Ok(())
+}
+
+impl Context {
+    /// 按产线测得的金样增益与本机增益安装白平衡出厂补偿。
+    /// Install the factory white balance compensation from golden and measured gains.
+    ///
+    /// 系数由 `factory_wb_ratio` 计算，增益无效时返回 `XCAM_RETURN_ERROR_PARAM`。
+    /// v4_0、v5_0 通过 AWB 增益偏移接口安装，对自动白平衡持续生效，见 `wb_gain_offset`；
+    /// 其余版本缩放当前的手动白平衡增益，只对手动白平衡有效，重复调用时替换之前的系数。
+    /// The ratio comes from `factory_wb_ratio`; invalid gains return `XCAM_RETURN_ERROR_PARAM`.
+    /// v4_0 and v5_0 install it through the AWB gain offset, which keeps applying under
+    /// auto white balance (see `wb_gain_offset`); other versions scale the current manual
+    /// gain, which only affects manual white balance, replacing any previous ratio.
+    pub fn apply_factory_wb_compensation(
+        &self,
+        golden: WbGain,
+        measured: WbGain,
+    ) -> XCamResult<()> {
+        let ratio = factory_wb_ratio(golden, measured)
+            .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))?;
+        let mut installed = self.factory_wb.lock().unwrap();
+        #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+        {
+            self.write_wb_gain_offset(wb_gain_offset(golden, ratio))?;
+            *installed = Some(ratio);
+        }
+        #[cfg(not(any(feature = "v4_0", feature = "v5_0")))]
+        scale_manual_gain(self, &mut installed, ratio)?;
+        Ok(())
+    }
+
+    /// 获取已安装的白平衡出厂补偿系数，未安装时返回 `None`。
+    /// Get the installed factory compensation ratio, or `None` if none is installed.
+    pub fn factory_wb_compensation(&self) -> Option<WbGain> {
+        *self.factory_wb.lock().unwrap()
+    }
+}
+
+/// 一个描述手动白平衡增益生效方式的枚举。How a manual white balance gain takes effect.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WbApplyMode {
+    /// 立即跳变到新增益，录像中可见色彩突变。Jump to the new gain at once.
+    #[default]
+    Immediate,
+    /// 经数帧过渡到新增益。Ramp to the new gain over a few frames.
+    Smooth,
+}
+
+enum_str!(WbApplyMode, "white balance apply mode" {
+    Immediate => "immediate",
+    Smooth => "smooth",
+});
+
+/// 模拟平滑过渡时每帧增益变化的上限，见 `limit_gain_step`，约 10 帧内完成常见的切换。
+/// Max per-frame change of the emulated ramp; see `limit_gain_step`.
+pub const MWB_SMOOTH_STEP: f32 = 0.1;
+
+/// 一个描述手动增益生效方式的类型。The apply mode of manual white balance gains.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MwbApply {
+    /// 生效方式。The apply mode.
+    pub mode: WbApplyMode,
+    /// 是否由本库模拟。Whether the mode is emulated by this crate.
+    pub emulated: bool,
+}
+
+/// `set_mwb_apply_mode` 设置的生效方式与模拟过渡的目标增益。
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct MwbApplyState {
+    mode: WbApplyMode,
+    emulated: bool,
+    target: Option<WbGain>,
+}
+
+/// 优先写入 AWB 属性的标志，标志不存在时记录为模拟。
+/// Write the attribute flag, falling back to emulation when it is absent.
+pub(crate) fn set_mwb_apply_mode<C: AutoWhiteBalance + ?Sized>(
+    ctx: &C,
+    state: &mut MwbApplyState,
+    mode: WbApplyMode,
+) -> XCamResult<()> {
+    let smooth = mode == WbApplyMode::Smooth;
+    let emulated = match ctx.set_mwb_smooth_flag(smooth) {
+        Ok(()) => false,
+        Err(XCamError::Unsupported) => smooth,
+        Err(e) => return Err(e),
+    };
+    *state = MwbApplyState {
+        mode,
+        emulated,
+        target: None,
+    };
+    Ok(())
+}
+
+pub(crate) fn get_mwb_apply_mode<C: AutoWhiteBalance + ?Sized>(
+    ctx: &C,
+    state: &MwbApplyState,
+) -> XCamResult<MwbApply> {
+    match ctx.get_mwb_smooth_flag() {
+        Ok(smooth) => Ok(MwbApply {
+            mode: if smooth {
+                WbApplyMode::Smooth
+            } else {
+                WbApplyMode::Immediate
+            },
+            emulated: false,
+        }),
+        Err(XCamError::Unsupported) => Ok(MwbApply {
+            mode: state.mode,
+            emulated: state.emulated,
+        }),
+        Err(e) => Err(e),
+    }
+}
+
+/// 以 `write` 写入手动增益；模拟平滑过渡时只写入第一步，其余由 `tick_mwb_gain` 完成。
+/// Write a manual gain with `write`; an emulated ramp writes the first step only.
+pub(crate) fn apply_mwb_gain<C, W>(
+    ctx: &C,
+    state: &mut MwbApplyState,
+    gain: WbGain,
+    write: W,
+) -> XCamResult<()>
+where
+    C: AutoWhiteBalance + ?Sized,
+    W: Fn(&C, WbGain) -> XCamResult<()>,
+{
+    if !(state.emulated && state.mode == WbApplyMode::Smooth) {
+        state.target = None;
+        return write(ctx, gain);
+    }
+    step_mwb_gain(ctx, state, gain, write)
+}
+
+/// 推进模拟的平滑过渡，写入时返回 `true`。Advance the emulated ramp; `true` if written.
+pub(crate) fn tick_mwb_gain<C, W>(ctx: &C, state: &mut MwbApplyState, write: W) -> XCamResult<bool>
+where
+    C: AutoWhiteBalance + ?Sized,
+    W: Fn(&C, WbGain) -> XCamResult<()>,
+{
+    let Some(target) = state.target else {
+        return Ok(false);
+    };
+    step_mwb_gain(ctx, state, target, write)?;
+    Ok(true)
+}
+
+fn step_mwb_gain<C, W>(
+    ctx: &C,
+    state: &mut MwbApplyState,
+    target: WbGain,
+    write: W,
+) -> XCamResult<()>
+where
+    C: AutoWhiteBalance + ?Sized,
+    W: Fn(&C, WbGain) -> XCamResult<()>,
+{
+    let next = limit_gain_step(ctx.get_mwb_gain()?, target, MWB_SMOOTH_STEP);
+    write(ctx, next)?;
+    state.target = (next.distance(&target) > 0.0).then_some(target);
+    Ok(())
+}
+
+impl Context {
+    /// 写入手动白平衡增益，不经过生效方式的处理。Write the manual gain as is.
+    fn write_mwb_gain(&self, mut gain: WbGain) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setMWBGain(self, &mut gain))
+    }
+
+    /// 设置手动白平衡增益的生效方式，影响之后的 `set_mwb_gain`。
+    /// Set how manual gains from `set_mwb_gain` take effect.
+    ///
+    /// AWB 属性有平滑生效标志时直接写入；否则 `Smooth` 由本库模拟：`set_mwb_gain` 只按
+    /// `MWB_SMOOTH_STEP` 写入第一步，应在每帧的处理循环中调用 `tick_mwb_gain` 完成过渡，
+    /// `get_mwb_apply_mode` 的 `emulated` 为 `true`。切换生效方式会放弃未完成的过渡。
+    /// Uses the AWB attribute flag when present. Otherwise `Smooth` is emulated:
+    /// `set_mwb_gain` writes one `MWB_SMOOTH_STEP` step and `tick_mwb_gain` must be called
+    /// every frame to finish the ramp; `get_mwb_apply_mode` then reports `emulated`.
+    /// Changing the mode drops any unfinished ramp.
+    pub fn set_mwb_apply_mode(&self, mode: WbApplyMode) -> XCamResult<()> {
+        set_mwb_apply_mode(self, &mut self.mwb_apply.lock().unwrap(), mode)
+    }
+
+    /// 获取手动白平衡增益的生效方式。Get how manual gains take effect.
+    pub fn get_mwb_apply_mode(&self) -> XCamResult<MwbApply> {
+        get_mwb_apply_mode(self, &self.mwb_apply.lock().unwrap())
+    }
+
+    /// 推进模拟的平滑过渡，写入时返回 `true`，没有未完成的过渡时返回 `false`。
+    /// Advance the emulated ramp; returns `false` when no ramp is pending.
+    pub fn tick_mwb_gain(&self) -> XCamResult<bool> {
+        tick_mwb_gain(
+            self,
+            &mut self.mwb_apply.lock().unwrap(),
+            Context::write_mwb_gain,
+        )
+    }
+}
+
+/// 一个描述白平衡工作模式的枚举。White balance operation mode.
+///
+/// 与通用的 `OpMode` 对应关系如下 / Mapping to the generic `OpMode`:
+///
+/// * `Auto`、`Manual` 与 `OpMode` 的同名模式一一对应；
+///   `Auto` and `Manual` map one-to-one.
+/// * 白平衡没有半自动模式，`OpMode::SemiAuto`、`OpMode::RegManual` 无法转换；
+///   SDK 报告的半自动模式在读取时被视为无效值（v1_0 为 `Invalid`，其余版本为 `Max`）。
+///   White balance has no semi-auto mode: `OpMode::SemiAuto` and `OpMode::RegManual`
+///   do not convert, and a semi-auto value read from the SDK becomes `Invalid` (v1_0) or `Max`.
+/// * `Max` 是 SDK 的枚举上界，并非有效模式，转换为 `OpMode::Invalid`。
+///   `Max` is the SDK's sentinel and converts to `OpMode::Invalid`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum WbOpMode {
     #[cfg(feature = "v1_0")]
     Invalid,
@@ -161,9 +1592,22 @@ impl From<ffi::opMode_t> for WbOpMode {
             OP_SEMI_AUTO => WbOpMode::Max,
             OP_MANUAL => WbOpMode::Manual,
             #[cfg(feature = "v1_0")]
-            _ => WbOpMode::Invalid,
+            OP_INVAL => WbOpMode::Invalid,
             #[cfg(not(feature = "v1_0"))]
-            _ => WbOpMode::Max,
+            OP_INVAL => WbOpMode::Max,
+            // 各版本 SDK 的取值不同（v3_0 起有 `OP_REG_MANUAL`），取值已全部列出的版本上不可达。
+            #[allow(unreachable_patterns)]
+            _ => {
+                super::convert::unmapped("opMode_t", val);
+                #[cfg(feature = "v1_0")]
+                {
+                    WbOpMode::Invalid
+                }
+                #[cfg(not(feature = "v1_0"))]
+                {
+                    WbOpMode::Max
+                }
+            }
         }
     }
 }
@@ -206,3 +1650,722 @@ impl From<WbOpMode> for ffi::rk_aiq_wb_op_mode_t {
         }
     }
 }
+
+impl From<WbOpMode> for OpMode {
+    fn from(val: WbOpMode) -> Self {
+        match val {
+            #[cfg(feature = "v1_0")]
+            WbOpMode::Invalid => OpMode::Invalid,
+            WbOpMode::Manual => OpMode::Manual,
+            WbOpMode::Auto => OpMode::Auto,
+            WbOpMode::Max => OpMode::Invalid,
+        }
+    }
+}
+
+impl TryFrom<OpMode> for WbOpMode {
+    type Error = XCamError;
+
+    /// 白平衡不支持的模式返回 `XCamError::Unsupported`。
+    /// Modes white balance does not support return `XCamError::Unsupported`.
+    fn try_from(val: OpMode) -> Result<Self, Self::Error> {
+        match val {
+            OpMode::Auto => Ok(WbOpMode::Auto),
+            OpMode::Manual => Ok(WbOpMode::Manual),
+            #[cfg(feature = "v1_0")]
+            OpMode::Invalid => Ok(WbOpMode::Invalid),
+            _ => Err(XCamError::Unsupported),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_step_limiter_follows_estimate() {
+        let ctx = MockContext::new();
+        let anchor = StepAnchor {
+            gain: WbGain {
+                rgain: 2.0,
+                grgain: 1.0,
+                gbgain: 1.0,
+                bgain: 1.6,
+            },
+            ct: 5000,
+        };
+        assert_eq!(estimate_step_target(anchor, 5000), anchor.gain);
+        let target = estimate_step_target(anchor, 3000);
+        assert!(target.rgain < anchor.gain.rgain && target.bgain > anchor.gain.bgain);
+
+        // 目标取自算法估计的色温，而不是上一次写入的手动增益。
+        ctx.state.borrow_mut().wb_ct = 3000;
+        let mut output = anchor.gain;
+        for _ in 0..100 {
+            let prev = output;
+            step_limiter_tick(&ctx, anchor, &mut output, 0.05).unwrap();
+            assert!((output.rgain - prev.rgain).abs() <= prev.rgain * 0.05 + 1e-6);
+            assert_eq!(ctx.get_mwb_gain(), Ok(output));
+        }
+        assert_close(output.rgain, target.rgain);
+        assert_close(output.bgain, target.bgain);
+    }
+
+    #[test]
+    fn test_step_limiter_stop() {
+        use std::sync::atomic::AtomicUsize;
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&ticks);
+        let worker = StepLimiter::spawn(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Some(Duration::from_secs(60))
+        })
+        .unwrap();
+        while ticks.load(Ordering::SeqCst) == 0 {
+            thread::yield_now();
+        }
+        // 停止时唤醒等待中的线程并等待其退出，不必等满间隔。
+        let start = std::time::Instant::now();
+        worker.stop();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(ticks.load(Ordering::SeqCst), 1);
+
+        // `tick` 返回 `None` 时线程自行退出。
+        let worker = StepLimiter::spawn(|| None).unwrap();
+        while !worker.handle.is_finished() {
+            thread::yield_now();
+        }
+        worker.stop();
+    }
+
+    fn write(ctx: &MockContext, gain: WbGain) -> XCamResult<()> {
+        ctx.set_mwb_gain(gain)
+    }
+
+    fn gain(r: f32, b: f32) -> WbGain {
+        WbGain {
+            rgain: r,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: b,
+        }
+    }
+
+    #[test]
+    fn test_mwb_apply_mode_native() {
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().mwb_smooth = Some(false);
+        let mut state = MwbApplyState::default();
+        set_mwb_apply_mode(&ctx, &mut state, WbApplyMode::Smooth).unwrap();
+        assert_eq!(ctx.state.borrow().mwb_smooth, Some(true));
+        assert_eq!(
+            get_mwb_apply_mode(&ctx, &state),
+            Ok(MwbApply {
+                mode: WbApplyMode::Smooth,
+                emulated: false,
+            })
+        );
+
+        // 标志存在时直接写入目标增益，由 SDK 过渡。
+        ctx.state.borrow_mut().wb_gain = gain(1.0, 1.0);
+        apply_mwb_gain(&ctx, &mut state, gain(2.0, 1.5), write).unwrap();
+        assert_close(ctx.state.borrow().wb_gain.rgain, 2.0);
+        assert_eq!(tick_mwb_gain(&ctx, &mut state, write), Ok(false));
+
+        set_mwb_apply_mode(&ctx, &mut state, WbApplyMode::Immediate).unwrap();
+        assert_eq!(ctx.state.borrow().mwb_smooth, Some(false));
+        assert_eq!(
+            get_mwb_apply_mode(&ctx, &state).map(|a| a.mode),
+            Ok(WbApplyMode::Immediate)
+        );
+    }
+
+    #[test]
+    fn test_mwb_apply_mode_emulated() {
+        let ctx = MockContext::new();
+        let mut state = MwbApplyState::default();
+        set_mwb_apply_mode(&ctx, &mut state, WbApplyMode::Immediate).unwrap();
+        assert_eq!(
+            get_mwb_apply_mode(&ctx, &state),
+            Ok(MwbApply {
+                mode: WbApplyMode::Immediate,
+                emulated: false,
+            })
+        );
+
+        set_mwb_apply_mode(&ctx, &mut state, WbApplyMode::Smooth).unwrap();
+        assert_eq!(
+            get_mwb_apply_mode(&ctx, &state),
+            Ok(MwbApply {
+                mode: WbApplyMode::Smooth,
+                emulated: true,
+            })
+        );
+        ctx.state.borrow_mut().wb_gain = gain(1.0, 1.0);
+        apply_mwb_gain(&ctx, &mut state, gain(1.2, 0.95), write).unwrap();
+        assert_close(ctx.state.borrow().wb_gain.rgain, 1.1);
+        assert_close(ctx.state.borrow().wb_gain.bgain, 0.95);
+        assert_eq!(tick_mwb_gain(&ctx, &mut state, write), Ok(true));
+        assert_close(ctx.state.borrow().wb_gain.rgain, 1.2);
+        assert_eq!(tick_mwb_gain(&ctx, &mut state, write), Ok(false));
+
+        // 切换为立即生效后直接写入。
+        set_mwb_apply_mode(&ctx, &mut state, WbApplyMode::Immediate).unwrap();
+        apply_mwb_gain(&ctx, &mut state, gain(2.0, 1.0), write).unwrap();
+        assert_close(ctx.state.borrow().wb_gain.rgain, 2.0);
+    }
+
+    #[test]
+    fn test_wb_op_mode_conversion() {
+        for mode in [WbOpMode::Auto, WbOpMode::Manual] {
+            assert_eq!(WbOpMode::try_from(OpMode::from(mode)), Ok(mode));
+        }
+        for mode in [OpMode::Auto, OpMode::Manual] {
+            assert_eq!(WbOpMode::try_from(mode).map(OpMode::from), Ok(mode));
+        }
+        // 有损方向：半自动无法转换，`Max` 不能还原。
+        assert_eq!(
+            WbOpMode::try_from(OpMode::SemiAuto),
+            Err(XCamError::Unsupported)
+        );
+        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+        assert_eq!(
+            WbOpMode::try_from(OpMode::RegManual),
+            Err(XCamError::Unsupported)
+        );
+        assert_eq!(OpMode::from(WbOpMode::Max), OpMode::Invalid);
+        #[cfg(not(feature = "v1_0"))]
+        assert_eq!(
+            WbOpMode::try_from(OpMode::from(WbOpMode::Max)),
+            Err(XCamError::Unsupported)
+        );
+        #[cfg(feature = "v1_0")]
+        assert_eq!(
+            WbOpMode::try_from(OpMode::from(WbOpMode::Max)),
+            Ok(WbOpMode::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_neutral_patch() {
+        let unity = WbGain {
+            rgain: 1.0,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 1.0,
+        };
+        assert_eq!(WbGain::from_neutral_patch(0.5, 0.5, 0.5), unity);
+
+        // 偏蓝的灰色区域：蓝色增益小于 1，红色增益大于 1。
+        let gain = WbGain::from_neutral_patch(80.0, 120.0, 200.0);
+        assert_close(gain.rgain, 1.5);
+        assert_eq!((gain.grgain, gain.gbgain), (1.0, 1.0));
+        assert_close(gain.bgain, 0.6);
+
+        for (r, g, b) in [
+            (0.0, 1.0, 1.0),
+            (1.0, f32::NAN, 1.0),
+            (1.0, f32::INFINITY, 1.0),
+            (1.0, 1.0, -2.0),
+        ] {
+            assert_eq!(WbGain::from_neutral_patch(r, g, b), unity);
+        }
+    }
+
+    #[test]
+    fn test_kelvin_from_wb_gain() {
+        for k in [AWB_CT_MIN, 2000, 2856, 4000, 5000, 6504, 10000, AWB_CT_MAX] {
+            let est = kelvin_from_wb_gain(&wb_gain_from_kelvin(k));
+            assert!(est.abs_diff(k) * 200 <= k, "{} -> {}", k, est);
+        }
+        // 暖光下红色增益小、蓝色增益大。
+        let warm = wb_gain_from_kelvin(2856);
+        assert!(warm.rgain < 1.0 && warm.bgain > 1.0);
+        assert_eq!((warm.grgain, warm.gbgain), (1.0, 1.0));
+
+        // 与绿色增益及整体缩放无关，超出范围时限制在范围内。
+        let mut scaled = wb_gain_from_kelvin(5000);
+        scaled.rgain *= 2.0;
+        scaled.bgain *= 2.0;
+        scaled.grgain = 1.5;
+        assert_eq!(
+            kelvin_from_wb_gain(&scaled),
+            kelvin_from_wb_gain(&wb_gain_from_kelvin(5000))
+        );
+        assert_eq!(wb_gain_from_kelvin(500), wb_gain_from_kelvin(AWB_CT_MIN));
+        let hot = WbGain {
+            rgain: 10.0,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 0.1,
+        };
+        assert_eq!(kelvin_from_wb_gain(&hot), AWB_CT_MAX);
+    }
+
+    #[test]
+    fn test_illuminant_preset() {
+        let all = [
+            Illuminant::Horizon,
+            Illuminant::A,
+            Illuminant::Cwf,
+            Illuminant::Tl84,
+            Illuminant::D50,
+            Illuminant::D65,
+            Illuminant::D75,
+            Illuminant::Other,
+        ];
+        for illum in all {
+            let g = WbGain::for_illuminant(illum);
+            assert_eq!((g.grgain, g.gbgain), (1.0, 1.0), "{}", illum);
+            for c in [g.rgain, g.bgain] {
+                assert!(c.is_finite() && (0.5..=4.0).contains(&c), "{}", illum);
+            }
+        }
+        let a = WbGain::for_illuminant(Illuminant::A);
+        assert!(a.rgain < a.bgain);
+        // 色温越高，红色增益越大、蓝色增益越小。
+        let d65 = WbGain::for_illuminant(Illuminant::D65);
+        assert!(d65.rgain > d65.bgain);
+        assert!(a.rgain < d65.rgain && a.bgain > d65.bgain);
+    }
+
+    #[test]
+    fn test_gain_distance() {
+        let gain = |rgain, grgain, gbgain, bgain| WbGain {
+            rgain,
+            grgain,
+            gbgain,
+            bgain,
+        };
+        let base = gain(1.8, 1.0, 1.0, 2.1);
+        assert_eq!(base.distance(&base), 0.0);
+        // 整体缩放不计入距离。
+        assert_close(base.distance(&gain(3.6, 2.0, 2.0, 4.2)), 0.0);
+        assert_close(base.distance(&gain(3.6, 1.0, 1.0, 2.1)), 1.0);
+        assert_close(base.distance(&gain(3.6, 1.0, 1.0, 4.2)), 2f32.sqrt());
+        assert_close(
+            gain(3.6, 1.0, 1.0, 2.1).distance(&base),
+            base.distance(&gain(3.6, 1.0, 1.0, 2.1)),
+        );
+
+        // 绿色分离：绿色几何平均不变，R/G、B/G 不变，只剩 log2(Gr/Gb) 分量。
+        let split = gain(1.8, 2f32.sqrt(), 0.5f32.sqrt(), 2.1);
+        assert_close(base.distance(&split), 1.0);
+
+        assert!(!base.distance(&gain(0.0, 1.0, 1.0, 2.1)).is_finite());
+    }
+
+    #[test]
+    fn test_factory_wb_ratio() {
+        let gain = |rgain, grgain, gbgain, bgain| WbGain {
+            rgain,
+            grgain,
+            gbgain,
+            bgain,
+        };
+        let golden = gain(2.0, 1.0, 1.0, 1.6);
+        let ratio = factory_wb_ratio(golden, golden).unwrap();
+        assert_eq!(ratio, gain(1.0, 1.0, 1.0, 1.0));
+        assert_eq!(wb_gain_offset(golden, ratio), [0.0; 4]);
+
+        // 本机整体偏亮 10%：归一化到绿色后只剩红、蓝的相对偏差。
+        let ratio = factory_wb_ratio(golden, gain(2.42, 1.1, 1.1, 1.76)).unwrap();
+        assert_close(ratio.rgain, 1.1);
+        assert_close(ratio.grgain, 1.0);
+        assert_close(ratio.gbgain, 1.0);
+        assert_close(ratio.bgain, 1.0);
+        let offset = wb_gain_offset(golden, ratio);
+        assert_close(offset[0], 0.2);
+        assert_close(offset[3], 0.0);
+
+        // 两个绿色通道取均值。
+        let ratio = factory_wb_ratio(golden, gain(2.0, 0.9, 1.1, 1.6)).unwrap();
+        assert_close(ratio.grgain, 0.9);
+        assert_close(ratio.gbgain, 1.1);
+        assert_close(ratio.rgain, 1.0);
+
+        assert_eq!(factory_wb_ratio(golden, gain(0.0, 1.0, 1.0, 1.0)), None);
+        assert_eq!(
+            factory_wb_ratio(gain(f32::NAN, 1.0, 1.0, 1.0), golden),
+            None
+        );
+    }
+
+    #[test]
+    fn test_scale_manual_gain() {
+        let ctx = MockContext::new();
+        let base = WbGain {
+            rgain: 2.0,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 1.5,
+        };
+        ctx.state.borrow_mut().wb_gain = base;
+        let mut installed = None;
+        let ratio = WbGain {
+            rgain: 1.1,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 0.9,
+        };
+        scale_manual_gain(&ctx, &mut installed, ratio).unwrap();
+        let gain = ctx.state.borrow().wb_gain;
+        assert_close(gain.rgain, 2.2);
+        assert_close(gain.bgain, 1.35);
+        assert_eq!(installed, Some(ratio));
+
+        // 再次安装时先撤销之前的系数，不会累积。
+        let ratio = WbGain {
+            rgain: 0.8,
+            ..ratio
+        };
+        scale_manual_gain(&ctx, &mut installed, ratio).unwrap();
+        let gain = ctx.state.borrow().wb_gain;
+        assert_close(gain.rgain, 1.6);
+        assert_close(gain.bgain, 1.35);
+
+        ctx.fail("set_mwb_gain");
+        let unity = WbGain {
+            rgain: 1.0,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 1.0,
+        };
+        assert!(scale_manual_gain(&ctx, &mut installed, unity).is_err());
+        assert_eq!(installed, Some(ratio));
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    #[test]
+    fn test_release_damping_step() {
+        let state = |emulated| {
+            Some(AwbStepState {
+                limit: AwbStepLimit {
+                    per_frame_delta: 0.5,
+                    emulated,
+                },
+                worker: None,
+            })
+        };
+        // 非模拟的限制与速度共用阻尼系数，被速度覆盖后清除。
+        let mut damping = state(false);
+        release_damping_step(&mut damping);
+        assert!(damping.is_none());
+        // 模拟的限制在手动模式下工作，不受阻尼系数影响。
+        let mut emulated = state(true);
+        release_damping_step(&mut emulated);
+        assert_eq!(emulated.map(|s| s.limit.emulated), Some(true));
+        let mut none = None;
+        release_damping_step(&mut none);
+        assert!(none.is_none());
+    }
+
+    #[test]
+    fn test_awb_speed() {
+        let ctx = MockContext::new();
+        assert_eq!(ctx.get_awb_speed(), Ok(1.0));
+        ctx.set_awb_speed(0.25).unwrap();
+        assert_eq!(ctx.get_awb_speed(), Ok(0.25));
+        ctx.set_awb_speed(-1.0).unwrap();
+        assert_eq!(ctx.get_awb_speed(), Ok(0.0));
+        ctx.set_awb_speed(3.0).unwrap();
+        assert_eq!(ctx.get_awb_speed(), Ok(1.0));
+        assert_eq!(
+            ctx.set_awb_speed(f32::NAN),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        assert_eq!(ctx.get_awb_speed(), Ok(1.0));
+    }
+
+    #[test]
+    fn test_as_shot_neutral() {
+        // 日光下拍摄的 DNG 中常见的中性灰坐标。
+        let gain = WbGain::from_as_shot_neutral([0.4716, 1.0, 0.6452]);
+        assert_close(gain.rgain, 2.1204);
+        assert_eq!((gain.grgain, gain.gbgain), (1.0, 1.0));
+        assert_close(gain.bgain, 1.5499);
+
+        // 未以绿色归一化的中性灰坐标。
+        let gain = WbGain::from_as_shot_neutral([0.25, 0.5, 0.4]);
+        assert_close(gain.rgain, 2.0);
+        assert_close(gain.bgain, 1.25);
+
+        for neutral in [
+            [0.4716, 1.0, 0.6452],
+            [0.6211, 1.0, 0.4123],
+            [1.0, 1.0, 1.0],
+        ] {
+            let back = WbGain::from_as_shot_neutral(neutral).to_as_shot_neutral();
+            for (a, b) in back.iter().zip(neutral.iter()) {
+                assert_close(*a, *b);
+            }
+        }
+
+        // 绿色增益不为 1 时先归一化。
+        let gain = WbGain {
+            rgain: 4.0,
+            grgain: 2.0,
+            gbgain: 2.0,
+            bgain: 3.0,
+        };
+        let neutral = gain.to_as_shot_neutral();
+        assert_close(neutral[0], 0.5);
+        assert_eq!(neutral[1], 1.0);
+        assert_close(neutral[2], 2.0 / 3.0);
+    }
+
+    #[test]
+    fn test_dominant_illuminant() {
+        assert_eq!(Illuminant::from_name("d65"), Illuminant::D65);
+        assert_eq!(Illuminant::from_name(" TL84 "), Illuminant::Tl84);
+        assert_eq!(Illuminant::from_name("HZ"), Illuminant::Horizon);
+        assert_eq!(Illuminant::from_name("LED"), Illuminant::Other);
+        let estimate = dominant_illuminant([("A", 0.1), ("D65", 0.7), ("", 0.9), ("CWF", 0.2)]);
+        assert_eq!(
+            estimate,
+            Some(IlluminantEstimate {
+                illuminant: Illuminant::D65,
+                confidence: Some(0.7),
+            })
+        );
+        assert_eq!(dominant_illuminant([("D50", f32::NAN)]), None);
+        assert_eq!(
+            MockContext::new().get_awb_illuminant(),
+            Err(XCamError::Unsupported)
+        );
+    }
+
+    #[test]
+    fn test_limit_gain_step() {
+        let current = WbGain {
+            rgain: 2.0,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 1.0,
+        };
+        let target = WbGain {
+            rgain: 1.0,
+            grgain: 1.0,
+            gbgain: 1.02,
+            bgain: 3.0,
+        };
+        let next = limit_gain_step(current, target, 0.1);
+        assert_close(next.rgain, 1.8);
+        assert_close(next.grgain, 1.0);
+        assert_close(next.gbgain, 1.02);
+        assert_close(next.bgain, 1.1);
+
+        let current = WbGain {
+            rgain: f32::NAN,
+            ..current
+        };
+        assert_close(limit_gain_step(current, target, 0.1).rgain, 1.0);
+    }
+
+    #[test]
+    fn test_awb_snapshot_consistent() {
+        let ctx = MockContext::new();
+        let gain = WbGain {
+            rgain: 1.8,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 2.1,
+        };
+        ctx.set_wb_mode(OpMode::Manual).unwrap();
+        ctx.set_mwb_gain(gain).unwrap();
+        ctx.set_mwb_ct(4500).unwrap();
+        let snapshot = ctx.get_awb_snapshot().unwrap();
+        assert_eq!(snapshot.mode, OpMode::Manual);
+        assert_eq!(snapshot.gain, gain);
+        assert_eq!(snapshot.ct, 4500);
+        assert_eq!(snapshot.mode, ctx.get_wb_mode().unwrap());
+        assert_eq!(snapshot.gain, ctx.get_mwb_gain().unwrap());
+        assert_eq!(snapshot.ct, ctx.get_mwb_ct().unwrap());
+    }
+
+    #[test]
+    fn test_lock_awb_partial() {
+        let ctx = MockContext::new();
+        let gain = WbGain {
+            rgain: 1.6,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 2.4,
+        };
+        ctx.set_mwb_gain(gain).unwrap();
+        ctx.lock_awb_partial(LockScope::Gains).unwrap();
+        assert_eq!(ctx.get_wb_mode(), Ok(OpMode::Manual));
+        assert_eq!(ctx.get_mwb_gain(), Ok(gain));
+        assert!(!ctx.state.borrow().awb_locked);
+        ctx.unlock_awb_partial(LockScope::Gains).unwrap();
+        assert_eq!(ctx.get_wb_mode(), Ok(OpMode::Auto));
+
+        ctx.lock_awb_partial(LockScope::Full).unwrap();
+        assert!(ctx.state.borrow().awb_locked);
+        ctx.unlock_awb_partial(LockScope::Full).unwrap();
+        assert!(!ctx.state.borrow().awb_locked);
+
+        ctx.fail("set_mwb_gain");
+        assert!(ctx.lock_awb_partial(LockScope::Gains).is_err());
+        assert_eq!(ctx.get_wb_mode(), Ok(OpMode::Auto));
+    }
+
+    #[test]
+    fn test_set_manual_wb() {
+        let ctx = MockContext::new();
+        let gain = WbGain {
+            rgain: 1.8,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 2.1,
+        };
+        assert_eq!(ctx.get_wb_mode(), Ok(OpMode::Auto));
+        assert_eq!(ctx.set_manual_wb(gain), Ok(gain));
+        assert_eq!(ctx.get_wb_mode(), Ok(OpMode::Manual));
+        assert_eq!(ctx.get_mwb_gain(), Ok(gain));
+
+        // 读回失败时回到原来的自动模式。
+        let ctx = MockContext::new();
+        ctx.fail("get_mwb_gain");
+        assert_eq!(
+            ctx.set_manual_wb(gain),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))
+        );
+        assert_eq!(ctx.state.borrow().wb_mode, OpMode::Auto);
+        assert_eq!(
+            ctx.calls(),
+            [
+                "get_wb_mode",
+                "set_wb_mode",
+                "set_mwb_gain",
+                "get_mwb_gain",
+                "set_wb_mode"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_mwb_rb_gain() {
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().wb_gain = WbGain {
+            rgain: 1.6,
+            grgain: 1.12,
+            gbgain: 1.1,
+            bgain: 1.9,
+        };
+        ctx.set_mwb_rb_gain(2.0, 1.5).unwrap();
+        assert_eq!(ctx.calls(), ["get_mwb_gain", "set_mwb_gain"]);
+        assert_eq!(
+            ctx.state.borrow().wb_gain,
+            WbGain {
+                rgain: 2.0,
+                grgain: 1.12,
+                gbgain: 1.1,
+                bgain: 1.5,
+            }
+        );
+
+        let param = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        assert_eq!(ctx.set_mwb_rb_gain(0.0, 1.5), param);
+        assert_eq!(ctx.set_mwb_rb_gain(2.0, f32::NAN), param);
+        assert_eq!(ctx.calls().len(), 2);
+    }
+
+    #[test]
+    fn test_awb_ct_range() {
+        let param = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        let range = Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+        assert_eq!(check_awb_ct_range(3000, 6500), Ok(()));
+        assert_eq!(check_awb_ct_range(6500, 3000), param);
+        assert_eq!(check_awb_ct_range(5000, 5000), param);
+        assert_eq!(check_awb_ct_range(1000, 6500), range);
+        assert_eq!(check_awb_ct_range(3000, 20000), range);
+
+        #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+        {
+            use std::cell::Cell;
+            // 标定范围未启用且不是整数开尔文。
+            let calib = AwbCtLimit {
+                enable: false,
+                min: 2799.6,
+                max: 7000.4,
+            };
+            assert_eq!(calib.range(), None);
+            assert_eq!(
+                AwbCtLimit {
+                    enable: true,
+                    ..calib
+                }
+                .range(),
+                Some((2800, 7000))
+            );
+
+            let attr = Cell::new(calib);
+            let mut defaults = None;
+            let limit = |defaults: &mut Option<AwbCtLimit>, min_k, max_k| {
+                limit_awb_ct_range(
+                    defaults,
+                    min_k,
+                    max_k,
+                    || Ok(attr.get()),
+                    |l| {
+                        attr.set(l);
+                        Ok(())
+                    },
+                )
+            };
+            // 无效范围不读写属性。
+            assert_eq!(limit(&mut defaults, 5500, 4000), param);
+            assert_eq!((attr.get(), defaults), (calib, None));
+            assert_eq!(limit(&mut defaults, 3000, 6500), Ok(()));
+            assert_eq!(limit(&mut defaults, 4000, 5500), Ok(()));
+            assert_eq!(attr.get().range(), Some((4000, 5500)));
+            assert_eq!(defaults, Some(calib));
+
+            // 写入失败时不保存原范围。
+            let mut unsaved = None;
+            let failed = limit_awb_ct_range(
+                &mut unsaved,
+                3000,
+                6500,
+                || Ok(calib),
+                |_| Err(XCamError::Busy),
+            );
+            assert_eq!(failed, Err(XCamError::Busy));
+            assert_eq!(unsaved, None);
+
+            let restore = |defaults: &mut Option<AwbCtLimit>| {
+                restore_awb_ct_range(defaults, |l| {
+                    attr.set(l);
+                    Ok(())
+                })
+            };
+            assert_eq!(restore(&mut defaults), Ok(()));
+            assert_eq!((attr.get(), defaults), (calib, None));
+            attr.set(AwbCtLimit {
+                enable: true,
+                ..calib
+            });
+            assert_eq!(restore(&mut defaults), Ok(()));
+            assert!(attr.get().enable);
+        }
+
+        let ctx = MockContext::new();
+        ctx.reset_awb_ct_range().unwrap();
+        assert_eq!(ctx.get_awb_ct_range(), Ok(None));
+        ctx.set_awb_ct_range(3000, 6500).unwrap();
+        ctx.set_awb_ct_range(4000, 5500).unwrap();
+        assert_eq!(ctx.get_awb_ct_range(), Ok(Some((4000, 5500))));
+        assert!(ctx.set_awb_ct_range(5500, 4000).is_err());
+        ctx.reset_awb_ct_range().unwrap();
+        assert_eq!(ctx.get_awb_ct_range(), Ok(None));
+    }
+}
@@ -0,0 +1,268 @@
+//! 黑电平校正
+//!
+//! 传感器在无光照时的输出并不为零，BLC 模块从四个 Bayer 通道分别减去黑电平。IQ 文件按增益
+//! 标定黑电平，本模块以手动黑电平覆盖标定值，用于更换传感器批次或调试时的临时修正。
+//!
+//! `Context::set_black_level` 以传感器 RAW 数据的位深表示黑电平，超过该位深最大值的取值返回
+//! `XCAM_RETURN_ERROR_OUTOFRANGE`，不写入 ISP。ISP 的 BLC 按固定位宽表示黑电平，写入前按两者的
+//! 位数差换算：
+//!
+//! | 版本 | BLC 位宽 |
+//! |---|---|
+//! | v3_0 及以上的 ISP21、ISP30 | 12 位 |
+//! | 其余版本 | 不支持，返回 `XCamError::Unsupported` |
+use super::context::Context;
+use super::error::XCamError;
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+))]
+use super::ffi;
+use super::shared::SharedContext;
+use super::types::{RawBitDepth, XCamResult};
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+))]
+use super::uapi::{uapi_call, uapi_get};
+
+/// ISP BLC 的位宽，不支持 BLC 的版本为 `None`，见模块说明。
+pub const BLC_DEPTH: Option<RawBitDepth> = if cfg!(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+)) {
+    Some(RawBitDepth::Bits12)
+} else {
+    None
+};
+
+/// 一个描述四个 Bayer 通道黑电平的类型。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlackLevel {
+    pub r: u32,
+    pub gr: u32,
+    pub gb: u32,
+    pub b: u32,
+}
+
+impl BlackLevel {
+    /// 四个通道相同的黑电平。
+    pub const fn uniform(level: u32) -> Self {
+        Self {
+            r: level,
+            gr: level,
+            gb: level,
+            b: level,
+        }
+    }
+
+    /// 检查以位深 `depth` 表示的各通道，超过最大值时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+    pub fn check(&self, depth: RawBitDepth) -> XCamResult<()> {
+        [self.r, self.gr, self.gb, self.b]
+            .into_iter()
+            .try_for_each(|v| depth.check_black_level(v))
+    }
+
+    /// 由位深 `from` 换算为位深 `to`，位数减少时四舍五入。
+    pub fn rescale(self, from: RawBitDepth, to: RawBitDepth) -> Self {
+        let (from, to) = (from.bits(), to.bits());
+        let f = |v: u32| {
+            if to >= from {
+                v << (to - from)
+            } else {
+                let shift = from - to;
+                ((v + (1 << (shift - 1))) >> shift).min((1 << to) - 1)
+            }
+        };
+        Self {
+            r: f(self.r),
+            gr: f(self.gr),
+            gb: f(self.gb),
+            b: f(self.b),
+        }
+    }
+}
+
+/// 一个描述黑电平校正控制的契定。
+pub trait BlackLevelCorrection {
+    /// 获取手动黑电平，以 `BLC_DEPTH` 表示。
+    ///
+    /// 不支持 BLC 的版本返回 `XCamError::Unsupported`。
+    fn get_blc_manual(&self) -> XCamResult<BlackLevel> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 切换为手动 BLC 并写入黑电平 `level`，以 `BLC_DEPTH` 表示，超过其最大值时返回
+    /// `XCAM_RETURN_ERROR_OUTOFRANGE`。
+    ///
+    /// 按传感器位深设置见 `Context::set_black_level`。不支持 BLC 的版本返回 `XCamError::Unsupported`。
+    fn set_blc_manual(&self, level: BlackLevel) -> XCamResult<()> {
+        let _ = level;
+        Err(XCamError::Unsupported)
+    }
+}
+
+/// 检查以传感器位深 `depth` 表示的黑电平 `level`，通过后换算为 `BLC_DEPTH` 写入。
+pub(crate) fn set_sensor_black_level<C: BlackLevelCorrection + ?Sized>(
+    ctx: &C,
+    depth: RawBitDepth,
+    level: BlackLevel,
+) -> XCamResult<()> {
+    let blc = BLC_DEPTH.ok_or(XCamError::Unsupported)?;
+    level.check(depth)?;
+    ctx.set_blc_manual(level.rescale(depth, blc))
+}
+
+/// 读取手动黑电平并换算为传感器位深 `depth`。
+pub(crate) fn get_sensor_black_level<C: BlackLevelCorrection + ?Sized>(
+    ctx: &C,
+    depth: RawBitDepth,
+) -> XCamResult<BlackLevel> {
+    let blc = BLC_DEPTH.ok_or(XCamError::Unsupported)?;
+    ctx.get_blc_manual().map(|level| level.rescale(blc, depth))
+}
+
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+))]
+type BlcAttr = ffi::rk_aiq_blc_attrib_t;
+
+impl BlackLevelCorrection for Context {
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    ))]
+    fn get_blc_manual(&self) -> XCamResult<BlackLevel> {
+        let attr = uapi_get!(ffi::rk_aiq_user_api2_ablc_GetAttrib(self) -> BlcAttr)?;
+        let manual = &attr.stBlc0Manual;
+        let f = |v: i32| v.max(0) as u32;
+        Ok(BlackLevel {
+            r: f(manual.blc_r),
+            gr: f(manual.blc_gr),
+            gb: f(manual.blc_gb),
+            b: f(manual.blc_b),
+        })
+    }
+
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    ))]
+    fn set_blc_manual(&self, level: BlackLevel) -> XCamResult<()> {
+        level.check(RawBitDepth::Bits12)?;
+        let mut attr = uapi_get!(ffi::rk_aiq_user_api2_ablc_GetAttrib(self) -> BlcAttr)?;
+        attr.eMode = ffi::AblcOPMode_t::ABLC_OP_MODE_MANUAL;
+        let manual = &mut attr.stBlc0Manual;
+        manual.enable = true;
+        manual.blc_r = level.r as i32;
+        manual.blc_gr = level.gr as i32;
+        manual.blc_gb = level.gb as i32;
+        manual.blc_b = level.b as i32;
+        uapi_call!(ffi::rk_aiq_user_api2_ablc_SetAttrib(self, &mut attr))
+    }
+}
+
+impl BlackLevelCorrection for SharedContext {
+    fn get_blc_manual(&self) -> XCamResult<BlackLevel> {
+        self.inner.get_blc_manual()
+    }
+
+    fn set_blc_manual(&self, level: BlackLevel) -> XCamResult<()> {
+        self.inner.set_blc_manual(level)
+    }
+}
+
+impl Context {
+    /// 以传感器 RAW 数据的位深设置手动黑电平，见模块说明。
+    ///
+    /// 尚未 `prepare` 时返回 `XCAM_RETURN_ERROR_ORDER`；传感器输出不是 Bayer 格式或无法识别位深时
+    /// 返回 `XCamError::Unsupported`。
+    pub fn set_black_level(&self, level: BlackLevel) -> XCamResult<()> {
+        set_sensor_black_level(self, self.sensor_depth()?, level)
+    }
+
+    /// 获取手动黑电平，以传感器 RAW 数据的位深表示，见 `set_black_level`。
+    pub fn get_black_level(&self) -> XCamResult<BlackLevel> {
+        get_sensor_black_level(self, self.sensor_depth()?)
+    }
+
+    fn sensor_depth(&self) -> XCamResult<RawBitDepth> {
+        self.negotiated_format()?
+            .depth
+            .ok_or(XCamError::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi;
+    use crate::mock::MockContext;
+
+    #[test]
+    fn test_black_level_rescale() {
+        use RawBitDepth::*;
+        let level = BlackLevel::uniform(64);
+        assert_eq!(level.rescale(Bits10, Bits12), BlackLevel::uniform(256));
+        assert_eq!(level.rescale(Bits12, Bits12), level);
+        assert_eq!(
+            BlackLevel::uniform(258).rescale(Bits12, Bits10),
+            BlackLevel::uniform(65)
+        );
+        assert_eq!(
+            BlackLevel::uniform(65535).rescale(Bits16, Bits12),
+            BlackLevel::uniform(4095)
+        );
+    }
+
+    #[test]
+    fn test_set_sensor_black_level() {
+        let ctx = MockContext::new();
+        let level = BlackLevel {
+            r: 64,
+            gr: 65,
+            gb: 66,
+            b: 1023,
+        };
+        if BLC_DEPTH.is_none() {
+            assert_eq!(
+                set_sensor_black_level(&ctx, RawBitDepth::Bits10, level),
+                Err(XCamError::Unsupported)
+            );
+            return;
+        }
+        assert_eq!(
+            set_sensor_black_level(&ctx, RawBitDepth::Bits10, level),
+            Ok(())
+        );
+        assert_eq!(
+            ctx.get_blc_manual(),
+            Ok(BlackLevel {
+                r: 256,
+                gr: 260,
+                gb: 264,
+                b: 4092,
+            })
+        );
+        assert_eq!(get_sensor_black_level(&ctx, RawBitDepth::Bits10), Ok(level));
+
+        // 超过 10 位的取值不写入。
+        let before = ctx.calls().len();
+        assert_eq!(
+            set_sensor_black_level(&ctx, RawBitDepth::Bits10, BlackLevel { gb: 1024, ..level }),
+            Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE
+            ))
+        );
+        assert_eq!(ctx.calls().len(), before);
+        assert_eq!(ctx.get_blc_manual().unwrap().gb, 264);
+
+        // 同一取值在 12 位传感器上有效。
+        assert_eq!(
+            set_sensor_black_level(&ctx, RawBitDepth::Bits12, BlackLevel::uniform(1024)),
+            Ok(())
+        );
+        assert_eq!(ctx.get_blc_manual(), Ok(BlackLevel::uniform(1024)));
+    }
+}
@@ -0,0 +1,281 @@
+//! 控制量范围
+//!
+//! 查询各控制量的取值范围（最小值、最大值、步长、默认值），便于界面构建滑块等控件。
+//! 能从 SDK 属性中读取的范围以 SDK 为准，其余使用各版本的常量。曝光时间与增益取传感器可达的
+//! 范围，色温取标定文件中的范围，都不受用户以 `set_exp_time_range` 等接口收窄的影响。
+use super::ae::AutoExposure;
+use super::awb::AutoWhiteBalance;
+use super::context::Context;
+use super::error::XCamError;
+use super::types::XCamResult;
+
+/// 一个描述可调控制量的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ControlId {
+    /// 亮度等级。
+    Brightness,
+    /// 对比度等级。
+    Contrast,
+    /// 饱和度等级。
+    Saturation,
+    /// 色度等级。
+    Hue,
+    /// 锐化等级。
+    Sharpness,
+    /// 降噪强度。
+    NrStrength,
+    /// 空域降噪强度。
+    SpatialNrStrength,
+    /// 时域降噪强度。
+    TemporalNrStrength,
+    /// 曝光时间，单位：秒。
+    ExposureTime,
+    /// 曝光增益，单位：倍。
+    Gain,
+    /// 白平衡色温，单位：K。
+    ColorTemperature,
+    /// 去雾强度。
+    DehazeStrength,
+    /// HDR 强度。
+    HdrStrength,
+    /// 暗区提升强度。
+    DarkAreaBoostStrength,
+    /// 强光抑制强度。
+    HlcStrength,
+    /// 背光补偿强度。
+    BlcStrength,
+}
+
+/// 一个描述控制量取值范围的类型。
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    /// 最小值。
+    pub min: f32,
+    /// 最大值。
+    pub max: f32,
+    /// 步长。
+    pub step: f32,
+    /// 默认值。
+    pub default: f32,
+}
+
+impl Bounds {
+    const fn new(min: f32, max: f32, step: f32, default: f32) -> Self {
+        Self {
+            min,
+            max,
+            step,
+            default,
+        }
+    }
+
+    /// 以 SDK 返回的范围替换最小值、最大值，并将默认值限制在新范围内。
+    fn with_range(self, (min, max): (f32, f32)) -> Self {
+        Self {
+            min,
+            max,
+            default: self.default.clamp(min, max.max(min)),
+            ..self
+        }
+    }
+}
+
+impl ControlId {
+    /// 全部控制量，按声明顺序排列。
+    pub const ALL: [Self; 16] = [
+        Self::Brightness,
+        Self::Contrast,
+        Self::Saturation,
+        Self::Hue,
+        Self::Sharpness,
+        Self::NrStrength,
+        Self::SpatialNrStrength,
+        Self::TemporalNrStrength,
+        Self::ExposureTime,
+        Self::Gain,
+        Self::ColorTemperature,
+        Self::DehazeStrength,
+        Self::HdrStrength,
+        Self::DarkAreaBoostStrength,
+        Self::HlcStrength,
+        Self::BlcStrength,
+    ];
+}
+
+/// 控制量范围表中的一项。
+struct BoundsEntry<C: ?Sized> {
+    /// 当前版本下的常量范围。
+    fallback: Bounds,
+    /// 从 SDK 读取实际范围的方法，返回 `(min, max)`。
+    probe: Option<Probe<C>>,
+}
+
+/// 从 SDK 读取实际范围的方法，不支持时返回 `XCamError::Unsupported`。
+type Probe<C> = fn(&C) -> XCamResult<(f32, f32)>;
+
+#[cfg(feature = "v2_0")]
+const DEHAZE_BOUNDS: Bounds = Bounds::new(0.0, 10.0, 1.0, 5.0);
+#[cfg(not(feature = "v2_0"))]
+const DEHAZE_BOUNDS: Bounds = Bounds::new(0.0, 100.0, 1.0, 50.0);
+
+/// 控制量范围表。新增控制量时，在 `ControlId` 及 `ControlId::ALL` 中添加变体并在此追加一项。
+fn entry<C>(id: ControlId) -> BoundsEntry<C>
+where
+    C: AutoExposure + AutoWhiteBalance + ?Sized,
+{
+    use ControlId::*;
+    let (fallback, probe): (Bounds, Option<Probe<C>>) = match id {
+        Brightness | Contrast | Hue => (Bounds::new(0.0, 255.0, 1.0, 128.0), None),
+        Saturation | Sharpness | NrStrength | SpatialNrStrength | TemporalNrStrength => {
+            (Bounds::new(0.0, 100.0, 1.0, 50.0), None)
+        }
+        ExposureTime => (
+            Bounds::new(0.0001, 1.0 / 15.0, 0.0001, 1.0 / 30.0),
+            Some(probe_exp_time),
+        ),
+        Gain => (Bounds::new(1.0, 64.0, 0.0625, 1.0), Some(probe_exp_gain)),
+        ColorTemperature => (Bounds::new(2000.0, 10000.0, 100.0, 5000.0), Some(probe_ct)),
+        DehazeStrength => (DEHAZE_BOUNDS, None),
+        HdrStrength | HlcStrength | BlcStrength => (Bounds::new(1.0, 100.0, 1.0, 50.0), None),
+        DarkAreaBoostStrength => (Bounds::new(1.0, 10.0, 1.0, 1.0), None),
+    };
+    BoundsEntry { fallback, probe }
+}
+
+fn probe_exp_time<C: AutoExposure + ?Sized>(ctx: &C) -> XCamResult<(f32, f32)> {
+    ctx.get_sensor_exp_time_range()
+}
+
+fn probe_exp_gain<C: AutoExposure + ?Sized>(ctx: &C) -> XCamResult<(f32, f32)> {
+    ctx.get_sensor_gain_range()
+}
+
+/// 标定未限制色温时视为不支持，使用常量范围。
+fn probe_ct<C: AutoWhiteBalance + ?Sized>(ctx: &C) -> XCamResult<(f32, f32)> {
+    ctx.get_awb_calib_ct_range()?
+        .map(|(min, max)| (min as f32, max as f32))
+        .ok_or(XCamError::Unsupported)
+}
+
+impl<C: ?Sized> BoundsEntry<C> {
+    /// 以 `probe` 读取范围，不支持时使用常量范围。
+    fn resolve(&self, ctx: &C) -> XCamResult<Bounds> {
+        match self.probe.map(|probe| probe(ctx)) {
+            Some(Ok(range)) => Ok(self.fallback.with_range(range)),
+            Some(Err(XCamError::Unsupported)) | None => Ok(self.fallback),
+            Some(Err(e)) => Err(e),
+        }
+    }
+}
+
+/// 获取 `ctx` 中指定控制量的取值范围，见 `Context::control_bounds`。
+pub(crate) fn control_bounds_of<C>(ctx: &C, id: ControlId) -> XCamResult<Bounds>
+where
+    C: AutoExposure + AutoWhiteBalance + ?Sized,
+{
+    entry(id).resolve(ctx)
+}
+
+impl Context {
+    /// 获取指定控制量的取值范围。
+    pub fn control_bounds(&self, id: ControlId) -> XCamResult<Bounds> {
+        control_bounds_of(self, id)
+    }
+
+    /// 获取全部控制量的取值范围，按 `ControlId::ALL` 的顺序排列。
+    pub fn all_control_bounds(&self) -> XCamResult<Vec<(ControlId, Bounds)>> {
+        ControlId::ALL
+            .into_iter()
+            .map(|id| control_bounds_of(self, id).map(|b| (id, b)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+
+    #[test]
+    fn test_bounds_table_consistent() {
+        for (i, id) in ControlId::ALL.into_iter().enumerate() {
+            let b = entry::<MockContext>(id).fallback;
+            assert!(b.min <= b.default && b.default <= b.max, "{:?}", id);
+            assert!(b.step > 0.0, "{:?}", id);
+            assert!(ControlId::ALL[i + 1..].iter().all(|x| *x != id));
+        }
+    }
+
+    #[test]
+    fn test_all_controls_listed() {
+        use ControlId::*;
+        // 新增变体而未列入此处时无法通过编译，提醒同时按声明顺序加入 `ControlId::ALL`。
+        let ordinal = |id: ControlId| match id {
+            Brightness => 0,
+            Contrast => 1,
+            Saturation => 2,
+            Hue => 3,
+            Sharpness => 4,
+            NrStrength => 5,
+            SpatialNrStrength => 6,
+            TemporalNrStrength => 7,
+            ExposureTime => 8,
+            Gain => 9,
+            ColorTemperature => 10,
+            DehazeStrength => 11,
+            HdrStrength => 12,
+            DarkAreaBoostStrength => 13,
+            HlcStrength => 14,
+            BlcStrength => 15,
+        };
+        for (i, id) in ControlId::ALL.into_iter().enumerate() {
+            assert_eq!(ordinal(id), i, "{:?}", id);
+        }
+    }
+
+    #[test]
+    fn test_bounds_with_range() {
+        let b = Bounds::new(0.0, 100.0, 1.0, 50.0).with_range((60.0, 80.0));
+        assert_eq!(b, Bounds::new(60.0, 80.0, 1.0, 60.0));
+    }
+
+    #[test]
+    fn test_bounds_ignore_user_range() {
+        let ctx = MockContext::new();
+        {
+            let mut s = ctx.state.borrow_mut();
+            s.exposure_steps = Some(vec![0.0001, 0.01, 0.04]);
+            s.awb_ct_range = Some((2500, 7500));
+        }
+        // 用户先收窄曝光与色温范围，范围仍取传感器与标定的范围。
+        ctx.set_exp_time_range(0.001, 0.01).unwrap();
+        ctx.set_exp_gain_range(1.0, 8.0).unwrap();
+        ctx.set_awb_ct_range(4000, 5000).unwrap();
+        assert_eq!(
+            control_bounds_of(&ctx, ControlId::ExposureTime),
+            Ok(Bounds::new(0.0001, 0.04, 0.0001, 1.0 / 30.0))
+        );
+        assert_eq!(
+            control_bounds_of(&ctx, ControlId::Gain),
+            Ok(Bounds::new(1.0, 64.0, 0.0625, 1.0))
+        );
+        assert_eq!(
+            control_bounds_of(&ctx, ControlId::ColorTemperature),
+            Ok(Bounds::new(2500.0, 7500.0, 100.0, 5000.0))
+        );
+
+        // 无法读取时使用常量范围，其余错误照常返回。
+        ctx.state.borrow_mut().exposure_steps = None;
+        assert_eq!(
+            control_bounds_of(&ctx, ControlId::ExposureTime),
+            Ok(entry::<MockContext>(ControlId::ExposureTime).fallback)
+        );
+        ctx.fail("get_sensor_gain_range");
+        assert_eq!(
+            control_bounds_of(&ctx, ControlId::Gain),
+            Err(XCamError::from(
+                crate::ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED
+            ))
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! 带缓存的读取接口
+//!
+//! 界面以 60 Hz 轮询参数时，每次读取都要经过 FFI，偶尔还会遇到 SDK 忙。`Context::cached`
+//! 返回的 `CachedContext` 在 `ttl` 内直接返回上次读取成功的值，过期后重新读取；
+//! 读取失败的结果不缓存。缓存按参数分别记录，可在多个线程间共享。
+//!
+//! 通过同一视图调用的设置接口会使受影响的缓存失效，例如 `set_mwb_ct` 同时使白平衡的
+//! 模式、场景、增益与色温失效。通过其他途径修改的参数，包括直接调用 `Context`、
+//! 另一个视图以及自动算法的调节，要等到过期或调用 `invalidate` 后才能读到。
+use super::acm::AutoColorManagment;
+use super::ae::AutoExposure;
+use super::af::AutoFocus;
+use super::awb::AutoWhiteBalance;
+use super::context::Context;
+use super::defog::Defog;
+use super::nr::NoiseRemoval;
+use super::sharpen::Sharpen;
+use super::types::{OpMode, Strength, WbGain, WbScene, XCamResult};
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 缓存的键，每个参数一个。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Control {
+    ExpMode,
+    WbMode,
+    MwbScene,
+    MwbGain,
+    MwbCt,
+    FocusMode,
+    NrMode,
+    AnrStrength,
+    Sharpness,
+    Brightness,
+    Contrast,
+    Saturation,
+    Hue,
+    DhzMode,
+}
+
+/// 白平衡的各参数互相影响，任一设置都使全部失效。
+const WB: &[Control] = &[
+    Control::WbMode,
+    Control::MwbScene,
+    Control::MwbGain,
+    Control::MwbCt,
+];
+
+#[derive(Default)]
+struct Cache {
+    /// 每次失效递增，用于丢弃失效前开始的读取结果。
+    generation: u64,
+    entries: HashMap<Control, (Instant, Box<dyn Any + Send>)>,
+}
+
+/// 一个缓存读取结果的 `Context` 借用，见模块说明。
+pub struct CachedContext<'a, C = Context> {
+    ctx: &'a C,
+    ttl: Duration,
+    cache: Mutex<Cache>,
+}
+
+/// 生成带缓存的读取接口。
+macro_rules! cached_getters {
+    ($($trait:ident::$name:ident => $key:ident -> $ret:ty;)*) => {
+        $(
+            #[doc = concat!("见 `", stringify!($trait), "::", stringify!($name), "`，`ttl` 内返回缓存的值。")]
+            pub fn $name(&self) -> XCamResult<$ret>
+            where
+                C: $trait,
+            {
+                self.fetch(Control::$key, || $trait::$name(self.ctx))
+            }
+        )*
+    };
+}
+
+/// 生成调用后使缓存失效的设置接口。
+macro_rules! cached_setters {
+    ($($trait:ident::$name:ident($($arg:ident: $ty:ty),*) => $keys:expr;)*) => {
+        $(
+            #[doc = concat!("见 `", stringify!($trait), "::", stringify!($name), "`，调用后使受影响的缓存失效。")]
+            pub fn $name(&self, $($arg: $ty),*) -> XCamResult<()>
+            where
+                C: $trait,
+            {
+                let ret = $trait::$name(self.ctx, $($arg),*);
+                self.invalidate_controls($keys);
+                ret
+            }
+        )*
+    };
+}
+
+impl<'a, C> CachedContext<'a, C> {
+    /// 创建 `ctx` 的缓存视图，缓存的值在 `ttl` 后过期，`Duration::ZERO` 等同不缓存。
+    pub fn new(ctx: &'a C, ttl: Duration) -> Self {
+        Self {
+            ctx,
+            ttl,
+            cache: Mutex::new(Cache::default()),
+        }
+    }
+
+    /// 缓存的有效期。
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// 使全部缓存失效，下次读取时重新查询。
+    pub fn invalidate(&self) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.generation += 1;
+        cache.entries.clear();
+    }
+
+    fn invalidate_controls(&self, keys: &[Control]) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.generation += 1;
+        for key in keys {
+            cache.entries.remove(key);
+        }
+    }
+
+    /// 返回未过期的缓存值，否则调用 `query` 读取并缓存成功的结果。
+    ///
+    /// 读取期间不持有锁；读取期间发生失效时结果只返回，不写入缓存。
+    fn fetch<T, F>(&self, key: Control, query: F) -> XCamResult<T>
+    where
+        T: Clone + Send + 'static,
+        F: FnOnce() -> XCamResult<T>,
+    {
+        let generation = {
+            let cache = self.cache.lock().unwrap();
+            if let Some((at, val)) = cache.entries.get(&key) {
+                if at.elapsed() < self.ttl {
+                    if let Some(val) = val.downcast_ref::<T>() {
+                        return Ok(val.clone());
+                    }
+                }
+            }
+            cache.generation
+        };
+        let val = query()?;
+        let mut cache = self.cache.lock().unwrap();
+        if cache.generation == generation {
+            cache
+                .entries
+                .insert(key, (Instant::now(), Box::new(val.clone())));
+        }
+        Ok(val)
+    }
+
+    cached_getters! {
+        AutoExposure::get_exp_mode => ExpMode -> OpMode;
+        AutoWhiteBalance::get_wb_mode => WbMode -> OpMode;
+        AutoWhiteBalance::get_mwb_scene => MwbScene -> WbScene;
+        AutoWhiteBalance::get_mwb_gain => MwbGain -> WbGain;
+        AutoWhiteBalance::get_mwb_ct => MwbCt -> u32;
+        AutoFocus::get_focus_mode => FocusMode -> OpMode;
+        NoiseRemoval::get_nr_mode => NrMode -> OpMode;
+        NoiseRemoval::get_anr_strength => AnrStrength -> Strength;
+        Sharpen::get_sharpness => Sharpness -> Strength;
+        AutoColorManagment::get_brightness => Brightness -> u32;
+        AutoColorManagment::get_contrast => Contrast -> u32;
+        AutoColorManagment::get_saturation => Saturation -> Strength;
+        AutoColorManagment::get_hue => Hue -> u32;
+        Defog::get_dhz_mode => DhzMode -> OpMode;
+    }
+
+    cached_setters! {
+        AutoExposure::set_exp_mode(mode: OpMode) => &[Control::ExpMode];
+        AutoWhiteBalance::set_wb_mode(mode: OpMode) => WB;
+        AutoWhiteBalance::set_mwb_scene(scene: WbScene) => WB;
+        AutoWhiteBalance::set_mwb_gain(gain: WbGain) => WB;
+        AutoWhiteBalance::set_mwb_ct(ct: u32) => WB;
+        AutoFocus::set_focus_mode(mode: OpMode) => &[Control::FocusMode];
+        NoiseRemoval::set_nr_mode(mode: OpMode) => &[Control::NrMode, Control::AnrStrength];
+        NoiseRemoval::set_anr_strength(strength: Strength) => &[Control::NrMode, Control::AnrStrength];
+        Sharpen::set_sharpness(level: Strength) => &[Control::Sharpness];
+        AutoColorManagment::set_brightness(val: u32) => &[Control::Brightness];
+        AutoColorManagment::set_contrast(val: u32) => &[Control::Contrast];
+        AutoColorManagment::set_saturation(val: Strength) => &[Control::Saturation];
+        AutoColorManagment::set_hue(val: u32) => &[Control::Hue];
+        Defog::set_dhz_mode(mode: OpMode) => &[Control::DhzMode];
+    }
+}
+
+impl Context {
+    /// 返回缓存读取结果的视图，见 `CachedContext`。
+    pub fn cached(&self, ttl: Duration) -> CachedContext<'_> {
+        CachedContext::new(self, ttl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::XCamError;
+    use crate::ffi;
+    use crate::mock::MockContext;
+
+    fn count(ctx: &MockContext, name: &str) -> usize {
+        ctx.calls().iter().filter(|c| **c == name).count()
+    }
+
+    #[test]
+    fn test_cached_getters() {
+        let ctx = MockContext::new();
+        let cached = CachedContext::new(&ctx, Duration::from_secs(60));
+        ctx.state.borrow_mut().cproc.brightness = 40;
+        assert_eq!(cached.get_brightness(), Ok(40));
+        // 绕过视图的修改在失效前读不到。
+        ctx.state.borrow_mut().cproc.brightness = 60;
+        assert_eq!(cached.get_brightness(), Ok(40));
+        assert_eq!(count(&ctx, "get_brightness"), 1);
+        cached.invalidate();
+        assert_eq!(cached.get_brightness(), Ok(60));
+        assert_eq!(count(&ctx, "get_brightness"), 2);
+
+        // 读取失败的结果不缓存。
+        ctx.fail("get_hue");
+        let err = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED));
+        assert_eq!(cached.get_hue(), err);
+        assert_eq!(cached.get_hue(), err);
+        assert_eq!(count(&ctx, "get_hue"), 2);
+
+        let uncached = CachedContext::new(&ctx, Duration::ZERO);
+        uncached.get_contrast().unwrap();
+        uncached.get_contrast().unwrap();
+        assert_eq!(count(&ctx, "get_contrast"), 2);
+    }
+
+    #[test]
+    fn test_cached_invalidate_on_set() {
+        let ctx = MockContext::new();
+        let cached = CachedContext::new(&ctx, Duration::from_secs(60));
+        cached.get_brightness().unwrap();
+        cached.get_contrast().unwrap();
+        assert_eq!(cached.set_brightness(70), Ok(()));
+        assert_eq!(cached.get_brightness(), Ok(70));
+        assert_eq!(count(&ctx, "get_brightness"), 2);
+        // 其他参数的缓存不受影响。
+        cached.get_contrast().unwrap();
+        assert_eq!(count(&ctx, "get_contrast"), 1);
+
+        // 设置色温使白平衡的全部缓存失效。
+        let gain = cached.get_mwb_gain().unwrap();
+        assert_eq!(cached.set_mwb_ct(3000), Ok(()));
+        ctx.state.borrow_mut().wb_gain.rgain = gain.rgain + 1.0;
+        assert_eq!(cached.get_mwb_ct(), Ok(3000));
+        assert_eq!(cached.get_mwb_gain().unwrap().rgain, gain.rgain + 1.0);
+
+        // 设置失败时同样失效。
+        cached.get_hue().unwrap();
+        ctx.fail("set_hue");
+        assert!(cached.set_hue(10).is_err());
+        cached.get_hue().unwrap();
+        assert_eq!(count(&ctx, "get_hue"), 2);
+    }
+}
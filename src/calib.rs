@@ -0,0 +1,642 @@
+//! IQ 标定文件
+//!
+//! 在创建上下文之前检查 IQ 文件是否与传感器匹配，并列出其中的场景。
+//!
+//! SDK 的标定数据库接口只能在上下文创建之后使用，因此本模块自行读取 IQ 文件：
+//! 启用 `json` 特性时，JSON 格式的文件以 `serde_json` 解析顶层的已知字段；其余格式（v1_0、v2_0
+//! 的 XML 文件与二进制 IQ 文件）以及未启用该特性时只能得到文件名中的信息。传感器名称按 SDK 的命名约定 `<传感器>_<模组>_<镜头>.<扩展名>`
+//! 取自文件名，这也是 SDK 按传感器选择 IQ 文件的依据。
+use super::error::XCamError;
+use super::ffi;
+use super::types::XCamResult;
+#[cfg(feature = "json")]
+use serde_json::Value;
+use std::fmt;
+use std::fs::DirBuilder;
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// SDK 接受的 IQ 文件扩展名，按 SDK 的查找顺序排列。
+#[cfg(any(feature = "v1_0", feature = "v2_0"))]
+pub const CALIB_EXTENSIONS: &[&str] = &["xml"];
+/// SDK 接受的 IQ 文件扩展名，按 SDK 的查找顺序排列。
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+pub const CALIB_EXTENSIONS: &[&str] = &["json", "bin"];
+
+/// sysfs 中 I2C 设备的目录，见 `module_lens_names`。
+const I2C_DEVICES: &str = "/sys/bus/i2c/devices";
+
+/// 一个描述 IQ 文件错误的枚举。
+#[derive(Debug)]
+pub enum CalibError {
+    /// 读取文件失败。
+    Io(io::Error),
+    /// 解析 JSON 失败。
+    #[cfg(feature = "json")]
+    Parse(serde_json::Error),
+    /// 文件名不符合 IQ 文件的命名约定。
+    BadFileName(PathBuf),
+    /// IQ 文件与传感器不匹配。
+    SensorMismatch {
+        /// 传感器实体对应的传感器名称。
+        sensor: String,
+        /// IQ 文件的传感器名称。
+        calib: String,
+    },
+    /// IQ 文件目录中没有与传感器匹配的 IQ 文件。
+    Missing {
+        /// SDK 查找的文件名，见 `expected_calib_names`。
+        expected_names: Vec<String>,
+        /// 查找的目录。
+        searched_dir: PathBuf,
+    },
+}
+
+impl fmt::Display for CalibError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            #[cfg(feature = "json")]
+            Self::Parse(e) => write!(f, "Parse error: {}", e),
+            Self::BadFileName(p) => write!(f, "Not an IQ file name: {}", p.display()),
+            Self::SensorMismatch { sensor, calib } => write!(
+                f,
+                "IQ file is for sensor `{}`, but the connected sensor is `{}`",
+                calib, sensor
+            ),
+            Self::Missing {
+                expected_names,
+                searched_dir,
+            } => write!(
+                f,
+                "No IQ file in {}, expected {}",
+                searched_dir.display(),
+                expected_names.join(" or ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CalibError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            #[cfg(feature = "json")]
+            Self::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CalibError {
+    fn from(val: io::Error) -> Self {
+        Self::Io(val)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for CalibError {
+    fn from(val: serde_json::Error) -> Self {
+        Self::Parse(val)
+    }
+}
+
+/// `CalibError::Missing` 为 `io::ErrorKind::NotFound`，其余为 `io::ErrorKind::InvalidInput`，
+/// 原错误可通过 `get_ref` 取回。
+impl From<CalibError> for io::Error {
+    fn from(val: CalibError) -> Self {
+        match val {
+            CalibError::Io(e) => e,
+            e @ CalibError::Missing { .. } => io::Error::new(io::ErrorKind::NotFound, e),
+            e => io::Error::new(io::ErrorKind::InvalidInput, e),
+        }
+    }
+}
+
+/// 一个描述 IQ 文件中场景的类型。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CalibScene {
+    /// 主场景名称，例如 `normal`、`hdr`。
+    pub main: String,
+    /// 子场景名称，例如 `day`、`night`。
+    pub sub: String,
+}
+
+/// 输出形如 `normal/day`。
+impl fmt::Display for CalibScene {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.main, self.sub)
+    }
+}
+
+/// 一个描述 IQ 标定文件的类型。
+#[derive(Clone, Debug)]
+pub struct CalibFile {
+    path: PathBuf,
+    sensor: String,
+    scenes: Option<Vec<CalibScene>>,
+    version: Option<String>,
+}
+
+impl CalibFile {
+    /// 打开并解析 IQ 文件。
+    ///
+    /// 启用 `json` 特性时解析扩展名为 `.json` 的文件的内容，其余情况只检查文件名。
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CalibError> {
+        let path = path.as_ref();
+        let is_json = path
+            .extension()
+            .map(|e| e.eq_ignore_ascii_case("json"))
+            .unwrap_or(false);
+        if is_json {
+            Self::from_json(path, &std::fs::read_to_string(path)?)
+        } else {
+            std::fs::metadata(path)?;
+            Ok(Self {
+                sensor: sensor_from_file_name(path)?,
+                path: path.to_owned(),
+                scenes: None,
+                version: None,
+            })
+        }
+    }
+
+    /// 以 `path` 为文件名解析 JSON 格式的 IQ 内容。
+    #[cfg(feature = "json")]
+    pub(crate) fn from_json(path: &Path, text: &str) -> Result<Self, CalibError> {
+        let sensor = sensor_from_file_name(path)?;
+        let root: Value = serde_json::from_str(text)?;
+        let name = |v: &Value| {
+            v.get("name")
+                .and_then(Value::as_str)
+                .unwrap_or("")
+                .to_owned()
+        };
+        let scenes = root
+            .get("main_scene")
+            .and_then(Value::as_array)
+            .map(|mains| {
+                mains
+                    .iter()
+                    .flat_map(|main| {
+                        let main_name = name(main);
+                        main.get("sub_scene")
+                            .and_then(Value::as_array)
+                            .map_or(&[][..], Vec::as_slice)
+                            .iter()
+                            .map(move |sub| CalibScene {
+                                main: main_name.clone(),
+                                sub: name(sub),
+                            })
+                    })
+                    .collect()
+            });
+        let version = root
+            .get("version")
+            .or_else(|| root.get("header").and_then(|h| h.get("version")))
+            .and_then(Value::as_str)
+            .map(str::to_owned);
+        Ok(Self {
+            path: path.to_owned(),
+            sensor,
+            scenes,
+            version,
+        })
+    }
+
+    /// 未启用 `json` 特性时不解析内容，只取文件名中的信息。
+    #[cfg(not(feature = "json"))]
+    pub(crate) fn from_json(path: &Path, _text: &str) -> Result<Self, CalibError> {
+        Ok(Self {
+            sensor: sensor_from_file_name(path)?,
+            path: path.to_owned(),
+            scenes: None,
+            version: None,
+        })
+    }
+
+    /// 文件路径。
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 传感器名称，取自文件名，例如 `ov5695`。
+    pub fn sensor_name(&self) -> &str {
+        &self.sensor
+    }
+
+    /// 文件中的 `主场景/子场景` 列表，非 JSON 文件返回 `None`。
+    pub fn scenes(&self) -> Option<&[CalibScene]> {
+        self.scenes.as_deref()
+    }
+
+    /// 生成文件的 SDK 版本，文件中没有记录时返回 `None`。
+    pub fn sdk_version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// 检查 IQ 文件是否与传感器实体匹配，传感器名称不区分大小写。
+    pub fn check_sensor(&self, sns_ent_name: &str) -> Result<(), CalibError> {
+        let sensor = sensor_from_entity(sns_ent_name);
+        if sensor.eq_ignore_ascii_case(&self.sensor) {
+            Ok(())
+        } else {
+            Err(CalibError::SensorMismatch {
+                sensor: sensor.to_owned(),
+                calib: self.sensor.clone(),
+            })
+        }
+    }
+}
+
+/// 临时 IQ 文件目录的序号，区分同一进程中的多个目录。
+static TEMP_CALIB_SEQ: AtomicU32 = AtomicU32::new(0);
+
+/// 一个描述由内存中的 IQ 内容写出的临时文件的类型，析构时连同所在目录一起删除。
+///
+/// 本库绑定的各版本 SDK 都只能按路径加载 IQ 文件，`Context::init_from_buffer` 与
+/// `SystemControl::update_iq_from_buffer` 借助该类型将内容写入私有的临时目录后再加载。
+pub(crate) struct TempCalib {
+    dir: PathBuf,
+    file_name: String,
+}
+
+impl TempCalib {
+    /// 检查 `data` 是可解析的 JSON IQ 内容，写入新建的临时目录，文件名为 `<stem>.json`。
+    ///
+    /// 内容不是 UTF-8 或无法解析（未启用 `json` 特性时不检查）时返回
+    /// `XCAM_RETURN_ERROR_PARAM`，写入失败时返回
+    /// `XCAM_RETURN_ERROR_FILE`，均不留下文件。
+    pub(crate) fn write(stem: &str, data: &[u8]) -> XCamResult<Self> {
+        let dir = std::env::temp_dir().join(format!(
+            "rkaiq-iq-{}-{}",
+            std::process::id(),
+            TEMP_CALIB_SEQ.fetch_add(1, Ordering::Relaxed)
+        ));
+        Self::write_in(dir, stem, data)
+    }
+
+    /// 同 `write`，目录为 `dir`。
+    ///
+    /// 目录以 0700 权限新建，已存在时（包括符号链接）返回 `XCAM_RETURN_ERROR_FILE`，
+    /// 不写入也不删除该目录，避免在可预测的路径上复用他人预先创建的目录。
+    fn write_in(dir: PathBuf, stem: &str, data: &[u8]) -> XCamResult<Self> {
+        let param = || XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM);
+        let file_name = format!("{}.json", stem);
+        let text = std::str::from_utf8(data).map_err(|_| param())?;
+        CalibFile::from_json(Path::new(&file_name), text).map_err(|_| param())?;
+        let file_error = |_| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FILE);
+        DirBuilder::new()
+            .mode(0o700)
+            .create(&dir)
+            .map_err(file_error)?;
+        // 目录由本次调用创建，此后失败时由析构删除。
+        let calib = Self { dir, file_name };
+        std::fs::write(calib.path(), data).map_err(file_error)?;
+        Ok(calib)
+    }
+
+    /// 所在目录。
+    pub(crate) fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 文件名，不含目录。
+    pub(crate) fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// 文件路径。
+    pub(crate) fn path(&self) -> PathBuf {
+        self.dir.join(&self.file_name)
+    }
+}
+
+impl Drop for TempCalib {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// 按 `<传感器>_<模组>_<镜头>` 的约定从文件名中取出传感器名称。
+fn sensor_from_file_name(path: &Path) -> Result<String, CalibError> {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .and_then(|s| s.split('_').next())
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .ok_or_else(|| CalibError::BadFileName(path.to_owned()))
+}
+
+/// 读取传感器的模组与镜头名称，即 IQ 文件名中的 `<模组>`、`<镜头>`。
+///
+/// 传感器驱动从设备树属性 `rockchip,camera-module-name`、`rockchip,camera-module-lens-name`
+/// 读取这两个名称并在 SDK 初始化时报告；本函数经 sysfs 中 I2C 设备的 `of_node` 读取同一属性，
+/// 设备名取自实体名称中的 `<总线>-<地址>`，例如 `m00_b_ov5695 4-0036` 的 `4-0036`。
+/// 无法读取时返回 `None`。
+pub fn module_lens_names(sns_ent_name: &str) -> Option<(String, String)> {
+    module_lens_names_in(Path::new(I2C_DEVICES), sns_ent_name)
+}
+
+fn module_lens_names_in(i2c_devices: &Path, sns_ent_name: &str) -> Option<(String, String)> {
+    let mut parts = sns_ent_name.split_whitespace().nth(1)?.splitn(3, '-');
+    let device = format!("{}-{}", parts.next()?, parts.next()?);
+    let node = i2c_devices.join(device).join("of_node");
+    let read = |prop: &str| {
+        let val = std::fs::read(node.join(prop)).ok()?;
+        let val = String::from_utf8(val).ok()?;
+        let val = val.trim_end_matches('\0');
+        (!val.is_empty()).then(|| val.to_owned())
+    };
+    Some((
+        read("rockchip,camera-module-name")?,
+        read("rockchip,camera-module-lens-name")?,
+    ))
+}
+
+/// 按传感器实体名称查找 IQ 文件时 SDK 使用的文件名，按 `CALIB_EXTENSIONS` 的顺序排列，
+/// 例如 `ov5695_TongJu_CHT842-MD.json`。
+///
+/// 模组与镜头名称见 `module_lens_names`，无法读取时以 `<module>`、`<lens>` 占位。
+pub fn expected_calib_names(sns_ent_name: &str) -> Vec<String> {
+    calib_names(
+        sensor_from_entity(sns_ent_name),
+        module_lens_names(sns_ent_name).as_ref(),
+    )
+}
+
+fn calib_names(sensor: &str, module_lens: Option<&(String, String)>) -> Vec<String> {
+    let (module, lens) = module_lens
+        .map(|(m, l)| (m.as_str(), l.as_str()))
+        .unwrap_or(("<module>", "<lens>"));
+    CALIB_EXTENSIONS
+        .iter()
+        .map(|ext| format!("{}_{}_{}.{}", sensor, module, lens, ext))
+        .collect()
+}
+
+/// 按 SDK 的规则在 `dir` 中查找传感器实体使用的 IQ 文件。
+///
+/// 能读取模组与镜头名称时只接受 `expected_calib_names` 中的文件，否则取 `find_calib_files`
+/// 的第一个结果。找不到时返回 `CalibError::Missing`，目录无法读取时返回 `CalibError::Io`。
+pub fn locate_calib_file<P: AsRef<Path>>(
+    dir: P,
+    sns_ent_name: &str,
+) -> Result<PathBuf, CalibError> {
+    locate_with(dir.as_ref(), sns_ent_name, module_lens_names(sns_ent_name))
+}
+
+fn locate_with(
+    dir: &Path,
+    sns_ent_name: &str,
+    module_lens: Option<(String, String)>,
+) -> Result<PathBuf, CalibError> {
+    let sensor = sensor_from_entity(sns_ent_name);
+    let expected_names = calib_names(sensor, module_lens.as_ref());
+    let found = match module_lens {
+        Some(_) => {
+            std::fs::read_dir(dir)?;
+            expected_names
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|p| p.is_file())
+        }
+        None => find_calib_files(dir, sns_ent_name)?.into_iter().next(),
+    };
+    found.ok_or_else(|| CalibError::Missing {
+        expected_names,
+        searched_dir: dir.to_owned(),
+    })
+}
+
+/// 列出 `dir` 中与传感器实体匹配的 IQ 文件，按文件名排序。
+///
+/// 文件名须符合 `<传感器>_<模组>_<镜头>` 的约定且扩展名为 `CALIB_EXTENSIONS` 之一，
+/// 传感器名称不区分大小写。
+pub fn find_calib_files<P: AsRef<Path>>(dir: P, sns_ent_name: &str) -> io::Result<Vec<PathBuf>> {
+    let sensor = sensor_from_entity(sns_ent_name);
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            p.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| CALIB_EXTENSIONS.contains(&ext))
+        })
+        .filter(|p| {
+            p.file_stem()
+                .and_then(|s| s.to_str())
+                .is_some_and(|s| s.split('_').count() >= 3)
+                && sensor_from_file_name(p).is_ok_and(|s| s.eq_ignore_ascii_case(sensor))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// 从 `m00_b_ov5695 4-0036-1` 形式的传感器实体名称中取出传感器名称 `ov5695`。
+///
+/// 不符合该形式时返回第一个空格前的部分。
+pub fn sensor_from_entity(sns_ent_name: &str) -> &str {
+    let name = sns_ent_name.split_whitespace().next().unwrap_or("");
+    let mut parts = name.splitn(3, '_');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(m), Some(_), Some(sensor)) if m.starts_with('m') && !sensor.is_empty() => sensor,
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IQ: &str = r#"{
+        "sensor_calib": { "resolution": { "width": 2592, "height": 1944 } },
+        "main_scene": [
+            { "name": "normal", "sub_scene": [
+                { "name": "day", "scene_isp30": { "ae_calib": [1.5, -2e-3, true, null] } },
+                { "name": "night", "scene_isp30": {} }
+            ] },
+            { "name": "hdr", "sub_scene": [ { "name": "day" } ] }
+        ],
+        "uapi": [],
+        "version": "v5.0.3 \u00b5"
+    }"#;
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn test_calib_from_json() {
+        let calib =
+            CalibFile::from_json(Path::new("/etc/iqfiles/ov5695_TongJu_CHT842-MD.json"), IQ)
+                .unwrap();
+        assert_eq!(calib.sensor_name(), "ov5695");
+        assert_eq!(calib.sdk_version(), Some("v5.0.3 \u{b5}"));
+        let scenes: Vec<String> = calib
+            .scenes()
+            .unwrap()
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(scenes, ["normal/day", "normal/night", "hdr/day"]);
+        assert!(calib.check_sensor("m00_b_ov5695 4-0036-1").is_ok());
+        assert!(matches!(
+            calib.check_sensor("m01_f_imx415 7-001a"),
+            Err(CalibError::SensorMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_calib_malformed_json() {
+        #[cfg(feature = "json")]
+        {
+            let path = Path::new("ov5695_a_b.json");
+            for text in ["", "{", "{\"a\" 1}", "[1,]", "\"\\q\"", "{} x", "tru"] {
+                assert!(
+                    matches!(CalibFile::from_json(path, text), Err(CalibError::Parse(_))),
+                    "{}",
+                    text
+                );
+            }
+            let deep = "[".repeat(200);
+            assert!(CalibFile::from_json(path, &deep).is_err());
+        }
+        assert!(matches!(
+            CalibFile::from_json(Path::new("_.json"), "{}"),
+            Err(CalibError::BadFileName(_))
+        ));
+    }
+
+    #[test]
+    fn test_temp_calib() {
+        let calib = TempCalib::write("ov5695_a_b", IQ.as_bytes()).unwrap();
+        let path = calib.path();
+        assert_eq!(calib.file_name(), "ov5695_a_b.json");
+        assert_eq!(path.parent(), Some(calib.dir()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), IQ);
+        let other = TempCalib::write("ov5695_a_b", b"{}").unwrap();
+        assert_ne!(other.dir(), calib.dir());
+        drop(calib);
+        assert!(!path.exists());
+
+        let param = XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM);
+        assert_eq!(TempCalib::write("ov5695_a_b", b"\xff{}").err(), Some(param));
+        #[cfg(feature = "json")]
+        for data in [&b"{"[..], b""] {
+            assert_eq!(TempCalib::write("ov5695_a_b", data).err(), Some(param));
+        }
+    }
+
+    #[test]
+    fn test_temp_calib_dir() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("rkaiq-iq-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let calib = TempCalib::write_in(dir.clone(), "ov5695_a_b", b"{}").unwrap();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        drop(calib);
+        assert!(!dir.exists());
+
+        // 已存在的目录不复用，也不删除。
+        std::fs::create_dir(&dir).unwrap();
+        assert_eq!(
+            TempCalib::write_in(dir.clone(), "ov5695_a_b", b"{}").err(),
+            Some(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FILE))
+        );
+        assert!(dir.is_dir());
+        assert!(!dir.join("ov5695_a_b.json").exists());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sensor_from_entity() {
+        assert_eq!(sensor_from_entity("m00_b_ov5695 4-0036-1"), "ov5695");
+        assert_eq!(sensor_from_entity("ov5695 4-0036"), "ov5695");
+    }
+
+    #[test]
+    fn test_find_calib_files() {
+        let dir = std::env::temp_dir().join(format!("rkaiq-calib-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ext = CALIB_EXTENSIONS[0];
+        for name in [
+            format!("OV5695_TongJu_CHT842-MD.{}", ext),
+            format!("ov5695_a_b.{}", ext),
+            format!("ov5695.{}", ext),
+            "ov5695_a_b.txt".to_owned(),
+            format!("imx415_CMK-OT2022-PX1_IR0147-50IRC-8M-F20.{}", ext),
+        ] {
+            std::fs::write(dir.join(name), "{}").unwrap();
+        }
+        let found = find_calib_files(&dir, "m00_b_ov5695 4-0036-1");
+        let missing = find_calib_files(&dir, "m01_f_gc2053 1-0037");
+        let absent = find_calib_files(dir.join("absent"), "m00_b_ov5695 4-0036-1");
+        let named = |module: &str, lens: &str| Some((module.to_owned(), lens.to_owned()));
+        let exact = locate_with(&dir, "m00_b_ov5695 4-0036-1", named("a", "b"));
+        let wrong_module = locate_with(&dir, "m00_b_ov5695 4-0036-1", named("c", "b"));
+        let by_sensor = locate_with(&dir, "m00_b_ov5695 4-0036-1", None);
+        let unreadable = locate_with(&dir.join("absent"), "m00_b_ov5695 4-0036-1", None);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<_> = found
+            .unwrap()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                format!("OV5695_TongJu_CHT842-MD.{}", ext),
+                format!("ov5695_a_b.{}", ext)
+            ]
+        );
+        assert!(missing.unwrap().is_empty());
+        assert!(absent.is_err());
+
+        assert_eq!(exact.unwrap(), dir.join(format!("ov5695_a_b.{}", ext)));
+        let Err(CalibError::Missing {
+            expected_names,
+            searched_dir,
+        }) = wrong_module
+        else {
+            panic!("expected Missing");
+        };
+        assert_eq!(expected_names[0], format!("ov5695_c_b.{}", ext));
+        assert_eq!(searched_dir, dir);
+        assert_eq!(
+            by_sensor.unwrap(),
+            dir.join(format!("OV5695_TongJu_CHT842-MD.{}", ext))
+        );
+        assert!(matches!(unreadable, Err(CalibError::Io(_))));
+    }
+
+    #[test]
+    fn test_expected_calib_names() {
+        let root = std::env::temp_dir().join(format!("rkaiq-i2c-{}", std::process::id()));
+        let node = root.join("4-0036").join("of_node");
+        std::fs::create_dir_all(&node).unwrap();
+        std::fs::write(node.join("rockchip,camera-module-name"), "TongJu\0").unwrap();
+        std::fs::write(node.join("rockchip,camera-module-lens-name"), "CHT842-MD\0").unwrap();
+        let found = module_lens_names_in(&root, "m00_b_ov5695 4-0036-1");
+        let absent = module_lens_names_in(&root, "m01_f_gc2053 1-0037");
+        let no_bus = module_lens_names_in(&root, "ov5695");
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(found, Some(("TongJu".to_owned(), "CHT842-MD".to_owned())));
+        assert_eq!(absent, None);
+        assert_eq!(no_bus, None);
+
+        let names = calib_names("ov5695", found.as_ref());
+        assert_eq!(names.len(), CALIB_EXTENSIONS.len());
+        for (name, ext) in names.iter().zip(CALIB_EXTENSIONS) {
+            assert_eq!(*name, format!("ov5695_TongJu_CHT842-MD.{}", ext));
+        }
+        assert_eq!(
+            calib_names("gc2053", None)[0],
+            format!("gc2053_<module>_<lens>.{}", CALIB_EXTENSIONS[0])
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! 回调保护
+//!
+//! 向 SDK 注册的回调经由 C 函数指针调用，panic 越过 C 栈帧展开属于未定义行为。
+//! 本库的所有回调入口都通过 `callback_guard` 执行：panic 被就地捕获，
+//! 回调向 SDK 返回一个安全的默认值，并置位进程内共享的回调异常标志。
+use super::context::Context;
+use super::logging::log_error;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 是否有回调发生过 panic。
+///
+/// 回调不携带上下文指针，因此该标志为进程内全部上下文共享。
+static CALLBACK_POISONED: AtomicBool = AtomicBool::new(false);
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("<non-string panic payload>")
+}
+
+/// 执行回调体 `f`，发生 panic 时记录错误、置位回调异常标志并返回 `fallback`。
+///
+/// # Parameters
+/// * `name` - 回调名称，用于错误信息。
+/// * `fallback` - panic 时返回给 SDK 的值。
+pub(crate) fn callback_guard<T, F>(name: &str, fallback: T, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(v) => v,
+        Err(payload) => {
+            CALLBACK_POISONED.store(true, Ordering::Release);
+            log_error!(
+                "panic in {} callback: {}",
+                name,
+                panic_message(payload.as_ref())
+            );
+            fallback
+        }
+    }
+}
+
+/// 是否有回调发生过 panic。
+pub(crate) fn callback_poisoned() -> bool {
+    CALLBACK_POISONED.load(Ordering::Acquire)
+}
+
+/// 清除回调异常标志。
+pub(crate) fn clear_callback_poisoned() {
+    CALLBACK_POISONED.store(false, Ordering::Release);
+}
+
+impl Context {
+    /// 是否有本库注册的回调发生过 panic。
+    ///
+    /// 发生 panic 的回调已向 SDK 返回默认值，但其负责维护的状态（例如帧号、错误标志）
+    /// 可能不再可靠。
+    ///
+    /// # Note
+    ///
+    /// 回调不携带上下文指针，因此标志为进程内全部上下文共享。
+    pub fn callback_poisoned(&self) -> bool {
+        callback_poisoned()
+    }
+
+    /// 清除回调异常标志。
+    pub fn clear_callback_poisoned(&self) {
+        clear_callback_poisoned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn panicking_callback(arg: i32) -> i32 {
+        callback_guard("test", -1, || {
+            if arg > 0 {
+                panic!("callback failed");
+            }
+            arg
+        })
+    }
+
+    #[test]
+    fn test_callback_guard_catches_panic() {
+        let cb: unsafe extern "C" fn(i32) -> i32 = panicking_callback;
+        assert_eq!(unsafe { cb(0) }, 0);
+        assert_eq!(unsafe { cb(1) }, -1);
+        assert!(callback_poisoned());
+        assert_eq!(
+            callback_guard("test", 0, || -> i32 { panic!("{}", String::from("owned")) }),
+            0
+        );
+        clear_callback_poisoned();
+        assert!(!callback_poisoned());
+    }
+}
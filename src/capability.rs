@@ -0,0 +1,287 @@
+//! 能力探测
+//!
+//! 汇总当前 ISP 硬件、SDK 版本与传感器所支持的功能模块。编译期能力由特性开关决定，
+//! 运行期能力来自传感器静态信息。`XCamError::Unsupported` 的判定与本模块使用同一份数据。
+//!
+//! SDK 以静态库或动态库链接，uAPI 函数在编译时即已确定存在与否：本库只在对应的版本与
+//! 硬件特性下声明并调用这些函数，因此符号可用性由特性开关判定，无需在运行期查找符号。
+//! 各能力的判定方法如下：
+//!
+//! | 能力 | 判定方法 |
+//! | --- | --- |
+//! | `Fec`、`Hdr3` | 编译期：isp_hw_v20、isp_hw_v30 |
+//! | `Ldch` | 编译期：isp_hw_v20、isp_hw_v21、isp_hw_v30、isp_hw_v32 |
+//! | `Lut3d` | 编译期：isp_hw_v20、isp_hw_v21、isp_hw_v30；本库只封装了 `get_lut3d_state`、`set_lut3d_state` 与手动查找表的读写，其余可直接调用 `ffi` |
+//! | `Cac` | 编译期：isp_hw_v30、isp_hw_v32 |
+//! | `Nr3d` | 编译期：isp_hw_v20、isp_hw_v30、isp_hw_v32 |
+//! | `Drc` | 编译期：isp_hw_v21、isp_hw_v30、isp_hw_v32 |
+//! | `CamGroup` | 编译期：v3_0 及以上且 isp_hw_v30 |
+//! | `Hdr2`、`Dehaze` | 编译期：全部组合 |
+//! | `Csm` | 编译期：v5_0 且 isp_hw_v21、isp_hw_v30、isp_hw_v32 |
+//! | `SemiAutoWb` | 白平衡没有半自动模式，总为 `false` |
+//! | `LensVcm`、`Flash`、`IrCut` | 运行期：传感器静态信息 |
+use super::context::Context;
+use super::error::XCamError;
+use super::sysctl;
+use super::types::{StaticInfo, XCamResult};
+
+bitflags::bitflags! {
+    /// 一个描述可用功能模块的位标志类型。
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    pub struct Capabilities: u32 {
+        /// 白平衡半自动模式。
+        const AWB_SEMI_AUTO = 1 << 0;
+        /// 两帧合成 HDR。
+        const HDR2 = 1 << 1;
+        /// 三帧合成 HDR。
+        const HDR3 = 1 << 2;
+        /// 鱼眼畸变校正。
+        const FEC = 1 << 3;
+        /// 水平畸变校正。
+        const LDCH = 1 << 4;
+        /// 色差校正。
+        const CAC = 1 << 5;
+        /// 去雾。
+        const DEHAZE = 1 << 6;
+        /// 三维（时域）降噪。
+        const NR3D = 1 << 7;
+        /// 多摄像头组同步控制。
+        const CAMGROUP = 1 << 8;
+        /// 动态范围压缩。
+        const DRC = 1 << 9;
+        /// 音圈马达镜头（可对焦）。
+        const LENS_VCM = 1 << 10;
+        /// 补光灯。
+        const FLASH = 1 << 11;
+        /// 红外截止滤光片。
+        const IRCUT = 1 << 12;
+        /// 三维查找表。
+        const LUT3D = 1 << 13;
+        /// 可设置的 RGB 到 YUV 转换矩阵，见 `csm` 模块。
+        const CSM = 1 << 14;
+    }
+}
+
+/// 一个描述单项能力的枚举，与 `Capabilities` 的标志一一对应，判定方法见模块说明。
+///
+/// 今后可能增加新的能力，匹配时须保留通配分支。
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+    SemiAutoWb,
+    Hdr2,
+    Hdr3,
+    Fec,
+    Ldch,
+    Cac,
+    Dehaze,
+    Nr3d,
+    CamGroup,
+    Drc,
+    LensVcm,
+    Flash,
+    IrCut,
+    Lut3d,
+    Csm,
+}
+
+impl From<Capability> for Capabilities {
+    fn from(val: Capability) -> Self {
+        match val {
+            Capability::SemiAutoWb => Self::AWB_SEMI_AUTO,
+            Capability::Hdr2 => Self::HDR2,
+            Capability::Hdr3 => Self::HDR3,
+            Capability::Fec => Self::FEC,
+            Capability::Ldch => Self::LDCH,
+            Capability::Cac => Self::CAC,
+            Capability::Dehaze => Self::DEHAZE,
+            Capability::Nr3d => Self::NR3D,
+            Capability::CamGroup => Self::CAMGROUP,
+            Capability::Drc => Self::DRC,
+            Capability::LensVcm => Self::LENS_VCM,
+            Capability::Flash => Self::FLASH,
+            Capability::IrCut => Self::IRCUT,
+            Capability::Lut3d => Self::LUT3D,
+            Capability::Csm => Self::CSM,
+        }
+    }
+}
+
+impl Capabilities {
+    /// 返回由编译特性决定的能力。
+    pub fn compiled() -> Self {
+        let mut caps = Self::DEHAZE | Self::HDR2;
+        if cfg!(any(feature = "isp_hw_v20", feature = "isp_hw_v30")) {
+            caps |= Self::HDR3 | Self::FEC;
+        }
+        if cfg!(any(
+            feature = "isp_hw_v20",
+            feature = "isp_hw_v21",
+            feature = "isp_hw_v30",
+            feature = "isp_hw_v32"
+        )) {
+            caps |= Self::LDCH;
+        }
+        if cfg!(any(
+            feature = "isp_hw_v20",
+            feature = "isp_hw_v21",
+            feature = "isp_hw_v30"
+        )) {
+            caps |= Self::LUT3D;
+        }
+        if cfg!(all(
+            feature = "v5_0",
+            any(
+                feature = "isp_hw_v21",
+                feature = "isp_hw_v30",
+                feature = "isp_hw_v32"
+            )
+        )) {
+            caps |= Self::CSM;
+        }
+        if cfg!(any(feature = "isp_hw_v30", feature = "isp_hw_v32")) {
+            caps |= Self::CAC;
+        }
+        if cfg!(any(
+            feature = "isp_hw_v20",
+            feature = "isp_hw_v30",
+            feature = "isp_hw_v32"
+        )) {
+            caps |= Self::NR3D;
+        }
+        if cfg!(any(
+            feature = "isp_hw_v21",
+            feature = "isp_hw_v30",
+            feature = "isp_hw_v32"
+        )) {
+            caps |= Self::DRC;
+        }
+        if cfg!(all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            feature = "isp_hw_v30"
+        )) {
+            caps |= Self::CAMGROUP;
+        }
+        caps
+    }
+
+    /// 返回由传感器静态信息决定的能力。
+    pub fn from_static_info(info: &StaticInfo) -> Self {
+        let mut caps = Self::empty();
+        caps.set(Self::LENS_VCM, info.has_lens_vcm);
+        caps.set(Self::FLASH, info.has_fl);
+        caps.set(Self::IRCUT, info.has_irc);
+        caps
+    }
+
+    /// 是否具备能力 `cap`。
+    pub fn supports(self, cap: Capability) -> bool {
+        self.contains(cap.into())
+    }
+
+    /// 检查是否具备全部指定能力，否则返回 `XCamError::Unsupported`。
+    pub fn require(self, caps: Self) -> XCamResult<()> {
+        if self.contains(caps) {
+            Ok(())
+        } else {
+            Err(XCamError::Unsupported)
+        }
+    }
+}
+
+impl Context {
+    /// 获取当前上下文可用的功能模块。
+    ///
+    /// 结果在首次查询后缓存；无法读取传感器静态信息时仅包含编译期能力。
+    pub fn capabilities(&self) -> Capabilities {
+        *self.caps.get_or_init(|| {
+            let runtime = sysctl::get_static_metas(self.sns_ent_name.as_str())
+                .map(|info| Capabilities::from_static_info(&info))
+                .unwrap_or(Capabilities::empty());
+            Capabilities::compiled() | runtime
+        })
+    }
+
+    /// 当前上下文是否具备能力 `cap`，用于调用前的功能探测，判定方法见模块说明。
+    pub fn supports(&self, cap: Capability) -> bool {
+        self.capabilities().supports(cap)
+    }
+
+    /// 检查当前上下文是否具备全部指定能力。
+    pub(crate) fn require(&self, caps: Capabilities) -> XCamResult<()> {
+        self.capabilities().require(caps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_static_info() {
+        let mut info = StaticInfo::default();
+        assert_eq!(Capabilities::from_static_info(&info), Capabilities::empty());
+        info.has_lens_vcm = true;
+        info.has_irc = true;
+        assert_eq!(
+            Capabilities::from_static_info(&info),
+            Capabilities::LENS_VCM | Capabilities::IRCUT
+        );
+    }
+
+    #[test]
+    fn test_require() {
+        let caps = Capabilities::DEHAZE | Capabilities::HDR2;
+        assert_eq!(caps.require(Capabilities::DEHAZE), Ok(()));
+        assert_eq!(
+            caps.require(Capabilities::DEHAZE | Capabilities::FEC),
+            Err(XCamError::Unsupported)
+        );
+        assert!(!Capabilities::compiled().contains(Capabilities::AWB_SEMI_AUTO));
+    }
+
+    #[test]
+    fn test_supports() {
+        let caps = Capabilities::compiled();
+        assert!(caps.supports(Capability::Hdr2));
+        assert!(caps.supports(Capability::Dehaze));
+        assert!(!caps.supports(Capability::SemiAutoWb));
+        // 运行期能力不在编译期能力中。
+        assert!(!caps.supports(Capability::LensVcm));
+        assert_eq!(
+            caps.supports(Capability::Fec),
+            cfg!(any(feature = "isp_hw_v20", feature = "isp_hw_v30"))
+        );
+        assert_eq!(
+            caps.supports(Capability::Lut3d),
+            cfg!(any(
+                feature = "isp_hw_v20",
+                feature = "isp_hw_v21",
+                feature = "isp_hw_v30"
+            ))
+        );
+
+        // 默认特性组合 v5_0 + isp_hw_v30。
+        #[cfg(all(feature = "v5_0", feature = "isp_hw_v30"))]
+        for (cap, expected) in [
+            (Capability::Fec, true),
+            (Capability::Hdr3, true),
+            (Capability::Ldch, true),
+            (Capability::Lut3d, true),
+            (Capability::Cac, true),
+            (Capability::Nr3d, true),
+            (Capability::Drc, true),
+            (Capability::CamGroup, true),
+            (Capability::Csm, true),
+            (Capability::SemiAutoWb, false),
+        ] {
+            assert_eq!(caps.supports(cap), expected, "{:?}", cap);
+        }
+
+        let mut info = StaticInfo::default();
+        info.has_fl = true;
+        let runtime = Capabilities::from_static_info(&info);
+        assert!(runtime.supports(Capability::Flash));
+        assert!(!runtime.supports(Capability::IrCut));
+    }
+}
@@ -0,0 +1,377 @@
+//! 图像帧
+//!
+//! AIQ 只负责 3A 与 ISP 参数，不经手图像缓冲区，SDK 也没有逐帧交付图像的回调。本模块直接在
+//! ISP 主通路 video 节点上以 mmap 缓冲区采集，省去自行接入 V4L2：
+//!
+//! - `Context::capture_frame` 用于缩略图、产线检验等只需要偶尔取一帧的场合：申请缓冲区、取出
+//!   一帧、复制后归还。每次抓取都会重新申请缓冲区并开关一次采集，整帧数据复制一次（1920x1080
+//!   NV12 约 3 MB），耗时在数十毫秒量级，不适合逐帧调用。
+//! - `Context::on_frame` 用于连续处理：采集线程逐帧以 `FrameRef` 调用回调，`FrameRef` 直接引用
+//!   驱动的缓冲区而不复制，释放时缓冲区归还驱动。
+//!
+//! 主通路节点同一时间只能由一个进程采集，下游已打开节点时两者都会失败。
+use super::callback::callback_guard;
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::logging::log_warn;
+use super::media;
+use super::sysctl::SystemControl;
+use super::types::{PixelFormat, XCamResult};
+use super::v4l2;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// 采集线程等待一帧的最长时间，也是释放订阅后线程退出的最长延迟。
+pub const FRAME_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 一个描述抓取到的一帧图像的类型。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// 图像数据，按 `format` 排列，各平面依次存放，行宽可能含驱动的对齐填充。
+    pub data: Vec<u8>,
+    /// 宽度。
+    pub width: u32,
+    /// 高度。
+    pub height: u32,
+    /// 像素格式。
+    pub format: PixelFormat,
+    /// 抓取完成时 AIQ 报告的帧号，与图像所在帧可能相差一两帧；无法获取时为 `None`。
+    pub frame_id: Option<u32>,
+}
+
+/// 抓取一帧：`grab` 返回节点格式 `(宽, 高, fourcc)` 与复制出的数据。
+///
+/// 未出流时返回 `XCAM_RETURN_ERROR_ORDER`，格式不在 `PixelFormat` 中时返回 `XCAM_RETURN_ERROR_FAILED`。
+pub(crate) fn capture_frame_with<C, F>(ctx: &C, grab: F) -> XCamResult<Frame>
+where
+    C: SystemControl + ?Sized,
+    F: FnOnce() -> XCamResult<((u32, u32, u32), Vec<u8>)>,
+{
+    if !ctx.is_streaming() {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER));
+    }
+    let ((width, height, code), data) = grab()?;
+    let format = PixelFormat::from_fourcc(code)
+        .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))?;
+    Ok(Frame {
+        data,
+        width,
+        height,
+        format,
+        frame_id: ctx.get_frame_id(),
+    })
+}
+
+/// 一个描述连续采集时各帧共同格式的类型。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FrameLayout {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) format: PixelFormat,
+    /// 各平面的行宽，单位：字节。
+    pub(crate) strides: Vec<u32>,
+}
+
+/// 一个引用驱动缓冲区中一帧图像的类型，由 `Context::on_frame` 交给回调。
+///
+/// 各平面直接引用驱动 mmap 的缓冲区，不复制数据。`FrameRef` 只在回调期间有效，释放时（最迟在
+/// 回调返回时）缓冲区归还驱动；持有期间驱动少一个可写的缓冲区，耗时的处理应先复制所需的数据。
+pub struct FrameRef<'a> {
+    planes: Vec<&'a [u8]>,
+    layout: &'a FrameLayout,
+    frame_id: u32,
+    release: Option<Box<dyn FnOnce() + 'a>>,
+}
+
+impl FrameRef<'_> {
+    /// 宽度。
+    pub fn width(&self) -> u32 {
+        self.layout.width
+    }
+
+    /// 高度。
+    pub fn height(&self) -> u32 {
+        self.layout.height
+    }
+
+    /// 像素格式。
+    pub fn format(&self) -> PixelFormat {
+        self.layout.format
+    }
+
+    /// 帧号，取自驱动为缓冲区填充的 `sequence`。
+    pub fn frame_id(&self) -> u32 {
+        self.frame_id
+    }
+
+    /// 平面数量，例如 NV12 在 rkisp 上为 1（色度紧随亮度存放）。
+    pub fn num_planes(&self) -> usize {
+        self.planes.len()
+    }
+
+    /// 第 `index` 个平面的数据，按 `stride` 的行宽排列，行尾可能含驱动的对齐填充。
+    pub fn plane(&self, index: usize) -> Option<&[u8]> {
+        self.planes.get(index).copied()
+    }
+
+    /// 第 `index` 个平面的行宽，单位：字节。
+    pub fn stride(&self, index: usize) -> Option<u32> {
+        self.layout.strides.get(index).copied()
+    }
+}
+
+impl Drop for FrameRef<'_> {
+    fn drop(&mut self) {
+        if let Some(release) = self.release.take() {
+            release();
+        }
+    }
+}
+
+/// 连续采集的缓冲区来源。
+pub(crate) trait FrameSource {
+    /// 等待下一个缓冲区，返回 `(缓冲区序号, 帧号)`，`timeout` 内没有时返回 `Ok(None)`。
+    fn dequeue(&mut self, timeout: Duration) -> XCamResult<Option<(u32, u32)>>;
+
+    /// 已出队的缓冲区 `index` 各平面的数据。
+    ///
+    /// # Safety
+    /// 返回的切片须在 `requeue` 归还该缓冲区之前释放。
+    unsafe fn planes(&self, index: u32) -> Vec<&[u8]>;
+
+    /// 将缓冲区 `index` 归还驱动。
+    fn requeue(&self, index: u32) -> XCamResult<()>;
+}
+
+impl FrameSource for v4l2::Capture {
+    fn dequeue(&mut self, timeout: Duration) -> XCamResult<Option<(u32, u32)>> {
+        match v4l2::Capture::dequeue(self, timeout) {
+            Ok(buf) => Ok(Some(buf)),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => Ok(None),
+            Err(e) => Err(media::ioctl_error(e)),
+        }
+    }
+
+    unsafe fn planes(&self, index: u32) -> Vec<&[u8]> {
+        v4l2::Capture::planes(self, index)
+    }
+
+    fn requeue(&self, index: u32) -> XCamResult<()> {
+        v4l2::Capture::requeue(self, index).map_err(media::ioctl_error)
+    }
+}
+
+/// 逐帧以 `FrameRef` 调用 `f`，直到 `stop` 置位或取帧失败。
+///
+/// `f` 发生 panic 时与 SDK 回调同样被捕获，缓冲区照常归还，采集继续。
+pub(crate) fn run_frames<S, F>(
+    source: &mut S,
+    layout: &FrameLayout,
+    stop: &AtomicBool,
+    mut f: F,
+) -> XCamResult<()>
+where
+    S: FrameSource,
+    F: FnMut(FrameRef),
+{
+    while !stop.load(Ordering::Acquire) {
+        let Some((index, frame_id)) = source.dequeue(FRAME_POLL_INTERVAL)? else {
+            continue;
+        };
+        let source = &*source;
+        let frame = FrameRef {
+            // `FrameRef` 释放时才归还缓冲区，切片不会超出其生存期。
+            planes: unsafe { source.planes(index) },
+            layout,
+            frame_id,
+            release: Some(Box::new(move || {
+                if let Err(e) = source.requeue(index) {
+                    log_warn!("failed to requeue frame buffer {}: {}", index, e);
+                }
+            })),
+        };
+        callback_guard("frame", (), || f(frame));
+    }
+    Ok(())
+}
+
+/// 逐帧回调的订阅，由 `Context::on_frame` 创建。
+///
+/// 被释放时停止采集并等待采集线程退出，最多等待 `FRAME_POLL_INTERVAL`；在回调中释放时不等待，
+/// 采集线程在回调返回后退出。
+pub struct FrameSubscription {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for FrameSubscription {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            // 在回调中释放时位于采集线程自身，不能等待自身退出，只释放句柄。
+            if handle.thread().id() == thread::current().id() {
+                return;
+            }
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Context {
+    /// 从 ISP 主通路抓取一帧处理后的图像，复制到 `Frame` 中返回，见模块说明。
+    ///
+    /// 须在 `start` 之后调用，否则返回 `XCAM_RETURN_ERROR_ORDER`；下游已打开主通路节点等导致
+    /// 采集失败时返回 `XCAM_RETURN_ERROR_IOCTL`；一秒内没有取到帧时返回 `XCamError::Timeout`；由
+    /// `from_raw_parts` 接管的上下文不知道传感器实体名称时返回 `XCamError::Unsupported`。
+    pub fn capture_frame(&self) -> XCamResult<Frame> {
+        capture_frame_with(self, || {
+            let node = self.mainpath_video_node()?;
+            let format = media::get_video_format(&node)?;
+            Ok((format, media::grab_video_frame(&node)?))
+        })
+    }
+
+    /// 在 ISP 主通路上连续采集，每帧处理后的图像以 `FrameRef` 调用一次 `cb`，见模块说明。
+    ///
+    /// 回调在独立的采集线程中依次执行，不会并发；`FrameRef` 不复制图像，只能在回调内使用，
+    /// 释放后缓冲区即归还驱动。回调耗时超过帧间隔时驱动因缺少缓冲区而丢帧，可由帧号的跳变得知。
+    /// 回调发生 panic 时与 SDK 回调同样被捕获并置位回调异常标志，采集继续。释放返回的
+    /// `FrameSubscription` 即停止采集；取帧失败（例如节点被关闭）时采集线程输出一条警告后退出。
+    ///
+    /// 须在 `start` 之后调用，否则返回 `XCAM_RETURN_ERROR_ORDER`；下游已打开主通路节点等导致
+    /// 采集失败时返回 `XCAM_RETURN_ERROR_IOCTL`；节点格式不在 `PixelFormat` 中时返回
+    /// `XCAM_RETURN_ERROR_FAILED`；由 `from_raw_parts` 接管的上下文不知道传感器实体名称时返回
+    /// `XCamError::Unsupported`。
+    pub fn on_frame(&self, cb: Box<dyn FnMut(FrameRef) + Send>) -> XCamResult<FrameSubscription> {
+        if !self.is_streaming() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER));
+        }
+        let node = self.mainpath_video_node()?;
+        let (mut capture, (width, height, code), strides) = media::start_video_stream(&node)?;
+        let layout = FrameLayout {
+            width,
+            height,
+            format: PixelFormat::from_fourcc(code)
+                .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))?,
+            strides,
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let handle = thread::Builder::new()
+            .name("rkaiq-frame".into())
+            .spawn(move || {
+                if let Err(e) = run_frames(&mut capture, &layout, &flag, cb) {
+                    log_warn!("frame capture stopped: {}", e);
+                }
+            })
+            .map_err(|_| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_THREAD))?;
+        Ok(FrameSubscription {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    /// 依次交付 `queue` 中的缓冲区，交付完后置位 `stop`。
+    struct FakeSource {
+        buffers: Vec<Vec<u8>>,
+        queue: VecDeque<(u32, u32)>,
+        released: Rc<RefCell<Vec<u32>>>,
+        stop: Arc<AtomicBool>,
+    }
+
+    impl FrameSource for FakeSource {
+        fn dequeue(&mut self, _: Duration) -> XCamResult<Option<(u32, u32)>> {
+            let buf = self.queue.pop_front();
+            if buf.is_none() {
+                self.stop.store(true, Ordering::Release);
+            }
+            Ok(buf)
+        }
+
+        unsafe fn planes(&self, index: u32) -> Vec<&[u8]> {
+            vec![&self.buffers[index as usize]]
+        }
+
+        fn requeue(&self, index: u32) -> XCamResult<()> {
+            self.released.borrow_mut().push(index);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_capture_frame() {
+        let ctx = MockContext::new();
+        let buffer: Vec<u8> = (0..24).collect();
+        let grab = || Ok(((4, 4, PixelFormat::Nv12.fourcc()), buffer.clone()));
+        assert_eq!(
+            capture_frame_with(&ctx, grab),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))
+        );
+
+        {
+            let mut s = ctx.state.borrow_mut();
+            s.streaming = true;
+            s.frame_id = Some(42);
+        }
+        let frame = capture_frame_with(&ctx, grab).unwrap();
+        assert_eq!(frame.data, buffer);
+        assert_eq!((frame.width, frame.height), (4, 4));
+        assert_eq!(frame.format, PixelFormat::Nv12);
+        assert_eq!(frame.frame_id, Some(42));
+
+        assert_eq!(
+            capture_frame_with(&ctx, || Ok((
+                (4, 4, u32::from_le_bytes(*b"ABCD")),
+                buffer.clone()
+            ))),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))
+        );
+    }
+
+    #[test]
+    fn test_run_frames() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let released = Rc::new(RefCell::new(Vec::new()));
+        let mut source = FakeSource {
+            buffers: vec![vec![1; 24], vec![2; 24]],
+            queue: VecDeque::from([(0, 7), (1, 8), (0, 9)]),
+            released: Rc::clone(&released),
+            stop: Arc::clone(&stop),
+        };
+        let layout = FrameLayout {
+            width: 4,
+            height: 4,
+            format: PixelFormat::Nv12,
+            strides: vec![4],
+        };
+        let mut seen = Vec::new();
+        run_frames(&mut source, &layout, &stop, |frame| {
+            assert_eq!((frame.width(), frame.height()), (4, 4));
+            assert_eq!(frame.format(), PixelFormat::Nv12);
+            assert_eq!(frame.num_planes(), 1);
+            assert_eq!(frame.stride(0), Some(4));
+            assert_eq!(frame.plane(1), None);
+            // 回调持有 `FrameRef` 期间缓冲区不归还，释放后立即归还。
+            let before = released.borrow().len();
+            seen.push((frame.frame_id(), frame.plane(0).unwrap()[0]));
+            drop(frame);
+            assert_eq!(released.borrow().len(), before + 1);
+        })
+        .unwrap();
+        assert_eq!(seen, [(7, 1), (8, 2), (9, 1)]);
+        assert_eq!(*released.borrow(), [0, 1, 0]);
+    }
+}
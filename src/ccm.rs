@@ -0,0 +1,694 @@
+//! 色彩校正
+//!
+//! CCM 模块以 3×3 矩阵加偏移校正传感器的颜色响应。自动模式下算法根据光源估计，
+//! 在两组标定光源的矩阵之间按权重插值得到最终矩阵。
+use super::awb::AutoWhiteBalance;
+use super::context::Context;
+use super::convert;
+use super::error::XCamError;
+use super::ffi;
+use super::shared::SharedContext;
+use super::types::{string_from_c_chars, OpMode, XCamResult};
+use super::uapi::{uapi_call, uapi_get};
+
+/// 一个描述颜色校正矩阵的类型。
+///
+/// 按行存储：第 i 行的三个系数分别乘以输入的 R、G、B，求和后加上 `offsets[i]`
+/// 得到第 i 个输出通道，即 `out = matrix · in + offsets`。SDK 与硬件寄存器中的 9 个系数
+/// 同样按行排列，`to_flat` 的结果可直接写入。按列书写系数（例如照搬某些以列向量
+/// 右乘的文献公式）会得到转置的矩阵，请使用 `from_rows` 逐行构建以避免混淆。
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ccm {
+    pub matrix: [[f32; 3]; 3],
+    /// 各输出通道的偏移。
+    pub offsets: [f32; 3],
+}
+
+impl Ccm {
+    /// 由 R、G、B 三个输出通道的系数行及偏移构建矩阵。
+    pub fn from_rows(r: [f32; 3], g: [f32; 3], b: [f32; 3], offset: [f32; 3]) -> Self {
+        Self {
+            matrix: [r, g, b],
+            offsets: offset,
+        }
+    }
+
+    /// 返回不改变颜色的单位矩阵。
+    pub fn identity() -> Self {
+        Self::from_rows([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.0; 3])
+    }
+
+    /// 所有系数与偏移是否均为有限值。
+    pub fn is_finite(&self) -> bool {
+        self.matrix.iter().flatten().all(|v| v.is_finite())
+            && self.offsets.iter().all(|v| v.is_finite())
+    }
+
+    /// 将每行缩放为和为 1，使中性灰经校正后保持中性。
+    ///
+    /// `enabled` 为 `false` 时原样返回，便于按配置跳过。和为 0 或非有限值的行无法缩放，保持不变。
+    /// 偏移不受影响。
+    pub fn normalize_rows(mut self, enabled: bool) -> Self {
+        if !enabled {
+            return self;
+        }
+        for row in self.matrix.iter_mut() {
+            let sum: f32 = row.iter().sum();
+            if sum.is_finite() && sum != 0.0 {
+                row.iter_mut().for_each(|v| *v /= sum);
+            }
+        }
+        self
+    }
+
+    /// 返回按行展开的 9 个系数，与 SDK 的排列一致。
+    pub fn to_flat(&self) -> [f32; 9] {
+        let m = self.matrix;
+        [
+            m[0][0], m[0][1], m[0][2], m[1][0], m[1][1], m[1][2], m[2][0], m[2][1], m[2][2],
+        ]
+    }
+
+    /// 由 SDK 按行展开的 9 个系数构建矩阵。
+    pub fn from_flat(m: [f32; 9], offset: [f32; 3]) -> Self {
+        Self::from_rows(
+            [m[0], m[1], m[2]],
+            [m[3], m[4], m[5]],
+            [m[6], m[7], m[8]],
+            offset,
+        )
+    }
+}
+
+impl Default for Ccm {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// 一个描述参与插值的标定光源的类型。
+#[derive(Clone, Debug, PartialEq)]
+pub struct CcmIlluminant {
+    /// 标定文件中的光源名称，SDK 未报告时为空字符串。
+    pub name: String,
+    /// 插值权重，范围：[0,1]，全部光源的权重之和为 1。
+    pub weight: f32,
+}
+
+/// 一个描述当前 CCM 状态的类型。
+#[derive(Clone, Debug, PartialEq)]
+pub struct CcmQueryInfo {
+    /// CCM 是否使能。
+    pub enabled: bool,
+    /// 当前生效的校正矩阵。
+    pub ccm: Ccm,
+    /// 参与插值的标定光源，只有一个光源生效时仅含一项，无法确定时为空。
+    pub illuminants: Vec<CcmIlluminant>,
+}
+
+/// 整理 SDK 报告的两组 `(光源名称, 权重)`。
+///
+/// * 名称为空且权重不为正的项视为未使用；
+/// * 两项名称相同（未插值时 SDK 可能重复报告同一光源）时合并为一项；
+/// * 权重归一化为和为 1；未报告权重时各项平分。
+pub(crate) fn blend_illuminants(entries: [(String, f32); 2]) -> Vec<CcmIlluminant> {
+    let mut out: Vec<CcmIlluminant> = Vec::with_capacity(2);
+    for (name, weight) in entries {
+        let weight = if weight.is_finite() {
+            weight.max(0.0)
+        } else {
+            0.0
+        };
+        if name.is_empty() && weight <= 0.0 {
+            continue;
+        }
+        match out.iter_mut().find(|i| !name.is_empty() && i.name == name) {
+            Some(same) => same.weight += weight,
+            None => out.push(CcmIlluminant { name, weight }),
+        }
+    }
+    let total: f32 = out.iter().map(|i| i.weight).sum();
+    if total > 0.0 {
+        out.iter_mut().for_each(|i| i.weight /= total);
+        out.retain(|i| i.weight > 0.0);
+    } else {
+        let n = out.len() as f32;
+        out.iter_mut().for_each(|i| i.weight = 1.0 / n);
+    }
+    out
+}
+
+impl From<ffi::rk_aiq_ccm_querry_info_t> for CcmQueryInfo {
+    fn from(val: ffi::rk_aiq_ccm_querry_info_t) -> Self {
+        Self {
+            enabled: val.ccm_en,
+            ccm: Ccm::from_flat(val.matrix, val.offs),
+            illuminants: blend_illuminants([
+                (string_from_c_chars(&val.ccmname1), val.illuWeight[0]),
+                (string_from_c_chars(&val.ccmname2), val.illuWeight[1]),
+            ]),
+        }
+    }
+}
+
+/// 一个描述 3D LUT 状态的类型。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lut3dState {
+    /// 3D LUT 是否使能（未被旁路）。
+    pub enabled: bool,
+    /// 工作模式。
+    pub mode: OpMode,
+}
+
+/// 3D LUT 查找表每个通道的点数，即 9×9×9 个格点。
+pub const LUT3D_TABLE_LEN: usize = 729;
+
+/// 一个描述 3D LUT 手动查找表的类型，各通道按格点顺序存储 `LUT3D_TABLE_LEN` 个输出值。
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lut3dTable {
+    pub r: Vec<u16>,
+    pub g: Vec<u16>,
+    pub b: Vec<u16>,
+}
+
+impl Lut3dTable {
+    /// 检查各通道的点数是否为 `LUT3D_TABLE_LEN`，否则返回 `XCAM_RETURN_ERROR_PARAM`。
+    pub fn check(&self) -> XCamResult<()> {
+        if [&self.r, &self.g, &self.b]
+            .iter()
+            .all(|c| c.len() == LUT3D_TABLE_LEN)
+        {
+            Ok(())
+        } else {
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        }
+    }
+}
+
+/// 检查按色温索引的 CCM 表：色温须严格递增，全部矩阵须为有限值，否则返回 `XCAM_RETURN_ERROR_PARAM`。
+pub fn check_ccm_table(entries: &[(u32, Ccm)]) -> XCamResult<()> {
+    let sorted = entries.windows(2).all(|w| w[0].0 < w[1].0);
+    if sorted && entries.iter().all(|(_, ccm)| ccm.is_finite()) {
+        Ok(())
+    } else {
+        Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+    }
+}
+
+/// 在按色温索引的 CCM 表中按色温 `ct` 插值，表为空时返回 `None`。
+///
+/// 在相邻两项之间对矩阵与偏移逐元素按色温线性插值；`ct` 超出表的范围时取最近一端的矩阵。
+/// `entries` 须已通过 `check_ccm_table` 检查。
+pub fn interpolate_ccm(entries: &[(u32, Ccm)], ct: u32) -> Option<Ccm> {
+    let (first, last) = (entries.first()?, entries.last()?);
+    if ct <= first.0 {
+        return Some(first.1);
+    }
+    if ct >= last.0 {
+        return Some(last.1);
+    }
+    let i = entries.partition_point(|(k, _)| *k <= ct);
+    let ((lo_ct, lo), (hi_ct, hi)) = (entries[i - 1], entries[i]);
+    let t = (ct - lo_ct) as f32 / (hi_ct - lo_ct) as f32;
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    let mut out = lo;
+    for (row, (a, b)) in out
+        .matrix
+        .iter_mut()
+        .zip(lo.matrix.iter().zip(hi.matrix.iter()))
+    {
+        for (v, (x, y)) in row.iter_mut().zip(a.iter().zip(b.iter())) {
+            *v = lerp(*x, *y);
+        }
+    }
+    for (v, (x, y)) in out
+        .offsets
+        .iter_mut()
+        .zip(lo.offsets.iter().zip(hi.offsets.iter()))
+    {
+        *v = lerp(*x, *y);
+    }
+    Some(out)
+}
+
+/// `Context::set_ccm_table` 设置的 CCM 表及最近一次写入的矩阵。
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CcmTable {
+    entries: Vec<(u32, Ccm)>,
+    applied: Option<Ccm>,
+}
+
+impl CcmTable {
+    /// 检查并替换 CCM 表，空表表示停用。
+    pub(crate) fn set(&mut self, entries: &[(u32, Ccm)]) -> XCamResult<()> {
+        check_ccm_table(entries)?;
+        self.entries = entries.to_vec();
+        self.applied = None;
+        Ok(())
+    }
+}
+
+/// 按当前色温从 `table` 插值并写入手动 CCM，写入时返回 `true`。
+///
+/// 表为空或插值结果与上次写入的相同时不写入，返回 `false`；写入失败时下次调用重试。
+pub(crate) fn tick_ccm_table<C: AutoWhiteBalance + ColorCorrection + ?Sized>(
+    ctx: &C,
+    table: &mut CcmTable,
+) -> XCamResult<bool> {
+    if table.entries.is_empty() {
+        return Ok(false);
+    }
+    let ct = ctx.get_mwb_ct()?;
+    let Some(ccm) = interpolate_ccm(&table.entries, ct) else {
+        return Ok(false);
+    };
+    if table.applied == Some(ccm) {
+        return Ok(false);
+    }
+    ctx.set_manual_ccm(&ccm)?;
+    table.applied = Some(ccm);
+    Ok(true)
+}
+
+pub trait ColorCorrection {
+    /// 查询当前生效的 CCM 矩阵及参与插值的标定光源。
+    ///
+    /// # Note
+    ///
+    /// CCM 插值期间调用会阻塞，最长约一个帧周期。
+    fn get_ccm_query_info(&self) -> XCamResult<CcmQueryInfo>;
+
+    /// 获取 CCM 的工作模式。
+    fn get_ccm_mode(&self) -> XCamResult<OpMode> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 切换为手动 CCM 并设置矩阵与偏移，矩阵须为有限值，否则返回 `XCAM_RETURN_ERROR_PARAM`。
+    fn set_manual_ccm(&self, ccm: &Ccm) -> XCamResult<()> {
+        if !ccm.is_finite() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        Err(XCamError::Unsupported)
+    }
+
+    /// 设置 CCM 的工作模式，切换为手动时沿用当前的手动矩阵，只支持 `OpMode::Auto` 与
+    /// `OpMode::Manual`，其余模式返回 `XCAM_RETURN_ERROR_PARAM`。
+    fn set_ccm_mode(&self, mode: OpMode) -> XCamResult<()> {
+        let _ = mode;
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取 3D LUT 的开关与工作模式。
+    ///
+    /// 仅 ISP20、ISP21、ISP30 有 3D LUT 模块，其余硬件返回 `XCamError::Unsupported`。
+    fn get_lut3d_state(&self) -> XCamResult<Lut3dState> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 设置 3D LUT 的开关与工作模式，只支持 `OpMode::Auto` 与 `OpMode::Manual`，
+    /// 其余模式返回 `XCAM_RETURN_ERROR_PARAM`。
+    ///
+    /// 仅 ISP20、ISP21、ISP30 有 3D LUT 模块，其余硬件返回 `XCamError::Unsupported`。
+    fn set_lut3d_state(&self, state: Lut3dState) -> XCamResult<()> {
+        let _ = state;
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取 3D LUT 的手动查找表。
+    ///
+    /// 仅 ISP20、ISP21、ISP30 有 3D LUT 模块，其余硬件返回 `XCamError::Unsupported`。
+    fn get_lut3d_table(&self) -> XCamResult<Lut3dTable> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 设置 3D LUT 的手动查找表，不改变工作模式，手动模式下生效。
+    ///
+    /// 各通道点数须为 `LUT3D_TABLE_LEN`，否则返回 `XCAM_RETURN_ERROR_PARAM`。
+    /// 仅 ISP20、ISP21、ISP30 有 3D LUT 模块，其余硬件返回 `XCamError::Unsupported`。
+    fn set_lut3d_table(&self, table: &Lut3dTable) -> XCamResult<()> {
+        table.check()?;
+        Err(XCamError::Unsupported)
+    }
+}
+
+/// 将 `OpMode` 转换为 3D LUT 的工作模式，无法对应时返回 `XCAM_RETURN_ERROR_PARAM`。
+#[cfg(any(feature = "isp_hw_v20", feature = "isp_hw_v21", feature = "isp_hw_v30"))]
+fn lut3d_mode_from(mode: OpMode) -> XCamResult<ffi::rk_aiq_lut3d_op_mode_t> {
+    use ffi::rk_aiq_lut3d_op_mode_t::*;
+    match mode {
+        OpMode::Auto => Ok(RK_AIQ_LUT3D_MODE_AUTO),
+        OpMode::Manual => Ok(RK_AIQ_LUT3D_MODE_MANUAL),
+        _ => Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM)),
+    }
+}
+
+/// 将 `OpMode` 转换为 CCM 的工作模式，无法对应时返回 `XCAM_RETURN_ERROR_PARAM`。
+fn ccm_mode_from(mode: OpMode) -> XCamResult<ffi::rk_aiq_ccm_op_mode_t> {
+    use ffi::rk_aiq_ccm_op_mode_t::*;
+    match mode {
+        OpMode::Auto => Ok(RK_AIQ_CCM_MODE_AUTO),
+        OpMode::Manual => Ok(RK_AIQ_CCM_MODE_MANUAL),
+        _ => Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM)),
+    }
+}
+
+/// 将 3D LUT 的工作模式转换为 `OpMode`，无法对应的值转换为 `OpMode::Invalid`。
+#[cfg(any(feature = "isp_hw_v20", feature = "isp_hw_v21", feature = "isp_hw_v30"))]
+fn lut3d_op_mode(mode: ffi::rk_aiq_lut3d_op_mode_t) -> OpMode {
+    use ffi::rk_aiq_lut3d_op_mode_t::*;
+    match mode {
+        RK_AIQ_LUT3D_MODE_AUTO => OpMode::Auto,
+        RK_AIQ_LUT3D_MODE_MANUAL => OpMode::Manual,
+        v => {
+            convert::unmapped("rk_aiq_lut3d_op_mode_t", v);
+            OpMode::Invalid
+        }
+    }
+}
+
+/// 将 CCM 的工作模式转换为 `OpMode`，无法对应的值转换为 `OpMode::Invalid`。
+fn ccm_op_mode(mode: ffi::rk_aiq_ccm_op_mode_t) -> OpMode {
+    use ffi::rk_aiq_ccm_op_mode_t::*;
+    match mode {
+        RK_AIQ_CCM_MODE_AUTO => OpMode::Auto,
+        RK_AIQ_CCM_MODE_MANUAL => OpMode::Manual,
+        v => {
+            convert::unmapped("rk_aiq_ccm_op_mode_t", v);
+            OpMode::Invalid
+        }
+    }
+}
+
+impl ColorCorrection for Context {
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn get_ccm_query_info(&self) -> XCamResult<CcmQueryInfo> {
+        let mut info = ffi::rk_aiq_ccm_querry_info_t::default();
+        uapi_call!(ffi::rk_aiq_user_api_accm_QueryCcmInfo(self, &mut info)).map(|_| info.into())
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ccm_query_info(&self) -> XCamResult<CcmQueryInfo> {
+        let mut info = ffi::rk_aiq_ccm_querry_info_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_accm_QueryCcmInfo(self, &mut info)).map(|_| info.into())
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn get_ccm_mode(&self) -> XCamResult<OpMode> {
+        let mut attr = ffi::rk_aiq_ccm_attrib_t::default();
+        uapi_call!(ffi::rk_aiq_user_api_accm_GetAttrib(self, &mut attr))
+            .map(|_| ccm_op_mode(attr.mode))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ccm_mode(&self) -> XCamResult<OpMode> {
+        let mut attr = ffi::rk_aiq_ccm_attrib_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_accm_GetAttrib(self, &mut attr))
+            .map(|_| ccm_op_mode(attr.mode))
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn set_manual_ccm(&self, ccm: &Ccm) -> XCamResult<()> {
+        if !ccm.is_finite() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        let mut attr =
+            uapi_get!(ffi::rk_aiq_user_api_accm_GetAttrib(self) -> ffi::rk_aiq_ccm_attrib_t)?;
+        write_manual_ccm(&mut attr, ccm);
+        uapi_call!(ffi::rk_aiq_user_api_accm_SetAttrib(self, attr))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_manual_ccm(&self, ccm: &Ccm) -> XCamResult<()> {
+        if !ccm.is_finite() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        let mut attr =
+            uapi_get!(ffi::rk_aiq_user_api2_accm_GetAttrib(self) -> ffi::rk_aiq_ccm_attrib_t)?;
+        write_manual_ccm(&mut attr, ccm);
+        uapi_call!(ffi::rk_aiq_user_api2_accm_SetAttrib(self, attr))
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn set_ccm_mode(&self, mode: OpMode) -> XCamResult<()> {
+        let mode = ccm_mode_from(mode)?;
+        let mut attr =
+            uapi_get!(ffi::rk_aiq_user_api_accm_GetAttrib(self) -> ffi::rk_aiq_ccm_attrib_t)?;
+        attr.mode = mode;
+        uapi_call!(ffi::rk_aiq_user_api_accm_SetAttrib(self, attr))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ccm_mode(&self, mode: OpMode) -> XCamResult<()> {
+        let mode = ccm_mode_from(mode)?;
+        let mut attr =
+            uapi_get!(ffi::rk_aiq_user_api2_accm_GetAttrib(self) -> ffi::rk_aiq_ccm_attrib_t)?;
+        attr.mode = mode;
+        uapi_call!(ffi::rk_aiq_user_api2_accm_SetAttrib(self, attr))
+    }
+
+    #[cfg(any(feature = "isp_hw_v20", feature = "isp_hw_v21", feature = "isp_hw_v30"))]
+    fn get_lut3d_state(&self) -> XCamResult<Lut3dState> {
+        let attr =
+            uapi_get!(ffi::rk_aiq_user_api2_a3dlut_GetAttrib(self) -> ffi::rk_aiq_lut3d_attrib_t)?;
+        Ok(Lut3dState {
+            enabled: !attr.byPass,
+            mode: lut3d_op_mode(attr.mode),
+        })
+    }
+
+    #[cfg(any(feature = "isp_hw_v20", feature = "isp_hw_v21", feature = "isp_hw_v30"))]
+    fn set_lut3d_state(&self, state: Lut3dState) -> XCamResult<()> {
+        let mode = lut3d_mode_from(state.mode)?;
+        let mut attr =
+            uapi_get!(ffi::rk_aiq_user_api2_a3dlut_GetAttrib(self) -> ffi::rk_aiq_lut3d_attrib_t)?;
+        attr.byPass = !state.enabled;
+        attr.mode = mode;
+        uapi_call!(ffi::rk_aiq_user_api2_a3dlut_SetAttrib(self, attr))
+    }
+
+    #[cfg(any(feature = "isp_hw_v20", feature = "isp_hw_v21", feature = "isp_hw_v30"))]
+    fn get_lut3d_table(&self) -> XCamResult<Lut3dTable> {
+        let attr =
+            uapi_get!(ffi::rk_aiq_user_api2_a3dlut_GetAttrib(self) -> ffi::rk_aiq_lut3d_attrib_t)?;
+        Ok(Lut3dTable {
+            r: attr.stManual.look_up_table_r.to_vec(),
+            g: attr.stManual.look_up_table_g.to_vec(),
+            b: attr.stManual.look_up_table_b.to_vec(),
+        })
+    }
+
+    #[cfg(any(feature = "isp_hw_v20", feature = "isp_hw_v21", feature = "isp_hw_v30"))]
+    fn set_lut3d_table(&self, table: &Lut3dTable) -> XCamResult<()> {
+        table.check()?;
+        let mut attr =
+            uapi_get!(ffi::rk_aiq_user_api2_a3dlut_GetAttrib(self) -> ffi::rk_aiq_lut3d_attrib_t)?;
+        attr.stManual.look_up_table_r.copy_from_slice(&table.r);
+        attr.stManual.look_up_table_g.copy_from_slice(&table.g);
+        attr.stManual.look_up_table_b.copy_from_slice(&table.b);
+        uapi_call!(ffi::rk_aiq_user_api2_a3dlut_SetAttrib(self, attr))
+    }
+}
+
+impl ColorCorrection for SharedContext {
+    fn get_ccm_query_info(&self) -> XCamResult<CcmQueryInfo> {
+        self.inner.get_ccm_query_info()
+    }
+
+    fn get_ccm_mode(&self) -> XCamResult<OpMode> {
+        self.inner.get_ccm_mode()
+    }
+
+    fn set_manual_ccm(&self, ccm: &Ccm) -> XCamResult<()> {
+        self.inner.set_manual_ccm(ccm)
+    }
+
+    fn set_ccm_mode(&self, mode: OpMode) -> XCamResult<()> {
+        self.inner.set_ccm_mode(mode)
+    }
+
+    #[cfg(any(feature = "isp_hw_v20", feature = "isp_hw_v21", feature = "isp_hw_v30"))]
+    fn get_lut3d_state(&self) -> XCamResult<Lut3dState> {
+        self.inner.get_lut3d_state()
+    }
+
+    #[cfg(any(feature = "isp_hw_v20", feature = "isp_hw_v21", feature = "isp_hw_v30"))]
+    fn set_lut3d_state(&self, state: Lut3dState) -> XCamResult<()> {
+        self.inner.set_lut3d_state(state)
+    }
+
+    #[cfg(any(feature = "isp_hw_v20", feature = "isp_hw_v21", feature = "isp_hw_v30"))]
+    fn get_lut3d_table(&self) -> XCamResult<Lut3dTable> {
+        self.inner.get_lut3d_table()
+    }
+
+    #[cfg(any(feature = "isp_hw_v20", feature = "isp_hw_v21", feature = "isp_hw_v30"))]
+    fn set_lut3d_table(&self, table: &Lut3dTable) -> XCamResult<()> {
+        self.inner.set_lut3d_table(table)
+    }
+}
+
+/// 将 `ccm` 写入 CCM 属性的手动参数并切换为手动模式。
+fn write_manual_ccm(attr: &mut ffi::rk_aiq_ccm_attrib_t, ccm: &Ccm) {
+    attr.mode = ffi::rk_aiq_ccm_op_mode_t::RK_AIQ_CCM_MODE_MANUAL;
+    attr.stManual.ccMatrix = ccm.to_flat();
+    attr.stManual.ccOffsets = ccm.offsets;
+}
+
+impl Context {
+    /// 设置按色温索引的 CCM 表，`entries` 为 `(色温, 矩阵)`，色温单位：K，须严格递增。
+    ///
+    /// 设置后由 `tick_ccm_table` 按当前色温插值并写入手动 CCM，插值方式见 `interpolate_ccm`；
+    /// 空表停用，已写入的手动 CCM 保持不变，需要时以 SDK 接口切回自动模式。
+    pub fn set_ccm_table(&self, entries: &[(u32, Ccm)]) -> XCamResult<()> {
+        self.ccm_table.lock().unwrap().set(entries)
+    }
+
+    /// 读取当前色温（`get_mwb_ct`），按 CCM 表插值并写入手动 CCM，见 `set_ccm_table`。
+    ///
+    /// 写入时返回 `true`；未设置表或插值结果与上次写入的相同时不调用 SDK，返回 `false`。
+    /// 应在每帧或按固定周期的处理循环中调用。
+    pub fn tick_ccm_table(&self) -> XCamResult<bool> {
+        tick_ccm_table(self, &mut self.ccm_table.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+
+    fn ill(name: &str, weight: f32) -> CcmIlluminant {
+        CcmIlluminant {
+            name: name.to_owned(),
+            weight,
+        }
+    }
+
+    #[test]
+    fn test_ccm_identity() {
+        let id = Ccm::identity();
+        assert!(id.is_finite());
+        assert_eq!(id.to_flat(), [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(id.normalize_rows(true), id);
+        assert_eq!(Ccm::from_flat(id.to_flat(), [0.0; 3]), id);
+
+        let mut bad = id;
+        bad.offsets[2] = f32::NAN;
+        assert!(!bad.is_finite());
+    }
+
+    #[test]
+    fn test_ccm_normalize_rows() {
+        let ccm = Ccm::from_rows(
+            [2.0, -0.5, -0.5],
+            [-0.25, 1.5, -0.25],
+            [0.0, 0.0, 0.0],
+            [1.0, 2.0, 3.0],
+        );
+        let n = ccm.normalize_rows(true);
+        assert_eq!(n.matrix[0], [2.0, -0.5, -0.5]);
+        assert_eq!(n.matrix[1], [-0.25, 1.5, -0.25]);
+        // 和为 0 的行无法缩放。
+        assert_eq!(n.matrix[2], [0.0, 0.0, 0.0]);
+        assert_eq!(n.offsets, [1.0, 2.0, 3.0]);
+
+        let ccm = Ccm::from_rows([3.0, -1.0, 0.0], [0.5, 1.0, 0.5], [0.0, 1.0, 3.0], [0.0; 3]);
+        let n = ccm.normalize_rows(true);
+        for row in n.matrix {
+            assert!((row.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        }
+        assert_eq!(n.matrix[0], [1.5, -0.5, 0.0]);
+        assert_eq!(ccm.normalize_rows(false), ccm);
+    }
+
+    #[test]
+    fn test_blend_illuminants() {
+        let blend = |a: (&str, f32), b: (&str, f32)| {
+            blend_illuminants([(a.0.to_owned(), a.1), (b.0.to_owned(), b.1)])
+        };
+        assert_eq!(
+            blend(("D65", 0.75), ("TL84", 0.25)),
+            vec![ill("D65", 0.75), ill("TL84", 0.25)]
+        );
+        // 只有一个光源生效。
+        assert_eq!(blend(("A", 1.0), ("", 0.0)), vec![ill("A", 1.0)]);
+        assert_eq!(blend(("A", 1.0), ("D50", 0.0)), vec![ill("A", 1.0)]);
+        assert_eq!(blend(("D65", 0.5), ("D65", 0.5)), vec![ill("D65", 1.0)]);
+        // 名称为空但有权重。
+        assert_eq!(
+            blend(("", 1.5), ("", 0.5)),
+            vec![ill("", 0.75), ill("", 0.25)]
+        );
+        // 未报告权重。
+        assert_eq!(blend(("CWF", 0.0), ("", 0.0)), vec![ill("CWF", 1.0)]);
+        assert_eq!(blend(("", 0.0), ("", f32::NAN)), vec![]);
+    }
+
+    #[test]
+    fn test_check_ccm_table() {
+        let id = Ccm::identity();
+        assert_eq!(check_ccm_table(&[]), Ok(()));
+        assert_eq!(check_ccm_table(&[(2800, id), (6500, id)]), Ok(()));
+        let param = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        assert_eq!(check_ccm_table(&[(6500, id), (2800, id)]), param);
+        assert_eq!(check_ccm_table(&[(5000, id), (5000, id)]), param);
+        let mut bad = id;
+        bad.matrix[1][1] = f32::INFINITY;
+        assert_eq!(check_ccm_table(&[(5000, bad)]), param);
+    }
+
+    #[test]
+    fn test_interpolate_ccm() {
+        let warm = Ccm::from_rows(
+            [2.0, -0.6, -0.4],
+            [-0.4, 1.6, -0.2],
+            [0.0, -1.0, 2.0],
+            [4.0, 0.0, -2.0],
+        );
+        let table = [(3000, warm), (6000, Ccm::identity())];
+        assert_eq!(interpolate_ccm(&[], 5000), None);
+        let mid = interpolate_ccm(&table, 4500).unwrap();
+        assert_eq!(
+            mid,
+            Ccm::from_rows(
+                [1.5, -0.3, -0.2],
+                [-0.2, 1.3, -0.1],
+                [0.0, -0.5, 1.5],
+                [2.0, 0.0, -1.0],
+            )
+        );
+        // 超出范围时取最近一端。
+        assert_eq!(interpolate_ccm(&table, 2000), Some(warm));
+        assert_eq!(interpolate_ccm(&table, 3000), Some(warm));
+        assert_eq!(interpolate_ccm(&table, 9000), Some(Ccm::identity()));
+    }
+
+    #[test]
+    fn test_tick_ccm_table() {
+        let ctx = MockContext::new();
+        let mut table = CcmTable::default();
+        assert_eq!(tick_ccm_table(&ctx, &mut table), Ok(false));
+        assert!(ctx.calls().is_empty());
+
+        let warm = Ccm::from_rows([2.0, -1.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.0; 3]);
+        table.set(&[(3000, warm), (7000, Ccm::identity())]).unwrap();
+        ctx.set_mwb_ct(5000).unwrap();
+        assert_eq!(tick_ccm_table(&ctx, &mut table), Ok(true));
+        let expected = Ccm::from_rows([1.5, -0.5, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.0; 3]);
+        assert_eq!(ctx.state.borrow().manual_ccm, Some(expected));
+
+        // 色温不变时不重复写入。
+        assert_eq!(tick_ccm_table(&ctx, &mut table), Ok(false));
+        assert_eq!(
+            ctx.calls()
+                .iter()
+                .filter(|&&c| c == "set_manual_ccm")
+                .count(),
+            1
+        );
+        assert!(table.set(&[(7000, warm), (3000, warm)]).is_err());
+    }
+}
@@ -0,0 +1,29 @@
+//! 时钟
+//!
+//! 需要按时间等待的接口通过 `Clock` 读取当前时间并休眠，测试中可替换为手动推进的时钟，
+//! 不必真的等待。
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 一个提供当前时间与休眠的时钟。
+pub trait Clock {
+    /// 当前时间。
+    fn now(&self) -> Instant;
+
+    /// 休眠 `dur`。
+    fn sleep(&self, dur: Duration);
+}
+
+/// 使用系统单调时钟的 `Clock`。
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, dur: Duration) {
+        thread::sleep(dur);
+    }
+}
@@ -0,0 +1,318 @@
+//! 色彩链路
+//!
+//! 排查偏色问题时需要同时确认色彩处理链路上各级的状态。`get_color_pipeline` 按处理顺序读取
+//! 各级的当前参数，汇总为一个结构：
+//!
+//! | 级 | 来源 |
+//! | --- | --- |
+//! | 白平衡增益 | `get_mwb_gain` |
+//! | CCM | `get_ccm_mode`、`get_ccm_query_info` |
+//! | 3D LUT | `get_lut3d_state`、`get_lut3d_table`，硬件没有该模块时为 `None` |
+//! | Gamma | `get_gamma_mode`，手动模式下另有 `get_gamma_manual_curve`，当前版本不支持查询时为 `None` |
+//!
+//! 读取仅用于诊断：只调用读取接口，不修改任何设置。除上述可缺省的两级外，任一级读取失败时返回该错误。
+//! 启用 `serde` 特性后可序列化，便于随问题报告附带；启用 `json` 特性后可保存为文件，
+//! 见 `colorprofile` 模块。
+//!
+//! `ColorPipeline::apply` 按同样的处理顺序写回各级：白平衡增益、CCM、3D LUT、Gamma。
+//! 自动 CCM 按白平衡估计的光源插值，先写入白平衡增益，切换回自动模式的 CCM 即按写入后的增益
+//! 插值；Gamma 作用于前级的输出，最后写入。写回白平衡增益会切换为手动白平衡，即冻结色彩链路。
+//! 写入前先检查全部各级，任一级无法写回（参数无效、硬件没有该模块，或 Gamma 需要回到
+//! 无法写入的自动模式）时返回错误，不写入任何一级。
+use super::awb::AutoWhiteBalance;
+use super::ccm::{Ccm, ColorCorrection, Lut3dState, Lut3dTable};
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::gamma::{check_gamma_curve, Gamma};
+use super::types::{OpMode, WbGain, XCamResult};
+
+/// 一个描述色彩处理链路各级状态的类型，按处理顺序排列。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ColorPipeline {
+    /// 白平衡增益 `[R, Gr, Gb, B]`。
+    pub wb_gain: [f32; 4],
+    /// CCM 的工作模式。
+    pub ccm_mode: OpMode,
+    /// CCM 是否使能。
+    pub ccm_enabled: bool,
+    /// 当前生效的校正矩阵。
+    pub ccm: Ccm,
+    /// 3D LUT 的状态，硬件没有该模块时为 `None`。
+    pub lut3d: Option<Lut3dState>,
+    /// 3D LUT 的手动查找表，硬件没有该模块时为 `None`。
+    pub lut3d_table: Option<Lut3dTable>,
+    /// Gamma 的工作模式，Gamma 关闭时为 `OpMode::Invalid`，不支持查询时为 `None`。
+    pub gamma_mode: Option<OpMode>,
+    /// Gamma 的手动曲线，只在 `gamma_mode` 为 `OpMode::Manual` 时读取，其余为 `None`。
+    pub gamma_curve: Option<Vec<u16>>,
+}
+
+/// 将 `XCamError::Unsupported` 转换为 `None`。
+fn optional<T>(r: XCamResult<T>) -> XCamResult<Option<T>> {
+    match r {
+        Ok(v) => Ok(Some(v)),
+        Err(XCamError::Unsupported) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// 读取 `ctx` 的色彩链路，见模块说明。
+pub(crate) fn color_pipeline<C>(ctx: &C) -> XCamResult<ColorPipeline>
+where
+    C: AutoWhiteBalance + ColorCorrection + Gamma + ?Sized,
+{
+    let gain = ctx.get_mwb_gain()?;
+    let ccm_mode = ctx.get_ccm_mode()?;
+    let info = ctx.get_ccm_query_info()?;
+    let lut3d = optional(ctx.get_lut3d_state())?;
+    let lut3d_table = match lut3d {
+        Some(_) => optional(ctx.get_lut3d_table())?,
+        None => None,
+    };
+    let gamma_mode = optional(ctx.get_gamma_mode())?;
+    let gamma_curve = match gamma_mode {
+        Some(OpMode::Manual) => optional(ctx.get_gamma_manual_curve())?,
+        _ => None,
+    };
+    Ok(ColorPipeline {
+        wb_gain: [gain.rgain, gain.grgain, gain.gbgain, gain.bgain],
+        ccm_mode,
+        ccm_enabled: info.enabled,
+        ccm: info.ccm,
+        lut3d,
+        lut3d_table,
+        gamma_mode,
+        gamma_curve,
+    })
+}
+
+impl ColorPipeline {
+    /// 按处理顺序将各级状态写回 `ctx`，顺序见模块说明。
+    ///
+    /// 为 `None` 的级保持不变。写入前由 `check` 检查全部各级，失败时不写入任何一级；
+    /// 写入过程中 SDK 返回错误时立即返回，此前已写入的级不会回滚。
+    pub fn apply<C>(&self, ctx: &C) -> XCamResult<()>
+    where
+        C: AutoWhiteBalance + ColorCorrection + Gamma + ?Sized,
+    {
+        self.check(ctx)?;
+        let [rgain, grgain, gbgain, bgain] = self.wb_gain;
+        ctx.set_mwb_gain(WbGain {
+            rgain,
+            grgain,
+            gbgain,
+            bgain,
+        })?;
+        match self.ccm_mode {
+            OpMode::Manual => ctx.set_manual_ccm(&self.ccm)?,
+            mode => ctx.set_ccm_mode(mode)?,
+        }
+        // 先写入查找表，切换为手动模式时即使用新表。
+        if let Some(table) = &self.lut3d_table {
+            ctx.set_lut3d_table(table)?;
+        }
+        if let Some(lut3d) = self.lut3d {
+            ctx.set_lut3d_state(lut3d)?;
+        }
+        match (&self.gamma_curve, self.gamma_mode) {
+            (Some(curve), _) => ctx.set_gamma_manual_curve(curve)?,
+            (None, Some(mode)) => ctx.set_gamma_mode(mode)?,
+            (None, None) => {}
+        }
+        Ok(())
+    }
+
+    /// 检查各级能否写回 `ctx`，只调用读取接口，不修改任何设置。
+    ///
+    /// 参数无效时返回 `XCAM_RETURN_ERROR_PARAM`，包括：白平衡增益不是正的有限值，CCM 或 3D LUT
+    /// 的模式不是 `OpMode::Auto` 或 `OpMode::Manual`，查找表或曲线长度不符，以及只有手动模式
+    /// 才带曲线。Gamma 属性中没有自动模式，`gamma_mode` 为 `OpMode::Auto` 而 `ctx` 当前不是自动
+    /// 模式时同样返回 `XCAM_RETURN_ERROR_PARAM`。`ctx` 没有 3D LUT 或 Gamma 模块而对应级不为
+    /// `None` 时返回读取接口的错误。
+    pub fn check<C>(&self, ctx: &C) -> XCamResult<()>
+    where
+        C: ColorCorrection + Gamma + ?Sized,
+    {
+        let param = || XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM);
+        if !self.wb_gain.iter().all(|g| g.is_finite() && *g > 0.0) {
+            return Err(param());
+        }
+        match self.ccm_mode {
+            OpMode::Manual if !self.ccm.is_finite() => return Err(param()),
+            OpMode::Auto | OpMode::Manual => {}
+            _ => return Err(param()),
+        }
+        if self.lut3d.is_some() || self.lut3d_table.is_some() {
+            ctx.get_lut3d_state()?;
+        }
+        if let Some(lut3d) = self.lut3d {
+            if !matches!(lut3d.mode, OpMode::Auto | OpMode::Manual) {
+                return Err(param());
+            }
+        }
+        if let Some(table) = &self.lut3d_table {
+            table.check()?;
+        }
+        match (self.gamma_mode, &self.gamma_curve) {
+            (None, None) => Ok(()),
+            (Some(OpMode::Manual), Some(curve)) => check_gamma_curve(curve),
+            (Some(OpMode::Manual | OpMode::Invalid), None) => ctx.get_gamma_mode().map(drop),
+            (Some(OpMode::Auto), None) => match ctx.get_gamma_mode()? {
+                OpMode::Auto => Ok(()),
+                _ => Err(param()),
+            },
+            _ => Err(param()),
+        }
+    }
+}
+
+impl Context {
+    /// 读取色彩处理链路各级的当前状态，仅用于诊断，见模块说明。
+    pub fn get_color_pipeline(&self) -> XCamResult<ColorPipeline> {
+        color_pipeline(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ccm::LUT3D_TABLE_LEN;
+    use crate::mock::MockContext;
+
+    #[test]
+    fn test_color_pipeline() {
+        let ctx = MockContext::new();
+        let ccm = Ccm::from_rows(
+            [1.5, -0.3, -0.2],
+            [-0.2, 1.4, -0.2],
+            [-0.1, -0.4, 1.5],
+            [0.0; 3],
+        );
+        let lut3d = Lut3dState {
+            enabled: true,
+            mode: OpMode::Auto,
+        };
+        {
+            let mut state = ctx.state.borrow_mut();
+            state.wb_gain = WbGain {
+                rgain: 1.8,
+                grgain: 1.0,
+                gbgain: 1.0,
+                bgain: 1.5,
+            };
+            state.manual_ccm = Some(ccm);
+            state.lut3d = Some(lut3d);
+        }
+        let pipeline = color_pipeline(&ctx).unwrap();
+        assert_eq!(pipeline.wb_gain, [1.8, 1.0, 1.0, 1.5]);
+        assert_eq!(pipeline.ccm_mode, OpMode::Manual);
+        assert!(pipeline.ccm_enabled);
+        assert_eq!(pipeline.ccm, ccm);
+        assert_eq!(pipeline.lut3d, Some(lut3d));
+        // 只调用读取接口。
+        assert!(ctx.calls().iter().all(|c| c.starts_with("get_")));
+
+        // 没有 3D LUT 时该级为 `None`，其余级读取失败时返回错误。
+        ctx.state.borrow_mut().lut3d = None;
+        assert_eq!(color_pipeline(&ctx).unwrap().lut3d, None);
+        ctx.fail("get_ccm_query_info");
+        assert_eq!(
+            color_pipeline(&ctx),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))
+        );
+    }
+
+    #[test]
+    fn test_color_pipeline_lut3d_table() {
+        let ctx = MockContext::new();
+        let table = Lut3dTable {
+            r: vec![1; LUT3D_TABLE_LEN],
+            g: vec![2; LUT3D_TABLE_LEN],
+            b: vec![3; LUT3D_TABLE_LEN],
+        };
+        ctx.state.borrow_mut().lut3d = Some(Lut3dState {
+            enabled: true,
+            mode: OpMode::Manual,
+        });
+        ctx.state.borrow_mut().lut3d_table = table.clone();
+        let pipeline = color_pipeline(&ctx).unwrap();
+        assert_eq!(pipeline.lut3d_table, Some(table.clone()));
+
+        let other = MockContext::new();
+        other.state.borrow_mut().lut3d = Some(Lut3dState {
+            enabled: false,
+            mode: OpMode::Auto,
+        });
+        pipeline.apply(&other).unwrap();
+        assert_eq!(other.state.borrow().lut3d_table, table);
+        assert_eq!(other.state.borrow().lut3d, pipeline.lut3d);
+    }
+
+    #[test]
+    fn test_color_pipeline_checks_before_writing() {
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().lut3d = Some(Lut3dState {
+            enabled: true,
+            mode: OpMode::Auto,
+        });
+        let pipeline = color_pipeline(&ctx).unwrap();
+        let param = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+
+        // 最后一级无效时前面各级同样不写入。
+        let mut bad = pipeline.clone();
+        bad.lut3d_table.as_mut().unwrap().g.pop();
+        assert_eq!(bad.apply(&ctx), param);
+        let mut bad = pipeline.clone();
+        bad.gamma_mode = Some(OpMode::Invalid);
+        bad.gamma_curve = Some(Vec::new());
+        assert_eq!(bad.apply(&ctx), param);
+        let mut bad = pipeline.clone();
+        bad.wb_gain[0] = f32::NAN;
+        assert_eq!(bad.apply(&ctx), param);
+
+        // 目标没有 3D LUT 模块时不写入白平衡与 CCM。
+        let other = MockContext::new();
+        assert_eq!(pipeline.apply(&other), Err(XCamError::Unsupported));
+        assert!(ctx
+            .calls()
+            .iter()
+            .chain(other.calls().iter())
+            .all(|c| c.starts_with("get_")));
+    }
+
+    #[cfg(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    ))]
+    #[test]
+    fn test_color_pipeline_gamma() {
+        use crate::gamma::GAMMA_CURVE_LEN;
+        let ctx = MockContext::new();
+        let curve: Vec<u16> = (0..GAMMA_CURVE_LEN as u16).map(|i| i * 80).collect();
+        ctx.set_gamma_manual_curve(&curve).unwrap();
+        let pipeline = color_pipeline(&ctx).unwrap();
+        assert_eq!(pipeline.gamma_mode, Some(OpMode::Manual));
+        assert_eq!(pipeline.gamma_curve.as_deref(), Some(&curve[..]));
+
+        let other = MockContext::new();
+        other.set_gamma_mode(OpMode::Invalid).unwrap();
+        pipeline.apply(&other).unwrap();
+        assert_eq!(other.get_gamma_manual_curve(), Ok(curve));
+
+        // Gamma 属性中没有自动模式，不能从手动模式写回自动模式。
+        let mut auto = pipeline.clone();
+        auto.gamma_mode = Some(OpMode::Auto);
+        auto.gamma_curve = None;
+        let sets = other.calls().len();
+        assert_eq!(
+            auto.apply(&other),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        assert!(other.calls()[sets..].iter().all(|c| c.starts_with("get_")));
+    }
+}
@@ -0,0 +1,226 @@
+//! 色彩链路配置文件
+//!
+//! 将 `get_color_pipeline` 读取的色彩链路保存为 JSON 文件，之后可由 `Context::load_color_pipeline`
+//! 读取并按处理顺序写回（顺序见 `colorpipe` 模块），用于在不同场景间切换整条色彩链路：
+//!
+//! ```json
+//! {
+//!   "schema_version": 2,
+//!   "pipeline": {
+//!     "wb_gain": [1.8, 1.0, 1.0, 1.5],
+//!     "ccm_mode": "manual",
+//!     ...
+//!   }
+//! }
+//! ```
+//!
+//! 读取时先检查 `schema_version`，与 `COLOR_PROFILE_SCHEMA_VERSION` 不同（包括缺失）时返回
+//! `ColorProfileError::Version`，不写回任何一级；其余内容按严格模式解析，出现未知字段时返回错误。
+//! 仅在启用 `json` 特性时编译。
+use super::colorpipe::ColorPipeline;
+use super::context::Context;
+use super::error::XCamError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// 色彩链路配置文件当前的格式版本，增删字段或改变字段含义时递增。
+pub const COLOR_PROFILE_SCHEMA_VERSION: u32 = 2;
+
+/// 一个描述色彩链路配置文件错误的枚举。
+#[derive(Debug)]
+pub enum ColorProfileError {
+    /// 读写文件失败。
+    Io(io::Error),
+    /// 解析 JSON 失败，包括出现未知字段。
+    Parse(serde_json::Error),
+    /// 格式版本不兼容，缺失时为 0。
+    Version(u32),
+    /// 写回色彩链路失败。
+    Apply(XCamError),
+}
+
+impl fmt::Display for ColorProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::Parse(e) => write!(f, "Parse error: {}", e),
+            Self::Version(v) => write!(
+                f,
+                "Incompatible schema version: {} (expected {})",
+                v, COLOR_PROFILE_SCHEMA_VERSION
+            ),
+            Self::Apply(e) => write!(f, "Apply error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ColorProfileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Parse(e) => Some(e),
+            Self::Version(_) => None,
+            Self::Apply(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ColorProfileError {
+    fn from(val: io::Error) -> Self {
+        Self::Io(val)
+    }
+}
+
+impl From<serde_json::Error> for ColorProfileError {
+    fn from(val: serde_json::Error) -> Self {
+        Self::Parse(val)
+    }
+}
+
+impl From<XCamError> for ColorProfileError {
+    fn from(val: XCamError) -> Self {
+        Self::Apply(val)
+    }
+}
+
+/// 配置文件的内容。
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColorProfile {
+    schema_version: u32,
+    pipeline: ColorPipeline,
+}
+
+impl ColorPipeline {
+    /// 转换为配置文件的 JSON 文本，见模块说明。
+    pub fn to_profile_json(&self) -> String {
+        serde_json::to_string_pretty(&ColorProfile {
+            schema_version: COLOR_PROFILE_SCHEMA_VERSION,
+            pipeline: self.clone(),
+        })
+        .expect("color pipeline is serializable")
+    }
+
+    /// 从配置文件的 JSON 文本解析，先检查格式版本，见模块说明。
+    pub fn from_profile_json(s: &str) -> Result<Self, ColorProfileError> {
+        let value: Value = serde_json::from_str(s)?;
+        let version = value
+            .get("schema_version")
+            .and_then(Value::as_u64)
+            .map_or(0, |v| u32::try_from(v).unwrap_or(u32::MAX));
+        if version != COLOR_PROFILE_SCHEMA_VERSION {
+            return Err(ColorProfileError::Version(version));
+        }
+        Ok(ColorProfile::deserialize(value)?.pipeline)
+    }
+
+    /// 保存为配置文件。
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ColorProfileError> {
+        Ok(std::fs::write(path, self.to_profile_json())?)
+    }
+
+    /// 读取配置文件，不写回，见 `Context::load_color_pipeline`。
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ColorProfileError> {
+        Self::from_profile_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+impl Context {
+    /// 读取色彩链路配置文件并按处理顺序写回，返回读取的色彩链路。
+    ///
+    /// 格式版本不兼容时不写回任何一级；写回失败时此前已写入的级不会回滚，见 `ColorPipeline::apply`。
+    pub fn load_color_pipeline<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<ColorPipeline, ColorProfileError> {
+        let pipeline = ColorPipeline::load(path)?;
+        pipeline.apply(self)?;
+        Ok(pipeline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ccm::{Ccm, Lut3dState};
+    use crate::colorpipe::color_pipeline;
+    use crate::mock::MockContext;
+    use crate::types::{OpMode, WbGain};
+
+    #[test]
+    fn test_color_profile_round_trip() {
+        let ctx = MockContext::new();
+        {
+            let mut state = ctx.state.borrow_mut();
+            state.wb_gain = WbGain {
+                rgain: 1.8,
+                grgain: 1.0,
+                gbgain: 1.0,
+                bgain: 1.5,
+            };
+            state.manual_ccm = Some(Ccm::from_rows(
+                [1.5, -0.3, -0.2],
+                [-0.2, 1.4, -0.2],
+                [-0.1, -0.4, 1.5],
+                [0.0; 3],
+            ));
+            state.lut3d = Some(Lut3dState {
+                enabled: false,
+                mode: OpMode::Manual,
+            });
+        }
+        let saved = color_pipeline(&ctx).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("rkaiq-colorprofile-{}.json", std::process::id()));
+        saved.save(&path).unwrap();
+
+        let other = MockContext::new();
+        other.state.borrow_mut().lut3d = Some(Lut3dState {
+            enabled: true,
+            mode: OpMode::Auto,
+        });
+        let loaded = ColorPipeline::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, saved);
+        loaded.apply(&other).unwrap();
+        assert_eq!(color_pipeline(&other).unwrap(), saved);
+        let sets: Vec<_> = other
+            .calls()
+            .into_iter()
+            .filter(|c| c.starts_with("set_"))
+            .collect();
+        assert_eq!(
+            sets[..4],
+            [
+                "set_mwb_gain",
+                "set_manual_ccm",
+                "set_lut3d_table",
+                "set_lut3d_state"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_color_profile_version() {
+        assert!(matches!(
+            ColorPipeline::from_profile_json(r#"{"pipeline": {}}"#),
+            Err(ColorProfileError::Version(0))
+        ));
+        let ctx = MockContext::new();
+        let json = color_pipeline(&ctx).unwrap().to_profile_json();
+        let future = json.replace("\"schema_version\": 2", "\"schema_version\": 3");
+        assert!(matches!(
+            ColorPipeline::from_profile_json(&future),
+            Err(ColorProfileError::Version(3))
+        ));
+        let unknown = json.replace("\"pipeline\"", "\"extra\": 0,\n  \"pipeline\"");
+        assert!(matches!(
+            ColorPipeline::from_profile_json(&unknown),
+            Err(ColorProfileError::Parse(_))
+        ));
+        assert!(ColorPipeline::from_profile_json(&json).is_ok());
+    }
+}
@@ -0,0 +1,218 @@
+//! 配置文件
+//!
+//! 从 TOML 文件读取 `CameraSettings`。解析采用严格模式，出现未知字段时返回错误。
+//! `CameraSettings` 与 `IspStateDump` 均可转换为 TOML 文本，后者的格式见 `dump` 模块。
+//! 文件格式参见 `examples/camera.toml`：
+//!
+//! ```toml
+//! [white_balance]
+//! mode = "manual"
+//! ct = 5000
+//!
+//! [exposure]
+//! gain_range = { min = 1.0, max = 32.0 }
+//!
+//! [adjust]
+//! brightness = 128
+//!
+//! [noise_reduction]
+//! strength = 50
+//! ```
+use super::context::Context;
+use super::dump::IspStateDump;
+use super::error::XCamError;
+use super::settings::CameraSettings;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// 一个描述配置文件错误的枚举。
+#[derive(Debug)]
+pub enum ConfigError {
+    /// 读取文件失败。
+    Io(io::Error),
+    /// 解析 TOML 失败，包括出现未知字段。
+    Parse(toml::de::Error),
+    /// 应用参数失败。
+    Apply(XCamError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::Parse(e) => write!(f, "Parse error: {}", e),
+            Self::Apply(e) => write!(f, "Apply error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Parse(e) => Some(e),
+            Self::Apply(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(val: io::Error) -> Self {
+        Self::Io(val)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(val: toml::de::Error) -> Self {
+        Self::Parse(val)
+    }
+}
+
+impl From<XCamError> for ConfigError {
+    fn from(val: XCamError) -> Self {
+        Self::Apply(val)
+    }
+}
+
+impl CameraSettings {
+    /// 从 TOML 字符串解析参数。
+    pub fn from_toml_str(s: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// 从 TOML 文件读取参数。
+    pub fn from_toml_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::from_toml_str(&std::fs::read_to_string(path)?)
+    }
+
+    /// 转换为 TOML 字符串，总是写入格式版本，未设置的项省略不写。
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string_pretty(self)
+    }
+}
+
+impl IspStateDump {
+    /// 转换为 TOML 字符串。
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string_pretty(self)
+    }
+
+    /// 从 TOML 字符串解析导出结果。
+    pub fn from_toml(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+}
+
+impl Context {
+    /// 从 TOML 文件读取参数并应用到上下文。
+    pub fn apply_settings_from_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        Ok(CameraSettings::from_toml_file(path)?.apply(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{GainSettings, RangeSettings};
+    use crate::types::{OpMode, Strength};
+
+    const EXAMPLE: &str = include_str!("../examples/camera.toml");
+
+    #[test]
+    fn test_parse_example() {
+        let s = CameraSettings::from_toml_str(EXAMPLE).unwrap();
+        let wb = s.white_balance.unwrap();
+        assert_eq!(wb.mode, Some(OpMode::Manual));
+        assert_eq!(wb.ct, Some(5000));
+        assert_eq!(
+            wb.gain,
+            Some(GainSettings {
+                r: 1.8,
+                gr: 1.0,
+                gb: 1.0,
+                b: 1.6
+            })
+        );
+        let exp = s.exposure.unwrap();
+        assert_eq!(
+            exp.gain_range,
+            Some(RangeSettings {
+                min: 1.0,
+                max: 32.0
+            })
+        );
+        assert_eq!(s.adjust.unwrap().saturation, Strength::new(55));
+        assert_eq!(s.noise_reduction.unwrap().temporal, Strength::new(60));
+    }
+
+    #[test]
+    fn test_parse_partial() {
+        let s = CameraSettings::from_toml_str("[adjust]\nbrightness = 100\n").unwrap();
+        assert_eq!(s.white_balance, None);
+        assert_eq!(s.adjust.unwrap().brightness, Some(100));
+        assert_eq!(s.adjust.unwrap().contrast, None);
+        assert_eq!(
+            CameraSettings::from_toml_str("").unwrap(),
+            CameraSettings::default()
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        for s in [
+            "[adjust]\nbrightnes = 100\n",
+            "[shutter]\nspeed = 1\n",
+            "[white_balance]\nmode = \"sometimes\"\n",
+            "[exposure]\ngain_range = { min = 1.0 }\n",
+            "[adjust]\nbrightness = -1\n",
+            "[adjust]\nsharpness = 101\n",
+            "[adjust\nbrightness = 1\n",
+        ] {
+            assert!(
+                matches!(CameraSettings::from_toml_str(s), Err(ConfigError::Parse(_))),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let s = CameraSettings::from_toml_str(EXAMPLE).unwrap();
+        let text = s.to_toml().unwrap();
+        assert_eq!(CameraSettings::from_toml_str(&text).unwrap(), s);
+        assert_eq!(
+            CameraSettings::default().to_toml().unwrap(),
+            "schema_version = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_dump_round_trip() {
+        let dump = crate::dump::sample_dump();
+        let text = dump.to_toml().unwrap();
+        assert!(!text.contains("frame_id"));
+        assert_eq!(IspStateDump::from_toml(&text).unwrap(), dump);
+
+        // 其它 SDK 版本导出的结果缺少版本相关的查询。
+        #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+        {
+            let text: String = text
+                .lines()
+                .filter(|l| !l.starts_with("compensation"))
+                .map(|l| format!("{}\n", l))
+                .collect();
+            let parsed = IspStateDump::from_toml(&text).unwrap();
+            assert_eq!(parsed.exposure.compensation, Err("Unsupported".to_owned()));
+        }
+    }
+
+    #[test]
+    fn test_missing_file() {
+        assert!(matches!(
+            CameraSettings::from_toml_file("/nonexistent/camera.toml"),
+            Err(ConfigError::Io(_))
+        ));
+    }
+}
@@ -1,14 +1,169 @@
+use super::adjust::CprocLevels;
+#[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+use super::ae::SavedExpRanges;
+use super::ae::DEFAULT_BASE_ISO;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::ae::{AeConvergenceState, SavedToleranceIn};
+use super::ae::{AutoFrameRate, OneShotExposure, TimingHistory};
+use super::af::FocusMeter;
+use super::average::StatsAveraging;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::awb::AwbCtLimit;
+use super::awb::AwbStepState;
+use super::awb::MwbApplyState;
+use super::calib::{self, CalibFile, TempCalib};
+use super::callback::callback_guard;
+use super::capability::Capabilities;
+use super::ccm::CcmTable;
+use super::device::DeviceState;
+use super::error::XCamError;
 use super::ffi::{self, XCamReturn};
-use super::sysctl;
-use super::types::WorkingMode;
+use super::gamma::GammaCurves;
+use super::hdr::HdrPassthroughState;
+use super::media::{self, MediaNode};
+use super::misc::Miscellaneous;
+use super::schedule::Scheduler;
+use super::sdkprobe;
+use super::settings::CameraSettings;
+use super::sysctl::{self, IspErrorFlags, PrepareResult, SystemControl, UpdateInterval};
+use super::timeout::{self, CancelToken};
+use super::types::{FrameRateInfo, Resolution, WbGain, WorkingMode, XCamResult};
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::wbpriority::WbPriorityState;
 
 use std::borrow::Cow;
 use std::ffi::CString;
 use std::io;
 use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU32, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// 最近一次元数据回调报告的帧号，尚未收到回调时为 -1。
+///
+/// 元数据回调不携带上下文指针，因此该帧号为进程内全部上下文共享。
+static LAST_FRAME_ID: AtomicI64 = AtomicI64::new(-1);
+
+/// 获取最近一次元数据回调报告的帧号。
+pub(crate) fn last_frame_id() -> Option<u32> {
+    let id = LAST_FRAME_ID.load(Ordering::Acquire);
+    (id >= 0).then_some(id as u32)
+}
+
+/// 错误回调累积的 ISP 错误标志。
+///
+/// 与帧号相同，错误回调不携带上下文指针，因此该标志为进程内全部上下文共享。
+static ISP_ERROR_FLAGS: AtomicU32 = AtomicU32::new(0);
+
+/// 获取已累积的 ISP 错误标志。
+pub(crate) fn isp_error_flags() -> IspErrorFlags {
+    IspErrorFlags::from_bits_retain(ISP_ERROR_FLAGS.load(Ordering::Acquire))
+}
+
+/// 清除已累积的 ISP 错误标志。
+pub(crate) fn clear_isp_error_flags() {
+    ISP_ERROR_FLAGS.store(0, Ordering::Release);
+}
+
+/// 下一个上下文的序号，用于没有传感器实体名称时的默认标签。
+static NEXT_CONTEXT_INDEX: AtomicU32 = AtomicU32::new(0);
+
+/// 同一帧内读取多个参数的最大尝试次数。
+const SAME_FRAME_ATTEMPTS: usize = 3;
+
+/// 尽量在同一帧内完成一组读取。
+///
+/// 读取前后帧号不一致时重新读取，最多尝试 `SAME_FRAME_ATTEMPTS` 次；
+/// 仍不一致时返回最后一次读取的结果及读取结束时的帧号。
+pub(crate) fn read_in_frame<T, F, R>(frame_id: F, mut read: R) -> XCamResult<(T, Option<u32>)>
+where
+    F: Fn() -> Option<u32>,
+    R: FnMut() -> XCamResult<T>,
+{
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let before = frame_id();
+        let value = read()?;
+        let after = frame_id();
+        if before == after || attempt >= SAME_FRAME_ATTEMPTS {
+            return Ok((value, after));
+        }
+    }
+}
 
 pub struct Context {
     pub(crate) internal: NonNull<ffi::rk_aiq_sys_ctx_t>,
+    /// 析构时是否释放 `internal`，见 `from_raw_parts`。
+    owned: bool,
+    /// `deinit` 或析构已释放 `internal` 后置位，防止重复释放。
+    deinited: AtomicBool,
+    pub(crate) sns_ent_name: String,
+    /// 日志中区分上下文的标签，见 `set_label`。
+    label: Mutex<String>,
+    /// 创建时的 IQ 文件目录，供 `reconnect` 重建时使用。
+    pub(crate) iq_file_dir: String,
+    /// 掉线状态及状态变化回调。
+    pub(crate) device: DeviceState,
+    pub(crate) caps: OnceLock<Capabilities>,
+    pub(crate) hdr_passthrough: Mutex<Option<HdrPassthroughState>>,
+    pub(crate) base_iso: AtomicU32,
+    /// `set_sensor_aperture` 设置的光圈 f 值的位表示，未设置时为 0。
+    pub(crate) aperture: AtomicU32,
+    /// 通过 `enable_ldch`、`disable_ldch` 设置的 LDCH 开关。
+    pub(crate) ldch_enabled: AtomicBool,
+    /// 通过 `enable_fec`、`disable_fec` 设置的 FEC 开关。
+    pub(crate) fec_enabled: AtomicBool,
+    /// 最近一次 `prepare` 的协商结果。
+    pub(crate) prepared: Mutex<Option<PrepareResult>>,
+    /// `start` 成功后置位，`stop` 成功后清除。
+    pub(crate) streaming: AtomicBool,
+    /// `set_awb_max_step` 设置的步长限制及模拟线程。
+    pub(crate) awb_step: Mutex<Option<AwbStepState>>,
+    /// 首次限制色温范围前 AWB 属性中的标定色温范围。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub(crate) awb_ct_defaults: Mutex<Option<AwbCtLimit>>,
+    /// `get_focus_score` 使用的清晰度历史。
+    pub(crate) focus_meter: Mutex<FocusMeter>,
+    /// 模拟曝光优先模式前保存的曝光范围。
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    pub(crate) exp_priority_ranges: Mutex<Option<SavedExpRanges>>,
+    /// `set_ae_deadband` 保存的进入容差。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub(crate) ae_deadband_tolerance_in: Mutex<Option<SavedToleranceIn>>,
+    /// 首次修改曝光增益范围前的增益上限，即标定中传感器可达的增益。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub(crate) sensor_gain_max: Mutex<Option<f32>>,
+    /// `set_oneshot_exposure` 设置后待恢复的曝光状态。
+    pub(crate) oneshot_exp: Mutex<Option<OneShotExposure>>,
+    /// `enable_auto_framerate` 设置的自动帧率状态。
+    pub(crate) auto_fps: Mutex<Option<AutoFrameRate>>,
+    /// `set_ae_convergence` 设置的双速率收敛状态。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub(crate) ae_convergence: Mutex<Option<AeConvergenceState>>,
+    /// 首次设置自动色阶前的 CPROC 等级。
+    pub(crate) auto_levels_prior: Mutex<Option<CprocLevels>>,
+    /// `set_zoom_calibration` 设置的变焦标定表，未设置时为空。
+    pub(crate) zoom_calib: Mutex<Vec<(u16, f32)>>,
+    /// `apply_factory_wb_compensation` 安装的白平衡补偿系数。
+    pub(crate) factory_wb: Mutex<Option<WbGain>>,
+    /// `set_mwb_apply_mode` 设置的手动增益生效方式。
+    pub(crate) mwb_apply: Mutex<MwbApplyState>,
+    /// `set_ccm_table` 设置的按色温索引的 CCM 表。
+    pub(crate) ccm_table: Mutex<CcmTable>,
+    /// `set_wb_priority` 离开 `Balanced` 前的色温范围与目标亮度。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub(crate) wb_priority: Mutex<Option<WbPriorityState>>,
+    /// `schedule` 记录的尚未应用的设置。
+    pub(crate) scheduler: Mutex<Scheduler<Context>>,
+    /// `set_3a_update_interval` 设置的 3A 运行间隔。
+    pub(crate) update_interval: Mutex<UpdateInterval>,
+    /// `set_stats_averaging_window` 设置的 3A 统计平均窗口及缓冲区。
+    pub(crate) stats_avg: Mutex<StatsAveraging>,
+    /// `set_gamma_curve`、`set_hdr_gamma_curve` 记录的手动曲线。
+    pub(crate) gamma_curves: Mutex<GammaCurves>,
+    /// `get_exposure_timing` 按帧号记录的曝光时序。
+    pub(crate) exposure_timings: Mutex<TimingHistory>,
 }
 
 unsafe impl Send for Context {}
@@ -16,6 +171,9 @@ unsafe impl Sync for Context {}
 
 impl Context {
     pub fn new(sns_ent_name: &str, iq_file_dir: &str) -> Result<Self, io::Error> {
+        sdkprobe::probe_sdk()?;
+        let name = sns_ent_name.to_owned();
+        let dir = iq_file_dir.to_owned();
         let sns_ent_name = CString::new(sns_ent_name).unwrap();
         let iq_file_dir = CString::new(iq_file_dir).unwrap();
         let ptr = unsafe {
@@ -26,10 +184,9 @@ impl Context {
                 Some(default_metas_callback),
             )
         };
-        assert!(!ptr.is_null());
         NonNull::new(ptr).map_or_else(
             || Err(io::Error::last_os_error()),
-            |v| Ok(Self { internal: v }),
+            |v| Ok(Self::from_parts(v, name, dir, true)),
         )
     }
 
@@ -39,16 +196,199 @@ impl Context {
         iq_file: &str,
         mode: WorkingMode,
     ) -> Result<Self, io::Error> {
-        sysctl::pre_init(sns_ent_name, mode, iq_file)
-            .map_err(|x| io::Error::new(io::ErrorKind::Other, format!("{}", x)))?;
+        sysctl::pre_init(sns_ent_name, mode, iq_file)?;
         Self::new(sns_ent_name, iq_file_dir)
     }
+
+    /// 以内存中的 JSON IQ 内容创建上下文，工作模式为 `WorkingMode::Normal`。
+    ///
+    /// 本库绑定的各版本 SDK 都没有从内存加载 IQ 的接口：内容先经解析检查（需启用 `json`
+    /// 特性），再写入私有的临时目录，以 `with_force_iq_file` 指定该文件初始化，初始化返回后删除。
+    /// 内容无法解析时在初始化 SDK 之前返回 `XCAM_RETURN_ERROR_PARAM`，写入临时文件失败时返回
+    /// `XCAM_RETURN_ERROR_FILE`；初始化失败时返回其内部的 `XCamError`（如 `XCamError::SdkMismatch`），
+    /// 其余为 `XCAM_RETURN_ERROR_FAILED`。
+    pub fn init_from_buffer(sns_ent_name: &str, iq_data: &[u8]) -> XCamResult<Context> {
+        let stem = format!("{}_buffer_iq", calib::sensor_from_entity(sns_ent_name));
+        let iq = TempCalib::write(&stem, iq_data)?;
+        let dir = iq
+            .dir()
+            .to_str()
+            .ok_or_else(|| XCamError::from(XCamReturn::XCAM_RETURN_ERROR_FILE))?;
+        Self::with_force_iq_file(sns_ent_name, dir, iq.file_name(), WorkingMode::Normal)
+            .map_err(build_error)
+    }
+
+    fn from_parts(
+        internal: NonNull<ffi::rk_aiq_sys_ctx_t>,
+        sns_ent_name: String,
+        iq_file_dir: String,
+        owned: bool,
+    ) -> Self {
+        let index = NEXT_CONTEXT_INDEX.fetch_add(1, Ordering::Relaxed);
+        let label = if sns_ent_name.is_empty() {
+            format!("ctx{}", index)
+        } else {
+            sns_ent_name.clone()
+        };
+        Self {
+            internal,
+            owned,
+            deinited: AtomicBool::new(false),
+            label: Mutex::new(label),
+            sns_ent_name,
+            iq_file_dir,
+            device: DeviceState::default(),
+            caps: OnceLock::new(),
+            hdr_passthrough: Mutex::new(None),
+            base_iso: AtomicU32::new(DEFAULT_BASE_ISO),
+            aperture: AtomicU32::new(0),
+            ldch_enabled: AtomicBool::new(false),
+            fec_enabled: AtomicBool::new(false),
+            prepared: Mutex::new(None),
+            streaming: AtomicBool::new(false),
+            awb_step: Mutex::new(None),
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            awb_ct_defaults: Mutex::new(None),
+            focus_meter: Mutex::new(FocusMeter::default()),
+            #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+            exp_priority_ranges: Mutex::new(None),
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            ae_deadband_tolerance_in: Mutex::new(None),
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            sensor_gain_max: Mutex::new(None),
+            oneshot_exp: Mutex::new(None),
+            auto_fps: Mutex::new(None),
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            ae_convergence: Mutex::new(None),
+            auto_levels_prior: Mutex::new(None),
+            zoom_calib: Mutex::new(Vec::new()),
+            factory_wb: Mutex::new(None),
+            mwb_apply: Mutex::new(MwbApplyState::default()),
+            ccm_table: Mutex::new(CcmTable::default()),
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            wb_priority: Mutex::new(None),
+            scheduler: Mutex::new(Scheduler::default()),
+            update_interval: Mutex::new(UpdateInterval::default()),
+            stats_avg: Mutex::new(StatsAveraging::default()),
+            gamma_curves: Mutex::new(GammaCurves::default()),
+            exposure_timings: Mutex::new(TimingHistory::default()),
+        }
+    }
+
+    /// 接管由其他代码创建的 SDK 上下文，用于与同样基于 rkaiq 的其他库共用一个上下文。
+    ///
+    /// `owned` 为 `true` 时由返回的 `Context` 负责释放：析构时调用 `rk_aiq_uapi2_sysctl_deinit`；
+    /// 为 `false` 时只借用，析构时不释放，由创建方在 `Context` 析构后自行释放。
+    ///
+    /// 接管的上下文不知道传感器实体名称与 IQ 文件目录：`sns_ent_name` 为空，不检测传感器掉线，
+    /// `reconnect` 返回 `XCamError::Unsupported`。本库在 `new` 时注册的错误与元数据回调也不存在，
+    /// `get_isp_error_flags`、`get_frame_id` 只反映以本库创建的上下文。本库记录的其余状态
+    /// （是否出流、协商的格式、各模块切换前保存的参数等）从初始值开始，不反映此前对该上下文的调用。
+    ///
+    /// # Safety
+    /// * `ptr` 必须是 `rk_aiq_uapi2_sysctl_init` 返回、尚未释放的上下文。
+    /// * `owned` 为 `true` 时，调用方此后不得再释放该上下文，也不得在 `Context` 析构后使用它。
+    /// * `owned` 为 `false` 时，调用方须保证该上下文在返回的 `Context` 析构前一直有效。
+    /// * 同一上下文至多由一个 `owned` 为 `true` 的 `Context` 接管，否则会重复释放。
+    /// * `Context` 可跨线程使用（`Send`、`Sync`）。同一上下文通过其他库与本 `Context` 并发调用时，
+    ///   调用方须保证这些调用在 SDK 中可以并发；`prepare`、`start`、`stop` 不应与任何其他调用并发。
+    pub unsafe fn from_raw_parts(ptr: *mut ffi::rk_aiq_sys_ctx_t, owned: bool) -> XCamResult<Self> {
+        NonNull::new(ptr)
+            .map(|v| Self::from_parts(v, String::new(), String::new(), owned))
+            .ok_or_else(|| XCamError::from(XCamReturn::XCAM_RETURN_ERROR_PARAM))
+    }
+
+    /// 返回 SDK 上下文指针，用于传给同样基于 rkaiq 的其他库。
+    ///
+    /// 指针仅在 `Context` 生存期间有效，不得由调用方释放。通过指针对上下文的修改不会更新
+    /// 本库记录的状态，见 `from_raw_parts`。
+    pub fn as_raw_ptr(&self) -> *const ffi::rk_aiq_sys_ctx_t {
+        self.internal.as_ptr()
+    }
+
+    /// 放弃对 SDK 上下文的所有权并返回其指针，析构时不再释放。
+    ///
+    /// 对所有权的 `Context` 调用后由调用方负责以 `rk_aiq_uapi2_sysctl_deinit` 释放，
+    /// 或以 `from_raw_parts` 重新接管；对借用的 `Context` 调用时与 `as_raw_ptr` 相同。
+    pub fn into_raw(mut self) -> *mut ffi::rk_aiq_sys_ctx_t {
+        self.owned = false;
+        self.internal.as_ptr()
+    }
+
+    /// 是否由本 `Context` 负责释放 SDK 上下文。
+    pub fn owns_raw(&self) -> bool {
+        self.owned
+    }
+
+    /// 停止并释放 SDK 上下文，返回过程中的错误，用于在可控的时机结束上下文并处理错误。
+    ///
+    /// 出流时先以 `stop(false)` 停止；停止失败时仍释放上下文，并返回停止的错误。调用后析构时不再
+    /// 释放；未调用时由析构尽力释放，但无法得知其中的错误。借用的上下文（见 `from_raw_parts`）
+    /// 既不停止也不释放，直接返回 `Ok`。
+    ///
+    /// 已检测到传感器掉线（见 `device_lost`）时不再调用 SDK，直接返回 `XCamError::DeviceLost`：
+    /// SDK 释放时会访问已移除的设备节点，可能阻塞。此时 SDK 上下文占用的内存不会释放。
+    pub fn deinit(self) -> XCamResult<()> {
+        if !self.owned {
+            return Ok(());
+        }
+        if self.device.is_lost() {
+            self.deinited.store(true, Ordering::Release);
+            return Err(XCamError::DeviceLost);
+        }
+        deinit_with(&self, &self.deinited, || unsafe {
+            ffi::rk_aiq_uapi2_sysctl_deinit(self.internal.as_ptr());
+        })
+    }
+
+    /// 设置日志中区分上下文的标签，例如 `cam-left`。
+    ///
+    /// 本库输出到日志的 SDK 调用失败信息形如 `[cam-left] rk_aiq_uapi_setMWBGain failed: ...`，
+    /// 见 `logging` 模块。
+    /// 默认为传感器实体名称；以 `from_raw_parts` 接管的上下文没有实体名称，默认为按创建顺序编号的
+    /// `ctx0`、`ctx1` 等。
+    pub fn set_label(&self, label: impl Into<String>) {
+        *self.label.lock().unwrap() = label.into();
+    }
+
+    /// 日志中区分上下文的标签，见 `set_label`。
+    pub fn label(&self) -> String {
+        self.label.lock().unwrap().clone()
+    }
+}
+
+/// 按停止、释放的顺序结束 `ctx`，`deinited` 已置位时不做任何操作，见 `Context::deinit`。
+pub(crate) fn deinit_with<C, F>(ctx: &C, deinited: &AtomicBool, deinit: F) -> XCamResult<()>
+where
+    C: SystemControl + ?Sized,
+    F: FnOnce(),
+{
+    if deinited.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+    let stopped = if ctx.is_streaming() {
+        ctx.stop(false)
+    } else {
+        Ok(())
+    };
+    deinit();
+    stopped
+}
+
+/// 析构时尽力释放，`deinited` 已置位（已调用 `Context::deinit`）时跳过。
+fn deinit_on_drop<F: FnOnce()>(deinited: &AtomicBool, deinit: F) {
+    if !deinited.swap(true, Ordering::AcqRel) {
+        deinit();
+    }
 }
 
 impl Drop for Context {
+    /// 释放 SDK 上下文；已检测到传感器掉线时跳过，见 `Context::deinit`。
     fn drop(&mut self) {
-        unsafe {
-            ffi::rk_aiq_uapi2_sysctl_deinit(self.internal.as_ptr());
+        if self.owned && !self.device.is_lost() {
+            deinit_on_drop(&self.deinited, || unsafe {
+                ffi::rk_aiq_uapi2_sysctl_deinit(self.internal.as_ptr());
+            });
         }
     }
 }
@@ -56,6 +396,13 @@ impl Drop for Context {
 pub struct ContextBuilder<'a> {
     sns_ent_name: Option<Cow<'a, str>>,
     iq_file_dir: Option<Cow<'a, str>>,
+    media_path: Option<String>,
+    calib: Option<CalibFile>,
+    allow_default_calib: bool,
+    resolution: Option<Resolution>,
+    frame_rate: Option<f32>,
+    working_mode: WorkingMode,
+    cancel: Option<CancelToken>,
 }
 
 impl<'a> ContextBuilder<'a> {
@@ -63,6 +410,13 @@ impl<'a> ContextBuilder<'a> {
         Self {
             sns_ent_name: None,
             iq_file_dir: None,
+            media_path: None,
+            calib: None,
+            allow_default_calib: false,
+            resolution: None,
+            frame_rate: None,
+            working_mode: WorkingMode::Normal,
+            cancel: None,
         }
     }
 
@@ -75,6 +429,209 @@ impl<'a> ContextBuilder<'a> {
         self.iq_file_dir = Some(val.into());
         self
     }
+
+    /// 使用指定媒体节点上的传感器，覆盖已设置的传感器实体名称。
+    ///
+    /// SDK 按传感器实体名称查找其所在的 ISP 节点，见 `media` 模块。`build` 时检查传感器确实位于
+    /// 节点 `val.path` 上，不在该节点上时返回 `io::ErrorKind::InvalidInput`，节点无法读取时返回
+    /// 读取的错误，避免拓扑变化后在其他 ISP 上初始化。
+    pub fn media_node(mut self, val: MediaNode) -> Self {
+        self.sns_ent_name = Some(Cow::Owned(val.sensor));
+        self.media_path = Some(val.path);
+        self
+    }
+
+    /// 指定预期使用的 IQ 文件，`build` 时检查其是否与传感器匹配。
+    ///
+    /// 未设置 `iq_file_dir` 时使用该文件所在的目录。
+    pub fn calib_file(mut self, val: CalibFile) -> Self {
+        self.calib = Some(val);
+        self
+    }
+
+    /// IQ 文件目录中没有与传感器匹配的 IQ 文件时仍然初始化 SDK，用于新传感器的调试。
+    ///
+    /// 默认为 `false`，此时 `build` 在初始化 SDK 之前检查 IQ 文件是否存在。
+    pub fn allow_default_calib(mut self, val: bool) -> Self {
+        self.allow_default_calib = val;
+        self
+    }
+
+    /// 出流分辨率，未设置时 `init` 使用传感器的默认分辨率。
+    pub fn resolution(mut self, val: Resolution) -> Self {
+        self.resolution = Some(val);
+        self
+    }
+
+    /// 出流帧率，未设置时 `init` 不修改帧率，传感器以默认模式的帧率出流。
+    pub fn frame_rate(mut self, val: f32) -> Self {
+        self.frame_rate = Some(val);
+        self
+    }
+
+    /// `build_with_timeout` 中 `prepare` 使用的工作模式，默认为 `WorkingMode::Normal`。
+    pub fn working_mode(mut self, val: WorkingMode) -> Self {
+        self.working_mode = val;
+        self
+    }
+
+    /// 用于取消 `build_with_timeout` 的句柄。
+    pub fn cancel_token(mut self, val: CancelToken) -> Self {
+        self.cancel = Some(val);
+        self
+    }
+
+    /// 创建上下文。
+    ///
+    /// 缺少传感器实体名称或 IQ 文件目录，或 IQ 文件与传感器不匹配时，
+    /// 在初始化 SDK 之前返回 `io::ErrorKind::InvalidInput`。
+    ///
+    /// IQ 文件目录中没有 SDK 将要加载的 IQ 文件（见 `calib::locate_calib_file`）时返回
+    /// `io::ErrorKind::NotFound`，其内部错误为 `CalibError::Missing`，可通过 `get_ref` 取出；
+    /// 设置 `allow_default_calib` 后跳过该检查。
+    pub fn build(self) -> Result<Context, io::Error> {
+        let sns_ent_name = self.sns_ent_name.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "missing sensor entity name")
+        })?;
+        let calib_dir = self
+            .calib
+            .as_ref()
+            .and_then(|c| c.path().parent())
+            .and_then(|p| p.to_str())
+            .map(|p| Cow::Owned(p.to_owned()));
+        let iq_file_dir = self.iq_file_dir.or(calib_dir).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "missing IQ file directory")
+        })?;
+        if let Some(path) = &self.media_path {
+            check_media_node(&sns_ent_name, path)?;
+        }
+        if let Some(calib) = &self.calib {
+            calib.check_sensor(&sns_ent_name)?;
+        }
+        if !self.allow_default_calib {
+            calib::locate_calib_file(&*iq_file_dir, &sns_ent_name)?;
+        }
+        Context::new(&sns_ent_name, &iq_file_dir)
+    }
+
+    /// 在工作线程中创建上下文，超过 `d` 仍未完成时返回 `XCamError::Timeout`。
+    ///
+    /// 工作线程依次执行 `build`，以及设置了 `resolution` 时以该分辨率与 `working_mode` 执行
+    /// `prepare`；`build` 的错误按 `init_with_overrides` 的规则转换。两步开始前各检查一次
+    /// `cancel_token`，已取消时返回 `XCamError::Cancelled`，已创建的上下文随之释放。
+    ///
+    /// 注意：超时与取消都不会中止正在进行的 SDK 调用。超时后工作线程继续运行，原调用返回时
+    /// 创建的上下文被立即释放（`rk_aiq_uapi2_sysctl_deinit`）；在此之前同一传感器仍被占用，
+    /// 立即重试可能失败。
+    pub fn build_with_timeout(self, d: Duration) -> XCamResult<Context> {
+        let resolution = self.resolution;
+        let mode = self.working_mode;
+        let cancel = self.cancel.clone();
+        let builder = self.into_owned();
+        timeout::init_with_timeout(
+            d,
+            cancel,
+            move || builder.build().map_err(build_error),
+            move |ctx: &Context| match resolution {
+                Some(r) => ctx.prepare(r.width, r.height, mode).map(|_| ()),
+                None => Ok(()),
+            },
+        )
+    }
+
+    /// 复制借用的字符串，使构造器可以移入工作线程。
+    fn into_owned(self) -> ContextBuilder<'static> {
+        ContextBuilder {
+            sns_ent_name: self.sns_ent_name.map(|v| Cow::Owned(v.into_owned())),
+            iq_file_dir: self.iq_file_dir.map(|v| Cow::Owned(v.into_owned())),
+            media_path: self.media_path,
+            calib: self.calib,
+            allow_default_calib: self.allow_default_calib,
+            resolution: self.resolution,
+            frame_rate: self.frame_rate,
+            working_mode: self.working_mode,
+            cancel: self.cancel,
+        }
+    }
+
+    /// 创建上下文并以 `overrides` 覆盖 IQ 默认参数后出流。
+    ///
+    /// 依次执行 `build`、`prepare`、应用 `overrides`、`start`，见 `CameraSettings::apply_and_start`。
+    /// 出流的第一帧即反映覆盖后的参数，不会先输出几帧 IQ 默认参数的画面。
+    ///
+    /// `build` 失败时返回其内部的 `XCamError`（如 `XCamError::SdkMismatch`），参数无效时返回
+    /// `XCAM_RETURN_ERROR_PARAM`，缺少 IQ 文件时返回 `XCAM_RETURN_ERROR_FILE`，其余为
+    /// `XCAM_RETURN_ERROR_FAILED`；
+    /// 之后任一步骤失败时上下文被释放并返回该错误。
+    pub fn init_with_overrides(
+        self,
+        width: u32,
+        height: u32,
+        mode: WorkingMode,
+        overrides: &CameraSettings,
+    ) -> XCamResult<Context> {
+        let ctx = self.build().map_err(build_error)?;
+        overrides.apply_and_start(&ctx, width, height, mode)?;
+        Ok(ctx)
+    }
+
+    /// 创建上下文并以设置的分辨率、帧率及 `overrides` 出流，见 `init_with_overrides`。
+    ///
+    /// 未设置分辨率时使用 `sysctl::default_mode_for` 查询的传感器默认模式，即驱动所列的
+    /// 第一个线性输出格式。设置了帧率时在应用 `overrides` 前以手动模式设置，取整到整数帧率；
+    /// 帧率不是大于 0 的有限值时返回 `XCAM_RETURN_ERROR_PARAM`。
+    pub fn init(self, mode: WorkingMode, overrides: &CameraSettings) -> XCamResult<Context> {
+        let fps = match self.frame_rate {
+            Some(fps) if !(fps.is_finite() && fps > 0.0) => {
+                return Err(XCamError::from(XCamReturn::XCAM_RETURN_ERROR_PARAM));
+            }
+            fps => fps,
+        };
+        let resolution = self.resolution;
+        let ctx = self.build().map_err(build_error)?;
+        let resolution = match resolution {
+            Some(r) => r,
+            None => sysctl::default_mode_for(&ctx.sns_ent_name)?.0,
+        };
+        ctx.prepare(resolution.width, resolution.height, mode)?;
+        if let Some(fps) = fps {
+            ctx.set_frame_rate(FrameRateInfo {
+                mode: ffi::opMode_t::OP_MANUAL,
+                fps: (fps.round() as u32).max(1),
+            })?;
+        }
+        overrides.apply(&ctx)?;
+        ctx.start()?;
+        Ok(ctx)
+    }
+}
+
+/// 将 `ContextBuilder::build` 的错误转换为 `XCamError`。
+fn build_error(e: io::Error) -> XCamError {
+    let kind = e.kind();
+    match e.into_inner().map(|inner| inner.downcast::<XCamError>()) {
+        Some(Ok(inner)) => *inner,
+        _ if kind == io::ErrorKind::InvalidInput => {
+            XCamError::from(XCamReturn::XCAM_RETURN_ERROR_PARAM)
+        }
+        _ if kind == io::ErrorKind::NotFound => XCamError::from(XCamReturn::XCAM_RETURN_ERROR_FILE),
+        _ => XCamError::from(XCamReturn::XCAM_RETURN_ERROR_FAILED),
+    }
+}
+
+/// 检查传感器 `sns_ent_name` 是否位于媒体节点 `path` 上。
+fn check_media_node(sns_ent_name: &str, path: &str) -> Result<(), io::Error> {
+    if media::read_media_node(path)?
+        .iter()
+        .any(|n| n.sensor == sns_ent_name)
+    {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("sensor `{}` is not on media node {}", sns_ent_name, path),
+        ))
+    }
 }
 
 impl<'a> Default for ContextBuilder<'a> {
@@ -83,12 +640,223 @@ impl<'a> Default for ContextBuilder<'a> {
     }
 }
 
-unsafe extern "C" fn default_error_callback(_err_msg: *mut ffi::rk_aiq_err_msg_t) -> XCamReturn {
-    // println!("err_msg={:p}", err_msg);
-    XCamReturn::XCAM_RETURN_NO_ERROR
+unsafe extern "C" fn default_error_callback(err_msg: *mut ffi::rk_aiq_err_msg_t) -> XCamReturn {
+    callback_guard("error", XCamReturn::XCAM_RETURN_NO_ERROR, || {
+        if let Some(msg) = err_msg.as_ref() {
+            let flags = IspErrorFlags::from_err_code(msg.err_code);
+            ISP_ERROR_FLAGS.fetch_or(flags.bits(), Ordering::AcqRel);
+        }
+        XCamReturn::XCAM_RETURN_NO_ERROR
+    })
 }
 
-unsafe extern "C" fn default_metas_callback(_metas: *mut ffi::rk_aiq_metas_t) -> XCamReturn {
-    // println!("metas={:p}", metas);
-    XCamReturn::XCAM_RETURN_NO_ERROR
+unsafe extern "C" fn default_metas_callback(metas: *mut ffi::rk_aiq_metas_t) -> XCamReturn {
+    callback_guard("metas", XCamReturn::XCAM_RETURN_NO_ERROR, || {
+        if let Some(metas) = metas.as_ref() {
+            LAST_FRAME_ID.store(metas.frame_id as i64, Ordering::Release);
+        }
+        XCamReturn::XCAM_RETURN_NO_ERROR
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calib::CalibError;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_init_from_buffer_rejects_bad_iq() {
+        // 内容检查先于 SDK 初始化，不调用 SDK 也不留下临时目录。
+        let param = XCamError::from(XCamReturn::XCAM_RETURN_ERROR_PARAM);
+        assert_eq!(
+            Context::init_from_buffer("m00_b_ov5695 4-0036", b"\xff{}").err(),
+            Some(param)
+        );
+        #[cfg(feature = "json")]
+        assert_eq!(
+            Context::init_from_buffer("m00_b_ov5695 4-0036", b"{").err(),
+            Some(param)
+        );
+    }
+
+    #[test]
+    fn test_raw_parts_round_trip() {
+        // 悬空指针不能传给 SDK，析构或重建时若调用了 SDK 测试会崩溃。
+        let ptr = NonNull::<ffi::rk_aiq_sys_ctx_t>::dangling().as_ptr();
+        let mut borrowed = unsafe { Context::from_raw_parts(ptr, false) }.unwrap();
+        assert!(!borrowed.owns_raw());
+        assert_eq!(borrowed.as_raw_ptr(), ptr as *const _);
+        assert_eq!(borrowed.reconnect(), Err(XCamError::Unsupported));
+        drop(borrowed);
+
+        let owned = unsafe { Context::from_raw_parts(ptr, true) }.unwrap();
+        assert!(owned.owns_raw());
+        assert_eq!(owned.into_raw(), ptr);
+
+        assert_eq!(
+            unsafe { Context::from_raw_parts(std::ptr::null_mut(), false) }.err(),
+            Some(XCamError::from(XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+    }
+
+    #[test]
+    fn test_builder_rejects_mismatched_calib() {
+        let calib = CalibFile::from_json(
+            std::path::Path::new("/etc/iqfiles/imx415_CMK-OT2022-PX1_IR0147-50IRC-8M-F20.json"),
+            "{}",
+        )
+        .unwrap();
+        let err = ContextBuilder::new()
+            .sns_ent_name("m00_b_ov5695 4-0036-1")
+            .calib_file(calib)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("imx415"), "{}", err);
+        assert_eq!(
+            ContextBuilder::new().build().err().unwrap().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_build_error() {
+        let lost = io::Error::new(io::ErrorKind::NotFound, XCamError::DeviceLost);
+        assert_eq!(build_error(lost), XCamError::DeviceLost);
+        let missing = CalibError::Missing {
+            expected_names: vec!["imx415".into()],
+            searched_dir: "/etc/iqfiles".into(),
+        };
+        assert_eq!(
+            build_error(io::Error::from(missing)),
+            XCamError::from(XCamReturn::XCAM_RETURN_ERROR_FILE)
+        );
+        assert_eq!(
+            build_error(io::Error::new(io::ErrorKind::InvalidInput, "missing")),
+            XCamError::from(XCamReturn::XCAM_RETURN_ERROR_PARAM)
+        );
+        assert_eq!(
+            build_error(io::Error::from(io::ErrorKind::Other)),
+            XCamError::from(XCamReturn::XCAM_RETURN_ERROR_FAILED)
+        );
+    }
+
+    #[test]
+    fn test_builder_rejects_missing_calib() {
+        let dir = std::env::temp_dir().join(format!("rkaiq-builder-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = format!(
+            "imx415_CMK-OT2022-PX1_IR0147-50IRC-8M-F20.{}",
+            calib::CALIB_EXTENSIONS[0]
+        );
+        std::fs::write(dir.join(&file), "{}").unwrap();
+        let err = ContextBuilder::new()
+            .sns_ent_name("m00_b_ov5695 4-0036-1")
+            .iq_file_dir(dir.to_str().unwrap())
+            .build()
+            .err()
+            .unwrap();
+        let present = calib::locate_calib_file(&dir, "m01_f_imx415 7-001a");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let inner = err.get_ref().and_then(|e| e.downcast_ref::<CalibError>());
+        let Some(CalibError::Missing {
+            expected_names,
+            searched_dir,
+        }) = inner
+        else {
+            panic!("expected CalibError::Missing, got {:?}", err);
+        };
+        assert_eq!(
+            *expected_names,
+            calib::expected_calib_names("m00_b_ov5695 4-0036-1")
+        );
+        assert_eq!(*searched_dir, dir);
+        assert_eq!(present.unwrap(), dir.join(file));
+    }
+
+    #[test]
+    fn test_read_in_frame_retries_on_frame_change() {
+        let frame = Cell::new(10);
+        let reads = Cell::new(0);
+        let r = read_in_frame(
+            || Some(frame.get()),
+            || {
+                reads.set(reads.get() + 1);
+                if reads.get() == 1 {
+                    frame.set(11);
+                }
+                Ok(reads.get())
+            },
+        );
+        assert_eq!(r, Ok((2, Some(11))));
+    }
+
+    #[test]
+    fn test_error_callback_accumulates_flags() {
+        use ffi::XCamReturn::*;
+        clear_isp_error_flags();
+        for code in [XCAM_RETURN_ERROR_SENSOR, XCAM_RETURN_ERROR_ISP] {
+            let mut msg = ffi::rk_aiq_err_msg_t {
+                err_code: code as i32,
+            };
+            unsafe {
+                default_error_callback(&mut msg);
+            }
+        }
+        assert_eq!(
+            isp_error_flags(),
+            IspErrorFlags::SENSOR | IspErrorFlags::ISP
+        );
+        clear_isp_error_flags();
+        assert_eq!(isp_error_flags(), IspErrorFlags::empty());
+    }
+
+    #[test]
+    fn test_deinit_skips_drop_release() {
+        use crate::mock::MockContext;
+
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().streaming = true;
+        let deinited = AtomicBool::new(false);
+        let released = Cell::new(0);
+        let release = || released.set(released.get() + 1);
+        assert_eq!(deinit_with(&ctx, &deinited, release), Ok(()));
+        assert_eq!(ctx.calls(), ["stop"]);
+        assert_eq!(released.get(), 1);
+        // 显式释放后析构路径不再调用 SDK。
+        deinit_on_drop(&deinited, release);
+        assert_eq!(deinit_with(&ctx, &deinited, release), Ok(()));
+        assert_eq!(released.get(), 1);
+        assert_eq!(ctx.calls(), ["stop"]);
+
+        // 未显式释放时由析构释放；停止失败时仍释放并返回错误。
+        let deinited = AtomicBool::new(false);
+        deinit_on_drop(&deinited, release);
+        assert_eq!(released.get(), 2);
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().streaming = true;
+        ctx.fail("stop");
+        assert_eq!(
+            deinit_with(&ctx, &AtomicBool::new(false), release),
+            Err(XCamError::from(XCamReturn::XCAM_RETURN_ERROR_FAILED))
+        );
+        assert_eq!(released.get(), 3);
+    }
+
+    #[test]
+    fn test_read_in_frame_gives_up() {
+        let frame = Cell::new(0);
+        let r = read_in_frame(
+            || {
+                frame.set(frame.get() + 1);
+                Some(frame.get())
+            },
+            || Ok(()),
+        );
+        assert_eq!(r, Ok(((), Some(6))));
+    }
 }
@@ -0,0 +1,134 @@
+//! 转换审计
+//!
+//! SDK 枚举与返回值转换为本库类型时，无法对应的取值落入兜底分支，转换为 `Invalid`、
+//! `XCAM_RETURN_ERROR_UNKNOWN` 等占位值。固件更新后 SDK 新增或重新编号的取值会因此被悄悄吞掉。
+//! 各兜底分支调用 `unmapped` 记录一次：
+//!
+//! * 总是计入 `unmapped_conversions`，集成测试可在运行一段时间后检查其为 0；
+//! * 启用 `strict` 特性时另外以 `warn` 级别输出日志（见 `logging` 模块），指出 SDK 类型与取值。
+//!
+//! 记录的转换：
+//!
+//! | 转换 | 占位值 |
+//! |---|---|
+//! | `From<i32> for XCamError` | `XCAM_RETURN_ERROR_UNKNOWN` |
+//! | `From<RKAIQ_AF_SEARCH_STATE> for AfSearchState` | `AfSearchState::Invalid`（`RK_AIQ_AF_SEARCH_INVAL` 除外） |
+//! | `From<opMode_t> for WbOpMode` | 未列出的取值（如 `OP_REG_MANUAL`）转换为 `WbOpMode::Max`，v1_0 为 `WbOpMode::Invalid` |
+//! | CCM、3D LUT、锐化的工作模式 | `OpMode::Invalid` |
+//!
+//! 按设计合并多个取值的分支（例如 Gamma 的各自动模式均视为 `OpMode::Auto`）不在此列。
+use super::logging::log_warn;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static UNMAPPED: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(test)]
+thread_local! {
+    /// 本线程记录的 `(SDK 类型, 警告)`，未启用 `strict` 时警告为 `None`。
+    static RECORDED: std::cell::RefCell<Vec<(&'static str, Option<String>)>> =
+        std::cell::RefCell::new(Vec::new());
+}
+
+/// 记录一次落入兜底分支的转换，`kind` 为 SDK 类型名称。
+pub(crate) fn unmapped<T: Debug>(kind: &'static str, val: T) {
+    UNMAPPED.fetch_add(1, Ordering::Relaxed);
+    let warning = if cfg!(feature = "strict") {
+        let msg = format!("unmapped {} value {:?}", kind, val);
+        log_warn!("{}", msg);
+        Some(msg)
+    } else {
+        None
+    };
+    #[cfg(test)]
+    RECORDED.with(|r| r.borrow_mut().push((kind, warning)));
+    #[cfg(not(test))]
+    let _ = warning;
+}
+
+/// 进程启动以来落入兜底分支的转换次数，见模块说明。
+pub fn unmapped_conversions() -> u64 {
+    UNMAPPED.load(Ordering::Relaxed)
+}
+
+/// 取出并清空本线程记录的转换。
+#[cfg(test)]
+pub(crate) fn take_recorded() -> Vec<(&'static str, Option<String>)> {
+    RECORDED.with(|r| std::mem::take(&mut *r.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::af::AfSearchState;
+    use crate::awb::WbOpMode;
+    use crate::error::XCamError;
+    use crate::ffi;
+
+    /// 已知取值都有对应，不落入兜底分支。
+    #[test]
+    fn test_known_values_mapped() {
+        take_recorded();
+        for code in [0, 1, -1, -2, -3, -4, -5, -6, -7, -8, -9, -10, -20, -21] {
+            let _ = XCamError::from(code);
+        }
+        {
+            use ffi::RKAIQ_AF_SEARCH_STATE::*;
+            for v in [
+                RK_AIQ_AF_SEARCH_INVAL,
+                RK_AIQ_AF_SEARCH_RUNNING,
+                RK_AIQ_AF_SEARCH_END,
+            ] {
+                let _ = AfSearchState::from(v);
+            }
+        }
+        {
+            use ffi::opMode_t::*;
+            for v in [OP_AUTO, OP_MANUAL, OP_SEMI_AUTO, OP_INVAL] {
+                let _ = WbOpMode::from(v);
+            }
+        }
+        assert_eq!(take_recorded(), []);
+    }
+
+    /// 超出范围的返回值落入兜底分支并被记录，`strict` 下附带警告。
+    #[test]
+    fn test_unmapped_values_recorded() {
+        take_recorded();
+        let before = unmapped_conversions();
+        for code in [2, -11, -22, i32::MIN] {
+            assert_eq!(
+                XCamError::from(code),
+                XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_UNKNOWN)
+            );
+        }
+        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+        assert_eq!(WbOpMode::from(ffi::opMode_t::OP_REG_MANUAL), WbOpMode::Max);
+        let recorded = take_recorded();
+        let expected = if cfg!(any(feature = "v3_0", feature = "v4_0", feature = "v5_0")) {
+            5
+        } else {
+            4
+        };
+        assert_eq!(recorded.len(), expected);
+        assert!(unmapped_conversions() >= before + expected as u64);
+        assert!(recorded[..4].iter().all(|(kind, _)| *kind == "XCamReturn"));
+        for (_, warning) in &recorded {
+            assert_eq!(warning.is_some(), cfg!(feature = "strict"));
+        }
+    }
+
+    #[cfg(feature = "strict")]
+    #[test]
+    fn test_strict_warning() {
+        take_recorded();
+        let _ = XCamError::from(-22);
+        assert_eq!(
+            take_recorded(),
+            [(
+                "XCamReturn",
+                Some("unmapped XCamReturn value -22".to_owned())
+            )]
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! uAPI 覆盖清单
+//!
+//! 记录各模块封装了哪些 SDK uAPI 函数，以及这些封装随哪些 SDK 版本编译，供生成覆盖报告、
+//! 与厂商发布的 uAPI 列表比对，或在 `capability` 之外判断某个接口在给定版本上是否可用。
+//! 仅在启用 `coverage` 特性时编译。
+//!
+//! 清单与源码的一致性由测试保证：源码中（测试以外）调用的每个 `ffi::rk_aiq_*` 函数都必须
+//! 登记在所在模块下，登记的每个函数都必须仍被该模块调用，且当前版本登记的函数必须存在于
+//! 绑定中。新增或删除封装时同步修改 `REGISTRY`。
+//!
+//! 版本列表为空的条目只在 `fullv` 下编译，默认的特性组合都不包含。
+use super::dump::sdk_version;
+#[cfg(test)]
+use super::ffi;
+
+/// 一个已封装的 uAPI 函数。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CoverageEntry {
+    /// 封装所在的模块，例如 `"ae"`。
+    pub module: &'static str,
+    /// SDK 中的函数名。
+    pub symbol: &'static str,
+    /// 封装随之编译的 SDK 版本特性，例如 `["v4_0", "v5_0"]`。
+    pub versions: &'static [&'static str],
+}
+
+impl CoverageEntry {
+    /// 封装是否随当前编译的 SDK 版本可用。
+    pub fn is_available(&self) -> bool {
+        self.versions.contains(&sdk_version())
+    }
+}
+
+/// 生成 `REGISTRY`，测试时另外生成当前版本登记函数的地址列表。
+macro_rules! registry {
+    ($($module:literal => [$($symbol:ident $(: $($version:tt),+)?;)*])*) => {
+        const REGISTRY: &[CoverageEntry] = &[$($(
+            CoverageEntry {
+                module: $module,
+                symbol: stringify!($symbol),
+                versions: &[$($($version),+)?],
+            },
+        )*)*];
+
+        /// 当前版本登记的函数，引用绑定中不存在的函数时编译失败。
+        #[cfg(test)]
+        fn bound_symbols() -> Vec<(&'static str, *const ())> {
+            let mut symbols = Vec::new();
+            $($(
+                #[cfg(any($($(feature = $version),+)?))]
+                symbols.push((stringify!($symbol), ffi::$symbol as *const ()));
+            )*)*
+            symbols
+        }
+    };
+}
+
+registry! {
+    "acm" => [
+        rk_aiq_uapi_getBrightness: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setBrightness: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getContrast: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setContrast: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getSaturation: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setSaturation: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getHue: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setHue: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "ae" => [
+        rk_aiq_uapi_getAeMode: "v1_0";
+        rk_aiq_uapi_getExpMode: "v1_0", "v2_0", "v3_0";
+        rk_aiq_uapi2_getExpMode: "v4_0", "v5_0";
+        rk_aiq_uapi_setExpMode: "v1_0", "v2_0", "v3_0";
+        rk_aiq_uapi2_setExpMode: "v4_0", "v5_0";
+        rk_aiq_uapi_getExpGainRange: "v1_0", "v2_0", "v3_0";
+        rk_aiq_uapi2_getExpGainRange: "v4_0", "v5_0";
+        rk_aiq_uapi_setExpGainRange: "v1_0", "v2_0", "v3_0";
+        rk_aiq_uapi2_setExpGainRange: "v4_0", "v5_0";
+        rk_aiq_uapi_getExpTimeRange: "v1_0", "v2_0", "v3_0";
+        rk_aiq_uapi2_getExpTimeRange: "v4_0", "v5_0";
+        rk_aiq_uapi_setExpTimeRange: "v1_0", "v2_0", "v3_0";
+        rk_aiq_uapi2_setExpTimeRange: "v4_0", "v5_0";
+        rk_aiq_uapi_setManualExp: "v1_0", "v2_0", "v3_0";
+        rk_aiq_user_api2_ae_getExpSwAttr: "v4_0", "v5_0";
+        rk_aiq_user_api2_ae_setExpSwAttr: "v4_0", "v5_0";
+        rk_aiq_user_api2_ae_getExpWinAttr: "v4_0", "v5_0";
+        rk_aiq_user_api2_ae_setExpWinAttr: "v4_0", "v5_0";
+        rk_aiq_uapi_setBLCMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setBLCStrength: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setHLCMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setHLCStrength: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getDarkAreaBoostStrth: "v2_0";
+        rk_aiq_uapi2_getDarkAreaBoostStrth: "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setDarkAreaBoostStrth: "v2_0";
+        rk_aiq_uapi2_setDarkAreaBoostStrth: "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getAntiFlickerMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setAntiFlickerMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getExpPwrLineFreqMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setExpPwrLineFreqMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_getSensorDiscrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api_ae_queryExpResInfo: "v1_0", "v2_0", "v3_0";
+        rk_aiq_user_api2_ae_queryExpResInfo: "v4_0", "v5_0";
+        rk_aiq_user_api2_ae_getLinExpAttr: "v4_0", "v5_0";
+        rk_aiq_user_api2_ae_setLinExpAttr: "v4_0", "v5_0";
+    ]
+    "af" => [
+        rk_aiq_uapi2_getFocusMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_setFocusMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_getFocusPosition: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_setFocusPosition: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_af_GetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_af_SetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_af_GetSearchResult: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_setOpZoomPosition: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_getOpZoomPosition: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "asd" => [
+        rk_aiq_user_api_asd_GetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "awb" => [
+        rk_aiq_uapi_getWBMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setWBMode: "v1_0", "v2_0", "v3_0";
+        rk_aiq_uapi2_setWBMode: "v4_0", "v5_0";
+        rk_aiq_uapi_lockAWB: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_unlockAWB: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getMWBScene: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setMWBScene: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getMWBGain: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getMWBCT: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setMWBCT: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_awbV30_GetAllAttrib: "v4_0", "v5_0";
+        rk_aiq_user_api2_awb_QueryWBInfo: "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_awbV30_SetAllAttrib: "v4_0", "v5_0";
+        rk_aiq_user_api2_awb_GetWbGainOffsetAttrib: "v4_0", "v5_0";
+        rk_aiq_user_api2_awb_SetWbGainOffsetAttrib: "v4_0", "v5_0";
+        rk_aiq_uapi_setMWBGain: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "blc" => [
+        rk_aiq_user_api2_ablc_GetAttrib: "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_ablc_SetAttrib: "v3_0", "v4_0", "v5_0";
+    ]
+    "ccm" => [
+        rk_aiq_user_api_accm_QueryCcmInfo: "v1_0", "v2_0", "v3_0";
+        rk_aiq_user_api2_accm_QueryCcmInfo: "v4_0", "v5_0";
+        rk_aiq_user_api_accm_GetAttrib: "v1_0", "v2_0", "v3_0";
+        rk_aiq_user_api2_accm_GetAttrib: "v4_0", "v5_0";
+        rk_aiq_user_api_accm_SetAttrib: "v1_0", "v2_0", "v3_0";
+        rk_aiq_user_api2_accm_SetAttrib: "v4_0", "v5_0";
+        rk_aiq_user_api2_a3dlut_GetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_a3dlut_SetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "context" => [
+        rk_aiq_uapi2_sysctl_init: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_deinit: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "csm" => [
+        rk_aiq_user_api2_acsm_GetAttrib: "v5_0";
+        rk_aiq_user_api2_acsm_SetAttrib: "v5_0";
+    ]
+    "defog" => [
+        rk_aiq_uapi2_enableDhz: "v2_0";
+        rk_aiq_uapi2_setDehazeEnable: "v3_0";
+        rk_aiq_uapi2_disableDhz: "v2_0";
+        rk_aiq_uapi2_getDhzMode: "v2_0";
+        rk_aiq_uapi2_setDhzMode: "v2_0";
+        rk_aiq_user_api2_adehaze_getSwAttrib: "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_adehaze_setSwAttrib: "v3_0", "v4_0", "v5_0";
+    ]
+    "dm" => [
+        rk_aiq_user_api2_adebayer_GetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_adebayer_SetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "events" => [
+        rk_aiq_uapi2_sysctl_getWorkingMode: "v4_0", "v5_0";
+    ]
+    "fec" => [
+        rk_aiq_uapi2_setFecEn: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "gamma" => [
+        rk_aiq_user_api_agamma_GetAttrib: "v2_0";
+        rk_aiq_user_api2_agamma_GetAttrib: "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api_agamma_SetAttrib: "v2_0";
+        rk_aiq_user_api2_agamma_SetAttrib: "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_adegamma_SetAttrib: "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_adegamma_GetAttrib: "v3_0", "v4_0", "v5_0";
+    ]
+    "hdr" => [
+        rk_aiq_uapi_getHDRMode: "v2_0";
+        rk_aiq_uapi_setHDRMode: "v2_0";
+        rk_aiq_uapi_getMHDRStrth: "v2_0";
+        rk_aiq_uapi2_getMHDRStrth: "v3_0";
+        rk_aiq_uapi_setMHDRStrth: "v2_0";
+        rk_aiq_uapi2_setMHDRStrth: "v3_0";
+        rk_aiq_user_api2_adrc_GetAttrib: "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_adrc_SetAttrib: "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_getSensorDiscrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_ae_getHdrExpAttr: "v4_0", "v5_0";
+        rk_aiq_user_api2_ae_setHdrExpAttr: "v4_0", "v5_0";
+    ]
+    "ldch" => [
+        rk_aiq_uapi2_setLdchEn: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "lsc" => [
+        rk_aiq_user_api_alsc_QueryLscInfo: "v1_0", "v2_0", "v3_0";
+        rk_aiq_user_api2_alsc_QueryLscInfo: "v4_0", "v5_0";
+    ]
+    "misc" => [
+        rk_aiq_uapi_getGrayMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setGrayMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getFrameRate: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setFrameRate: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getMirrorFlip: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setMirroFlip: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "nr" => [
+        rk_aiq_uapi_getNRMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setNRMode: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_getANRStrth: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setANRStrth: "v1_0", "v2_0", "v3_0";
+        rk_aiq_uapi2_setANRStrth: "v4_0", "v5_0";
+        rk_aiq_uapi_getMSpaNRStrth: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setMSpaNRStrth: "v1_0", "v2_0", "v3_0";
+        rk_aiq_uapi2_setMSpaNRStrth: "v4_0", "v5_0";
+        rk_aiq_uapi_getMTNRStrth: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_setMTNRStrth: "v1_0", "v2_0", "v3_0";
+        rk_aiq_uapi2_setMTNRStrth: "v4_0", "v5_0";
+        rk_aiq_user_api2_abayernrV2_GetInfo: "v4_0", "v5_0";
+        rk_aiq_user_api2_abayernrV2_GetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_abayernrV2_SetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_acnrV1_GetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_acnrV1_SetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_acnrV1_GetStrength: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_acnrV1_SetStrength: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_aynrV2_GetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_aynrV2_SetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_aynrV2_GetStrength: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_aynrV2_SetStrength: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "sharpen" => [
+        rk_aiq_uapi2_getSharpness: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_setSharpness: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_asharpV3_GetInfo: "v4_0", "v5_0";
+        rk_aiq_user_api2_asharpV3_GetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api2_asharpV3_SetAttrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+    ]
+    "sysctl" => [
+        rk_aiq_uapi2_sysctl_start: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_stop: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_user_api_ae_queryExpResInfo: "v1_0", "v2_0", "v3_0";
+        rk_aiq_user_api2_ae_queryExpResInfo: "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_prepare: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_getSensorDiscrib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_setModuleCtl: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_getModuleCtl: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_sysctl_regLib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_sysctl_unRegLib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_enableAxlib: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_sysctl_getAxlibStatus: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_getEnabledAxlibCtx: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_getCpsLtInfo: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_queryCpsLtCap: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_setCpsLtCfg: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_updateIq: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_getCrop: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_sysctl_setCrop: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_get3AStatsBlk: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_release3AStatsRef: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_getBindedSnsEntNmByVd: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_getStaticMetas: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_enumStaticMetas: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi2_sysctl_preInit: "v1_0", "v2_0", "v3_0", "v4_0", "v5_0";
+        rk_aiq_uapi_sysctl_set_gll;
+        rk_aiq_uapi2_sysctl_set_gll;
+        rk_aiq_uapi_sysctl_get_gll;
+        rk_aiq_uapi2_sysctl_get_gll;
+        rk_aiq_init_lib;
+        rk_aiq_deinit_lib;
+        rk_aiq_set_log_callback;
+        rk_aiq_uapi2_set_log_callback;
+    ]
+}
+
+/// 返回全部已封装的 uAPI 函数，按模块分组。
+pub fn coverage_report() -> Vec<CoverageEntry> {
+    REGISTRY.to_vec()
+}
+
+/// `symbol` 是否已封装并随 SDK 版本特性 `version`（例如 `"v5_0"`）编译。
+pub fn is_wrapped(symbol: &str, version: &str) -> bool {
+    REGISTRY
+        .iter()
+        .any(|e| e.symbol == symbol && e.versions.contains(&version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::Path;
+
+    /// 扫描各模块测试以外的源码，返回 `(模块, 函数)`。
+    fn referenced_symbols() -> BTreeSet<(String, String)> {
+        let call = Regex::new(r"ffi::(rk_aiq_\w+)\(").unwrap();
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut symbols = BTreeSet::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|e| e == "rs") {
+                let module = path.file_stem().unwrap().to_str().unwrap().to_owned();
+                let source = fs::read_to_string(&path).unwrap();
+                let lines = source
+                    .lines()
+                    .take_while(|l| !l.starts_with("#[cfg(test)]"))
+                    .filter(|l| !l.trim_start().starts_with("//"));
+                for line in lines {
+                    for cap in call.captures_iter(line) {
+                        symbols.insert((module.clone(), cap[1].to_owned()));
+                    }
+                }
+            }
+        }
+        symbols
+    }
+
+    #[test]
+    fn test_registry_matches_sources() {
+        let registered: BTreeSet<_> = coverage_report()
+            .iter()
+            .map(|e| (e.module.to_owned(), e.symbol.to_owned()))
+            .collect();
+        assert_eq!(registered.len(), REGISTRY.len(), "duplicate entries");
+        let referenced = referenced_symbols();
+        let missing: Vec<_> = referenced.difference(&registered).collect();
+        assert!(missing.is_empty(), "not registered: {:?}", missing);
+        let stale: Vec<_> = registered.difference(&referenced).collect();
+        assert!(stale.is_empty(), "no longer wrapped: {:?}", stale);
+    }
+
+    #[test]
+    fn test_registry_matches_bindings() {
+        let bound = bound_symbols();
+        let available: Vec<_> = REGISTRY.iter().filter(|e| e.is_available()).collect();
+        assert_eq!(bound.len(), available.len());
+        assert!(bound.iter().all(|(_, f)| !f.is_null()));
+        for e in REGISTRY {
+            for v in e.versions {
+                assert!(["v1_0", "v2_0", "v3_0", "v4_0", "v5_0"].contains(v));
+            }
+        }
+        assert!(is_wrapped("rk_aiq_uapi_setHue", "v1_0"));
+        assert!(!is_wrapped("rk_aiq_uapi_setHue", "v9_0"));
+    }
+}
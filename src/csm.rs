@@ -0,0 +1,219 @@
+//! 色彩空间转换
+//!
+//! ISP 输出 YUV 前由 CSM（Color Space Matrix）模块将 RGB 转换为 YUV，转换矩阵决定了输出遵循
+//! 的标准；编码器按 BT.709 标记码流而 ISP 按 BT.601 转换时，画面会出现轻微的色偏。
+//! `set_yuv_matrix` 以手动模式写入所选标准的矩阵，仅 v5_0 的 ISP21、ISP30、ISP32 提供 CSM 接口，
+//! 其余版本返回 `XCamError::Unsupported`，可用性亦反映在 `Capability::Csm` 中。
+//!
+//! # 量化范围
+//!
+//! 全范围（full range）与限制范围（limited range）由 CSM 属性中的 `full_range` 决定，并由 CGC
+//! （Color Gamut Clip）模块按同一范围限幅：限制范围下亮度限于 [16,235]、色度限于 [16,240]。
+//! `set_yuv_matrix` 不修改量化范围：沿用当前的 `full_range`，限制范围时将亮度系数乘以 219/255、
+//! 色度系数乘以 224/255，亮度偏移取 16；`get_yuv_matrix` 同样按当前范围识别矩阵。
+//! 修改量化范围须另行设置 CSM 属性中的 `full_range` 与 CGC 属性中的 `cgc_yuv_limit`，
+//! 之后应重新调用 `set_yuv_matrix`，否则系数仍按原范围缩放。
+use super::context::Context;
+use super::error::XCamError;
+#[cfg(all(
+    feature = "v5_0",
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30", feature = "isp_hw_v32")
+))]
+use super::ffi;
+use super::shared::SharedContext;
+use super::types::XCamResult;
+#[cfg(all(
+    feature = "v5_0",
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30", feature = "isp_hw_v32")
+))]
+use super::uapi::{uapi_call, uapi_get};
+
+/// 一个描述 RGB 到 YUV 转换标准的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum YuvMatrix {
+    /// ITU-R BT.601，标清及 JPEG 常用。
+    Bt601,
+    /// ITU-R BT.709，高清视频常用。
+    Bt709,
+    /// ITU-R BT.2020，超高清视频。
+    Bt2020,
+}
+
+impl YuvMatrix {
+    /// 全部标准。
+    pub const ALL: [YuvMatrix; 3] = [Self::Bt601, Self::Bt709, Self::Bt2020];
+
+    /// 红、蓝两个分量的亮度权重 `(Kr, Kb)`。
+    fn weights(self) -> (f32, f32) {
+        match self {
+            Self::Bt601 => (0.299, 0.114),
+            Self::Bt709 => (0.2126, 0.0722),
+            Self::Bt2020 => (0.2627, 0.0593),
+        }
+    }
+}
+
+/// 识别矩阵时各系数允许的误差，容纳 ISP 寄存器的定点量化。
+const COEFF_TOLERANCE: f32 = 0.01;
+
+/// 计算 `matrix` 的 RGB 到 YUV 转换系数，按行排列 `[Y, U, V]`。
+///
+/// `full_range` 为假时按限制范围缩放，见模块说明。
+pub fn csm_coeffs(matrix: YuvMatrix, full_range: bool) -> [f32; 9] {
+    let (kr, kb) = matrix.weights();
+    let kg = 1.0 - kr - kb;
+    let (ys, cs) = if full_range {
+        (1.0, 1.0)
+    } else {
+        (219.0 / 255.0, 224.0 / 255.0)
+    };
+    let u = 0.5 / (1.0 - kb);
+    let v = 0.5 / (1.0 - kr);
+    [
+        kr * ys,
+        kg * ys,
+        kb * ys,
+        -kr * u * cs,
+        -kg * u * cs,
+        0.5 * cs,
+        0.5 * cs,
+        -kg * v * cs,
+        -kb * v * cs,
+    ]
+}
+
+/// 由转换系数识别标准，与任何标准都不一致（例如 IQ 文件中调校过的矩阵）时返回 `None`。
+pub fn yuv_matrix_from_coeffs(coeffs: &[f32; 9], full_range: bool) -> Option<YuvMatrix> {
+    YuvMatrix::ALL.into_iter().find(|&m| {
+        csm_coeffs(m, full_range)
+            .iter()
+            .zip(coeffs)
+            .all(|(a, b)| (a - b).abs() <= COEFF_TOLERANCE)
+    })
+}
+
+/// 一个描述色彩空间转换控制的契定。
+pub trait ColorSpace {
+    /// 获取当前 RGB 到 YUV 转换矩阵对应的标准，矩阵不属于任何标准时为 `None`。
+    fn get_yuv_matrix(&self) -> XCamResult<Option<YuvMatrix>> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 以手动模式设置 RGB 到 YUV 转换矩阵，沿用当前的量化范围，见模块说明。
+    fn set_yuv_matrix(&self, matrix: YuvMatrix) -> XCamResult<()> {
+        let _ = matrix;
+        Err(XCamError::Unsupported)
+    }
+}
+
+#[cfg(all(
+    feature = "v5_0",
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30", feature = "isp_hw_v32")
+))]
+type CsmAttr = ffi::rk_aiq_uapi_acsm_attrib_t;
+
+/// 将 `matrix` 按属性中的量化范围写入 CSM 属性，并切换为手动模式。
+#[cfg(all(
+    feature = "v5_0",
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30", feature = "isp_hw_v32")
+))]
+fn write_csm(attr: &mut CsmAttr, matrix: YuvMatrix) {
+    let param = &mut attr.param;
+    param.op_mode = ffi::RKAiqOPMode_e::RK_AIQ_OP_MODE_MANUAL;
+    param.coeff = csm_coeffs(matrix, param.full_range);
+    param.y_offset = if param.full_range { 0 } else { 16 };
+    param.c_offset = 128;
+}
+
+impl ColorSpace for Context {
+    #[cfg(all(
+        feature = "v5_0",
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30", feature = "isp_hw_v32")
+    ))]
+    fn get_yuv_matrix(&self) -> XCamResult<Option<YuvMatrix>> {
+        let attr = uapi_get!(ffi::rk_aiq_user_api2_acsm_GetAttrib(self) -> CsmAttr)?;
+        Ok(yuv_matrix_from_coeffs(
+            &attr.param.coeff,
+            attr.param.full_range,
+        ))
+    }
+
+    #[cfg(all(
+        feature = "v5_0",
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30", feature = "isp_hw_v32")
+    ))]
+    fn set_yuv_matrix(&self, matrix: YuvMatrix) -> XCamResult<()> {
+        let mut attr = uapi_get!(ffi::rk_aiq_user_api2_acsm_GetAttrib(self) -> CsmAttr)?;
+        write_csm(&mut attr, matrix);
+        uapi_call!(ffi::rk_aiq_user_api2_acsm_SetAttrib(self, attr))
+    }
+}
+
+impl ColorSpace for SharedContext {
+    #[cfg(all(
+        feature = "v5_0",
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30", feature = "isp_hw_v32")
+    ))]
+    fn get_yuv_matrix(&self) -> XCamResult<Option<YuvMatrix>> {
+        self.inner.get_yuv_matrix()
+    }
+
+    #[cfg(all(
+        feature = "v5_0",
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30", feature = "isp_hw_v32")
+    ))]
+    fn set_yuv_matrix(&self, matrix: YuvMatrix) -> XCamResult<()> {
+        self.inner.set_yuv_matrix(matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yuv_matrix_round_trip() {
+        for full_range in [true, false] {
+            for m in YuvMatrix::ALL {
+                let coeffs = csm_coeffs(m, full_range);
+                assert_eq!(yuv_matrix_from_coeffs(&coeffs, full_range), Some(m));
+                // 各行系数之和：亮度为 1（限制范围按比例缩小），色度为 0。
+                let y: f32 = coeffs[..3].iter().sum();
+                assert!((y - if full_range { 1.0 } else { 219.0 / 255.0 }).abs() < 1e-6);
+                assert!(coeffs[3..6].iter().sum::<f32>().abs() < 1e-6);
+                assert!(coeffs[6..].iter().sum::<f32>().abs() < 1e-6);
+            }
+        }
+        // BT.601 全范围与常用的定点系数一致。
+        let bt601 = csm_coeffs(YuvMatrix::Bt601, true);
+        assert!((bt601[3] + 0.1687).abs() < 1e-3);
+        assert!((bt601[7] + 0.4187).abs() < 1e-3);
+
+        // 范围不一致或调校过的矩阵不属于任何标准。
+        let limited = csm_coeffs(YuvMatrix::Bt709, false);
+        assert_eq!(yuv_matrix_from_coeffs(&limited, true), None);
+        let mut tuned = csm_coeffs(YuvMatrix::Bt709, true);
+        tuned[0] += 0.05;
+        assert_eq!(yuv_matrix_from_coeffs(&tuned, true), None);
+    }
+
+    #[cfg(all(
+        feature = "v5_0",
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30", feature = "isp_hw_v32")
+    ))]
+    #[test]
+    fn test_write_csm() {
+        let mut attr = CsmAttr::default();
+        attr.param.full_range = false;
+        write_csm(&mut attr, YuvMatrix::Bt709);
+        assert_eq!(
+            attr.param.op_mode,
+            ffi::RKAiqOPMode_e::RK_AIQ_OP_MODE_MANUAL
+        );
+        assert_eq!((attr.param.y_offset, attr.param.c_offset), (16, 128));
+        assert_eq!(
+            yuv_matrix_from_coeffs(&attr.param.coeff, attr.param.full_range),
+            Some(YuvMatrix::Bt709)
+        );
+    }
+}
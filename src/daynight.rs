@@ -0,0 +1,550 @@
+//! 日夜切换
+//!
+//! 根据画面平均亮度与增益自动切换日夜模式，亮度默认取自自动曝光结果中的平均亮度。
+//! 设置 `DayNightConfig::use_luma_map` 后改为取自 `get_luma_map` 的亮度图，
+//! 无法获取时退回平均亮度；读取亮度图会消耗统计帧，与其他统计读取者竞争。
+//! 进入与退出夜间模式使用两组阈值形成回差，且条件须持续满足一段驻留时间后才切换，
+//! 以避免在临界亮度下反复切换。
+//!
+//! 切换判决由 `DayNightDecider` 完成，其输出只取决于输入的统计序列与时间戳，可独立测试；
+//! `AutoDayNight` 在独立线程中周期查询曝光结果并驱动判决器。
+//!
+//! `EnvProfile` 将红外截止、黑白模式、降噪强度与曝光限制作为一组设置同时切换，
+//! 避免逐项切换时画面出现可见的中间状态。
+use super::ae::AutoExposure;
+use super::context::Context;
+use super::display::enum_str;
+use super::error::XCamError;
+use super::ffi;
+use super::luma::LumaMap;
+use super::misc::Miscellaneous;
+use super::nr::NoiseRemoval;
+use super::sysctl::SystemControl;
+use super::types::{CpslCfg, GrayMode, Strength, XCamResult};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// 一个描述日夜模式的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DayNightMode {
+    /// 白天模式：彩色图像，红外截止滤光片切入。
+    Day,
+    /// 夜间模式：黑白图像，红外截止滤光片切出。
+    Night,
+}
+
+enum_str!(DayNightMode, "day/night mode" {
+    Day => "day",
+    Night => "night",
+});
+
+impl DayNightMode {
+    fn toggled(self) -> Self {
+        match self {
+            Self::Day => Self::Night,
+            Self::Night => Self::Day,
+        }
+    }
+}
+
+/// 一个描述日夜切换参数的类型。
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DayNightConfig {
+    /// 进入夜间模式的亮度上限，范围：[0,255]。
+    pub night_luma: f32,
+    /// 退出夜间模式的亮度下限，须大于 `night_luma`。
+    pub day_luma: f32,
+    /// 进入夜间模式的增益下限，单位：倍。
+    pub night_gain: f32,
+    /// 退出夜间模式的增益上限，须小于 `night_gain`。
+    pub day_gain: f32,
+    /// 切换条件须持续满足的时间。
+    pub dwell: Duration,
+    /// 查询曝光结果的周期。
+    pub interval: Duration,
+    /// 是否每次查询时读取亮度图，以其均值作为画面亮度，默认不读取。
+    ///
+    /// 每次读取消耗一帧 3A 统计，其他通过 `get_3a_stats` 等接口读取统计的调用方会因此丢帧。
+    pub use_luma_map: bool,
+}
+
+impl Default for DayNightConfig {
+    fn default() -> Self {
+        Self {
+            night_luma: 20.0,
+            day_luma: 60.0,
+            night_gain: 32.0,
+            day_gain: 8.0,
+            dwell: Duration::from_secs(5),
+            interval: Duration::from_millis(200),
+            use_luma_map: false,
+        }
+    }
+}
+
+impl DayNightConfig {
+    /// 检查阈值是否构成有效的回差区间。
+    pub fn validate(&self) -> XCamResult<()> {
+        if self.night_luma < self.day_luma
+            && self.day_gain < self.night_gain
+            && !self.interval.is_zero()
+        {
+            Ok(())
+        } else {
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        }
+    }
+}
+
+/// 一个日夜切换判决器。
+#[derive(Clone, Debug)]
+pub struct DayNightDecider {
+    cfg: DayNightConfig,
+    mode: DayNightMode,
+    pending_since: Option<Duration>,
+}
+
+impl DayNightDecider {
+    /// 创建一个以 `mode` 为初始模式的判决器。
+    pub fn new(cfg: DayNightConfig, mode: DayNightMode) -> Self {
+        Self {
+            cfg,
+            mode,
+            pending_since: None,
+        }
+    }
+
+    /// 返回当前模式。
+    pub fn mode(&self) -> DayNightMode {
+        self.mode
+    }
+
+    /// 输入一次曝光结果，需要切换时返回新的模式。
+    ///
+    /// # Parameters
+    /// * `luma` - 画面平均亮度。
+    /// * `gain` - 当前总增益。
+    /// * `now` - 单调递增的时间戳。
+    pub fn update(&mut self, luma: f32, gain: f32, now: Duration) -> Option<DayNightMode> {
+        let wants_switch = match self.mode {
+            DayNightMode::Day => luma <= self.cfg.night_luma && gain >= self.cfg.night_gain,
+            DayNightMode::Night => luma >= self.cfg.day_luma && gain <= self.cfg.day_gain,
+        };
+        if !wants_switch {
+            self.pending_since = None;
+            return None;
+        }
+        let since = *self.pending_since.get_or_insert(now);
+        if now.saturating_sub(since) < self.cfg.dwell {
+            return None;
+        }
+        self.pending_since = None;
+        self.mode = self.mode.toggled();
+        Some(self.mode)
+    }
+}
+
+/// 一个描述日夜切换控制器状态的类型。
+#[derive(Clone, Debug, PartialEq)]
+pub struct DayNightStatus {
+    /// 当前模式。
+    pub mode: DayNightMode,
+    /// 最近一次测得的画面平均亮度。
+    pub luma: f32,
+    /// 最近一次测得的总增益。
+    pub gain: f32,
+    /// 最近一次读取的亮度图，可供场景分析复用，不必再消耗一帧统计；
+    /// 未设置 `DayNightConfig::use_luma_map` 时为 `None`。
+    pub luma_map: Option<LumaMap>,
+    /// 最近一次查询或切换失败的错误。
+    pub last_error: Option<XCamError>,
+}
+
+/// 一个在独立线程中运行的日夜切换控制器。
+///
+/// 控制器被释放或调用 `stop` 时停止线程。
+pub struct AutoDayNight {
+    stop: Arc<AtomicBool>,
+    status: Arc<Mutex<DayNightStatus>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl AutoDayNight {
+    /// 启动日夜切换控制器。
+    ///
+    /// 切换到夜间模式时先调用 `on_switch` 切出红外截止滤光片，再开启黑白模式；
+    /// 切换到白天模式时先关闭黑白模式，再调用 `on_switch`。
+    ///
+    /// # Parameters
+    /// * `ctx` - 上下文。
+    /// * `cfg` - 切换参数。
+    /// * `initial` - 当前所处的模式。
+    /// * `on_switch` - 控制红外截止滤光片等硬件的回调。
+    pub fn spawn<C, F>(
+        ctx: Arc<C>,
+        cfg: DayNightConfig,
+        initial: DayNightMode,
+        mut on_switch: F,
+    ) -> XCamResult<Self>
+    where
+        C: AutoExposure + Miscellaneous + SystemControl + Send + Sync + 'static,
+        F: FnMut(DayNightMode) + Send + 'static,
+    {
+        cfg.validate()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let status = Arc::new(Mutex::new(DayNightStatus {
+            mode: initial,
+            luma: 0.0,
+            gain: 0.0,
+            luma_map: None,
+            last_error: None,
+        }));
+        let handle = {
+            let stop = stop.clone();
+            let status = status.clone();
+            thread::Builder::new()
+                .name("rkaiq-daynight".into())
+                .spawn(move || {
+                    let start = Instant::now();
+                    let mut decider = DayNightDecider::new(cfg, initial);
+                    while !stop.load(Ordering::Acquire) {
+                        let r = ctx.query_exp_info().and_then(|info| {
+                            let map = if cfg.use_luma_map {
+                                ctx.get_luma_map().ok()
+                            } else {
+                                None
+                            };
+                            let luma = map.as_ref().map_or(info.mean_luma, LumaMap::mean);
+                            let mut st = status.lock().unwrap();
+                            st.luma = luma;
+                            st.gain = info.gain;
+                            st.luma_map = map;
+                            drop(st);
+                            match decider.update(luma, info.gain, start.elapsed()) {
+                                Some(mode) => switch(ctx.as_ref(), mode, &mut on_switch),
+                                None => Ok(()),
+                            }
+                        });
+                        let mut st = status.lock().unwrap();
+                        st.mode = decider.mode();
+                        if let Err(e) = r {
+                            st.last_error = Some(e);
+                        }
+                        drop(st);
+                        thread::park_timeout(cfg.interval);
+                    }
+                })
+                .map_err(|_| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_THREAD))?
+        };
+        Ok(Self {
+            stop,
+            status,
+            handle: Some(handle),
+        })
+    }
+
+    /// 返回控制器当前状态。
+    pub fn status(&self) -> DayNightStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// 停止控制器并等待线程退出。
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for AutoDayNight {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn switch<C, F>(ctx: &C, mode: DayNightMode, on_switch: &mut F) -> XCamResult<()>
+where
+    C: Miscellaneous,
+    F: FnMut(DayNightMode),
+{
+    match mode {
+        DayNightMode::Night => {
+            on_switch(mode);
+            ctx.set_gray_mode(GrayMode::RK_AIQ_GRAY_MODE_ON)
+        }
+        DayNightMode::Day => {
+            ctx.set_gray_mode(GrayMode::RK_AIQ_GRAY_MODE_OFF)?;
+            on_switch(mode);
+            Ok(())
+        }
+    }
+}
+
+/// 一个描述环境配置的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EnvProfile {
+    /// 白天：彩色，红外截止滤光片切入，曝光上限 1/30s，增益上限 16 倍，降噪强度 50。
+    Day,
+    /// 黄昏：彩色，红外截止滤光片切入，曝光上限 1/15s，增益上限 32 倍，降噪强度 65。
+    Dusk,
+    /// 夜间：黑白，红外截止滤光片切出并开启红外补光，曝光上限 1/12.5s，增益上限 64 倍，
+    /// 降噪强度 80。
+    Night,
+}
+
+enum_str!(EnvProfile, "environment profile" {
+    Day => "day",
+    Dusk => "dusk",
+    Night => "night",
+});
+
+/// 一个描述环境配置所含设置的类型。
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EnvSettings {
+    /// 红外补光是否开启。补光由 SDK 以手动模式控制，开启时同时切出红外截止滤光片。
+    pub ir_light: bool,
+    /// 黑白图像模式。
+    pub gray_mode: GrayMode,
+    /// 降噪强度。
+    pub nr_strength: Strength,
+    /// 曝光时间范围，单位：秒。
+    pub exp_time_range: (f32, f32),
+    /// 增益范围，单位：倍。
+    pub gain_range: (f32, f32),
+}
+
+/// 环境配置中的单项设置，按开启红外补光时的应用顺序排列。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum EnvStep {
+    ExpTime,
+    Gain,
+    Nr,
+    IrLight,
+    Gray,
+}
+
+const ENV_STEPS: [EnvStep; 5] = [
+    EnvStep::ExpTime,
+    EnvStep::Gain,
+    EnvStep::Nr,
+    EnvStep::IrLight,
+    EnvStep::Gray,
+];
+
+impl EnvProfile {
+    /// 返回该配置所含的设置。
+    pub fn settings(self) -> EnvSettings {
+        let (ir_light, nr, max_time, max_gain) = match self {
+            Self::Day => (false, 50, 1.0 / 30.0, 16.0),
+            Self::Dusk => (false, 65, 1.0 / 15.0, 32.0),
+            Self::Night => (true, 80, 1.0 / 12.5, 64.0),
+        };
+        EnvSettings {
+            ir_light,
+            gray_mode: if ir_light {
+                GrayMode::RK_AIQ_GRAY_MODE_ON
+            } else {
+                GrayMode::RK_AIQ_GRAY_MODE_OFF
+            },
+            nr_strength: Strength::new(nr).unwrap(),
+            exp_time_range: (0.0001, max_time),
+            gain_range: (1.0, max_gain),
+        }
+    }
+
+    /// 应用环境配置。
+    ///
+    /// 开启红外补光时依次设置曝光时间范围、增益范围、降噪强度、红外补光与黑白模式，
+    /// 即先在彩色下适应暗光再切换为黑白；关闭红外补光时顺序相反。
+    /// 某项设置失败时按相反顺序恢复已修改的设置并返回该错误，恢复过程中的错误被忽略。
+    /// 补光开关以手动模式恢复，原先的自动补光模式不会保留。
+    pub fn apply<C>(self, ctx: &C) -> XCamResult<()>
+    where
+        C: AutoExposure + Miscellaneous + NoiseRemoval + SystemControl,
+    {
+        let prior = read_env_settings(ctx)?;
+        let target = self.settings();
+        let mut steps = ENV_STEPS;
+        if !target.ir_light {
+            steps.reverse();
+        }
+        for (i, &step) in steps.iter().enumerate() {
+            if let Err(e) = apply_env_step(ctx, step, &target) {
+                for &done in steps[..i].iter().rev() {
+                    let _ = apply_env_step(ctx, done, &prior);
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// 检测当前所处的环境配置，各项设置与某个配置完全一致时返回该配置。
+    pub fn detect<C>(ctx: &C) -> XCamResult<Option<Self>>
+    where
+        C: AutoExposure + Miscellaneous + NoiseRemoval + SystemControl,
+    {
+        let current = read_env_settings(ctx)?;
+        Ok([Self::Day, Self::Dusk, Self::Night]
+            .into_iter()
+            .find(|p| p.settings() == current))
+    }
+}
+
+fn read_env_settings<C>(ctx: &C) -> XCamResult<EnvSettings>
+where
+    C: AutoExposure + Miscellaneous + NoiseRemoval + SystemControl,
+{
+    Ok(EnvSettings {
+        ir_light: ctx.get_cps_lt_info()?.on != 0,
+        gray_mode: ctx.get_gray_mode(),
+        nr_strength: ctx.get_anr_strength()?,
+        exp_time_range: ctx.get_exp_time_range()?,
+        gain_range: ctx.get_exp_gain_range()?,
+    })
+}
+
+fn apply_env_step<C>(ctx: &C, step: EnvStep, s: &EnvSettings) -> XCamResult<()>
+where
+    C: AutoExposure + Miscellaneous + NoiseRemoval + SystemControl,
+{
+    match step {
+        EnvStep::ExpTime => ctx.set_exp_time_range(s.exp_time_range.0, s.exp_time_range.1),
+        EnvStep::Gain => ctx.set_exp_gain_range(s.gain_range.0, s.gain_range.1),
+        EnvStep::Nr => ctx.set_anr_strength(s.nr_strength),
+        EnvStep::IrLight => ctx.set_cps_lt_cfg(ir_light_cfg(s.ir_light)),
+        EnvStep::Gray => ctx.set_gray_mode(s.gray_mode),
+    }
+}
+
+/// 手动开关红外补光的配置。
+fn ir_light_cfg(on: bool) -> CpslCfg {
+    let mut cfg = CpslCfg {
+        mode: ffi::RKAiqOPMode_e::RK_AIQ_OP_MODE_MANUAL,
+        lght_src: ffi::rk_aiq_cpsls_e::RK_AIQ_CPSLS_IR,
+        gray_on: on,
+        ..Default::default()
+    };
+    cfg.u.m.on = on;
+    cfg
+}
+
+impl Context {
+    /// 应用环境配置，见 `EnvProfile::apply`。
+    pub fn apply_environment_profile(&self, p: EnvProfile) -> XCamResult<()> {
+        p.apply(self)
+    }
+
+    /// 检测当前所处的环境配置，见 `EnvProfile::detect`。
+    pub fn environment_profile(&self) -> XCamResult<Option<EnvProfile>> {
+        EnvProfile::detect(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg() -> DayNightConfig {
+        DayNightConfig {
+            dwell: Duration::from_secs(2),
+            ..Default::default()
+        }
+    }
+
+    /// 以 1 秒为间隔依次输入 `(亮度, 增益)`，返回发生切换的时刻与模式。
+    fn run(decider: &mut DayNightDecider, seq: &[(f32, f32)]) -> Vec<(u64, DayNightMode)> {
+        seq.iter()
+            .enumerate()
+            .filter_map(|(t, &(luma, gain))| {
+                decider
+                    .update(luma, gain, Duration::from_secs(t as u64))
+                    .map(|m| (t as u64, m))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decider_dwell() {
+        let mut d = DayNightDecider::new(cfg(), DayNightMode::Day);
+        let dark = (10.0, 64.0);
+        let seq = [(100.0, 1.0), dark, dark, dark, dark];
+        assert_eq!(run(&mut d, &seq), vec![(3, DayNightMode::Night)]);
+        assert_eq!(d.mode(), DayNightMode::Night);
+    }
+
+    #[test]
+    fn test_decider_interruption_resets_dwell() {
+        let mut d = DayNightDecider::new(cfg(), DayNightMode::Day);
+        let dark = (10.0, 64.0);
+        let seq = [dark, dark, (30.0, 64.0), dark, dark, dark];
+        assert_eq!(run(&mut d, &seq), vec![(5, DayNightMode::Night)]);
+    }
+
+    #[test]
+    fn test_decider_hysteresis() {
+        let mut d = DayNightDecider::new(cfg(), DayNightMode::Night);
+        // 亮度介于两阈值之间，或增益仍高时保持夜间模式。
+        let seq = [(40.0, 4.0); 5];
+        assert!(run(&mut d, &seq).is_empty());
+        let seq = [(80.0, 16.0); 5];
+        assert!(run(&mut d, &seq).is_empty());
+        let seq = [(80.0, 4.0); 5];
+        assert_eq!(run(&mut d, &seq), vec![(2, DayNightMode::Day)]);
+    }
+
+    #[test]
+    fn test_config_validate() {
+        assert_eq!(DayNightConfig::default().validate(), Ok(()));
+        let c = DayNightConfig {
+            night_luma: 80.0,
+            ..Default::default()
+        };
+        assert!(c.validate().is_err());
+        let c = DayNightConfig {
+            day_gain: 64.0,
+            ..Default::default()
+        };
+        assert!(c.validate().is_err());
+    }
+
+    #[test]
+    fn test_apply_environment_profile() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        assert_eq!(EnvProfile::detect(&ctx), Ok(None));
+
+        EnvProfile::Night.apply(&ctx).unwrap();
+        let sets: Vec<_> = ctx
+            .calls()
+            .into_iter()
+            .filter(|c| c.starts_with("set_"))
+            .collect();
+        assert_eq!(
+            sets,
+            [
+                "set_exp_time_range",
+                "set_exp_gain_range",
+                "set_anr_strength",
+                "set_cps_lt_cfg",
+                "set_gray_mode",
+            ]
+        );
+        assert_eq!(EnvProfile::detect(&ctx), Ok(Some(EnvProfile::Night)));
+
+        ctx.fail("set_anr_strength");
+        assert!(EnvProfile::Day.apply(&ctx).is_err());
+        assert_eq!(EnvProfile::detect(&ctx), Ok(Some(EnvProfile::Night)));
+        let state = ctx.state.borrow();
+        assert!(state.cpsl_on);
+        assert_eq!(state.gray_mode, GrayMode::RK_AIQ_GRAY_MODE_ON);
+    }
+}
@@ -1,10 +1,48 @@
 //! 图像去雾
 //!
 //! Defog 是通过动态的改变图象的对比度和亮度来实现的去雾增强。
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+use super::attrib::with_fields;
 use super::context::Context;
-use super::error::XCamError;
 use super::ffi;
-use super::types::{OpMode, XCamResult};
+use super::shared::SharedContext;
+use super::types::{OpMode, Strength, XCamResult};
+use super::uapi::uapi_call;
+
+/// 一个代表去雾属性的类型。
+///
+/// 通过 `with_*` 方法修改个别字段，其余字段保持原值。
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+#[derive(Copy, Clone, Default)]
+pub struct DehazeAttrib(pub ffi::adehaze_sw_V2_t);
+
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+with_fields!(DehazeAttrib {
+    /// 设置工作模式。
+    with_mode: ffi::dehaze_api_mode_t => mode;
+    /// 设置手动去雾模块开关。
+    with_enable: bool => stManual.Enable;
+    /// 设置手动融合系数。
+    with_cfg_alpha: f32 => stManual.cfg_alpha;
+    /// 设置手动去雾强度。
+    with_dehaze_level: u32 => stDehazeManu.level;
+    /// 设置手动增强强度。
+    with_enhance_level: u32 => stEnhanceManu.level;
+});
+
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+impl From<ffi::adehaze_sw_V2_t> for DehazeAttrib {
+    fn from(val: ffi::adehaze_sw_V2_t) -> Self {
+        Self(val)
+    }
+}
+
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+impl From<DehazeAttrib> for ffi::adehaze_sw_V2_t {
+    fn from(val: DehazeAttrib) -> Self {
+        val.0
+    }
+}
 
 pub trait Defog {
     fn enable_dhz(&self) -> XCamResult<()>;
@@ -12,70 +50,134 @@ pub trait Defog {
 
     fn get_dhz_mode(&self) -> XCamResult<OpMode>;
     fn set_dhz_mode(&self, mode: OpMode) -> XCamResult<()>;
+
+    /// 获取去雾属性。
+    ///
+    /// # Note
+    ///
+    /// 去雾算法处理一帧期间调用会阻塞，最长约一个帧周期。
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn get_dhz_attrib(&self) -> XCamResult<DehazeAttrib>;
+
+    /// 设置去雾属性。
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn set_dhz_attrib<T: Into<DehazeAttrib>>(&self, attr: T) -> XCamResult<()>;
+
+    /// 获取手动去雾强度。
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn get_dhz_strength(&self) -> XCamResult<Strength> {
+        self.get_dhz_attrib()
+            .map(|attr| Strength::saturating(attr.0.stDehazeManu.level))
+    }
+
+    /// 设置手动去雾强度，其余去雾属性保持不变。
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn set_dhz_strength(&self, strength: Strength) -> XCamResult<()> {
+        let attr = self.get_dhz_attrib()?;
+        self.set_dhz_attrib(attr.with_dehaze_level(strength.into()))
+    }
 }
 
 impl Defog for Context {
+    #[cfg(feature = "v2_0")]
+    fn enable_dhz(&self) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi2_enableDhz(self))
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
     fn enable_dhz(&self) -> XCamResult<()> {
-        #[cfg(feature = "v2_0")]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_enableDhz(self.internal.as_ptr())).ok()
-        }
-        // #[cfg(feature = "v3_0")]
-        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setDehazeEnable(
-                self.internal.as_ptr(),
-                true,
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi2_setDehazeEnable(self, true))
     }
 
+    #[cfg(feature = "v2_0")]
     fn disable_dhz(&self) -> XCamResult<()> {
-        #[cfg(feature = "v2_0")]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_disableDhz(self.internal.as_ptr())).ok()
-        }
-        // #[cfg(feature = "v3_0")]
-        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setDehazeEnable(
-                self.internal.as_ptr(),
-                false,
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi2_disableDhz(self))
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn disable_dhz(&self) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi2_setDehazeEnable(self, false))
+    }
+
+    #[cfg(feature = "v2_0")]
+    fn get_dhz_mode(&self) -> XCamResult<OpMode> {
+        let mut mode: ffi::opMode_t = ffi::opMode_t::OP_AUTO;
+        uapi_call!(ffi::rk_aiq_uapi2_getDhzMode(self, &mut mode)).map(|_| mode.into())
     }
 
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
     fn get_dhz_mode(&self) -> XCamResult<OpMode> {
-        #[cfg(feature = "v2_0")]
-        unsafe {
-            let mut mode: ffi::opMode_t = ffi::opMode_t::OP_AUTO;
-            XCamError::from(ffi::rk_aiq_uapi2_getDhzMode(
-                self.internal.as_ptr(),
-                &mut mode,
-            ))
-            .ok()
-            .map(|_| mode.into())
-        }
-        // #[cfg(feature = "v3_0")]
-        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
         Ok(OpMode::Auto)
     }
 
     #[cfg(feature = "v2_0")]
     fn set_dhz_mode(&self, mode: OpMode) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setDhzMode(
-                self.internal.as_ptr(),
-                mode.into(),
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi2_setDhzMode(self, mode.into()))
     }
 
     #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
     fn set_dhz_mode(&self, _mode: OpMode) -> XCamResult<()> {
         Ok(())
     }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn get_dhz_attrib(&self) -> XCamResult<DehazeAttrib> {
+        let mut attr = ffi::adehaze_sw_V2_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_adehaze_getSwAttrib(self, &mut attr)).map(|_| attr.into())
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn set_dhz_attrib<T: Into<DehazeAttrib>>(&self, attr: T) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_user_api2_adehaze_setSwAttrib(
+            self,
+            attr.into().0
+        ))
+    }
+}
+
+impl Defog for SharedContext {
+    fn enable_dhz(&self) -> XCamResult<()> {
+        self.inner.enable_dhz()
+    }
+
+    fn disable_dhz(&self) -> XCamResult<()> {
+        self.inner.disable_dhz()
+    }
+
+    fn get_dhz_mode(&self) -> XCamResult<OpMode> {
+        self.inner.get_dhz_mode()
+    }
+
+    fn set_dhz_mode(&self, mode: OpMode) -> XCamResult<()> {
+        self.inner.set_dhz_mode(mode)
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn get_dhz_attrib(&self) -> XCamResult<DehazeAttrib> {
+        self.inner.get_dhz_attrib()
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn set_dhz_attrib<T: Into<DehazeAttrib>>(&self, attr: T) -> XCamResult<()> {
+        self.inner.set_dhz_attrib(attr)
+    }
+}
+
+#[cfg(test)]
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dehaze_attrib_builder_keeps_fields() {
+        let mut raw = ffi::adehaze_sw_V2_t::default();
+        raw.stManual.Enable = true;
+        raw.stManual.cfg_alpha = 0.75;
+        raw.stEnhanceManu.level = 30;
+        let attr = DehazeAttrib::from(raw).with_dehaze_level(60);
+        assert_eq!(attr.0.stDehazeManu.level, 60);
+        assert!(attr.0.stManual.Enable);
+        assert_eq!(attr.0.stManual.cfg_alpha, 0.75);
+        assert_eq!(attr.0.stEnhanceManu.level, 30);
+    }
 }
@@ -0,0 +1,378 @@
+//! 细节管理
+//!
+//! 增益升高时噪声增大，锐化会放大噪声，调试时通常需要随 ISO 同时提高降噪强度、降低锐化强度。
+//! `DetailManager` 保存一张 `(ISO, 降噪强度, 锐化强度)` 表，在独立线程中周期查询曝光结果，
+//! 按 ISO 在表中插值，并只在 ISO 跨入新的分档时写入降噪与锐化模块，避免每次查询都设置参数。
+//!
+//! 插值与分档由 `DetailTracker` 完成，其输出只取决于输入的 ISO 序列，可独立测试。
+use super::ae::{AutoExposure, DEFAULT_BASE_ISO};
+use super::error::XCamError;
+use super::ffi;
+use super::logging::log_warn;
+use super::misc::Miscellaneous;
+use super::nr::NoiseRemoval;
+use super::settle::{default_settle_frames, frame_period_from_fps, settle_duration};
+use super::sharpen::Sharpen;
+use super::types::{ModuleId, Strength, XCamResult};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// 默认分档宽度，单位：档（ISO 翻倍为 1 档）。
+pub const DEFAULT_BUCKET_STOPS: f32 = 1.0 / 3.0;
+/// 默认查询周期。
+pub const DEFAULT_DETAIL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 一个描述细节表中一行的类型。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DetailRow {
+    /// ISO 值，须大于 0。
+    pub iso: u32,
+    /// 降噪强度。
+    pub nr_strength: Strength,
+    /// 锐化强度。
+    pub sharpness: Strength,
+}
+
+/// 一个描述降噪与锐化强度的类型。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DetailLevels {
+    /// 降噪强度。
+    pub nr_strength: Strength,
+    /// 锐化强度。
+    pub sharpness: Strength,
+}
+
+/// 检查细节表：非空、ISO 大于 0 且严格递增，否则返回 `XCAM_RETURN_ERROR_PARAM`。
+pub fn check_detail_table(table: &[DetailRow]) -> XCamResult<()> {
+    let increasing = table.windows(2).all(|w| w[0].iso < w[1].iso);
+    match table.first() {
+        Some(first) if first.iso > 0 && increasing => Ok(()),
+        _ => Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM)),
+    }
+}
+
+/// 在细节表中按 ISO 插值，表须通过 `check_detail_table` 检查。
+///
+/// 插值在 ISO 的对数域上进行，使每档之间的变化均匀；超出表的范围时取两端的值。
+pub fn interpolate_detail(table: &[DetailRow], iso: u32) -> DetailLevels {
+    let levels = |row: &DetailRow| DetailLevels {
+        nr_strength: row.nr_strength,
+        sharpness: row.sharpness,
+    };
+    let i = table.partition_point(|row| row.iso <= iso);
+    if i == 0 {
+        return levels(&table[0]);
+    }
+    if i == table.len() {
+        return levels(&table[i - 1]);
+    }
+    let (lo, hi) = (&table[i - 1], &table[i]);
+    let t = (iso as f32 / lo.iso as f32).log2() / (hi.iso as f32 / lo.iso as f32).log2();
+    let lerp = |a: Strength, b: Strength| {
+        let v = a.get() as f32 + (b.get() as f32 - a.get() as f32) * t;
+        Strength::saturating(v.round() as u32)
+    };
+    DetailLevels {
+        nr_strength: lerp(lo.nr_strength, hi.nr_strength),
+        sharpness: lerp(lo.sharpness, hi.sharpness),
+    }
+}
+
+/// 返回 ISO 所在的分档，以基准 ISO 所在的分档为 0，每档宽 `stops` 档。
+pub fn iso_bucket(iso: u32, stops: f32) -> i32 {
+    ((iso.max(1) as f32 / DEFAULT_BASE_ISO as f32).log2() / stops).round() as i32
+}
+
+/// 返回分档的中心 ISO。
+pub fn bucket_iso(bucket: i32, stops: f32) -> u32 {
+    (DEFAULT_BASE_ISO as f32 * (bucket as f32 * stops).exp2()).round() as u32
+}
+
+/// 一个按 ISO 分档输出细节强度的跟踪器。
+#[derive(Clone, Debug)]
+pub struct DetailTracker {
+    table: Vec<DetailRow>,
+    stops: f32,
+    bucket: Option<i32>,
+}
+
+impl DetailTracker {
+    /// 创建跟踪器，细节表或分档宽度无效时返回 `XCAM_RETURN_ERROR_PARAM`。
+    pub fn new(table: Vec<DetailRow>, stops: f32) -> XCamResult<Self> {
+        check_detail_table(&table)?;
+        if !(stops.is_finite() && stops > 0.0) {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        Ok(Self {
+            table,
+            stops,
+            bucket: None,
+        })
+    }
+
+    /// 输入一次 ISO，分档变化时返回按分档中心 ISO 插值的强度。
+    pub fn update(&mut self, iso: u32) -> Option<DetailLevels> {
+        let bucket = iso_bucket(iso, self.stops);
+        if self.bucket == Some(bucket) {
+            return None;
+        }
+        self.bucket = Some(bucket);
+        Some(interpolate_detail(
+            &self.table,
+            bucket_iso(bucket, self.stops),
+        ))
+    }
+
+    /// 清除记录的分档，下一次输入总是返回强度。
+    pub fn reset(&mut self) {
+        self.bucket = None;
+    }
+}
+
+/// 读取当前的降噪与锐化强度。
+pub(crate) fn read_levels<C: NoiseRemoval + Sharpen>(ctx: &C) -> XCamResult<DetailLevels> {
+    Ok(DetailLevels {
+        nr_strength: ctx.get_anr_strength()?,
+        sharpness: ctx.get_sharpness()?,
+    })
+}
+
+/// 写入降噪与锐化强度，两项都会写入，返回遇到的第一个错误。
+pub(crate) fn write_levels<C: NoiseRemoval + Sharpen>(
+    ctx: &C,
+    levels: DetailLevels,
+) -> XCamResult<()> {
+    let nr = ctx.set_anr_strength(levels.nr_strength);
+    let sharp = ctx.set_sharpness(levels.sharpness);
+    nr.and(sharp)
+}
+
+/// 一个按 ISO 联动降噪与锐化强度的细节管理器。
+#[derive(Clone, Debug)]
+pub struct DetailManager {
+    table: Vec<DetailRow>,
+    bucket_stops: f32,
+    interval: Duration,
+}
+
+impl DetailManager {
+    /// 以细节表创建管理器，分档宽度与查询周期取默认值。
+    pub fn new(table: Vec<DetailRow>) -> Self {
+        Self {
+            table,
+            bucket_stops: DEFAULT_BUCKET_STOPS,
+            interval: DEFAULT_DETAIL_INTERVAL,
+        }
+    }
+
+    /// 设置分档宽度，单位：档。
+    pub fn bucket_stops(mut self, stops: f32) -> Self {
+        self.bucket_stops = stops;
+        self
+    }
+
+    /// 设置查询曝光结果的周期。
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// 启动管理线程，返回的句柄被释放或调用 `detach` 时停止并恢复启动前的强度。
+    ///
+    /// 细节表、分档宽度或查询周期无效时返回 `XCAM_RETURN_ERROR_PARAM`。每次写入强度后，
+    /// 线程按当前帧率等待时域降噪的生效帧数再继续查询，等待时间不短于查询周期。
+    pub fn attach<C>(&self, ctx: Arc<C>) -> XCamResult<AttachedDetailManager<C>>
+    where
+        C: AutoExposure + Miscellaneous + NoiseRemoval + Sharpen + Send + Sync + 'static,
+    {
+        if self.interval.is_zero() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        let mut tracker = DetailTracker::new(self.table.clone(), self.bucket_stops)?;
+        let prior = read_levels(ctx.as_ref())?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let last_error = Arc::new(Mutex::new(None));
+        let interval = self.interval;
+        let handle = {
+            let ctx = ctx.clone();
+            let stop = stop.clone();
+            let last_error = last_error.clone();
+            thread::Builder::new()
+                .name("rkaiq-detail".into())
+                .spawn(move || {
+                    while !stop.load(Ordering::Acquire) {
+                        let mut wait = interval;
+                        let r = ctx.query_exp_info().and_then(|info| {
+                            let Some(levels) = tracker.update(info.iso) else {
+                                return Ok(());
+                            };
+                            if let Err(e) = write_levels(ctx.as_ref(), levels) {
+                                // 写入失败时下次查询重新写入
+                                tracker.reset();
+                                return Err(e);
+                            }
+                            let fps = ctx.get_frame_rate()?.fps;
+                            if let Some(period) = frame_period_from_fps(fps) {
+                                let frames = default_settle_frames(ModuleId::RK_MODULE_TNR);
+                                wait = wait.max(settle_duration(period, frames));
+                            }
+                            Ok(())
+                        });
+                        if let Err(e) = r {
+                            *last_error.lock().unwrap() = Some(e);
+                        }
+                        thread::park_timeout(wait);
+                    }
+                })
+                .map_err(|_| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_THREAD))?
+        };
+        Ok(AttachedDetailManager {
+            ctx,
+            prior,
+            stop,
+            last_error,
+            handle: Some(handle),
+            restored: false,
+        })
+    }
+}
+
+/// 一个运行中的细节管理器，由 `DetailManager::attach` 返回。
+pub struct AttachedDetailManager<C: NoiseRemoval + Sharpen> {
+    ctx: Arc<C>,
+    prior: DetailLevels,
+    stop: Arc<AtomicBool>,
+    last_error: Arc<Mutex<Option<XCamError>>>,
+    handle: Option<JoinHandle<()>>,
+    restored: bool,
+}
+
+impl<C: NoiseRemoval + Sharpen> AttachedDetailManager<C> {
+    /// 最近一次查询或写入失败的错误。
+    pub fn last_error(&self) -> Option<XCamError> {
+        *self.last_error.lock().unwrap()
+    }
+
+    /// 停止管理线程并恢复启动前的降噪与锐化强度，返回恢复中遇到的第一个错误。
+    pub fn detach(mut self) -> XCamResult<()> {
+        self.shutdown();
+        self.restored = true;
+        write_levels(self.ctx.as_ref(), self.prior)
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl<C: NoiseRemoval + Sharpen> Drop for AttachedDetailManager<C> {
+    fn drop(&mut self) {
+        self.shutdown();
+        if !self.restored {
+            if let Err(e) = write_levels(self.ctx.as_ref(), self.prior) {
+                log_warn!("failed to restore detail levels: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+
+    fn s(v: u8) -> Strength {
+        Strength::new(v).unwrap()
+    }
+
+    fn row(iso: u32, nr: u8, sharp: u8) -> DetailRow {
+        DetailRow {
+            iso,
+            nr_strength: s(nr),
+            sharpness: s(sharp),
+        }
+    }
+
+    fn levels(nr: u8, sharp: u8) -> DetailLevels {
+        DetailLevels {
+            nr_strength: s(nr),
+            sharpness: s(sharp),
+        }
+    }
+
+    #[test]
+    fn test_check_detail_table() {
+        assert!(check_detail_table(&[row(100, 20, 80)]).is_ok());
+        assert!(check_detail_table(&[row(100, 20, 80), row(800, 60, 40)]).is_ok());
+        assert!(check_detail_table(&[]).is_err());
+        assert!(check_detail_table(&[row(0, 20, 80)]).is_err());
+        assert!(check_detail_table(&[row(800, 60, 40), row(100, 20, 80)]).is_err());
+        assert!(check_detail_table(&[row(100, 20, 80), row(100, 60, 40)]).is_err());
+    }
+
+    #[test]
+    fn test_interpolate_detail() {
+        let table = [row(100, 20, 80), row(400, 60, 40), row(1600, 100, 0)];
+        assert_eq!(interpolate_detail(&table, 50), levels(20, 80));
+        assert_eq!(interpolate_detail(&table, 100), levels(20, 80));
+        // 对数域插值：ISO 200 位于 100 与 400 的正中。
+        assert_eq!(interpolate_detail(&table, 200), levels(40, 60));
+        assert_eq!(interpolate_detail(&table, 400), levels(60, 40));
+        assert_eq!(interpolate_detail(&table, 800), levels(80, 20));
+        assert_eq!(interpolate_detail(&table, 6400), levels(100, 0));
+        assert_eq!(interpolate_detail(&table[..1], 6400), levels(20, 80));
+    }
+
+    #[test]
+    fn test_iso_bucket() {
+        let stops = DEFAULT_BUCKET_STOPS;
+        assert_eq!(iso_bucket(100, stops), 0);
+        assert_eq!(iso_bucket(110, stops), 0);
+        assert_eq!(iso_bucket(125, stops), 1);
+        assert_eq!(iso_bucket(200, stops), 3);
+        assert_eq!(iso_bucket(50, 1.0), -1);
+        assert_eq!(iso_bucket(0, 1.0), iso_bucket(1, 1.0));
+        assert_eq!(bucket_iso(0, stops), 100);
+        assert_eq!(bucket_iso(3, stops), 200);
+        assert_eq!(bucket_iso(1, stops), 126);
+        assert_eq!(bucket_iso(-1, 1.0), 50);
+    }
+
+    #[test]
+    fn test_detail_tracker() {
+        assert!(DetailTracker::new(vec![row(100, 20, 80)], 0.0).is_err());
+        assert!(DetailTracker::new(vec![row(100, 20, 80)], f32::NAN).is_err());
+        assert!(DetailTracker::new(vec![], 1.0).is_err());
+
+        let table = vec![row(100, 20, 80), row(400, 60, 40)];
+        let mut tracker = DetailTracker::new(table, 1.0).unwrap();
+        assert_eq!(tracker.update(100), Some(levels(20, 80)));
+        // 同一分档内的波动不重复输出。
+        assert_eq!(tracker.update(120), None);
+        assert_eq!(tracker.update(90), None);
+        assert_eq!(tracker.update(190), Some(levels(40, 60)));
+        assert_eq!(tracker.update(210), None);
+        assert_eq!(tracker.update(400), Some(levels(60, 40)));
+        tracker.reset();
+        assert_eq!(tracker.update(400), Some(levels(60, 40)));
+    }
+
+    #[test]
+    fn test_read_write_levels() {
+        let ctx = MockContext::new();
+        write_levels(&ctx, levels(70, 30)).unwrap();
+        assert_eq!(read_levels(&ctx), Ok(levels(70, 30)));
+
+        // 一项写入失败时另一项仍然写入。
+        ctx.fail("set_anr_strength");
+        assert!(write_levels(&ctx, levels(10, 90)).is_err());
+        assert_eq!(ctx.state.borrow().sharpness, s(90));
+        assert_eq!(ctx.state.borrow().anr_strength, s(70));
+    }
+}
@@ -0,0 +1,208 @@
+//! 设备掉线
+//!
+//! USB、CSI 扩展板等可热插拔的传感器在运行中被拔出后，SDK 的每次调用都会失败，部分调用还会阻塞。
+//! 调用返回 `XCAM_RETURN_ERROR_IOCTL` 或 `XCAM_RETURN_ERROR_SENSOR` 时，检查媒体拓扑中是否
+//! 仍有该传感器；传感器已不存在时返回 `XCamError::DeviceLost` 并锁存掉线状态，此后经由
+//! `uapi_call!`、`uapi_get!` 的调用直接返回 `XCamError::DeviceLost`，不再调用 SDK。返回错误码的
+//! SDK 调用均经由这两个宏或 `Context::guard_device`；析构与 `Context::deinit` 在掉线后不再释放
+//! SDK 上下文。
+//! 传感器重新接入后由 `Context::reconnect` 重建上下文。
+//!
+//! 无法读取媒体节点时无法判断掉线，错误按原样返回。
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::media::list_media_nodes;
+use super::types::XCamResult;
+use std::io;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// 一个描述设备状态变化的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DeviceEvent {
+    /// 检测到传感器掉线。
+    Lost,
+    /// `Context::reconnect` 重建成功。
+    Reconnected,
+}
+
+type DeviceHook = Box<dyn Fn(DeviceEvent) + Send + Sync>;
+
+/// 是否为传感器掉线时 SDK 返回的错误代码。
+pub(crate) fn is_device_lost_code(e: &XCamError) -> bool {
+    matches!(
+        e,
+        XCamError::Code(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_IOCTL | ffi::XCamReturn::XCAM_RETURN_ERROR_SENSOR
+        )
+    )
+}
+
+/// 媒体拓扑中是否仍有传感器 `sns_ent_name`，无法获取拓扑时视为存在。
+///
+/// 拓扑经媒体控制器的 ioctl 读取，不启动外部进程，见 `media::list_media_nodes`。
+pub(crate) fn sensor_present(sns_ent_name: &str) -> bool {
+    match list_media_nodes() {
+        Ok(nodes) => nodes.iter().any(|n| n.sensor == sns_ent_name),
+        Err(_) => true,
+    }
+}
+
+/// 上下文的掉线状态及状态变化回调。
+#[derive(Default)]
+pub(crate) struct DeviceState {
+    lost: AtomicBool,
+    hook: Mutex<Option<DeviceHook>>,
+}
+
+impl DeviceState {
+    pub(crate) fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Acquire)
+    }
+
+    /// 调用 SDK 前检查，已掉线时返回 `XCamError::DeviceLost`。
+    pub(crate) fn check(&self) -> XCamResult<()> {
+        if self.is_lost() {
+            Err(XCamError::DeviceLost)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 处理 SDK 调用的错误。
+    ///
+    /// 错误代码可能由掉线引起且 `present` 报告传感器已不存在时锁存掉线状态，首次锁存时调用回调，
+    /// 并返回 `XCamError::DeviceLost`；否则原样返回错误。
+    pub(crate) fn on_error<P: FnOnce() -> bool>(&self, e: XCamError, present: P) -> XCamError {
+        if !is_device_lost_code(&e) || present() {
+            return e;
+        }
+        if !self.lost.swap(true, Ordering::AcqRel) {
+            self.notify(DeviceEvent::Lost);
+        }
+        XCamError::DeviceLost
+    }
+
+    pub(crate) fn set_hook(&self, hook: Option<DeviceHook>) {
+        *self.hook.lock().unwrap() = hook;
+    }
+
+    pub(crate) fn take_hook(&self) -> Option<DeviceHook> {
+        self.hook.lock().unwrap().take()
+    }
+
+    pub(crate) fn notify(&self, event: DeviceEvent) {
+        if let Some(hook) = self.hook.lock().unwrap().as_ref() {
+            hook(event);
+        }
+    }
+}
+
+impl Context {
+    /// 执行一次 SDK 调用：已掉线时直接返回 `XCamError::DeviceLost`，调用失败时检查是否掉线。
+    ///
+    /// 由 `from_raw_parts` 接管的上下文不知道传感器实体名称，不检查掉线。
+    pub(crate) fn guard_device<T, F>(&self, f: F) -> XCamResult<T>
+    where
+        F: FnOnce() -> XCamResult<T>,
+    {
+        self.device.check()?;
+        f().map_err(|e| {
+            self.device.on_error(e, || {
+                self.sns_ent_name.is_empty() || sensor_present(&self.sns_ent_name)
+            })
+        })
+    }
+
+    /// 是否已检测到传感器掉线。
+    pub fn device_lost(&self) -> bool {
+        self.device.is_lost()
+    }
+
+    /// 设置设备状态变化的回调，用于掉线时暂停采集循环，重建后恢复，替换已设置的回调。
+    ///
+    /// 回调在检测到掉线的调用所在线程中执行，不应在回调中调用本上下文的接口。
+    pub fn set_device_event_callback<F>(&self, f: F)
+    where
+        F: Fn(DeviceEvent) + Send + Sync + 'static,
+    {
+        self.device.set_hook(Some(Box::new(f)));
+    }
+
+    /// 清除设备状态变化的回调。
+    pub fn clear_device_event_callback(&self) {
+        self.device.set_hook(None);
+    }
+
+    /// 以创建时的传感器实体名称与 IQ 文件目录重建上下文。
+    ///
+    /// 先初始化新的 SDK 上下文，成功后才替换旧的上下文，失败时保持原状态，可在传感器
+    /// 重新接入后重试。旧上下文已掉线时不调用 SDK 释放（设备节点已移除），其内存不回收，
+    /// 见 `Context::deinit`。重建后处于未出流状态，需要重新 `prepare`、`start` 并应用参数；
+    /// 通过本库设置的其余状态（白平衡补偿、变焦标定等）恢复为初始值，状态变化回调保留，
+    /// 并以 `DeviceEvent::Reconnected` 调用。
+    ///
+    /// 由 `from_raw_parts` 接管的上下文无法重建，返回 `XCamError::Unsupported`。
+    pub fn reconnect(&mut self) -> XCamResult<()> {
+        if self.sns_ent_name.is_empty() {
+            return Err(XCamError::Unsupported);
+        }
+        let fresh =
+            Context::new(&self.sns_ent_name, &self.iq_file_dir).map_err(|e| match e.kind() {
+                io::ErrorKind::InvalidInput => {
+                    XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM)
+                }
+                _ => XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_SENSOR),
+            })?;
+        let hook = self.device.take_hook();
+        let stale = mem::replace(self, fresh);
+        // 掉线的上下文析构时跳过 `sysctl_deinit`，未掉线时正常释放。
+        drop(stale);
+        self.device.set_hook(hook);
+        self.device.notify(DeviceEvent::Reconnected);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_device_state() {
+        let ioctl = XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_IOCTL);
+        let param = XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM);
+        assert!(is_device_lost_code(&ioctl));
+        assert!(is_device_lost_code(&XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_SENSOR
+        )));
+        assert!(!is_device_lost_code(&param));
+        assert!(!is_device_lost_code(&XCamError::Timeout));
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let state = DeviceState::default();
+        let log = events.clone();
+        state.set_hook(Some(Box::new(move |e| log.lock().unwrap().push(e))));
+        assert_eq!(state.check(), Ok(()));
+
+        // 其他错误或传感器仍存在时不锁存。
+        assert_eq!(state.on_error(param, || false), param);
+        assert_eq!(state.on_error(ioctl, || true), ioctl);
+        assert!(!state.is_lost());
+
+        assert_eq!(state.on_error(ioctl, || false), XCamError::DeviceLost);
+        assert!(state.is_lost());
+        assert_eq!(state.check(), Err(XCamError::DeviceLost));
+        // 回调只在首次锁存时调用。
+        assert_eq!(state.on_error(ioctl, || false), XCamError::DeviceLost);
+        assert_eq!(*events.lock().unwrap(), [DeviceEvent::Lost]);
+
+        let hook = state.take_hook();
+        assert!(hook.is_some());
+        state.notify(DeviceEvent::Reconnected);
+        assert_eq!(events.lock().unwrap().len(), 1);
+    }
+}
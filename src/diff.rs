@@ -0,0 +1,399 @@
+//! 参数快照的比较
+//!
+//! 调优时在厂商工具中反复修改参数，画面改善后需要知道究竟改了哪些项。先以
+//! `CameraSettings::query` 记录基线，调整之后调用 `Context::diff_since` 读取当前值并与基线比较，
+//! 或以 `CameraSettings::diff` 比较两份快照。
+//!
+//! 结果按字段列出，路径与 `CameraSettings` 的序列化格式相同，形如 `adjust.saturation`；
+//! 未设置的分组视为其中各项均未设置。`Display` 每项一行，便于贴入工单；启用 `serde`
+//! 特性后可序列化，附在问题报告中。
+//!
+//! 浮点字段按各自的容差比较，差异在容差以内视为未改变：
+//!
+//! | 字段 | 容差 |
+//! |---|---|
+//! | `white_balance.gain` | 各通道 0.005 |
+//! | `exposure.gain_range` | 0.01 倍 |
+//! | `exposure.time_range` | 10 µs |
+//!
+//! 其余字段为整数或枚举，按值比较。
+use super::context::Context;
+use super::settings::{CameraSettings, GainSettings, RangeSettings};
+use super::types::{OpMode, Strength, XCamResult};
+use std::fmt;
+
+/// 白平衡各通道增益的容差。
+const GAIN_EPSILON: f32 = 0.005;
+/// 曝光增益范围的容差，单位：倍。
+const GAIN_RANGE_EPSILON: f32 = 0.01;
+/// 曝光时间范围的容差，单位：秒。
+const TIME_RANGE_EPSILON: f32 = 1e-5;
+
+/// 一个描述参数取值的枚举。
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum SettingValue {
+    Mode(OpMode),
+    Level(u32),
+    Strength(Strength),
+    Gain(GainSettings),
+    Range(RangeSettings),
+}
+
+impl fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mode(v) => write!(f, "{}", v),
+            Self::Level(v) => write!(f, "{}", v),
+            Self::Strength(v) => write!(f, "{}%", v),
+            Self::Gain(g) => write!(f, "r={} gr={} gb={} b={}", g.r, g.gr, g.gb, g.b),
+            Self::Range(r) => write!(f, "[{}, {}]", r.min, r.max),
+        }
+    }
+}
+
+/// 一个描述单个字段变化的类型。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldChange {
+    /// 字段路径，形如 `adjust.saturation`。
+    pub path: String,
+    /// 原值，`None` 表示未设置。
+    pub old: Option<SettingValue>,
+    /// 新值，`None` 表示未设置。
+    pub new: Option<SettingValue>,
+}
+
+/// 形如 `adjust.saturation: 50% -> 65%`。
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |v: &Option<SettingValue>| match v {
+            Some(v) => v.to_string(),
+            None => "unset".to_owned(),
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path,
+            show(&self.old),
+            show(&self.new)
+        )
+    }
+}
+
+/// 一个描述两份参数快照之间差异的类型。
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SettingsDiff {
+    /// 改变的字段，按 `CameraSettings` 中的字段顺序排列。
+    pub changes: Vec<FieldChange>,
+}
+
+impl SettingsDiff {
+    /// 两份快照是否一致。
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// 字段 `path` 的变化，未改变时为 `None`。
+    pub fn get(&self, path: &str) -> Option<&FieldChange> {
+        self.changes.iter().find(|c| c.path == path)
+    }
+
+    /// 比较一个字段，取值不同时记录。
+    fn field<T: Copy>(
+        &mut self,
+        path: &str,
+        old: Option<T>,
+        new: Option<T>,
+        same: impl Fn(T, T) -> bool,
+        value: impl Fn(T) -> SettingValue,
+    ) {
+        let changed = match (old, new) {
+            (Some(a), Some(b)) => !same(a, b),
+            (None, None) => false,
+            _ => true,
+        };
+        if changed {
+            self.changes.push(FieldChange {
+                path: path.to_owned(),
+                old: old.map(&value),
+                new: new.map(&value),
+            });
+        }
+    }
+}
+
+/// 每项一行，没有差异时输出 `no changes`。
+impl fmt::Display for SettingsDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+fn near(a: f32, b: f32, epsilon: f32) -> bool {
+    (a - b).abs() <= epsilon
+}
+
+fn same_gain(a: GainSettings, b: GainSettings) -> bool {
+    near(a.r, b.r, GAIN_EPSILON)
+        && near(a.gr, b.gr, GAIN_EPSILON)
+        && near(a.gb, b.gb, GAIN_EPSILON)
+        && near(a.b, b.b, GAIN_EPSILON)
+}
+
+fn same_range(epsilon: f32) -> impl Fn(RangeSettings, RangeSettings) -> bool {
+    move |a, b| near(a.min, b.min, epsilon) && near(a.max, b.max, epsilon)
+}
+
+fn eq<T: PartialEq>(a: T, b: T) -> bool {
+    a == b
+}
+
+impl CameraSettings {
+    /// 比较本快照（原值）与 `other`（新值），列出改变的字段，见模块说明。
+    ///
+    /// 不比较 `schema_version`。
+    pub fn diff(&self, other: &CameraSettings) -> SettingsDiff {
+        let mut d = SettingsDiff::default();
+
+        let (a, b) = (
+            self.white_balance.unwrap_or_default(),
+            other.white_balance.unwrap_or_default(),
+        );
+        d.field("white_balance.mode", a.mode, b.mode, eq, SettingValue::Mode);
+        d.field("white_balance.ct", a.ct, b.ct, eq, SettingValue::Level);
+        d.field(
+            "white_balance.gain",
+            a.gain,
+            b.gain,
+            same_gain,
+            SettingValue::Gain,
+        );
+
+        let (a, b) = (
+            self.exposure.unwrap_or_default(),
+            other.exposure.unwrap_or_default(),
+        );
+        d.field("exposure.mode", a.mode, b.mode, eq, SettingValue::Mode);
+        d.field(
+            "exposure.gain_range",
+            a.gain_range,
+            b.gain_range,
+            same_range(GAIN_RANGE_EPSILON),
+            SettingValue::Range,
+        );
+        d.field(
+            "exposure.time_range",
+            a.time_range,
+            b.time_range,
+            same_range(TIME_RANGE_EPSILON),
+            SettingValue::Range,
+        );
+
+        let (a, b) = (
+            self.adjust.unwrap_or_default(),
+            other.adjust.unwrap_or_default(),
+        );
+        d.field(
+            "adjust.brightness",
+            a.brightness,
+            b.brightness,
+            eq,
+            SettingValue::Level,
+        );
+        d.field(
+            "adjust.contrast",
+            a.contrast,
+            b.contrast,
+            eq,
+            SettingValue::Level,
+        );
+        d.field(
+            "adjust.saturation",
+            a.saturation,
+            b.saturation,
+            eq,
+            SettingValue::Strength,
+        );
+        d.field("adjust.hue", a.hue, b.hue, eq, SettingValue::Level);
+        d.field(
+            "adjust.sharpness",
+            a.sharpness,
+            b.sharpness,
+            eq,
+            SettingValue::Strength,
+        );
+
+        let (a, b) = (
+            self.noise_reduction.unwrap_or_default(),
+            other.noise_reduction.unwrap_or_default(),
+        );
+        d.field(
+            "noise_reduction.mode",
+            a.mode,
+            b.mode,
+            eq,
+            SettingValue::Mode,
+        );
+        d.field(
+            "noise_reduction.strength",
+            a.strength,
+            b.strength,
+            eq,
+            SettingValue::Strength,
+        );
+        d.field(
+            "noise_reduction.spatial",
+            a.spatial,
+            b.spatial,
+            eq,
+            SettingValue::Strength,
+        );
+        d.field(
+            "noise_reduction.temporal",
+            a.temporal,
+            b.temporal,
+            eq,
+            SettingValue::Strength,
+        );
+        d
+    }
+}
+
+impl Context {
+    /// 读取各项的当前值，与基线 `baseline` 比较，见 `CameraSettings::query` 与 `CameraSettings::diff`。
+    pub fn diff_since(&self, baseline: &CameraSettings) -> XCamResult<SettingsDiff> {
+        Ok(baseline.diff(&CameraSettings::query(self)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+    use crate::settings::{AdjustSettings, ExposureSettings, WhiteBalanceSettings};
+
+    fn strength(val: u8) -> Strength {
+        Strength::new(val).unwrap()
+    }
+
+    #[test]
+    fn test_settings_diff() {
+        let old = CameraSettings {
+            white_balance: Some(WhiteBalanceSettings {
+                mode: Some(OpMode::Manual),
+                ct: None,
+                gain: Some(GainSettings {
+                    r: 1.8,
+                    gr: 1.0,
+                    gb: 1.0,
+                    b: 1.6,
+                }),
+            }),
+            exposure: Some(ExposureSettings {
+                mode: Some(OpMode::Auto),
+                gain_range: Some(RangeSettings {
+                    min: 1.0,
+                    max: 16.0,
+                }),
+                time_range: Some(RangeSettings {
+                    min: 0.0001,
+                    max: 0.033,
+                }),
+            }),
+            adjust: Some(AdjustSettings {
+                saturation: Some(strength(50)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(old.diff(&old).is_empty());
+        assert_eq!(old.diff(&old).to_string(), "no changes\n");
+
+        let mut new = old.clone();
+        // 容差以内的浮点差异不算改变。
+        new.white_balance.as_mut().unwrap().gain.as_mut().unwrap().r = 1.802;
+        new.exposure.as_mut().unwrap().time_range = Some(RangeSettings {
+            min: 0.000105,
+            max: 0.033,
+        });
+        assert!(old.diff(&new).is_empty());
+
+        new.white_balance.as_mut().unwrap().ct = Some(5000);
+        new.exposure.as_mut().unwrap().gain_range = Some(RangeSettings {
+            min: 1.0,
+            max: 32.0,
+        });
+        new.adjust.as_mut().unwrap().saturation = Some(strength(65));
+        new.noise_reduction = Some(Default::default());
+        let diff = old.diff(&new);
+        assert_eq!(diff.changes.len(), 3);
+        assert_eq!(
+            diff.get("white_balance.ct"),
+            Some(&FieldChange {
+                path: "white_balance.ct".to_owned(),
+                old: None,
+                new: Some(SettingValue::Level(5000)),
+            })
+        );
+        assert_eq!(
+            diff.to_string(),
+            "white_balance.ct: unset -> 5000\n\
+             exposure.gain_range: [1, 16] -> [1, 32]\n\
+             adjust.saturation: 50% -> 65%\n"
+        );
+        // 分组缺失时各项均视为未设置。
+        let diff = new.diff(&CameraSettings::default());
+        assert_eq!(
+            diff.get("exposure.mode").unwrap().old,
+            Some(SettingValue::Mode(OpMode::Auto))
+        );
+        assert!(diff.get("noise_reduction.mode").is_none());
+    }
+
+    #[test]
+    fn test_query_diff() {
+        let ctx = MockContext::new();
+        let baseline = CameraSettings::query(&ctx).unwrap();
+        assert!(baseline
+            .diff(&CameraSettings::query(&ctx).unwrap())
+            .is_empty());
+
+        ctx.state.borrow_mut().sharpness = strength(80);
+        let diff = baseline.diff(&CameraSettings::query(&ctx).unwrap());
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(
+            diff.get("adjust.sharpness").unwrap().new,
+            Some(SettingValue::Strength(strength(80)))
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_settings_diff_json() {
+        let diff = SettingsDiff {
+            changes: vec![FieldChange {
+                path: "noise_reduction.mode".to_owned(),
+                old: Some(SettingValue::Mode(OpMode::Auto)),
+                new: None,
+            }],
+        };
+        let text = serde_json::to_string(&diff).unwrap();
+        assert_eq!(
+            text,
+            r#"{"changes":[{"path":"noise_reduction.mode","old":{"mode":"auto"},"new":null}]}"#
+        );
+        assert_eq!(serde_json::from_str::<SettingsDiff>(&text).unwrap(), diff);
+    }
+}
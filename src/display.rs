@@ -0,0 +1,186 @@
+//! 文本表示
+//!
+//! 为本库的枚举生成 `Display` 与 `FromStr`，两者使用同一组小写下划线名称，
+//! 与配置文件中的写法一致；解析时不区分大小写，并接受 `-` 或空格代替下划线。
+//! 输出不依赖区域设置，小数点总是 `.`。
+//!
+//! SDK 类型不能直接实现这些特性，通过 `Readable` 包装后使用：
+//!
+//! ```ignore
+//! println!("{}", Readable(ctx.get_mwb_gain()?)); // R1.83 Gr1.00 Gb1.00 B2.10
+//! let scene: Readable<WbScene> = "daylight".parse()?;
+//! ```
+use super::ffi;
+use super::types::WbGain;
+use std::fmt;
+use std::str::FromStr;
+
+/// 一个描述枚举名称无法识别的错误类型。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseEnumError {
+    /// 目标类型的名称。
+    pub kind: &'static str,
+    /// 无法识别的输入。
+    pub input: String,
+}
+
+impl fmt::Display for ParseEnumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unrecognized {} `{}`", self.kind, self.input)
+    }
+}
+
+impl std::error::Error for ParseEnumError {}
+
+/// 将输入规整为小写下划线形式。
+pub(crate) fn normalize_name(s: &str) -> String {
+    s.trim()
+        .chars()
+        .map(|c| match c {
+            '-' | ' ' => '_',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+/// 为枚举生成 `Display` 与 `FromStr`。
+///
+/// 本库的枚举写作 `enum_str!(类型, "描述" { 变体 => "名称", ... });`；
+/// SDK 枚举写作 `enum_str!(Readable<SDK 类型路径>, "描述" { 变体 => "名称", ... });`。
+macro_rules! enum_str {
+    (Readable<$($seg:ident)::+>, $kind:literal { $( $(#[$meta:meta])* $variant:ident => $name:literal ),+ $(,)? }) => {
+        impl std::fmt::Display for $crate::display::Readable<$($seg)::+> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.pad(match self.0 {
+                    $( $(#[$meta])* $($seg)::+::$variant => $name, )+
+                })
+            }
+        }
+
+        impl std::str::FromStr for $crate::display::Readable<$($seg)::+> {
+            type Err = $crate::display::ParseEnumError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match $crate::display::normalize_name(s).as_str() {
+                    $( $(#[$meta])* $name => Ok(Self($($seg)::+::$variant)), )+
+                    _ => Err($crate::display::ParseEnumError {
+                        kind: $kind,
+                        input: s.to_owned(),
+                    }),
+                }
+            }
+        }
+    };
+    ($ty:ty, $kind:literal { $( $(#[$meta:meta])* $variant:ident => $name:literal ),+ $(,)? }) => {
+        impl std::fmt::Display for $ty {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.pad(match self {
+                    $( $(#[$meta])* Self::$variant => $name, )+
+                })
+            }
+        }
+
+        impl std::str::FromStr for $ty {
+            type Err = $crate::display::ParseEnumError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match $crate::display::normalize_name(s).as_str() {
+                    $( $(#[$meta])* $name => Ok(Self::$variant), )+
+                    _ => Err($crate::display::ParseEnumError {
+                        kind: $kind,
+                        input: s.to_owned(),
+                    }),
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use enum_str;
+
+/// 一个为 SDK 类型提供可读文本表示的包装类型。
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Readable<T>(pub T);
+
+/// 输出形如 `R1.83 Gr1.00 Gb1.00 B2.10`。
+impl fmt::Display for Readable<WbGain> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let g = &self.0;
+        write!(
+            f,
+            "R{:.2} Gr{:.2} Gb{:.2} B{:.2}",
+            g.rgain, g.grgain, g.gbgain, g.bgain
+        )
+    }
+}
+
+enum_str!(Readable<ffi::rk_aiq_wb_scene_t>, "white balance scene" {
+    RK_AIQ_WBCT_INCANDESCENT => "incandescent",
+    RK_AIQ_WBCT_FLUORESCENT => "fluorescent",
+    RK_AIQ_WBCT_WARM_FLUORESCENT => "warm_fluorescent",
+    RK_AIQ_WBCT_DAYLIGHT => "daylight",
+    RK_AIQ_WBCT_CLOUDY_DAYLIGHT => "cloudy_daylight",
+    RK_AIQ_WBCT_TWILIGHT => "twilight",
+    RK_AIQ_WBCT_SHADE => "shade",
+});
+
+enum_str!(Readable<ffi::antiFlickerMode_t>, "anti-flicker mode" {
+    ANTIFLICKER_NORMAL_MODE => "normal",
+    ANTIFLICKER_AUTO_MODE => "auto",
+});
+
+enum_str!(Readable<ffi::expPwrLineFreq_t>, "power line frequency" {
+    EXP_PWR_LINE_FREQ_DIS => "disabled",
+    EXP_PWR_LINE_FREQ_50HZ => "50hz",
+    EXP_PWR_LINE_FREQ_60HZ => "60hz",
+});
+
+impl<T> From<T> for Readable<T> {
+    fn from(val: T) -> Self {
+        Self(val)
+    }
+}
+
+/// 解析 SDK 枚举的名称。
+pub fn parse_sdk_enum<T>(s: &str) -> Result<T, ParseEnumError>
+where
+    Readable<T>: FromStr<Err = ParseEnumError>,
+{
+    s.parse::<Readable<T>>().map(|r| r.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{AntiFlickerMode, ExpPwrLineFreq, WbScene};
+
+    #[test]
+    fn test_readable_wb_gain() {
+        let gain = WbGain {
+            rgain: 1.834,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 2.1,
+        };
+        assert_eq!(Readable(gain).to_string(), "R1.83 Gr1.00 Gb1.00 B2.10");
+    }
+
+    #[test]
+    fn test_sdk_enum_parse() {
+        use ffi::rk_aiq_wb_scene_t::*;
+        let scene: WbScene = parse_sdk_enum("Cloudy-Daylight").unwrap();
+        assert_eq!(scene, RK_AIQ_WBCT_CLOUDY_DAYLIGHT);
+        assert_eq!(Readable(scene).to_string(), "cloudy_daylight");
+        assert_eq!(
+            parse_sdk_enum::<ExpPwrLineFreq>("50HZ"),
+            Ok(ffi::expPwrLineFreq_t::EXP_PWR_LINE_FREQ_50HZ)
+        );
+        assert_eq!(
+            parse_sdk_enum::<AntiFlickerMode>("off"),
+            Err(ParseEnumError {
+                kind: "anti-flicker mode",
+                input: "off".to_owned(),
+            })
+        );
+    }
+}
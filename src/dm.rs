@@ -0,0 +1,127 @@
+//! 去马赛克
+//!
+//! 传感器每个像素只采集一种颜色，去马赛克（Debayer）沿边缘方向插值出其余两种颜色。
+//! 织物、百叶窗等接近奈奎斯特频率的重复纹理会使方向判断失误，插值出原本不存在的彩色条纹（伪彩）。
+//! 本模块以 [0,100] 的等级调节 SDK 去马赛克属性中的两个阈值，等级按比例换算为 8 位阈值：
+//!
+//! | 接口 | 属性 |
+//! | --- | --- |
+//! | `set_demosaic_edge_threshold` | `high_freq_thresh` |
+//! | `set_demosaic_false_color_suppress` | `low_freq_thresh` |
+//!
+//! 两者都是细节与伪影之间的取舍：边缘阈值越低，越多像素按边缘方向插值，细节越锐利，
+//! 但细密纹理上更容易出现迷宫状伪影；伪彩抑制越强，重复纹理上的彩色条纹越少，
+//! 但细小的彩色细节（例如彩色文字的笔画）会随之褪色。IQ 文件中的标定值通常已是折中，
+//! 只在特定场景出现伪彩时再调整。
+use super::context::Context;
+use super::ffi;
+use super::shared::SharedContext;
+use super::types::XCamResult;
+use super::uapi::{uapi_call, uapi_get};
+
+/// 去马赛克等级的最大值。
+pub const DEMOSAIC_LEVEL_MAX: u8 = 100;
+
+type DebayerAttr = ffi::adebayer_attrib_t;
+
+/// 将等级换算为 8 位阈值，超出 [0,100] 时截断。
+pub(crate) fn level_to_thresh(level: u8) -> u8 {
+    let level = u32::from(level.min(DEMOSAIC_LEVEL_MAX));
+    ((level * 255 + 50) / 100) as u8
+}
+
+/// 将 8 位阈值换算为等级。
+pub(crate) fn thresh_to_level(thresh: u8) -> u8 {
+    ((u32::from(thresh) * 100 + 127) / 255) as u8
+}
+
+/// 一个描述去马赛克控制的契定。
+pub trait Demosaic {
+    /// 获取伪彩抑制等级，范围：[0,100]。
+    fn get_demosaic_false_color_suppress(&self) -> XCamResult<u8>;
+
+    /// 设置伪彩抑制等级，超出 [0,100] 时截断，取舍见模块说明。
+    fn set_demosaic_false_color_suppress(&self, level: u8) -> XCamResult<()>;
+
+    /// 获取边缘阈值等级，范围：[0,100]。
+    fn get_demosaic_edge_threshold(&self) -> XCamResult<u8>;
+
+    /// 设置边缘阈值等级，超出 [0,100] 时截断，取舍见模块说明。
+    fn set_demosaic_edge_threshold(&self, level: u8) -> XCamResult<()>;
+}
+
+impl Demosaic for Context {
+    fn get_demosaic_false_color_suppress(&self) -> XCamResult<u8> {
+        let attr = uapi_get!(ffi::rk_aiq_user_api2_adebayer_GetAttrib(self) -> DebayerAttr)?;
+        Ok(thresh_to_level(attr.low_freq_thresh))
+    }
+
+    fn set_demosaic_false_color_suppress(&self, level: u8) -> XCamResult<()> {
+        let mut attr = uapi_get!(ffi::rk_aiq_user_api2_adebayer_GetAttrib(self) -> DebayerAttr)?;
+        attr.low_freq_thresh = level_to_thresh(level);
+        uapi_call!(ffi::rk_aiq_user_api2_adebayer_SetAttrib(self, attr))
+    }
+
+    fn get_demosaic_edge_threshold(&self) -> XCamResult<u8> {
+        let attr = uapi_get!(ffi::rk_aiq_user_api2_adebayer_GetAttrib(self) -> DebayerAttr)?;
+        Ok(thresh_to_level(attr.high_freq_thresh))
+    }
+
+    fn set_demosaic_edge_threshold(&self, level: u8) -> XCamResult<()> {
+        let mut attr = uapi_get!(ffi::rk_aiq_user_api2_adebayer_GetAttrib(self) -> DebayerAttr)?;
+        attr.high_freq_thresh = level_to_thresh(level);
+        uapi_call!(ffi::rk_aiq_user_api2_adebayer_SetAttrib(self, attr))
+    }
+}
+
+impl Demosaic for SharedContext {
+    fn get_demosaic_false_color_suppress(&self) -> XCamResult<u8> {
+        self.inner.get_demosaic_false_color_suppress()
+    }
+
+    fn set_demosaic_false_color_suppress(&self, level: u8) -> XCamResult<()> {
+        self.inner.set_demosaic_false_color_suppress(level)
+    }
+
+    fn get_demosaic_edge_threshold(&self) -> XCamResult<u8> {
+        self.inner.get_demosaic_edge_threshold()
+    }
+
+    fn set_demosaic_edge_threshold(&self, level: u8) -> XCamResult<()> {
+        self.inner.set_demosaic_edge_threshold(level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demosaic_level_round_trip() {
+        for level in 0..=DEMOSAIC_LEVEL_MAX {
+            assert_eq!(thresh_to_level(level_to_thresh(level)), level);
+        }
+        assert_eq!(level_to_thresh(0), 0);
+        assert_eq!(level_to_thresh(50), 128);
+        assert_eq!(thresh_to_level(u8::MAX), DEMOSAIC_LEVEL_MAX);
+    }
+
+    #[test]
+    fn test_demosaic_false_color_suppress_clamp() {
+        for level in [101, 200, u8::MAX] {
+            assert_eq!(level_to_thresh(level), u8::MAX);
+        }
+        let mut attr = DebayerAttr::default();
+        attr.low_freq_thresh = level_to_thresh(150);
+        assert_eq!(thresh_to_level(attr.low_freq_thresh), DEMOSAIC_LEVEL_MAX);
+    }
+
+    #[test]
+    fn test_demosaic_edge_threshold_clamp() {
+        let mut attr = DebayerAttr::default();
+        attr.high_freq_thresh = level_to_thresh(101);
+        assert_eq!(thresh_to_level(attr.high_freq_thresh), DEMOSAIC_LEVEL_MAX);
+        attr.high_freq_thresh = level_to_thresh(30);
+        assert_eq!(thresh_to_level(attr.high_freq_thresh), 30);
+    }
+}
@@ -0,0 +1,529 @@
+//! 环境检查
+//!
+//! 初次使用时最常见的问题是 IQ 文件目录错误、传感器实体名称错误以及 SDK 版本与编译特性
+//! 不一致。`doctor` 依次执行下列检查，逐项报告通过与否及修正建议：
+//!
+//! | 检查 | 内容 |
+//! | --- | --- |
+//! | `Sensors` | 通过 `media::list_media_nodes` 列出 ISP 媒体节点上的传感器 |
+//! | `IqFiles` | 每个传感器在 IQ 文件目录中是否有 SDK 将要加载的 IQ 文件 |
+//! | `SdkVersion` | IQ 文件记录的 SDK 版本与编译的版本特性是否一致 |
+//! | `DryRun` | 以每个传感器初始化 SDK 后立即释放 |
+//!
+//! SDK 没有初始化之前可用的版本查询接口，IQ 文件随 SDK 库一同发布并记录生成它的 SDK 版本，
+//! 因此以其主版本号代表库的版本；文件中没有记录版本时该项跳过。
+//!
+//! 每项检查都可通过 `DoctorOptions::skip` 跳过。试初始化成功时上下文在返回前释放，
+//! 失败时 SDK 不会返回上下文，检查结束后不会遗留初始化了一半的上下文。
+use super::calib::{self, CalibError, CalibFile};
+use super::context::Context;
+use super::dump::sdk_version;
+use super::error::XCamError;
+use super::media;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// 默认的 IQ 文件目录。
+pub const DEFAULT_IQ_DIR: &str = "/etc/iqfiles";
+
+/// 一个描述检查项的枚举，见模块说明。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DoctorCheck {
+    Sensors,
+    IqFiles,
+    SdkVersion,
+    DryRun,
+}
+
+impl DoctorCheck {
+    /// 全部检查项，按执行顺序排列。
+    pub const ALL: [Self; 4] = [Self::Sensors, Self::IqFiles, Self::SdkVersion, Self::DryRun];
+
+    /// 检查项的名称，例如 `iq-files`。
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sensors => "sensors",
+            Self::IqFiles => "iq-files",
+            Self::SdkVersion => "sdk-version",
+            Self::DryRun => "dry-run",
+        }
+    }
+
+    /// 按 `name` 返回的名称查找检查项。
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+}
+
+impl fmt::Display for DoctorCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// 一个描述检查结果的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// 被选项跳过，或缺少执行该检查的前提。
+    Skipped,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Pass => "PASS",
+            Self::Fail => "FAIL",
+            Self::Skipped => "SKIP",
+        })
+    }
+}
+
+/// 一项检查的结果。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckResult {
+    pub check: DoctorCheck,
+    /// 检查针对的传感器实体名称，`Sensors` 检查为 `None`。
+    pub sensor: Option<String>,
+    pub status: CheckStatus,
+    /// 检查得到的信息或失败原因。
+    pub detail: String,
+    /// 失败时的修正建议。
+    pub remedy: Option<String>,
+}
+
+impl CheckResult {
+    fn new(check: DoctorCheck, sensor: Option<&str>, status: CheckStatus, detail: String) -> Self {
+        Self {
+            check,
+            sensor: sensor.map(str::to_owned),
+            status,
+            detail,
+            remedy: None,
+        }
+    }
+
+    fn pass(check: DoctorCheck, sensor: Option<&str>, detail: String) -> Self {
+        Self::new(check, sensor, CheckStatus::Pass, detail)
+    }
+
+    fn skipped(check: DoctorCheck, sensor: Option<&str>, detail: String) -> Self {
+        Self::new(check, sensor, CheckStatus::Skipped, detail)
+    }
+
+    fn fail(check: DoctorCheck, sensor: Option<&str>, detail: String, remedy: String) -> Self {
+        Self {
+            remedy: Some(remedy),
+            ..Self::new(check, sensor, CheckStatus::Fail, detail)
+        }
+    }
+}
+
+/// 输出形如 `[FAIL] iq-files (m00_b_ov5695 4-0036-1): ...`，有修正建议时另起一行。
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.status, self.check)?;
+        if let Some(sensor) = &self.sensor {
+            write!(f, " ({})", sensor)?;
+        }
+        write!(f, ": {}", self.detail)?;
+        if let Some(remedy) = &self.remedy {
+            write!(f, "\n       fix: {}", remedy)?;
+        }
+        Ok(())
+    }
+}
+
+/// `doctor` 的检查报告。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DoctorReport {
+    /// 按执行顺序排列的结果，每个传感器的检查各占一项。
+    pub results: Vec<CheckResult>,
+}
+
+impl DoctorReport {
+    /// 是否没有失败的检查。
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// 失败的检查。
+    pub fn failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.results
+            .iter()
+            .filter(|r| r.status == CheckStatus::Fail)
+    }
+}
+
+/// 每项结果一行。
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            writeln!(f, "{}", result)?;
+        }
+        Ok(())
+    }
+}
+
+/// `doctor_with` 的选项。
+#[derive(Clone, Debug)]
+pub struct DoctorOptions {
+    iq_file_dir: PathBuf,
+    sensors: Vec<String>,
+    skip: Vec<DoctorCheck>,
+}
+
+impl DoctorOptions {
+    /// 使用 `DEFAULT_IQ_DIR`，检查枚举到的全部传感器，不跳过任何检查。
+    pub fn new() -> Self {
+        Self {
+            iq_file_dir: PathBuf::from(DEFAULT_IQ_DIR),
+            sensors: Vec::new(),
+            skip: Vec::new(),
+        }
+    }
+
+    /// IQ 文件目录。
+    pub fn iq_file_dir<P: Into<PathBuf>>(mut self, val: P) -> Self {
+        self.iq_file_dir = val.into();
+        self
+    }
+
+    /// 检查指定的传感器实体名称，可多次调用。
+    ///
+    /// 指定后其余检查只针对这些传感器；`Sensors` 检查仍列出枚举结果，跳过时也不影响其余检查。
+    pub fn sensor<T: Into<String>>(mut self, val: T) -> Self {
+        self.sensors.push(val.into());
+        self
+    }
+
+    /// 跳过检查项 `check`，可多次调用。
+    ///
+    /// 未指定传感器时跳过 `Sensors` 会使针对传感器的检查因没有传感器而跳过。
+    pub fn skip(mut self, check: DoctorCheck) -> Self {
+        self.skip.push(check);
+        self
+    }
+
+    fn skips(&self, check: DoctorCheck) -> bool {
+        self.skip.contains(&check)
+    }
+}
+
+impl Default for DoctorOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 以默认选项执行全部检查，见模块说明。
+pub fn doctor() -> DoctorReport {
+    doctor_with(&DoctorOptions::new())
+}
+
+/// 按 `opts` 执行检查，见模块说明。
+pub fn doctor_with(opts: &DoctorOptions) -> DoctorReport {
+    let mut results = Vec::new();
+    let mut sensors = opts.sensors.clone();
+    if opts.skips(DoctorCheck::Sensors) {
+        results.push(CheckResult::skipped(
+            DoctorCheck::Sensors,
+            None,
+            "skipped".to_owned(),
+        ));
+    } else {
+        let (result, found) = check_sensors(media::list_media_nodes());
+        results.push(result);
+        if sensors.is_empty() {
+            sensors = found;
+        }
+    }
+    if sensors.is_empty() {
+        for check in &DoctorCheck::ALL[1..] {
+            results.push(CheckResult::skipped(
+                *check,
+                None,
+                "no sensor to check".to_owned(),
+            ));
+        }
+    }
+
+    let dir = opts.iq_file_dir.as_path();
+    for sensor in &sensors {
+        let sensor = sensor.as_str();
+        let file = calib::locate_calib_file(dir, sensor).ok();
+        let iq_ok = if opts.skips(DoctorCheck::IqFiles) {
+            results.push(CheckResult::skipped(
+                DoctorCheck::IqFiles,
+                Some(sensor),
+                "skipped".to_owned(),
+            ));
+            file.is_some()
+        } else {
+            let result = check_iq_files(dir, sensor);
+            let ok = result.status == CheckStatus::Pass;
+            results.push(result);
+            ok
+        };
+
+        results.push(if opts.skips(DoctorCheck::SdkVersion) {
+            CheckResult::skipped(DoctorCheck::SdkVersion, Some(sensor), "skipped".to_owned())
+        } else {
+            match file.and_then(|p| CalibFile::open(p).ok()) {
+                Some(calib) => check_sdk_version(sensor, &calib),
+                None => CheckResult::skipped(
+                    DoctorCheck::SdkVersion,
+                    Some(sensor),
+                    "no readable IQ file".to_owned(),
+                ),
+            }
+        });
+
+        results.push(if opts.skips(DoctorCheck::DryRun) {
+            CheckResult::skipped(DoctorCheck::DryRun, Some(sensor), "skipped".to_owned())
+        } else if !iq_ok {
+            CheckResult::skipped(
+                DoctorCheck::DryRun,
+                Some(sensor),
+                "no IQ file to initialize with".to_owned(),
+            )
+        } else {
+            dry_run(dir, sensor)
+        });
+    }
+    DoctorReport { results }
+}
+
+/// 由 `list_media_nodes` 的结果生成 `Sensors` 检查的结果及传感器实体名称。
+pub(crate) fn check_sensors(
+    nodes: Result<Vec<media::MediaNode>, XCamError>,
+) -> (CheckResult, Vec<String>) {
+    let check = DoctorCheck::Sensors;
+    match nodes {
+        Ok(nodes) if !nodes.is_empty() => {
+            let list: Vec<_> = nodes
+                .iter()
+                .map(|n| format!("`{}` on {}", n.sensor, n.path))
+                .collect();
+            let detail = format!("found {}", list.join(", "));
+            let names = nodes.into_iter().map(|n| n.sensor).collect();
+            (CheckResult::pass(check, None, detail), names)
+        }
+        Ok(_) => (
+            CheckResult::fail(
+                check,
+                None,
+                "no sensor on any ISP media node".to_owned(),
+                "check that the sensor driver probed (dmesg) and is linked to an ISP in the device tree"
+                    .to_owned(),
+            ),
+            Vec::new(),
+        ),
+        Err(e) => (
+            CheckResult::fail(
+                check,
+                None,
+                format!("cannot list media nodes: {}", e),
+                "check that /dev is readable".to_owned(),
+            ),
+            Vec::new(),
+        ),
+    }
+}
+
+/// `IqFiles` 检查：`dir` 中是否有 SDK 为传感器 `sensor` 加载的 IQ 文件，见 `calib::locate_calib_file`。
+pub(crate) fn check_iq_files(dir: &Path, sensor: &str) -> CheckResult {
+    let check = DoctorCheck::IqFiles;
+    match calib::locate_calib_file(dir, sensor) {
+        Ok(path) => CheckResult::pass(
+            check,
+            Some(sensor),
+            format!("found {}", path.file_name().unwrap_or_default().to_string_lossy()),
+        ),
+        Err(CalibError::Missing { expected_names, .. }) => CheckResult::fail(
+            check,
+            Some(sensor),
+            format!("no IQ file in {}", dir.display()),
+            format!(
+                "copy the IQ file for this sensor into {} as {}, or pass the directory that holds it",
+                dir.display(),
+                expected_names.join(" or ")
+            ),
+        ),
+        Err(e) => CheckResult::fail(
+            check,
+            Some(sensor),
+            format!("cannot read {}: {}", dir.display(), e),
+            "pass the directory that holds the IQ files".to_owned(),
+        ),
+    }
+}
+
+/// 从 `v5.0.3` 形式的版本字符串中取出主版本号。
+fn major_version(text: &str) -> Option<u32> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix('v')
+        .or_else(|| text.strip_prefix('V'))
+        .unwrap_or(text);
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    text[..end].parse().ok()
+}
+
+/// `SdkVersion` 检查：IQ 文件记录的主版本号是否与编译的版本特性一致。
+pub(crate) fn check_sdk_version(sensor: &str, calib: &CalibFile) -> CheckResult {
+    let check = DoctorCheck::SdkVersion;
+    let compiled = sdk_version();
+    let Some(version) = calib.sdk_version() else {
+        return CheckResult::skipped(
+            check,
+            Some(sensor),
+            format!("{} records no SDK version", calib.path().display()),
+        );
+    };
+    let detail = format!(
+        "{} is from SDK {}, compiled for {}",
+        calib.path().display(),
+        version.trim(),
+        compiled
+    );
+    match major_version(version) {
+        Some(major) if major_version(compiled) == Some(major) => {
+            CheckResult::pass(check, Some(sensor), detail)
+        }
+        Some(major) => CheckResult::fail(
+            check,
+            Some(sensor),
+            detail,
+            format!(
+                "build with `--no-default-features --features v{}_0,<isp_hw_*>`, or use IQ files from the installed SDK",
+                major
+            ),
+        ),
+        None => CheckResult::skipped(
+            check,
+            Some(sensor),
+            format!("{}: unrecognized version", detail),
+        ),
+    }
+}
+
+/// `DryRun` 检查：以传感器初始化 SDK 后立即释放。
+fn dry_run(dir: &Path, sensor: &str) -> CheckResult {
+    let check = DoctorCheck::DryRun;
+    let Some(dir_str) = dir.to_str() else {
+        return CheckResult::fail(
+            check,
+            Some(sensor),
+            format!("{} is not valid UTF-8", dir.display()),
+            "move the IQ files to a directory with a UTF-8 path".to_owned(),
+        );
+    };
+    match Context::new(sensor, dir_str) {
+        Ok(ctx) => {
+            drop(ctx);
+            CheckResult::pass(check, Some(sensor), "initialized and released".to_owned())
+        }
+        Err(e) => CheckResult::fail(
+            check,
+            Some(sensor),
+            format!("SDK initialization failed: {}", e),
+            "check the sensor entity name and that no other process (e.g. rkaiq_3A_server) holds the ISP"
+                .to_owned(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_names() {
+        for check in DoctorCheck::ALL {
+            assert_eq!(DoctorCheck::from_name(check.name()), Some(check));
+        }
+        assert_eq!(DoctorCheck::from_name("iq"), None);
+    }
+
+    #[test]
+    fn test_major_version() {
+        assert_eq!(major_version("v5.0.3 \u{b5}"), Some(5));
+        assert_eq!(major_version(" V4.1"), Some(4));
+        assert_eq!(major_version("v5_0"), Some(5));
+        assert_eq!(major_version("unknown"), None);
+    }
+
+    #[test]
+    fn test_check_sensors() {
+        let node = media::MediaNode {
+            path: "/dev/media0".to_owned(),
+            sensor: "m00_b_ov5695 4-0036-1".to_owned(),
+            isp_index: 0,
+        };
+        let (result, names) = check_sensors(Ok(vec![node]));
+        assert_eq!(result.status, CheckStatus::Pass);
+        assert_eq!(names, ["m00_b_ov5695 4-0036-1"]);
+
+        let (result, names) = check_sensors(Ok(Vec::new()));
+        assert_eq!(result.status, CheckStatus::Fail);
+        assert!(result.remedy.is_some());
+        assert!(names.is_empty());
+        let (result, _) = check_sensors(Err(XCamError::from(
+            super::super::ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED,
+        )));
+        assert_eq!(result.status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn test_check_iq_files_and_version() {
+        let dir = std::env::temp_dir().join(format!("rkaiq-doctor-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ov5695_TongJu_CHT842-MD.json");
+        let ext = calib::CALIB_EXTENSIONS[0];
+        for p in [path.clone(), path.with_extension(ext)] {
+            std::fs::write(p, r#"{"version": "v1.0.0"}"#).unwrap();
+        }
+        let found = check_iq_files(&dir, "m00_b_ov5695 4-0036-1");
+        let missing = check_iq_files(&dir, "m01_f_gc2053 1-0037");
+        let absent = check_iq_files(&dir.join("absent"), "m00_b_ov5695 4-0036-1");
+        let calib = CalibFile::open(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(found.status, CheckStatus::Pass);
+        assert_eq!(missing.status, CheckStatus::Fail);
+        assert!(missing
+            .remedy
+            .unwrap()
+            .contains(&format!("gc2053_<module>_<lens>.{}", ext)));
+        assert_eq!(absent.status, CheckStatus::Fail);
+
+        let result = check_sdk_version("m00_b_ov5695 4-0036-1", &calib);
+        let expected = if !cfg!(feature = "json") {
+            CheckStatus::Skipped
+        } else if sdk_version() == "v1_0" {
+            CheckStatus::Pass
+        } else {
+            CheckStatus::Fail
+        };
+        assert_eq!(result.status, expected);
+    }
+
+    #[test]
+    fn test_doctor_skip_all() {
+        let opts = DoctorCheck::ALL.into_iter().fold(
+            DoctorOptions::new().sensor("m00_b_ov5695 4-0036-1"),
+            |o, c| o.skip(c),
+        );
+        let report = doctor_with(&opts);
+        assert_eq!(report.results.len(), 4);
+        assert!(report
+            .results
+            .iter()
+            .all(|r| r.status == CheckStatus::Skipped));
+        assert!(report.is_ok());
+    }
+}
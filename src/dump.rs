@@ -0,0 +1,895 @@
+//! 状态导出
+//!
+//! 一次性读取本库封装的全部可查询参数，用于随画质问题报告附带 ISP 状态。
+//! 导出只调用读取接口，不修改任何 ISP 状态。每个查询在工作线程中以 `with_timeout` 执行，
+//! 单个查询失败或超时只记录在对应字段中，不影响其余查询。
+//!
+//! 启用 `serde` 特性后导出结果可序列化与反序列化，启用 `config` 特性后可与 TOML 相互转换。
+//! 字段名即导出格式，作为稳定的配置模式维护：成功的查询记为 `{ Ok = 值 }`，
+//! 失败的查询记为 `{ Err = "错误描述" }`；值为 `None` 的字段（例如尚未收到元数据时的
+//! `system.frame_id`）省略不写。只在部分 SDK 版本上可用的查询（例如 `exposure.manual_gains`）
+//! 缺失时读取为 `{ Err = "Unsupported" }`，因此不同版本导出的结果可以互相读取：
+//!
+//! ```toml
+//! [version]
+//! crate_version = "5.0.3"
+//! sdk = "v5_0"
+//! isp_hw = "isp_hw_v30"
+//!
+//! [white_balance]
+//! mode = { Ok = "auto" }
+//! ct = { Ok = 5000 }
+//! illuminant = { Err = "Unsupported" }
+//! ```
+//!
+//! 回归测试调优结果时，先保存一份基准导出，之后以 `IspStateDump::diff` 与当前导出比较，
+//! 逐项列出偏离基准的字段。比较的容差规则见该方法的说明。
+use super::acm::AutoColorManagment;
+use super::ae::{AutoExposure, ExpInfo};
+use super::af::AutoFocus;
+use super::asd::AmbientDetection;
+use super::awb::{AutoWhiteBalance, Illuminant, IlluminantEstimate};
+use super::context::Context;
+use super::defog::Defog;
+use super::gamma::Gamma;
+use super::hdr::HighDynamicRange;
+use super::misc::Miscellaneous;
+use super::nr::NoiseRemoval;
+use super::sharpen::Sharpen;
+use super::sysctl::{self, NegotiatedFormat, SystemControl};
+use super::timeout::Timed;
+use super::types::{OpMode, Strength, XCamResult};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 单个查询的默认超时。
+pub const DUMP_CALL_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// 单个查询的结果，失败时记录错误描述。
+pub type DumpResult<T> = Result<T, String>;
+
+/// 反序列化时缺失的版本相关查询结果。
+#[cfg(feature = "serde")]
+fn unsupported<T>() -> DumpResult<T> {
+    Err(super::error::XCamError::Unsupported.to_string())
+}
+
+/// 一个描述完整 ISP 状态的类型。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IspStateDump {
+    pub version: VersionDump,
+    pub sensor: SensorDump,
+    pub system: SystemDump,
+    pub white_balance: WhiteBalanceDump,
+    pub exposure: ExposureDump,
+    pub focus: FocusDump,
+    pub adjust: AdjustDump,
+    pub noise_reduction: NoiseReductionDump,
+    pub hdr: HdrDump,
+    pub defog: DefogDump,
+    pub misc: MiscDump,
+}
+
+/// 版本信息。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VersionDump {
+    /// 本库版本。
+    pub crate_version: String,
+    /// 编译时选择的 SDK 版本。
+    pub sdk: String,
+    /// 编译时选择的 ISP 硬件版本。
+    pub isp_hw: String,
+}
+
+/// 传感器描述。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SensorDump {
+    pub entity_name: String,
+    /// 可用功能模块的名称。
+    pub capabilities: DumpResult<Vec<String>>,
+    pub static_info: DumpResult<String>,
+}
+
+/// 反序列化早于 `system.format` 的导出结果时的缺省值。
+#[cfg(feature = "serde")]
+fn not_prepared<T>() -> DumpResult<T> {
+    Err(super::error::XCamError::from(super::ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER).to_string())
+}
+
+/// 系统状态。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SystemDump {
+    pub frame_id: Option<u32>,
+    pub isp_error_flags: DumpResult<u32>,
+    pub crop: DumpResult<String>,
+    /// `prepare` 后实际配置的输出格式，尚未 `prepare` 时记为错误。
+    #[cfg_attr(feature = "serde", serde(default = "not_prepared"))]
+    pub format: DumpResult<NegotiatedFormat>,
+}
+
+/// 白平衡状态。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WhiteBalanceDump {
+    pub mode: DumpResult<OpMode>,
+    /// `[R, Gr, Gb, B]` 通道增益。
+    pub gain: DumpResult<[f32; 4]>,
+    pub ct: DumpResult<u32>,
+    pub scene: DumpResult<String>,
+    pub illuminant: DumpResult<IlluminantEstimate>,
+}
+
+/// 曝光状态。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExposureDump {
+    pub mode: DumpResult<OpMode>,
+    pub gain_range: DumpResult<(f32, f32)>,
+    pub time_range: DumpResult<(f32, f32)>,
+    pub info: DumpResult<ExpInfo>,
+    pub base_iso: u32,
+    pub dark_area_boost: DumpResult<u32>,
+    pub anti_flicker_mode: DumpResult<String>,
+    pub pwr_line_freq: DumpResult<String>,
+    /// 自动抗闪检测到的频率，见 `AutoExposure::get_detected_flicker`。
+    #[cfg_attr(feature = "serde", serde(default = "unsupported"))]
+    pub detected_flicker: DumpResult<Option<String>>,
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    #[cfg_attr(feature = "serde", serde(default = "unsupported"))]
+    pub manual_gains: DumpResult<(f32, f32)>,
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    #[cfg_attr(feature = "serde", serde(default = "unsupported"))]
+    pub compensation: DumpResult<f32>,
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    #[cfg_attr(feature = "serde", serde(default = "unsupported"))]
+    pub lin_exp_attr: DumpResult<String>,
+}
+
+/// 对焦状态。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FocusDump {
+    pub mode: DumpResult<OpMode>,
+    pub search_state: DumpResult<String>,
+}
+
+/// 图像调节状态。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdjustDump {
+    pub brightness: DumpResult<u32>,
+    pub contrast: DumpResult<u32>,
+    pub saturation: DumpResult<Strength>,
+    pub hue: DumpResult<u32>,
+    pub sharpness: DumpResult<Strength>,
+    pub sharp_attrib: DumpResult<String>,
+    pub gamma: DumpResult<String>,
+}
+
+/// 降噪状态。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoiseReductionDump {
+    pub mode: DumpResult<OpMode>,
+    pub strength: DumpResult<Strength>,
+    pub spatial: DumpResult<(bool, Strength)>,
+    pub temporal: DumpResult<(bool, Strength)>,
+}
+
+/// HDR 状态。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HdrDump {
+    pub mode: DumpResult<OpMode>,
+    pub strength: DumpResult<(bool, u32)>,
+}
+
+/// 去雾状态。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DefogDump {
+    pub mode: DumpResult<OpMode>,
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    #[cfg_attr(feature = "serde", serde(default = "unsupported"))]
+    pub strength: DumpResult<Strength>,
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    #[cfg_attr(feature = "serde", serde(default = "unsupported"))]
+    pub attrib: DumpResult<String>,
+}
+
+/// 其它状态。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MiscDump {
+    pub gray_mode: DumpResult<String>,
+    pub frame_rate: DumpResult<String>,
+    /// `(镜像, 翻转)`。
+    pub mirror_flip: DumpResult<(bool, bool)>,
+    pub asd_attrib: DumpResult<String>,
+}
+
+/// 一个描述两份导出结果中单个字段差异的类型。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldDiff {
+    /// 字段路径，形如 `white_balance.gain`。
+    pub field: String,
+    /// 基准中的值，以调试格式记录。
+    pub expected: String,
+    /// 比较对象中的值，以调试格式记录。
+    pub actual: String,
+}
+
+/// 形如 `white_balance.ct: Ok(5000) -> Ok(4600)`。
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.expected, self.actual)
+    }
+}
+
+/// 一个描述可按容差比较的导出字段的契定。
+trait DumpField: fmt::Debug {
+    /// 两个值是否在容差 `tolerance` 以内一致。
+    fn same(&self, other: &Self, tolerance: f64) -> bool;
+}
+
+/// 不含浮点数的类型，按值比较。
+macro_rules! exact_field {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl DumpField for $t {
+                fn same(&self, other: &Self, _: f64) -> bool {
+                    self == other
+                }
+            }
+        )*
+    };
+}
+
+exact_field!(
+    bool,
+    u32,
+    String,
+    OpMode,
+    Strength,
+    Illuminant,
+    NegotiatedFormat
+);
+
+impl DumpField for f32 {
+    fn same(&self, other: &Self, tolerance: f64) -> bool {
+        f64::from(*self).same(&f64::from(*other), tolerance)
+    }
+}
+
+impl DumpField for f64 {
+    fn same(&self, other: &Self, tolerance: f64) -> bool {
+        (self.is_nan() && other.is_nan()) || (self - other).abs() <= tolerance
+    }
+}
+
+impl<T: DumpField> DumpField for Option<T> {
+    fn same(&self, other: &Self, tolerance: f64) -> bool {
+        match (self, other) {
+            (Some(a), Some(b)) => a.same(b, tolerance),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T: DumpField> DumpField for DumpResult<T> {
+    fn same(&self, other: &Self, tolerance: f64) -> bool {
+        match (self, other) {
+            (Ok(a), Ok(b)) => a.same(b, tolerance),
+            (Err(a), Err(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<T: DumpField> DumpField for Vec<T> {
+    fn same(&self, other: &Self, tolerance: f64) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a.same(b, tolerance))
+    }
+}
+
+impl<T: DumpField, const N: usize> DumpField for [T; N] {
+    fn same(&self, other: &Self, tolerance: f64) -> bool {
+        self.iter().zip(other).all(|(a, b)| a.same(b, tolerance))
+    }
+}
+
+impl<A: DumpField, B: DumpField> DumpField for (A, B) {
+    fn same(&self, other: &Self, tolerance: f64) -> bool {
+        self.0.same(&other.0, tolerance) && self.1.same(&other.1, tolerance)
+    }
+}
+
+impl DumpField for IlluminantEstimate {
+    fn same(&self, other: &Self, tolerance: f64) -> bool {
+        self.illuminant == other.illuminant && self.confidence.same(&other.confidence, tolerance)
+    }
+}
+
+impl DumpField for ExpInfo {
+    fn same(&self, other: &Self, tolerance: f64) -> bool {
+        self.mean_luma.same(&other.mean_luma, tolerance)
+            && self.gain.same(&other.gain, tolerance)
+            && self.time.same(&other.time, tolerance)
+            && self.converged == other.converged
+            && self.iso == other.iso
+    }
+}
+
+/// 逐项比较 `$group` 分组中的字段，不一致时记录 `分组.字段` 的差异；`skip` 中的字段不比较。
+///
+/// 先以不含 `..` 的模式解构分组，分组新增字段而未列入比较或 `skip` 时无法通过编译。
+macro_rules! diff_fields {
+    (
+        $diffs:ident, $tolerance:ident, $expected:ident, $actual:ident,
+        $group:ident: $ty:ident { $($(#[$m:meta])* $field:ident),* $(,)? }
+        $(skip { $($(#[$sm:meta])* $skip:ident),* $(,)? })?
+    ) => {
+        let $ty {
+            $($(#[$m])* $field: _,)*
+            $($($(#[$sm])* $skip: _,)*)?
+        } = &$expected.$group;
+        $(
+            $(#[$m])*
+            if !$expected.$group.$field.same(&$actual.$group.$field, $tolerance) {
+                $diffs.push(FieldDiff {
+                    field: concat!(stringify!($group), ".", stringify!($field)).to_owned(),
+                    expected: format!("{:?}", $expected.$group.$field),
+                    actual: format!("{:?}", $actual.$group.$field),
+                });
+            }
+        )*
+    };
+}
+
+impl IspStateDump {
+    /// 以 `self` 为基准与 `other` 比较，返回不一致的字段，按字段在导出结果中的顺序排列。
+    ///
+    /// 浮点数（包括元组、数组与结构中的浮点分量）之差的绝对值不超过 `tolerance` 时视为一致，
+    /// 两者均为 NaN 时也视为一致；容差对所有浮点字段统一生效，单位即各字段本身的单位。
+    /// 其余值按值比较：`Ok` 与 `Err` 总是不一致，错误描述按字符串比较。以调试格式记录的字段
+    /// （例如 `adjust.gamma`）在导出时已是字符串，其中的浮点数同样按字符串比较，不受容差影响。
+    ///
+    /// 逐帧变化的字段 `system.frame_id` 不参与比较。
+    pub fn diff(&self, other: &IspStateDump, tolerance: f32) -> Vec<FieldDiff> {
+        let (expected, actual) = (self, other);
+        let tolerance = f64::from(tolerance);
+        let mut diffs = Vec::new();
+        let IspStateDump {
+            version: _,
+            sensor: _,
+            system: _,
+            white_balance: _,
+            exposure: _,
+            focus: _,
+            adjust: _,
+            noise_reduction: _,
+            hdr: _,
+            defog: _,
+            misc: _,
+        } = expected;
+        diff_fields!(diffs, tolerance, expected, actual, version: VersionDump {
+            crate_version, sdk, isp_hw
+        });
+        diff_fields!(diffs, tolerance, expected, actual, sensor: SensorDump {
+            entity_name, capabilities, static_info
+        });
+        diff_fields!(diffs, tolerance, expected, actual, system: SystemDump {
+            isp_error_flags, crop, format
+        } skip { frame_id });
+        diff_fields!(diffs, tolerance, expected, actual, white_balance: WhiteBalanceDump {
+            mode, gain, ct, scene, illuminant
+        });
+        diff_fields!(diffs, tolerance, expected, actual, exposure: ExposureDump {
+            mode,
+            gain_range,
+            time_range,
+            info,
+            base_iso,
+            dark_area_boost,
+            anti_flicker_mode,
+            pwr_line_freq,
+            detected_flicker,
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            manual_gains,
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            compensation,
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            lin_exp_attr,
+        });
+        diff_fields!(diffs, tolerance, expected, actual, focus: FocusDump {
+            mode, search_state
+        });
+        diff_fields!(diffs, tolerance, expected, actual, adjust: AdjustDump {
+            brightness, contrast, saturation, hue, sharpness, sharp_attrib, gamma
+        });
+        diff_fields!(diffs, tolerance, expected, actual, noise_reduction: NoiseReductionDump {
+            mode, strength, spatial, temporal
+        });
+        diff_fields!(diffs, tolerance, expected, actual, hdr: HdrDump {
+            mode, strength
+        });
+        diff_fields!(diffs, tolerance, expected, actual, defog: DefogDump {
+            mode,
+            #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+            strength,
+            #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+            attrib,
+        });
+        diff_fields!(diffs, tolerance, expected, actual, misc: MiscDump {
+            gray_mode, frame_rate, mirror_flip, asd_attrib
+        });
+        diffs
+    }
+}
+
+/// 一个在工作线程中执行带超时查询的类型。
+pub(crate) struct Probe<C> {
+    timed: Timed<C>,
+}
+
+impl<C: Send + Sync + 'static> Probe<C> {
+    pub(crate) fn new(ctx: &Arc<C>, timeout: Duration) -> Self {
+        Self {
+            timed: Timed::new(ctx, timeout),
+        }
+    }
+
+    /// 执行一次查询，失败或超时时返回错误描述。
+    pub(crate) fn get<T, F>(&self, f: F) -> DumpResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&C) -> XCamResult<T> + Send + 'static,
+    {
+        self.timed.call(f).map_err(|e| e.to_string())
+    }
+
+    /// 执行一次查询并以调试格式记录结果，用于没有结构化表示的 SDK 类型。
+    pub(crate) fn debug<T, F>(&self, f: F) -> DumpResult<String>
+    where
+        T: std::fmt::Debug + 'static,
+        F: FnOnce(&C) -> XCamResult<T> + Send + 'static,
+    {
+        self.get(move |ctx| f(ctx).map(|v| format!("{:?}", v)))
+    }
+}
+
+impl WhiteBalanceDump {
+    /// 以 `p` 逐项读取本分组，每项查询的失败或超时只记录在对应字段中。
+    pub(crate) fn collect<C>(p: &Probe<C>) -> Self
+    where
+        C: AutoWhiteBalance + Send + Sync + 'static,
+    {
+        Self {
+            mode: p.get(|c| c.get_wb_mode()),
+            gain: p.get(|c| {
+                c.get_mwb_gain()
+                    .map(|g| [g.rgain, g.grgain, g.gbgain, g.bgain])
+            }),
+            ct: p.get(|c| c.get_mwb_ct()),
+            scene: p.debug(|c| c.get_mwb_scene()),
+            illuminant: p.get(|c| c.get_awb_illuminant()),
+        }
+    }
+}
+
+impl FocusDump {
+    /// 以 `p` 逐项读取本分组，每项查询的失败或超时只记录在对应字段中。
+    pub(crate) fn collect<C>(p: &Probe<C>) -> Self
+    where
+        C: AutoFocus + Send + Sync + 'static,
+    {
+        Self {
+            mode: p.get(|c| c.get_focus_mode()),
+            search_state: p.debug(|c| c.get_af_search_state()),
+        }
+    }
+}
+
+impl AdjustDump {
+    /// 以 `p` 逐项读取本分组，每项查询的失败或超时只记录在对应字段中。
+    pub(crate) fn collect<C>(p: &Probe<C>) -> Self
+    where
+        C: AutoColorManagment + Sharpen + Gamma + Send + Sync + 'static,
+    {
+        Self {
+            brightness: p.get(|c| c.get_brightness()),
+            contrast: p.get(|c| c.get_contrast()),
+            saturation: p.get(|c| c.get_saturation()),
+            hue: p.get(|c| c.get_hue()),
+            sharpness: p.get(|c| c.get_sharpness()),
+            sharp_attrib: p.debug(|c| c.get_sharp_attrib().map(|a| a.0)),
+            gamma: p.debug(|c| c.get_gamma_coef()),
+        }
+    }
+}
+
+impl NoiseReductionDump {
+    /// 以 `p` 逐项读取本分组，每项查询的失败或超时只记录在对应字段中。
+    pub(crate) fn collect<C>(p: &Probe<C>) -> Self
+    where
+        C: NoiseRemoval + Send + Sync + 'static,
+    {
+        Self {
+            mode: p.get(|c| c.get_nr_mode()),
+            strength: p.get(|c| c.get_anr_strength()),
+            spatial: p.get(|c| c.get_ms_nr_strength()),
+            temporal: p.get(|c| c.get_mt_nr_strength()),
+        }
+    }
+}
+
+impl HdrDump {
+    /// 以 `p` 逐项读取本分组，每项查询的失败或超时只记录在对应字段中。
+    pub(crate) fn collect<C>(p: &Probe<C>) -> Self
+    where
+        C: HighDynamicRange + Send + Sync + 'static,
+    {
+        Self {
+            mode: p.get(|c| c.get_hdr_mode()),
+            strength: p.get(|c| c.get_hdr_strth()),
+        }
+    }
+}
+
+impl DefogDump {
+    /// 以 `p` 逐项读取本分组，每项查询的失败或超时只记录在对应字段中。
+    pub(crate) fn collect<C>(p: &Probe<C>) -> Self
+    where
+        C: Defog + Send + Sync + 'static,
+    {
+        Self {
+            mode: p.get(|c| c.get_dhz_mode()),
+            #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+            strength: p.get(|c| c.get_dhz_strength()),
+            #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+            attrib: p.debug(|c| c.get_dhz_attrib().map(|a| a.0)),
+        }
+    }
+}
+
+impl MiscDump {
+    /// 以 `p` 逐项读取本分组，每项查询的失败或超时只记录在对应字段中。
+    pub(crate) fn collect<C>(p: &Probe<C>) -> Self
+    where
+        C: Miscellaneous + AmbientDetection + Send + Sync + 'static,
+    {
+        Self {
+            gray_mode: p.debug(|c| Ok(c.get_gray_mode())),
+            frame_rate: p.debug(|c| c.get_frame_rate()),
+            mirror_flip: p.get(|c| c.get_mirror_flip()),
+            asd_attrib: p.debug(|c| c.get_asd_attrib()),
+        }
+    }
+}
+
+pub(crate) fn sdk_version() -> &'static str {
+    if cfg!(feature = "v5_0") {
+        "v5_0"
+    } else if cfg!(feature = "v4_0") {
+        "v4_0"
+    } else if cfg!(feature = "v3_0") {
+        "v3_0"
+    } else if cfg!(feature = "v2_0") {
+        "v2_0"
+    } else {
+        "v1_0"
+    }
+}
+
+fn isp_hw_version() -> &'static str {
+    if cfg!(feature = "isp_hw_v32") {
+        "isp_hw_v32"
+    } else if cfg!(feature = "isp_hw_v31") {
+        "isp_hw_v31"
+    } else if cfg!(feature = "isp_hw_v30") {
+        "isp_hw_v30"
+    } else if cfg!(feature = "isp_hw_v21") {
+        "isp_hw_v21"
+    } else {
+        "isp_hw_v20"
+    }
+}
+
+impl Context {
+    /// 导出当前 ISP 状态，每个查询的超时为 `DUMP_CALL_TIMEOUT`。
+    pub fn dump_state(self: &Arc<Self>) -> IspStateDump {
+        self.dump_state_with_timeout(DUMP_CALL_TIMEOUT)
+    }
+
+    /// 导出当前 ISP 状态。
+    ///
+    /// 只调用读取接口。单个查询超过 `d` 未返回时记录为超时并继续下一项，
+    /// 因此总耗时不超过查询数与 `d` 之积。
+    pub fn dump_state_with_timeout(self: &Arc<Self>, d: Duration) -> IspStateDump {
+        let p = Probe::new(self, d);
+        let sns_ent_name = self.sns_ent_name.clone();
+        IspStateDump {
+            version: VersionDump {
+                crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+                sdk: sdk_version().to_owned(),
+                isp_hw: isp_hw_version().to_owned(),
+            },
+            sensor: SensorDump {
+                entity_name: self.sns_ent_name.clone(),
+                capabilities: p.get(|c| {
+                    Ok(c.capabilities()
+                        .iter_names()
+                        .map(|(name, _)| name.to_owned())
+                        .collect())
+                }),
+                static_info: p.debug(move |_| sysctl::get_static_metas(sns_ent_name)),
+            },
+            system: SystemDump {
+                frame_id: self.get_frame_id(),
+                isp_error_flags: p.get(|c| c.get_isp_error_flags().map(|f| f.bits())),
+                crop: p.debug(|c| c.get_crop()),
+                format: p.get(|c| c.negotiated_format()),
+            },
+            white_balance: WhiteBalanceDump::collect(&p),
+            exposure: ExposureDump {
+                mode: p.get(|c| c.get_exp_mode()),
+                gain_range: p.get(|c| c.get_exp_gain_range()),
+                time_range: p.get(|c| c.get_exp_time_range()),
+                info: p.get(|c| c.query_exp_info()),
+                base_iso: self.get_base_iso(),
+                dark_area_boost: p.get(|c| c.get_dark_area_boost_strth()),
+                anti_flicker_mode: p.debug(|c| c.get_anti_flicker_mode()),
+                pwr_line_freq: p.debug(|c| c.get_exp_pwr_line_freq_mode()),
+                detected_flicker: p
+                    .get(|c| c.get_detected_flicker().map(|f| f.map(|f| f.to_string()))),
+                #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+                manual_gains: p.get(|c| c.get_manual_gains()),
+                #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+                compensation: p.get(|c| c.get_exposure_compensation()),
+                #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+                lin_exp_attr: p.debug(|c| c.get_lin_exp_attr().map(|a| a.0)),
+            },
+            focus: FocusDump::collect(&p),
+            adjust: AdjustDump::collect(&p),
+            noise_reduction: NoiseReductionDump::collect(&p),
+            hdr: HdrDump::collect(&p),
+            defog: DefogDump::collect(&p),
+            misc: MiscDump::collect(&p),
+        }
+    }
+
+    /// 以 JSON 格式导出当前 ISP 状态，见 `dump_state`。
+    #[cfg(feature = "json")]
+    pub fn dump_state_json(self: &Arc<Self>) -> String {
+        serde_json::to_string_pretty(&self.dump_state()).expect("ISP state dump is serializable")
+    }
+}
+
+/// 测试用的导出结果。
+#[cfg(test)]
+pub(crate) fn sample_dump() -> IspStateDump {
+    use super::sysctl::BayerPattern;
+    use super::types::RawBitDepth;
+
+    let unsupported = Err("Unsupported".to_owned());
+    IspStateDump {
+        version: VersionDump {
+            crate_version: "5.0.3".to_owned(),
+            sdk: "v5_0".to_owned(),
+            isp_hw: "isp_hw_v30".to_owned(),
+        },
+        sensor: SensorDump {
+            entity_name: "m00_b_ov5695 4-0036-1".to_owned(),
+            capabilities: Ok(vec!["AWB".to_owned(), "AE".to_owned()]),
+            static_info: Err("Timeout".to_owned()),
+        },
+        system: SystemDump {
+            frame_id: None,
+            isp_error_flags: Ok(0),
+            crop: Ok("rk_aiq_rect_t { left: 0, top: 0 }".to_owned()),
+            format: Ok(NegotiatedFormat {
+                width: 1296,
+                height: 972,
+                format: 0x3007,
+                bayer: Some(BayerPattern::Bggr),
+                depth: Some(RawBitDepth::Bits10),
+            }),
+        },
+        white_balance: WhiteBalanceDump {
+            mode: Ok(OpMode::Auto),
+            gain: Ok([1.83, 1.0, 1.0, 2.1]),
+            ct: Ok(5000),
+            scene: Ok("RK_AIQ_WBCT_DAYLIGHT".to_owned()),
+            illuminant: Ok(IlluminantEstimate {
+                illuminant: Illuminant::D65,
+                confidence: None,
+            }),
+        },
+        exposure: ExposureDump {
+            mode: Ok(OpMode::Auto),
+            gain_range: Ok((1.0, 32.0)),
+            time_range: Ok((0.0001, 0.033)),
+            info: unsupported.clone(),
+            base_iso: 100,
+            dark_area_boost: Ok(0),
+            anti_flicker_mode: unsupported.clone(),
+            pwr_line_freq: unsupported.clone(),
+            detected_flicker: Ok(None),
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            manual_gains: Ok((4.0, 1.0)),
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            compensation: Ok(-0.5),
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            lin_exp_attr: unsupported.clone(),
+        },
+        focus: FocusDump {
+            mode: unsupported.clone(),
+            search_state: unsupported.clone(),
+        },
+        adjust: AdjustDump {
+            brightness: Ok(128),
+            contrast: Ok(128),
+            saturation: Ok(Strength::new(50).unwrap()),
+            hue: Ok(128),
+            sharpness: Ok(Strength::new(50).unwrap()),
+            sharp_attrib: unsupported.clone(),
+            gamma: unsupported.clone(),
+        },
+        noise_reduction: NoiseReductionDump {
+            mode: Ok(OpMode::Manual),
+            strength: Ok(Strength::new(60).unwrap()),
+            spatial: Ok((true, Strength::new(40).unwrap())),
+            temporal: Ok((false, Strength::new(0).unwrap())),
+        },
+        hdr: HdrDump {
+            mode: unsupported.clone(),
+            strength: Ok((true, 50)),
+        },
+        defog: DefogDump {
+            mode: Ok(OpMode::Auto),
+            #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+            strength: Ok(Strength::new(30).unwrap()),
+            #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+            attrib: unsupported.clone(),
+        },
+        misc: MiscDump {
+            gray_mode: unsupported.clone(),
+            frame_rate: unsupported.clone(),
+            mirror_flip: Ok((false, true)),
+            asd_attrib: unsupported,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::XCamError;
+    use crate::ffi;
+    use std::thread;
+
+    #[test]
+    fn test_probe_records_failures_inline() {
+        let p = Probe::new(&Arc::new(7u32), Duration::from_millis(50));
+        assert_eq!(p.get(|v| Ok(*v + 1)), Ok(8));
+        assert_eq!(p.debug(|v| Ok(Some(*v))), Ok("Some(7)".to_owned()));
+        assert_eq!(
+            p.get(|_| Err::<u32, _>(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))),
+            Err("Bad Parameters".to_owned())
+        );
+        // 挂起的查询在超时后被记录，后续查询不受影响。
+        assert_eq!(
+            p.get(|_| {
+                thread::sleep(Duration::from_millis(500));
+                Ok(0u32)
+            }),
+            Err("Timeout".to_owned())
+        );
+        assert_eq!(p.get(|v| Ok(*v)), Ok(7));
+    }
+
+    /// 只实现 HDR 查询的上下文，读取模式时休眠 `delay`，写入接口不应被导出调用。
+    struct SlowHdr {
+        delay: Duration,
+    }
+
+    impl HighDynamicRange for SlowHdr {
+        fn get_hdr_mode(&self) -> XCamResult<OpMode> {
+            thread::sleep(self.delay);
+            Ok(OpMode::Manual)
+        }
+
+        fn set_hdr_mode<T: Into<OpMode>>(&self, _mode: T) -> XCamResult<()> {
+            unreachable!("dump must not write ISP state")
+        }
+
+        fn get_hdr_strth(&self) -> XCamResult<(bool, u32)> {
+            Ok((true, 50))
+        }
+
+        fn set_hdr_strth(&self, _enabled: bool, _level: u32) -> XCamResult<()> {
+            unreachable!("dump must not write ISP state")
+        }
+    }
+
+    #[test]
+    fn test_collect_group() {
+        let ctx = Arc::new(SlowHdr {
+            delay: Duration::ZERO,
+        });
+        assert_eq!(
+            HdrDump::collect(&Probe::new(&ctx, Duration::from_millis(500))),
+            HdrDump {
+                mode: Ok(OpMode::Manual),
+                strength: Ok((true, 50)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_collect_group_timeout() {
+        // 挂起的查询只使本字段记为超时，同一分组的其余字段照常读取，耗时受超时限制。
+        let ctx = Arc::new(SlowHdr {
+            delay: Duration::from_secs(2),
+        });
+        let start = std::time::Instant::now();
+        assert_eq!(
+            HdrDump::collect(&Probe::new(&ctx, Duration::from_millis(50))),
+            HdrDump {
+                mode: Err("Timeout".to_owned()),
+                strength: Ok((true, 50)),
+            }
+        );
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_diff_reports_single_field() {
+        let golden = sample_dump();
+        assert!(golden.diff(&golden, 0.0).is_empty());
+
+        let mut live = golden.clone();
+        live.white_balance.ct = Ok(4600);
+        // 容差以内的浮点漂移不记录。
+        live.white_balance.gain = Ok([1.832, 1.0, 1.0, 2.099]);
+        live.exposure.time_range = Ok((0.0001, 0.0331));
+        let diffs = golden.diff(&live, 0.005);
+        assert_eq!(
+            diffs,
+            [FieldDiff {
+                field: "white_balance.ct".to_owned(),
+                expected: "Ok(5000)".to_owned(),
+                actual: "Ok(4600)".to_owned(),
+            }]
+        );
+        assert_eq!(
+            diffs[0].to_string(),
+            "white_balance.ct: Ok(5000) -> Ok(4600)"
+        );
+
+        // 超出容差的浮点字段与 `Ok`、`Err` 的变化均被记录。
+        live.white_balance.ct = golden.white_balance.ct.clone();
+        live.focus.mode = Ok(OpMode::Auto);
+        let fields: Vec<_> = golden
+            .diff(&live, 0.001)
+            .into_iter()
+            .map(|d| d.field)
+            .collect();
+        assert_eq!(fields, ["white_balance.gain", "focus.mode"]);
+    }
+
+    #[test]
+    fn test_diff_skips_volatile_fields() {
+        let golden = sample_dump();
+        let mut live = golden.clone();
+        live.system.frame_id = live.system.frame_id.map_or(Some(1), |id| Some(id + 1));
+        assert!(golden.diff(&live, 0.0).is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+//! 传感器嵌入数据
+//!
+//! 部分传感器在每帧图像之前输出若干行嵌入数据（MIPI CSI-2 数据类型 0x12），记录该帧实际生效的
+//! 寄存器值。与 AE 下发的参数不同，嵌入数据反映的是传感器真正使用的曝光，可用于核对曝光
+//! 生效的帧延迟。本模块解析 SMIA/CCS 格式的第一行嵌入数据：
+//!
+//! | 字节 | 含义 |
+//! | --- | --- |
+//! | `0x0a` | 行首 |
+//! | `0xaa` `hi` | 寄存器地址高字节 |
+//! | `0xa5` `lo` | 寄存器地址低字节 |
+//! | `0x5a` `val` | 当前地址的寄存器值，地址加一 |
+//! | `0x55` `-` | 跳过当前地址，地址加一 |
+//! | `0x07` | 行尾 |
+//!
+//! 嵌入数据行与图像按相同的 MIPI 格式打包，RAW10、RAW12、RAW14 中存放低位的字节不含数据，
+//! 解析前按位深去除。目前支持的传感器及读取的寄存器：
+//!
+//! | 型号 | 曝光行数 | 模拟增益 | 温度 |
+//! | --- | --- | --- | --- |
+//! | `imx219` | `0x015a`-`0x015b` | `0x0157` | 无 |
+//! | `imx477` | `0x0202`-`0x0203` | `0x0204`-`0x0205` | `0x013a` |
+//! | `imx708` | `0x0202`-`0x0203` | `0x0204`-`0x0205` | `0x013a` |
+//!
+//! 模拟增益为寄存器原值，可用 `sysctl::again_register_to_multiplier` 换算为倍数。
+//! 驱动须开启嵌入数据输出，并将其作为单独的 video 节点或图像缓冲区的前几行交给应用。
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::types::{RawBitDepth, XCamResult};
+use std::collections::BTreeMap;
+
+/// 一个描述单帧嵌入数据中曝光参数的类型。
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EmbeddedMeta {
+    /// 曝光时间，单位：行。
+    pub exposure_lines: u32,
+    /// 模拟增益寄存器原值。
+    pub again_reg: u32,
+    /// 传感器温度，单位：摄氏度，传感器不输出温度时为 `None`。
+    pub temperature_c: Option<f32>,
+}
+
+/// 一个描述传感器嵌入数据中寄存器位置的类型，多字节寄存器按高字节在前排列。
+struct EmbeddedLayout {
+    model: &'static str,
+    exposure: &'static [u16],
+    again: &'static [u16],
+    temperature: Option<u16>,
+}
+
+/// 已知传感器的嵌入数据布局，寄存器地址取自各传感器的数据手册。
+const EMBEDDED_LAYOUTS: &[EmbeddedLayout] = &[
+    EmbeddedLayout {
+        model: "imx219",
+        exposure: &[0x015a, 0x015b],
+        again: &[0x0157],
+        temperature: None,
+    },
+    EmbeddedLayout {
+        model: "imx477",
+        exposure: &[0x0202, 0x0203],
+        again: &[0x0204, 0x0205],
+        temperature: Some(0x013a),
+    },
+    EmbeddedLayout {
+        model: "imx708",
+        exposure: &[0x0202, 0x0203],
+        again: &[0x0204, 0x0205],
+        temperature: Some(0x013a),
+    },
+];
+
+const TAG_LINE_START: u8 = 0x0a;
+const TAG_ADDR_HI: u8 = 0xaa;
+const TAG_ADDR_LO: u8 = 0xa5;
+const TAG_VALUE: u8 = 0x5a;
+const TAG_SKIP: u8 = 0x55;
+const TAG_LINE_END: u8 = 0x07;
+
+/// 按传感器实体名称查找嵌入数据布局，不区分大小写。
+fn layout_for_sensor(sensor: &str) -> Option<&'static EmbeddedLayout> {
+    let sensor = sensor.to_ascii_lowercase();
+    EMBEDDED_LAYOUTS
+        .iter()
+        .find(|layout| sensor.contains(layout.model))
+}
+
+/// 传感器是否支持嵌入数据解析，`sensor` 为传感器实体名称或型号，支持的型号见模块说明。
+pub fn embedded_metadata_supported(sensor: &str) -> bool {
+    layout_for_sensor(sensor).is_some()
+}
+
+/// 去除 MIPI 打包中存放低位的字节，RAW16 返回 `XCamError::Unsupported`。
+fn unpack(raw: &[u8], depth: RawBitDepth) -> XCamResult<Vec<u8>> {
+    // 每组的字节数与其中数据字节数。
+    let (group, data) = match depth {
+        RawBitDepth::Bits8 => return Ok(raw.to_vec()),
+        RawBitDepth::Bits10 => (5, 4),
+        RawBitDepth::Bits12 => (3, 2),
+        RawBitDepth::Bits14 => (7, 4),
+        RawBitDepth::Bits16 => return Err(XCamError::Unsupported),
+    };
+    Ok(raw
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i % group < data)
+        .map(|(_, &b)| b)
+        .collect())
+}
+
+/// 解析一行嵌入数据中的寄存器值，格式见模块说明。
+///
+/// 不以行首标记开始或出现未知标记时返回 `XCAM_RETURN_ERROR_PARAM`。
+fn parse_registers(raw: &[u8], depth: RawBitDepth) -> XCamResult<BTreeMap<u16, u8>> {
+    let bytes = unpack(raw, depth)?;
+    let param = || XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM);
+    let (&start, rest) = bytes.split_first().ok_or_else(param)?;
+    if start != TAG_LINE_START {
+        return Err(param());
+    }
+    let mut regs = BTreeMap::new();
+    let mut addr = 0u16;
+    for pair in rest.chunks(2) {
+        let tag = pair[0];
+        if tag == TAG_LINE_END {
+            break;
+        }
+        let &value = pair.get(1).ok_or_else(param)?;
+        match tag {
+            TAG_ADDR_HI => addr = (addr & 0x00ff) | (u16::from(value) << 8),
+            TAG_ADDR_LO => addr = (addr & 0xff00) | u16::from(value),
+            TAG_VALUE => {
+                regs.insert(addr, value);
+                addr = addr.wrapping_add(1);
+            }
+            TAG_SKIP => addr = addr.wrapping_add(1),
+            _ => return Err(param()),
+        }
+    }
+    Ok(regs)
+}
+
+/// 按 `sensor` 的布局解析一行嵌入数据，`depth` 为传感器输出的 RAW 位深。
+///
+/// `sensor` 为传感器实体名称或型号，不支持的型号返回 `XCamError::Unsupported`；
+/// 数据格式错误或缺少曝光、增益寄存器时返回 `XCAM_RETURN_ERROR_PARAM`。
+pub fn parse_embedded_line(
+    raw: &[u8],
+    sensor: &str,
+    depth: RawBitDepth,
+) -> XCamResult<EmbeddedMeta> {
+    let layout = layout_for_sensor(sensor).ok_or(XCamError::Unsupported)?;
+    let regs = parse_registers(raw, depth)?;
+    let read = |addrs: &[u16]| {
+        addrs.iter().try_fold(0u32, |acc, addr| {
+            regs.get(addr)
+                .map(|&v| (acc << 8) | u32::from(v))
+                .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        })
+    };
+    Ok(EmbeddedMeta {
+        exposure_lines: read(layout.exposure)?,
+        again_reg: read(layout.again)?,
+        temperature_c: layout
+            .temperature
+            .and_then(|addr| regs.get(&addr))
+            .map(|&v| f32::from(v as i8)),
+    })
+}
+
+impl Context {
+    /// 解析当前传感器的一行嵌入数据，支持的传感器与数据格式见模块说明。
+    ///
+    /// 按 `negotiated_format` 的位深去除打包字节，尚未 `prepare` 时返回
+    /// `XCAM_RETURN_ERROR_ORDER`；不支持的传感器或无法获取位深时返回 `XCamError::Unsupported`，
+    /// 其余错误见 `parse_embedded_line`。
+    pub fn parse_embedded_metadata(&self, raw: &[u8]) -> XCamResult<EmbeddedMeta> {
+        let depth = self
+            .negotiated_format()?
+            .depth
+            .ok_or(XCamError::Unsupported)?;
+        parse_embedded_line(raw, &self.sns_ent_name, depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMX477_RAW10: &[u8] = include_bytes!("../tests/fixtures/imx477_embedded_raw10.bin");
+
+    #[test]
+    fn test_parse_embedded_fixture() {
+        let meta =
+            parse_embedded_line(IMX477_RAW10, "m00_b_imx477 4-001a", RawBitDepth::Bits10).unwrap();
+        assert_eq!(
+            meta,
+            EmbeddedMeta {
+                exposure_lines: 4000,
+                again_reg: 0x0160,
+                temperature_c: Some(42.0),
+            }
+        );
+
+        // IMX219 的布局读取不同的寄存器，且不输出温度。
+        let line = [
+            TAG_LINE_START,
+            TAG_ADDR_HI,
+            0x01,
+            TAG_ADDR_LO,
+            0x57,
+            TAG_VALUE,
+            0x80,
+            TAG_SKIP,
+            0x00,
+            TAG_SKIP,
+            0x00,
+            TAG_VALUE,
+            0x03,
+            TAG_VALUE,
+            0xe8,
+            TAG_LINE_END,
+        ];
+        assert_eq!(
+            parse_embedded_line(&line, "imx219", RawBitDepth::Bits8),
+            Ok(EmbeddedMeta {
+                exposure_lines: 1000,
+                again_reg: 0x80,
+                temperature_c: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_embedded_errors() {
+        let param = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        assert_eq!(
+            parse_embedded_line(IMX477_RAW10, "ov5695", RawBitDepth::Bits10),
+            Err(XCamError::Unsupported)
+        );
+        // 位深不符时打包字节被当作数据。
+        assert_eq!(
+            parse_embedded_line(IMX477_RAW10, "imx477", RawBitDepth::Bits8),
+            param
+        );
+        assert_eq!(
+            parse_embedded_line(&IMX477_RAW10[1..], "imx477", RawBitDepth::Bits10),
+            param
+        );
+        assert_eq!(
+            parse_embedded_line(&[], "imx477", RawBitDepth::Bits8),
+            param
+        );
+        // 缺少增益寄存器。
+        let line = [
+            TAG_LINE_START,
+            TAG_ADDR_HI,
+            0x02,
+            TAG_ADDR_LO,
+            0x02,
+            TAG_VALUE,
+            0x0f,
+            TAG_LINE_END,
+        ];
+        assert_eq!(
+            parse_embedded_line(&line, "imx708", RawBitDepth::Bits8),
+            param
+        );
+        assert!(embedded_metadata_supported("m01_f_IMX708 2-001a"));
+    }
+}
@@ -1,24 +1,94 @@
+use super::convert;
 use super::ffi;
 use std::fmt;
+use std::io;
 
 /// 一个描述摄像头访问错误代码的类型。
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-pub struct XCamError(ffi::XCamReturn);
+pub enum XCamError {
+    /// SDK 接口返回的错误代码。
+    ///
+    /// `XCAM_RETURN_ERROR_TIMEOUT` 经 `From` 转换为 `Timeout`，不以本变体表示。
+    Code(ffi::XCamReturn),
+    /// 当前 ISP 硬件、SDK 版本或传感器不支持该功能。
+    Unsupported,
+    /// 操作超时，包括 SDK 返回的超时错误和 `with_timeout` 等待超时。
+    Timeout,
+    /// 结果尚未就绪，非阻塞调用立即返回。
+    Busy,
+    /// 传感器已掉线，见 `device` 模块。
+    DeviceLost,
+    /// 操作被 `CancelToken` 取消，见 `ContextBuilder::build_with_timeout`。
+    Cancelled,
+    /// 运行时的 librkaiq 缺少编译的版本特性依赖的符号，见 `sdkprobe` 模块。
+    SdkMismatch {
+        /// 编译的版本特性，例如 `"v4_0"`。
+        compiled_for: &'static str,
+        /// 按符号推测的库版本，无法识别时为 `None`。
+        detected: Option<&'static str>,
+        /// 第一个缺少符号的版本的代表性符号。
+        missing_symbols: &'static [&'static str],
+    },
+}
 
 impl XCamError {
+    /// 将 SDK 返回值转换为 `Result`，只有 `XCAM_RETURN_NO_ERROR` 视为成功。
     pub fn ok(self) -> Result<(), Self> {
-        if self.0 != ffi::XCamReturn::XCAM_RETURN_NO_ERROR {
-            Err(self)
-        } else {
+        match self {
+            Self::Code(ffi::XCamReturn::XCAM_RETURN_NO_ERROR) => Ok(()),
+            _ => Err(self),
+        }
+    }
+
+    /// 同 `ok`，但 `XCAM_RETURN_BYPASS` 也视为成功。
+    ///
+    /// `XCAM_RETURN_BYPASS` 表示“无需处理”而非失败。SDK 中返回该值的 uAPI 有：
+    ///
+    /// * `rk_aiq_uapi2_sysctl_setModuleCtl`：模块已处于目标使能状态；
+    /// * `rk_aiq_uapi2_sysctl_enableAxlib`：算法库已处于目标使能状态。
+    ///
+    /// 本库对这些调用使用本方法。其余调用返回该值时仍按 `ok` 视为错误，可由 `is_bypass` 识别。
+    pub fn ok_or_bypass(self) -> Result<(), Self> {
+        if self.is_bypass() {
             Ok(())
+        } else {
+            self.ok()
         }
     }
+
+    /// 是否为 `XCAM_RETURN_BYPASS`，见 `ok_or_bypass`。
+    pub fn is_bypass(&self) -> bool {
+        *self == Self::Code(ffi::XCamReturn::XCAM_RETURN_BYPASS)
+    }
 }
 
 impl fmt::Display for XCamError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use ffi::XCamReturn::*;
-        let desc = match self.0 {
+        let code = match self {
+            Self::Code(code) => *code,
+            Self::Unsupported => return write!(f, "Unsupported"),
+            Self::Timeout => return write!(f, "Timeout"),
+            Self::Busy => return write!(f, "Busy"),
+            Self::DeviceLost => return write!(f, "Device Lost"),
+            Self::Cancelled => return write!(f, "Cancelled"),
+            Self::SdkMismatch {
+                compiled_for,
+                detected,
+                missing_symbols,
+            } => {
+                return write!(
+                    f,
+                    "SDK mismatch: built for {} but the installed librkaiq is {} (missing {}); \
+                     rebuild with the feature matching the installed SDK or install a librkaiq for {}",
+                    compiled_for,
+                    detected.unwrap_or("older or unrecognized"),
+                    missing_symbols.join(", "),
+                    compiled_for
+                )
+            }
+        };
+        let desc = match code {
             XCAM_RETURN_NO_ERROR => "No Error",
             XCAM_RETURN_BYPASS => "ByPass",
             XCAM_RETURN_ERROR_FAILED => "Failed",
@@ -57,16 +127,128 @@ impl From<i32> for XCamError {
             -10 => XCAM_RETURN_ERROR_ORDER,
             -20 => XCAM_RETURN_ERROR_TIMEOUT,
             -21 => XCAM_RETURN_ERROR_OUTOFRANGE,
-            _ => XCAM_RETURN_ERROR_UNKNOWN,
+            _ => {
+                convert::unmapped("XCamReturn", val);
+                XCAM_RETURN_ERROR_UNKNOWN
+            }
         };
-        Self(val)
+        Self::from(val)
     }
 }
 
 impl From<ffi::XCamReturn> for XCamError {
     fn from(val: ffi::XCamReturn) -> Self {
-        Self(val)
+        match val {
+            ffi::XCamReturn::XCAM_RETURN_ERROR_TIMEOUT => Self::Timeout,
+            _ => Self::Code(val),
+        }
     }
 }
 
 impl std::error::Error for XCamError {}
+
+/// 按错误种类选择 `io::ErrorKind`，消息与 `Display` 相同，原错误可通过 `get_ref` 取回。
+impl From<XCamError> for io::Error {
+    fn from(err: XCamError) -> Self {
+        use ffi::XCamReturn::*;
+        let kind = match &err {
+            XCamError::Unsupported => io::ErrorKind::Unsupported,
+            XCamError::Timeout => io::ErrorKind::TimedOut,
+            XCamError::Busy => io::ErrorKind::WouldBlock,
+            XCamError::DeviceLost => io::ErrorKind::NotConnected,
+            XCamError::Cancelled => io::ErrorKind::Interrupted,
+            XCamError::SdkMismatch { .. } => io::ErrorKind::Unsupported,
+            XCamError::Code(code) => match code {
+                XCAM_RETURN_ERROR_PARAM | XCAM_RETURN_ERROR_OUTOFRANGE => {
+                    io::ErrorKind::InvalidInput
+                }
+                XCAM_RETURN_ERROR_MEM => io::ErrorKind::OutOfMemory,
+                _ => io::ErrorKind::Other,
+            },
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bypass() {
+        let bypass = XCamError::from(1);
+        assert!(bypass.is_bypass());
+        assert_eq!(bypass.ok_or_bypass(), Ok(()));
+        assert_eq!(bypass.ok(), Err(bypass));
+        assert_eq!(XCamError::from(0).ok_or_bypass(), Ok(()));
+
+        let failed = XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED);
+        assert!(!failed.is_bypass());
+        assert_eq!(failed.ok_or_bypass(), Err(failed));
+        assert!(!XCamError::Unsupported.is_bypass());
+    }
+
+    #[test]
+    fn test_sdk_mismatch_display() {
+        let err = XCamError::SdkMismatch {
+            compiled_for: "v4_0",
+            detected: None,
+            missing_symbols: &["a", "b"],
+        };
+        assert_eq!(
+            err.to_string(),
+            "SDK mismatch: built for v4_0 but the installed librkaiq is older or unrecognized \
+             (missing a, b); rebuild with the feature matching the installed SDK or install a \
+             librkaiq for v4_0"
+        );
+    }
+
+    #[test]
+    fn test_timeout_normalized() {
+        assert_eq!(XCamError::from(-20), XCamError::Timeout);
+        assert_eq!(
+            XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_TIMEOUT),
+            XCamError::Timeout
+        );
+        assert_eq!(
+            XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_TIMEOUT).ok(),
+            Err(XCamError::Timeout)
+        );
+    }
+
+    #[test]
+    fn test_into_io_error() {
+        let cases = [
+            (XCamError::Unsupported, io::ErrorKind::Unsupported),
+            (XCamError::Timeout, io::ErrorKind::TimedOut),
+            (XCamError::DeviceLost, io::ErrorKind::NotConnected),
+            (XCamError::Cancelled, io::ErrorKind::Interrupted),
+            (
+                XCamError::SdkMismatch {
+                    compiled_for: "v4_0",
+                    detected: Some("v3_0"),
+                    missing_symbols: &["rk_aiq_uapi2_getExpMode"],
+                },
+                io::ErrorKind::Unsupported,
+            ),
+            (
+                XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM),
+                io::ErrorKind::InvalidInput,
+            ),
+            (
+                XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_IOCTL),
+                io::ErrorKind::Other,
+            ),
+        ];
+        for (err, kind) in cases {
+            let msg = err.to_string();
+            let io_err = io::Error::from(err);
+            assert_eq!(io_err.kind(), kind);
+            assert_eq!(io_err.to_string(), msg);
+            assert_eq!(
+                io_err.get_ref().and_then(|e| e.downcast_ref::<XCamError>()),
+                Some(&err)
+            );
+        }
+    }
+}
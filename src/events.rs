@@ -0,0 +1,271 @@
+//! 状态事件
+//!
+//! AIQ 内核会自行改变部分状态，例如出错时退出 HDR、自动对焦放弃搜索。`Context::on_state_event`
+//! 在这些状态变化时调用回调，应用无需自行轮询。
+//!
+//! SDK 的元数据回调只携带帧号，错误回调只携带错误代码，都不报告状态变化，因此全部事件均由
+//! 内部轮询得出：独立线程每隔 `STATE_EVENT_INTERVAL` 读取一次状态，与上一次比较后产生事件。
+//! 间隔内发生又恢复的变化不会被观察到，事件相对实际变化最多晚一个间隔。各事件的来源如下：
+//!
+//! | 事件 | 来源 |
+//! | --- | --- |
+//! | `WorkingModeChanged` | v4_0、v5_0 查询 SDK 的工作模式；其余版本取最近一次 `prepare` 的模式，只反映本库发起的切换 |
+//! | `AfSearchStarted`、`AfSearchStopped` | `get_af_search_state` |
+//! | `AeConvergenceChanged` | `query_exp_info` 的收敛标志 |
+//! | `AwbConvergenceChanged` | 相邻两次 `get_awb_snapshot` 增益的变化，判据同 `warmup` |
+//!
+//! 读取失败的状态视为未知，不产生事件，恢复读取后与上一次已知的状态比较。
+use super::ae::AutoExposure;
+use super::af::{AfSearchState, AutoFocus};
+use super::awb::AutoWhiteBalance;
+use super::callback::callback_guard;
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::sysctl::awb_stable;
+use super::types::{WbGain, WorkingMode, XCamResult};
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::uapi::uapi_get;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// 轮询状态的间隔。
+pub const STATE_EVENT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 一个描述状态变化的枚举，来源见模块说明。
+///
+/// 今后可能增加新的事件，匹配时须保留通配分支。
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StateEvent {
+    /// 工作模式改变，例如 HDR 出错后回到线性模式。
+    WorkingModeChanged { from: WorkingMode, to: WorkingMode },
+    /// 自动对焦开始搜索。
+    AfSearchStarted,
+    /// 自动对焦停止搜索，`state` 为停止后的状态。
+    AfSearchStopped { state: AfSearchState },
+    /// 自动曝光收敛或重新开始调节。
+    AeConvergenceChanged { converged: bool },
+    /// 自动白平衡稳定或重新开始调节。
+    AwbConvergenceChanged { converged: bool },
+}
+
+/// 一次轮询读取的状态，读取失败的项为 `None`。
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) struct StateSnapshot {
+    pub(crate) working_mode: Option<WorkingMode>,
+    pub(crate) af_state: Option<AfSearchState>,
+    pub(crate) ae_converged: Option<bool>,
+    pub(crate) awb_gain: Option<WbGain>,
+}
+
+impl StateSnapshot {
+    /// 读取 `ctx` 的状态，工作模式由调用者提供。
+    pub(crate) fn read<C>(ctx: &C, working_mode: Option<WorkingMode>) -> Self
+    where
+        C: AutoExposure + AutoFocus + AutoWhiteBalance,
+    {
+        Self {
+            working_mode,
+            af_state: ctx.get_af_search_state().ok(),
+            ae_converged: ctx.query_exp_info().ok().map(|info| info.converged),
+            awb_gain: ctx.get_awb_snapshot().ok().map(|s| s.gain),
+        }
+    }
+}
+
+/// 比较相邻两次读取的状态并产生事件。
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StateTracker {
+    working_mode: Option<WorkingMode>,
+    af_state: Option<AfSearchState>,
+    ae_converged: Option<bool>,
+    awb_gain: Option<WbGain>,
+    awb_converged: Option<bool>,
+}
+
+/// 已知的状态由 `last` 变为 `cur` 时返回 `(原状态, 新状态)`，并记录新状态。
+fn changed<T: Copy + PartialEq>(last: &mut Option<T>, cur: Option<T>) -> Option<(T, T)> {
+    let cur = cur?;
+    let prev = last.replace(cur)?;
+    (prev != cur).then_some((prev, cur))
+}
+
+impl StateTracker {
+    /// 记录一次读取，返回与上一次已知状态相比发生的事件，首次读取只记录不产生事件。
+    pub(crate) fn update(&mut self, cur: StateSnapshot) -> Vec<StateEvent> {
+        let mut events = Vec::new();
+        if let Some((from, to)) = changed(&mut self.working_mode, cur.working_mode) {
+            events.push(StateEvent::WorkingModeChanged { from, to });
+        }
+        let searching = |s: AfSearchState| s == AfSearchState::Running;
+        if let Some((prev, state)) = changed(&mut self.af_state, cur.af_state) {
+            match (searching(prev), searching(state)) {
+                (false, true) => events.push(StateEvent::AfSearchStarted),
+                (true, false) => events.push(StateEvent::AfSearchStopped { state }),
+                _ => {}
+            }
+        }
+        if let Some((_, converged)) = changed(&mut self.ae_converged, cur.ae_converged) {
+            events.push(StateEvent::AeConvergenceChanged { converged });
+        }
+        if let Some(gain) = cur.awb_gain {
+            let stable = self.awb_gain.map(|prev| awb_stable(&prev, &gain));
+            self.awb_gain = Some(gain);
+            if let Some((_, converged)) = changed(&mut self.awb_converged, stable) {
+                events.push(StateEvent::AwbConvergenceChanged { converged });
+            }
+        }
+        events
+    }
+}
+
+/// 状态事件的订阅，由 `Context::on_state_event` 创建。
+///
+/// 被释放时取消订阅并等待轮询线程退出；线程只持有上下文的弱引用，上下文释放后同样退出。
+/// 在回调中释放时不等待，轮询线程在回调返回后退出。
+pub struct StateEventSubscription {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for StateEventSubscription {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            // 在回调中释放时位于轮询线程自身，不能等待自身退出，只释放句柄。
+            if handle.thread().id() == thread::current().id() {
+                return;
+            }
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Context {
+    /// 订阅状态变化，每个事件调用一次 `f`，事件及其来源见模块说明。
+    ///
+    /// 回调在轮询线程中执行；回调发生 panic 时与 SDK 回调同样被捕获并置位回调异常标志，
+    /// 订阅继续有效。释放返回的 `StateEventSubscription` 即取消订阅。
+    pub fn on_state_event<F>(self: &Arc<Self>, f: F) -> XCamResult<StateEventSubscription>
+    where
+        F: FnMut(StateEvent) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let ctx = Arc::downgrade(self);
+        let flag = Arc::clone(&stop);
+        let handle = thread::Builder::new()
+            .name("rkaiq-state-event".into())
+            .spawn(move || run_state_events(ctx, flag, f))
+            .map_err(|_| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_THREAD))?;
+        Ok(StateEventSubscription {
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    /// 当前工作模式，见模块说明。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub(crate) fn current_working_mode(&self) -> Option<WorkingMode> {
+        uapi_get!(ffi::rk_aiq_uapi2_sysctl_getWorkingMode(self) -> ffi::rk_aiq_working_mode_t)
+            .ok()
+            .map(WorkingMode::from)
+    }
+
+    #[cfg(not(any(feature = "v4_0", feature = "v5_0")))]
+    pub(crate) fn current_working_mode(&self) -> Option<WorkingMode> {
+        self.prepared.lock().unwrap().as_ref().map(|r| r.mode)
+    }
+}
+
+fn run_state_events<F>(ctx: Weak<Context>, stop: Arc<AtomicBool>, mut f: F)
+where
+    F: FnMut(StateEvent),
+{
+    let mut tracker = StateTracker::default();
+    while !stop.load(Ordering::Acquire) {
+        let Some(ctx) = ctx.upgrade() else { break };
+        let snapshot = StateSnapshot::read(&*ctx, ctx.current_working_mode());
+        drop(ctx);
+        for event in tracker.update(snapshot) {
+            callback_guard("state event", (), || f(event));
+        }
+        thread::park_timeout(STATE_EVENT_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_state_tracker() {
+        let ctx = MockContext::new();
+        let mut tracker = StateTracker::default();
+        let read = |ctx: &MockContext, mode| StateSnapshot::read(ctx, Some(mode));
+        assert!(tracker.update(read(&ctx, WorkingMode::IspHdr2)).is_empty());
+
+        {
+            let mut state = ctx.state.borrow_mut();
+            state.af_search_state = AfSearchState::Running;
+            state.exp_info.converged = !state.exp_info.converged;
+        }
+        let converged = ctx.state.borrow().exp_info.converged;
+        assert_eq!(
+            tracker.update(read(&ctx, WorkingMode::Normal)),
+            [
+                StateEvent::WorkingModeChanged {
+                    from: WorkingMode::IspHdr2,
+                    to: WorkingMode::Normal
+                },
+                StateEvent::AfSearchStarted,
+                StateEvent::AeConvergenceChanged { converged },
+            ]
+        );
+
+        // 读取失败时不产生事件，恢复后与上一次已知的状态比较。
+        ctx.fail("get_af_search_state");
+        ctx.state.borrow_mut().af_search_state = AfSearchState::Invalid;
+        assert!(tracker.update(read(&ctx, WorkingMode::Normal)).is_empty());
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().af_search_state = AfSearchState::Invalid;
+        ctx.state.borrow_mut().exp_info.converged = converged;
+        assert_eq!(
+            tracker.update(read(&ctx, WorkingMode::Normal)),
+            [StateEvent::AfSearchStopped {
+                state: AfSearchState::Invalid
+            }]
+        );
+
+        // 白平衡增益连续两次读取后才能判断是否稳定。
+        ctx.state.borrow_mut().wb_gain.rgain *= 1.5;
+        assert_eq!(
+            tracker.update(read(&ctx, WorkingMode::Normal)),
+            [StateEvent::AwbConvergenceChanged { converged: false }]
+        );
+    }
+
+    #[test]
+    fn test_drop_subscription_in_callback() {
+        // 在轮询线程中释放订阅时不等待自身，停止标志已置位。
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let (tx, rx) = mpsc::channel::<StateEventSubscription>();
+        let (done_tx, done_rx) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            drop(rx.recv().unwrap());
+            done_tx.send(flag.load(Ordering::Acquire)).unwrap();
+        });
+        tx.send(StateEventSubscription {
+            stop,
+            handle: Some(handle),
+        })
+        .unwrap();
+        assert_eq!(done_rx.recv_timeout(Duration::from_secs(1)), Ok(true));
+    }
+}
@@ -3,22 +3,59 @@
 //! 光学系统、电子扫描系统失真而引起的斜视畸变、枕形、桶形畸变等，都可能使图像产生几何特性失真。
 //! 图像的畸变矫正是以某种变换方式将畸变图像转换为理想图像的过程。
 //! 该模块对x和y方向的图像畸变进行校正。
+use super::capability::Capabilities;
 use super::context::Context;
 use super::error::XCamError;
 use super::ffi;
+use super::shared::SharedContext;
 use super::types::XCamResult;
+use super::uapi::uapi_call;
+use std::sync::atomic::Ordering;
 
 pub trait FEC {
     fn enable_fec(&self) -> XCamResult<()>;
     fn disable_fec(&self) -> XCamResult<()>;
+
+    /// 获取 FEC 是否开启。
+    ///
+    /// SDK 没有读取开关的接口，只反映通过 `enable_fec`、`disable_fec` 的设置，
+    /// 由 IQ 文件开启而未经本库设置时为 `false`。
+    fn is_fec_enabled(&self) -> XCamResult<bool> {
+        Err(XCamError::Unsupported)
+    }
 }
 
 impl FEC for Context {
     fn enable_fec(&self) -> XCamResult<()> {
-        unsafe { XCamError::from(ffi::rk_aiq_uapi2_setFecEn(self.internal.as_ptr(), true)).ok() }
+        self.require(Capabilities::FEC)?;
+        uapi_call!(ffi::rk_aiq_uapi2_setFecEn(self, true))?;
+        self.fec_enabled.store(true, Ordering::Relaxed);
+        Ok(())
     }
 
     fn disable_fec(&self) -> XCamResult<()> {
-        unsafe { XCamError::from(ffi::rk_aiq_uapi2_setFecEn(self.internal.as_ptr(), false)).ok() }
+        self.require(Capabilities::FEC)?;
+        uapi_call!(ffi::rk_aiq_uapi2_setFecEn(self, false))?;
+        self.fec_enabled.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn is_fec_enabled(&self) -> XCamResult<bool> {
+        self.require(Capabilities::FEC)?;
+        Ok(self.fec_enabled.load(Ordering::Relaxed))
+    }
+}
+
+impl FEC for SharedContext {
+    fn enable_fec(&self) -> XCamResult<()> {
+        self.inner.enable_fec()
+    }
+
+    fn disable_fec(&self) -> XCamResult<()> {
+        self.inner.disable_fec()
+    }
+
+    fn is_fec_enabled(&self) -> XCamResult<bool> {
+        self.inner.is_fec_enabled()
     }
 }
@@ -1,9 +1,38 @@
 //! Gamma
 //!
 //! Gamma 模块对图像进行亮度空间非线性转换以适配输出设备。
+//!
+//! # 线性与 HDR 两条通路
+//!
+//! 线性模式下 Gamma 的输入是白平衡、CCM 之后的线性数据；HDR 模式下多帧合成后先经 DRC
+//! （ISP20 为 TMO）压缩动态范围，Gamma 的输入已是压缩后的数据。两者的亮度分布不同，
+//! 为线性模式标定的曲线在 HDR 模式下会使画面偏亮、对比度不足，因此 IQ 文件为两种模式分别
+//! 标定曲线。ISP 只有一条 Gamma 曲线，手动曲线会覆盖当前模式的标定曲线。
+//!
+//! `Context::set_gamma_curve` 与 `Context::set_hdr_gamma_curve` 分别记录两条通路的手动曲线，
+//! 只有与当前工作模式一致的一条立即写入；另一条保存下来，在以对应模式 `prepare` 后的
+//! `start` 成功时写入。目标通路与当前模式不一致时输出一条警告。
+//!
+//! 记录的曲线在每次 `start` 时重新写入，直到以 `Context::clear_gamma_curves` 清除；以
+//! `set_gamma_coef` 或 `set_gamma_mode` 将 Gamma 切换为手动以外的模式时也会清除，避免下次
+//! `start` 时覆盖新的模式。
 use super::context::Context;
 use super::error::XCamError;
 use super::ffi;
+#[cfg(any(
+    feature = "v2_0",
+    all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    )
+))]
+use super::logging::log_warn;
+use super::shared::SharedContext;
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+))]
+use super::types::DegammaAttr;
 #[cfg(all(
     any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
     feature = "isp_hw_v21"
@@ -24,57 +53,665 @@ use super::types::GammaApiManualV30;
 use super::types::GammaCurveType;
 #[cfg(feature = "v2_0")]
 use super::types::{GammaApiManual, GammaCurveUsrDefine1Para, GammaCurveUsrDefine2Para};
-use super::types::{GammaAttr, GammaCaliDb, GammaMode, XCamResult};
+use super::types::{GammaAttr, GammaCaliDb, GammaMode, OpMode, WorkingMode, XCamResult};
+use super::uapi::uapi_call;
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+))]
+use super::uapi::uapi_get;
+
+#[cfg(any(
+    feature = "v2_0",
+    all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    )
+))]
+use super::uapi::log_failure;
 
 /// 一个描述 Gamma 控制的契定。
 pub trait Gamma {
     /// 获取伽玛。
+    ///
+    /// # Note
+    ///
+    /// 读取完整的曲线属性，Gamma 算法处理一帧期间调用会阻塞，最长约一个帧周期。
     fn get_gamma_coef(&self) -> XCamResult<GammaAttr>;
 
     /// 设置伽玛。
     fn set_gamma_coef<T: Into<GammaAttr>>(&self, gamma_attr: T) -> XCamResult<()>;
+
+    /// 获取 Gamma 的工作模式，取自 Gamma 属性；Gamma 关闭时为 `OpMode::Invalid`。
+    fn get_gamma_mode(&self) -> XCamResult<OpMode> {
+        gamma_op_mode(&self.get_gamma_coef()?)
+    }
+
+    /// 设置 Gamma 的工作模式，`OpMode::Invalid` 表示关闭 Gamma，切换为手动时沿用当前曲线。
+    ///
+    /// Gamma 属性中没有与 `OpMode::Auto` 对应的取值，与当前模式相同时不写入，否则只能切换为
+    /// `OpMode::Manual` 或 `OpMode::Invalid`，其余模式返回 `XCAM_RETURN_ERROR_PARAM`。
+    fn set_gamma_mode(&self, mode: OpMode) -> XCamResult<()> {
+        let mut attr = self.get_gamma_coef()?;
+        if gamma_op_mode(&attr)? == mode {
+            return Ok(());
+        }
+        write_gamma_op_mode(&mut attr, mode)?;
+        self.set_gamma_coef(attr)
+    }
+
+    /// 获取 Gamma 属性中的手动曲线，长度为 `GAMMA_CURVE_LEN`。
+    ///
+    /// 不支持手动曲线的版本返回 `XCamError::Unsupported`。
+    fn get_gamma_manual_curve(&self) -> XCamResult<Vec<u16>> {
+        read_manual_curve(&self.get_gamma_coef()?)
+    }
+
+    /// 切换为手动 Gamma 并写入曲线 `curve`，不区分线性与 HDR 通路，见 `Context::set_gamma_curve`。
+    ///
+    /// 曲线长度须为 `GAMMA_CURVE_LEN`，否则返回 `XCAM_RETURN_ERROR_PARAM`；不支持手动曲线的
+    /// 版本返回 `XCamError::Unsupported`。
+    fn set_gamma_manual_curve(&self, curve: &[u16]) -> XCamResult<()> {
+        check_gamma_curve(curve)?;
+        self.set_gamma_coef(checked_curve_attr(curve)?)
+    }
+
+    /// 获取 Degamma 的开关与曲线。
+    ///
+    /// 仅 v3_0 及以上版本的 ISP21、ISP30 有 Degamma 模块，其余版本返回 `XCamError::Unsupported`。
+    fn get_degamma(&self) -> XCamResult<Degamma> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 设置 Degamma，用于还原传感器输出的压扩（companding）曲线。
+    ///
+    /// Degamma 位于管线前端，将传感器数据线性化后再交给白平衡、CCM 等模块；Gamma 位于管线末端，
+    /// 将线性数据转换为输出曲线。两者相互独立，设置 Degamma 不会修改 Gamma，但 Gamma 曲线
+    /// 是按线性输入标定的，传感器压扩而 Degamma 关闭时画面会偏亮、偏灰。
+    ///
+    /// # Parameters
+    /// * `enable` - 是否启用，关闭时保留曲线。
+    /// * `curve` - 曲线，`None` 时沿用当前曲线；长度须为 `DEGAMMA_CURVE_LEN`，否则返回
+    ///   `XCAM_RETURN_ERROR_PARAM`，各点不超过 `DEGAMMA_CURVE_MAX`，否则返回
+    ///   `XCAM_RETURN_ERROR_OUTOFRANGE`。三个颜色通道使用同一条曲线。
+    fn set_degamma(&self, enable: bool, curve: Option<&[u16]>) -> XCamResult<()> {
+        let _ = (enable, curve);
+        Err(XCamError::Unsupported)
+    }
 }
 
-impl Gamma for Context {
-    fn get_gamma_coef(&self) -> XCamResult<GammaAttr> {
-        #[cfg(feature = "v2_0")]
-        unsafe {
-            let mut gamma_attr = GammaAttr::default();
-            XCamError::from(ffi::rk_aiq_user_api_agamma_GetAttrib(
-                self.internal.as_ptr(),
-                &mut gamma_attr,
-            ))
-            .ok()
-            .map(|_| gamma_attr)
+/// 一个描述 Gamma 曲线所属通路的枚举，见模块说明。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GammaPath {
+    /// 线性（非 HDR）模式。
+    Linear,
+    /// HDR 模式，DRC 之后。
+    Hdr,
+}
+
+impl GammaPath {
+    /// 工作模式 `mode` 使用的通路。
+    pub fn for_mode(mode: WorkingMode) -> Self {
+        match mode {
+            WorkingMode::Normal => Self::Linear,
+            _ => Self::Hdr,
         }
-        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
-        unsafe {
-            let mut gamma_attr = GammaAttr::default();
-            XCamError::from(ffi::rk_aiq_user_api2_agamma_GetAttrib(
-                self.internal.as_ptr(),
-                &mut gamma_attr,
-            ))
-            .ok()
-            .map(|_| gamma_attr)
+    }
+}
+
+/// 手动 Gamma 曲线的点数。
+#[cfg(any(
+    feature = "v2_0",
+    all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        feature = "isp_hw_v21"
+    )
+))]
+pub const GAMMA_CURVE_LEN: usize = 45;
+
+/// 手动 Gamma 曲线的点数。
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    feature = "isp_hw_v30",
+    not(feature = "isp_hw_v21")
+))]
+pub const GAMMA_CURVE_LEN: usize = 49;
+
+/// 两条通路的手动曲线，未设置时为 `None`。
+#[cfg_attr(
+    not(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    )),
+    allow(dead_code)
+)]
+#[derive(Clone, Debug, Default)]
+pub(crate) struct GammaCurves {
+    linear: Option<Vec<u16>>,
+    hdr: Option<Vec<u16>>,
+}
+
+#[cfg_attr(
+    not(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    )),
+    allow(dead_code)
+)]
+impl GammaCurves {
+    /// 写入属性 `attr` 后调用，`attr` 不是手动模式时清除记录的曲线。
+    fn retain_for(&mut self, attr: &GammaAttr) {
+        if gamma_op_mode(attr) != Ok(OpMode::Manual) {
+            *self = Self::default();
+        }
+    }
+
+    fn get(&self, path: GammaPath) -> Option<&[u16]> {
+        match path {
+            GammaPath::Linear => self.linear.as_deref(),
+            GammaPath::Hdr => self.hdr.as_deref(),
+        }
+    }
+}
+
+/// 检查手动曲线的长度是否为 `GAMMA_CURVE_LEN`，否则返回 `XCAM_RETURN_ERROR_PARAM`；
+/// 不支持手动曲线的版本返回 `XCamError::Unsupported`。
+pub(crate) fn check_gamma_curve(curve: &[u16]) -> XCamResult<()> {
+    #[cfg(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    ))]
+    {
+        if curve.len() != GAMMA_CURVE_LEN {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        Ok(())
+    }
+    #[cfg(not(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    )))]
+    {
+        let _ = curve;
+        Err(XCamError::Unsupported)
+    }
+}
+
+/// 以手动曲线 `curve` 构建 Gamma 属性，曲线长度须已检查；不支持手动曲线的版本返回
+/// `XCamError::Unsupported`。
+fn checked_curve_attr(curve: &[u16]) -> XCamResult<GammaAttr> {
+    #[cfg(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    ))]
+    {
+        Ok(manual_curve_attr(curve))
+    }
+    #[cfg(not(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    )))]
+    {
+        let _ = curve;
+        Err(XCamError::Unsupported)
+    }
+}
+
+/// 读取 Gamma 属性中的手动曲线。
+fn read_manual_curve(attr: &GammaAttr) -> XCamResult<Vec<u16>> {
+    #[cfg(feature = "v2_0")]
+    {
+        Ok(attr
+            .stManual
+            .user2
+            .gamma_table
+            .iter()
+            .map(|&v| v.clamp(0, i32::from(u16::MAX)) as u16)
+            .collect())
+    }
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        feature = "isp_hw_v21"
+    ))]
+    {
+        Ok(attr.atrrV21.stManual.Gamma_curve.to_vec())
+    }
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        feature = "isp_hw_v30",
+        not(feature = "isp_hw_v21")
+    ))]
+    {
+        Ok(attr.atrrV30.stManual.Gamma_curve.to_vec())
+    }
+    #[cfg(not(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    )))]
+    {
+        let _ = attr;
+        Err(XCamError::Unsupported)
+    }
+}
+
+/// 以手动曲线 `curve` 构建 Gamma 属性，曲线长度须已检查。
+#[cfg(any(
+    feature = "v2_0",
+    all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    )
+))]
+fn manual_curve_attr(curve: &[u16]) -> GammaAttr {
+    #[cfg(feature = "v2_0")]
+    {
+        let table: Vec<i32> = curve.iter().map(|&v| i32::from(v)).collect();
+        GammaAttrBuilder::with_manual_usr_define2(&table).build()
+    }
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    ))]
+    {
+        GammaAttrBuilder::with_manual_usr_define2(curve).build()
+    }
+}
+
+/// 记录通路 `path` 的手动曲线，与当前工作模式 `mode` 一致时写入，返回是否已写入。
+///
+/// 尚未 `prepare`（`mode` 为 `None`）时按线性模式处理。
+#[cfg(any(
+    feature = "v2_0",
+    all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    )
+))]
+pub(crate) fn set_path_curve<C: Gamma + ?Sized>(
+    ctx: &C,
+    curves: &mut GammaCurves,
+    mode: Option<WorkingMode>,
+    path: GammaPath,
+    curve: &[u16],
+) -> XCamResult<bool> {
+    check_gamma_curve(curve)?;
+    let active = mode.map_or(GammaPath::Linear, GammaPath::for_mode);
+    if active != path {
+        log_warn!(
+            "gamma curve for {:?} path saved but not applied, current path is {:?}",
+            path,
+            active
+        );
+    } else {
+        ctx.set_gamma_coef(manual_curve_attr(curve))?;
+    }
+    let slot = match path {
+        GammaPath::Linear => &mut curves.linear,
+        GammaPath::Hdr => &mut curves.hdr,
+    };
+    *slot = Some(curve.to_vec());
+    Ok(active == path)
+}
+
+/// 写入当前工作模式 `mode` 对应通路的手动曲线，没有记录时不做修改，返回是否已写入。
+#[cfg(any(
+    feature = "v2_0",
+    all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    )
+))]
+pub(crate) fn apply_path_curve<C: Gamma + ?Sized>(
+    ctx: &C,
+    curves: &GammaCurves,
+    mode: Option<WorkingMode>,
+) -> XCamResult<bool> {
+    let path = mode.map_or(GammaPath::Linear, GammaPath::for_mode);
+    match curves.get(path) {
+        Some(curve) => ctx.set_gamma_coef(manual_curve_attr(curve)).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// 一个描述 Degamma 状态的类型。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Degamma {
+    /// 是否启用。
+    pub enable: bool,
+    /// 曲线，取自绿色通道。
+    pub curve: Vec<u16>,
+}
+
+/// Degamma 曲线的点数。
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+))]
+pub const DEGAMMA_CURVE_LEN: usize = 17;
+
+/// Degamma 曲线各点的上限（12 位）。
+pub const DEGAMMA_CURVE_MAX: u16 = 4095;
+
+/// 检查 Degamma 曲线的长度与取值。
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+))]
+fn check_degamma_curve(curve: &[u16]) -> XCamResult<()> {
+    if curve.len() != DEGAMMA_CURVE_LEN {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    if curve.iter().any(|&v| v > DEGAMMA_CURVE_MAX) {
+        return Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+    }
+    Ok(())
+}
+
+/// 将开关与曲线写入 Degamma 属性，曲线须已通过检查。
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+))]
+fn write_degamma(attr: &mut DegammaAttr, enable: bool, curve: Option<&[u16]>) {
+    attr.mode = ffi::degamma_op_mode_t::RK_AIQ_DEGAMMA_MODE_MANUAL;
+    attr.stManual.en = enable;
+    if let Some(curve) = curve {
+        let manual = &mut attr.stManual;
+        for (i, &v) in curve.iter().enumerate() {
+            manual.curve_R[i] = v.into();
+            manual.curve_G[i] = v.into();
+            manual.curve_B[i] = v.into();
+        }
+    }
+}
+
+/// 从 Degamma 属性读取开关与曲线。
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+))]
+fn read_degamma(attr: &DegammaAttr) -> Degamma {
+    Degamma {
+        enable: attr.mode != ffi::degamma_op_mode_t::RK_AIQ_DEGAMMA_MODE_OFF && attr.stManual.en,
+        curve: attr
+            .stManual
+            .curve_G
+            .iter()
+            .map(|&v| v.clamp(0, i32::from(u16::MAX)) as u16)
+            .collect(),
+    }
+}
+
+/// 取出 Gamma 属性中的工作模式。
+fn gamma_op_mode(attr: &GammaAttr) -> XCamResult<OpMode> {
+    #[cfg(feature = "v2_0")]
+    {
+        use GammaMode::*;
+        Ok(match attr.mode {
+            GAMMA_MODE_MANUAL => OpMode::Manual,
+            GAMMA_MODE_OFF => OpMode::Invalid,
+            _ => OpMode::Auto,
+        })
+    }
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    ))]
+    {
+        use GammaMode::*;
+        #[cfg(feature = "isp_hw_v21")]
+        let mode = attr.atrrV21.mode;
+        #[cfg(all(feature = "isp_hw_v30", not(feature = "isp_hw_v21")))]
+        let mode = attr.atrrV30.mode;
+        Ok(match mode {
+            RK_AIQ_GAMMA_MODE_MANUAL => OpMode::Manual,
+            RK_AIQ_GAMMA_MODE_OFF => OpMode::Invalid,
+            _ => OpMode::Auto,
+        })
+    }
+    #[cfg(not(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    )))]
+    {
+        let _ = attr;
+        Err(XCamError::Unsupported)
+    }
+}
+
+/// 将工作模式写入 Gamma 属性，只支持 `OpMode::Manual` 与 `OpMode::Invalid`。
+fn write_gamma_op_mode(attr: &mut GammaAttr, mode: OpMode) -> XCamResult<()> {
+    #[cfg(feature = "v2_0")]
+    {
+        use GammaMode::*;
+        attr.mode = match mode {
+            OpMode::Manual => GAMMA_MODE_MANUAL,
+            OpMode::Invalid => GAMMA_MODE_OFF,
+            _ => return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM)),
+        };
+        Ok(())
+    }
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    ))]
+    {
+        use GammaMode::*;
+        let value = match mode {
+            OpMode::Manual => RK_AIQ_GAMMA_MODE_MANUAL,
+            OpMode::Invalid => RK_AIQ_GAMMA_MODE_OFF,
+            _ => return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM)),
+        };
+        #[cfg(feature = "isp_hw_v21")]
+        {
+            attr.atrrV21.mode = value;
         }
+        #[cfg(all(feature = "isp_hw_v30", not(feature = "isp_hw_v21")))]
+        {
+            attr.atrrV30.mode = value;
+        }
+        Ok(())
+    }
+    #[cfg(not(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    )))]
+    {
+        let _ = (attr, mode);
+        Err(XCamError::Unsupported)
     }
+}
 
+impl Gamma for Context {
+    #[cfg(feature = "v2_0")]
+    fn get_gamma_coef(&self) -> XCamResult<GammaAttr> {
+        let mut gamma_attr = GammaAttr::default();
+        uapi_call!(ffi::rk_aiq_user_api_agamma_GetAttrib(self, &mut gamma_attr)).map(|_| gamma_attr)
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn get_gamma_coef(&self) -> XCamResult<GammaAttr> {
+        let mut gamma_attr = GammaAttr::default();
+        uapi_call!(ffi::rk_aiq_user_api2_agamma_GetAttrib(
+            self,
+            &mut gamma_attr
+        ))
+        .map(|_| gamma_attr)
+    }
+
+    #[cfg(feature = "v2_0")]
     fn set_gamma_coef<T: Into<GammaAttr>>(&self, gamma_attr: T) -> XCamResult<()> {
-        #[cfg(feature = "v2_0")]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_user_api_agamma_SetAttrib(
-                self.internal.as_ptr(),
-                gamma_attr.into(),
-            ))
-            .ok()
+        let attr = gamma_attr.into();
+        uapi_call!(ffi::rk_aiq_user_api_agamma_SetAttrib(self, attr))?;
+        // 通路曲线以手动模式写入，不会进入这里的加锁分支。
+        self.gamma_curves.lock().unwrap().retain_for(&attr);
+        Ok(())
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn set_gamma_coef<T: Into<GammaAttr>>(&self, gamma_attr: T) -> XCamResult<()> {
+        let attr = gamma_attr.into();
+        uapi_call!(ffi::rk_aiq_user_api2_agamma_SetAttrib(self, attr))?;
+        // 通路曲线以手动模式写入，不会进入这里的加锁分支。
+        self.gamma_curves.lock().unwrap().retain_for(&attr);
+        Ok(())
+    }
+
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    ))]
+    fn get_degamma(&self) -> XCamResult<Degamma> {
+        self.get_degamma_attr().map(|attr| read_degamma(&attr))
+    }
+
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    ))]
+    fn set_degamma(&self, enable: bool, curve: Option<&[u16]>) -> XCamResult<()> {
+        if let Some(curve) = curve {
+            check_degamma_curve(curve)?;
         }
-        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_user_api2_agamma_SetAttrib(
-                self.internal.as_ptr(),
-                gamma_attr.into(),
-            ))
-            .ok()
+        let mut attr = self.get_degamma_attr()?;
+        write_degamma(&mut attr, enable, curve);
+        uapi_call!(ffi::rk_aiq_user_api2_adegamma_SetAttrib(self, attr))
+    }
+}
+
+impl Gamma for SharedContext {
+    fn get_gamma_coef(&self) -> XCamResult<GammaAttr> {
+        self.inner.get_gamma_coef()
+    }
+
+    fn set_gamma_coef<T: Into<GammaAttr>>(&self, gamma_attr: T) -> XCamResult<()> {
+        self.inner.set_gamma_coef(gamma_attr)
+    }
+
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    ))]
+    fn get_degamma(&self) -> XCamResult<Degamma> {
+        self.inner.get_degamma()
+    }
+
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+    ))]
+    fn set_degamma(&self, enable: bool, curve: Option<&[u16]>) -> XCamResult<()> {
+        self.inner.set_degamma(enable, curve)
+    }
+}
+
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+))]
+impl Context {
+    fn get_degamma_attr(&self) -> XCamResult<DegammaAttr> {
+        uapi_get!(ffi::rk_aiq_user_api2_adegamma_GetAttrib(self) -> DegammaAttr)
+    }
+}
+
+impl Context {
+    /// 设置线性模式的手动 Gamma 曲线，返回是否已写入，见模块说明。
+    ///
+    /// 当前为 HDR 模式时只记录曲线并输出警告，在以线性模式 `prepare` 后的 `start` 成功时写入。
+    /// 曲线长度须为 `GAMMA_CURVE_LEN`，否则返回 `XCAM_RETURN_ERROR_PARAM`；不支持手动曲线的
+    /// 版本返回 `XCamError::Unsupported`。
+    pub fn set_gamma_curve(&self, curve: &[u16]) -> XCamResult<bool> {
+        self.set_gamma_path_curve(GammaPath::Linear, curve)
+    }
+
+    /// 设置 HDR 模式的手动 Gamma 曲线，返回是否已写入，见 `set_gamma_curve`。
+    pub fn set_hdr_gamma_curve(&self, curve: &[u16]) -> XCamResult<bool> {
+        self.set_gamma_path_curve(GammaPath::Hdr, curve)
+    }
+
+    #[cfg(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    ))]
+    fn set_gamma_path_curve(&self, path: GammaPath, curve: &[u16]) -> XCamResult<bool> {
+        let mode = self.current_working_mode();
+        set_path_curve(
+            self,
+            &mut self.gamma_curves.lock().unwrap(),
+            mode,
+            path,
+            curve,
+        )
+    }
+
+    #[cfg(not(any(
+        feature = "v2_0",
+        all(
+            any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+            any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+        )
+    )))]
+    fn set_gamma_path_curve(&self, _path: GammaPath, _curve: &[u16]) -> XCamResult<bool> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 清除 `set_gamma_curve` 与 `set_hdr_gamma_curve` 记录的两条曲线，不修改当前的 Gamma 属性。
+    ///
+    /// 清除后 `start` 不再写入手动曲线。
+    pub fn clear_gamma_curves(&self) {
+        *self.gamma_curves.lock().unwrap() = GammaCurves::default();
+    }
+
+    /// `start` 成功后写入当前工作模式对应通路的手动曲线，失败时输出到日志。
+    pub(crate) fn apply_gamma_curve(&self) {
+        #[cfg(any(
+            feature = "v2_0",
+            all(
+                any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+                any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+            )
+        ))]
+        {
+            let mode = self.current_working_mode();
+            let curves = self.gamma_curves.lock().unwrap();
+            let _ = log_failure(
+                Some(&self.label()),
+                "apply_gamma_curve",
+                apply_path_curve(self, &curves, mode),
+            );
         }
     }
 }
@@ -288,3 +925,115 @@ impl Default for GammaAttrBuilder {
         Self::new()
     }
 }
+
+#[cfg(all(
+    test,
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    any(feature = "isp_hw_v21", feature = "isp_hw_v30")
+))]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+
+    #[test]
+    fn test_gamma_paths() {
+        let ctx = MockContext::new();
+        let mut curves = GammaCurves::default();
+        let linear: Vec<u16> = (0..GAMMA_CURVE_LEN as u16).map(|i| i * 80).collect();
+        let hdr: Vec<u16> = (0..GAMMA_CURVE_LEN as u16).map(|i| i * 60).collect();
+        let hdr2 = Some(WorkingMode::IspHdr2);
+        assert_eq!(
+            set_path_curve(&ctx, &mut curves, hdr2, GammaPath::Linear, &linear[1..]),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+
+        // HDR 模式下线性曲线只记录，不写入。
+        assert_eq!(
+            set_path_curve(&ctx, &mut curves, hdr2, GammaPath::Linear, &linear),
+            Ok(false)
+        );
+        assert!(!ctx.calls().contains(&"set_gamma_coef"));
+        assert_eq!(
+            set_path_curve(&ctx, &mut curves, hdr2, GammaPath::Hdr, &hdr),
+            Ok(true)
+        );
+        assert_eq!(ctx.get_gamma_mode(), Ok(OpMode::Manual));
+        assert_eq!(manual_curve(&ctx), hdr);
+
+        // 切换到线性模式后写入线性曲线。
+        let normal = Some(WorkingMode::Normal);
+        assert_eq!(apply_path_curve(&ctx, &curves, normal), Ok(true));
+        assert_eq!(manual_curve(&ctx), linear);
+        assert_eq!(
+            apply_path_curve(&ctx, &GammaCurves::default(), normal),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn test_gamma_curves_retain() {
+        let ctx = MockContext::new();
+        let mut curves = GammaCurves::default();
+        let linear: Vec<u16> = (0..GAMMA_CURVE_LEN as u16).map(|i| i * 80).collect();
+        let normal = Some(WorkingMode::Normal);
+        assert_eq!(
+            set_path_curve(&ctx, &mut curves, normal, GammaPath::Linear, &linear),
+            Ok(true)
+        );
+
+        // 手动模式保留记录。
+        curves.retain_for(&ctx.get_gamma_coef().unwrap());
+        assert_eq!(curves.get(GammaPath::Linear), Some(&linear[..]));
+
+        // 切换为其他模式后清除，start 时不再写入。
+        ctx.set_gamma_mode(OpMode::Invalid).unwrap();
+        curves.retain_for(&ctx.get_gamma_coef().unwrap());
+        assert_eq!(curves.get(GammaPath::Linear), None);
+        assert_eq!(apply_path_curve(&ctx, &curves, normal), Ok(false));
+        assert_eq!(ctx.get_gamma_mode(), Ok(OpMode::Invalid));
+    }
+
+    fn manual_curve(ctx: &MockContext) -> Vec<u16> {
+        let attr = ctx.get_gamma_coef().unwrap();
+        #[cfg(feature = "isp_hw_v21")]
+        let curve = attr.atrrV21.stManual.Gamma_curve;
+        #[cfg(all(feature = "isp_hw_v30", not(feature = "isp_hw_v21")))]
+        let curve = attr.atrrV30.stManual.Gamma_curve;
+        curve.to_vec()
+    }
+
+    #[test]
+    fn test_degamma_round_trip() {
+        let curve: Vec<u16> = (0..DEGAMMA_CURVE_LEN as u16).map(|i| i * 255).collect();
+        assert_eq!(check_degamma_curve(&curve), Ok(()));
+        assert_eq!(
+            check_degamma_curve(&curve[1..]),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        let mut bad = curve.clone();
+        bad[16] = DEGAMMA_CURVE_MAX + 1;
+        assert_eq!(
+            check_degamma_curve(&bad),
+            Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE
+            ))
+        );
+
+        let mut attr = DegammaAttr::default();
+        write_degamma(&mut attr, true, Some(&curve));
+        assert_eq!(attr.stManual.curve_R, attr.stManual.curve_B);
+        assert_eq!(
+            read_degamma(&attr),
+            Degamma {
+                enable: true,
+                curve: curve.clone(),
+            }
+        );
+
+        // 关闭时保留曲线。
+        write_degamma(&mut attr, false, None);
+        let off = read_degamma(&attr);
+        assert!(!off.enable);
+        assert_eq!(off.curve, curve);
+    }
+}
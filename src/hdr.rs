@@ -5,9 +5,18 @@
 /// 技术更大曝光动态范围（即更大的明暗差别）图像的一种技术。
 /// HDR 的目的就是要正确地还原出超出现有设备动态范围的现实场景光亮比例。
 use super::context::Context;
+use super::display::enum_str;
 use super::error::XCamError;
 use super::ffi;
-use super::types::{OpMode, XCamResult};
+use super::shared::SharedContext;
+use super::sysctl::{self, SystemControl};
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    feature = "isp_hw_v30"
+))]
+use super::types::DrcAttr;
+use super::types::{ModuleId, OpMode, SensorDescriptor, StaticInfo, WorkingMode, XCamResult};
+use super::uapi::{uapi_call, uapi_get};
 
 pub trait HighDynamicRange {
     /// 获取 HDR 工作模式。
@@ -21,34 +30,304 @@ pub trait HighDynamicRange {
 
     /// 设置手动模式下的 HDR 强度。
     fn set_hdr_strth(&self, enabled: bool, level: u32) -> XCamResult<()>;
+
+    /// 获取 DRC 的手动属性。
+    ///
+    /// 仅 v3_0 及以上版本的 ISP30 支持，其余版本返回 `XCamError::Unsupported`。
+    fn get_drc_manual_attrib(&self) -> XCamResult<DrcManualAttrib> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 设置 DRC 的手动属性并切换为手动模式，属性须通过 `check_drc_manual_attrib`。
+    fn set_drc_manual_attrib(&self, attr: &DrcManualAttrib) -> XCamResult<()> {
+        check_drc_manual_attrib(attr)?;
+        Err(XCamError::Unsupported)
+    }
+
+    /// 以单一强度设置 DRC，换算见 `drc_strength_levels`，其余手动属性保持不变。
+    ///
+    /// # Parameters
+    /// * `strength` - 强度，超出 [0,1] 时取边界值，NaN 返回 `XCAM_RETURN_ERROR_PARAM`。
+    fn set_drc_strength(&self, strength: f32) -> XCamResult<()> {
+        let levels = drc_strength_levels(strength)?;
+        let mut attr = self.get_drc_manual_attrib()?;
+        levels.apply(&mut attr);
+        self.set_drc_manual_attrib(&attr)
+    }
+
+    /// 获取 HDR 长帧与最短帧曝光比的范围 `(min, max)`，见 `hdr_ratio_limits`。
+    ///
+    /// 范围取决于 `prepare` 选择的 HDR 帧数。尚未 `prepare` 时返回 `XCAM_RETURN_ERROR_ORDER`，
+    /// 线性模式返回 `XCamError::Unsupported`。
+    fn get_hdr_ratio_limits(&self) -> XCamResult<(f32, f32)> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 以固定曝光比运行 HDR 自动曝光，曝光路线保持不变。
+    ///
+    /// 写入 AE HDR 属性的 `ExpRatioCtrl`：曝光比类型设为固定，两帧 HDR 的 `M2SRatioFix`
+    /// （Frm1 / Frm0）取 `ratio`；三帧 HDR 的 `M2SRatioFix`（Frm1 / Frm0）与 `L2MRatioFix`
+    /// （Frm2 / Frm1）都取 `ratio` 的平方根。仅 v4_0、v5_0 支持，其余版本返回
+    /// `XCamError::Unsupported`。
+    ///
+    /// # Parameters
+    /// * `ratio` - 长帧与最短帧的曝光比，须在 `get_hdr_ratio_limits` 的范围内，否则返回
+    ///   `XCAM_RETURN_ERROR_OUTOFRANGE`；NaN 返回 `XCAM_RETURN_ERROR_PARAM`。
+    fn set_hdr_exp_ratio(&self, ratio: f32) -> XCamResult<()> {
+        check_hdr_exp_ratio(ratio, self.get_hdr_ratio_limits()?)?;
+        Err(XCamError::Unsupported)
+    }
+}
+
+/// HDR 长帧与最短帧曝光比的范围 `(min, max)`，下限为 1。
+///
+/// 两帧 HDR 的长帧为 Frm1，三帧 HDR 为 Frm2，最短帧都为 Frm0。上限取以下两者中较小的一个：
+///
+/// * 传感器描述：长帧最多积分 `line_periods_per_field − coarse_integration_time_max_margin` 行，
+///   最短帧至少积分 `coarse_integration_time_min` 行；
+/// * `ae_max`：AE 允许的最大曝光比，见 `Context::get_hdr_ratio_limits`，`None` 表示不限制。
+///
+/// 线性模式没有曝光比，与描述中的行数无效时都返回 `XCamError::Unsupported`。
+pub fn hdr_ratio_limits(
+    des: &SensorDescriptor,
+    mode: WorkingMode,
+    ae_max: Option<f32>,
+) -> XCamResult<(f32, f32)> {
+    if mode == WorkingMode::Normal {
+        return Err(XCamError::Unsupported);
+    }
+    let min_lines = des.coarse_integration_time_min.max(1);
+    let long =
+        (des.line_periods_per_field as u32).saturating_sub(des.coarse_integration_time_max_margin);
+    if long < min_lines {
+        return Err(XCamError::Unsupported);
+    }
+    let sensor_max = long as f32 / min_lines as f32;
+    Ok((
+        1.0,
+        ae_max.map_or(sensor_max, |m| sensor_max.min(m.max(1.0))),
+    ))
+}
+
+/// AE HDR 属性中的最大曝光比：两帧 HDR 为 `M2SRatioMax`，三帧 HDR 为
+/// `M2SRatioMax × L2MRatioMax`，取各曝光节点中的最小值，没有节点时为 `None`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+fn ae_hdr_ratio_max(attr: &ffi::Uapi_HdrExpAttrV2_t, mode: WorkingMode) -> Option<f32> {
+    let ratio = &attr.Params.ExpRatioCtrl.ExpRatio;
+    let len = (ratio.array_size.max(0) as usize).min(ratio.M2SRatioMax.len());
+    (0..len)
+        .map(|i| match mode {
+            WorkingMode::IspHdr3 => ratio.M2SRatioMax[i] * ratio.L2MRatioMax[i],
+            _ => ratio.M2SRatioMax[i],
+        })
+        .reduce(f32::min)
+}
+
+/// 将曝光比类型设为固定，并按 HDR 帧数写入各曝光节点的固定曝光比，见 `set_hdr_exp_ratio`。
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+fn write_hdr_exp_ratio(attr: &mut ffi::Uapi_HdrExpAttrV2_t, mode: WorkingMode, ratio: f32) {
+    let ctrl = &mut attr.Params.ExpRatioCtrl;
+    ctrl.ExpRatioType = ffi::CalibDb_HdrAeRatioTypeV2_t::AECV2_HDR_RATIOTYPE_MODE_FIX;
+    let (m2s, l2m) = match mode {
+        WorkingMode::IspHdr3 => (ratio.sqrt(), ratio.sqrt()),
+        _ => (ratio, 1.0),
+    };
+    let fixed = &mut ctrl.ExpRatio;
+    let len = (fixed.array_size.max(0) as usize).min(fixed.M2SRatioFix.len());
+    fixed.M2SRatioFix[..len].fill(m2s);
+    fixed.L2MRatioFix[..len].fill(l2m);
+}
+
+/// 检查曝光比：NaN 返回 `XCAM_RETURN_ERROR_PARAM`，超出 `limits` 返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+pub fn check_hdr_exp_ratio(ratio: f32, limits: (f32, f32)) -> XCamResult<()> {
+    if ratio.is_nan() {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    if !(limits.0..=limits.1).contains(&ratio) {
+        return Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+    }
+    Ok(())
+}
+
+/// 一个描述 DRC 压缩曲线来源的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DrcCompressMode {
+    /// 由全局增益自动生成。
+    Auto,
+    /// 使用 `DrcManualAttrib::compress_curve`。
+    Manual,
+}
+
+enum_str!(DrcCompressMode, "DRC compress mode" {
+    Auto => "auto",
+    Manual => "manual",
+});
+
+/// DRC 曲线的点数。
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    feature = "isp_hw_v30"
+))]
+pub const DRC_CURVE_LEN: usize = 17;
+
+/// DRC 曲线的点数，本版本不支持 DRC。
+#[cfg(not(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    feature = "isp_hw_v30"
+)))]
+pub const DRC_CURVE_LEN: usize = 0;
+
+/// DRC 全局增益的上限。
+pub const DRC_GAIN_MAX: f32 = 8.0;
+
+/// 一个描述 DRC 手动属性的类型。
+///
+/// RK3588 等 ISP30 平台以 DRC 模块取代了早期的 TMO，对 HDR 合成后的数据做全局压缩与局部增益。
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrcManualAttrib {
+    /// 是否启用。
+    pub enable: bool,
+    /// 全局增益，范围：[1, `DRC_GAIN_MAX`]，越大暗部提亮越多。
+    pub gain: f32,
+    /// 增益曲线的形状参数，范围：[0,1]。
+    pub alpha: f32,
+    /// 高光裁剪，范围：[0, 64]。
+    pub clip: f32,
+    /// 局部增益的权重，范围：[0,1]，0 时只做全局压缩。
+    pub local_weight: f32,
+    /// 压缩曲线的来源。
+    pub compress_mode: DrcCompressMode,
+    /// 手动压缩曲线，长度为 `DRC_CURVE_LEN`。
+    pub compress_curve: Vec<u16>,
+    /// 按亮度分段的局部增益曲线，长度为 `DRC_CURVE_LEN`。
+    pub local_gain_curve: Vec<u16>,
+}
+
+/// 检查 DRC 手动属性：曲线长度须为 `DRC_CURVE_LEN`、数值须为有限值，否则返回
+/// `XCAM_RETURN_ERROR_PARAM`；数值超出字段说明的范围时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+pub fn check_drc_manual_attrib(attr: &DrcManualAttrib) -> XCamResult<()> {
+    let values = [attr.gain, attr.alpha, attr.clip, attr.local_weight];
+    if attr.compress_curve.len() != DRC_CURVE_LEN
+        || attr.local_gain_curve.len() != DRC_CURVE_LEN
+        || !values.iter().all(|v| v.is_finite())
+    {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    if !(1.0..=DRC_GAIN_MAX).contains(&attr.gain)
+        || !(0.0..=1.0).contains(&attr.alpha)
+        || !(0.0..=64.0).contains(&attr.clip)
+        || !(0.0..=1.0).contains(&attr.local_weight)
+    {
+        return Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ));
+    }
+    Ok(())
+}
+
+/// 一个描述由单一强度换算出的 DRC 参数的类型。
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrcLevels {
+    /// 全局增益。
+    pub gain: f32,
+    /// 局部增益的权重。
+    pub local_weight: f32,
+}
+
+impl DrcLevels {
+    /// 写入手动属性：强度为 0 时关闭 DRC，否则启用并由全局增益自动生成压缩曲线。
+    pub fn apply(&self, attr: &mut DrcManualAttrib) {
+        attr.enable = self.gain > 1.0;
+        attr.gain = self.gain;
+        attr.local_weight = self.local_weight;
+        attr.compress_mode = DrcCompressMode::Auto;
+    }
+}
+
+/// 将 [0,1] 的强度换算为 DRC 参数。
+///
+/// 全局增益按对数均匀变化，`gain = DRC_GAIN_MAX ^ strength`，滑块每一格提亮的档数相同；
+/// 局部权重与强度相等，强度越高局部对比度保留越多。超出 [0,1] 时取边界值，
+/// NaN 返回 `XCAM_RETURN_ERROR_PARAM`。
+pub fn drc_strength_levels(strength: f32) -> XCamResult<DrcLevels> {
+    if strength.is_nan() {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+    }
+    let s = strength.clamp(0.0, 1.0);
+    Ok(DrcLevels {
+        gain: DRC_GAIN_MAX.powf(s),
+        local_weight: s,
+    })
+}
+
+/// 从 DRC 属性读取手动属性。
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    feature = "isp_hw_v30"
+))]
+fn read_drc_manual(attr: &DrcAttr) -> DrcManualAttrib {
+    let m = &attr.stManualV30;
+    DrcManualAttrib {
+        enable: m.Enable,
+        gain: m.DrcGain.DrcGain,
+        alpha: m.DrcGain.Alpha,
+        clip: m.DrcGain.Clip,
+        local_weight: m.LocalSetting.LocalData.LocalWeit,
+        compress_mode: match m.CompressSetting.Mode {
+            ffi::CompressMode_t::COMPRESS_MANUAL => DrcCompressMode::Manual,
+            _ => DrcCompressMode::Auto,
+        },
+        compress_curve: m.CompressSetting.Manual_curve.to_vec(),
+        local_gain_curve: m
+            .Scale_y
+            .iter()
+            .map(|&v| u16::try_from(v).unwrap_or(0))
+            .collect(),
+    }
+}
+
+/// 将手动属性写入 DRC 属性并切换为手动模式，手动属性须已通过检查。
+#[cfg(all(
+    any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+    feature = "isp_hw_v30"
+))]
+fn write_drc_manual(attr: &mut DrcAttr, manual: &DrcManualAttrib) {
+    attr.opMode = ffi::drc_OpMode_t::DRC_OPMODE_MANU;
+    let m = &mut attr.stManualV30;
+    m.Enable = manual.enable;
+    m.DrcGain.DrcGain = manual.gain;
+    m.DrcGain.Alpha = manual.alpha;
+    m.DrcGain.Clip = manual.clip;
+    m.LocalSetting.LocalData.LocalWeit = manual.local_weight;
+    m.CompressSetting.Mode = match manual.compress_mode {
+        DrcCompressMode::Auto => ffi::CompressMode_t::COMPRESS_AUTO,
+        DrcCompressMode::Manual => ffi::CompressMode_t::COMPRESS_MANUAL,
+    };
+    m.CompressSetting
+        .Manual_curve
+        .copy_from_slice(&manual.compress_curve);
+    for (dst, &v) in m.Scale_y.iter_mut().zip(&manual.local_gain_curve) {
+        *dst = v.into();
+    }
 }
 
 impl HighDynamicRange for Context {
+    #[cfg(feature = "v2_0")]
+    fn get_hdr_mode(&self) -> XCamResult<OpMode> {
+        let mut mode: ffi::opMode_t = Default::default();
+        uapi_call!(ffi::rk_aiq_uapi_getHDRMode(self, &mut mode)).map(|_| mode.into())
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
     fn get_hdr_mode(&self) -> XCamResult<OpMode> {
-        #[cfg(feature = "v2_0")]
-        unsafe {
-            let mut mode: ffi::opMode_t = Default::default();
-            XCamError::from(ffi::rk_aiq_uapi_getHDRMode(
-                self.internal.as_ptr(),
-                &mut mode,
-            ))
-            .ok()
-            .map(|_| mode.into())
-        }
-        // #[cfg(feature = "v3_0")]
-        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
         Ok(OpMode::Auto)
     }
 
     #[cfg(feature = "v2_0")]
     fn set_hdr_mode<T: Into<OpMode>>(&self, mode: T) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setHDRMode(
-                self.internal.as_ptr(),
-                mode.into().into(),
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi_setHDRMode(self, mode.into().into()))
     }
 
     // #[cfg(feature = "v3_0")]
@@ -57,51 +336,537 @@ impl HighDynamicRange for Context {
         Ok(())
     }
 
+    #[cfg(feature = "v2_0")]
     fn get_hdr_strth(&self) -> XCamResult<(bool, u32)> {
         let mut enabled: bool = false;
         let mut level: u32 = 0;
-        #[cfg(feature = "v2_0")]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getMHDRStrth(
-                self.internal.as_ptr(),
-                &mut enabled,
-                &mut level,
-            ))
-            .ok()
-            .map(|_| (enabled, level))
+        uapi_call!(ffi::rk_aiq_uapi_getMHDRStrth(
+            self,
+            &mut enabled,
+            &mut level
+        ))
+        .map(|_| (enabled, level))
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn get_hdr_strth(&self) -> XCamResult<(bool, u32)> {
+        let mut enabled: bool = false;
+        let mut level: u32 = 0;
+        uapi_call!(ffi::rk_aiq_uapi2_getMHDRStrth(
+            self,
+            &mut enabled,
+            &mut level
+        ))
+        .map(|_| (enabled, level))
+    }
+
+    #[cfg(feature = "v2_0")]
+    fn set_hdr_strth(&self, enabled: bool, level: u32) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setMHDRStrth(self, enabled, level))
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn set_hdr_strth(&self, enabled: bool, level: u32) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi2_setMHDRStrth(self, enabled, level))
+    }
+
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        feature = "isp_hw_v30"
+    ))]
+    fn get_drc_manual_attrib(&self) -> XCamResult<DrcManualAttrib> {
+        uapi_get!(ffi::rk_aiq_user_api2_adrc_GetAttrib(self) -> DrcAttr)
+            .map(|attr| read_drc_manual(&attr))
+    }
+
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        feature = "isp_hw_v30"
+    ))]
+    fn set_drc_manual_attrib(&self, manual: &DrcManualAttrib) -> XCamResult<()> {
+        check_drc_manual_attrib(manual)?;
+        let mut attr = uapi_get!(ffi::rk_aiq_user_api2_adrc_GetAttrib(self) -> DrcAttr)?;
+        write_drc_manual(&mut attr, manual);
+        uapi_call!(ffi::rk_aiq_user_api2_adrc_SetAttrib(self, attr))
+    }
+
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn get_hdr_ratio_limits(&self) -> XCamResult<(f32, f32)> {
+        let mode = prepared_mode(self)?;
+        let des = uapi_get!(ffi::rk_aiq_uapi2_sysctl_getSensorDiscrib(self) -> SensorDescriptor)?;
+        hdr_ratio_limits(&des, mode, None)
+    }
+
+    /// 上限另受 AE HDR 属性中的 `M2SRatioMax`、`L2MRatioMax` 限制，见 `hdr_ratio_limits`。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_hdr_ratio_limits(&self) -> XCamResult<(f32, f32)> {
+        let mode = prepared_mode(self)?;
+        let des = uapi_get!(ffi::rk_aiq_uapi2_sysctl_getSensorDiscrib(self) -> SensorDescriptor)?;
+        let attr =
+            uapi_get!(ffi::rk_aiq_user_api2_ae_getHdrExpAttr(self) -> ffi::Uapi_HdrExpAttrV2_t)?;
+        hdr_ratio_limits(&des, mode, ae_hdr_ratio_max(&attr, mode))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_hdr_exp_ratio(&self, ratio: f32) -> XCamResult<()> {
+        check_hdr_exp_ratio(ratio, self.get_hdr_ratio_limits()?)?;
+        let mode = prepared_mode(self)?;
+        let mut attr =
+            uapi_get!(ffi::rk_aiq_user_api2_ae_getHdrExpAttr(self) -> ffi::Uapi_HdrExpAttrV2_t)?;
+        write_hdr_exp_ratio(&mut attr, mode, ratio);
+        uapi_call!(ffi::rk_aiq_user_api2_ae_setHdrExpAttr(self, attr))
+    }
+}
+
+/// `prepare` 选择的工作模式，尚未 `prepare` 时返回 `XCAM_RETURN_ERROR_ORDER`。
+fn prepared_mode(ctx: &Context) -> XCamResult<WorkingMode> {
+    ctx.prepared
+        .lock()
+        .unwrap()
+        .map(|p| p.mode)
+        .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))
+}
+
+impl HighDynamicRange for SharedContext {
+    fn get_hdr_mode(&self) -> XCamResult<OpMode> {
+        self.inner.get_hdr_mode()
+    }
+
+    fn set_hdr_mode<T: Into<OpMode>>(&self, mode: T) -> XCamResult<()> {
+        self.inner.set_hdr_mode(mode)
+    }
+
+    fn get_hdr_strth(&self) -> XCamResult<(bool, u32)> {
+        self.inner.get_hdr_strth()
+    }
+
+    fn set_hdr_strth(&self, enabled: bool, level: u32) -> XCamResult<()> {
+        self.inner.set_hdr_strth(enabled, level)
+    }
+
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        feature = "isp_hw_v30"
+    ))]
+    fn get_drc_manual_attrib(&self) -> XCamResult<DrcManualAttrib> {
+        self.inner.get_drc_manual_attrib()
+    }
+
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        feature = "isp_hw_v30"
+    ))]
+    fn set_drc_manual_attrib(&self, manual: &DrcManualAttrib) -> XCamResult<()> {
+        self.inner.set_drc_manual_attrib(manual)
+    }
+
+    fn get_hdr_ratio_limits(&self) -> XCamResult<(f32, f32)> {
+        self.inner.get_hdr_ratio_limits()
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_hdr_exp_ratio(&self, ratio: f32) -> XCamResult<()> {
+        self.inner.set_hdr_exp_ratio(ratio)
+    }
+}
+
+/// 一个描述传感器 HDR 读出方式的枚举。
+///
+/// 读出方式是传感器输出数据的形式，由传感器驱动决定，只能查询。
+/// 它不同于 `WorkingMode`：后者在 `prepare` 时选择 ISP 合成的帧数（线性、两帧或三帧 HDR），
+/// 而读出方式决定这些曝光帧如何到达 ISP，因而决定了缓冲区的组织方式。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HdrReadout {
+    /// 线性读出，每帧一次曝光。
+    Linear,
+    /// 交错（stagger/DOL）读出：长短曝光按行交错输出，经虚拟通道分离为多路帧，由 ISP 合成。
+    StaggerHdr,
+    /// 传感器内部合成：传感器合成多次曝光后输出单帧（通常为压扩的高位宽数据）。
+    BuiltinHdr,
+}
+
+enum_str!(HdrReadout, "HDR readout" {
+    Linear => "linear",
+    StaggerHdr => "stagger_hdr",
+    BuiltinHdr => "builtin_hdr",
+});
+
+/// 传感器驱动报告的 HDR 模式（`rkmodule_hdr_mode`）。
+const SENSOR_NO_HDR: u32 = 0;
+const SENSOR_HDR_X2: u32 = 5;
+const SENSOR_HDR_X3: u32 = 6;
+const SENSOR_HDR_COMPR: u32 = 10;
+
+impl HdrReadout {
+    /// 由传感器驱动报告的 HDR 模式得到读出方式，未知的取值返回 `None`。
+    pub fn from_sensor_hdr_mode(mode: u32) -> Option<Self> {
+        match mode {
+            SENSOR_NO_HDR => Some(Self::Linear),
+            SENSOR_HDR_X2 | SENSOR_HDR_X3 => Some(Self::StaggerHdr),
+            SENSOR_HDR_COMPR => Some(Self::BuiltinHdr),
+            _ => None,
         }
-        // #[cfg(feature = "v3_0")]
-        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_getMHDRStrth(
-                self.internal.as_ptr(),
-                &mut enabled,
-                &mut level,
-            ))
-            .ok()
-            .map(|_| (enabled, level))
+    }
+
+    /// 由传感器静态信息得到读出方式。
+    ///
+    /// 传感器支持多种输出格式时，优先报告 HDR 读出方式；全部为线性格式时返回 `Linear`。
+    pub fn from_static_info(info: &StaticInfo) -> XCamResult<Self> {
+        let sensor = &info.sensor_info;
+        let count = (sensor.num as usize).min(sensor.support_fmt.len());
+        let mut readout = None;
+        for fmt in &sensor.support_fmt[..count] {
+            match Self::from_sensor_hdr_mode(fmt.hdr_mode) {
+                Some(Self::Linear) => readout = readout.or(Some(Self::Linear)),
+                Some(hdr) => return Ok(hdr),
+                None => {}
+            }
         }
+        readout.ok_or(XCamError::Unsupported)
     }
+}
 
-    fn set_hdr_strth(&self, enabled: bool, level: u32) -> XCamResult<()> {
-        #[cfg(feature = "v2_0")]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setMHDRStrth(
-                self.internal.as_ptr(),
-                enabled,
-                level,
-            ))
-            .ok()
+/// HDR 直通时色调映射使用的手动强度（最弱压缩）。
+const PASSTHROUGH_HDR_STRTH: u32 = 1;
+
+/// 一个记录 HDR 直通所修改模块原有状态的类型。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HdrPassthroughState {
+    /// 色调映射（DRC/TMO）的手动强度设置。
+    pub hdr_strth: (bool, u32),
+    /// Gamma（GOC）模块是否使能。
+    pub gamma_enabled: bool,
+}
+
+impl HdrPassthroughState {
+    /// 捕获 HDR 直通将要修改的模块状态。
+    pub fn capture<C: HighDynamicRange + SystemControl>(ctx: &C) -> XCamResult<Self> {
+        Ok(Self {
+            hdr_strth: ctx.get_hdr_strth()?,
+            gamma_enabled: ctx.is_module_enabled(ModuleId::RK_MODULE_GOC),
+        })
+    }
+
+    /// 恢复捕获的模块状态。
+    pub fn restore<C: HighDynamicRange + SystemControl>(&self, ctx: &C) -> XCamResult<()> {
+        ctx.set_hdr_strth(self.hdr_strth.0, self.hdr_strth.1)?;
+        if self.gamma_enabled {
+            ctx.enable_module(ModuleId::RK_MODULE_GOC)
+        } else {
+            ctx.disable_module(ModuleId::RK_MODULE_GOC)
         }
-        // #[cfg(feature = "v3_0")]
-        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setMHDRStrth(
-                self.internal.as_ptr(),
-                enabled,
-                level,
-            ))
-            .ok()
+    }
+}
+
+/// 切换 HDR 直通状态，`saved` 保存开启前捕获的状态。
+fn switch_passthrough<C: HighDynamicRange + SystemControl>(
+    ctx: &C,
+    saved: &mut Option<HdrPassthroughState>,
+    on: bool,
+) -> XCamResult<()> {
+    match (on, saved.as_ref()) {
+        (true, None) => {
+            let prior = HdrPassthroughState::capture(ctx)?;
+            let r = ctx
+                .set_hdr_strth(true, PASSTHROUGH_HDR_STRTH)
+                .and_then(|_| ctx.disable_module(ModuleId::RK_MODULE_GOC));
+            match r {
+                Ok(_) => {
+                    *saved = Some(prior);
+                    Ok(())
+                }
+                Err(e) => {
+                    let _ = prior.restore(ctx);
+                    Err(e)
+                }
+            }
+        }
+        (false, Some(prior)) => {
+            prior.restore(ctx)?;
+            *saved = None;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Context {
+    /// 开启或关闭 HDR 直通，用于向外部编码器输出 HDR10 等高动态范围信号。
+    ///
+    /// 开启时修改以下模块：
+    /// * 色调映射（DRC/TMO）：切换为手动强度并设为最弱压缩 `1`，SDK 未提供完全旁路的接口；
+    /// * Gamma（GOC）：关闭模块，不再施加 SDR 伽玛曲线。
+    ///
+    /// ISP 不具备 PQ/HLG 传输曲线，开启后输出为场景线性信号，
+    /// 需由下游编码器施加 PQ（SMPTE ST 2084）或 HLG（ARIB STD-B67）传输函数。
+    ///
+    /// 开启前的状态被捕获，关闭时恢复；重复开启不会覆盖首次捕获的状态。
+    /// 开启过程中任一步骤失败时，会尝试恢复原有状态。
+    pub fn set_hdr_passthrough(&self, on: bool) -> XCamResult<()> {
+        let mut saved = self.hdr_passthrough.lock().unwrap();
+        switch_passthrough(self, &mut saved, on)
+    }
+
+    /// 查询 HDR 直通是否开启。
+    pub fn is_hdr_passthrough(&self) -> bool {
+        self.hdr_passthrough.lock().unwrap().is_some()
+    }
+
+    /// 从传感器静态信息查询 HDR 读出方式。
+    ///
+    /// 传感器未报告可识别的输出格式时返回 `XCamError::Unsupported`。
+    pub fn get_hdr_readout_mode(&self) -> XCamResult<HdrReadout> {
+        let info = sysctl::get_static_metas(self.sns_ent_name.as_str())?;
+        HdrReadout::from_static_info(&info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+
+    #[test]
+    fn test_hdr_readout_from_sensor_hdr_mode() {
+        assert_eq!(
+            HdrReadout::from_sensor_hdr_mode(SENSOR_NO_HDR),
+            Some(HdrReadout::Linear)
+        );
+        assert_eq!(
+            HdrReadout::from_sensor_hdr_mode(SENSOR_HDR_X2),
+            Some(HdrReadout::StaggerHdr)
+        );
+        assert_eq!(
+            HdrReadout::from_sensor_hdr_mode(SENSOR_HDR_X3),
+            Some(HdrReadout::StaggerHdr)
+        );
+        assert_eq!(
+            HdrReadout::from_sensor_hdr_mode(SENSOR_HDR_COMPR),
+            Some(HdrReadout::BuiltinHdr)
+        );
+        assert_eq!(HdrReadout::from_sensor_hdr_mode(3), None);
+    }
+
+    #[test]
+    fn test_hdr_readout_from_static_info() {
+        let mut info = StaticInfo::default();
+        assert_eq!(
+            HdrReadout::from_static_info(&info),
+            Err(XCamError::Unsupported)
+        );
+        info.sensor_info.num = 2;
+        info.sensor_info.support_fmt[0].hdr_mode = SENSOR_NO_HDR;
+        info.sensor_info.support_fmt[1].hdr_mode = SENSOR_HDR_X2;
+        assert_eq!(
+            HdrReadout::from_static_info(&info),
+            Ok(HdrReadout::StaggerHdr)
+        );
+        info.sensor_info.num = 1;
+        assert_eq!(HdrReadout::from_static_info(&info), Ok(HdrReadout::Linear));
+    }
+
+    fn drc_attrib() -> DrcManualAttrib {
+        DrcManualAttrib {
+            enable: true,
+            gain: 4.0,
+            alpha: 0.2,
+            clip: 16.0,
+            local_weight: 1.0,
+            compress_mode: DrcCompressMode::Manual,
+            compress_curve: (0..DRC_CURVE_LEN as u16).map(|i| i * 256).collect(),
+            local_gain_curve: vec![1024; DRC_CURVE_LEN],
         }
     }
+
+    #[test]
+    fn test_hdr_ratio_limits() {
+        let des = SensorDescriptor {
+            line_periods_per_field: 1000,
+            coarse_integration_time_min: 2,
+            coarse_integration_time_max_margin: 4,
+            ..Default::default()
+        };
+        // 长帧最多 1000 − 4 = 996 行，最短帧至少 2 行。
+        for mode in [WorkingMode::IspHdr2, WorkingMode::IspHdr3] {
+            assert_eq!(hdr_ratio_limits(&des, mode, None), Ok((1.0, 498.0)));
+        }
+        assert_eq!(
+            hdr_ratio_limits(&des, WorkingMode::IspHdr2, Some(16.0)),
+            Ok((1.0, 16.0))
+        );
+        assert_eq!(
+            hdr_ratio_limits(&des, WorkingMode::IspHdr2, Some(1000.0)),
+            Ok((1.0, 498.0))
+        );
+        assert_eq!(
+            hdr_ratio_limits(&des, WorkingMode::Normal, None),
+            Err(XCamError::Unsupported)
+        );
+        let short = SensorDescriptor {
+            line_periods_per_field: 5,
+            ..des
+        };
+        assert_eq!(
+            hdr_ratio_limits(&short, WorkingMode::IspHdr3, None),
+            Err(XCamError::Unsupported)
+        );
+    }
+
+    #[test]
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn test_hdr_exp_ratio_attr() {
+        let mut attr = ffi::Uapi_HdrExpAttrV2_t::default();
+        let ratio = &mut attr.Params.ExpRatioCtrl.ExpRatio;
+        ratio.array_size = 2;
+        ratio.M2SRatioMax[..2].copy_from_slice(&[16.0, 8.0]);
+        ratio.L2MRatioMax[..2].copy_from_slice(&[4.0, 4.0]);
+        assert_eq!(ae_hdr_ratio_max(&attr, WorkingMode::IspHdr2), Some(8.0));
+        assert_eq!(ae_hdr_ratio_max(&attr, WorkingMode::IspHdr3), Some(32.0));
+
+        write_hdr_exp_ratio(&mut attr, WorkingMode::IspHdr2, 8.0);
+        let ctrl = &attr.Params.ExpRatioCtrl;
+        assert_eq!(
+            ctrl.ExpRatioType,
+            ffi::CalibDb_HdrAeRatioTypeV2_t::AECV2_HDR_RATIOTYPE_MODE_FIX
+        );
+        assert_eq!(ctrl.ExpRatio.M2SRatioFix[..2], [8.0, 8.0]);
+        assert_eq!(ctrl.ExpRatio.L2MRatioFix[..2], [1.0, 1.0]);
+        assert_eq!(ctrl.ExpRatio.M2SRatioFix[2], 0.0);
+
+        write_hdr_exp_ratio(&mut attr, WorkingMode::IspHdr3, 16.0);
+        let fixed = &attr.Params.ExpRatioCtrl.ExpRatio;
+        assert_eq!(fixed.M2SRatioFix[..2], [4.0, 4.0]);
+        assert_eq!(fixed.L2MRatioFix[..2], [4.0, 4.0]);
+
+        attr.Params.ExpRatioCtrl.ExpRatio.array_size = 0;
+        assert_eq!(ae_hdr_ratio_max(&attr, WorkingMode::IspHdr2), None);
+    }
+
+    #[test]
+    fn test_set_hdr_exp_ratio_checks_limits() {
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().hdr_ratio_limits = Some((1.0, 16.0));
+        assert_eq!(ctx.set_hdr_exp_ratio(8.0), Ok(()));
+        assert_eq!(ctx.state.borrow().hdr_exp_ratio, 8.0);
+        for ratio in [0.5, 32.0] {
+            assert_eq!(
+                ctx.set_hdr_exp_ratio(ratio),
+                Err(XCamError::from(
+                    ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE
+                ))
+            );
+        }
+        assert_eq!(
+            ctx.set_hdr_exp_ratio(f32::NAN),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        assert_eq!(ctx.state.borrow().hdr_exp_ratio, 8.0);
+
+        ctx.state.borrow_mut().hdr_ratio_limits = None;
+        assert_eq!(ctx.set_hdr_exp_ratio(8.0), Err(XCamError::Unsupported));
+    }
+
+    #[test]
+    fn test_drc_strength_levels() {
+        let param = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        assert_eq!(drc_strength_levels(f32::NAN), param);
+        let off = drc_strength_levels(0.0).unwrap();
+        assert_eq!(
+            off,
+            DrcLevels {
+                gain: 1.0,
+                local_weight: 0.0
+            }
+        );
+        assert_eq!(drc_strength_levels(-1.0), Ok(off));
+        let full = drc_strength_levels(1.0).unwrap();
+        assert_eq!(full.gain, DRC_GAIN_MAX);
+        assert_eq!(drc_strength_levels(2.0), Ok(full));
+        // 对数均匀：中点为上限的平方根。
+        let half = drc_strength_levels(0.5).unwrap();
+        assert!((half.gain - DRC_GAIN_MAX.sqrt()).abs() < 1e-5);
+        assert_eq!(half.local_weight, 0.5);
+
+        let mut attr = drc_attrib();
+        half.apply(&mut attr);
+        assert!(attr.enable);
+        assert_eq!(attr.compress_mode, DrcCompressMode::Auto);
+        assert_eq!(attr.alpha, 0.2);
+        off.apply(&mut attr);
+        assert!(!attr.enable);
+    }
+
+    #[test]
+    fn test_check_drc_manual_attrib() {
+        let attr = drc_attrib();
+        assert_eq!(check_drc_manual_attrib(&attr), Ok(()));
+        let mut long = attr.clone();
+        long.compress_curve.push(0);
+        assert_eq!(
+            check_drc_manual_attrib(&long),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        let mut nan = attr.clone();
+        nan.local_weight = f32::NAN;
+        assert_eq!(
+            check_drc_manual_attrib(&nan),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        let mut big = attr;
+        big.gain = DRC_GAIN_MAX * 2.0;
+        assert_eq!(
+            check_drc_manual_attrib(&big),
+            Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE
+            ))
+        );
+    }
+
+    #[cfg(all(
+        any(feature = "v3_0", feature = "v4_0", feature = "v5_0"),
+        feature = "isp_hw_v30"
+    ))]
+    #[test]
+    fn test_drc_manual_round_trip() {
+        let manual = drc_attrib();
+        let mut attr = DrcAttr::default();
+        write_drc_manual(&mut attr, &manual);
+        assert_eq!(attr.opMode, ffi::drc_OpMode_t::DRC_OPMODE_MANU);
+        assert_eq!(read_drc_manual(&attr), manual);
+    }
+
+    #[test]
+    fn test_passthrough_restores_state() {
+        let ctx = MockContext::new();
+        ctx.set_hdr_strth(true, 60).unwrap();
+        let before = HdrPassthroughState::capture(&ctx).unwrap();
+        assert!(before.gamma_enabled);
+
+        let mut saved = None;
+        switch_passthrough(&ctx, &mut saved, true).unwrap();
+        assert_eq!(saved, Some(before));
+        let during = HdrPassthroughState::capture(&ctx).unwrap();
+        assert_eq!(during.hdr_strth, (true, PASSTHROUGH_HDR_STRTH));
+        assert!(!during.gamma_enabled);
+
+        switch_passthrough(&ctx, &mut saved, true).unwrap();
+        assert_eq!(saved, Some(before));
+
+        switch_passthrough(&ctx, &mut saved, false).unwrap();
+        assert_eq!(saved, None);
+        assert_eq!(HdrPassthroughState::capture(&ctx).unwrap(), before);
+    }
+
+    #[test]
+    fn test_passthrough_rolls_back_on_failure() {
+        let ctx = MockContext::new();
+        ctx.set_hdr_strth(false, 30).unwrap();
+        let before = HdrPassthroughState::capture(&ctx).unwrap();
+        ctx.fail("disable_module");
+
+        let mut saved = None;
+        assert!(switch_passthrough(&ctx, &mut saved, true).is_err());
+        assert_eq!(saved, None);
+        assert_eq!(HdrPassthroughState::capture(&ctx).unwrap(), before);
+    }
 }
@@ -1,20 +1,115 @@
+pub use doctor::doctor;
 pub use rkaiq_sys as ffi;
+pub use sdklog::{set_sdk_log_level, set_sdk_log_modules};
+
+// SDK 版本特性互斥，且必须启用其中一个。手动验证：
+// `cargo check --features v1_0` 应报告多个版本（默认已启用 v5_0），
+// `cargo check --no-default-features --features isp_hw_v30` 应报告未选择版本。
+#[cfg(not(any(
+    feature = "v1_0",
+    feature = "v2_0",
+    feature = "v3_0",
+    feature = "v4_0",
+    feature = "v5_0"
+)))]
+compile_error!(
+    "no SDK version selected: enable exactly one of the features v1_0, v2_0, v3_0, v4_0, v5_0"
+);
+
+#[cfg(any(
+    all(feature = "v1_0", feature = "v2_0"),
+    all(feature = "v1_0", feature = "v3_0"),
+    all(feature = "v1_0", feature = "v4_0"),
+    all(feature = "v1_0", feature = "v5_0"),
+    all(feature = "v2_0", feature = "v3_0"),
+    all(feature = "v2_0", feature = "v4_0"),
+    all(feature = "v2_0", feature = "v5_0"),
+    all(feature = "v3_0", feature = "v4_0"),
+    all(feature = "v3_0", feature = "v5_0"),
+    all(feature = "v4_0", feature = "v5_0"),
+))]
+compile_error!(
+    "conflicting SDK versions: enable exactly one of the features v1_0, v2_0, v3_0, v4_0, v5_0 (use --no-default-features to drop the default v5_0)"
+);
 
 pub mod acm;
+pub mod adjust;
 pub mod ae;
 pub mod af;
+pub mod analyzer;
 pub mod asd;
+pub mod attrib;
+pub mod autostate;
+pub mod average;
 pub mod awb;
+pub mod blc;
+pub mod bounds;
+pub mod cached;
+pub mod calib;
+mod callback;
+pub mod capability;
+pub mod capture;
+pub mod ccm;
+pub mod clock;
+pub mod colorpipe;
+#[cfg(feature = "json")]
+pub mod colorprofile;
+#[cfg(feature = "config")]
+pub mod config;
 pub mod context;
+pub mod convert;
+#[cfg(feature = "coverage")]
+pub mod coverage;
+pub mod csm;
+pub mod daynight;
 pub mod defog;
+pub mod detail;
+pub mod device;
+pub mod diff;
+pub mod display;
+pub mod dm;
+pub mod doctor;
+pub mod dump;
+pub mod embedded;
 pub mod error;
+pub mod events;
 pub mod fec;
 pub mod gamma;
 pub mod hdr;
 pub mod ldch;
+mod logging;
+pub mod longexp;
+pub mod lsc;
+pub mod luma;
+pub mod media;
+#[cfg(feature = "json")]
+pub mod migrate;
 pub mod misc;
+#[cfg(test)]
+mod mock;
+pub mod multi;
 pub mod nr;
+pub mod otp;
 pub mod prelude;
+pub mod retry;
+pub mod schedule;
+pub mod sdklog;
+pub mod sdkprobe;
+pub mod selftest;
+pub mod settings;
+pub mod settle;
+pub mod shared;
 pub mod sharpen;
+#[cfg(feature = "tokio")]
+pub mod stream;
+pub mod strength;
+pub mod summary;
 pub mod sysctl;
+pub mod timeout;
 pub mod types;
+mod uapi;
+mod v4l2;
+pub mod view;
+pub mod watchdog;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub mod wbpriority;
@@ -0,0 +1,27 @@
+//! 日志
+//!
+//! 本库运行中的警告与错误（SDK 调用失败、回调 panic、恢复设置失败等）经由 `log` 门面输出，
+//! 目标（target）为 `rkaiq`，由应用选择日志实现与级别。仅在启用 `log` 特性时输出，
+//! 未启用时丢弃，本库不直接写标准错误。
+
+/// 以 `warn` 级别输出一条日志，参数同 `format!`。
+macro_rules! log_warn {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::warn!(target: "rkaiq", $($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+/// 以 `error` 级别输出一条日志，参数同 `format!`。
+macro_rules! log_error {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        ::log::error!(target: "rkaiq", $($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+pub(crate) use {log_error, log_warn};
@@ -0,0 +1,212 @@
+//! 长曝光拍摄
+//!
+//! 视频模式下曝光时间受帧周期限制，数秒的长曝光（如星空拍摄）需要先降低帧率，
+//! 再设置手动曝光并等待曝光寄存器生效。`Context::long_exposure_capture` 完成这些步骤，
+//! 返回时下一帧即为长曝光帧；释放返回的守卫后恢复原帧率与曝光模式。
+//!
+//! 各版本 SDK 的曝光属性中都没有专门的长曝光模式，统一通过降低帧率的慢快门方式实现。
+//! SDK 的帧率为整数，最低为 1 帧/秒；超过 1 秒的曝光由传感器驱动按曝光时间加长帧长，
+//! 需要驱动支持，否则曝光时间被截断为帧周期。
+use super::ae::AutoExposure;
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::logging::log_warn;
+use super::misc::Miscellaneous;
+use super::settle::{default_settle_frames, settle_duration};
+use super::types::{FrameRateInfo, ModuleId, OpMode, XCamResult};
+use std::thread;
+use std::time::Duration;
+
+/// 返回能容纳 `time_s` 秒曝光的最高整数帧率，最低为 1。
+pub fn long_exposure_fps(time_s: f32) -> u32 {
+    ((1.0 / time_s).floor() as u32).max(1)
+}
+
+/// 一个在释放时恢复长曝光前帧率与曝光模式的守卫，由 `Context::long_exposure_capture` 返回。
+///
+/// 先恢复曝光，再恢复帧率。`Drop` 忽略恢复中的错误，需要检查错误时调用 `restore`。
+#[must_use = "the previous frame rate and exposure are restored as soon as the guard is dropped"]
+pub struct LongExposureGuard<'a, C: AutoExposure + Miscellaneous> {
+    ctx: &'a C,
+    prior: PriorExposure,
+    restored: bool,
+}
+
+/// 长曝光前的帧率与曝光状态。
+#[derive(Copy, Clone, Debug)]
+struct PriorExposure {
+    frame_rate: FrameRateInfo,
+    mode: OpMode,
+    /// 原为手动模式时的 `(增益, 曝光时间)`。
+    manual: Option<(f32, f32)>,
+}
+
+impl PriorExposure {
+    /// 恢复曝光与帧率，两步都会执行，返回遇到的第一个错误。
+    fn restore<C: AutoExposure + Miscellaneous>(&self, ctx: &C) -> XCamResult<()> {
+        let exposure = match self.manual {
+            Some((gain, time)) => ctx.set_manual_exp(gain, time),
+            None => ctx.set_exp_mode(self.mode),
+        };
+        let frame_rate = ctx.set_frame_rate(self.frame_rate);
+        exposure.and(frame_rate)
+    }
+}
+
+impl<C: AutoExposure + Miscellaneous> LongExposureGuard<'_, C> {
+    /// 恢复长曝光前的帧率与曝光模式并返回遇到的第一个错误。
+    pub fn restore(mut self) -> XCamResult<()> {
+        self.restored = true;
+        self.prior.restore(self.ctx)
+    }
+}
+
+impl<C: AutoExposure + Miscellaneous> Drop for LongExposureGuard<'_, C> {
+    fn drop(&mut self) {
+        if !self.restored {
+            if let Err(e) = self.prior.restore(self.ctx) {
+                log_warn!("failed to restore exposure after long exposure: {}", e);
+            }
+        }
+    }
+}
+
+/// 进入长曝光，设置完成后调用 `wait` 等待生效。
+///
+/// `time_s` 须为大于 0 且可以表示为 `Duration` 的有限值，`gain` 须为不小于 1 的有限值，否则返回
+/// `XCAM_RETURN_ERROR_PARAM`。任一步骤失败时恢复已修改的设置并返回该错误。
+pub(crate) fn begin_long_exposure<C, W>(
+    ctx: &C,
+    time_s: f32,
+    gain: f32,
+    wait: W,
+) -> XCamResult<LongExposureGuard<'_, C>>
+where
+    C: AutoExposure + Miscellaneous,
+    W: FnOnce(Duration),
+{
+    let exposure = match Duration::try_from_secs_f32(time_s) {
+        Ok(d) if time_s > 0.0 && gain.is_finite() && gain >= 1.0 => d,
+        _ => return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM)),
+    };
+    let mode = ctx.get_exp_mode()?;
+    let prior = PriorExposure {
+        frame_rate: ctx.get_frame_rate()?,
+        mode,
+        manual: match mode {
+            OpMode::Manual => Some(ctx.get_effective_exposure()?),
+            _ => None,
+        },
+    };
+    let fps = long_exposure_fps(time_s);
+    ctx.set_frame_rate(FrameRateInfo {
+        mode: ffi::opMode_t::OP_MANUAL,
+        fps,
+    })?;
+    let guard = LongExposureGuard {
+        ctx,
+        prior,
+        restored: false,
+    };
+    ctx.set_manual_exp(gain, time_s)?;
+    // 新帧周期不短于曝光时间，按曝光模块的生效帧数等待
+    let period = exposure.max(Duration::from_secs(1) / fps);
+    wait(settle_duration(
+        period,
+        default_settle_frames(ModuleId::RK_MODULE_AE),
+    ));
+    Ok(guard)
+}
+
+impl Context {
+    /// 进入长曝光拍摄，曝光生效后返回，此后输出的帧即为长曝光帧。
+    ///
+    /// 依次降低帧率至 `long_exposure_fps(time_s)`、设置手动曝光，再等待曝光模块的生效帧数，
+    /// 因此本调用会阻塞数倍于曝光时间。释放返回的守卫后恢复原帧率与曝光模式，见模块说明。
+    ///
+    /// # Parameters
+    /// * `time_s` - 曝光时间，单位：秒，须大于 0。
+    /// * `gain` - 总增益，单位：倍，须不小于 1。
+    pub fn long_exposure_capture(
+        &self,
+        time_s: f32,
+        gain: f32,
+    ) -> XCamResult<LongExposureGuard<'_, Self>> {
+        begin_long_exposure(self, time_s, gain, thread::sleep)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+
+    #[test]
+    fn test_long_exposure_fps() {
+        assert_eq!(long_exposure_fps(5.0), 1);
+        assert_eq!(long_exposure_fps(1.0), 1);
+        assert_eq!(long_exposure_fps(0.3), 3);
+        assert_eq!(long_exposure_fps(0.04), 25);
+    }
+
+    #[test]
+    fn test_long_exposure_restores() {
+        let ctx = MockContext::new();
+        let prior = FrameRateInfo {
+            mode: ffi::opMode_t::OP_AUTO,
+            fps: 30,
+        };
+        ctx.state.borrow_mut().frame_rate = prior;
+        let mut waited = None;
+        let guard = begin_long_exposure(&ctx, 4.0, 2.0, |d| waited = Some(d)).unwrap();
+        assert_eq!(waited, Some(Duration::from_secs(12)));
+        assert_eq!(ctx.state.borrow().frame_rate.fps, 1);
+        assert_eq!(ctx.state.borrow().manual_exp, (2.0, 4.0));
+        guard.restore().unwrap();
+        assert_eq!(ctx.state.borrow().frame_rate, prior);
+        assert_eq!(ctx.state.borrow().exp_mode, OpMode::Auto);
+        assert!(ctx.calls().contains(&"set_exp_mode"));
+
+        // 原为手动曝光时恢复原增益与曝光时间，释放守卫同样恢复。
+        {
+            let mut st = ctx.state.borrow_mut();
+            st.exp_mode = OpMode::Manual;
+            st.exp_info.gain = 4.0;
+            st.exp_info.time = 0.01;
+        }
+        drop(begin_long_exposure(&ctx, 2.0, 1.0, |_| ()).unwrap());
+        assert_eq!(ctx.state.borrow().manual_exp, (4.0, 0.01));
+        assert_eq!(ctx.state.borrow().frame_rate, prior);
+    }
+
+    #[test]
+    fn test_long_exposure_cleanup_on_error() {
+        let ctx = MockContext::new();
+        let param = || Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        assert_eq!(
+            begin_long_exposure(&ctx, 0.0, 1.0, |_| ()).map(|_| ()),
+            param()
+        );
+        assert_eq!(
+            begin_long_exposure(&ctx, 1.0, 0.5, |_| ()).map(|_| ()),
+            param()
+        );
+        // 超出 `Duration` 范围或非有限的曝光时间不会引起 panic。
+        for time_s in [f32::MAX, f32::INFINITY, f32::NAN] {
+            assert_eq!(
+                begin_long_exposure(&ctx, time_s, 1.0, |_| ()).map(|_| ()),
+                param()
+            );
+        }
+        assert!(ctx.calls().is_empty());
+
+        // 设置曝光失败时恢复帧率。
+        ctx.fail("set_manual_exp");
+        assert!(begin_long_exposure(&ctx, 2.0, 1.0, |_| ()).is_err());
+        let calls = ctx.calls();
+        assert_eq!(calls.iter().filter(|c| **c == "set_frame_rate").count(), 2);
+        assert_eq!(calls.last(), Some(&"set_frame_rate"));
+        assert_eq!(ctx.state.borrow().frame_rate, FrameRateInfo::default());
+    }
+}
@@ -0,0 +1,160 @@
+//! 镜头阴影校正
+//!
+//! LSC 模块按网格对四个 Bayer 通道分别施加增益，补偿镜头边缘的亮度衰减与色偏。
+//! 网格尺寸因 ISP 版本而异，本模块从 SDK 查询结构体的表格长度得出尺寸。
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::shared::SharedContext;
+use super::types::{string_from_c_chars, BayerChannel, BayerPattern, XCamResult};
+use super::uapi::uapi_call;
+
+/// 一个描述 LSC 增益表的类型。
+///
+/// 每个通道的表按行排列，共 `height` 行 `width` 列，第 0 行为画面顶部。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LscTable {
+    /// 网格列数。
+    pub width: usize,
+    /// 网格行数。
+    pub height: usize,
+    pub r: Vec<u16>,
+    pub gr: Vec<u16>,
+    pub gb: Vec<u16>,
+    pub b: Vec<u16>,
+}
+
+impl LscTable {
+    /// 由四个通道的表格构建增益表，网格为正方形，边长由表格长度得出。
+    ///
+    /// 四个表格长度不一致或长度不是完全平方数时返回 `XCAM_RETURN_ERROR_PARAM`。
+    pub fn from_channels(r: &[u16], gr: &[u16], gb: &[u16], b: &[u16]) -> XCamResult<Self> {
+        let len = r.len();
+        if gr.len() != len || gb.len() != len || b.len() != len {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        let side = (0..=len)
+            .find(|n| n * n >= len)
+            .filter(|n| n * n == len)
+            .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))?;
+        Ok(Self {
+            width: side,
+            height: side,
+            r: r.to_vec(),
+            gr: gr.to_vec(),
+            gb: gb.to_vec(),
+            b: b.to_vec(),
+        })
+    }
+
+    /// 返回通道 `ch` 的增益表。
+    pub fn channel(&self, ch: BayerChannel) -> &[u16] {
+        match ch {
+            BayerChannel::R => &self.r,
+            BayerChannel::Gr => &self.gr,
+            BayerChannel::Gb => &self.gb,
+            BayerChannel::B => &self.b,
+        }
+    }
+
+    /// 返回传感器坐标 `(x, y)` 处像素所属通道的增益表，通道由 Bayer 排列 `pattern` 决定。
+    ///
+    /// 四个表按颜色而非按 2×2 单元中的位置存放，按位置取表时须经由传感器的 Bayer 排列换算，
+    /// 否则非 RGGB 的传感器会取错通道。
+    pub fn channel_at(&self, pattern: BayerPattern, x: u32, y: u32) -> &[u16] {
+        self.channel(pattern.channel_at(x, y))
+    }
+}
+
+/// 一个描述当前 LSC 状态的类型。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LscQueryInfo {
+    /// LSC 是否使能。
+    pub enabled: bool,
+    /// 当前生效的增益表。
+    pub table: LscTable,
+    /// 选用的标定表名称，SDK 未报告时为空字符串。
+    pub name: String,
+}
+
+impl TryFrom<&ffi::rk_aiq_lsc_querry_info_t> for LscQueryInfo {
+    type Error = XCamError;
+
+    fn try_from(val: &ffi::rk_aiq_lsc_querry_info_t) -> XCamResult<Self> {
+        Ok(Self {
+            enabled: val.lsc_en,
+            table: LscTable::from_channels(
+                &val.r_data_tbl,
+                &val.gr_data_tbl,
+                &val.gb_data_tbl,
+                &val.b_data_tbl,
+            )?,
+            name: string_from_c_chars(&val.lsc_name),
+        })
+    }
+}
+
+pub trait LensShading {
+    /// 查询当前生效的 LSC 增益表及选用的标定表名称。
+    ///
+    /// # Note
+    ///
+    /// 需要复制四个通道的完整增益表，LSC 算法处理一帧期间调用会阻塞，最长约一个帧周期。
+    fn get_lsc_query_info(&self) -> XCamResult<LscQueryInfo>;
+}
+
+impl LensShading for Context {
+    #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+    fn get_lsc_query_info(&self) -> XCamResult<LscQueryInfo> {
+        let mut info = ffi::rk_aiq_lsc_querry_info_t::default();
+        uapi_call!(ffi::rk_aiq_user_api_alsc_QueryLscInfo(self, &mut info))?;
+        LscQueryInfo::try_from(&info)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_lsc_query_info(&self) -> XCamResult<LscQueryInfo> {
+        let mut info = ffi::rk_aiq_lsc_querry_info_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_alsc_QueryLscInfo(self, &mut info))?;
+        LscQueryInfo::try_from(&info)
+    }
+}
+
+impl LensShading for SharedContext {
+    fn get_lsc_query_info(&self) -> XCamResult<LscQueryInfo> {
+        self.inner.get_lsc_query_info()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lsc_table_dimensions() {
+        let ch: Vec<u16> = (0..289).collect();
+        let table = LscTable::from_channels(&ch, &ch, &ch, &ch).unwrap();
+        assert_eq!((table.width, table.height), (17, 17));
+        assert_eq!(table.b[17 * 16 + 16], 288);
+
+        let small = [1024u16; 81];
+        let table = LscTable::from_channels(&small, &small, &small, &small).unwrap();
+        assert_eq!((table.width, table.height), (9, 9));
+
+        let param = Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        assert_eq!(
+            LscTable::from_channels(&ch[..288], &ch[..288], &ch[..288], &ch[..288]),
+            param
+        );
+        assert_eq!(LscTable::from_channels(&ch, &ch, &ch, &small), param);
+    }
+
+    #[test]
+    fn test_lsc_channel_at() {
+        let table = LscTable::from_channels(&[1], &[2], &[3], &[4]).unwrap();
+        assert_eq!(table.channel(BayerChannel::Gb), [3]);
+        assert_eq!(table.channel_at(BayerPattern::Rggb, 0, 0), [1]);
+        assert_eq!(table.channel_at(BayerPattern::Bggr, 0, 0), [4]);
+        assert_eq!(table.channel_at(BayerPattern::Bggr, 0, 1), [2]);
+        assert_eq!(table.channel_at(BayerPattern::Grbg, 1, 1), [3]);
+    }
+}
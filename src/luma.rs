@@ -0,0 +1,168 @@
+//! 亮度图
+//!
+//! 日夜切换、场景变化检测等只需要一张很小的亮度图，不必读取整帧图像。`get_luma_map`
+//! 从 3A 统计中取出按网格划分的平均亮度，归一化到 [0,255]，按版本选择可用的最佳来源：
+//!
+//! | 版本 | 来源 |
+//! |---|---|
+//! | isp_hw_v20 | YUV 域 AE 统计（`yuvae`）的 15x15 平均亮度，已是 8 位 Y 值；全部为 0（模块未开启）时退回 RAW 域 AE 网格 |
+//! | 其余 ISP | RAW 域 AE 网格（`rawae_big`）的 15x15 通道均值，按 BT.601 权重合成亮度 |
+//! | v1_0 | 统计信息布局不同，返回 `XCamError::Unsupported` |
+//!
+//! RAW 域网格位于白平衡与 gamma 之前，合成的亮度与输出图像的亮度并不相同，但随场景亮度单调变化，
+//! 适合比较与阈值判断。统计取自 `get_3a_stats`，会消耗统计队列中的一帧。
+#[cfg(feature = "v1_0")]
+use super::error::XCamError;
+use super::types::{IspStats, Rect, XCamResult};
+use std::time::Duration;
+
+/// 读取亮度图时等待 3A 统计的超时。
+pub const LUMA_STATS_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// RAW 域 AE 网格的列数与行数。
+#[cfg(not(feature = "v1_0"))]
+const RAWAE_GRID: usize = 15;
+/// RAW 域 AE 网格 R、B 通道均值的最大值（10 位）。
+#[cfg(not(feature = "v1_0"))]
+const RAWAE_RB_MAX: f32 = 1023.0;
+/// RAW 域 AE 网格 G 通道均值的最大值（12 位）。
+#[cfg(not(feature = "v1_0"))]
+const RAWAE_G_MAX: f32 = 4095.0;
+
+/// 一个描述按网格划分的画面亮度的类型，按行排列，第 0 行为画面顶部。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LumaMap {
+    /// 列数。
+    pub width: usize,
+    /// 行数。
+    pub height: usize,
+    /// 各格的平均亮度，范围：[0,255]，长度为 `width * height`。
+    pub data: Vec<u8>,
+}
+
+impl LumaMap {
+    /// 由 8 位亮度创建。
+    pub fn from_luma(width: usize, height: usize, data: Vec<u8>) -> Self {
+        debug_assert_eq!(data.len(), width * height);
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// 由 RAW 域 AE 网格的 R、G、B 通道均值合成亮度，R、B 为 10 位，G 为 12 位。
+    #[cfg(not(feature = "v1_0"))]
+    pub(crate) fn from_rawae(width: usize, height: usize, r: &[u16], g: &[u16], b: &[u16]) -> Self {
+        let data = r
+            .iter()
+            .zip(g)
+            .zip(b)
+            .take(width * height)
+            .map(|((&r, &g), &b)| {
+                let y = 0.299 * f32::from(r) / RAWAE_RB_MAX
+                    + 0.587 * f32::from(g) / RAWAE_G_MAX
+                    + 0.114 * f32::from(b) / RAWAE_RB_MAX;
+                (y * 255.0).round().clamp(0.0, 255.0) as u8
+            })
+            .collect();
+        Self::from_luma(width, height, data)
+    }
+
+    /// 全图平均亮度，空图为 0。
+    pub fn mean(&self) -> f32 {
+        if self.data.is_empty() {
+            return 0.0;
+        }
+        self.data.iter().map(|&v| f32::from(v)).sum::<f32>() / self.data.len() as f32
+    }
+
+    /// `rect` 内各格的平均亮度，坐标以格为单位；`rect` 裁剪到图内后为空时返回 `None`。
+    pub fn region_mean(&self, rect: Rect) -> Option<f32> {
+        let span = |start: i32, len: i32, max: usize| {
+            let max = max as i64;
+            let lo = i64::from(start).clamp(0, max) as usize;
+            let hi = (i64::from(start) + i64::from(len.max(0))).clamp(0, max) as usize;
+            (lo, hi)
+        };
+        let (x0, x1) = span(rect.left, rect.width, self.width);
+        let (y0, y1) = span(rect.top, rect.height, self.height);
+        if x0 >= x1 || y0 >= y1 {
+            return None;
+        }
+        let sum: u32 = (y0..y1)
+            .flat_map(|y| &self.data[y * self.width + x0..y * self.width + x1])
+            .map(|&v| u32::from(v))
+            .sum();
+        Some(sum as f32 / ((x1 - x0) * (y1 - y0)) as f32)
+    }
+}
+
+/// 按版本选择来源，由一帧统计得到亮度图，见模块说明。
+#[cfg(not(feature = "v1_0"))]
+pub(crate) fn luma_map_from_stats(stats: &IspStats) -> XCamResult<LumaMap> {
+    let cells = RAWAE_GRID * RAWAE_GRID;
+    #[cfg(feature = "isp_hw_v20")]
+    {
+        let mean = &stats.aec_stats.ae_data.yuvae.mean[..cells];
+        if mean.iter().any(|&v| v != 0) {
+            return Ok(LumaMap::from_luma(RAWAE_GRID, RAWAE_GRID, mean.to_vec()));
+        }
+    }
+    let big = &stats.aec_stats.ae_data.chn[0].rawae_big;
+    Ok(LumaMap::from_rawae(
+        RAWAE_GRID,
+        RAWAE_GRID,
+        &big.channelr_xy[..cells],
+        &big.channelg_xy[..cells],
+        &big.channelb_xy[..cells],
+    ))
+}
+
+/// 统计信息布局不同，不支持。
+#[cfg(feature = "v1_0")]
+pub(crate) fn luma_map_from_stats(_stats: &IspStats) -> XCamResult<LumaMap> {
+    Err(XCamError::Unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: i32, top: i32, width: i32, height: i32) -> Rect {
+        Rect {
+            left,
+            top,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn test_luma_map_mean() {
+        // 3x2：上行 0、30、60，下行 90、120、150。
+        let map = LumaMap::from_luma(3, 2, vec![0, 30, 60, 90, 120, 150]);
+        assert_eq!(map.mean(), 75.0);
+        assert_eq!(map.region_mean(rect(0, 0, 3, 1)), Some(30.0));
+        assert_eq!(map.region_mean(rect(1, 0, 2, 2)), Some(90.0));
+        // 超出图的部分被裁剪，裁剪后为空时为 `None`。
+        assert_eq!(map.region_mean(rect(-1, 1, 3, 5)), Some(105.0));
+        assert_eq!(map.region_mean(rect(3, 0, 2, 2)), None);
+        assert_eq!(map.region_mean(rect(0, 0, 0, 2)), None);
+        assert_eq!(LumaMap::from_luma(0, 0, Vec::new()).mean(), 0.0);
+    }
+
+    #[cfg(not(feature = "v1_0"))]
+    #[test]
+    fn test_luma_map_from_rawae() {
+        let map = LumaMap::from_rawae(
+            2,
+            2,
+            &[0, 1023, 1023, 0],
+            &[0, 4095, 0, 4095],
+            &[0, 1023, 0, 0],
+        );
+        assert_eq!((map.width, map.height), (2, 2));
+        assert_eq!(map.data, [0, 255, 76, 150]);
+    }
+}
@@ -0,0 +1,289 @@
+//! 媒体节点
+//!
+//! SDK 初始化时只接收传感器实体名称，由 SDK 在全部 `/dev/media*` 节点中查找包含该传感器的
+//! ISP 节点。多 ISP 平台上同一传感器只会链接到一个 ISP，因此选定节点等同于选定其上的传感器：
+//! 先用 `list_media_nodes` 列出节点，再将选中的节点交给 `ContextBuilder::media_node`。
+//!
+//! 拓扑通过媒体控制器的 ioctl（`MEDIA_IOC_DEVICE_INFO`、`MEDIA_IOC_G_TOPOLOGY`）获取，不依赖
+//! `media-ctl` 等外部工具。只采用型号中包含 `isp` 的节点（CIF 等节点上的传感器由对应的 ISP 节点
+//! 重复报告），每个功能为传感器的实体对应一项。
+use super::error::XCamError;
+use super::ffi;
+use super::sysctl::ReadoutConfig;
+use super::types::XCamResult;
+use super::v4l2::{self, Topology, V4l2Rect};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::time::Duration;
+
+/// 一个描述 ISP 媒体节点及其传感器的类型。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MediaNode {
+    /// 媒体节点路径，例如 `/dev/media0`。
+    pub path: String,
+    /// 传感器实体名称，例如 `m00_b_imx415 3-001a`。
+    pub sensor: String,
+    /// ISP 编号，取自节点型号末尾的数字，例如 `rkisp1` 为 1，`rkisp` 为 0。
+    pub isp_index: u8,
+}
+
+/// 列出系统中全部 ISP 媒体节点上的传感器，按 ISP 编号与节点路径排序。
+///
+/// 无法读取 `/dev` 时返回 `XCAM_RETURN_ERROR_FAILED`。单个节点无法打开（例如权限不足）或
+/// 拓扑读取失败时跳过该节点。
+pub fn list_media_nodes() -> XCamResult<Vec<MediaNode>> {
+    let entries = fs::read_dir("/dev")
+        .map_err(|_| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))?;
+    let mut paths: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| is_media_node_name(name))
+        .map(|name| format!("/dev/{}", name))
+        .collect();
+    paths.sort_by_key(|p| media_node_number(p));
+
+    let mut nodes: Vec<MediaNode> = paths
+        .iter()
+        .filter_map(|path| read_media_node(path).ok())
+        .flatten()
+        .collect();
+    nodes.sort_by_key(|n| n.isp_index);
+    Ok(nodes)
+}
+
+/// 读取媒体节点 `path` 上的传感器，不是 ISP 节点时返回空表。
+pub(crate) fn read_media_node(path: &str) -> io::Result<Vec<MediaNode>> {
+    let file = File::open(path)?;
+    let Some(isp_index) = isp_index(&v4l2::media_model(&file)?) else {
+        return Ok(Vec::new());
+    };
+    Ok(sensors_in(path, isp_index, &v4l2::media_topology(&file)?))
+}
+
+/// ISP 主通路输出节点的实体名称。
+pub const MAINPATH_ENTITY: &str = "rkisp_mainpath";
+
+/// 查找传感器 `sns_ent_name` 所在 ISP 的主通路 video 节点，例如 `/dev/video0`。
+///
+/// 设备节点号取自媒体拓扑中主通路实体的 video 接口，路径经 sysfs 由设备号得到。
+/// 媒体拓扑中没有该传感器或主通路节点时返回 `XCAM_RETURN_ERROR_PARAM`，
+/// 无法读取拓扑时返回 `XCAM_RETURN_ERROR_IOCTL`。
+pub fn mainpath_video_node(sns_ent_name: &str) -> XCamResult<String> {
+    entity_node(sns_ent_name, MAINPATH_ENTITY, v4l2::MEDIA_INTF_T_V4L_VIDEO)
+}
+
+/// 查找传感器 `sns_ent_name` 的子设备节点，例如 `/dev/v4l-subdev2`。
+///
+/// 错误同 `mainpath_video_node`。
+pub(crate) fn sensor_subdev_node(sns_ent_name: &str) -> XCamResult<String> {
+    entity_node(sns_ent_name, sns_ent_name, v4l2::MEDIA_INTF_T_V4L_SUBDEV)
+}
+
+/// 在传感器 `sns_ent_name` 所在的媒体节点中查找实体 `entity` 类型为 `intf_type` 的设备节点路径。
+fn entity_node(sns_ent_name: &str, entity: &str, intf_type: u32) -> XCamResult<String> {
+    let param = || XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM);
+    let node = list_media_nodes()?
+        .into_iter()
+        .find(|n| n.sensor == sns_ent_name)
+        .ok_or_else(param)?;
+    let topo = File::open(&node.path)
+        .and_then(|f| v4l2::media_topology(&f))
+        .map_err(ioctl_error)?;
+    let devnode = topo.devnode(entity, intf_type).ok_or_else(param)?;
+    v4l2::devnode_path(devnode).map_err(|_| param())
+}
+
+/// 节点访问失败统一报告为 `XCAM_RETURN_ERROR_IOCTL`。
+pub(crate) fn ioctl_error(_: io::Error) -> XCamError {
+    XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_IOCTL)
+}
+
+/// 以读写方式打开 video 节点或子设备节点 `node`。
+fn open_video_node(node: &str) -> XCamResult<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(node)
+        .map_err(ioctl_error)
+}
+
+/// 以 `VIDIOC_S_FMT` 设置 video 节点 `node` 的像素格式（fourcc），返回驱动实际采用的 fourcc。
+///
+/// 打开节点或 ioctl 失败时返回 `XCAM_RETURN_ERROR_IOCTL`。
+pub(crate) fn set_video_format(node: &str, fourcc: u32) -> XCamResult<u32> {
+    let file = open_video_node(node)?;
+    v4l2::set_pixel_format(&file, fourcc)
+        .map(|f| f.pixelformat)
+        .map_err(ioctl_error)
+}
+
+/// 以 `VIDIOC_G_FMT` 读取 video 节点 `node` 的 `(宽, 高, fourcc)`。
+///
+/// 打开节点或 ioctl 失败时返回 `XCAM_RETURN_ERROR_IOCTL`。
+pub(crate) fn get_video_format(node: &str) -> XCamResult<(u32, u32, u32)> {
+    let file = open_video_node(node)?;
+    v4l2::get_format(&file)
+        .map(|f| (f.width, f.height, f.pixelformat))
+        .map_err(ioctl_error)
+}
+
+/// 设置传感器子设备节点 `node` 的读出方式，见 `Context::set_sensor_readout`。
+///
+/// 先以 `VIDIOC_SUBDEV_S_SELECTION` 设置裁剪区域，`cfg.crop` 为 `None` 时恢复为完整的像素阵列
+/// （`V4L2_SEL_TGT_CROP_BOUNDS`），驱动不支持裁剪时跳过这一步；再以 `VIDIOC_SUBDEV_S_FMT` 将输出
+/// 尺寸设为裁剪尺寸按合并倍数缩小后的尺寸，由驱动选择对应的合并模式。
+///
+/// 驱动调整了裁剪区域或选择了其他尺寸时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`，打开节点或 ioctl
+/// 失败时返回 `XCAM_RETURN_ERROR_IOCTL`。
+pub(crate) fn set_sensor_readout(node: &str, cfg: ReadoutConfig) -> XCamResult<()> {
+    let out_of_range = || XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE);
+    let file = open_video_node(node)?;
+    let bounds = v4l2::get_crop_bounds(&file).map_err(ioctl_error)?;
+    let crop = match cfg.crop {
+        Some(r) => {
+            // 宽高、偏移非负已由调用者检查。
+            let r = V4l2Rect {
+                left: r.left,
+                top: r.top,
+                width: r.width as u32,
+                height: r.height as u32,
+            };
+            if v4l2::set_crop(&file, r).map_err(ioctl_error)? != r {
+                return Err(out_of_range());
+            }
+            r
+        }
+        None => match v4l2::set_crop(&file, bounds) {
+            Ok(_) => bounds,
+            Err(e) if matches!(e.raw_os_error(), Some(libc::ENOTTY | libc::EINVAL)) => bounds,
+            Err(e) => return Err(ioctl_error(e)),
+        },
+    };
+    let size = cfg.binning.output_size((crop.width, crop.height));
+    if v4l2::set_subdev_size(&file, size.0, size.1).map_err(ioctl_error)? != size {
+        return Err(out_of_range());
+    }
+    Ok(())
+}
+
+/// 抓取一帧时等待的最长时间。
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 以 mmap 缓冲区从 video 节点 `node` 取一帧图像，返回各平面依次拼接的数据，见
+/// `v4l2::capture_frame`。
+///
+/// 节点被其他进程占用等导致采集失败时返回 `XCAM_RETURN_ERROR_IOCTL`，`CAPTURE_TIMEOUT` 内没有
+/// 取到帧时返回 `XCamError::Timeout`，没有取到数据时返回 `XCAM_RETURN_ERROR_FAILED`。
+pub(crate) fn grab_video_frame(node: &str) -> XCamResult<Vec<u8>> {
+    let file = open_video_node(node)?;
+    match v4l2::capture_frame(file, CAPTURE_TIMEOUT) {
+        Ok(data) if !data.is_empty() => Ok(data),
+        Ok(_) => Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED)),
+        Err(e) if e.kind() == io::ErrorKind::TimedOut => Err(XCamError::Timeout),
+        Err(e) => Err(ioctl_error(e)),
+    }
+}
+
+/// 在 video 节点 `node` 上开始连续采集，返回采集会话、节点格式 `(宽, 高, fourcc)` 与各平面行宽。
+///
+/// 节点被其他进程占用等导致采集失败时返回 `XCAM_RETURN_ERROR_IOCTL`。
+pub(crate) fn start_video_stream(
+    node: &str,
+) -> XCamResult<(v4l2::Capture, (u32, u32, u32), Vec<u32>)> {
+    let file = open_video_node(node)?;
+    let format = v4l2::get_format(&file).map_err(ioctl_error)?;
+    let strides = v4l2::get_plane_strides(&file).map_err(ioctl_error)?;
+    let capture = v4l2::Capture::start(file, v4l2::STREAM_BUFFERS).map_err(ioctl_error)?;
+    Ok((
+        capture,
+        (format.width, format.height, format.pixelformat),
+        strides,
+    ))
+}
+
+/// 判断 `/dev` 下的文件名是否为媒体节点，即 `media` 后跟数字。
+fn is_media_node_name(name: &str) -> bool {
+    name.strip_prefix("media")
+        .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// 媒体节点路径末尾的编号，用于按数值排序。
+fn media_node_number(path: &str) -> u32 {
+    path.trim_start_matches("/dev/media")
+        .parse()
+        .unwrap_or(u32::MAX)
+}
+
+/// 由节点型号得到 ISP 编号，型号中不含 `isp` 或末尾的编号超出范围时返回 `None`；
+/// 型号末尾没有编号时视为 0 号 ISP。
+pub(crate) fn isp_index(model: &str) -> Option<u8> {
+    if !model.contains("isp") {
+        return None;
+    }
+    match &model[model.trim_end_matches(|c: char| c.is_ascii_digit()).len()..] {
+        "" => Some(0),
+        digits => digits.parse().ok(),
+    }
+}
+
+/// 拓扑 `topo` 中的传感器实体。
+pub(crate) fn sensors_in(path: &str, isp_index: u8, topo: &Topology) -> Vec<MediaNode> {
+    topo.entities_with(v4l2::MEDIA_ENT_F_CAM_SENSOR)
+        .map(|e| MediaNode {
+            path: path.to_owned(),
+            sensor: e.name.clone(),
+            isp_index,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensors_in() {
+        let sensor = |id, name: &str| v4l2::Entity {
+            id,
+            name: name.to_owned(),
+            function: v4l2::MEDIA_ENT_F_CAM_SENSOR,
+        };
+        let topo = Topology {
+            entities: vec![
+                v4l2::Entity {
+                    id: 1,
+                    name: "rkisp-isp-subdev".to_owned(),
+                    function: 0x0004_0001,
+                },
+                sensor(63, "m00_b_imx415 3-001a"),
+                sensor(67, "m01_f_ov5695 4-0036-1"),
+            ],
+            ..Topology::default()
+        };
+        assert_eq!(
+            sensors_in("/dev/media1", 1, &topo),
+            [
+                MediaNode {
+                    path: "/dev/media1".to_owned(),
+                    sensor: "m00_b_imx415 3-001a".to_owned(),
+                    isp_index: 1,
+                },
+                MediaNode {
+                    path: "/dev/media1".to_owned(),
+                    sensor: "m01_f_ov5695 4-0036-1".to_owned(),
+                    isp_index: 1,
+                },
+            ]
+        );
+
+        assert_eq!(isp_index("rkisp1"), Some(1));
+        assert_eq!(isp_index("rkisp"), Some(0));
+        assert_eq!(isp_index("rkcif-mipi-lvds"), None);
+        assert_eq!(isp_index("rkisp999"), None);
+
+        assert!(is_media_node_name("media12"));
+        assert!(!is_media_node_name("media"));
+        assert!(!is_media_node_name("mediax"));
+        assert_eq!(media_node_number("/dev/media12"), 12);
+    }
+}
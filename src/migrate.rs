@@ -0,0 +1,252 @@
+//! 参数快照的迁移
+//!
+//! 旧固件保存的 `CameraSettings` 快照须能在新固件上读取，即使本库之后增加了字段。
+//! `CameraSettings::migrate_from_json` 以宽松模式读取 JSON 快照：
+//!
+//! * 未知字段（较新版本写入的字段，或已删除的字段）被忽略；
+//! * 缺失的字段取默认值，即不设置，保持 SDK 当前值；
+//! * 取值无效的字段被丢弃，例如版本 0 的快照中以 [0,255] 等级记录、超出百分比范围的饱和度；
+//!
+//! 每一项都记录在返回的 `MigrationReport` 中，路径形如 `adjust.saturation`。
+//! 迁移结果的 `schema_version` 总为 `SETTINGS_SCHEMA_VERSION`。
+//!
+//! TOML 配置文件仍按严格模式解析（见 `config` 模块），以便尽早发现手写文件中的拼写错误。
+//! 仅在启用 `json` 特性时编译。
+use super::settings::{
+    AdjustSettings, CameraSettings, ExposureSettings, NoiseReductionSettings, WhiteBalanceSettings,
+    SETTINGS_SCHEMA_VERSION,
+};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// 一个描述快照迁移结果的类型。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// 快照记录的格式版本，没有记录时为 0。
+    pub from_version: u32,
+    /// 被丢弃的字段路径及原因，未知字段与取值无效的字段均在此列。
+    pub dropped: Vec<(String, String)>,
+    /// 快照中缺失而取默认值的字段路径；整个分组缺失时只记录分组名。
+    pub defaulted: Vec<String>,
+}
+
+impl MigrationReport {
+    /// 字段 `path` 是否因缺失而取默认值。
+    pub fn was_defaulted(&self, path: &str) -> bool {
+        self.defaulted.iter().any(|p| p == path)
+    }
+
+    /// 字段 `path` 是否被丢弃。
+    pub fn was_dropped(&self, path: &str) -> bool {
+        self.dropped.iter().any(|(p, _)| p == path)
+    }
+
+    /// 快照是否无需任何改动即可读取。
+    pub fn is_clean(&self) -> bool {
+        self.dropped.is_empty() && self.defaulted.is_empty()
+    }
+
+    fn drop_field(&mut self, path: String, reason: impl Into<String>) {
+        self.dropped.push((path, reason.into()));
+    }
+
+    /// 将 `obj` 中不在 `known` 里的键记为丢弃。
+    fn drop_unknown(&mut self, prefix: Option<&str>, obj: &Map<String, Value>, known: &[&str]) {
+        for key in obj.keys().filter(|k| !known.contains(&k.as_str())) {
+            self.drop_field(join(prefix, key), "unknown field");
+        }
+    }
+}
+
+fn join(prefix: Option<&str>, key: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{}.{}", prefix, key),
+        None => key.to_owned(),
+    }
+}
+
+/// 读取 `obj` 中的一个字段：缺失时记为默认，`null` 视为未设置，无效时记为丢弃。
+fn take<T: DeserializeOwned>(
+    obj: &Map<String, Value>,
+    prefix: Option<&str>,
+    key: &str,
+    report: &mut MigrationReport,
+) -> Option<T> {
+    match obj.get(key) {
+        None => {
+            report.defaulted.push(join(prefix, key));
+            None
+        }
+        Some(Value::Null) => None,
+        Some(v) => T::deserialize(v)
+            .map_err(|e| report.drop_field(join(prefix, key), e.to_string()))
+            .ok(),
+    }
+}
+
+/// 读取一个参数分组，按字段逐项读取；字段列表须覆盖分组类型的全部字段。
+macro_rules! take_group {
+    ($root:expr, $report:expr, $group:ident: $ty:ident { $($field:ident),* $(,)? }) => {{
+        let name = stringify!($group);
+        match $root.get(name) {
+            None => {
+                $report.defaulted.push(name.to_owned());
+                None
+            }
+            Some(Value::Null) => None,
+            Some(Value::Object(obj)) => {
+                $report.drop_unknown(Some(name), obj, &[$(stringify!($field)),*]);
+                Some($ty {
+                    $($field: take(obj, Some(name), stringify!($field), &mut $report),)*
+                })
+            }
+            Some(_) => {
+                $report.drop_field(name.to_owned(), "expected an object");
+                None
+            }
+        }
+    }};
+}
+
+impl CameraSettings {
+    /// 以宽松模式读取 JSON 快照，返回读取结果与迁移报告，见模块说明。
+    ///
+    /// 文本不是 JSON 对象时返回默认参数，报告中以空路径记录原因。
+    pub fn migrate_from_json(s: &str) -> (CameraSettings, MigrationReport) {
+        let mut report = MigrationReport::default();
+        let root = match serde_json::from_str::<Value>(s) {
+            Ok(Value::Object(root)) => root,
+            Ok(_) => {
+                report.drop_field(String::new(), "expected an object");
+                return (CameraSettings::default(), report);
+            }
+            Err(e) => {
+                report.drop_field(String::new(), e.to_string());
+                return (CameraSettings::default(), report);
+            }
+        };
+        report.from_version = match root.get("schema_version").map(u32::deserialize) {
+            None => 0,
+            Some(Ok(v)) => v,
+            Some(Err(e)) => {
+                report.drop_field("schema_version".to_owned(), e.to_string());
+                0
+            }
+        };
+        report.drop_unknown(
+            None,
+            &root,
+            &[
+                "schema_version",
+                "white_balance",
+                "exposure",
+                "adjust",
+                "noise_reduction",
+            ],
+        );
+        let settings = CameraSettings {
+            schema_version: SETTINGS_SCHEMA_VERSION,
+            white_balance: take_group!(root, report, white_balance: WhiteBalanceSettings {
+                mode, ct, gain,
+            }),
+            exposure: take_group!(root, report, exposure: ExposureSettings {
+                mode, gain_range, time_range,
+            }),
+            adjust: take_group!(root, report, adjust: AdjustSettings {
+                brightness, contrast, saturation, hue, sharpness,
+            }),
+            noise_reduction: take_group!(root, report, noise_reduction: NoiseReductionSettings {
+                mode, strength, spatial, temporal,
+            }),
+        };
+        (settings, report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{GainSettings, RangeSettings};
+    use crate::types::{OpMode, Strength};
+
+    const UNVERSIONED_LEVELS: &str =
+        include_str!("../tests/fixtures/settings_unversioned_levels.json");
+    const UNVERSIONED: &str = include_str!("../tests/fixtures/settings_unversioned.json");
+    const FUTURE: &str = include_str!("../tests/fixtures/settings_future.json");
+
+    #[test]
+    fn test_migrate_unversioned_levels() {
+        // 引入百分比强度之前的布局，饱和度为 [0,255] 等级。
+        let (s, report) = CameraSettings::migrate_from_json(UNVERSIONED_LEVELS);
+        assert_eq!(report.from_version, 0);
+        assert_eq!(s.schema_version, SETTINGS_SCHEMA_VERSION);
+        assert!(report.was_dropped("adjust.saturation"));
+        assert_eq!(report.dropped.len(), 1);
+        assert!(report.defaulted.is_empty());
+        let wb = s.white_balance.unwrap();
+        assert_eq!(
+            wb.gain,
+            Some(GainSettings {
+                r: 1.8,
+                gr: 1.0,
+                gb: 1.0,
+                b: 1.6
+            })
+        );
+        let adj = s.adjust.unwrap();
+        assert_eq!(adj.saturation, None);
+        assert_eq!(adj.sharpness, Strength::new(50));
+        let nr = s.noise_reduction.unwrap();
+        assert_eq!(nr.spatial, None);
+        assert_eq!(nr.temporal, Strength::new(60));
+    }
+
+    #[test]
+    fn test_migrate_unversioned() {
+        // 引入格式版本之前的布局，缺少的分组与字段取默认值。
+        let (s, report) = CameraSettings::migrate_from_json(UNVERSIONED);
+        assert_eq!(report.from_version, 0);
+        assert!(report.dropped.is_empty());
+        assert!(report.was_defaulted("exposure"));
+        assert!(report.was_defaulted("noise_reduction.spatial"));
+        assert!(report.was_defaulted("noise_reduction.temporal"));
+        assert!(!report.was_defaulted("white_balance.ct"));
+        assert_eq!(s.exposure, None);
+        assert_eq!(s.white_balance.unwrap().mode, Some(OpMode::Auto));
+        assert_eq!(s.adjust.unwrap().saturation, Strength::new(55));
+        assert_eq!(s.noise_reduction.unwrap().strength, Strength::new(30));
+    }
+
+    #[test]
+    fn test_migrate_future() {
+        let (s, report) = CameraSettings::migrate_from_json(FUTURE);
+        assert_eq!(report.from_version, 7);
+        assert_eq!(s.schema_version, SETTINGS_SCHEMA_VERSION);
+        for path in ["white_balance.tint", "exposure.flicker", "dehaze"] {
+            assert!(report.was_dropped(path), "{}", path);
+        }
+        assert!(report.was_defaulted("white_balance.gain"));
+        assert_eq!(
+            s.exposure.unwrap().gain_range,
+            Some(RangeSettings {
+                min: 1.0,
+                max: 16.0
+            })
+        );
+    }
+
+    #[test]
+    fn test_migrate_round_trip() {
+        let (s, _) = CameraSettings::migrate_from_json(UNVERSIONED);
+        let text = serde_json::to_string(&s).unwrap();
+        let (again, report) = CameraSettings::migrate_from_json(&text);
+        assert_eq!(again, s);
+        assert_eq!(report.from_version, SETTINGS_SCHEMA_VERSION);
+        assert!(report.is_clean());
+
+        let (s, report) = CameraSettings::migrate_from_json("[1, 2]");
+        assert_eq!(s, CameraSettings::default());
+        assert!(report.was_dropped(""));
+    }
+}
@@ -2,9 +2,12 @@
 //!
 //! 未分类的功能、接口等。
 use super::context::Context;
-use super::error::XCamError;
+use super::fec::FEC;
 use super::ffi;
+use super::ldch::LDCH;
+use super::shared::SharedContext;
 use super::types::{FrameRateInfo, GrayMode, XCamResult};
+use super::uapi::uapi_call;
 
 /// 一个描述杂项控制的契定。
 pub trait Miscellaneous {
@@ -27,66 +30,206 @@ pub trait Miscellaneous {
     fn set_mirror_flip(&self, mirror: bool, flip: bool, skip_frm_cnt: i32) -> XCamResult<()>;
 }
 
+/// `set_orientation` 切换镜像、翻转时丢弃的帧数，覆盖传感器寄存器的生效延迟。
+pub const ORIENTATION_SKIP_FRAMES: i32 = 3;
+
+/// 一个描述模组安装方向的枚举，由传感器的镜像与翻转组合而成。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    /// 正装。
+    Normal,
+    /// 倒装，旋转 180°，即同时镜像与翻转。
+    Rot180,
+    /// 水平镜像。
+    MirrorH,
+    /// 垂直翻转。
+    MirrorV,
+}
+
+impl Orientation {
+    /// 对应的 `(镜像, 翻转)`。
+    pub fn mirror_flip(self) -> (bool, bool) {
+        match self {
+            Self::Normal => (false, false),
+            Self::Rot180 => (true, true),
+            Self::MirrorH => (true, false),
+            Self::MirrorV => (false, true),
+        }
+    }
+
+    /// 由 `(镜像, 翻转)` 转换。
+    pub fn from_mirror_flip(mirror: bool, flip: bool) -> Self {
+        match (mirror, flip) {
+            (false, false) => Self::Normal,
+            (true, true) => Self::Rot180,
+            (true, false) => Self::MirrorH,
+            (false, true) => Self::MirrorV,
+        }
+    }
+}
+
+/// 切换安装方向并重新加载畸变校正网格，见 `Context::set_orientation`。
+pub(crate) fn set_orientation_with<C: Miscellaneous + LDCH + FEC + ?Sized>(
+    ctx: &C,
+    orientation: Orientation,
+) -> XCamResult<()> {
+    let ldch = ctx.is_ldch_enabled().unwrap_or(false);
+    let fec = ctx.is_fec_enabled().unwrap_or(false);
+    if ldch {
+        ctx.disable_ldch()?;
+    }
+    if fec {
+        ctx.disable_fec()?;
+    }
+    let (mirror, flip) = orientation.mirror_flip();
+    let r = ctx.set_mirror_flip(mirror, flip, ORIENTATION_SKIP_FRAMES);
+    // 切换失败时同样恢复网格，不使画面失去校正。
+    let fec_r = if fec { ctx.enable_fec() } else { Ok(()) };
+    let ldch_r = if ldch { ctx.enable_ldch() } else { Ok(()) };
+    r.and(fec_r).and(ldch_r)
+}
+
+impl Context {
+    /// 设置模组的安装方向，例如倒装时使用 `Orientation::Rot180`。
+    ///
+    /// 镜像与翻转在传感器读出时完成，ISP 之后的各模块看到的已是翻转后的画面。LDCH 与 FEC 的
+    /// 校正网格按标定时的方向生成，只翻转传感器会使网格作用在错误的位置，例如倒装时校正的是
+    /// 对角的畸变。因此本接口先关闭已开启的 LDCH、FEC，设置镜像与翻转，再重新开启，由算法按
+    /// 新的方向重新生成网格；切换期间丢弃 `ORIENTATION_SKIP_FRAMES` 帧。
+    ///
+    /// # Note
+    ///
+    /// 只会重新加载通过 `enable_ldch`、`enable_fec` 开启的网格（见 `is_ldch_enabled`），由 IQ 文件
+    /// 开启的网格应先以 `enable_ldch`、`enable_fec` 设置一次。网格来自外部文件、或 SDK 版本不按
+    /// 镜像状态重新生成网格时，重新开启不足以调整方向，需要以对应方向标定的 IQ 文件重新初始化。
+    /// 设置失败时仍会恢复网格的开关，返回第一个错误。
+    pub fn set_orientation(&self, orientation: Orientation) -> XCamResult<()> {
+        set_orientation_with(self, orientation)
+    }
+
+    /// 获取模组的安装方向，取自传感器的镜像与翻转。
+    pub fn get_orientation(&self) -> XCamResult<Orientation> {
+        let (mirror, flip) = self.get_mirror_flip()?;
+        Ok(Orientation::from_mirror_flip(mirror, flip))
+    }
+}
+
 impl Miscellaneous for Context {
     fn get_gray_mode(&self) -> GrayMode {
         unsafe { ffi::rk_aiq_uapi_getGrayMode(self.internal.as_ptr()) }
     }
 
     fn set_gray_mode<T: Into<GrayMode>>(&self, mode: T) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setGrayMode(
-                self.internal.as_ptr(),
-                mode.into(),
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi_setGrayMode(self, mode.into()))
     }
 
     fn get_frame_rate(&self) -> XCamResult<FrameRateInfo> {
         let mut info: FrameRateInfo = Default::default();
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getFrameRate(
-                self.internal.as_ptr(),
-                &mut info,
-            ))
-            .ok()
-            .map(|_| info)
-        }
+        uapi_call!(ffi::rk_aiq_uapi_getFrameRate(self, &mut info)).map(|_| info)
     }
 
     fn set_frame_rate<T: Into<FrameRateInfo>>(&self, info: T) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setFrameRate(
-                self.internal.as_ptr(),
-                info.into(),
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi_setFrameRate(self, info.into()))
     }
 
     fn get_mirror_flip(&self) -> XCamResult<(bool, bool)> {
         let mut mirror: bool = false;
         let mut flip: bool = false;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getMirrorFlip(
-                self.internal.as_ptr(),
-                &mut mirror,
-                &mut flip,
-            ))
-            .ok()
+        uapi_call!(ffi::rk_aiq_uapi_getMirrorFlip(self, &mut mirror, &mut flip))
             .map(|_| (mirror, flip))
-        }
     }
 
     fn set_mirror_flip(&self, mirror: bool, flip: bool, skip_frm_cnt: i32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setMirroFlip(
-                self.internal.as_ptr(),
-                mirror,
-                flip,
-                skip_frm_cnt,
-            ))
-            .ok()
+        uapi_call!(ffi::rk_aiq_uapi_setMirroFlip(
+            self,
+            mirror,
+            flip,
+            skip_frm_cnt
+        ))
+    }
+}
+
+impl Miscellaneous for SharedContext {
+    fn get_gray_mode(&self) -> GrayMode {
+        self.inner.get_gray_mode()
+    }
+
+    fn set_gray_mode<T: Into<GrayMode>>(&self, mode: T) -> XCamResult<()> {
+        self.inner.set_gray_mode(mode)
+    }
+
+    fn get_frame_rate(&self) -> XCamResult<FrameRateInfo> {
+        self.inner.get_frame_rate()
+    }
+
+    fn set_frame_rate<T: Into<FrameRateInfo>>(&self, info: T) -> XCamResult<()> {
+        self.inner.set_frame_rate(info)
+    }
+
+    fn get_mirror_flip(&self) -> XCamResult<(bool, bool)> {
+        self.inner.get_mirror_flip()
+    }
+
+    fn set_mirror_flip(&self, mirror: bool, flip: bool, skip_frm_cnt: i32) -> XCamResult<()> {
+        self.inner.set_mirror_flip(mirror, flip, skip_frm_cnt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::XCamError;
+    use crate::mock::MockContext;
+
+    #[test]
+    fn test_orientation_rot180_reloads_meshes() {
+        let ctx = MockContext::new();
+        {
+            let mut state = ctx.state.borrow_mut();
+            state.ldch_enabled = true;
+            state.fec_enabled = true;
+        }
+        set_orientation_with(&ctx, Orientation::Rot180).unwrap();
+        assert_eq!(ctx.get_mirror_flip(), Ok((true, true)));
+        assert_eq!(
+            ctx.calls(),
+            [
+                "is_ldch_enabled",
+                "is_fec_enabled",
+                "disable_ldch",
+                "disable_fec",
+                "set_mirror_flip",
+                "enable_fec",
+                "enable_ldch",
+                "get_mirror_flip"
+            ]
+        );
+        let state = ctx.state.borrow();
+        assert!(state.ldch_enabled && state.fec_enabled);
+    }
+
+    #[test]
+    fn test_orientation_keeps_disabled_meshes() {
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().fec_enabled = true;
+        ctx.fail("set_mirror_flip");
+        assert_eq!(
+            set_orientation_with(&ctx, Orientation::MirrorV),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))
+        );
+        // 切换失败时仍恢复已开启的网格，未开启的网格不受影响。
+        assert!(!ctx.calls().contains(&"enable_ldch"));
+        assert!(ctx.calls().contains(&"enable_fec"));
+        assert!(ctx.state.borrow().fec_enabled);
+
+        for o in [
+            Orientation::Normal,
+            Orientation::Rot180,
+            Orientation::MirrorH,
+            Orientation::MirrorV,
+        ] {
+            let (mirror, flip) = o.mirror_flip();
+            assert_eq!(Orientation::from_mirror_flip(mirror, flip), o);
         }
     }
 }
@@ -0,0 +1,1382 @@
+//! 测试用的模拟上下文。
+//!
+//! 以内存状态模拟各模块的接口，并记录调用顺序，便于在没有硬件的环境下验证组合逻辑。
+use super::acm::AutoColorManagment;
+use super::adjust::CprocLevels;
+#[cfg(feature = "v1_0")]
+use super::ae::AeMode;
+use super::ae::{self, AeMeasAreaType, AutoExposure, ExpInfo, ExposurePriority, SavedExpRanges};
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::ae::{LinExpAttr, SavedToleranceIn};
+use super::af::{
+    self, AfSearchState, AfZoneWeights, AutoFocus, FocusMeter, AF_ZONE_COLS, AF_ZONE_ROWS,
+};
+use super::awb::{self, AutoWhiteBalance};
+use super::blc::{BlackLevel, BlackLevelCorrection};
+use super::ccm::{Ccm, CcmQueryInfo, ColorCorrection, Lut3dState, Lut3dTable, LUT3D_TABLE_LEN};
+use super::clock::Clock;
+use super::defog::Defog;
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+use super::defog::DehazeAttrib;
+use super::error::XCamError;
+use super::fec::FEC;
+use super::ffi;
+use super::gamma::Gamma;
+use super::hdr::{check_hdr_exp_ratio, HighDynamicRange};
+use super::ldch::LDCH;
+use super::luma::{self, LumaMap, LUMA_STATS_TIMEOUT};
+use super::misc::Miscellaneous;
+use super::nr::{nr_balance_from_percent, nr_balance_to_percent, NoiseRemoval, NrState};
+use super::sharpen::{SharpAttrib, Sharpen};
+use super::sysctl::{check_isp_clock, IspErrorFlags, PrepareResult, SystemControl};
+use super::types::{
+    AlgoContext, AlgoDescComm, AntiFlickerMode, CpslCap, CpslCfg, CpslInfo, ExpPwrLineFreq,
+    FrameRateInfo, GammaAttr, GrayMode, IspStats, ModuleId, OpMode, Rect, Strength, WbGain,
+    WbScene, WorkingMode, XCamResult,
+};
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+/// 模拟的曝光路径数组长度。
+pub(crate) const MOCK_EXP_ROUTE_LEN: usize = 10;
+
+/// 模拟的传感器像素格式，`MEDIA_BUS_FMT_SBGGR10_1X10`。
+pub(crate) const MOCK_PIXEL_FORMAT: u32 = 0x3007;
+
+/// 模拟上下文的内部状态。
+pub(crate) struct MockState {
+    pub wb_mode: OpMode,
+    pub wb_gain: WbGain,
+    pub wb_ct: u32,
+    pub wb_scene: WbScene,
+    /// 手动增益的平滑生效标志，`None` 表示属性中没有该标志。
+    pub mwb_smooth: Option<bool>,
+    pub awb_locked: bool,
+    /// 当前的色温范围限制，`None` 表示不限制。
+    pub awb_ct_range: Option<(u32, u32)>,
+    pub awb_speed: f32,
+    /// 首次限制色温范围前的设置。
+    pub awb_ct_defaults: Option<Option<(u32, u32)>>,
+    /// 最近一次设置的手动 CCM，`None` 表示自动模式。
+    pub manual_ccm: Option<Ccm>,
+    /// AE 统计窗口，为 `None` 时不支持。
+    pub ae_stats_window: Option<Rect>,
+    pub ae_speed: f32,
+    /// 3D LUT 的状态，为 `None` 时没有该模块。
+    pub lut3d: Option<Lut3dState>,
+    /// 3D LUT 的手动查找表，没有该模块时忽略。
+    pub lut3d_table: Lut3dTable,
+    /// 手动黑电平，以 `blc::BLC_DEPTH` 表示。
+    pub blc_manual: BlackLevel,
+    pub dhz_enabled: bool,
+    pub dhz_mode: OpMode,
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    pub dhz_attrib: DehazeAttrib,
+    pub gamma: GammaAttr,
+    pub hdr_mode: OpMode,
+    pub hdr_strth: (bool, u32),
+    /// 曝光比的范围，`None` 表示不支持。
+    pub hdr_ratio_limits: Option<(f32, f32)>,
+    pub hdr_exp_ratio: f32,
+    /// 被显式设置过的模块使能状态，未设置的模块视为使能。
+    pub modules: Vec<(ModuleId, bool)>,
+    /// 按调用顺序记录的 `(算法类型, 是否使能)`，未记录的算法视为使能。
+    pub ax_libs: Vec<(i32, bool)>,
+    pub crop: Rect,
+    /// `update_iq` 时 IQ 文件的内容。
+    pub iq_loaded: Option<Vec<u8>>,
+    /// `get_luma_map` 返回的亮度图，为 `None` 时由 `stats` 计算。
+    pub luma_map: Option<LumaMap>,
+    pub streaming: bool,
+    /// 平台允许的 ISP 时钟频率与当前频率，为 `None` 时不支持时钟控制。
+    pub isp_clock: Option<(Vec<u32>, u32)>,
+    /// 最近一次设置的补光灯手动开关状态。
+    pub cpsl_on: bool,
+    pub gray_mode: GrayMode,
+    pub nr_mode: OpMode,
+    pub anr_strength: Strength,
+    /// 算法状态中实际生效的降噪参数。
+    pub nr_status: NrState,
+    /// 空域降噪开关。
+    pub ms_nr_on: bool,
+    /// 时域降噪开关。
+    pub mt_nr_on: bool,
+    /// 亮度、色度降噪的 SDK 强度比例。
+    pub nr_balance: (f32, f32),
+    pub sharpness: Strength,
+    /// 算法状态中实际生效的锐化等级。
+    pub sharp_status: u8,
+    pub cproc: CprocLevels,
+    /// `prepare` 协商得到的分辨率，`None` 时与请求相同。
+    pub negotiated: Option<(u32, u32)>,
+    pub focus_mode: OpMode,
+    pub focus_position: u16,
+    pub af_zone_weights: AfZoneWeights,
+    pub af_search_state: AfSearchState,
+    pub focus_meter: FocusMeter,
+    pub isp_error_flags: IspErrorFlags,
+    pub exp_mode: OpMode,
+    pub exp_gain_range: (f32, f32),
+    pub exp_time_range: (f32, f32),
+    /// 传感器可达的增益范围，不随 `exp_gain_range` 收窄。
+    pub sensor_gain_range: (f32, f32),
+    /// 手动曝光的 `(增益, 时间)`。
+    pub manual_exp: (f32, f32),
+    /// 手动曝光的 `(模拟增益, 数字增益)`。
+    pub manual_gains: (f32, f32),
+    /// ISP 数字增益上限，`None` 表示由 IQ 文件决定。
+    pub dgain_cap: Option<f32>,
+    /// 曝光路径，为 `None` 时视为不支持。
+    pub exp_route: Option<Vec<ae::ExpRoutePoint>>,
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub ae_weights: Vec<u8>,
+    pub blc_mode: (bool, AeMeasAreaType),
+    pub blc_strength: i32,
+    pub hlc_mode: bool,
+    pub hlc_strength: i32,
+    pub dark_area_boost: u32,
+    pub anti_flicker_mode: AntiFlickerMode,
+    pub pwr_line_freq: ExpPwrLineFreq,
+    pub exp_info: ExpInfo,
+    pub base_iso: u32,
+    pub frame_rate: FrameRateInfo,
+    pub mirror_flip: (bool, bool),
+    pub ldch_enabled: bool,
+    pub fec_enabled: bool,
+    pub exp_priority: ExposurePriority,
+    pub exp_priority_ranges: Option<SavedExpRanges>,
+    /// 传感器曝光时间表，为 `None` 时视为不支持。
+    pub exposure_steps: Option<Vec<f32>>,
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub lin_exp_attr: LinExpAttr,
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub ae_deadband_tolerance_in: Option<SavedToleranceIn>,
+    pub frame_id: Option<u32>,
+    /// 每次读取帧号前帧号的增量，为 0 时帧号保持不变。
+    pub frame_step: u32,
+    /// 待读取的 3A 统计信息，读取后清空。
+    pub stats: Option<IspStats>,
+    /// 帧号增长后以新帧号调用，用于模拟逐帧变化的状态。
+    pub on_frame: Option<fn(u32, &mut MockState)>,
+}
+
+impl MockState {
+    fn set_module(&mut self, id: ModuleId, enabled: bool) {
+        self.modules.retain(|(m, _)| *m != id);
+        self.modules.push((id, enabled));
+    }
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self {
+            wb_mode: OpMode::Auto,
+            wb_gain: WbGain {
+                rgain: 1.0,
+                grgain: 1.0,
+                gbgain: 1.0,
+                bgain: 1.0,
+            },
+            wb_ct: 5000,
+            wb_scene: WbScene::default(),
+            mwb_smooth: None,
+            awb_locked: false,
+            awb_ct_range: None,
+            awb_speed: 1.0,
+            awb_ct_defaults: None,
+            manual_ccm: None,
+            ae_stats_window: Some(Rect::default()),
+            ae_speed: 0.5,
+            lut3d: None,
+            lut3d_table: Lut3dTable {
+                r: vec![0; LUT3D_TABLE_LEN],
+                g: vec![0; LUT3D_TABLE_LEN],
+                b: vec![0; LUT3D_TABLE_LEN],
+            },
+            blc_manual: BlackLevel::default(),
+            dhz_enabled: false,
+            dhz_mode: OpMode::Auto,
+            #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+            dhz_attrib: DehazeAttrib::default(),
+            gamma: GammaAttr::default(),
+            hdr_mode: OpMode::Auto,
+            hdr_strth: (false, 50),
+            hdr_ratio_limits: None,
+            hdr_exp_ratio: 1.0,
+            modules: Vec::new(),
+            ax_libs: Vec::new(),
+            crop: Rect::default(),
+            iq_loaded: None,
+            luma_map: None,
+            streaming: false,
+            isp_clock: None,
+            cpsl_on: false,
+            gray_mode: GrayMode::RK_AIQ_GRAY_MODE_OFF,
+            nr_mode: OpMode::Auto,
+            anr_strength: Strength::new(50).unwrap(),
+            nr_status: NrState::default(),
+            ms_nr_on: true,
+            mt_nr_on: true,
+            nr_balance: (0.5, 0.5),
+            sharpness: Strength::new(50).unwrap(),
+            sharp_status: 0,
+            cproc: CprocLevels::default(),
+            negotiated: None,
+            focus_mode: OpMode::Auto,
+            focus_position: 0,
+            af_zone_weights: [[1; AF_ZONE_COLS]; AF_ZONE_ROWS],
+            af_search_state: AfSearchState::Done,
+            focus_meter: FocusMeter::default(),
+            isp_error_flags: IspErrorFlags::empty(),
+            exp_mode: OpMode::Auto,
+            exp_gain_range: (1.0, 64.0),
+            exp_time_range: (0.0001, 1.0 / 30.0),
+            sensor_gain_range: (1.0, 64.0),
+            manual_exp: (1.0, 1.0 / 30.0),
+            manual_gains: (1.0, 1.0),
+            dgain_cap: None,
+            exp_route: None,
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            ae_weights: {
+                let (cols, rows) = ae::ae_grid_dimensions();
+                vec![1; cols * rows]
+            },
+            blc_mode: (false, AeMeasAreaType::Auto),
+            blc_strength: 1,
+            hlc_mode: false,
+            hlc_strength: 1,
+            dark_area_boost: 1,
+            anti_flicker_mode: AntiFlickerMode::default(),
+            pwr_line_freq: ExpPwrLineFreq::default(),
+            exp_info: ExpInfo::default(),
+            base_iso: ae::DEFAULT_BASE_ISO,
+            frame_rate: FrameRateInfo::default(),
+            mirror_flip: (false, false),
+            ldch_enabled: false,
+            fec_enabled: false,
+            exp_priority: ExposurePriority::Auto,
+            exp_priority_ranges: None,
+            exposure_steps: None,
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            lin_exp_attr: LinExpAttr::default(),
+            #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+            ae_deadband_tolerance_in: None,
+            frame_id: None,
+            frame_step: 0,
+            stats: None,
+            on_frame: None,
+        }
+    }
+}
+
+/// 一个模拟 `Context` 的类型。
+pub(crate) struct MockContext {
+    pub state: RefCell<MockState>,
+    calls: RefCell<Vec<&'static str>>,
+    failing: RefCell<Vec<&'static str>>,
+}
+
+impl MockContext {
+    pub fn new() -> Self {
+        Self {
+            state: RefCell::new(MockState::default()),
+            calls: RefCell::new(Vec::new()),
+            failing: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// 记录一次接口调用；若该接口被设置为失败则返回错误。
+    pub fn call(&self, name: &'static str) -> XCamResult<()> {
+        self.calls.borrow_mut().push(name);
+        if self.failing.borrow().contains(&name) {
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 使后续对指定接口的调用返回错误。
+    pub fn fail(&self, name: &'static str) {
+        self.failing.borrow_mut().push(name);
+    }
+
+    /// 返回已记录的接口调用序列。
+    pub fn calls(&self) -> Vec<&'static str> {
+        self.calls.borrow().clone()
+    }
+}
+
+/// 一个只在休眠时推进的时钟。
+pub(crate) struct MockClock {
+    start: Instant,
+    elapsed: Cell<Duration>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Cell::new(Duration::ZERO),
+        }
+    }
+
+    /// 累计休眠的时间。
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed.get()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed.get()
+    }
+
+    fn sleep(&self, dur: Duration) {
+        self.elapsed.set(self.elapsed.get() + dur);
+    }
+}
+
+impl AutoColorManagment for MockContext {
+    fn get_brightness(&self) -> XCamResult<u32> {
+        self.call("get_brightness")?;
+        Ok(self.state.borrow().cproc.brightness)
+    }
+
+    fn set_brightness(&self, val: u32) -> XCamResult<()> {
+        self.call("set_brightness")?;
+        self.state.borrow_mut().cproc.brightness = val;
+        Ok(())
+    }
+
+    fn get_contrast(&self) -> XCamResult<u32> {
+        self.call("get_contrast")?;
+        Ok(self.state.borrow().cproc.contrast)
+    }
+
+    fn set_contrast(&self, val: u32) -> XCamResult<()> {
+        self.call("set_contrast")?;
+        self.state.borrow_mut().cproc.contrast = val;
+        Ok(())
+    }
+
+    fn get_saturation(&self) -> XCamResult<Strength> {
+        self.call("get_saturation")?;
+        Ok(Strength::new(50).unwrap())
+    }
+
+    fn set_saturation(&self, _val: Strength) -> XCamResult<()> {
+        self.call("set_saturation")
+    }
+
+    fn get_hue(&self) -> XCamResult<u32> {
+        self.call("get_hue")?;
+        Ok(CprocLevels::default().brightness)
+    }
+
+    fn set_hue(&self, _val: u32) -> XCamResult<()> {
+        self.call("set_hue")
+    }
+}
+
+impl AutoWhiteBalance for MockContext {
+    fn get_wb_mode(&self) -> XCamResult<OpMode> {
+        self.call("get_wb_mode")?;
+        Ok(self.state.borrow().wb_mode)
+    }
+
+    fn set_wb_mode(&self, mode: OpMode) -> XCamResult<()> {
+        self.call("set_wb_mode")?;
+        self.state.borrow_mut().wb_mode = mode;
+        Ok(())
+    }
+
+    fn lock_awb(&self) -> XCamResult<()> {
+        self.call("lock_awb")?;
+        self.state.borrow_mut().awb_locked = true;
+        Ok(())
+    }
+
+    fn unlock_awb(&self) -> XCamResult<()> {
+        self.call("unlock_awb")?;
+        self.state.borrow_mut().awb_locked = false;
+        Ok(())
+    }
+
+    fn get_awb_speed(&self) -> XCamResult<f32> {
+        self.call("get_awb_speed")?;
+        Ok(self.state.borrow().awb_speed)
+    }
+
+    fn set_awb_speed(&self, speed: f32) -> XCamResult<()> {
+        let speed = awb::clamp_awb_speed(speed)?;
+        self.call("set_awb_speed")?;
+        self.state.borrow_mut().awb_speed = speed;
+        Ok(())
+    }
+
+    fn get_awb_ct_range(&self) -> XCamResult<Option<(u32, u32)>> {
+        self.call("get_awb_ct_range")?;
+        Ok(self.state.borrow().awb_ct_range)
+    }
+
+    fn set_awb_ct_range(&self, min_k: u32, max_k: u32) -> XCamResult<()> {
+        awb::check_awb_ct_range(min_k, max_k)?;
+        self.call("set_awb_ct_range")?;
+        let mut state = self.state.borrow_mut();
+        let current = state.awb_ct_range;
+        state.awb_ct_defaults.get_or_insert(current);
+        state.awb_ct_range = Some((min_k, max_k));
+        Ok(())
+    }
+
+    fn reset_awb_ct_range(&self) -> XCamResult<()> {
+        self.call("reset_awb_ct_range")?;
+        let mut state = self.state.borrow_mut();
+        if let Some(defaults) = state.awb_ct_defaults.take() {
+            state.awb_ct_range = defaults;
+        }
+        Ok(())
+    }
+
+    fn get_awb_calib_ct_range(&self) -> XCamResult<Option<(u32, u32)>> {
+        self.call("get_awb_calib_ct_range")?;
+        let state = self.state.borrow();
+        Ok(state.awb_ct_defaults.unwrap_or(state.awb_ct_range))
+    }
+
+    fn get_mwb_scene(&self) -> XCamResult<WbScene> {
+        self.call("get_mwb_scene")?;
+        Ok(self.state.borrow().wb_scene)
+    }
+
+    fn set_mwb_scene<T: Into<WbScene>>(&self, scene: T) -> XCamResult<()> {
+        self.call("set_mwb_scene")?;
+        self.state.borrow_mut().wb_scene = scene.into();
+        Ok(())
+    }
+
+    fn get_mwb_gain(&self) -> XCamResult<WbGain> {
+        self.call("get_mwb_gain")?;
+        Ok(self.state.borrow().wb_gain)
+    }
+
+    fn set_mwb_gain<T: Into<WbGain>>(&self, gain: T) -> XCamResult<()> {
+        self.call("set_mwb_gain")?;
+        self.state.borrow_mut().wb_gain = gain.into();
+        Ok(())
+    }
+
+    fn get_mwb_ct(&self) -> XCamResult<u32> {
+        self.call("get_mwb_ct")?;
+        Ok(self.state.borrow().wb_ct)
+    }
+
+    fn set_mwb_ct(&self, ct: u32) -> XCamResult<()> {
+        self.call("set_mwb_ct")?;
+        self.state.borrow_mut().wb_ct = ct;
+        Ok(())
+    }
+
+    fn get_mwb_smooth_flag(&self) -> XCamResult<bool> {
+        self.call("get_mwb_smooth_flag")?;
+        self.state.borrow().mwb_smooth.ok_or(XCamError::Unsupported)
+    }
+
+    fn set_mwb_smooth_flag(&self, smooth: bool) -> XCamResult<()> {
+        self.call("set_mwb_smooth_flag")?;
+        let mut state = self.state.borrow_mut();
+        let flag = state.mwb_smooth.as_mut().ok_or(XCamError::Unsupported)?;
+        *flag = smooth;
+        Ok(())
+    }
+}
+
+impl AutoExposure for MockContext {
+    #[cfg(feature = "v1_0")]
+    fn get_ae_mode(&self) -> XCamResult<AeMode> {
+        self.call("get_ae_mode")?;
+        Ok(AeMode::Auto)
+    }
+
+    #[cfg(feature = "v1_0")]
+    fn set_ae_mode(&self, _mode: AeMode) -> XCamResult<()> {
+        self.call("set_ae_mode")
+    }
+
+    fn get_exp_mode(&self) -> XCamResult<OpMode> {
+        self.call("get_exp_mode")?;
+        Ok(self.state.borrow().exp_mode)
+    }
+
+    fn get_ae_stats_window(&self) -> XCamResult<Rect> {
+        self.call("get_ae_stats_window")?;
+        self.state
+            .borrow()
+            .ae_stats_window
+            .ok_or(XCamError::Unsupported)
+    }
+
+    fn set_ae_stats_window(&self, rect: Rect) -> XCamResult<()> {
+        self.call("set_ae_stats_window")?;
+        let mut state = self.state.borrow_mut();
+        let window = state
+            .ae_stats_window
+            .as_mut()
+            .ok_or(XCamError::Unsupported)?;
+        *window = rect;
+        Ok(())
+    }
+
+    fn get_ae_speed(&self) -> XCamResult<f32> {
+        self.call("get_ae_speed")?;
+        Ok(self.state.borrow().ae_speed)
+    }
+
+    fn set_ae_speed(&self, speed: f32) -> XCamResult<()> {
+        if !(speed > 0.0 && speed <= 1.0) {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        self.call("set_ae_speed")?;
+        self.state.borrow_mut().ae_speed = speed;
+        Ok(())
+    }
+
+    fn set_exp_mode(&self, mode: OpMode) -> XCamResult<()> {
+        self.call("set_exp_mode")?;
+        self.state.borrow_mut().exp_mode = mode;
+        Ok(())
+    }
+
+    fn get_exp_gain_range(&self) -> XCamResult<(f32, f32)> {
+        self.call("get_exp_gain_range")?;
+        Ok(self.state.borrow().exp_gain_range)
+    }
+
+    fn set_exp_gain_range(&self, min: f32, max: f32) -> XCamResult<()> {
+        self.call("set_exp_gain_range")?;
+        self.state.borrow_mut().exp_gain_range = (min, max);
+        Ok(())
+    }
+
+    fn get_exp_time_range(&self) -> XCamResult<(f32, f32)> {
+        self.call("get_exp_time_range")?;
+        Ok(self.state.borrow().exp_time_range)
+    }
+
+    fn set_exp_time_range(&self, min: f32, max: f32) -> XCamResult<()> {
+        self.call("set_exp_time_range")?;
+        self.state.borrow_mut().exp_time_range = (min, max);
+        Ok(())
+    }
+
+    fn set_manual_exp(&self, gain: f32, time: f32) -> XCamResult<()> {
+        self.call("set_manual_exp")?;
+        self.state.borrow_mut().manual_exp = (gain, time);
+        Ok(())
+    }
+
+    fn set_manual_exp_fps(&self, gain: f32, time_fps: usize) -> XCamResult<()> {
+        self.set_manual_exp(gain, 1.0 / (time_fps as f32))
+    }
+
+    fn set_manual_exp_ms(&self, gain: f32, time_ms: usize) -> XCamResult<()> {
+        self.set_manual_exp(gain, 1.0 / 1000.0 * (time_ms as f32))
+    }
+
+    fn set_manual_exp_us(&self, gain: f32, time_us: usize) -> XCamResult<()> {
+        self.set_manual_exp(gain, 1.0 / 1000000.0 * (time_us as f32))
+    }
+
+    fn set_blc_mode(&self, enabled: bool, mode: AeMeasAreaType) -> XCamResult<()> {
+        self.call("set_blc_mode")?;
+        self.state.borrow_mut().blc_mode = (enabled, mode);
+        Ok(())
+    }
+
+    fn set_blc_strength(&self, strength: i32) -> XCamResult<()> {
+        self.call("set_blc_strength")?;
+        self.state.borrow_mut().blc_strength = strength;
+        Ok(())
+    }
+
+    fn set_hlc_mode(&self, enabled: bool) -> XCamResult<()> {
+        self.call("set_hlc_mode")?;
+        self.state.borrow_mut().hlc_mode = enabled;
+        Ok(())
+    }
+
+    fn set_hlc_strength(&self, strength: i32) -> XCamResult<()> {
+        self.call("set_hlc_strength")?;
+        self.state.borrow_mut().hlc_strength = strength;
+        Ok(())
+    }
+
+    fn get_dark_area_boost_strth(&self) -> XCamResult<u32> {
+        self.call("get_dark_area_boost_strth")?;
+        Ok(self.state.borrow().dark_area_boost)
+    }
+
+    fn set_dark_area_boost_strth(&self, level: u32) -> XCamResult<()> {
+        self.call("set_dark_area_boost_strth")?;
+        self.state.borrow_mut().dark_area_boost = level;
+        Ok(())
+    }
+
+    fn get_anti_flicker_mode(&self) -> XCamResult<AntiFlickerMode> {
+        self.call("get_anti_flicker_mode")?;
+        Ok(self.state.borrow().anti_flicker_mode)
+    }
+
+    fn set_anti_flicker_mode<T: Into<AntiFlickerMode>>(&self, mode: T) -> XCamResult<()> {
+        self.call("set_anti_flicker_mode")?;
+        self.state.borrow_mut().anti_flicker_mode = mode.into();
+        Ok(())
+    }
+
+    fn get_exp_pwr_line_freq_mode(&self) -> XCamResult<ExpPwrLineFreq> {
+        self.call("get_exp_pwr_line_freq_mode")?;
+        Ok(self.state.borrow().pwr_line_freq)
+    }
+
+    fn set_exp_pwr_line_freq_mode<T: Into<ExpPwrLineFreq>>(&self, mode: T) -> XCamResult<()> {
+        self.call("set_exp_pwr_line_freq_mode")?;
+        self.state.borrow_mut().pwr_line_freq = mode.into();
+        Ok(())
+    }
+
+    fn get_base_iso(&self) -> u32 {
+        self.state.borrow().base_iso
+    }
+
+    fn set_base_iso(&self, base_iso: u32) -> XCamResult<()> {
+        ae::check_base_iso(base_iso)?;
+        self.call("set_base_iso")?;
+        self.state.borrow_mut().base_iso = base_iso;
+        Ok(())
+    }
+
+    fn get_exposure_steps(&self) -> XCamResult<Vec<f32>> {
+        self.call("get_exposure_steps")?;
+        self.state
+            .borrow()
+            .exposure_steps
+            .clone()
+            .ok_or(XCamError::Unsupported)
+    }
+
+    fn get_sensor_gain_range(&self) -> XCamResult<(f32, f32)> {
+        self.call("get_sensor_gain_range")?;
+        Ok(self.state.borrow().sensor_gain_range)
+    }
+
+    /// 以曝光范围模拟，与 v1_0 至 v3_0 的实现相同。
+    fn set_exposure_priority(&self, priority: ExposurePriority) -> XCamResult<()> {
+        self.call("set_exposure_priority")?;
+        let mut saved = self.state.borrow().exp_priority_ranges;
+        let r = ae::emulate_exposure_priority(self, &mut saved, priority);
+        let mut state = self.state.borrow_mut();
+        state.exp_priority_ranges = saved;
+        if r.is_ok() {
+            state.exp_priority = priority;
+        }
+        r
+    }
+
+    fn query_exp_info(&self) -> XCamResult<ExpInfo> {
+        self.call("query_exp_info")?;
+        let mut info = self.state.borrow().exp_info;
+        info.iso = super::ae::gain_to_iso(info.gain, self.get_base_iso());
+        Ok(info)
+    }
+
+    fn get_manual_gains(&self) -> XCamResult<(f32, f32)> {
+        self.call("get_manual_gains")?;
+        Ok(self.state.borrow().manual_gains)
+    }
+
+    fn set_manual_gains(&self, analog: f32, digital: f32) -> XCamResult<()> {
+        let (_, analog_max) = self.get_sensor_gain_range()?;
+        super::ae::check_manual_gains(analog, digital, analog_max)?;
+        self.call("set_manual_gains")?;
+        self.state.borrow_mut().manual_gains = (analog, digital);
+        Ok(())
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_global_digital_gain_cap(&self) -> XCamResult<Option<f32>> {
+        self.call("get_global_digital_gain_cap")?;
+        Ok(self.state.borrow().dgain_cap)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_global_digital_gain_cap(&self, cap: f32) -> XCamResult<()> {
+        super::ae::check_digital_gain_cap(cap)?;
+        self.call("set_global_digital_gain_cap")?;
+        self.state.borrow_mut().dgain_cap = Some(cap);
+        Ok(())
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_ae_weights(&self) -> XCamResult<Vec<u8>> {
+        self.call("get_ae_weights")?;
+        Ok(self.state.borrow().ae_weights.clone())
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_weights(&self, weights: &[u8]) -> XCamResult<()> {
+        ae::check_ae_weights(weights)?;
+        self.call("set_ae_weights")?;
+        self.state.borrow_mut().ae_weights = weights.to_vec();
+        Ok(())
+    }
+
+    fn get_exposure_route(&self) -> XCamResult<Vec<ae::ExpRoutePoint>> {
+        self.call("get_exposure_route")?;
+        self.state
+            .borrow()
+            .exp_route
+            .clone()
+            .ok_or(XCamError::Unsupported)
+    }
+
+    fn set_exposure_route(&self, points: &[ae::ExpRoutePoint]) -> Result<(), ae::ExpRouteError> {
+        ae::check_exp_route(points, MOCK_EXP_ROUTE_LEN)?;
+        self.call("set_exposure_route")?;
+        self.state.borrow_mut().exp_route = Some(points.to_vec());
+        Ok(())
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_lin_exp_attr(&self) -> XCamResult<LinExpAttr> {
+        self.call("get_lin_exp_attr")?;
+        Ok(self.state.borrow().lin_exp_attr)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_lin_exp_attr<T: Into<LinExpAttr>>(&self, attr: T) -> XCamResult<()> {
+        self.call("set_lin_exp_attr")?;
+        self.state.borrow_mut().lin_exp_attr = attr.into();
+        Ok(())
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn set_ae_deadband(&self, luma_delta: u8) -> XCamResult<()> {
+        let mut saved = self.state.borrow().ae_deadband_tolerance_in;
+        let r = ae::apply_ae_deadband(self, &mut saved, luma_delta);
+        self.state.borrow_mut().ae_deadband_tolerance_in = saved;
+        r
+    }
+}
+
+impl AutoFocus for MockContext {
+    fn get_focus_mode(&self) -> XCamResult<OpMode> {
+        self.call("get_focus_mode")?;
+        Ok(self.state.borrow().focus_mode)
+    }
+
+    fn set_focus_mode(&self, mode: OpMode) -> XCamResult<()> {
+        self.call("set_focus_mode")?;
+        self.state.borrow_mut().focus_mode = mode;
+        Ok(())
+    }
+
+    fn get_focus_position(&self) -> XCamResult<u16> {
+        self.call("get_focus_position")?;
+        Ok(self.state.borrow().focus_position)
+    }
+
+    fn set_focus_position(&self, code: u16) -> XCamResult<()> {
+        self.call("set_focus_position")?;
+        self.state.borrow_mut().focus_position = code;
+        Ok(())
+    }
+
+    fn get_af_zone_weights(&self) -> XCamResult<AfZoneWeights> {
+        self.call("get_af_zone_weights")?;
+        Ok(self.state.borrow().af_zone_weights)
+    }
+
+    fn set_af_zone_weights(&self, weights: &AfZoneWeights) -> XCamResult<()> {
+        af::check_af_zone_weights(weights)?;
+        self.call("set_af_zone_weights")?;
+        self.state.borrow_mut().af_zone_weights = *weights;
+        Ok(())
+    }
+
+    fn get_af_search_state(&self) -> XCamResult<AfSearchState> {
+        self.call("get_af_search_state")?;
+        Ok(self.state.borrow().af_search_state)
+    }
+
+    fn get_focus_score(&self) -> XCamResult<f32> {
+        self.call("get_focus_score")?;
+        let sharpness = self.get_focus_sharpness()?;
+        Ok(self.state.borrow_mut().focus_meter.update(sharpness))
+    }
+}
+
+impl ColorCorrection for MockContext {
+    fn get_ccm_query_info(&self) -> XCamResult<CcmQueryInfo> {
+        self.call("get_ccm_query_info")?;
+        Ok(CcmQueryInfo {
+            enabled: true,
+            ccm: self.state.borrow().manual_ccm.unwrap_or_default(),
+            illuminants: Vec::new(),
+        })
+    }
+
+    fn get_ccm_mode(&self) -> XCamResult<OpMode> {
+        self.call("get_ccm_mode")?;
+        Ok(match self.state.borrow().manual_ccm {
+            Some(_) => OpMode::Manual,
+            None => OpMode::Auto,
+        })
+    }
+
+    fn set_manual_ccm(&self, ccm: &Ccm) -> XCamResult<()> {
+        if !ccm.is_finite() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        self.call("set_manual_ccm")?;
+        self.state.borrow_mut().manual_ccm = Some(*ccm);
+        Ok(())
+    }
+
+    fn set_ccm_mode(&self, mode: OpMode) -> XCamResult<()> {
+        self.call("set_ccm_mode")?;
+        let mut state = self.state.borrow_mut();
+        match mode {
+            OpMode::Auto => state.manual_ccm = None,
+            OpMode::Manual => {
+                state.manual_ccm.get_or_insert_with(Ccm::default);
+            }
+            _ => return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM)),
+        }
+        Ok(())
+    }
+
+    fn get_lut3d_state(&self) -> XCamResult<Lut3dState> {
+        self.call("get_lut3d_state")?;
+        self.state.borrow().lut3d.ok_or(XCamError::Unsupported)
+    }
+
+    fn set_lut3d_state(&self, lut3d: Lut3dState) -> XCamResult<()> {
+        self.call("set_lut3d_state")?;
+        let mut state = self.state.borrow_mut();
+        let current = state.lut3d.as_mut().ok_or(XCamError::Unsupported)?;
+        *current = lut3d;
+        Ok(())
+    }
+
+    fn get_lut3d_table(&self) -> XCamResult<Lut3dTable> {
+        self.call("get_lut3d_table")?;
+        let state = self.state.borrow();
+        state.lut3d.ok_or(XCamError::Unsupported)?;
+        Ok(state.lut3d_table.clone())
+    }
+
+    fn set_lut3d_table(&self, table: &Lut3dTable) -> XCamResult<()> {
+        table.check()?;
+        self.call("set_lut3d_table")?;
+        let mut state = self.state.borrow_mut();
+        state.lut3d.ok_or(XCamError::Unsupported)?;
+        state.lut3d_table = table.clone();
+        Ok(())
+    }
+}
+
+impl Defog for MockContext {
+    fn enable_dhz(&self) -> XCamResult<()> {
+        self.call("enable_dhz")?;
+        self.state.borrow_mut().dhz_enabled = true;
+        Ok(())
+    }
+
+    fn disable_dhz(&self) -> XCamResult<()> {
+        self.call("disable_dhz")?;
+        self.state.borrow_mut().dhz_enabled = false;
+        Ok(())
+    }
+
+    fn get_dhz_mode(&self) -> XCamResult<OpMode> {
+        self.call("get_dhz_mode")?;
+        Ok(self.state.borrow().dhz_mode)
+    }
+
+    fn set_dhz_mode(&self, mode: OpMode) -> XCamResult<()> {
+        self.call("set_dhz_mode")?;
+        self.state.borrow_mut().dhz_mode = mode;
+        Ok(())
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn get_dhz_attrib(&self) -> XCamResult<DehazeAttrib> {
+        self.call("get_dhz_attrib")?;
+        Ok(self.state.borrow().dhz_attrib)
+    }
+
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    fn set_dhz_attrib<T: Into<DehazeAttrib>>(&self, attr: T) -> XCamResult<()> {
+        self.call("set_dhz_attrib")?;
+        self.state.borrow_mut().dhz_attrib = attr.into();
+        Ok(())
+    }
+}
+
+impl BlackLevelCorrection for MockContext {
+    fn get_blc_manual(&self) -> XCamResult<BlackLevel> {
+        self.call("get_blc_manual")?;
+        Ok(self.state.borrow().blc_manual)
+    }
+
+    fn set_blc_manual(&self, level: BlackLevel) -> XCamResult<()> {
+        self.call("set_blc_manual")?;
+        self.state.borrow_mut().blc_manual = level;
+        Ok(())
+    }
+}
+
+impl Gamma for MockContext {
+    fn get_gamma_coef(&self) -> XCamResult<GammaAttr> {
+        self.call("get_gamma_coef")?;
+        Ok(self.state.borrow().gamma)
+    }
+
+    fn set_gamma_coef<T: Into<GammaAttr>>(&self, gamma_attr: T) -> XCamResult<()> {
+        self.call("set_gamma_coef")?;
+        self.state.borrow_mut().gamma = gamma_attr.into();
+        Ok(())
+    }
+}
+
+impl HighDynamicRange for MockContext {
+    fn get_hdr_mode(&self) -> XCamResult<OpMode> {
+        self.call("get_hdr_mode")?;
+        Ok(self.state.borrow().hdr_mode)
+    }
+
+    fn set_hdr_mode<T: Into<OpMode>>(&self, mode: T) -> XCamResult<()> {
+        self.call("set_hdr_mode")?;
+        self.state.borrow_mut().hdr_mode = mode.into();
+        Ok(())
+    }
+
+    fn get_hdr_strth(&self) -> XCamResult<(bool, u32)> {
+        self.call("get_hdr_strth")?;
+        Ok(self.state.borrow().hdr_strth)
+    }
+
+    fn set_hdr_strth(&self, enabled: bool, level: u32) -> XCamResult<()> {
+        self.call("set_hdr_strth")?;
+        self.state.borrow_mut().hdr_strth = (enabled, level);
+        Ok(())
+    }
+
+    fn get_hdr_ratio_limits(&self) -> XCamResult<(f32, f32)> {
+        self.call("get_hdr_ratio_limits")?;
+        self.state
+            .borrow()
+            .hdr_ratio_limits
+            .ok_or(XCamError::Unsupported)
+    }
+
+    fn set_hdr_exp_ratio(&self, ratio: f32) -> XCamResult<()> {
+        check_hdr_exp_ratio(ratio, self.get_hdr_ratio_limits()?)?;
+        self.call("set_hdr_exp_ratio")?;
+        self.state.borrow_mut().hdr_exp_ratio = ratio;
+        Ok(())
+    }
+}
+
+impl Miscellaneous for MockContext {
+    fn get_gray_mode(&self) -> GrayMode {
+        let _ = self.call("get_gray_mode");
+        self.state.borrow().gray_mode
+    }
+
+    fn set_gray_mode<T: Into<GrayMode>>(&self, mode: T) -> XCamResult<()> {
+        self.call("set_gray_mode")?;
+        self.state.borrow_mut().gray_mode = mode.into();
+        Ok(())
+    }
+
+    fn get_frame_rate(&self) -> XCamResult<FrameRateInfo> {
+        self.call("get_frame_rate")?;
+        Ok(self.state.borrow().frame_rate)
+    }
+
+    fn set_frame_rate<T: Into<FrameRateInfo>>(&self, info: T) -> XCamResult<()> {
+        self.call("set_frame_rate")?;
+        self.state.borrow_mut().frame_rate = info.into();
+        Ok(())
+    }
+
+    fn get_mirror_flip(&self) -> XCamResult<(bool, bool)> {
+        self.call("get_mirror_flip")?;
+        Ok(self.state.borrow().mirror_flip)
+    }
+
+    fn set_mirror_flip(&self, mirror: bool, flip: bool, _skip_frm_cnt: i32) -> XCamResult<()> {
+        self.call("set_mirror_flip")?;
+        self.state.borrow_mut().mirror_flip = (mirror, flip);
+        Ok(())
+    }
+}
+
+impl LDCH for MockContext {
+    fn enable_ldch(&self) -> XCamResult<()> {
+        self.call("enable_ldch")?;
+        self.state.borrow_mut().ldch_enabled = true;
+        Ok(())
+    }
+
+    fn disable_ldch(&self) -> XCamResult<()> {
+        self.call("disable_ldch")?;
+        self.state.borrow_mut().ldch_enabled = false;
+        Ok(())
+    }
+
+    fn is_ldch_enabled(&self) -> XCamResult<bool> {
+        self.call("is_ldch_enabled")?;
+        Ok(self.state.borrow().ldch_enabled)
+    }
+}
+
+impl FEC for MockContext {
+    fn enable_fec(&self) -> XCamResult<()> {
+        self.call("enable_fec")?;
+        self.state.borrow_mut().fec_enabled = true;
+        Ok(())
+    }
+
+    fn disable_fec(&self) -> XCamResult<()> {
+        self.call("disable_fec")?;
+        self.state.borrow_mut().fec_enabled = false;
+        Ok(())
+    }
+
+    fn is_fec_enabled(&self) -> XCamResult<bool> {
+        self.call("is_fec_enabled")?;
+        Ok(self.state.borrow().fec_enabled)
+    }
+}
+
+impl NoiseRemoval for MockContext {
+    fn get_nr_mode(&self) -> XCamResult<OpMode> {
+        self.call("get_nr_mode")?;
+        Ok(self.state.borrow().nr_mode)
+    }
+
+    fn set_nr_mode(&self, mode: OpMode) -> XCamResult<()> {
+        self.call("set_nr_mode")?;
+        self.state.borrow_mut().nr_mode = mode;
+        Ok(())
+    }
+
+    fn get_anr_strength(&self) -> XCamResult<Strength> {
+        self.call("get_anr_strength")?;
+        Ok(self.state.borrow().anr_strength)
+    }
+
+    fn set_anr_strength(&self, strength: Strength) -> XCamResult<()> {
+        self.call("set_anr_strength")?;
+        self.state.borrow_mut().anr_strength = strength;
+        Ok(())
+    }
+
+    fn get_ms_nr_strength(&self) -> XCamResult<(bool, Strength)> {
+        self.call("get_ms_nr_strength")?;
+        let state = self.state.borrow();
+        Ok((state.ms_nr_on, state.anr_strength))
+    }
+
+    fn set_ms_nr_strength(&self, on: bool, _strength: Strength) -> XCamResult<()> {
+        self.call("set_ms_nr_strength")?;
+        self.state.borrow_mut().ms_nr_on = on;
+        Ok(())
+    }
+
+    fn get_mt_nr_strength(&self) -> XCamResult<(bool, Strength)> {
+        self.call("get_mt_nr_strength")?;
+        let state = self.state.borrow();
+        Ok((state.mt_nr_on, state.anr_strength))
+    }
+
+    fn set_mt_nr_strength(&self, on: bool, _strength: Strength) -> XCamResult<()> {
+        self.call("set_mt_nr_strength")?;
+        self.state.borrow_mut().mt_nr_on = on;
+        Ok(())
+    }
+
+    fn get_effective_nr(&self) -> XCamResult<NrState> {
+        self.call("get_effective_nr")?;
+        Ok(self.state.borrow().nr_status)
+    }
+
+    fn enable_bayernr2d(&self) -> XCamResult<()> {
+        self.call("enable_bayernr2d")
+    }
+
+    fn disable_bayernr2d(&self) -> XCamResult<()> {
+        self.call("disable_bayernr2d")
+    }
+
+    fn enable_bayernr3d(&self) -> XCamResult<()> {
+        self.call("enable_bayernr3d")
+    }
+
+    fn disable_bayernr3d(&self) -> XCamResult<()> {
+        self.call("disable_bayernr3d")
+    }
+
+    fn enable_cnr(&self) -> XCamResult<()> {
+        self.call("enable_cnr")
+    }
+
+    fn disable_cnr(&self) -> XCamResult<()> {
+        self.call("disable_cnr")
+    }
+
+    fn enable_ynr(&self) -> XCamResult<()> {
+        self.call("enable_ynr")
+    }
+
+    fn disable_ynr(&self) -> XCamResult<()> {
+        self.call("disable_ynr")
+    }
+
+    fn get_nr_luma_chroma_balance(&self) -> XCamResult<(u8, u8)> {
+        self.call("get_nr_luma_chroma_balance")?;
+        let (luma, chroma) = self.state.borrow().nr_balance;
+        Ok((
+            nr_balance_from_percent(luma),
+            nr_balance_from_percent(chroma),
+        ))
+    }
+
+    fn set_nr_luma_chroma_balance(&self, luma: u8, chroma: u8) -> XCamResult<()> {
+        self.call("set_nr_luma_chroma_balance")?;
+        self.state.borrow_mut().nr_balance =
+            (nr_balance_to_percent(luma), nr_balance_to_percent(chroma));
+        Ok(())
+    }
+}
+
+impl Sharpen for MockContext {
+    fn get_sharpness(&self) -> XCamResult<Strength> {
+        self.call("get_sharpness")?;
+        Ok(self.state.borrow().sharpness)
+    }
+
+    fn set_sharpness(&self, level: Strength) -> XCamResult<()> {
+        self.call("set_sharpness")?;
+        self.state.borrow_mut().sharpness = level;
+        Ok(())
+    }
+
+    fn get_effective_sharpness(&self) -> XCamResult<u8> {
+        self.call("get_effective_sharpness")?;
+        Ok(self.state.borrow().sharp_status)
+    }
+
+    fn get_sharp_attrib(&self) -> XCamResult<SharpAttrib> {
+        self.call("get_sharp_attrib")?;
+        Ok(SharpAttrib::default())
+    }
+
+    fn set_sharp_attrib<T: Into<SharpAttrib>>(&self, _attr: T) -> XCamResult<()> {
+        self.call("set_sharp_attrib")
+    }
+}
+
+impl SystemControl for MockContext {
+    fn prepare(&self, width: u32, height: u32, mode: WorkingMode) -> XCamResult<PrepareResult> {
+        self.call("prepare")?;
+        let (width, height) = self.state.borrow().negotiated.unwrap_or((width, height));
+        Ok(PrepareResult {
+            width,
+            height,
+            mode,
+            format: MOCK_PIXEL_FORMAT,
+        })
+    }
+
+    fn start(&self) -> XCamResult<()> {
+        self.call("start")?;
+        self.state.borrow_mut().streaming = true;
+        Ok(())
+    }
+
+    fn stop(&self, _keep_ext_hw_st: bool) -> XCamResult<()> {
+        self.call("stop")?;
+        self.state.borrow_mut().streaming = false;
+        Ok(())
+    }
+
+    fn is_streaming(&self) -> bool {
+        self.state.borrow().streaming
+    }
+
+    fn enable_module<T: Into<ModuleId>>(&self, id: T) -> XCamResult<()> {
+        self.call("enable_module")?;
+        self.state.borrow_mut().set_module(id.into(), true);
+        Ok(())
+    }
+
+    fn disable_module<T: Into<ModuleId>>(&self, id: T) -> XCamResult<()> {
+        self.call("disable_module")?;
+        self.state.borrow_mut().set_module(id.into(), false);
+        Ok(())
+    }
+
+    fn is_module_enabled<T: Into<ModuleId>>(&self, id: T) -> bool {
+        let id = id.into();
+        let _ = self.call("is_module_enabled");
+        self.state
+            .borrow()
+            .modules
+            .iter()
+            .find(|(m, _)| *m == id)
+            .map_or(true, |(_, enabled)| *enabled)
+    }
+
+    fn register_lib(&self, _algo_lib_des: AlgoDescComm) -> XCamResult<()> {
+        self.call("register_lib")
+    }
+
+    fn unregister_lib(&self, _algo_type: i32, _lib_id: i32) -> XCamResult<()> {
+        self.call("unregister_lib")
+    }
+
+    fn enable_ax_lib(&self, algo_type: i32, _lib_id: i32) -> XCamResult<()> {
+        self.call("enable_ax_lib")?;
+        self.state.borrow_mut().ax_libs.push((algo_type, true));
+        Ok(())
+    }
+
+    fn disable_ax_lib(&self, algo_type: i32, _lib_id: i32) -> XCamResult<()> {
+        self.call("disable_ax_lib")?;
+        self.state.borrow_mut().ax_libs.push((algo_type, false));
+        Ok(())
+    }
+
+    fn is_ax_lib_enabled(&self, algo_type: i32, _lib_id: i32) -> bool {
+        self.call("is_ax_lib_enabled").is_ok()
+            && self
+                .state
+                .borrow()
+                .ax_libs
+                .iter()
+                .rev()
+                .find(|(t, _)| *t == algo_type)
+                .map(|(_, enabled)| *enabled)
+                .unwrap_or(true)
+    }
+
+    unsafe fn get_enabled_ax_lib_ctx(&self, _algo_type: i32) -> *const AlgoContext {
+        std::ptr::null()
+    }
+
+    fn get_cps_lt_info(&self) -> XCamResult<CpslInfo> {
+        self.call("get_cps_lt_info")?;
+        Ok(CpslInfo {
+            on: self.state.borrow().cpsl_on.into(),
+            ..Default::default()
+        })
+    }
+
+    fn query_cps_lt_cap(&self) -> XCamResult<CpslCap> {
+        self.call("query_cps_lt_cap")?;
+        Ok(CpslCap::default())
+    }
+
+    fn set_cps_lt_cfg<T: Into<CpslCfg>>(&self, cfg: T) -> XCamResult<()> {
+        self.call("set_cps_lt_cfg")?;
+        let cfg = cfg.into();
+        self.state.borrow_mut().cpsl_on = unsafe { cfg.u.m.on };
+        Ok(())
+    }
+
+    fn update_iq<T: Into<Vec<u8>>>(&self, iq_file: T) -> XCamResult<()> {
+        self.call("update_iq")?;
+        let path = String::from_utf8(iq_file.into()).unwrap();
+        self.state.borrow_mut().iq_loaded = std::fs::read(path).ok();
+        Ok(())
+    }
+
+    fn get_crop(&self) -> XCamResult<Rect> {
+        self.call("get_crop")?;
+        Ok(self.state.borrow().crop)
+    }
+
+    fn set_crop(&self, crop: Rect) -> XCamResult<()> {
+        self.call("set_crop")?;
+        self.state.borrow_mut().crop = crop;
+        Ok(())
+    }
+
+    fn get_3a_stats(&self, _timeout: Duration) -> XCamResult<IspStats> {
+        self.call("get_3a_stats")?;
+        self.state
+            .borrow_mut()
+            .stats
+            .take()
+            .ok_or(XCamError::Timeout)
+    }
+
+    fn get_luma_map(&self) -> XCamResult<LumaMap> {
+        let map = self.state.borrow().luma_map.clone();
+        match map {
+            Some(map) => self.call("get_luma_map").map(|_| map),
+            None => luma::luma_map_from_stats(&self.get_3a_stats(LUMA_STATS_TIMEOUT)?),
+        }
+    }
+
+    fn get_isp_error_flags(&self) -> XCamResult<IspErrorFlags> {
+        self.call("get_isp_error_flags")?;
+        Ok(self.state.borrow().isp_error_flags)
+    }
+
+    fn clear_isp_error_flags(&self) -> XCamResult<()> {
+        self.call("clear_isp_error_flags")?;
+        self.state.borrow_mut().isp_error_flags = IspErrorFlags::empty();
+        Ok(())
+    }
+
+    fn get_isp_clock_rates(&self) -> XCamResult<Vec<u32>> {
+        self.call("get_isp_clock_rates")?;
+        let state = self.state.borrow();
+        let (rates, _) = state.isp_clock.as_ref().ok_or(XCamError::Unsupported)?;
+        Ok(rates.clone())
+    }
+
+    fn get_isp_clock(&self) -> XCamResult<u32> {
+        self.call("get_isp_clock")?;
+        let state = self.state.borrow();
+        let (_, mhz) = state.isp_clock.as_ref().ok_or(XCamError::Unsupported)?;
+        Ok(*mhz)
+    }
+
+    fn set_isp_clock(&self, mhz: u32) -> XCamResult<()> {
+        self.call("set_isp_clock")?;
+        let mut state = self.state.borrow_mut();
+        let (rates, current) = state.isp_clock.as_mut().ok_or(XCamError::Unsupported)?;
+        check_isp_clock(mhz, rates)?;
+        *current = mhz;
+        Ok(())
+    }
+
+    fn get_frame_id(&self) -> Option<u32> {
+        let mut state = self.state.borrow_mut();
+        if state.frame_step > 0 {
+            let id = state
+                .frame_id
+                .map_or(0, |id| id.wrapping_add(state.frame_step));
+            state.frame_id = Some(id);
+            if let Some(on_frame) = state.on_frame {
+                on_frame(id, &mut state);
+            }
+        }
+        state.frame_id
+    }
+}
@@ -0,0 +1,174 @@
+//! 多摄像头
+//!
+//! 不使用 camgroup 的多摄像头设备中，各摄像头拥有独立的上下文。`ContextSet` 将同一设置
+//! 依次应用到每个上下文，并按摄像头编号收集各自的结果，而不是在第一个失败处中止。
+use super::ae::AutoExposure;
+use super::awb::AutoWhiteBalance;
+use super::shared::SharedContext;
+use super::types::{OpMode, WbGain, XCamResult};
+
+/// 一个描述某个摄像头设置失败后如何处理其余摄像头的枚举。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FanOutPolicy {
+    /// 继续设置其余摄像头。
+    #[default]
+    BestEffort,
+    /// 立即停止，其余摄像头不设置，也不出现在结果中。
+    FailFast,
+}
+
+/// 一个同时控制多个上下文的类型。
+///
+/// 成员通常为 `SharedContext`，也可以是 `Context` 等任何实现了控制接口的类型。
+/// 结果中的编号为成员在集合中的下标。
+pub struct ContextSet<C = SharedContext> {
+    members: Vec<C>,
+    policy: FanOutPolicy,
+}
+
+impl<C> ContextSet<C> {
+    /// 以 `FanOutPolicy::BestEffort` 创建集合。
+    pub fn new(members: Vec<C>) -> Self {
+        Self {
+            members,
+            policy: FanOutPolicy::default(),
+        }
+    }
+
+    /// 设置失败处理策略。
+    pub fn with_policy(mut self, policy: FanOutPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// 返回失败处理策略。
+    pub fn policy(&self) -> FanOutPolicy {
+        self.policy
+    }
+
+    /// 返回全部成员。
+    pub fn members(&self) -> &[C] {
+        &self.members
+    }
+
+    /// 依次对每个成员执行 `f`，返回 `(下标, 结果)` 列表。
+    ///
+    /// `FanOutPolicy::FailFast` 下遇到第一个失败即停止，该失败为列表的最后一项。
+    pub fn for_each_try<F>(&self, mut f: F) -> Vec<(usize, XCamResult<()>)>
+    where
+        F: FnMut(&C) -> XCamResult<()>,
+    {
+        let mut results = Vec::with_capacity(self.members.len());
+        for (i, ctx) in self.members.iter().enumerate() {
+            let r = f(ctx);
+            let failed = r.is_err();
+            results.push((i, r));
+            if failed && self.policy == FanOutPolicy::FailFast {
+                break;
+            }
+        }
+        results
+    }
+}
+
+impl<C> ContextSet<C>
+where
+    C: AutoWhiteBalance,
+{
+    /// 设置全部摄像头的白平衡工作模式。
+    pub fn set_wb_mode(&self, mode: OpMode) -> Vec<(usize, XCamResult<()>)> {
+        self.for_each_try(|ctx| ctx.set_wb_mode(mode))
+    }
+
+    /// 锁定全部摄像头的白平衡参数。
+    pub fn lock_awb(&self) -> Vec<(usize, XCamResult<()>)> {
+        self.for_each_try(|ctx| ctx.lock_awb())
+    }
+
+    /// 解锁全部摄像头的白平衡参数。
+    pub fn unlock_awb(&self) -> Vec<(usize, XCamResult<()>)> {
+        self.for_each_try(|ctx| ctx.unlock_awb())
+    }
+
+    /// 设置全部摄像头的白平衡增益系数。
+    pub fn set_mwb_gain(&self, gain: WbGain) -> Vec<(usize, XCamResult<()>)> {
+        self.for_each_try(|ctx| ctx.set_mwb_gain(gain))
+    }
+
+    /// 设置全部摄像头的白平衡色温。
+    pub fn set_mwb_ct(&self, ct: u32) -> Vec<(usize, XCamResult<()>)> {
+        self.for_each_try(|ctx| ctx.set_mwb_ct(ct))
+    }
+}
+
+impl<C> ContextSet<C>
+where
+    C: AutoExposure,
+{
+    /// 设置全部摄像头的曝光工作模式。
+    pub fn set_exp_mode(&self, mode: OpMode) -> Vec<(usize, XCamResult<()>)> {
+        self.for_each_try(|ctx| ctx.set_exp_mode(mode))
+    }
+
+    /// 设置全部摄像头的增益范围。
+    pub fn set_exp_gain_range(&self, min: f32, max: f32) -> Vec<(usize, XCamResult<()>)> {
+        self.for_each_try(|ctx| ctx.set_exp_gain_range(min, max))
+    }
+
+    /// 设置全部摄像头的曝光时间范围，单位：秒。
+    pub fn set_exp_time_range(&self, min: f32, max: f32) -> Vec<(usize, XCamResult<()>)> {
+        self.for_each_try(|ctx| ctx.set_exp_time_range(min, max))
+    }
+
+    /// 设置全部摄像头的手动曝光增益与时间，时间单位：秒。
+    pub fn set_manual_exp(&self, gain: f32, time: f32) -> Vec<(usize, XCamResult<()>)> {
+        self.for_each_try(|ctx| ctx.set_manual_exp(gain, time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+
+    fn mock_set(n: usize) -> ContextSet<MockContext> {
+        ContextSet::new((0..n).map(|_| MockContext::new()).collect())
+    }
+
+    #[test]
+    fn test_context_set_best_effort() {
+        let set = mock_set(3);
+        set.members()[1].fail("set_wb_mode");
+        let results = set.set_wb_mode(OpMode::Manual);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], (0, Ok(())));
+        assert!(results[1].1.is_err());
+        assert_eq!(results[2], (2, Ok(())));
+        assert_eq!(set.members()[2].state.borrow().wb_mode, OpMode::Manual);
+
+        let results = set.set_manual_exp(2.0, 0.01);
+        assert!(results.iter().all(|(_, r)| r.is_ok()));
+        for ctx in set.members() {
+            assert_eq!(ctx.state.borrow().manual_exp, (2.0, 0.01));
+        }
+    }
+
+    #[test]
+    fn test_context_set_fail_fast() {
+        let set = mock_set(3).with_policy(FanOutPolicy::FailFast);
+        set.members()[1].fail("lock_awb");
+        let results = set.lock_awb();
+        assert_eq!(results.len(), 2);
+        assert!(results[1].1.is_err());
+        assert!(set.members()[0].state.borrow().awb_locked);
+        assert!(set.members()[2].calls().is_empty());
+
+        let mut seen = Vec::new();
+        let results = set.for_each_try(|ctx| {
+            seen.push(ctx.calls().len());
+            Ok(())
+        });
+        assert_eq!(results.len(), 3);
+        assert_eq!(seen, [1, 1, 0]);
+    }
+}
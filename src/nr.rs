@@ -3,23 +3,67 @@
 //! AWB 模块的功能是通过改变拍摄设备的色彩通道的增益，
 //！对色温环境所造成的颜色偏差和拍摄设备本身所固有的色彩通道增益的偏差进行统一补偿，
 //！从而让获得的图像能正确反映物体的真实色彩。
+use super::capability::Capabilities;
 use super::context::Context;
 use super::error::XCamError;
 use super::ffi;
-use super::types::{OpMode, XCamResult};
+use super::shared::SharedContext;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::types::NrInfo;
+use super::types::{OpMode, Strength, XCamResult};
+use super::uapi::{uapi_call, uapi_get};
+
+/// 一个描述自动降噪当前实际生效参数的类型。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NrState {
+    /// 算法插值所用的 ISO。
+    pub iso: u32,
+    /// 空域降噪是否使能及其强度。
+    pub spatial: (bool, Strength),
+    /// 时域降噪是否使能及其强度。
+    pub temporal: (bool, Strength),
+}
+
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+impl From<&NrInfo> for NrState {
+    fn from(info: &NrInfo) -> Self {
+        Self {
+            iso: info.iso.max(0) as u32,
+            spatial: (
+                info.bayernr2DEn != 0,
+                Strength::from_ratio(info.fStrength2D),
+            ),
+            temporal: (
+                info.bayernr3DEn != 0,
+                Strength::from_ratio(info.fStrength3D),
+            ),
+        }
+    }
+}
 
 pub trait NoiseRemoval {
     fn get_nr_mode(&self) -> XCamResult<OpMode>;
     fn set_nr_mode(&self, mode: OpMode) -> XCamResult<()>;
 
-    fn get_anr_strength(&self) -> XCamResult<u32>;
-    fn set_anr_strength(&self, strength: u32) -> XCamResult<()>;
+    fn get_anr_strength(&self) -> XCamResult<Strength>;
+    fn set_anr_strength(&self, strength: Strength) -> XCamResult<()>;
+
+    fn get_ms_nr_strength(&self) -> XCamResult<(bool, Strength)>;
+    fn set_ms_nr_strength(&self, on: bool, strength: Strength) -> XCamResult<()>;
 
-    fn get_ms_nr_strength(&self) -> XCamResult<(bool, u32)>;
-    fn set_ms_nr_strength(&self, on: bool, strength: u32) -> XCamResult<()>;
+    fn get_mt_nr_strength(&self) -> XCamResult<(bool, Strength)>;
+    fn set_mt_nr_strength(&self, on: bool, strength: Strength) -> XCamResult<()>;
 
-    fn get_mt_nr_strength(&self) -> XCamResult<(bool, u32)>;
-    fn set_mt_nr_strength(&self, on: bool, strength: u32) -> XCamResult<()>;
+    /// 获取自动降噪当前实际生效的参数，用于记录与调优。
+    ///
+    /// 自动模式下算法按当前 ISO 在标定表中插值得到各级降噪参数，`set_ms_nr_strength` 等设置的
+    /// 是作用于插值结果的缩放，`get_ms_nr_strength` 读回的也只是该设置值；本接口读取算法状态，
+    /// 返回插值并缩放后的最终值，随 ISO 变化。
+    ///
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn get_effective_nr(&self) -> XCamResult<NrState> {
+        Err(XCamError::Unsupported)
+    }
 
     fn enable_bayernr2d(&self) -> XCamResult<()>;
     fn disable_bayernr2d(&self) -> XCamResult<()>;
@@ -32,338 +76,590 @@ pub trait NoiseRemoval {
 
     fn enable_ynr(&self) -> XCamResult<()>;
     fn disable_ynr(&self) -> XCamResult<()>;
+
+    /// 获取亮度降噪（YNR）与色度降噪（CNR）各自的强度 `(luma, chroma)`，范围：[0,100]。
+    ///
+    /// 默认返回 `XCamError::Unsupported`。
+    fn get_nr_luma_chroma_balance(&self) -> XCamResult<(u8, u8)> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 分别设置亮度降噪与色度降噪的强度，超出 [0,100] 时截断。
+    ///
+    /// 50 为标定值。亮度降噪越强画面越平滑，但毛发、草地、织物等细小纹理随之被抹平；
+    /// 色度降噪只作用于色彩，可在几乎不损失纹理的情况下去除暗部的彩色噪点，过强时小块颜色
+    /// 被晕开、边缘渗色。低照度下常降低亮度降噪、提高色度降噪，以平滑度换取纹理。
+    ///
+    /// 默认返回 `XCamError::Unsupported`。
+    fn set_nr_luma_chroma_balance(&self, luma: u8, chroma: u8) -> XCamResult<()> {
+        let _ = (luma, chroma);
+        Err(XCamError::Unsupported)
+    }
+}
+
+/// 将亮度、色度降噪强度转换为 SDK 的强度比例（0.5 为标定值），超出 [0,100] 时截断。
+pub(crate) fn nr_balance_to_percent(val: u8) -> f32 {
+    f32::from(val.min(Strength::MAX.get())) / 100.0
+}
+
+/// 将 SDK 的强度比例转换为亮度、色度降噪强度，超出范围时截断。
+pub(crate) fn nr_balance_from_percent(percent: f32) -> u8 {
+    (percent.clamp(0.0, 1.0) * 100.0).round() as u8
 }
 
 impl NoiseRemoval for Context {
     fn get_nr_mode(&self) -> XCamResult<OpMode> {
         let mut mode = ffi::opMode_t::OP_INVAL;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getNRMode(
-                self.internal.as_ptr(),
-                &mut mode,
-            ))
-            .ok()
-            .map(|_| mode.into())
-        }
+        uapi_call!(ffi::rk_aiq_uapi_getNRMode(self, &mut mode)).map(|_| mode.into())
     }
 
     fn set_nr_mode(&self, mode: OpMode) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setNRMode(
-                self.internal.as_ptr(),
-                mode.into(),
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi_setNRMode(self, mode.into()))
     }
 
-    fn get_anr_strength(&self) -> XCamResult<u32> {
+    fn get_anr_strength(&self) -> XCamResult<Strength> {
         let mut strength: u32 = 0;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getANRStrth(
-                self.internal.as_ptr(),
-                &mut strength,
-            ))
-            .ok()
-            .map(|_| strength)
-        }
+        uapi_call!(ffi::rk_aiq_uapi_getANRStrth(self, &mut strength))
+            .map(|_| Strength::saturating(strength))
     }
 
     #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
-    fn set_anr_strength(&self, strength: u32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setANRStrth(
-                self.internal.as_ptr(),
-                strength,
-            ))
-            .ok()
-        }
+    fn set_anr_strength(&self, strength: Strength) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setANRStrth(self, strength.into()))
     }
 
     #[cfg(any(feature = "v4_0", feature = "v5_0"))]
-    fn set_anr_strength(&self, strength: u32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setANRStrth(
-                self.internal.as_ptr(),
-                strength,
-            ))
-            .ok()
-        }
+    fn set_anr_strength(&self, strength: Strength) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi2_setANRStrth(self, strength.into()))
     }
 
-    fn get_ms_nr_strength(&self) -> XCamResult<(bool, u32)> {
+    fn get_ms_nr_strength(&self) -> XCamResult<(bool, Strength)> {
         let mut on: bool = false;
         let mut strength: u32 = 0;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getMSpaNRStrth(
-                self.internal.as_ptr(),
-                &mut on,
-                &mut strength,
-            ))
-            .ok()
-            .map(|_| (on, strength))
-        }
+        uapi_call!(ffi::rk_aiq_uapi_getMSpaNRStrth(
+            self,
+            &mut on,
+            &mut strength
+        ))
+        .map(|_| (on, Strength::saturating(strength)))
     }
 
     #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
-    fn set_ms_nr_strength(&self, on: bool, strength: u32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setMSpaNRStrth(
-                self.internal.as_ptr(),
-                on,
-                strength,
-            ))
-            .ok()
-        }
+    fn set_ms_nr_strength(&self, on: bool, strength: Strength) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setMSpaNRStrth(self, on, strength.into()))
     }
 
     #[cfg(any(feature = "v4_0", feature = "v5_0"))]
-    fn set_ms_nr_strength(&self, on: bool, strength: u32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setMSpaNRStrth(
-                self.internal.as_ptr(),
-                on,
-                strength,
-            ))
-            .ok()
-        }
+    fn set_ms_nr_strength(&self, on: bool, strength: Strength) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi2_setMSpaNRStrth(self, on, strength.into()))
     }
 
-    fn get_mt_nr_strength(&self) -> XCamResult<(bool, u32)> {
+    fn get_mt_nr_strength(&self) -> XCamResult<(bool, Strength)> {
         let mut on: bool = false;
         let mut strength: u32 = 0;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_getMTNRStrth(
-                self.internal.as_ptr(),
-                &mut on,
-                &mut strength,
-            ))
-            .ok()
-            .map(|_| (on, strength))
-        }
+        uapi_call!(ffi::rk_aiq_uapi_getMTNRStrth(self, &mut on, &mut strength))
+            .map(|_| (on, Strength::saturating(strength)))
     }
 
     #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
-    fn set_mt_nr_strength(&self, on: bool, strength: u32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_setMTNRStrth(
-                self.internal.as_ptr(),
-                on,
-                strength,
-            ))
-            .ok()
-        }
+    fn set_mt_nr_strength(&self, on: bool, strength: Strength) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi_setMTNRStrth(self, on, strength.into()))
     }
 
     #[cfg(any(feature = "v4_0", feature = "v5_0"))]
-    fn set_mt_nr_strength(&self, on: bool, strength: u32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setMTNRStrth(
-                self.internal.as_ptr(),
-                on,
-                strength,
-            ))
-            .ok()
-        }
+    fn set_mt_nr_strength(&self, on: bool, strength: Strength) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi2_setMTNRStrth(self, on, strength.into()))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_effective_nr(&self) -> XCamResult<NrState> {
+        uapi_get!(ffi::rk_aiq_user_api2_abayernrV2_GetInfo(self) -> NrInfo)
+            .map(|info| NrState::from(&info))
     }
 
     fn enable_bayernr2d(&self) -> XCamResult<()> {
-        unsafe {
-            let mut attr: ffi::rk_aiq_bayernr_attrib_v2_t = Default::default();
-            XCamError::from(ffi::rk_aiq_user_api2_abayernrV2_GetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()?;
-            match attr.eMode {
-                ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_AUTO => {
-                    attr.stAuto.bayernr2DEn = 1;
-                }
-                ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_MANUAL => {
-                    attr.stManual.bayernr2DEn = 1;
-                }
-                _ => {}
+        let mut attr: ffi::rk_aiq_bayernr_attrib_v2_t = Default::default();
+        uapi_call!(ffi::rk_aiq_user_api2_abayernrV2_GetAttrib(self, &mut attr))?;
+        match attr.eMode {
+            ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_AUTO => {
+                attr.stAuto.bayernr2DEn = 1;
+            }
+            ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_MANUAL => {
+                attr.stManual.bayernr2DEn = 1;
             }
-            XCamError::from(ffi::rk_aiq_user_api2_abayernrV2_SetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()
+            _ => {}
         }
+        uapi_call!(ffi::rk_aiq_user_api2_abayernrV2_SetAttrib(self, &mut attr))
     }
 
     fn disable_bayernr2d(&self) -> XCamResult<()> {
-        unsafe {
-            let mut attr: ffi::rk_aiq_bayernr_attrib_v2_t = Default::default();
-            XCamError::from(ffi::rk_aiq_user_api2_abayernrV2_GetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()?;
-            match attr.eMode {
-                ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_AUTO => {
-                    attr.stAuto.bayernr2DEn = 0;
-                }
-                ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_MANUAL => {
-                    attr.stManual.bayernr2DEn = 0;
-                }
-                _ => {}
+        let mut attr: ffi::rk_aiq_bayernr_attrib_v2_t = Default::default();
+        uapi_call!(ffi::rk_aiq_user_api2_abayernrV2_GetAttrib(self, &mut attr))?;
+        match attr.eMode {
+            ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_AUTO => {
+                attr.stAuto.bayernr2DEn = 0;
             }
-            XCamError::from(ffi::rk_aiq_user_api2_abayernrV2_SetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()
+            ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_MANUAL => {
+                attr.stManual.bayernr2DEn = 0;
+            }
+            _ => {}
         }
+        uapi_call!(ffi::rk_aiq_user_api2_abayernrV2_SetAttrib(self, &mut attr))
     }
 
     fn enable_bayernr3d(&self) -> XCamResult<()> {
-        unsafe {
-            let mut attr: ffi::rk_aiq_bayernr_attrib_v2_t = Default::default();
-            XCamError::from(ffi::rk_aiq_user_api2_abayernrV2_GetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()?;
-            match attr.eMode {
-                ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_AUTO => {
-                    attr.stAuto.bayernr3DEn = 1;
-                }
-                ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_MANUAL => {
-                    attr.stManual.bayernr3DEn = 1;
-                }
-                _ => {}
+        self.require(Capabilities::NR3D)?;
+        let mut attr: ffi::rk_aiq_bayernr_attrib_v2_t = Default::default();
+        uapi_call!(ffi::rk_aiq_user_api2_abayernrV2_GetAttrib(self, &mut attr))?;
+        match attr.eMode {
+            ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_AUTO => {
+                attr.stAuto.bayernr3DEn = 1;
+            }
+            ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_MANUAL => {
+                attr.stManual.bayernr3DEn = 1;
             }
-            XCamError::from(ffi::rk_aiq_user_api2_abayernrV2_SetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()
+            _ => {}
         }
+        uapi_call!(ffi::rk_aiq_user_api2_abayernrV2_SetAttrib(self, &mut attr))
     }
 
     fn disable_bayernr3d(&self) -> XCamResult<()> {
-        unsafe {
-            let mut attr: ffi::rk_aiq_bayernr_attrib_v2_t = Default::default();
-            XCamError::from(ffi::rk_aiq_user_api2_abayernrV2_GetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()?;
-            match attr.eMode {
-                ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_AUTO => {
-                    attr.stAuto.bayernr3DEn = 0;
-                }
-                ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_MANUAL => {
-                    attr.stManual.bayernr3DEn = 0;
-                }
-                _ => {}
+        self.require(Capabilities::NR3D)?;
+        let mut attr: ffi::rk_aiq_bayernr_attrib_v2_t = Default::default();
+        uapi_call!(ffi::rk_aiq_user_api2_abayernrV2_GetAttrib(self, &mut attr))?;
+        match attr.eMode {
+            ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_AUTO => {
+                attr.stAuto.bayernr3DEn = 0;
             }
-            XCamError::from(ffi::rk_aiq_user_api2_abayernrV2_SetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()
+            ffi::Abayernr_OPMode_t::ABAYERNR_OP_MODE_MANUAL => {
+                attr.stManual.bayernr3DEn = 0;
+            }
+            _ => {}
         }
+        uapi_call!(ffi::rk_aiq_user_api2_abayernrV2_SetAttrib(self, &mut attr))
     }
 
     fn enable_cnr(&self) -> XCamResult<()> {
-        unsafe {
-            let mut attr: ffi::rk_aiq_cnr_attrib_v1_t = Default::default();
-            XCamError::from(ffi::rk_aiq_user_api2_acnrV1_GetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()?;
-            match attr.eMode {
-                ffi::Acnr_OPMode_t::ACNR_OP_MODE_AUTO => {
-                    attr.stAuto.cnrEn = 1;
-                }
-                ffi::Acnr_OPMode_t::ACNR_OP_MODE_MANUAL => {
-                    attr.stManual.cnrEn = 1;
-                }
-                _ => {}
+        let mut attr: ffi::rk_aiq_cnr_attrib_v1_t = Default::default();
+        uapi_call!(ffi::rk_aiq_user_api2_acnrV1_GetAttrib(self, &mut attr))?;
+        match attr.eMode {
+            ffi::Acnr_OPMode_t::ACNR_OP_MODE_AUTO => {
+                attr.stAuto.cnrEn = 1;
+            }
+            ffi::Acnr_OPMode_t::ACNR_OP_MODE_MANUAL => {
+                attr.stManual.cnrEn = 1;
             }
-            XCamError::from(ffi::rk_aiq_user_api2_acnrV1_SetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()
+            _ => {}
         }
+        uapi_call!(ffi::rk_aiq_user_api2_acnrV1_SetAttrib(self, &mut attr))
     }
 
     fn disable_cnr(&self) -> XCamResult<()> {
-        unsafe {
-            let mut attr: ffi::rk_aiq_cnr_attrib_v1_t = Default::default();
-            XCamError::from(ffi::rk_aiq_user_api2_acnrV1_GetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()?;
-            match attr.eMode {
-                ffi::Acnr_OPMode_t::ACNR_OP_MODE_AUTO => {
-                    attr.stAuto.cnrEn = 0;
-                }
-                ffi::Acnr_OPMode_t::ACNR_OP_MODE_MANUAL => {
-                    attr.stManual.cnrEn = 0;
-                }
-                _ => {}
+        let mut attr: ffi::rk_aiq_cnr_attrib_v1_t = Default::default();
+        uapi_call!(ffi::rk_aiq_user_api2_acnrV1_GetAttrib(self, &mut attr))?;
+        match attr.eMode {
+            ffi::Acnr_OPMode_t::ACNR_OP_MODE_AUTO => {
+                attr.stAuto.cnrEn = 0;
             }
-            XCamError::from(ffi::rk_aiq_user_api2_acnrV1_SetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()
+            ffi::Acnr_OPMode_t::ACNR_OP_MODE_MANUAL => {
+                attr.stManual.cnrEn = 0;
+            }
+            _ => {}
         }
+        uapi_call!(ffi::rk_aiq_user_api2_acnrV1_SetAttrib(self, &mut attr))
     }
 
     fn enable_ynr(&self) -> XCamResult<()> {
-        unsafe {
-            let mut attr: ffi::rk_aiq_ynr_attrib_v2_t = Default::default();
-            XCamError::from(ffi::rk_aiq_user_api2_aynrV2_GetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()?;
-            match attr.eMode {
-                ffi::Aynr_OPMode_t::AYNR_OP_MODE_AUTO => {
-                    attr.stAuto.ynrEn = 1;
-                }
-                ffi::Aynr_OPMode_t::AYNR_OP_MODE_MANUAL => {
-                    attr.stManual.ynrEn = 1;
-                }
-                _ => {}
+        let mut attr: ffi::rk_aiq_ynr_attrib_v2_t = Default::default();
+        uapi_call!(ffi::rk_aiq_user_api2_aynrV2_GetAttrib(self, &mut attr))?;
+        match attr.eMode {
+            ffi::Aynr_OPMode_t::AYNR_OP_MODE_AUTO => {
+                attr.stAuto.ynrEn = 1;
+            }
+            ffi::Aynr_OPMode_t::AYNR_OP_MODE_MANUAL => {
+                attr.stManual.ynrEn = 1;
             }
-            XCamError::from(ffi::rk_aiq_user_api2_aynrV2_SetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()
+            _ => {}
         }
+        uapi_call!(ffi::rk_aiq_user_api2_aynrV2_SetAttrib(self, &mut attr))
     }
 
     fn disable_ynr(&self) -> XCamResult<()> {
-        unsafe {
-            let mut attr: ffi::rk_aiq_ynr_attrib_v2_t = Default::default();
-            XCamError::from(ffi::rk_aiq_user_api2_aynrV2_GetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()?;
-            match attr.eMode {
-                ffi::Aynr_OPMode_t::AYNR_OP_MODE_AUTO => {
-                    attr.stAuto.ynrEn = 0;
-                }
-                ffi::Aynr_OPMode_t::AYNR_OP_MODE_MANUAL => {
-                    attr.stManual.ynrEn = 0;
-                }
-                _ => {}
+        let mut attr: ffi::rk_aiq_ynr_attrib_v2_t = Default::default();
+        uapi_call!(ffi::rk_aiq_user_api2_aynrV2_GetAttrib(self, &mut attr))?;
+        match attr.eMode {
+            ffi::Aynr_OPMode_t::AYNR_OP_MODE_AUTO => {
+                attr.stAuto.ynrEn = 0;
+            }
+            ffi::Aynr_OPMode_t::AYNR_OP_MODE_MANUAL => {
+                attr.stManual.ynrEn = 0;
             }
-            XCamError::from(ffi::rk_aiq_user_api2_aynrV2_SetAttrib(
-                self.internal.as_ptr(),
-                &mut attr,
-            ))
-            .ok()
+            _ => {}
+        }
+        uapi_call!(ffi::rk_aiq_user_api2_aynrV2_SetAttrib(self, &mut attr))
+    }
+
+    fn get_nr_luma_chroma_balance(&self) -> XCamResult<(u8, u8)> {
+        let luma = uapi_get!(ffi::rk_aiq_user_api2_aynrV2_GetStrength(self) -> f32)?;
+        let chroma = uapi_get!(ffi::rk_aiq_user_api2_acnrV1_GetStrength(self) -> f32)?;
+        Ok((
+            nr_balance_from_percent(luma),
+            nr_balance_from_percent(chroma),
+        ))
+    }
+
+    fn set_nr_luma_chroma_balance(&self, luma: u8, chroma: u8) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_user_api2_aynrV2_SetStrength(
+            self,
+            nr_balance_to_percent(luma)
+        ))?;
+        uapi_call!(ffi::rk_aiq_user_api2_acnrV1_SetStrength(
+            self,
+            nr_balance_to_percent(chroma)
+        ))
+    }
+}
+
+impl NoiseRemoval for SharedContext {
+    fn get_nr_mode(&self) -> XCamResult<OpMode> {
+        self.inner.get_nr_mode()
+    }
+
+    fn set_nr_mode(&self, mode: OpMode) -> XCamResult<()> {
+        self.inner.set_nr_mode(mode)
+    }
+
+    fn get_anr_strength(&self) -> XCamResult<Strength> {
+        self.inner.get_anr_strength()
+    }
+
+    fn set_anr_strength(&self, strength: Strength) -> XCamResult<()> {
+        self.inner.set_anr_strength(strength)
+    }
+
+    fn get_ms_nr_strength(&self) -> XCamResult<(bool, Strength)> {
+        self.inner.get_ms_nr_strength()
+    }
+
+    fn set_ms_nr_strength(&self, on: bool, strength: Strength) -> XCamResult<()> {
+        self.inner.set_ms_nr_strength(on, strength)
+    }
+
+    fn get_mt_nr_strength(&self) -> XCamResult<(bool, Strength)> {
+        self.inner.get_mt_nr_strength()
+    }
+
+    fn set_mt_nr_strength(&self, on: bool, strength: Strength) -> XCamResult<()> {
+        self.inner.set_mt_nr_strength(on, strength)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_effective_nr(&self) -> XCamResult<NrState> {
+        self.inner.get_effective_nr()
+    }
+
+    fn enable_bayernr2d(&self) -> XCamResult<()> {
+        self.inner.enable_bayernr2d()
+    }
+
+    fn disable_bayernr2d(&self) -> XCamResult<()> {
+        self.inner.disable_bayernr2d()
+    }
+
+    fn enable_bayernr3d(&self) -> XCamResult<()> {
+        self.inner.enable_bayernr3d()
+    }
+
+    fn disable_bayernr3d(&self) -> XCamResult<()> {
+        self.inner.disable_bayernr3d()
+    }
+
+    fn enable_cnr(&self) -> XCamResult<()> {
+        self.inner.enable_cnr()
+    }
+
+    fn disable_cnr(&self) -> XCamResult<()> {
+        self.inner.disable_cnr()
+    }
+
+    fn enable_ynr(&self) -> XCamResult<()> {
+        self.inner.enable_ynr()
+    }
+
+    fn disable_ynr(&self) -> XCamResult<()> {
+        self.inner.disable_ynr()
+    }
+
+    fn get_nr_luma_chroma_balance(&self) -> XCamResult<(u8, u8)> {
+        self.inner.get_nr_luma_chroma_balance()
+    }
+
+    fn set_nr_luma_chroma_balance(&self, luma: u8, chroma: u8) -> XCamResult<()> {
+        self.inner.set_nr_luma_chroma_balance(luma, chroma)
+    }
+}
+
+/// 一个描述一组降噪设置的类型，以链式调用构造，由 `Context::apply_nr_config` 一次应用。
+///
+/// 未设置的项保持 SDK 当前值。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct NrConfig {
+    /// 工作模式。
+    pub mode: Option<OpMode>,
+    /// 时域降噪的参考帧数，0 关闭时域降噪。
+    pub tnr_frames: Option<u8>,
+    /// RAW 域降噪强度，即整体降噪强度。
+    pub raw: Option<Strength>,
+    /// 空域降噪强度。
+    pub spatial: Option<Strength>,
+    /// 时域降噪强度。
+    pub temporal: Option<Strength>,
+}
+
+impl NrConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mode(mut self, mode: OpMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    pub fn tnr_frames(mut self, frames: u8) -> Self {
+        self.tnr_frames = Some(frames);
+        self
+    }
+
+    pub fn raw(mut self, strength: Strength) -> Self {
+        self.raw = Some(strength);
+        self
+    }
+
+    pub fn spatial(mut self, strength: Strength) -> Self {
+        self.spatial = Some(strength);
+        self
+    }
+
+    pub fn temporal(mut self, strength: Strength) -> Self {
+        self.temporal = Some(strength);
+        self
+    }
+}
+
+/// 一个描述 `NrConfig` 中各项的枚举，用于报告应用失败的项。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NrField {
+    Mode,
+    TnrFrames,
+    Raw,
+    Spatial,
+    Temporal,
+}
+
+/// 按 `Context::apply_nr_config` 说明的顺序应用 `cfg`，返回失败的 `(项, 错误)` 列表。
+pub(crate) fn apply_nr_config<C>(ctx: &C, cfg: &NrConfig) -> Vec<(NrField, XCamError)>
+where
+    C: NoiseRemoval + ?Sized,
+{
+    let mut failures = Vec::new();
+    let mut report = |field, res: XCamResult<()>| {
+        if let Err(e) = res {
+            failures.push((field, e));
         }
+    };
+    if let Some(mode) = cfg.mode {
+        report(NrField::Mode, ctx.set_nr_mode(mode));
+    }
+    if let Some(frames) = cfg.tnr_frames {
+        report(
+            NrField::TnrFrames,
+            match frames {
+                0 => ctx.disable_bayernr3d(),
+                1 => ctx.enable_bayernr3d(),
+                _ => Err(XCamError::Unsupported),
+            },
+        );
+    }
+    if let Some(v) = cfg.raw {
+        report(NrField::Raw, ctx.set_anr_strength(v));
+    }
+    if let Some(v) = cfg.spatial {
+        // 只设置强度，空域降噪保持当前的开关状态。
+        let res = ctx
+            .get_ms_nr_strength()
+            .and_then(|(on, _)| ctx.set_ms_nr_strength(on, v));
+        report(NrField::Spatial, res);
+    }
+    if let Some(v) = cfg.temporal {
+        // 开关由 `tnr_frames` 决定，未设置时保持当前状态。
+        let on = match cfg.tnr_frames {
+            Some(0) => Ok(false),
+            Some(1) => Ok(true),
+            _ => ctx.get_mt_nr_strength().map(|(on, _)| on),
+        };
+        let res = on.and_then(|on| ctx.set_mt_nr_strength(on, v));
+        report(NrField::Temporal, res);
+    }
+    failures
+}
+
+impl Context {
+    /// 一次应用一组降噪设置，尽力设置每一项，返回失败的 `(项, 错误)` 列表，全部成功时为空。
+    ///
+    /// 各项按以下顺序应用，使较重的重新配置先于依赖它的设置，且每项只设置一次：
+    ///
+    /// 1. `mode`：切换工作模式会按新模式重新初始化降噪参数，须最先设置；
+    /// 2. `tnr_frames`：使能或关闭时域降噪模块，模块开关会重新配置 ISP 的参考帧缓冲；
+    /// 3. `raw`：整体强度缩放空域与时域两级降噪，先于两者单独的强度设置；
+    /// 4. `spatial`：只设置强度，空域降噪保持当前的开关状态；
+    /// 5. `temporal`：开关与 `tnr_frames` 一致，`tnr_frames` 未设置或不受支持时保持当前状态。
+    ///
+    /// SDK 不提供设置参考帧数的接口，参考帧数由 IQ 文件与驱动决定：`tnr_frames` 为 0 关闭、
+    /// 为 1 打开时域降噪，更大的值记为 `XCamError::Unsupported` 失败。
+    pub fn apply_nr_config(&self, cfg: &NrConfig) -> Vec<(NrField, XCamError)> {
+        apply_nr_config(self, cfg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+
+    fn strength(val: u8) -> Strength {
+        Strength::new(val).unwrap()
+    }
+
+    #[test]
+    fn test_effective_nr_differs_from_setpoint() {
+        let ctx = MockContext::new();
+        ctx.set_anr_strength(strength(50)).unwrap();
+        ctx.state.borrow_mut().nr_status = NrState {
+            iso: 800,
+            spatial: (true, strength(72)),
+            temporal: (false, strength(35)),
+        };
+        assert_eq!(ctx.get_anr_strength(), Ok(strength(50)));
+        let state = ctx.get_effective_nr().unwrap();
+        assert_eq!(state.iso, 800);
+        assert_eq!(state.spatial, (true, strength(72)));
+        assert_eq!(state.temporal, (false, strength(35)));
+    }
+
+    #[test]
+    fn test_apply_nr_config_order() {
+        let ctx = MockContext::new();
+        let cfg = NrConfig::new()
+            .temporal(strength(40))
+            .spatial(strength(30))
+            .raw(strength(60))
+            .tnr_frames(1)
+            .mode(OpMode::Manual);
+        assert!(apply_nr_config(&ctx, &cfg).is_empty());
+        assert_eq!(
+            ctx.calls(),
+            [
+                "set_nr_mode",
+                "enable_bayernr3d",
+                "set_anr_strength",
+                "get_ms_nr_strength",
+                "set_ms_nr_strength",
+                "set_mt_nr_strength",
+            ]
+        );
+        assert_eq!(ctx.state.borrow().nr_mode, OpMode::Manual);
+        assert_eq!(ctx.state.borrow().anr_strength, strength(60));
+
+        // 未设置的项不调用，失败不影响其余各项。
+        let ctx = MockContext::new();
+        ctx.fail("set_ms_nr_strength");
+        let cfg = NrConfig::new()
+            .tnr_frames(3)
+            .spatial(strength(30))
+            .temporal(strength(40));
+        assert_eq!(
+            apply_nr_config(&ctx, &cfg),
+            [
+                (NrField::TnrFrames, XCamError::Unsupported),
+                (
+                    NrField::Spatial,
+                    XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED)
+                ),
+            ]
+        );
+        assert_eq!(
+            ctx.calls(),
+            [
+                "get_ms_nr_strength",
+                "set_ms_nr_strength",
+                "get_mt_nr_strength",
+                "set_mt_nr_strength",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_nr_config_keeps_enables() {
+        let ctx = MockContext::new();
+        {
+            let mut state = ctx.state.borrow_mut();
+            state.ms_nr_on = false;
+            state.mt_nr_on = false;
+        }
+        let cfg = NrConfig::new().spatial(strength(30)).temporal(strength(40));
+        assert!(apply_nr_config(&ctx, &cfg).is_empty());
+        assert_eq!(ctx.get_ms_nr_strength().map(|(on, _)| on), Ok(false));
+        assert_eq!(ctx.get_mt_nr_strength().map(|(on, _)| on), Ok(false));
+
+        // `tnr_frames` 决定时域降噪的开关。
+        assert!(apply_nr_config(&ctx, &cfg.tnr_frames(1)).is_empty());
+        assert_eq!(ctx.get_ms_nr_strength().map(|(on, _)| on), Ok(false));
+        assert_eq!(ctx.get_mt_nr_strength().map(|(on, _)| on), Ok(true));
+        let cfg = NrConfig::new().tnr_frames(0).temporal(strength(40));
+        assert!(apply_nr_config(&ctx, &cfg).is_empty());
+        assert_eq!(ctx.get_mt_nr_strength().map(|(on, _)| on), Ok(false));
+    }
+
+    #[test]
+    fn test_nr_luma_chroma_balance() {
+        let ctx = MockContext::new();
+        assert_eq!(ctx.get_nr_luma_chroma_balance(), Ok((50, 50)));
+        for (luma, chroma) in [(0, 100), (30, 70), (100, 0), (1, 99)] {
+            ctx.set_nr_luma_chroma_balance(luma, chroma).unwrap();
+            assert_eq!(ctx.get_nr_luma_chroma_balance(), Ok((luma, chroma)));
+        }
+        // 超出范围的值截断到 100。
+        ctx.set_nr_luma_chroma_balance(101, 255).unwrap();
+        assert_eq!(ctx.state.borrow().nr_balance, (1.0, 1.0));
+        assert_eq!(ctx.get_nr_luma_chroma_balance(), Ok((100, 100)));
+        assert_eq!(nr_balance_from_percent(-0.5), 0);
+        assert_eq!(nr_balance_from_percent(1.5), 100);
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    #[test]
+    fn test_nr_state_from_info() {
+        let info = NrInfo {
+            iso: 1600,
+            bayernr2DEn: 1,
+            fStrength2D: 0.6,
+            bayernr3DEn: 0,
+            fStrength3D: 1.2,
+            ..Default::default()
+        };
+        assert_eq!(
+            NrState::from(&info),
+            NrState {
+                iso: 1600,
+                spatial: (true, strength(60)),
+                temporal: (false, Strength::MAX),
+            }
+        );
     }
 }
@@ -0,0 +1,99 @@
+//! 模组 OTP 信息
+//!
+//! 模组出厂时写入 OTP 的标定数据由传感器驱动读出，SDK 在静态信息中转发其中的一部分。
+//! 各 SDK 版本的 `rk_aiq_static_info_t` 只带有镜头名称，白平衡典型值、LSC 标定与模组名称
+//! 均未转发，因此这些字段目前总为 `None`；`None` 表示“未提供”，不应按 0 处理。
+use super::context::Context;
+use super::sysctl;
+use super::types::{StaticInfo, XCamResult};
+use std::ffi::CStr;
+
+/// 一个描述模组 OTP 中白平衡标定值的类型。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OtpAwb {
+    /// 本模组在标定光源下的 R/G/B 响应，顺序为 `[r, gr, gb, b]`。
+    pub typical: [u32; 4],
+    /// 金样模组在标定光源下的 R/G/B 响应，顺序同 `typical`。
+    pub golden: [u32; 4],
+}
+
+/// 一个描述模组 OTP 信息的类型，未提供的项为 `None`。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OtpInfo {
+    /// 模组名称。
+    pub module_id: Option<String>,
+    /// 镜头名称。
+    pub lens_id: Option<String>,
+    /// 白平衡标定值。
+    pub awb: Option<OtpAwb>,
+    /// 是否带有 LSC 标定数据。
+    pub lsc_present: Option<bool>,
+}
+
+impl OtpInfo {
+    /// 是否没有任何 OTP 信息。
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 传感器静态信息的扩展接口。
+pub trait SensorInfo {
+    /// 解析模组 OTP 信息。
+    fn otp(&self) -> OtpInfo;
+}
+
+impl SensorInfo for StaticInfo {
+    fn otp(&self) -> OtpInfo {
+        OtpInfo {
+            lens_id: c_name(&self.lens_info.len_name),
+            ..Default::default()
+        }
+    }
+}
+
+impl Context {
+    /// 读取当前传感器模组的 OTP 信息。
+    pub fn otp_info(&self) -> XCamResult<OtpInfo> {
+        sysctl::get_static_metas(self.sns_ent_name.as_str()).map(|info| info.otp())
+    }
+}
+
+/// 将定长 C 字符串转换为 `String`，空串或缺少结尾 `\0` 时返回 `None`。
+fn c_name(raw: &[std::os::raw::c_char]) -> Option<String> {
+    let bytes: Vec<u8> = raw.iter().map(|&c| c as u8).collect();
+    CStr::from_bytes_until_nul(&bytes)
+        .ok()
+        .map(|s| s.to_string_lossy().trim().to_owned())
+        .filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(lens: &[u8]) -> StaticInfo {
+        let mut info = StaticInfo::default();
+        for (dst, &src) in info.lens_info.len_name.iter_mut().zip(lens) {
+            *dst = src as _;
+        }
+        info
+    }
+
+    #[test]
+    fn test_otp() {
+        let otp = fixture(b"").otp();
+        assert!(otp.is_empty());
+        assert_eq!(otp.awb, None);
+        assert_eq!(otp.lsc_present, None);
+
+        let otp = fixture(b"CMK-OT1607-FV1\0").otp();
+        assert_eq!(otp.lens_id.as_deref(), Some("CMK-OT1607-FV1"));
+        assert_eq!(otp.module_id, None);
+        assert_eq!(otp.awb, None);
+        assert!(!otp.is_empty());
+
+        let unterminated = fixture(&[b'x'; 64]);
+        assert_eq!(unterminated.otp().lens_id, None);
+    }
+}
@@ -2,16 +2,24 @@ pub use super::acm::AutoColorManagment;
 pub use super::ae::AutoExposure;
 pub use super::af::AutoFocus;
 pub use super::asd::AmbientDetection;
-pub use super::awb::AutoWhiteBalance;
+pub use super::awb::{
+    AsShotNeutral, AutoWhiteBalance, GainDistance, IlluminantPreset, NeutralPatch,
+};
+pub use super::blc::BlackLevelCorrection;
+pub use super::ccm::ColorCorrection;
 pub use super::context::Context;
+pub use super::csm::ColorSpace;
 pub use super::defog::Defog;
+pub use super::dm::Demosaic;
 pub use super::error::XCamError;
 pub use super::fec::FEC;
 pub use super::gamma::Gamma;
 pub use super::hdr::HighDynamicRange;
 pub use super::ldch::LDCH;
+pub use super::lsc::LensShading;
 pub use super::misc::Miscellaneous;
 pub use super::nr::NoiseRemoval;
+pub use super::otp::SensorInfo;
 pub use super::sharpen::Sharpen;
 pub use super::sysctl::SystemControl;
 pub use super::types::XCamResult;
@@ -0,0 +1,256 @@
+//! 重试
+//!
+//! 部分 SDK 接口在设备暂时忙碌时失败，稍后重试即可成功，例如 `prepare` 后约 100ms 内
+//! 调用 `start`，或 `stop` 时仍有帧在排空。`retry` 只重试此类暂时性错误（见 `is_transient`），
+//! 每次重试前以 `warn` 级别输出一条日志（见 `logging` 模块）并按 `RetryPolicy` 退避。
+//!
+//! 切换工作模式期间部分设置接口会间歇返回 `XCamError::Busy` 或 `XCamError::Timeout`，
+//! `retry_busy` 以固定间隔重试这两种错误，适合包装单个设置调用。
+use super::clock::{Clock, SystemClock};
+use super::error::XCamError;
+use super::logging::log_warn;
+use super::types::XCamResult;
+use std::thread;
+use std::time::Duration;
+
+/// 一个描述重试次数与退避时间的类型。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// 首次调用失败后最多重试的次数，为 0 时不重试。
+    pub max_retries: u32,
+    /// 第一次重试前的等待时间。
+    pub initial_backoff: Duration,
+    /// 每次重试后等待时间的倍数。
+    pub multiplier: u32,
+    /// 等待时间的上限。
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// 最多重试 5 次，等待时间从 20ms 起每次加倍，不超过 200ms，共约 0.6s。
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(20),
+            multiplier: 2,
+            max_backoff: Duration::from_millis(200),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 不重试，首次失败即返回错误。
+    pub const fn none() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: Duration::ZERO,
+            multiplier: 1,
+            max_backoff: Duration::ZERO,
+        }
+    }
+
+    /// 第 `retry` 次重试（从 0 开始）前的等待时间。
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self.multiplier.max(1).saturating_pow(retry);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// 是否为值得重试的暂时性错误，即 `XCamError::Busy` 与 `XCamError::Timeout`。
+///
+/// SDK 把驱动返回的各种 errno 统一报告为 `XCAM_RETURN_ERROR_IOCTL`，其中既有 `EBUSY`、
+/// `EAGAIN`，也有设备断开、参数无效等重试无济于事的错误，因此不视为暂时性错误。
+pub fn is_transient(e: &XCamError) -> bool {
+    matches!(e, XCamError::Busy | XCamError::Timeout)
+}
+
+/// 执行 `f`，遇到暂时性错误时按 `policy` 重试，返回最后一次调用的结果。
+///
+/// # Parameters
+/// * `policy` - 重试策略。
+/// * `name` - 操作名称，用于警告信息。
+/// * `f` - 被重试的调用。
+pub fn retry<T, F>(policy: &RetryPolicy, name: &str, mut f: F) -> XCamResult<T>
+where
+    F: FnMut() -> XCamResult<T>,
+{
+    let mut retries = 0;
+    loop {
+        match f() {
+            Err(e) if is_transient(&e) && retries < policy.max_retries => {
+                let wait = policy.backoff(retries);
+                retries += 1;
+                log_warn!(
+                    "{} failed: {}, retry {}/{} in {:?}",
+                    name,
+                    e,
+                    retries,
+                    policy.max_retries,
+                    wait
+                );
+                thread::sleep(wait);
+            }
+            r => return r,
+        }
+    }
+}
+
+/// 执行 `f` 至多 `attempts` 次，只在返回暂时性错误（见 `is_transient`）时等待 `backoff`
+/// 后重试，返回最后一次调用的结果。
+///
+/// 其余错误不重试，立即返回；`attempts` 为 0 时与 1 相同，只调用一次。
+pub fn retry_busy<T, F>(attempts: u32, backoff: Duration, f: F) -> XCamResult<T>
+where
+    F: Fn() -> XCamResult<T>,
+{
+    retry_busy_with(&SystemClock, attempts, backoff, f)
+}
+
+/// 同 `retry_busy`，以 `clock` 等待。
+pub fn retry_busy_with<C, T, F>(clock: &C, attempts: u32, backoff: Duration, f: F) -> XCamResult<T>
+where
+    C: Clock + ?Sized,
+    F: Fn() -> XCamResult<T>,
+{
+    let mut left = attempts.max(1);
+    loop {
+        left -= 1;
+        match f() {
+            Err(e) if is_transient(&e) && left > 0 => clock.sleep(backoff),
+            r => return r,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi;
+    use crate::mock::MockClock;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.backoff(0), Duration::from_millis(20));
+        assert_eq!(policy.backoff(2), Duration::from_millis(80));
+        assert_eq!(policy.backoff(4), Duration::from_millis(200));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(200));
+        assert_eq!(RetryPolicy::none().backoff(3), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_retry() {
+        let policy = RetryPolicy {
+            max_retries: 3,
+            initial_backoff: Duration::ZERO,
+            ..Default::default()
+        };
+        let calls = Cell::new(0);
+        let busy_twice = || {
+            calls.set(calls.get() + 1);
+            if calls.get() <= 2 {
+                Err(XCamError::Busy)
+            } else {
+                Ok(calls.get())
+            }
+        };
+        assert_eq!(retry(&policy, "test", busy_twice), Ok(3));
+
+        calls.set(0);
+        let r: XCamResult<()> = retry(&policy, "test", || {
+            calls.set(calls.get() + 1);
+            Err(XCamError::Busy)
+        });
+        assert_eq!(r, Err(XCamError::Busy));
+        assert_eq!(calls.get(), 4);
+
+        calls.set(0);
+        let param = || XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM);
+        let r: XCamResult<()> = retry(&policy, "test", || {
+            calls.set(calls.get() + 1);
+            Err(param())
+        });
+        assert_eq!(r, Err(param()));
+        assert_eq!(calls.get(), 1);
+
+        calls.set(0);
+        let r: XCamResult<()> = retry(&RetryPolicy::none(), "test", || {
+            calls.set(calls.get() + 1);
+            Err(XCamError::Busy)
+        });
+        assert!(r.is_err());
+        assert_eq!(calls.get(), 1);
+
+        // 超时同样重试，ioctl 错误不重试。
+        calls.set(0);
+        let r: XCamResult<()> = retry(&policy, "test", || {
+            calls.set(calls.get() + 1);
+            Err(XCamError::Timeout)
+        });
+        assert_eq!(r, Err(XCamError::Timeout));
+        assert_eq!(calls.get(), 4);
+
+        calls.set(0);
+        let ioctl = || XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_IOCTL);
+        let r: XCamResult<()> = retry(&policy, "test", || {
+            calls.set(calls.get() + 1);
+            Err(ioctl())
+        });
+        assert_eq!(r, Err(ioctl()));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_retry_busy() {
+        let clock = MockClock::new();
+        let backoff = Duration::from_millis(10);
+        let calls = Cell::new(0);
+        let busy_twice = || {
+            calls.set(calls.get() + 1);
+            match calls.get() {
+                1 => Err(XCamError::Busy),
+                // SDK 返回的超时与 `with_timeout` 的超时是同一个值。
+                2 => Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_TIMEOUT)),
+                n => Ok(n),
+            }
+        };
+        assert_eq!(retry_busy_with(&clock, 5, backoff, busy_twice), Ok(3));
+        assert_eq!(clock.elapsed(), Duration::from_millis(20));
+
+        // 次数用尽时返回最后一次的错误，最后一次之后不再等待。
+        calls.set(0);
+        let clock = MockClock::new();
+        let r: XCamResult<()> = retry_busy_with(&clock, 3, backoff, || {
+            calls.set(calls.get() + 1);
+            Err(XCamError::Busy)
+        });
+        assert_eq!(r, Err(XCamError::Busy));
+        assert_eq!(calls.get(), 3);
+        assert_eq!(clock.elapsed(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_retry_busy_not_transient() {
+        let clock = MockClock::new();
+        let calls = Cell::new(0);
+        let param = || XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM);
+        let r: XCamResult<()> = retry_busy_with(&clock, 5, Duration::from_millis(10), || {
+            calls.set(calls.get() + 1);
+            Err(param())
+        });
+        assert_eq!(r, Err(param()));
+        assert_eq!(calls.get(), 1);
+        assert_eq!(clock.elapsed(), Duration::ZERO);
+
+        calls.set(0);
+        let r: XCamResult<()> = retry_busy_with(&clock, 0, Duration::from_millis(10), || {
+            calls.set(calls.get() + 1);
+            Err(XCamError::Busy)
+        });
+        assert!(r.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+}
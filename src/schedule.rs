@@ -0,0 +1,524 @@
+//! 按帧应用参数
+//!
+//! 在两组曝光等参数之间无闪烁地切换时，需要让新参数从指定的帧开始生效，而不是尽快生效。
+//! `Context::schedule` 把一组设置记录为 `Transaction`，在指定帧生效：
+//!
+//! ```ignore
+//! let scheduled = ctx.schedule(Some(frame_id + 10), |tx| {
+//!     tx.push(|ctx| ctx.set_manual_exp(2.0, 0.01));
+//!     tx.push(|ctx| ctx.set_mwb_ct(4500));
+//! })?;
+//! // 每帧的处理循环中
+//! for (id, result) in ctx.tick_schedule() { /* ... */ }
+//! ```
+//!
+//! 各 SDK 版本的属性中没有指定生效帧号的字段（属性中的 `sync` 只选择同步或异步下发），
+//! 因此按帧应用总是以模拟实现：记录帧号 N 与传播延迟 k，由 `tick_schedule` 在元数据回调
+//! 报告的帧号到达 N - k 时依次调用各设置接口。参数经 k 帧传播后约在第 N 帧生效，
+//! 实际生效帧受 `tick_schedule` 的调用时机影响，可能晚一帧。`Scheduled::mode` 报告使用的方式。
+//! 尚未应用的设置可以用 `cancel_scheduled` 取消。
+//!
+//! 已 `prepare` 而尚未 `start` 时，多数设置接口的调用会失败，或在出流时被 IQ 参数覆盖。
+//! 需要“先配置、再出流”时，以 `Context::configure` 提交同样的一组设置：出流期间与 `prepare`
+//! 之前立即应用；已 `prepare` 未出流时按 `set_configure_policy` 处理，默认的
+//! `ConfigurePolicy::Queue` 记录下来，在 `start` 成功后按提交顺序应用，各组的结果由
+//! `take_configure_results` 取回。
+//!
+//! 记录与拒绝只作用于经 `configure` 提交的设置。直接调用的设置接口（如 `set_mwb_ct`）
+//! 总是立即下发给 SDK，不会被记录，也不会因 `ConfigurePolicy::Reject` 被拒绝；
+//! 已 `prepare` 未出流时调用它们的结果由 SDK 决定。
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::sysctl::{frame_reached, SystemControl};
+use super::types::XCamResult;
+use std::sync::Mutex;
+
+/// 默认的传播延迟，单位：帧，与传感器曝光寄存器 2 至 3 帧的生效延迟相当。
+pub const DEFAULT_LEAD_FRAMES: u32 = 2;
+
+type Op<C> = Box<dyn FnOnce(&C) -> XCamResult<()> + Send>;
+
+/// 一组按顺序应用的设置。
+pub struct Transaction<C> {
+    ops: Vec<Op<C>>,
+    lead_frames: u32,
+}
+
+impl<C> Transaction<C> {
+    fn new() -> Self {
+        Self {
+            ops: Vec::new(),
+            lead_frames: DEFAULT_LEAD_FRAMES,
+        }
+    }
+
+    /// 追加一次设置，应用时以上下文调用 `op`。
+    pub fn push<F>(&mut self, op: F) -> &mut Self
+    where
+        F: FnOnce(&C) -> XCamResult<()> + Send + 'static,
+    {
+        self.ops.push(Box::new(op));
+        self
+    }
+
+    /// 设置传播延迟，默认为 `DEFAULT_LEAD_FRAMES`。
+    pub fn lead_frames(&mut self, frames: u32) -> &mut Self {
+        self.lead_frames = frames;
+        self
+    }
+
+    /// 依次应用各设置，遇到第一个失败即停止并返回该错误。
+    fn apply(self, ctx: &C) -> XCamResult<()> {
+        self.ops.into_iter().try_for_each(|op| op(ctx))
+    }
+}
+
+/// 一个描述按帧应用方式的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScheduleMode {
+    /// 未指定帧号或应用时机已到，已在 `schedule` 中应用。
+    Immediate,
+    /// 以帧号模拟，等待 `tick_schedule` 应用。
+    Emulated,
+    /// 已 `prepare` 未出流，等待 `start` 成功后应用，见 `Context::configure`。
+    Deferred,
+}
+
+/// 一个描述已 `prepare` 未出流时 `Context::configure` 行为的枚举，不影响直接调用的设置接口。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ConfigurePolicy {
+    /// 记录下来，在 `start` 成功后应用。
+    #[default]
+    Queue,
+    /// `configure` 返回 `XCAM_RETURN_ERROR_ORDER`，不记录也不应用。
+    Reject,
+}
+
+/// 以 `start` 启动，成功后按提交顺序应用 `scheduler` 中延后的设置。
+///
+/// `start` 失败时直接返回其错误，延后的设置保留到下一次成功启动。
+pub(crate) fn start_then_flush<C, F>(
+    ctx: &C,
+    scheduler: &Mutex<Scheduler<C>>,
+    start: F,
+) -> XCamResult<()>
+where
+    C: SystemControl,
+    F: FnOnce() -> XCamResult<()>,
+{
+    start()?;
+    scheduler.lock().unwrap().flush_deferred(ctx);
+    Ok(())
+}
+
+/// 按帧应用的标识，用于取消与匹配 `tick_schedule` 的结果。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ScheduleId(u64);
+
+/// `schedule` 的结果。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Scheduled {
+    pub id: ScheduleId,
+    pub mode: ScheduleMode,
+}
+
+struct Pending<C> {
+    id: ScheduleId,
+    /// 帧号到达该值时应用。
+    apply_at: u32,
+    tx: Transaction<C>,
+}
+
+/// 等待应用的设置。
+pub(crate) struct Scheduler<C> {
+    next_id: u64,
+    pending: Vec<Pending<C>>,
+    pub(crate) policy: ConfigurePolicy,
+    /// 等待 `start` 后应用的设置。
+    deferred: Vec<(ScheduleId, Transaction<C>)>,
+    /// 已应用的延后设置的结果，尚未取回。
+    deferred_results: Vec<(ScheduleId, XCamResult<()>)>,
+}
+
+impl<C> Default for Scheduler<C> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            pending: Vec::new(),
+            policy: ConfigurePolicy::default(),
+            deferred: Vec::new(),
+            deferred_results: Vec::new(),
+        }
+    }
+}
+
+impl<C: SystemControl> Scheduler<C> {
+    /// 记录 `tx`，在帧号 `at_frame` 生效；为 `None` 或应用时机已到时立即应用。
+    ///
+    /// 指定了帧号但尚未收到元数据回调时无法确定时机，返回 `XCAM_RETURN_ERROR_ORDER`。
+    pub(crate) fn schedule(
+        &mut self,
+        ctx: &C,
+        at_frame: Option<u32>,
+        tx: Transaction<C>,
+    ) -> XCamResult<Scheduled> {
+        let id = self.next_id();
+        let Some(at_frame) = at_frame else {
+            tx.apply(ctx)?;
+            return Ok(Scheduled {
+                id,
+                mode: ScheduleMode::Immediate,
+            });
+        };
+        let frame_id = ctx
+            .get_frame_id()
+            .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))?;
+        let apply_at = at_frame.wrapping_sub(tx.lead_frames);
+        if frame_reached(frame_id, apply_at) {
+            tx.apply(ctx)?;
+            return Ok(Scheduled {
+                id,
+                mode: ScheduleMode::Immediate,
+            });
+        }
+        self.pending.push(Pending { id, apply_at, tx });
+        Ok(Scheduled {
+            id,
+            mode: ScheduleMode::Emulated,
+        })
+    }
+
+    fn next_id(&mut self) -> ScheduleId {
+        let id = ScheduleId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// 提交 `tx`：`prepared` 为真且未出流时按 `policy` 延后或拒绝，否则立即应用。
+    pub(crate) fn configure(
+        &mut self,
+        ctx: &C,
+        prepared: bool,
+        tx: Transaction<C>,
+    ) -> XCamResult<Scheduled> {
+        if prepared && !ctx.is_streaming() {
+            if self.policy == ConfigurePolicy::Reject {
+                return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER));
+            }
+            let id = self.next_id();
+            self.deferred.push((id, tx));
+            return Ok(Scheduled {
+                id,
+                mode: ScheduleMode::Deferred,
+            });
+        }
+        let id = self.next_id();
+        tx.apply(ctx)?;
+        Ok(Scheduled {
+            id,
+            mode: ScheduleMode::Immediate,
+        })
+    }
+
+    /// 按提交顺序应用延后的设置，结果留待 `take_deferred_results` 取回；失败的设置不会重试。
+    pub(crate) fn flush_deferred(&mut self, ctx: &C) {
+        for (id, tx) in std::mem::take(&mut self.deferred) {
+            self.deferred_results.push((id, tx.apply(ctx)));
+        }
+    }
+
+    /// 取回已应用的延后设置的结果。
+    pub(crate) fn take_deferred_results(&mut self) -> Vec<(ScheduleId, XCamResult<()>)> {
+        std::mem::take(&mut self.deferred_results)
+    }
+
+    /// 应用时机已到的设置按记录顺序应用，返回各自的结果；失败的设置不会重试。
+    pub(crate) fn tick(&mut self, ctx: &C) -> Vec<(ScheduleId, XCamResult<()>)> {
+        let Some(frame_id) = ctx.get_frame_id() else {
+            return Vec::new();
+        };
+        let (due, rest) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|p| frame_reached(frame_id, p.apply_at));
+        self.pending = rest;
+        due.into_iter().map(|p| (p.id, p.tx.apply(ctx))).collect()
+    }
+
+    /// 取消尚未应用的设置，返回是否找到。
+    pub(crate) fn cancel(&mut self, id: ScheduleId) -> bool {
+        let len = self.pending.len() + self.deferred.len();
+        self.pending.retain(|p| p.id != id);
+        self.deferred.retain(|(d, _)| *d != id);
+        self.pending.len() + self.deferred.len() != len
+    }
+
+    /// 尚未应用的设置数，包括等待 `start` 的设置。
+    pub(crate) fn pending(&self) -> usize {
+        self.pending.len() + self.deferred.len()
+    }
+}
+
+impl Context {
+    /// 记录由 `build` 填充的一组设置，使其在帧号 `at_frame` 生效，方式见模块说明。
+    ///
+    /// `at_frame` 为 `None` 时立即依次应用。指定帧号时若时机已到同样立即应用，否则等待
+    /// `tick_schedule` 应用，应在每帧的处理循环中调用后者。立即应用时遇到的第一个错误直接返回。
+    pub fn schedule<F>(&self, at_frame: Option<u32>, build: F) -> XCamResult<Scheduled>
+    where
+        F: FnOnce(&mut Transaction<Context>),
+    {
+        let mut tx = Transaction::new();
+        build(&mut tx);
+        self.scheduler.lock().unwrap().schedule(self, at_frame, tx)
+    }
+
+    /// 应用时机已到的设置，返回 `(标识, 结果)` 列表，没有到期的设置时为空。
+    pub fn tick_schedule(&self) -> Vec<(ScheduleId, XCamResult<()>)> {
+        self.scheduler.lock().unwrap().tick(self)
+    }
+
+    /// 提交由 `build` 填充的一组设置，已 `prepare` 未出流时按 `set_configure_policy` 处理，
+    /// 见模块说明。
+    ///
+    /// 其余状态下立即依次应用，遇到的第一个错误直接返回。延后的设置在 `start` 成功后
+    /// 按提交顺序应用，每组遇到第一个失败即停止，结果由 `take_configure_results` 取回；
+    /// 尚未应用时可用 `cancel_scheduled` 取消。
+    pub fn configure<F>(&self, build: F) -> XCamResult<Scheduled>
+    where
+        F: FnOnce(&mut Transaction<Context>),
+    {
+        let mut tx = Transaction::new();
+        build(&mut tx);
+        let prepared = self.prepared.lock().unwrap().is_some();
+        self.scheduler.lock().unwrap().configure(self, prepared, tx)
+    }
+
+    /// 设置已 `prepare` 未出流时 `configure` 的行为，默认为 `ConfigurePolicy::Queue`。
+    ///
+    /// 只作用于 `configure`，直接调用的设置接口不受影响。改为 `Reject` 时已记录的设置保留，
+    /// 仍在 `start` 后应用。
+    pub fn set_configure_policy(&self, policy: ConfigurePolicy) {
+        self.scheduler.lock().unwrap().policy = policy;
+    }
+
+    /// 获取已 `prepare` 未出流时 `configure` 的行为。
+    pub fn get_configure_policy(&self) -> ConfigurePolicy {
+        self.scheduler.lock().unwrap().policy
+    }
+
+    /// 取回 `start` 后已应用的延后设置的 `(标识, 结果)` 列表，取回后清空。
+    pub fn take_configure_results(&self) -> Vec<(ScheduleId, XCamResult<()>)> {
+        self.scheduler.lock().unwrap().take_deferred_results()
+    }
+
+    /// 取消尚未应用的设置，已应用或不存在时返回 `false`。
+    pub fn cancel_scheduled(&self, id: ScheduleId) -> bool {
+        self.scheduler.lock().unwrap().cancel(id)
+    }
+
+    /// 尚未应用的设置数，包括等待 `start` 的设置。
+    pub fn pending_scheduled(&self) -> usize {
+        self.scheduler.lock().unwrap().pending()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::awb::AutoWhiteBalance;
+    use crate::mock::MockContext;
+
+    fn set_ct(ct: u32) -> Transaction<MockContext> {
+        let mut tx = Transaction::new();
+        tx.push(move |ctx: &MockContext| ctx.set_mwb_ct(ct));
+        tx
+    }
+
+    #[test]
+    fn test_schedule_immediate() {
+        let ctx = MockContext::new();
+        let mut sched = Scheduler::default();
+        let s = sched.schedule(&ctx, None, set_ct(3000)).unwrap();
+        assert_eq!(s.mode, ScheduleMode::Immediate);
+        assert_eq!(ctx.state.borrow().wb_ct, 3000);
+
+        // 指定帧号时需要已知当前帧号。
+        assert_eq!(
+            sched.schedule(&ctx, Some(10), set_ct(4000)).err(),
+            Some(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))
+        );
+        // 应用时机已到。
+        ctx.state.borrow_mut().frame_id = Some(8);
+        let s = sched.schedule(&ctx, Some(10), set_ct(4000)).unwrap();
+        assert_eq!(s.mode, ScheduleMode::Immediate);
+        assert_eq!(ctx.state.borrow().wb_ct, 4000);
+        assert_eq!(sched.pending(), 0);
+    }
+
+    #[test]
+    fn test_schedule_emulated() {
+        let ctx = MockContext::new();
+        let mut sched = Scheduler::default();
+        ctx.state.borrow_mut().frame_id = Some(u32::MAX - 1);
+        // 跨越帧号回绕，在第 3 - 2 = 1 帧应用。
+        let a = sched.schedule(&ctx, Some(3), set_ct(3000)).unwrap();
+        assert_eq!(a.mode, ScheduleMode::Emulated);
+        let mut tx = set_ct(6000);
+        tx.lead_frames(0);
+        let b = sched.schedule(&ctx, Some(3), tx).unwrap();
+        assert_ne!(a.id, b.id);
+        assert_eq!(sched.pending(), 2);
+
+        assert!(sched.tick(&ctx).is_empty());
+        ctx.state.borrow_mut().frame_id = Some(1);
+        let done = sched.tick(&ctx);
+        assert_eq!(done, [(a.id, Ok(()))]);
+        assert_eq!(ctx.state.borrow().wb_ct, 3000);
+
+        // 取消后不再应用。
+        assert!(sched.cancel(b.id));
+        assert!(!sched.cancel(b.id));
+        ctx.state.borrow_mut().frame_id = Some(5);
+        assert!(sched.tick(&ctx).is_empty());
+        assert_eq!(ctx.state.borrow().wb_ct, 3000);
+    }
+
+    #[test]
+    fn test_configure_queue() {
+        let ctx = MockContext::new();
+        let mut sched = Scheduler::default();
+        // 尚未 prepare 时立即应用。
+        let s = sched.configure(&ctx, false, set_ct(3000)).unwrap();
+        assert_eq!(s.mode, ScheduleMode::Immediate);
+        assert_eq!(ctx.state.borrow().wb_ct, 3000);
+
+        let a = sched.configure(&ctx, true, set_ct(4000)).unwrap();
+        assert_eq!(a.mode, ScheduleMode::Deferred);
+        let b = sched.configure(&ctx, true, set_ct(5000)).unwrap();
+        let c = sched.configure(&ctx, true, set_ct(6000)).unwrap();
+        assert!(sched.cancel(c.id));
+        assert_eq!(sched.pending(), 2);
+        assert_eq!(ctx.state.borrow().wb_ct, 3000);
+
+        ctx.start().unwrap();
+        ctx.fail("set_mwb_ct");
+        sched.flush_deferred(&ctx);
+        assert_eq!(
+            sched.take_deferred_results(),
+            [
+                (
+                    a.id,
+                    Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))
+                ),
+                (
+                    b.id,
+                    Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))
+                ),
+            ]
+        );
+        assert!(sched.take_deferred_results().is_empty());
+        assert_eq!(sched.pending(), 0);
+    }
+
+    #[test]
+    fn test_configure_queue_applies_in_order() {
+        let ctx = MockContext::new();
+        let mut sched = Scheduler::default();
+        let a = sched.configure(&ctx, true, set_ct(4000)).unwrap();
+        let b = sched.configure(&ctx, true, set_ct(5000)).unwrap();
+        ctx.start().unwrap();
+        sched.flush_deferred(&ctx);
+        assert_eq!(
+            sched.take_deferred_results(),
+            [(a.id, Ok(())), (b.id, Ok(()))]
+        );
+        assert_eq!(ctx.state.borrow().wb_ct, 5000);
+        // 出流期间立即应用。
+        let s = sched.configure(&ctx, true, set_ct(6000)).unwrap();
+        assert_eq!(s.mode, ScheduleMode::Immediate);
+        assert_eq!(ctx.state.borrow().wb_ct, 6000);
+    }
+
+    #[test]
+    fn test_configure_reject() {
+        let ctx = MockContext::new();
+        let ct = ctx.state.borrow().wb_ct;
+        let sched = Mutex::new(Scheduler::default());
+        let queued = sched
+            .lock()
+            .unwrap()
+            .configure(&ctx, true, set_ct(4000))
+            .unwrap();
+        sched.lock().unwrap().policy = ConfigurePolicy::Reject;
+        assert_eq!(
+            sched
+                .lock()
+                .unwrap()
+                .configure(&ctx, true, set_ct(5000))
+                .err(),
+            Some(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))
+        );
+        assert_eq!(sched.lock().unwrap().pending(), 1);
+        assert_eq!(ctx.state.borrow().wb_ct, ct);
+
+        // 改为 Reject 前记录的设置仍在启动后应用，被拒绝的设置不会应用。
+        start_then_flush(&ctx, &sched, || ctx.start()).unwrap();
+        assert_eq!(
+            sched.lock().unwrap().take_deferred_results(),
+            [(queued.id, Ok(()))]
+        );
+        assert_eq!(ctx.state.borrow().wb_ct, 4000);
+    }
+
+    #[test]
+    fn test_start_then_flush() {
+        let ctx = MockContext::new();
+        let sched = Mutex::new(Scheduler::default());
+        let a = sched
+            .lock()
+            .unwrap()
+            .configure(&ctx, true, set_ct(4000))
+            .unwrap();
+        let b = sched
+            .lock()
+            .unwrap()
+            .configure(&ctx, true, set_ct(5000))
+            .unwrap();
+        let failed = XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED);
+
+        // 启动失败时不应用，延后的设置保留到下一次启动。
+        assert_eq!(start_then_flush(&ctx, &sched, || Err(failed)), Err(failed));
+        assert_eq!(sched.lock().unwrap().pending(), 2);
+        assert!(sched.lock().unwrap().take_deferred_results().is_empty());
+        assert!(ctx.calls().is_empty());
+
+        start_then_flush(&ctx, &sched, || ctx.start()).unwrap();
+        assert!(ctx.is_streaming());
+        assert_eq!(sched.lock().unwrap().pending(), 0);
+        assert_eq!(
+            sched.lock().unwrap().take_deferred_results(),
+            [(a.id, Ok(())), (b.id, Ok(()))]
+        );
+        assert_eq!(ctx.calls(), ["start", "set_mwb_ct", "set_mwb_ct"]);
+        assert_eq!(ctx.state.borrow().wb_ct, 5000);
+    }
+
+    #[test]
+    fn test_schedule_reports_failure() {
+        let ctx = MockContext::new();
+        let mut sched = Scheduler::default();
+        ctx.state.borrow_mut().frame_id = Some(0);
+        let s = sched.schedule(&ctx, Some(5), set_ct(3000)).unwrap();
+        ctx.fail("set_mwb_ct");
+        ctx.state.borrow_mut().frame_id = Some(3);
+        assert_eq!(
+            sched.tick(&ctx),
+            [(
+                s.id,
+                Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))
+            )]
+        );
+        assert_eq!(sched.pending(), 0);
+    }
+}
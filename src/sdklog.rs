@@ -0,0 +1,153 @@
+//! SDK 日志
+//!
+//! librkaiq 自行向标准输出打印日志，级别与模块由环境变量 `persist_camera_engine_log`
+//! 控制，取值为十六进制数：bit[3:0] 为日志级别，bit[11:4] 为子模块掩码，
+//! bit[12] 起每一位对应一个模块，顺序与 SDK 的 `xcam_log.h` 相同。
+//!
+//! 各版本 SDK 都没有导出日志控制接口，也不支持日志回调，本模块通过设置该环境变量控制日志，
+//! 无法将 SDK 日志转交给 Rust 的日志系统。SDK 只在初始化时读取一次环境变量，
+//! 因此须在创建第一个 `Context` 之前调用，之后的修改在进程重启前不生效。
+//!
+//! # Note
+//!
+//! 设置环境变量时其他线程不能同时读取环境变量，应在程序启动时、创建其他线程之前调用。
+use std::env;
+use std::sync::Mutex;
+
+/// SDK 读取的日志环境变量名。
+pub const SDK_LOG_ENV: &str = "persist_camera_engine_log";
+/// 子模块掩码，本库总是打开全部子模块。
+const SUB_MODULE_MASK: u64 = 0xff;
+
+/// 一个描述 SDK 日志级别的枚举，取值与 SDK 的 `XCORE_LOG_LEVEL_*` 相同。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// 不输出日志。
+    None = 0,
+    /// SDK 的默认级别。
+    #[default]
+    Error = 1,
+    Warning = 2,
+    Info = 3,
+    Debug = 4,
+    Verbose = 5,
+}
+
+/// 一个描述 SDK 日志模块的枚举，取值为模块在 `xcam_log.h` 中的序号。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SdkLogModule {
+    Aec = 0,
+    Awb,
+    Af,
+    Ablc,
+    Adpcc,
+    Atmo,
+    Anr,
+    Alsc,
+    Agic,
+    Adebayer,
+    Accm,
+    Agamma,
+    Awdr,
+    Adehaze,
+    A3dlut,
+    Aldch,
+    Acsm,
+    Acp,
+    Aie,
+    Asharp,
+    Aorb,
+    Afec,
+    Acgc,
+    Asd,
+    Xcore,
+    Analyzer,
+    CamHw,
+}
+
+impl SdkLogModule {
+    /// 全部模块。
+    pub fn all() -> &'static [SdkLogModule] {
+        use SdkLogModule::*;
+        &[
+            Aec, Awb, Af, Ablc, Adpcc, Atmo, Anr, Alsc, Agic, Adebayer, Accm, Agamma, Awdr,
+            Adehaze, A3dlut, Aldch, Acsm, Acp, Aie, Asharp, Aorb, Afec, Acgc, Asd, Xcore, Analyzer,
+            CamHw,
+        ]
+    }
+}
+
+/// 已设置的日志级别与模块掩码，模块掩码为 `None` 时打开全部模块。
+static SDK_LOG: Mutex<(LogLevel, Option<u64>)> = Mutex::new((LogLevel::Error, None));
+
+/// 由模块列表计算模块掩码。
+pub fn sdk_log_module_mask(modules: &[SdkLogModule]) -> u64 {
+    modules.iter().fold(0, |mask, &m| mask | (1 << m as u32))
+}
+
+/// 计算日志环境变量的取值。
+///
+/// 模块掩码为 `None` 时打开全部模块；级别为 `LogLevel::None` 或模块掩码为空时关闭日志。
+pub fn sdk_log_value(level: LogLevel, modules: Option<u64>) -> String {
+    let modules = modules.unwrap_or_else(|| sdk_log_module_mask(SdkLogModule::all()));
+    if level == LogLevel::None || modules == 0 {
+        return "0x0".into();
+    }
+    format!(
+        "{:#x}",
+        (modules << 12) | (SUB_MODULE_MASK << 4) | level as u64
+    )
+}
+
+fn update_sdk_log<F: FnOnce(&mut (LogLevel, Option<u64>))>(f: F) {
+    let mut config = SDK_LOG.lock().unwrap();
+    f(&mut config);
+    env::set_var(SDK_LOG_ENV, sdk_log_value(config.0, config.1));
+}
+
+/// 设置 SDK 的日志级别，未设置模块时对全部模块生效。须在创建 `Context` 之前调用。
+pub fn set_sdk_log_level(level: LogLevel) {
+    update_sdk_log(|config| config.0 = level);
+}
+
+/// 设置输出日志的 SDK 模块，其余模块不输出日志；级别不变，默认为 `LogLevel::Error`。
+/// 须在创建 `Context` 之前调用。
+pub fn set_sdk_log_modules(modules: &[SdkLogModule]) {
+    update_sdk_log(|config| config.1 = Some(sdk_log_module_mask(modules)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sdk_log_value() {
+        assert_eq!(SdkLogModule::all().len(), SdkLogModule::CamHw as usize + 1);
+        assert_eq!(
+            sdk_log_module_mask(&[SdkLogModule::Aec, SdkLogModule::Af]),
+            0b101
+        );
+        assert_eq!(
+            sdk_log_value(
+                LogLevel::Verbose,
+                Some(sdk_log_module_mask(&[SdkLogModule::Aec]))
+            ),
+            "0x1ff5"
+        );
+        assert_eq!(
+            sdk_log_value(LogLevel::Error, None),
+            format!("{:#x}", (((1u64 << 27) - 1) << 12) | 0xff1)
+        );
+        assert_eq!(sdk_log_value(LogLevel::None, None), "0x0");
+        assert_eq!(sdk_log_value(LogLevel::Debug, Some(0)), "0x0");
+    }
+
+    #[test]
+    fn test_set_sdk_log() {
+        set_sdk_log_level(LogLevel::Warning);
+        set_sdk_log_modules(&[SdkLogModule::Awb]);
+        assert_eq!(env::var(SDK_LOG_ENV).unwrap(), "0x2ff2");
+        set_sdk_log_level(LogLevel::Info);
+        assert_eq!(env::var(SDK_LOG_ENV).unwrap(), "0x2ff3");
+    }
+}
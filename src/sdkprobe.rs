@@ -0,0 +1,144 @@
+//! SDK 版本探测
+//!
+//! 本库按版本特性（`v1_0`–`v5_0`）编译，与运行时的 librkaiq 之间没有任何版本检查：以 `v4_0`
+//! 编译的程序运行在 v3.x 的库上时，首次调用新版本才有的 uAPI 会因找不到符号而崩溃，
+//! 只留下一行 `symbol lookup error`。
+//!
+//! 本库静态声明 SDK 符号，由动态链接器按需（lazy binding）解析，因而可以在首次真正调用之前，
+//! 以 `dlsym` 在已加载的库中查找当前版本特性依赖的一组代表性符号：
+//!
+//! | 版本 | 代表性符号 |
+//! |---|---|
+//! | v3_0 | `rk_aiq_user_api2_adrc_GetAttrib`、`rk_aiq_user_api2_agamma_GetAttrib` |
+//! | v4_0 | `rk_aiq_user_api2_ae_getExpSwAttr`、`rk_aiq_uapi2_getExpMode`、`rk_aiq_uapi2_setWBMode` |
+//! | v5_0 | `rk_aiq_uapi2_sysctl_preInit_iq_addr` |
+//!
+//! v5_0 的代表性符号取自 sysctl 的公共接口，各 ISP 硬件版本均提供，不依赖某个算法模块。
+//!
+//! 编译版本及更早版本的符号缺失时返回 `XCamError::SdkMismatch`，列出第一个缺少符号的版本的
+//! 代表性符号，并按各版本的符号推测库的版本。
+//! `Context::new` 在初始化 SDK 之前调用 `probe_sdk`，结果在进程内缓存。v1_0、v2_0 没有代表性符号，
+//! 总是通过。
+//!
+//! 以立即绑定（`LD_BIND_NOW` 或 `-z now`）链接时，缺失的符号在程序启动时即报错，无法由本模块检出。
+use super::dump::sdk_version;
+use super::error::XCamError;
+use super::types::XCamResult;
+use std::ffi::{c_char, c_void, CString};
+use std::sync::OnceLock;
+
+#[link(name = "dl")]
+extern "C" {
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
+
+/// `dlsym` 的 `RTLD_DEFAULT`：在全局符号表中查找。
+const RTLD_DEFAULT: *mut c_void = std::ptr::null_mut();
+
+/// 各版本新增的代表性符号，按版本从旧到新排列，见模块说明。
+const MARKERS: &[(&str, &[&str])] = &[
+    (
+        "v3_0",
+        &[
+            "rk_aiq_user_api2_adrc_GetAttrib",
+            "rk_aiq_user_api2_agamma_GetAttrib",
+        ],
+    ),
+    (
+        "v4_0",
+        &[
+            "rk_aiq_user_api2_ae_getExpSwAttr",
+            "rk_aiq_uapi2_getExpMode",
+            "rk_aiq_uapi2_setWBMode",
+        ],
+    ),
+    ("v5_0", &["rk_aiq_uapi2_sysctl_preInit_iq_addr"]),
+];
+
+/// 以 `lookup` 检查版本 `compiled` 依赖的符号，见模块说明。
+pub(crate) fn probe_with<F>(compiled: &'static str, lookup: F) -> XCamResult<()>
+where
+    F: Fn(&str) -> bool,
+{
+    let end = MARKERS
+        .iter()
+        .position(|(v, _)| *v == compiled)
+        .map_or(0, |i| i + 1);
+    let Some(&(_, missing_symbols)) = MARKERS[..end]
+        .iter()
+        .find(|(_, symbols)| !symbols.iter().all(|s| lookup(s)))
+    else {
+        return Ok(());
+    };
+    let detected = MARKERS
+        .iter()
+        .take_while(|(_, symbols)| symbols.iter().all(|s| lookup(s)))
+        .last()
+        .map(|(v, _)| *v);
+    Err(XCamError::SdkMismatch {
+        compiled_for: compiled,
+        detected,
+        missing_symbols,
+    })
+}
+
+fn has_symbol(name: &str) -> bool {
+    let Ok(name) = CString::new(name) else {
+        return false;
+    };
+    unsafe { !dlsym(RTLD_DEFAULT, name.as_ptr()).is_null() }
+}
+
+/// 检查已加载的 librkaiq 是否提供当前版本特性依赖的符号，结果在进程内缓存。
+///
+/// 缺少符号时返回 `XCamError::SdkMismatch`，见模块说明。
+pub fn probe_sdk() -> XCamResult<()> {
+    static RESULT: OnceLock<XCamResult<()>> = OnceLock::new();
+    *RESULT.get_or_init(|| probe_with(sdk_version(), has_symbol))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_with() {
+        let v3_lib = |s: &str| MARKERS[0].1.contains(&s);
+        assert_eq!(probe_with("v3_0", v3_lib), Ok(()));
+        assert_eq!(probe_with("v1_0", |_| false), Ok(()));
+        assert_eq!(probe_with("v4_0", |_| true), Ok(()));
+        assert_eq!(
+            probe_with("v4_0", v3_lib),
+            Err(XCamError::SdkMismatch {
+                compiled_for: "v4_0",
+                detected: Some("v3_0"),
+                missing_symbols: MARKERS[1].1,
+            })
+        );
+        let Err(XCamError::SdkMismatch {
+            detected,
+            missing_symbols,
+            ..
+        }) = probe_with("v4_0", |_| false)
+        else {
+            panic!("expected SdkMismatch");
+        };
+        assert_eq!(detected, None);
+        assert_eq!(missing_symbols, MARKERS[0].1);
+    }
+
+    #[test]
+    fn test_probe_v5() {
+        let v4_lib = |s: &str| MARKERS[..2].iter().any(|(_, symbols)| symbols.contains(&s));
+        assert_eq!(probe_with("v4_0", v4_lib), Ok(()));
+        assert_eq!(
+            probe_with("v5_0", v4_lib),
+            Err(XCamError::SdkMismatch {
+                compiled_for: "v5_0",
+                detected: Some("v4_0"),
+                missing_symbols: &["rk_aiq_uapi2_sysctl_preInit_iq_addr"],
+            })
+        );
+        assert_eq!(probe_with("v5_0", |_| true), Ok(()));
+    }
+}
@@ -0,0 +1,207 @@
+//! 自检
+//!
+//! 现场排查问题时，`Context::self_test` 对各子系统各执行一次只读操作，逐项报告是否正常：
+//!
+//! | 子系统 | 检查 |
+//! | --- | --- |
+//! | `WhiteBalance` | `get_wb_mode` |
+//! | `Exposure` | `get_exp_mode` |
+//! | `Focus` | `get_af_search_state` |
+//! | `Version` | `sdkprobe::probe_sdk`，运行时的 librkaiq 是否与编译的版本特性一致 |
+//! | `Stats` | `get_3a_stats`，最多等待 `SELF_TEST_STATS_TIMEOUT` |
+//!
+//! 自检只调用读取接口，不修改任何设置；各项相互独立，一项失败（包括 panic）不影响其余各项，
+//! 自检本身不会 panic。统计一项会消耗统计队列中的一帧，未出流时该项失败；
+//! 没有对焦马达的模组上 `Focus` 一项失败属于正常现象。
+use super::ae::AutoExposure;
+use super::af::AutoFocus;
+use super::awb::AutoWhiteBalance;
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::sdkprobe;
+use super::sysctl::SystemControl;
+use super::types::XCamResult;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+/// 自检读取 3A 统计的超时。
+pub const SELF_TEST_STATS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// 一个描述自检子系统的枚举，见模块说明。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Subsystem {
+    WhiteBalance,
+    Exposure,
+    Focus,
+    Version,
+    Stats,
+}
+
+impl Subsystem {
+    /// 全部子系统，按检查顺序排列。
+    pub const ALL: [Self; 5] = [
+        Self::WhiteBalance,
+        Self::Exposure,
+        Self::Focus,
+        Self::Version,
+        Self::Stats,
+    ];
+
+    /// 子系统的名称，例如 `white-balance`。
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::WhiteBalance => "white-balance",
+            Self::Exposure => "exposure",
+            Self::Focus => "focus",
+            Self::Version => "version",
+            Self::Stats => "stats",
+        }
+    }
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// `Context::self_test` 的自检报告。
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelfTestReport {
+    /// 按 `Subsystem::ALL` 的顺序排列的结果。
+    pub results: Vec<(Subsystem, XCamResult<()>)>,
+}
+
+impl SelfTestReport {
+    /// 是否全部正常。
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// 失败的子系统及其错误。
+    pub fn failures(&self) -> impl Iterator<Item = (Subsystem, &XCamError)> {
+        self.results
+            .iter()
+            .filter_map(|(s, r)| r.as_ref().err().map(|e| (*s, e)))
+    }
+
+    /// 子系统 `subsystem` 的结果。
+    pub fn get(&self, subsystem: Subsystem) -> Option<&XCamResult<()>> {
+        self.results
+            .iter()
+            .find(|(s, _)| *s == subsystem)
+            .map(|(_, r)| r)
+    }
+}
+
+/// 每个子系统一行，形如 `[ OK ] exposure`、`[FAIL] stats: ...`。
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (subsystem, result) in &self.results {
+            match result {
+                Ok(()) => writeln!(f, "[ OK ] {}", subsystem)?,
+                Err(e) => writeln!(f, "[FAIL] {}: {}", subsystem, e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 执行一项检查，panic 视为 `XCAM_RETURN_ERROR_FAILED`。
+fn check<T, F>(f: F) -> XCamResult<()>
+where
+    F: FnOnce() -> XCamResult<T>,
+{
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|_| Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED)))
+        .map(|_| ())
+}
+
+/// 对 `ctx` 执行自检，`version` 为版本一项的检查，见模块说明。
+pub(crate) fn self_test_with<C, F>(ctx: &C, version: F) -> SelfTestReport
+where
+    C: AutoWhiteBalance + AutoExposure + AutoFocus + SystemControl + ?Sized,
+    F: FnOnce() -> XCamResult<()>,
+{
+    let mut version = Some(version);
+    let results = Subsystem::ALL
+        .into_iter()
+        .map(|subsystem| {
+            let result = match subsystem {
+                Subsystem::WhiteBalance => check(|| ctx.get_wb_mode()),
+                Subsystem::Exposure => check(|| ctx.get_exp_mode()),
+                Subsystem::Focus => check(|| ctx.get_af_search_state()),
+                Subsystem::Version => check(|| version.take().map_or(Ok(()), |f| f())),
+                Subsystem::Stats => check(|| ctx.get_3a_stats(SELF_TEST_STATS_TIMEOUT)),
+            };
+            (subsystem, result)
+        })
+        .collect();
+    SelfTestReport { results }
+}
+
+impl Context {
+    /// 对各子系统各执行一次只读操作并报告结果，不修改任何设置，见模块说明。
+    pub fn self_test(&self) -> SelfTestReport {
+        self_test_with(self, sdkprobe::probe_sdk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockContext;
+    use crate::types::IspStats;
+
+    #[test]
+    fn test_self_test_flags_failing_subsystem() {
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().stats = Some(IspStats::default());
+        let report = self_test_with(&ctx, || Ok(()));
+        assert!(report.is_ok());
+        assert_eq!(report.results.len(), Subsystem::ALL.len());
+
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().stats = Some(IspStats::default());
+        ctx.fail("get_af_search_state");
+        let report = self_test_with(&ctx, || Ok(()));
+        let failed = XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED);
+        assert_eq!(
+            report.failures().collect::<Vec<_>>(),
+            [(Subsystem::Focus, &failed)]
+        );
+        assert_eq!(report.get(Subsystem::Exposure), Some(&Ok(())));
+        // 只调用读取接口。
+        assert_eq!(
+            ctx.calls(),
+            [
+                "get_wb_mode",
+                "get_exp_mode",
+                "get_af_search_state",
+                "get_3a_stats"
+            ]
+        );
+        let text = report.to_string();
+        assert!(text.contains("[ OK ] white-balance\n"));
+        assert!(text.contains(&format!("[FAIL] focus: {}\n", failed)));
+    }
+
+    #[test]
+    fn test_self_test_catches_panic() {
+        let ctx = MockContext::new();
+        let report = self_test_with(&ctx, || panic!("probe"));
+        assert_eq!(
+            report.get(Subsystem::Version),
+            Some(&Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED
+            )))
+        );
+        // 未设置统计时统计一项超时，其余各项不受影响。
+        assert_eq!(
+            report.failures().map(|(s, _)| s).collect::<Vec<_>>(),
+            [Subsystem::Version, Subsystem::Stats]
+        );
+    }
+}
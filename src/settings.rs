@@ -0,0 +1,587 @@
+//! 摄像头参数
+//!
+//! 以结构体描述一组摄像头初始参数，未设置的项保持当前值。
+//! 启用 `serde` 特性后可序列化，启用 `config` 特性后可从 TOML 文件读取。
+use super::acm::AutoColorManagment;
+use super::ae::{AutoExposure, ExpInfo};
+use super::awb::AutoWhiteBalance;
+use super::context::Context;
+use super::display::enum_str;
+use super::nr::NoiseRemoval;
+use super::sharpen::Sharpen;
+use super::sysctl::{PrepareResult, SystemControl};
+use super::types::{OpMode, Strength, WbGain, WorkingMode, XCamResult};
+
+/// `CameraSettings` 当前的格式版本。
+///
+/// 增删字段或改变字段含义时递增。早于 `schema_version` 字段写入的快照视为版本 0，
+/// 其中的饱和度可能是 [0,255] 的等级而非百分比，见 `CameraSettings::migrate_from_json`。
+pub const SETTINGS_SCHEMA_VERSION: u32 = 1;
+
+/// 一个描述摄像头参数的类型。
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct CameraSettings {
+    /// 格式版本，默认为 `SETTINGS_SCHEMA_VERSION`。
+    pub schema_version: u32,
+    /// 白平衡参数。
+    pub white_balance: Option<WhiteBalanceSettings>,
+    /// 曝光参数。
+    pub exposure: Option<ExposureSettings>,
+    /// 图像调节参数。
+    pub adjust: Option<AdjustSettings>,
+    /// 降噪参数。
+    pub noise_reduction: Option<NoiseReductionSettings>,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            schema_version: SETTINGS_SCHEMA_VERSION,
+            white_balance: None,
+            exposure: None,
+            adjust: None,
+            noise_reduction: None,
+        }
+    }
+}
+
+/// 一个描述白平衡参数的类型。
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct WhiteBalanceSettings {
+    /// 工作模式。
+    pub mode: Option<OpMode>,
+    /// 手动色温，单位：K。
+    pub ct: Option<u32>,
+    /// 手动增益。
+    pub gain: Option<GainSettings>,
+}
+
+/// 一个描述白平衡通道增益的类型。
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct GainSettings {
+    pub r: f32,
+    pub gr: f32,
+    pub gb: f32,
+    pub b: f32,
+}
+
+impl From<GainSettings> for WbGain {
+    fn from(val: GainSettings) -> Self {
+        WbGain {
+            rgain: val.r,
+            grgain: val.gr,
+            gbgain: val.gb,
+            bgain: val.b,
+        }
+    }
+}
+
+impl From<WbGain> for GainSettings {
+    fn from(val: WbGain) -> Self {
+        GainSettings {
+            r: val.rgain,
+            gr: val.grgain,
+            gb: val.gbgain,
+            b: val.bgain,
+        }
+    }
+}
+
+/// 一个描述取值范围的类型。
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct RangeSettings {
+    pub min: f32,
+    pub max: f32,
+}
+
+/// 一个描述曝光参数的类型。
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct ExposureSettings {
+    /// 工作模式。
+    pub mode: Option<OpMode>,
+    /// 增益范围，单位：倍。
+    pub gain_range: Option<RangeSettings>,
+    /// 曝光时间范围，单位：秒。
+    pub time_range: Option<RangeSettings>,
+}
+
+/// 一个描述图像调节参数的类型。
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct AdjustSettings {
+    /// 亮度等级，范围：[0,255]。
+    pub brightness: Option<u32>,
+    /// 对比度等级，范围：[0,255]。
+    pub contrast: Option<u32>,
+    /// 饱和度。
+    pub saturation: Option<Strength>,
+    /// 色度等级，范围：[0,255]。
+    pub hue: Option<u32>,
+    /// 锐化强度。
+    pub sharpness: Option<Strength>,
+}
+
+/// 一个描述降噪参数的类型。
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct NoiseReductionSettings {
+    /// 工作模式。
+    pub mode: Option<OpMode>,
+    /// 降噪强度。
+    pub strength: Option<Strength>,
+    /// 手动空域降噪强度。
+    pub spatial: Option<Strength>,
+    /// 手动时域降噪强度。
+    pub temporal: Option<Strength>,
+}
+
+impl CameraSettings {
+    /// 将参数应用到上下文。
+    ///
+    /// 按白平衡、曝光、图像调节、降噪的顺序应用；每组内先设置工作模式，再设置具体数值。
+    /// 遇到第一个错误即返回，此前已应用的参数不会回滚。
+    pub fn apply<C>(&self, ctx: &C) -> XCamResult<()>
+    where
+        C: AutoWhiteBalance + AutoExposure + AutoColorManagment + NoiseRemoval + Sharpen,
+    {
+        if let Some(wb) = &self.white_balance {
+            if let Some(mode) = wb.mode {
+                ctx.set_wb_mode(mode)?;
+            }
+            if let Some(ct) = wb.ct {
+                ctx.set_mwb_ct(ct)?;
+            }
+            if let Some(gain) = wb.gain {
+                ctx.set_mwb_gain(gain)?;
+            }
+        }
+        if let Some(exp) = &self.exposure {
+            if let Some(mode) = exp.mode {
+                ctx.set_exp_mode(mode)?;
+            }
+            if let Some(r) = exp.gain_range {
+                ctx.set_exp_gain_range(r.min, r.max)?;
+            }
+            if let Some(r) = exp.time_range {
+                ctx.set_exp_time_range(r.min, r.max)?;
+            }
+        }
+        if let Some(adj) = &self.adjust {
+            if let Some(v) = adj.brightness {
+                ctx.set_brightness(v)?;
+            }
+            if let Some(v) = adj.contrast {
+                ctx.set_contrast(v)?;
+            }
+            if let Some(v) = adj.saturation {
+                ctx.set_saturation(v)?;
+            }
+            if let Some(v) = adj.hue {
+                ctx.set_hue(v)?;
+            }
+            if let Some(v) = adj.sharpness {
+                ctx.set_sharpness(v)?;
+            }
+        }
+        if let Some(nr) = &self.noise_reduction {
+            if let Some(mode) = nr.mode {
+                ctx.set_nr_mode(mode)?;
+            }
+            if let Some(v) = nr.strength {
+                ctx.set_anr_strength(v)?;
+            }
+            if let Some(v) = nr.spatial {
+                ctx.set_ms_nr_strength(true, v)?;
+            }
+            if let Some(v) = nr.temporal {
+                ctx.set_mt_nr_strength(true, v)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 读取各项的当前值，返回每一项都已设置的参数，遇到第一个错误即返回。
+    ///
+    /// 与 `CommonSettings::query` 相同，各项依次读取，并非原子快照。
+    pub fn query<C>(ctx: &C) -> XCamResult<Self>
+    where
+        C: AutoWhiteBalance + AutoExposure + AutoColorManagment + NoiseRemoval + Sharpen,
+    {
+        let range = |(min, max)| Some(RangeSettings { min, max });
+        Ok(Self {
+            schema_version: SETTINGS_SCHEMA_VERSION,
+            white_balance: Some(WhiteBalanceSettings {
+                mode: Some(ctx.get_wb_mode()?),
+                ct: Some(ctx.get_mwb_ct()?),
+                gain: Some(ctx.get_mwb_gain()?.into()),
+            }),
+            exposure: Some(ExposureSettings {
+                mode: Some(ctx.get_exp_mode()?),
+                gain_range: range(ctx.get_exp_gain_range()?),
+                time_range: range(ctx.get_exp_time_range()?),
+            }),
+            adjust: Some(AdjustSettings {
+                brightness: Some(ctx.get_brightness()?),
+                contrast: Some(ctx.get_contrast()?),
+                saturation: Some(ctx.get_saturation()?),
+                hue: Some(ctx.get_hue()?),
+                sharpness: Some(ctx.get_sharpness()?),
+            }),
+            noise_reduction: Some(NoiseReductionSettings {
+                mode: Some(ctx.get_nr_mode()?),
+                strength: Some(ctx.get_anr_strength()?),
+                spatial: Some(ctx.get_ms_nr_strength()?.1),
+                temporal: Some(ctx.get_mt_nr_strength()?.1),
+            }),
+        })
+    }
+
+    /// 依次 `prepare`、应用参数、`start`，使出流的第一帧即反映这组参数。
+    ///
+    /// 参数在 `prepare` 之后应用：`prepare` 按 IQ 文件初始化各算法，此前设置的参数可能被覆盖；
+    /// 在 `start` 之前应用则避免出流后先输出几帧 IQ 默认参数的画面。任一步骤失败即返回，
+    /// 应用参数失败时不会出流。
+    pub fn apply_and_start<C>(
+        &self,
+        ctx: &C,
+        width: u32,
+        height: u32,
+        mode: WorkingMode,
+    ) -> XCamResult<PrepareResult>
+    where
+        C: SystemControl
+            + AutoWhiteBalance
+            + AutoExposure
+            + AutoColorManagment
+            + NoiseRemoval
+            + Sharpen,
+    {
+        let result = ctx.prepare(width, height, mode)?;
+        self.apply(ctx)?;
+        ctx.start()?;
+        Ok(result)
+    }
+}
+
+/// 一个描述曝光与白平衡工作模式组合的枚举。
+///
+/// | 组合 | 曝光 | 白平衡 | 典型用途 |
+/// |---|---|---|---|
+/// | `FullAuto` | 自动 | 自动 | 一般拍摄 |
+/// | `ExposureManualWbAuto` | 手动 | 自动 | 商品拍摄：亮度固定，色彩随光源校正 |
+/// | `ExposureAutoWbManual` | 自动 | 手动 | 光源固定而亮度变化的场景，色彩保持一致 |
+/// | `FullManual` | 手动 | 手动 | 标定、测量 |
+///
+/// 切换为手动时沿用之前通过 `set_manual_exp`、`set_mwb_gain` 等接口设置的手动值。
+/// 与 `AutoExposure::set_exposure_priority` 的快门优先、增益优先无关，后者只作用于曝光内部。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AutoModes {
+    #[default]
+    FullAuto,
+    ExposureManualWbAuto,
+    ExposureAutoWbManual,
+    FullManual,
+}
+
+enum_str!(AutoModes, "auto modes" {
+    FullAuto => "full_auto",
+    ExposureManualWbAuto => "exposure_manual_wb_auto",
+    ExposureAutoWbManual => "exposure_auto_wb_manual",
+    FullManual => "full_manual",
+});
+
+impl AutoModes {
+    /// 曝光工作模式。
+    pub fn exp_mode(self) -> OpMode {
+        match self {
+            Self::FullAuto | Self::ExposureAutoWbManual => OpMode::Auto,
+            Self::ExposureManualWbAuto | Self::FullManual => OpMode::Manual,
+        }
+    }
+
+    /// 白平衡工作模式。
+    pub fn wb_mode(self) -> OpMode {
+        match self {
+            Self::FullAuto | Self::ExposureManualWbAuto => OpMode::Auto,
+            Self::ExposureAutoWbManual | Self::FullManual => OpMode::Manual,
+        }
+    }
+
+    /// 由曝光与白平衡的工作模式得到组合，任一模式既非自动也非手动时返回 `None`。
+    pub fn from_modes(exp_mode: OpMode, wb_mode: OpMode) -> Option<Self> {
+        match (exp_mode, wb_mode) {
+            (OpMode::Auto, OpMode::Auto) => Some(Self::FullAuto),
+            (OpMode::Manual, OpMode::Auto) => Some(Self::ExposureManualWbAuto),
+            (OpMode::Auto, OpMode::Manual) => Some(Self::ExposureAutoWbManual),
+            (OpMode::Manual, OpMode::Manual) => Some(Self::FullManual),
+            _ => None,
+        }
+    }
+}
+
+/// 依次设置曝光与白平衡的工作模式，白平衡设置失败时恢复原曝光模式。
+pub(crate) fn set_auto_modes<C>(ctx: &C, modes: AutoModes) -> XCamResult<()>
+where
+    C: AutoExposure + AutoWhiteBalance + ?Sized,
+{
+    let prior = ctx.get_exp_mode()?;
+    ctx.set_exp_mode(modes.exp_mode())?;
+    if let Err(e) = ctx.set_wb_mode(modes.wb_mode()) {
+        let _ = ctx.set_exp_mode(prior);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// 读取曝光与白平衡的工作模式组合，见 `AutoModes::from_modes`。
+pub(crate) fn get_auto_modes<C>(ctx: &C) -> XCamResult<Option<AutoModes>>
+where
+    C: AutoExposure + AutoWhiteBalance + ?Sized,
+{
+    Ok(AutoModes::from_modes(
+        ctx.get_exp_mode()?,
+        ctx.get_wb_mode()?,
+    ))
+}
+
+/// 一个汇总常用参数当前值的类型，用于界面刷新。
+///
+/// 这只是便于一次读取的汇总，并非原子快照：各项依次读取，期间 3A 算法仍在运行，
+/// 自动模式下各项可能来自不同的帧。
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CommonSettings {
+    /// 白平衡工作模式。
+    pub wb_mode: OpMode,
+    /// 手动白平衡增益。
+    pub wb_gain: WbGain,
+    /// 手动色温，单位：K。
+    pub wb_ct: u32,
+    /// 曝光工作模式。
+    pub exp_mode: OpMode,
+    /// 当前曝光结果。
+    pub exposure: ExpInfo,
+    /// 锐化强度。
+    pub sharpness: Strength,
+    /// 降噪工作模式。
+    pub nr_mode: OpMode,
+    /// 降噪强度。
+    pub nr_strength: Strength,
+    /// 空域降噪强度。
+    pub spatial_nr: Strength,
+    /// 时域降噪强度。
+    pub temporal_nr: Strength,
+    /// 饱和度。
+    pub saturation: Strength,
+    /// 亮度等级，范围：[0,255]。
+    pub brightness: u32,
+    /// 对比度等级，范围：[0,255]。
+    pub contrast: u32,
+}
+
+impl CommonSettings {
+    /// 依次读取各项，遇到第一个错误即返回。
+    ///
+    /// 每项一次 SDK 调用，曝光的增益、时间与亮度由一次 `query_exp_info` 读取。
+    pub fn query<C>(ctx: &C) -> XCamResult<Self>
+    where
+        C: AutoWhiteBalance + AutoExposure + AutoColorManagment + NoiseRemoval + Sharpen,
+    {
+        Ok(Self {
+            wb_mode: ctx.get_wb_mode()?,
+            wb_gain: ctx.get_mwb_gain()?,
+            wb_ct: ctx.get_mwb_ct()?,
+            exp_mode: ctx.get_exp_mode()?,
+            exposure: ctx.query_exp_info()?,
+            sharpness: ctx.get_sharpness()?,
+            nr_mode: ctx.get_nr_mode()?,
+            nr_strength: ctx.get_anr_strength()?,
+            spatial_nr: ctx.get_ms_nr_strength()?.1,
+            temporal_nr: ctx.get_mt_nr_strength()?.1,
+            saturation: ctx.get_saturation()?,
+            brightness: ctx.get_brightness()?,
+            contrast: ctx.get_contrast()?,
+        })
+    }
+}
+
+impl Context {
+    /// 一次读取常用参数的当前值，见 `CommonSettings::query`。
+    pub fn get_common_settings(&self) -> XCamResult<CommonSettings> {
+        CommonSettings::query(self)
+    }
+
+    /// 一次设置曝光与白平衡的工作模式组合，见 `AutoModes`。
+    ///
+    /// 先设置曝光模式，白平衡模式设置失败时恢复原曝光模式并返回该错误。
+    pub fn set_auto_modes(&self, modes: AutoModes) -> XCamResult<()> {
+        set_auto_modes(self, modes)
+    }
+
+    /// 获取曝光与白平衡的工作模式组合，任一模式既非自动也非手动时返回 `None`。
+    pub fn get_auto_modes(&self) -> XCamResult<Option<AutoModes>> {
+        get_auto_modes(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adjust::CprocLevels;
+    use crate::error::XCamError;
+    use crate::mock::MockContext;
+
+    #[test]
+    fn test_apply_and_start() {
+        let ctx = MockContext::new();
+        let settings = CameraSettings {
+            white_balance: Some(WhiteBalanceSettings {
+                mode: Some(OpMode::Manual),
+                ct: Some(4000),
+                gain: None,
+            }),
+            adjust: Some(AdjustSettings {
+                brightness: Some(120),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mode = WorkingMode::Normal;
+        let result = settings.apply_and_start(&ctx, 1920, 1080, mode).unwrap();
+        assert_eq!((result.width, result.height), (1920, 1080));
+        assert_eq!(
+            ctx.calls(),
+            [
+                "prepare",
+                "set_wb_mode",
+                "set_mwb_ct",
+                "set_brightness",
+                "start"
+            ]
+        );
+        assert_eq!(ctx.state.borrow().wb_ct, 4000);
+        assert!(ctx.is_streaming());
+
+        // 应用失败时不出流。
+        let ctx = MockContext::new();
+        ctx.fail("set_mwb_ct");
+        assert!(settings.apply_and_start(&ctx, 1920, 1080, mode).is_err());
+        assert!(!ctx.calls().contains(&"start"));
+    }
+
+    #[test]
+    fn test_auto_modes() {
+        let ctx = MockContext::new();
+        for (modes, exp, wb) in [
+            (
+                AutoModes::ExposureManualWbAuto,
+                OpMode::Manual,
+                OpMode::Auto,
+            ),
+            (
+                AutoModes::ExposureAutoWbManual,
+                OpMode::Auto,
+                OpMode::Manual,
+            ),
+            (AutoModes::FullManual, OpMode::Manual, OpMode::Manual),
+            (AutoModes::FullAuto, OpMode::Auto, OpMode::Auto),
+        ] {
+            assert_eq!(set_auto_modes(&ctx, modes), Ok(()));
+            assert_eq!(ctx.state.borrow().exp_mode, exp);
+            assert_eq!(ctx.state.borrow().wb_mode, wb);
+            assert_eq!(get_auto_modes(&ctx), Ok(Some(modes)));
+        }
+
+        // 白平衡设置失败时恢复曝光模式。
+        ctx.fail("set_wb_mode");
+        assert!(set_auto_modes(&ctx, AutoModes::FullManual).is_err());
+        assert_eq!(ctx.state.borrow().exp_mode, OpMode::Auto);
+        assert_eq!(AutoModes::from_modes(OpMode::SemiAuto, OpMode::Auto), None);
+    }
+
+    #[test]
+    fn test_common_settings() {
+        let ctx = MockContext::new();
+        let strength = |v| Strength::new(v).unwrap();
+        let wb_gain = WbGain {
+            rgain: 1.5,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 2.0,
+        };
+        {
+            let mut st = ctx.state.borrow_mut();
+            st.wb_mode = OpMode::Manual;
+            st.wb_gain = wb_gain;
+            st.wb_ct = 4200;
+            st.exp_mode = OpMode::Manual;
+            st.exp_info.gain = 2.0;
+            st.exp_info.time = 0.02;
+            st.sharpness = strength(70);
+            st.nr_mode = OpMode::Manual;
+            st.anr_strength = strength(30);
+            st.cproc = CprocLevels {
+                brightness: 110,
+                contrast: 140,
+            };
+        }
+        let settings = CommonSettings::query(&ctx).unwrap();
+        assert_eq!(settings.wb_mode, OpMode::Manual);
+        assert_eq!(settings.wb_gain, wb_gain);
+        assert_eq!(settings.wb_ct, 4200);
+        assert_eq!(settings.exp_mode, OpMode::Manual);
+        assert_eq!(
+            (settings.exposure.gain, settings.exposure.time),
+            (2.0, 0.02)
+        );
+        assert_eq!(settings.sharpness, strength(70));
+        assert_eq!(settings.nr_mode, OpMode::Manual);
+        assert_eq!(settings.nr_strength, strength(30));
+        assert_eq!(settings.spatial_nr, strength(30));
+        assert_eq!(settings.temporal_nr, strength(30));
+        assert_eq!(settings.saturation, strength(50));
+        assert_eq!(settings.brightness, 110);
+        assert_eq!(settings.contrast, 140);
+        assert_eq!(ctx.calls().len(), 13);
+
+        ctx.fail("get_sharpness");
+        assert_eq!(
+            CommonSettings::query(&ctx),
+            Err(XCamError::from(
+                crate::ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED
+            ))
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! 参数生效等待
+//!
+//! 大部分 ISP 参数在设置后需要 2 至 4 帧才反映到输出图像上：新参数在下一帧的
+//! 配置周期写入 ISP，传感器曝光寄存器还有额外的 1 至 2 帧延迟。设置后立即抓图
+//! 往往得到旧参数下的图像，`Context::set_and_settle` 在设置后按帧周期等待指定帧数。
+//!
+//! ```ignore
+//! let frames = default_settle_frames(ModuleId::RK_MODULE_AE);
+//! ctx.set_and_settle(frames, |ctx| ctx.set_manual_exp(4.0, 0.01))?;
+//! ```
+use super::context::Context;
+use super::error::XCamError;
+use super::misc::Miscellaneous;
+use super::types::{ModuleId, XCamResult};
+use std::thread;
+use std::time::Duration;
+
+/// 未列出的模块默认等待的帧数。
+pub const DEFAULT_SETTLE_FRAMES: u32 = 2;
+
+/// 返回模块参数从设置到反映在输出图像上通常需要的帧数。
+///
+/// 曝光需经传感器寄存器生效，比 ISP 内部模块多一帧；时域降噪需要数帧累积。
+pub fn default_settle_frames(id: ModuleId) -> u32 {
+    match id {
+        ModuleId::RK_MODULE_AE => 3,
+        ModuleId::RK_MODULE_TNR => 4,
+        _ => DEFAULT_SETTLE_FRAMES,
+    }
+}
+
+/// 由帧率计算帧周期，帧率为 0 时返回 `None`。
+pub fn frame_period_from_fps(fps: u32) -> Option<Duration> {
+    match fps {
+        0 => None,
+        fps => Some(Duration::from_secs(1) / fps),
+    }
+}
+
+/// 计算等待 `frames` 帧所需的时间。
+pub fn settle_duration(period: Duration, frames: u32) -> Duration {
+    period.saturating_mul(frames)
+}
+
+impl Context {
+    /// 获取当前的帧周期。
+    ///
+    /// 由 `get_frame_rate` 报告的帧率计算，SDK 未报告有效帧率时返回 `XCamError::Unsupported`。
+    pub fn frame_period(&self) -> XCamResult<Duration> {
+        let info = self.get_frame_rate()?;
+        frame_period_from_fps(info.fps).ok_or(XCamError::Unsupported)
+    }
+
+    /// 执行设置 `f`，然后等待 `frames` 个帧周期再返回。
+    ///
+    /// 帧周期在 `f` 返回后获取，因此 `f` 修改帧率时按新帧率等待。
+    /// `f` 失败时不等待；帧周期获取失败时返回错误，此时 `f` 的设置已生效。
+    ///
+    /// # Parameters
+    /// * `frames` - 等待的帧数，可参考 `default_settle_frames`。
+    /// * `f` - 执行设置的闭包。
+    pub fn set_and_settle<F>(&self, frames: u32, f: F) -> XCamResult<()>
+    where
+        F: FnOnce(&Context) -> XCamResult<()>,
+    {
+        f(self)?;
+        let period = self.frame_period()?;
+        thread::sleep(settle_duration(period, frames));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settle_duration() {
+        assert_eq!(frame_period_from_fps(0), None);
+        assert_eq!(frame_period_from_fps(25), Some(Duration::from_millis(40)));
+        let period = frame_period_from_fps(30).unwrap();
+        assert_eq!(period, Duration::from_nanos(33_333_333));
+        assert_eq!(settle_duration(period, 3), Duration::from_nanos(99_999_999));
+        assert_eq!(settle_duration(period, 0), Duration::ZERO);
+        assert_eq!(settle_duration(Duration::MAX, 2), Duration::MAX);
+        assert_eq!(default_settle_frames(ModuleId::RK_MODULE_AE), 3);
+        assert_eq!(
+            default_settle_frames(ModuleId::RK_MODULE_SHARP),
+            DEFAULT_SETTLE_FRAMES
+        );
+    }
+}
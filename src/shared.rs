@@ -0,0 +1,195 @@
+//! 共享上下文
+//!
+//! `SharedContext` 是可克隆的上下文句柄，供多个线程或子系统同时控制同一个摄像头。
+//! 最后一个句柄释放时才反初始化 SDK 上下文。
+//!
+//! 各控制接口的转发实现位于对应模块中，与 `Context` 的实现相邻。
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::sysctl::{IspErrorFlags, PrepareResult, SystemControl};
+use super::types::{
+    AlgoContext, AlgoDescComm, CpslCap, CpslCfg, CpslInfo, IspStats, ModuleId, Rect, WorkingMode,
+    XCamResult,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 一个可克隆的共享上下文句柄。
+///
+/// 实现与 `Context` 相同的控制接口，调用均转发到共享的上下文；不提供取得内部
+/// `Context` 引用的途径。
+///
+/// 停止出流会影响其它句柄的持有者，因此 `SharedContext` 的 `stop` 总是返回
+/// `XCAM_RETURN_ERROR_ORDER`，须先通过 `try_close` 取回独占的 `Context`。
+#[derive(Clone)]
+pub struct SharedContext {
+    pub(crate) inner: Arc<Context>,
+}
+
+impl Context {
+    /// 转换为可克隆的共享句柄。
+    pub fn into_shared(self) -> SharedContext {
+        SharedContext {
+            inner: Arc::new(self),
+        }
+    }
+}
+
+impl SharedContext {
+    /// 返回当前存活的句柄数量。
+    pub fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.inner)
+    }
+
+    /// 取回独占的 `Context`，以便调用 `stop` 或释放上下文。
+    ///
+    /// 仍有其它句柄存活时失败，并原样返回本句柄。
+    pub fn try_close(self) -> Result<Context, Self> {
+        Arc::try_unwrap(self.inner).map_err(|inner| Self { inner })
+    }
+}
+
+impl SystemControl for SharedContext {
+    fn prepare(&self, width: u32, height: u32, mode: WorkingMode) -> XCamResult<PrepareResult> {
+        self.inner.prepare(width, height, mode)
+    }
+
+    fn start(&self) -> XCamResult<()> {
+        self.inner.start()
+    }
+
+    /// 共享句柄不能停止出流，总是返回 `XCAM_RETURN_ERROR_ORDER`，请使用 `try_close`。
+    fn stop(&self, _keep_ext_hw_st: bool) -> XCamResult<()> {
+        Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))
+    }
+
+    fn is_streaming(&self) -> bool {
+        self.inner.is_streaming()
+    }
+
+    fn enable_module<T: Into<ModuleId>>(&self, id: T) -> XCamResult<()> {
+        self.inner.enable_module(id)
+    }
+
+    fn disable_module<T: Into<ModuleId>>(&self, id: T) -> XCamResult<()> {
+        self.inner.disable_module(id)
+    }
+
+    fn is_module_enabled<T: Into<ModuleId>>(&self, id: T) -> bool {
+        self.inner.is_module_enabled(id)
+    }
+
+    fn register_lib(&self, algo_lib_des: AlgoDescComm) -> XCamResult<()> {
+        self.inner.register_lib(algo_lib_des)
+    }
+
+    fn unregister_lib(&self, algo_type: i32, lib_id: i32) -> XCamResult<()> {
+        self.inner.unregister_lib(algo_type, lib_id)
+    }
+
+    fn enable_ax_lib(&self, algo_type: i32, lib_id: i32) -> XCamResult<()> {
+        self.inner.enable_ax_lib(algo_type, lib_id)
+    }
+
+    fn disable_ax_lib(&self, algo_type: i32, lib_id: i32) -> XCamResult<()> {
+        self.inner.disable_ax_lib(algo_type, lib_id)
+    }
+
+    fn is_ax_lib_enabled(&self, algo_type: i32, lib_id: i32) -> bool {
+        self.inner.is_ax_lib_enabled(algo_type, lib_id)
+    }
+
+    unsafe fn get_enabled_ax_lib_ctx(&self, algo_type: i32) -> *const AlgoContext {
+        self.inner.get_enabled_ax_lib_ctx(algo_type)
+    }
+
+    fn get_cps_lt_info(&self) -> XCamResult<CpslInfo> {
+        self.inner.get_cps_lt_info()
+    }
+
+    fn query_cps_lt_cap(&self) -> XCamResult<CpslCap> {
+        self.inner.query_cps_lt_cap()
+    }
+
+    fn set_cps_lt_cfg<T: Into<CpslCfg>>(&self, cfg: T) -> XCamResult<()> {
+        self.inner.set_cps_lt_cfg(cfg)
+    }
+
+    fn update_iq<T: Into<Vec<u8>>>(&self, iq_file: T) -> XCamResult<()> {
+        self.inner.update_iq(iq_file)
+    }
+
+    fn get_crop(&self) -> XCamResult<Rect> {
+        self.inner.get_crop()
+    }
+
+    fn set_crop(&self, crop: Rect) -> XCamResult<()> {
+        self.inner.set_crop(crop)
+    }
+
+    fn get_3a_stats(&self, timeout: Duration) -> XCamResult<IspStats> {
+        self.inner.get_3a_stats(timeout)
+    }
+
+    fn get_isp_error_flags(&self) -> XCamResult<IspErrorFlags> {
+        self.inner.get_isp_error_flags()
+    }
+
+    fn clear_isp_error_flags(&self) -> XCamResult<()> {
+        self.inner.clear_isp_error_flags()
+    }
+
+    fn get_frame_id(&self) -> Option<u32> {
+        self.inner.get_frame_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ae::AutoExposure;
+    use std::thread;
+
+    fn exp_time<C: AutoExposure>(ctx: &C) -> XCamResult<f32> {
+        ctx.query_exp_info().map(|info| info.time)
+    }
+
+    #[test]
+    fn test_shared_context_threads() {
+        let r = Context::new("m00_b_ov5695 4-0036-1", "/etc/iqfiles");
+        assert_eq!(r.is_ok(), true);
+        if let Ok(ctx) = r {
+            let shared = ctx.into_shared();
+            assert!(shared.prepare(2592, 1944, WorkingMode::Normal).is_ok());
+            assert_eq!(shared.start(), Ok(()));
+            let handles: Vec<_> = (0..3)
+                .map(|_| {
+                    let handle = shared.clone();
+                    thread::spawn(move || {
+                        assert!(handle.query_exp_info().is_ok());
+                        assert!(exp_time(&handle).is_ok());
+                        handle.stop(false)
+                    })
+                })
+                .collect();
+            for h in handles {
+                assert_eq!(
+                    h.join().unwrap(),
+                    Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))
+                );
+            }
+            assert_eq!(shared.handle_count(), 1);
+            let other = shared.clone();
+            let shared = match shared.try_close() {
+                Ok(_) => panic!("closed while another handle is alive"),
+                Err(shared) => shared,
+            };
+            drop(other);
+            match shared.try_close() {
+                Ok(ctx) => assert_eq!(ctx.stop(false), Ok(())),
+                Err(_) => panic!("last handle failed to close"),
+            }
+        }
+    }
+}
@@ -1,32 +1,174 @@
 //! 图像锐化
 //!
 //! Sharpen 模块用于增强图像的清晰度，包括调节图像边缘的锐化属性和增强图像的细节和纹理。
+use super::attrib::with_fields;
 use super::context::Context;
+use super::convert;
 use super::error::XCamError;
 use super::ffi;
-use super::types::XCamResult;
+use super::shared::SharedContext;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::types::SharpInfo;
+use super::types::{OpMode, Strength, XCamResult};
+use super::uapi::uapi_call;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::uapi::uapi_get;
+
+/// 一个代表锐化属性的类型。
+///
+/// 通过 `with_*` 方法修改手动参数的个别字段，其余字段保持原值。
+#[derive(Copy, Clone, Default)]
+pub struct SharpAttrib(pub ffi::rk_aiq_sharp_attrib_v3_t);
+
+with_fields!(SharpAttrib {
+    /// 设置工作模式。
+    with_mode: ffi::Asharp3_OPMode_t => eMode;
+    /// 设置手动锐化开关。
+    with_enable: bool => stManual.stSelect.enable;
+    /// 设置手动预滤波增益。
+    with_pbf_gain: f32 => stManual.stSelect.pbf_gain;
+    /// 设置手动预滤波比例。
+    with_pbf_ratio: f32 => stManual.stSelect.pbf_ratio;
+    /// 设置手动高斯滤波比例。
+    with_gaus_ratio: f32 => stManual.stSelect.gaus_ratio;
+    /// 设置手动锐化比例。
+    with_sharp_ratio: f32 => stManual.stSelect.sharp_ratio;
+    /// 设置手动双边滤波增益。
+    with_bf_gain: f32 => stManual.stSelect.bf_gain;
+    /// 设置手动双边滤波比例。
+    with_bf_ratio: f32 => stManual.stSelect.bf_ratio;
+});
+
+impl From<ffi::rk_aiq_sharp_attrib_v3_t> for SharpAttrib {
+    fn from(val: ffi::rk_aiq_sharp_attrib_v3_t) -> Self {
+        Self(val)
+    }
+}
+
+impl From<SharpAttrib> for ffi::rk_aiq_sharp_attrib_v3_t {
+    fn from(val: SharpAttrib) -> Self {
+        val.0
+    }
+}
 
 pub trait Sharpen {
-    fn get_sharpness(&self) -> XCamResult<u32>;
-    fn set_sharpness(&self, mode: u32) -> XCamResult<()>;
+    fn get_sharpness(&self) -> XCamResult<Strength>;
+    fn set_sharpness(&self, level: Strength) -> XCamResult<()>;
+
+    /// 获取锐化属性。
+    ///
+    /// # Note
+    ///
+    /// 锐化算法处理一帧期间调用会阻塞，最长约一个帧周期。
+    fn get_sharp_attrib(&self) -> XCamResult<SharpAttrib>;
+
+    /// 设置锐化属性。
+    fn set_sharp_attrib<T: Into<SharpAttrib>>(&self, attr: T) -> XCamResult<()>;
+
+    /// 获取锐化的工作模式，取自锐化属性。
+    fn get_sharp_mode(&self) -> XCamResult<OpMode> {
+        self.get_sharp_attrib()
+            .map(|attr| sharp_op_mode(attr.0.eMode))
+    }
+
+    /// 获取自动锐化当前实际生效的锐化等级，范围：[0,100]，与 `get_sharpness` 同一刻度。
+    ///
+    /// 自动模式下算法按当前 ISO 在标定表中插值，`set_sharpness` 设置的是作用于插值结果的
+    /// 缩放，`get_sharpness` 读回的也只是该设置值；本接口读取算法状态，返回最终生效的等级。
+    ///
+    /// 仅 v4_0、v5_0 支持，其余版本返回 `XCamError::Unsupported`。
+    fn get_effective_sharpness(&self) -> XCamResult<u8> {
+        Err(XCamError::Unsupported)
+    }
+}
+
+/// 将锐化算法的工作模式转换为 `OpMode`，无法对应的值转换为 `OpMode::Invalid`。
+fn sharp_op_mode(mode: ffi::Asharp3_OPMode_t) -> OpMode {
+    use ffi::Asharp3_OPMode_t::*;
+    match mode {
+        ASHARP3_OP_MODE_AUTO => OpMode::Auto,
+        ASHARP3_OP_MODE_MANUAL => OpMode::Manual,
+        v => {
+            convert::unmapped("Asharp3_OPMode_t", v);
+            OpMode::Invalid
+        }
+    }
 }
 
 impl Sharpen for Context {
-    fn get_sharpness(&self) -> XCamResult<u32> {
+    fn get_sharpness(&self) -> XCamResult<Strength> {
         let mut level: u32 = 0;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_getSharpness(
-                self.internal.as_ptr(),
-                &mut level,
-            ))
-            .ok()
-            .map(|_| level)
-        }
+        uapi_call!(ffi::rk_aiq_uapi2_getSharpness(self, &mut level))
+            .map(|_| Strength::saturating(level))
     }
 
-    fn set_sharpness(&self, level: u32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_setSharpness(self.internal.as_ptr(), level)).ok()
-        }
+    fn set_sharpness(&self, level: Strength) -> XCamResult<()> {
+        uapi_call!(ffi::rk_aiq_uapi2_setSharpness(self, level.into()))
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_effective_sharpness(&self) -> XCamResult<u8> {
+        uapi_get!(ffi::rk_aiq_user_api2_asharpV3_GetInfo(self) -> SharpInfo)
+            .map(|info| Strength::from_ratio(info.fStrength).get())
+    }
+
+    fn get_sharp_attrib(&self) -> XCamResult<SharpAttrib> {
+        let mut attr = ffi::rk_aiq_sharp_attrib_v3_t::default();
+        uapi_call!(ffi::rk_aiq_user_api2_asharpV3_GetAttrib(self, &mut attr)).map(|_| attr.into())
+    }
+
+    fn set_sharp_attrib<T: Into<SharpAttrib>>(&self, attr: T) -> XCamResult<()> {
+        let mut attr: ffi::rk_aiq_sharp_attrib_v3_t = attr.into().into();
+        uapi_call!(ffi::rk_aiq_user_api2_asharpV3_SetAttrib(self, &mut attr))
+    }
+}
+
+impl Sharpen for SharedContext {
+    fn get_sharpness(&self) -> XCamResult<Strength> {
+        self.inner.get_sharpness()
+    }
+
+    fn set_sharpness(&self, level: Strength) -> XCamResult<()> {
+        self.inner.set_sharpness(level)
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    fn get_effective_sharpness(&self) -> XCamResult<u8> {
+        self.inner.get_effective_sharpness()
+    }
+
+    fn get_sharp_attrib(&self) -> XCamResult<SharpAttrib> {
+        self.inner.get_sharp_attrib()
+    }
+
+    fn set_sharp_attrib<T: Into<SharpAttrib>>(&self, attr: T) -> XCamResult<()> {
+        self.inner.set_sharp_attrib(attr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sharp_attrib_builder_keeps_fields() {
+        let mut raw = ffi::rk_aiq_sharp_attrib_v3_t::default();
+        raw.stManual.stSelect.enable = true;
+        raw.stManual.stSelect.pbf_gain = 0.5;
+        raw.stManual.stSelect.bf_ratio = 0.25;
+        let attr = SharpAttrib::from(raw).with_sharp_ratio(4.0);
+        assert_eq!(attr.0.stManual.stSelect.sharp_ratio, 4.0);
+        assert!(attr.0.stManual.stSelect.enable);
+        assert_eq!(attr.0.stManual.stSelect.pbf_gain, 0.5);
+        assert_eq!(attr.0.stManual.stSelect.bf_ratio, 0.25);
+    }
+
+    #[test]
+    fn test_effective_sharpness_differs_from_setpoint() {
+        let ctx = crate::mock::MockContext::new();
+        ctx.set_sharpness(Strength::new(30).unwrap()).unwrap();
+        ctx.state.borrow_mut().sharp_status = 64;
+        assert_eq!(ctx.get_sharpness(), Ok(Strength::new(30).unwrap()));
+        assert_eq!(ctx.get_effective_sharpness(), Ok(64));
     }
 }
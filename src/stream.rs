@@ -0,0 +1,225 @@
+//! 异步统计流
+//!
+//! `Context::stats_stream` 以 `Stream` 的形式逐帧提供 3A 统计，供 async 代码消费。
+//! 仅在启用 `tokio` 特性时编译。
+//!
+//! SDK 没有统计回调（元数据回调只携带帧号），统计由独立线程以 `get_3a_stats` 阻塞读取，
+//! 每次最多等待 `STATS_STREAM_POLL`，再送入容量为 `STATS_STREAM_CAPACITY` 的有界通道。
+//!
+//! # 背压
+//!
+//! 通道已满时读取线程停止读取统计，直到消费者取走一帧或流被释放：SDK 的统计队列很短，
+//! 满载时继续读取只会白白消耗统计帧，并拖慢同一上下文上其他读取统计的调用。
+//! 通道溢出时丢弃最早的一帧，使通道中保留最新的统计。消费者慢于帧率时，流中的帧号会跳跃，
+//! 可据 `frame_id` 发现。
+//!
+//! # 运行时
+//!
+//! 读取线程是普通的系统线程，不占用 tokio 的工作线程，流本身可在任意 tokio 运行时
+//! （包括单线程运行时）中消费。流被释放后读取线程在下一次读取返回时退出；上下文释放后
+//! 流随之结束。同一上下文同时存在多个统计流或其他读取统计的调用时，每帧统计只会交给其中之一。
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::sysctl::SystemControl;
+use super::types::{IspStats, XCamResult};
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context as TaskContext, Poll, Waker};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tokio_stream::Stream;
+
+/// 一帧 3A 统计。
+pub type Stats3A = IspStats;
+
+/// 统计流通道的容量，单位：帧。
+pub const STATS_STREAM_CAPACITY: usize = 4;
+
+/// 读取线程每次等待统计的最长时间，也是流被释放后读取线程退出的最长延迟。
+pub const STATS_STREAM_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+struct Channel {
+    frames: VecDeque<Stats3A>,
+    waker: Option<Waker>,
+    /// 流已被释放。
+    closed: bool,
+    /// 读取线程已退出。
+    done: bool,
+}
+
+impl Channel {
+    fn is_full(&self) -> bool {
+        self.frames.len() >= STATS_STREAM_CAPACITY
+    }
+
+    /// 送入一帧，已满时丢弃最早的一帧。
+    fn push(&mut self, stats: Stats3A) {
+        if self.is_full() {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(stats);
+        self.wake();
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    channel: Mutex<Channel>,
+    /// 流取走一帧或被释放时通知读取线程。
+    space: Condvar,
+}
+
+/// `spawn_stats_stream` 返回的统计流，释放后读取线程退出。
+pub(crate) struct StatsStream {
+    shared: Arc<Shared>,
+}
+
+impl Stream for StatsStream {
+    type Item = Stats3A;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Stats3A>> {
+        let mut ch = self.shared.channel.lock().unwrap();
+        if let Some(stats) = ch.frames.pop_front() {
+            self.shared.space.notify_one();
+            return Poll::Ready(Some(stats));
+        }
+        if ch.done {
+            return Poll::Ready(None);
+        }
+        ch.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for StatsStream {
+    fn drop(&mut self) {
+        self.shared.channel.lock().unwrap().closed = true;
+        self.shared.space.notify_one();
+    }
+}
+
+/// 启动读取线程，逐次调用 `next` 并将统计送入通道，返回流与线程句柄。
+///
+/// 通道已满时不调用 `next`。`next` 返回 `None` 时流结束；返回错误时跳过该次读取，
+/// 非超时错误后等待 `STATS_STREAM_POLL` 再重试，避免空转。
+pub(crate) fn spawn_stats_stream<F>(mut next: F) -> XCamResult<(StatsStream, JoinHandle<()>)>
+where
+    F: FnMut() -> Option<XCamResult<Stats3A>> + Send + 'static,
+{
+    let shared = Arc::new(Shared::default());
+    let handle = {
+        let shared = shared.clone();
+        thread::Builder::new()
+            .name("rkaiq-stats-stream".into())
+            .spawn(move || {
+                loop {
+                    let ch = shared.channel.lock().unwrap();
+                    let ch = shared
+                        .space
+                        .wait_while(ch, |ch| ch.is_full() && !ch.closed)
+                        .unwrap();
+                    if ch.closed {
+                        break;
+                    }
+                    drop(ch);
+                    match next() {
+                        None => break,
+                        Some(Ok(stats)) => {
+                            let mut ch = shared.channel.lock().unwrap();
+                            if ch.closed {
+                                break;
+                            }
+                            ch.push(stats);
+                        }
+                        Some(Err(XCamError::Timeout)) => {}
+                        Some(Err(_)) => thread::sleep(STATS_STREAM_POLL),
+                    }
+                }
+                let mut ch = shared.channel.lock().unwrap();
+                ch.done = true;
+                ch.wake();
+            })
+            .map_err(|_| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_THREAD))?
+    };
+    Ok((StatsStream { shared }, handle))
+}
+
+impl Context {
+    /// 以异步流的形式逐帧获取 3A 统计，背压与运行时要求见模块说明。
+    ///
+    /// 读取线程只持有上下文的弱引用；创建线程失败时返回 `XCAM_RETURN_ERROR_THREAD`。
+    pub fn stats_stream(self: &Arc<Self>) -> XCamResult<impl Stream<Item = Stats3A>> {
+        let ctx = Arc::downgrade(self);
+        let (stream, _) = spawn_stats_stream(move || {
+            let ctx = ctx.upgrade()?;
+            Some(ctx.get_3a_stats(STATS_STREAM_POLL))
+        })?;
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+
+    fn stats(frame_id: u32) -> Stats3A {
+        let mut stats = Stats3A::default();
+        stats.frame_id = frame_id;
+        stats
+    }
+
+    #[tokio::test]
+    async fn test_stats_stream_order() {
+        let mut events = vec![
+            Ok(stats(1)),
+            Err(XCamError::Timeout),
+            Ok(stats(2)),
+            Ok(stats(3)),
+        ]
+        .into_iter();
+        let (stream, _) = spawn_stats_stream(move || events.next()).unwrap();
+        let ids: Vec<u32> = stream.map(|s| s.frame_id).collect().await;
+        assert_eq!(ids, [1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_stats_stream_full() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        // 消费者尚未读取时通道写满，读取线程停止读取，直到流被释放。
+        let polled = Arc::new(AtomicU32::new(0));
+        let (stream, handle) = {
+            let polled = polled.clone();
+            spawn_stats_stream(move || {
+                let id = polled.fetch_add(1, Ordering::SeqCst) + 1;
+                Some(Ok(stats(id)))
+            })
+            .unwrap()
+        };
+        while polled.load(Ordering::SeqCst) < STATS_STREAM_CAPACITY as u32 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(stream);
+        handle.join().unwrap();
+        assert_eq!(polled.load(Ordering::SeqCst), STATS_STREAM_CAPACITY as u32);
+    }
+
+    #[test]
+    fn test_channel_drops_oldest() {
+        let mut ch = Channel::default();
+        for id in 1..=6 {
+            ch.push(stats(id));
+        }
+        let ids: Vec<u32> = ch.frames.iter().map(|s| s.frame_id).collect();
+        assert_eq!(ids, [3, 4, 5, 6]);
+    }
+}
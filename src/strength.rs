@@ -0,0 +1,166 @@
+//! 批量设置强度
+//!
+//! 调优参数常以“模块 → 强度”的表格维护，`Context::apply_strengths` 一次应用整张表，
+//! 每一项分派到对应模块的设置接口：
+//!
+//! | 模块 | 设置接口 |
+//! |---|---|
+//! | `Block::Nr` | `NoiseRemoval::set_anr_strength`，整体降噪强度 |
+//! | `Block::SpatialNr` | `NoiseRemoval::set_ms_nr_strength`，同时打开空域降噪 |
+//! | `Block::TemporalNr` | `NoiseRemoval::set_mt_nr_strength`，同时打开时域降噪 |
+//! | `Block::Sharpen` | `Sharpen::set_sharpness` |
+//! | `Block::Dehaze` | `Defog::set_dhz_strength`，仅 v3_0 及以上支持 |
+//! | `Block::Saturation` | `AutoColorManagment::set_saturation` |
+//!
+//! RAW 域降噪没有单独的强度接口，由 `Block::Nr` 一并调节。亮度、对比度等取值不是
+//! `Strength` 的控制量不在此列。
+use super::acm::AutoColorManagment;
+use super::context::Context;
+use super::defog::Defog;
+use super::display::enum_str;
+use super::error::XCamError;
+use super::nr::NoiseRemoval;
+use super::sharpen::Sharpen;
+use super::types::{Strength, XCamResult};
+
+/// 一个描述可按强度调节的模块的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Block {
+    /// 整体降噪。
+    Nr,
+    /// 空域降噪。
+    SpatialNr,
+    /// 时域降噪。
+    TemporalNr,
+    /// 锐化。
+    Sharpen,
+    /// 去雾。
+    Dehaze,
+    /// 饱和度。
+    Saturation,
+}
+
+enum_str!(Block, "block" {
+    Nr => "nr",
+    SpatialNr => "spatial_nr",
+    TemporalNr => "temporal_nr",
+    Sharpen => "sharpen",
+    Dehaze => "dehaze",
+    Saturation => "saturation",
+});
+
+impl Block {
+    /// 全部模块。
+    pub const ALL: [Block; 6] = [
+        Block::Nr,
+        Block::SpatialNr,
+        Block::TemporalNr,
+        Block::Sharpen,
+        Block::Dehaze,
+        Block::Saturation,
+    ];
+}
+
+/// 设置 `block` 的强度。
+fn set_block_strength<C>(ctx: &C, block: Block, strength: Strength) -> XCamResult<()>
+where
+    C: AutoColorManagment + Defog + NoiseRemoval + Sharpen,
+{
+    match block {
+        Block::Nr => ctx.set_anr_strength(strength),
+        Block::SpatialNr => ctx.set_ms_nr_strength(true, strength),
+        Block::TemporalNr => ctx.set_mt_nr_strength(true, strength),
+        Block::Sharpen => ctx.set_sharpness(strength),
+        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+        Block::Dehaze => ctx.set_dhz_strength(strength),
+        #[cfg(not(any(feature = "v3_0", feature = "v4_0", feature = "v5_0")))]
+        Block::Dehaze => Err(XCamError::Unsupported),
+        Block::Saturation => ctx.set_saturation(strength),
+    }
+}
+
+/// 依次设置 `map` 中的每一项，返回失败的 `(模块, 错误)` 列表。
+///
+/// 某项失败不影响其余各项；同一模块出现多次时按顺序设置，以最后一项为准。
+pub(crate) fn apply_strengths<C>(ctx: &C, map: &[(Block, Strength)]) -> Vec<(Block, XCamError)>
+where
+    C: AutoColorManagment + Defog + NoiseRemoval + Sharpen,
+{
+    map.iter()
+        .filter_map(|&(block, strength)| {
+            set_block_strength(ctx, block, strength)
+                .err()
+                .map(|e| (block, e))
+        })
+        .collect()
+}
+
+impl Context {
+    /// 一次设置多个模块的强度，分派规则见模块说明。
+    ///
+    /// 尽力设置每一项，返回失败的 `(模块, 错误)` 列表，全部成功时为空。
+    pub fn apply_strengths(&self, map: &[(Block, Strength)]) -> Vec<(Block, XCamError)> {
+        apply_strengths(self, map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi;
+    use crate::mock::MockContext;
+
+    fn strength(val: u8) -> Strength {
+        Strength::new(val).unwrap()
+    }
+
+    #[test]
+    fn test_apply_strengths_dispatch() {
+        let ctx = MockContext::new();
+        let map: Vec<_> = Block::ALL.iter().map(|&b| (b, strength(30))).collect();
+        let failures = apply_strengths(&ctx, &map);
+
+        let mut expected = vec![
+            "set_anr_strength",
+            "set_ms_nr_strength",
+            "set_mt_nr_strength",
+            "set_sharpness",
+        ];
+        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+        expected.extend(["get_dhz_attrib", "set_dhz_attrib"]);
+        expected.push("set_saturation");
+        assert_eq!(ctx.calls(), expected);
+
+        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+        {
+            assert!(failures.is_empty());
+            assert_eq!(ctx.state.borrow().dhz_attrib.0.stDehazeManu.level, 30);
+        }
+        #[cfg(not(any(feature = "v3_0", feature = "v4_0", feature = "v5_0")))]
+        assert_eq!(failures, [(Block::Dehaze, XCamError::Unsupported)]);
+        assert_eq!(ctx.state.borrow().anr_strength, strength(30));
+        assert_eq!(ctx.state.borrow().sharpness, strength(30));
+    }
+
+    #[test]
+    fn test_apply_strengths_best_effort() {
+        let ctx = MockContext::new();
+        ctx.fail("set_sharpness");
+        let failures = apply_strengths(
+            &ctx,
+            &[
+                (Block::Sharpen, strength(80)),
+                (Block::Nr, strength(20)),
+                (Block::Nr, strength(40)),
+            ],
+        );
+        assert_eq!(
+            failures,
+            [(
+                Block::Sharpen,
+                XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED)
+            )]
+        );
+        assert_eq!(ctx.state.borrow().anr_strength, strength(40));
+    }
+}
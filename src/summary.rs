@@ -0,0 +1,180 @@
+//! 模式汇总
+//!
+//! 一次查询本库封装的各算法模块当前处于自动还是手动模式，便于排查问题时确认状态。
+use super::ae::AutoExposure;
+use super::af::AutoFocus;
+use super::awb::AutoWhiteBalance;
+use super::ccm::ColorCorrection;
+use super::context::Context;
+use super::defog::Defog;
+use super::gamma::Gamma;
+use super::nr::NoiseRemoval;
+use super::sharpen::Sharpen;
+use super::types::{OpMode, XCamResult};
+use std::fmt;
+
+/// 一个描述单个模块工作模式的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ModuleMode {
+    /// 查询成功。
+    Known(OpMode),
+    /// 当前版本不支持查询、查询失败或 SDK 报告了无效值。
+    Unknown,
+}
+
+impl From<XCamResult<OpMode>> for ModuleMode {
+    fn from(val: XCamResult<OpMode>) -> Self {
+        match val {
+            Ok(OpMode::Invalid) | Err(_) => Self::Unknown,
+            Ok(mode) => Self::Known(mode),
+        }
+    }
+}
+
+impl fmt::Display for ModuleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Known(mode) => fmt::Display::fmt(mode, f),
+            Self::Unknown => f.pad("unknown"),
+        }
+    }
+}
+
+/// 一个汇总各模块工作模式的类型。
+///
+/// `Display` 输出每个模块一行，例如：
+///
+/// ```text
+/// wb        auto
+/// ae        manual
+/// af        unknown
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ModeSummary {
+    /// 白平衡。
+    pub wb: ModuleMode,
+    /// 曝光。
+    pub ae: ModuleMode,
+    /// 对焦。
+    pub af: ModuleMode,
+    /// 降噪。
+    pub nr: ModuleMode,
+    /// 锐化。
+    pub sharpness: ModuleMode,
+    /// 去雾。
+    pub dehaze: ModuleMode,
+    /// 颜色校正。
+    pub ccm: ModuleMode,
+    /// Gamma。Gamma 关闭时为 `Unknown`。
+    pub gamma: ModuleMode,
+}
+
+impl ModeSummary {
+    /// 依次查询各模块，单个模块查询失败时记为 `Unknown`，不影响其余模块。
+    pub fn query<C>(ctx: &C) -> Self
+    where
+        C: AutoWhiteBalance
+            + AutoExposure
+            + AutoFocus
+            + NoiseRemoval
+            + Sharpen
+            + Defog
+            + ColorCorrection
+            + Gamma,
+    {
+        Self {
+            wb: ctx.get_wb_mode().into(),
+            ae: ctx.get_exp_mode().into(),
+            af: ctx.get_focus_mode().into(),
+            nr: ctx.get_nr_mode().into(),
+            sharpness: ctx.get_sharp_mode().into(),
+            dehaze: ctx.get_dhz_mode().into(),
+            ccm: ctx.get_ccm_mode().into(),
+            gamma: ctx.get_gamma_mode().into(),
+        }
+    }
+
+    /// 以 `(模块名称, 工作模式)` 的形式依次返回各模块。
+    pub fn entries(&self) -> [(&'static str, ModuleMode); 8] {
+        [
+            ("wb", self.wb),
+            ("ae", self.ae),
+            ("af", self.af),
+            ("nr", self.nr),
+            ("sharpness", self.sharpness),
+            ("dehaze", self.dehaze),
+            ("ccm", self.ccm),
+            ("gamma", self.gamma),
+        ]
+    }
+
+    /// 处于手动模式（含寄存器手动模式）的模块名称。
+    pub fn manual_modules(&self) -> Vec<&'static str> {
+        self.entries()
+            .iter()
+            .filter(|(_, mode)| match mode {
+                ModuleMode::Known(OpMode::Manual) => true,
+                #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+                ModuleMode::Known(OpMode::RegManual) => true,
+                _ => false,
+            })
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
+
+impl fmt::Display for ModeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (name, mode)) in self.entries().iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{:<10}{}", name, mode)?;
+        }
+        Ok(())
+    }
+}
+
+impl Context {
+    /// 查询各模块的工作模式，见 `ModeSummary::query`。
+    pub fn mode_summary(&self) -> ModeSummary {
+        ModeSummary::query(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::XCamError;
+    use crate::ffi;
+
+    #[test]
+    fn test_mode_summary() {
+        let failed: XCamResult<OpMode> =
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED));
+        let summary = ModeSummary {
+            wb: XCamResult::Ok(OpMode::Auto).into(),
+            ae: XCamResult::Ok(OpMode::Manual).into(),
+            af: failed.into(),
+            nr: Err::<OpMode, _>(XCamError::Unsupported).into(),
+            sharpness: XCamResult::Ok(OpMode::Invalid).into(),
+            dehaze: XCamResult::Ok(OpMode::SemiAuto).into(),
+            ccm: Err::<OpMode, _>(XCamError::Timeout).into(),
+            gamma: XCamResult::Ok(OpMode::Manual).into(),
+        };
+        assert_eq!(summary.af, ModuleMode::Unknown);
+        assert_eq!(summary.sharpness, ModuleMode::Unknown);
+        assert_eq!(summary.manual_modules(), ["ae", "gamma"]);
+        assert_eq!(
+            summary.to_string(),
+            "wb        auto\n\
+             ae        manual\n\
+             af        unknown\n\
+             nr        unknown\n\
+             sharpness unknown\n\
+             dehaze    semi_auto\n\
+             ccm       unknown\n\
+             gamma     manual"
+        );
+    }
+}
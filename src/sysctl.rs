@@ -1,14 +1,574 @@
 //! 系统控制。
 //!
 //! 系统控制部分包含了 AIQ 公共属性配置，初始化 AIQ、运行 AIQ、退出AIQ，设置 AIQ 各模块等功能。
-use super::context::Context;
+//!
+//! AIQ 只根据统计信息计算并下发 ISP 参数，不经手图像数据，SDK 也没有提供逐帧交付图像缓冲区的回调。
+//! 处理后的帧由 `Context::on_frame` 从 ISP 主通路 video 节点逐帧取得，见 `capture` 模块。
+use super::ae::{AutoExposure, ExpInfo};
+use super::af::{AfSearchState, AutoFocus};
+use super::awb::AutoWhiteBalance;
+use super::calib::TempCalib;
+use super::clock::{Clock, SystemClock};
+use super::context::{self, Context};
+use super::display::enum_str;
 use super::error::XCamError;
 use super::ffi;
+use super::hdr::HdrReadout;
+use super::luma::{self, LumaMap, LUMA_STATS_TIMEOUT};
+use super::media;
+use super::retry::{retry, RetryPolicy};
+use super::schedule;
+pub use super::types::BayerPattern;
+pub use super::types::PixelFormat;
 use super::types::{
-    AlgoContext, AlgoDescComm, CpslCap, CpslCfg, CpslInfo, ModuleId, Rect, StaticInfo, WorkingMode,
-    XCamResult,
+    AlgoContext, AlgoDescComm, CpslCap, CpslCfg, CpslInfo, IspStats, ModuleId, OpMode, RawBitDepth,
+    RawFormat, Rect, Resolution, SensorDescriptor, StaticInfo, WbGain, WorkingMode, XCamResult,
 };
+use super::uapi::uapi_call;
 use std::ffi::{CStr, CString};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// 预热时相邻两帧白平衡增益的最大相对变化，低于该值视为稳定。
+pub const AWB_GAIN_TOLERANCE: f32 = 0.01;
+
+/// 预热时查询帧号的间隔。
+const WARMUP_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// 预热时帧号停止增长的最长时间，超过后视为数据流中断。
+const WARMUP_STALL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// `wait_for_frame` 检查帧号的间隔。
+const FRAME_POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+/// 帧号 `id` 是否已到达 `target`，按回绕差值比较：`id` 领先 `target` 不超过 2^31 帧时视为已到达。
+pub(crate) fn frame_reached(id: u32, target: u32) -> bool {
+    id.wrapping_sub(target) as i32 >= 0
+}
+
+/// 以 `clock` 计时等待帧号到达 `target`，见 `SystemControl::wait_for_frame`。
+pub(crate) fn wait_for_frame_with<C, K>(
+    ctx: &C,
+    target: u32,
+    timeout: Duration,
+    clock: &K,
+) -> XCamResult<()>
+where
+    C: SystemControl + ?Sized,
+    K: Clock + ?Sized,
+{
+    let start = clock.now();
+    loop {
+        if ctx
+            .get_frame_id()
+            .is_some_and(|id| frame_reached(id, target))
+        {
+            return Ok(());
+        }
+        let elapsed = clock.now().saturating_duration_since(start);
+        if elapsed >= timeout {
+            return Err(XCamError::Timeout);
+        }
+        clock.sleep(FRAME_POLL_INTERVAL.min(timeout - elapsed));
+    }
+}
+
+/// 自动曝光是否已收敛。
+pub(crate) fn ae_stable(info: &ExpInfo) -> bool {
+    info.converged
+}
+
+/// 自动白平衡是否已稳定：相邻两次观测的各通道增益相对变化均不超过 `AWB_GAIN_TOLERANCE`。
+pub(crate) fn awb_stable(prev: &WbGain, cur: &WbGain) -> bool {
+    let pairs = [
+        (prev.rgain, cur.rgain),
+        (prev.grgain, cur.grgain),
+        (prev.gbgain, cur.gbgain),
+        (prev.bgain, cur.bgain),
+    ];
+    pairs
+        .iter()
+        .all(|&(a, b)| (a - b).abs() <= AWB_GAIN_TOLERANCE * a.abs().max(f32::EPSILON))
+}
+
+/// 自动对焦是否已稳定：非自动对焦模式下始终稳定，否则须搜索完成。
+pub(crate) fn af_stable(mode: OpMode, state: AfSearchState) -> bool {
+    mode != OpMode::Auto || state == AfSearchState::Done
+}
+
+/// 一个跟踪 3A 收敛状态的类型，每观测到一帧更新一次。
+#[derive(Clone, Debug, Default)]
+pub(crate) struct WarmupTracker {
+    last_gain: Option<WbGain>,
+}
+
+impl WarmupTracker {
+    /// 输入一帧的观测结果，3A 均已稳定时返回 `true`。
+    pub(crate) fn update(
+        &mut self,
+        exp: &ExpInfo,
+        gain: WbGain,
+        focus: (OpMode, AfSearchState),
+    ) -> bool {
+        let awb = self
+            .last_gain
+            .replace(gain)
+            .is_some_and(|prev| awb_stable(&prev, &gain));
+        ae_stable(exp) && awb && af_stable(focus.0, focus.1)
+    }
+}
+
+bitflags::bitflags! {
+    /// 一个描述 ISP 错误、警告标志的位标志类型。
+    ///
+    /// 标志由 SDK 错误回调累积，一经置位即保持，直至被清除。
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct IspErrorFlags: u32 {
+        /// ISP 硬件错误，例如缓冲区欠载或溢出。
+        const ISP = 1 << 0;
+        /// 传感器错误，例如同步信号丢失。
+        const SENSOR = 1 << 1;
+        /// 等待帧数据或驱动事件超时。
+        const TIMEOUT = 1 << 2;
+        /// 驱动 ioctl 调用失败。
+        const IOCTL = 1 << 3;
+        /// 内存或缓冲区分配失败。
+        const MEMORY = 1 << 4;
+        /// 3A 分析线程错误。
+        const ANALYZER = 1 << 5;
+        /// 内部线程错误。
+        const THREAD = 1 << 6;
+        /// 其它错误。
+        const OTHER = 1 << 31;
+    }
+}
+
+impl IspErrorFlags {
+    /// 由错误回调报告的错误代码得到对应的标志。
+    pub fn from_err_code(code: i32) -> Self {
+        use ffi::XCamReturn::*;
+        match XCamError::from(code) {
+            XCamError::Timeout => Self::TIMEOUT,
+            XCamError::Code(XCAM_RETURN_NO_ERROR | XCAM_RETURN_BYPASS) => Self::empty(),
+            XCamError::Code(XCAM_RETURN_ERROR_ISP) => Self::ISP,
+            XCamError::Code(XCAM_RETURN_ERROR_SENSOR) => Self::SENSOR,
+            XCamError::Code(XCAM_RETURN_ERROR_IOCTL) => Self::IOCTL,
+            XCamError::Code(XCAM_RETURN_ERROR_MEM) => Self::MEMORY,
+            XCamError::Code(XCAM_RETURN_ERROR_ANALYZER) => Self::ANALYZER,
+            XCamError::Code(XCAM_RETURN_ERROR_THREAD) => Self::THREAD,
+            _ => Self::OTHER,
+        }
+    }
+}
+
+/// SDK 内置算法库的编号。
+pub const DEFAULT_ALGO_LIB_ID: i32 = 0;
+
+bitflags::bitflags! {
+    /// 一个描述 3A 及图像算法使能状态的位标志类型。
+    ///
+    /// 每个标志对应 SDK 中一个或多个算法类型，由 `SystemControl::set_algo_mask` 统一切换：
+    ///
+    /// * `ANR` 在 v1.0、v2.0 对应单一的 ANR 算法；v3.0 起 SDK 将降噪拆分为 RAWNR、MFNR、
+    ///   YNR、CNR 四个算法，该标志同时切换这四个算法，仅当全部使能时才视为使能；
+    /// * `AF` 需要传感器带有可对焦镜头，否则 SDK 会拒绝使能；
+    /// * 其余标志在各 SDK 版本中均对应单一算法。
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct AlgoMask: u32 {
+        /// 自动白平衡。
+        const AWB = 1 << 0;
+        /// 自动曝光。
+        const AEC = 1 << 1;
+        /// 自动对焦。
+        const AF = 1 << 2;
+        /// 降噪。
+        const ANR = 1 << 3;
+        /// 去雾。
+        const ADHAZ = 1 << 4;
+        /// 锐化。
+        const ASHARP = 1 << 5;
+        /// 色彩校正。
+        const ACCM = 1 << 6;
+        /// Gamma 校正。
+        const AGAMMA = 1 << 7;
+    }
+}
+
+impl AlgoMask {
+    /// 返回按使能顺序排列的标志及其对应的 SDK 算法类型。
+    ///
+    /// 被依赖的算法排在前面：CCM 依赖 AWB 的光源估计，降噪、锐化依赖 AE 的增益，
+    /// Gamma 作用于最终输出。禁用时按相反顺序进行。
+    fn algo_types() -> [(Self, &'static [i32]); 8] {
+        use ffi::RkAiqAlgoType_t::*;
+        [
+            (Self::AEC, &[RK_AIQ_ALGO_TYPE_AE as i32]),
+            (Self::AWB, &[RK_AIQ_ALGO_TYPE_AWB as i32]),
+            (Self::AF, &[RK_AIQ_ALGO_TYPE_AF as i32]),
+            #[cfg(any(feature = "v1_0", feature = "v2_0"))]
+            (Self::ANR, &[RK_AIQ_ALGO_TYPE_ANR as i32]),
+            #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+            (
+                Self::ANR,
+                &[
+                    RK_AIQ_ALGO_TYPE_ARAWNR as i32,
+                    RK_AIQ_ALGO_TYPE_AMFNR as i32,
+                    RK_AIQ_ALGO_TYPE_AYNR as i32,
+                    RK_AIQ_ALGO_TYPE_ACNR as i32,
+                ],
+            ),
+            (Self::ASHARP, &[RK_AIQ_ALGO_TYPE_ASHARP as i32]),
+            (Self::ADHAZ, &[RK_AIQ_ALGO_TYPE_ADHAZ as i32]),
+            (Self::ACCM, &[RK_AIQ_ALGO_TYPE_ACCM as i32]),
+            (Self::AGAMMA, &[RK_AIQ_ALGO_TYPE_AGAMMA as i32]),
+        ]
+    }
+}
+
+/// 一个描述 `set_algo_mask` 部分失败的类型。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AlgoMaskError {
+    /// 未能切换到目标状态的算法。
+    pub failed: AlgoMask,
+    /// 遇到的第一个错误。
+    pub error: XCamError,
+}
+
+impl std::fmt::Display for AlgoMaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to toggle {:?}: {}", self.failed, self.error)
+    }
+}
+
+impl std::error::Error for AlgoMaskError {}
+
+/// `freeze_3a` 冻结的算法。
+pub const FROZEN_3A: AlgoMask = AlgoMask::AEC.union(AlgoMask::AWB).union(AlgoMask::AF);
+
+/// 一个在作用域内禁用 3A 算法的守卫，由 `SystemControl::freeze_3a` 创建。
+///
+/// 释放时按依赖顺序重新使能冻结前处于使能状态的算法，冻结前已禁用的算法保持禁用。
+/// `Drop` 忽略恢复中的错误，需要检查错误时调用 `release`。
+#[must_use = "3A algorithms are restored as soon as the guard is dropped"]
+pub struct Frozen3aGuard<'a, C: SystemControl> {
+    ctx: &'a C,
+    prior: AlgoMask,
+    released: bool,
+}
+
+impl<C: SystemControl> Frozen3aGuard<'_, C> {
+    /// 冻结前处于使能状态的 3A 算法。
+    pub fn prior(&self) -> AlgoMask {
+        self.prior
+    }
+
+    /// 恢复冻结前的使能状态并返回遇到的错误。
+    ///
+    /// 某个算法恢复失败时继续恢复其余算法。
+    pub fn release(mut self) -> Result<(), AlgoMaskError> {
+        self.released = true;
+        restore_algos(self.ctx, self.prior)
+    }
+}
+
+impl<C: SystemControl> Drop for Frozen3aGuard<'_, C> {
+    fn drop(&mut self) {
+        if !self.released {
+            let _ = restore_algos(self.ctx, self.prior);
+        }
+    }
+}
+
+/// 按依赖顺序使能 `algos` 中的算法。
+fn restore_algos<C: SystemControl + ?Sized>(ctx: &C, algos: AlgoMask) -> Result<(), AlgoMaskError> {
+    let mut failed = AlgoMask::empty();
+    let mut error = None;
+    for (flag, types) in AlgoMask::algo_types()
+        .iter()
+        .filter(|(f, _)| algos.contains(*f))
+    {
+        for &t in types.iter() {
+            if let Err(e) = ctx.enable_ax_lib(t, DEFAULT_ALGO_LIB_ID) {
+                failed |= *flag;
+                error.get_or_insert(e);
+            }
+        }
+    }
+    match error {
+        Some(error) => Err(AlgoMaskError { failed, error }),
+        None => Ok(()),
+    }
+}
+
+/// 按依赖顺序的逆序禁用 `algos` 中的算法。
+fn disable_algos<C: SystemControl + ?Sized>(ctx: &C, algos: AlgoMask) -> Result<(), AlgoMaskError> {
+    let mut failed = AlgoMask::empty();
+    let mut error = None;
+    for (flag, types) in AlgoMask::algo_types()
+        .iter()
+        .rev()
+        .filter(|(f, _)| algos.contains(*f))
+    {
+        for &t in types.iter() {
+            if let Err(e) = ctx.disable_ax_lib(t, DEFAULT_ALGO_LIB_ID) {
+                failed |= *flag;
+                error.get_or_insert(e);
+            }
+        }
+    }
+    match error {
+        Some(error) => Err(AlgoMaskError { failed, error }),
+        None => Ok(()),
+    }
+}
+
+/// `set_3a_update_interval` 设置的节流状态。
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct UpdateInterval {
+    frames: u8,
+    /// 开始节流时处于使能状态的 3A 算法。
+    algos: AlgoMask,
+    /// 3A 是否处于暂停状态。
+    paused: bool,
+    /// 最近一次运行 3A 的帧号。
+    last_run: Option<u32>,
+}
+
+impl Default for UpdateInterval {
+    fn default() -> Self {
+        Self {
+            frames: 1,
+            algos: AlgoMask::empty(),
+            paused: false,
+            last_run: None,
+        }
+    }
+}
+
+/// 设置 3A 的运行间隔，`frames` 为 0 时取 1；恢复为 1 时使能暂停中的算法。
+pub(crate) fn set_3a_update_interval<C: SystemControl + ?Sized>(
+    ctx: &C,
+    state: &mut UpdateInterval,
+    frames: u8,
+) -> XCamResult<()> {
+    let frames = frames.max(1);
+    if frames == 1 {
+        if state.paused {
+            restore_algos(ctx, state.algos - ctx.get_algo_mask()).map_err(|e| e.error)?;
+        }
+        *state = UpdateInterval::default();
+        return Ok(());
+    }
+    if state.frames == 1 {
+        state.algos = ctx.get_algo_mask() & FROZEN_3A;
+    }
+    state.frames = frames;
+    Ok(())
+}
+
+/// 按当前帧号暂停或恢复 3A，返回本帧 3A 是否运行。
+///
+/// 距上次运行满 `frames` 帧时使能算法运行一帧，其余帧禁用；尚未收到帧号时不做切换。
+/// 只在暂停与运行之间切换时写入，且只写入使能状态与目标不同的算法。
+pub(crate) fn tick_3a_update<C: SystemControl + ?Sized>(
+    ctx: &C,
+    state: &mut UpdateInterval,
+) -> XCamResult<bool> {
+    if state.frames == 1 {
+        return Ok(true);
+    }
+    let Some(id) = ctx.get_frame_id() else {
+        return Ok(!state.paused);
+    };
+    let due = match state.last_run {
+        Some(last) => id.wrapping_sub(last) >= u32::from(state.frames),
+        None => true,
+    };
+    if due {
+        if state.paused {
+            restore_algos(ctx, state.algos - ctx.get_algo_mask()).map_err(|e| e.error)?;
+            state.paused = false;
+        }
+        state.last_run = Some(id);
+    } else if !state.paused {
+        disable_algos(ctx, state.algos & ctx.get_algo_mask()).map_err(|e| e.error)?;
+        state.paused = true;
+    }
+    Ok(!state.paused)
+}
+
+/// 一个描述传感器合并读出方式的类型。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Binning {
+    /// 全分辨率读出。
+    #[default]
+    None,
+    /// 水平、垂直方向各 2 合 1，分辨率为原来的 1/4，噪声降低。
+    V2H2,
+    /// 水平、垂直方向各 4 合 1，分辨率为原来的 1/16。
+    V4H4,
+}
+
+impl Binning {
+    pub const ALL: [Self; 3] = [Self::None, Self::V2H2, Self::V4H4];
+
+    /// 返回单个方向上的合并倍数。
+    pub fn factor(self) -> u32 {
+        match self {
+            Self::None => 1,
+            Self::V2H2 => 2,
+            Self::V4H4 => 4,
+        }
+    }
+
+    /// 读出区域为 `(宽, 高)` 时传感器的输出尺寸。
+    pub fn output_size(self, (width, height): (u32, u32)) -> (u32, u32) {
+        (width / self.factor(), height / self.factor())
+    }
+}
+
+enum_str!(Binning, "binning" {
+    None => "none",
+    V2H2 => "v2h2",
+    V4H4 => "v4h4",
+});
+
+/// 一个描述传感器读出方式的类型。
+///
+/// 合并与裁剪都会改变视场角：裁剪缩小视场，合并在保持视场的同时降低分辨率。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReadoutConfig {
+    pub binning: Binning,
+    /// 传感器端裁剪区域，以全分辨率像素坐标表示；`None` 表示读出完整画面。
+    pub crop: Option<Rect>,
+}
+
+/// 检查读出方式能否修改，通过后调用 `apply` 修改。
+///
+/// 出流期间返回 `XCAM_RETURN_ERROR_ORDER`；裁剪区域的偏移为负或宽高不大于 0 时返回
+/// `XCAM_RETURN_ERROR_PARAM`。
+pub(crate) fn set_sensor_readout_with<C, F>(ctx: &C, cfg: ReadoutConfig, apply: F) -> XCamResult<()>
+where
+    C: SystemControl + ?Sized,
+    F: FnOnce(ReadoutConfig) -> XCamResult<()>,
+{
+    if ctx.is_streaming() {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER));
+    }
+    if let Some(r) = cfg.crop {
+        if r.left < 0 || r.top < 0 || r.width <= 0 || r.height <= 0 {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+    }
+    apply(cfg)
+}
+
+/// 一个描述 `prepare` 实际协商结果的类型。
+///
+/// 请求的分辨率不是传感器支持的输出模式时，SDK 选择最接近的传感器模式，此时 `width`、`height`
+/// 与请求不同，差异由 ISP 的裁剪与缩放处理；`set_sensor_readout` 设置的合并与裁剪也会改变
+/// 传感器输出的分辨率。工作模式不会被协商，传感器不支持时 `prepare` 直接失败。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PrepareResult {
+    /// 传感器输出宽度。
+    pub width: u32,
+    /// 传感器输出高度。
+    pub height: u32,
+    /// 工作模式。
+    pub mode: WorkingMode,
+    /// 传感器输出的像素格式（V4L2 media bus 代码），无法获取时为 0。
+    pub format: u32,
+}
+
+impl PrepareResult {
+    /// 由传感器描述得到协商结果。
+    pub(crate) fn from_descriptor(des: &SensorDescriptor, mode: WorkingMode) -> Self {
+        Self {
+            width: u32::from(des.sensor_output_width),
+            height: u32::from(des.sensor_output_height),
+            mode,
+            format: des.sensor_pixelformat,
+        }
+    }
+
+    /// 协商得到的分辨率是否与请求一致。
+    pub fn matches(&self, width: u32, height: u32) -> bool {
+        (self.width, self.height) == (width, height)
+    }
+}
+
+/// 由 V4L2 media bus 代码得到 Bayer 排列，非 Bayer 格式返回 `None`。
+///
+/// 支持 8、10、12、14、16 位的 `MEDIA_BUS_FMT_S*_1X*` 代码。
+pub fn bayer_pattern_from_bus_code(code: u32) -> Option<BayerPattern> {
+    RawFormat::from_bus_code(code).map(|f| f.bayer)
+}
+
+/// 一个描述 `prepare` 后实际配置的传感器输出格式的类型，用于配置下游 V4L2 采集的缓冲区。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NegotiatedFormat {
+    /// 输出宽度。
+    pub width: u32,
+    /// 输出高度。
+    pub height: u32,
+    /// 传感器描述中的像素格式代码，见 `RawFormat::from_sensor_format`，无法获取时为 0。
+    pub format: u32,
+    /// Bayer 排列，非 Bayer 格式或无法获取格式时为 `None`。
+    pub bayer: Option<BayerPattern>,
+    /// RAW 数据位深，非 Bayer 格式或无法获取格式时为 `None`。
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub depth: Option<RawBitDepth>,
+}
+
+impl NegotiatedFormat {
+    /// RAW 输出格式，非 Bayer 格式或无法获取格式时为 `None`。
+    pub fn raw_format(&self) -> Option<RawFormat> {
+        Some(RawFormat {
+            bayer: self.bayer?,
+            depth: self.depth?,
+        })
+    }
+}
+
+impl From<PrepareResult> for NegotiatedFormat {
+    fn from(val: PrepareResult) -> Self {
+        let raw = RawFormat::from_sensor_format(val.format);
+        Self {
+            width: val.width,
+            height: val.height,
+            format: val.format,
+            bayer: raw.map(|f| f.bayer),
+            depth: raw.map(|f| f.depth),
+        }
+    }
+}
+
+/// 检查输出格式能否修改，通过后调用 `apply` 修改。
+///
+/// 出流期间返回 `XCAM_RETURN_ERROR_ORDER`；RAW 格式与已知的传感器输出格式 `sensor` 不一致时
+/// 返回 `XCAM_RETURN_ERROR_PARAM`。
+pub(crate) fn set_output_format_with<C, F>(
+    ctx: &C,
+    fmt: PixelFormat,
+    sensor: Option<RawFormat>,
+    apply: F,
+) -> XCamResult<()>
+where
+    C: SystemControl + ?Sized,
+    F: FnOnce(PixelFormat) -> XCamResult<()>,
+{
+    if ctx.is_streaming() {
+        return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER));
+    }
+    if let (PixelFormat::Raw(raw), Some(sensor)) = (fmt, sensor) {
+        if raw != sensor {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+    }
+    apply(fmt)
+}
 
 /// 一个描述静态信息枚举器的类型。
 pub struct StaticMetas {
@@ -41,14 +601,20 @@ impl Iterator for StaticMetas {
 }
 
 pub trait SystemControl {
-    fn prepare(&self, width: u32, height: u32, mode: WorkingMode) -> XCamResult<()>;
+    /// 按请求的分辨率与工作模式准备出流，返回实际协商的结果，见 `PrepareResult`。
+    fn prepare(&self, width: u32, height: u32, mode: WorkingMode) -> XCamResult<PrepareResult>;
 
     fn start(&self) -> XCamResult<()>;
 
     fn stop(&self, keep_ext_hw_st: bool) -> XCamResult<()>;
 
+    /// 是否正在出流，即 `start` 成功之后、`stop` 成功之前。
+    fn is_streaming(&self) -> bool;
+
+    /// 使能模块，模块已使能时 SDK 返回的 `XCAM_RETURN_BYPASS` 视为成功。
     fn enable_module<T: Into<ModuleId>>(&self, id: T) -> XCamResult<()>;
 
+    /// 禁用模块，模块已禁用时 SDK 返回的 `XCAM_RETURN_BYPASS` 视为成功。
     fn disable_module<T: Into<ModuleId>>(&self, id: T) -> XCamResult<()>;
 
     fn is_module_enabled<T: Into<ModuleId>>(&self, id: T) -> bool;
@@ -57,8 +623,10 @@ pub trait SystemControl {
 
     fn unregister_lib(&self, algo_type: i32, lib_id: i32) -> XCamResult<()>;
 
+    /// 使能算法库，已使能时 SDK 返回的 `XCAM_RETURN_BYPASS` 视为成功。
     fn enable_ax_lib(&self, algo_type: i32, lib_id: i32) -> XCamResult<()>;
 
+    /// 禁用算法库，已禁用时 SDK 返回的 `XCAM_RETURN_BYPASS` 视为成功。
     fn disable_ax_lib(&self, algo_type: i32, lib_id: i32) -> XCamResult<()>;
 
     fn is_ax_lib_enabled(&self, algo_type: i32, lib_id: i32) -> bool;
@@ -80,120 +648,528 @@ pub trait SystemControl {
 
     fn update_iq<T: Into<Vec<u8>>>(&self, iq_file: T) -> XCamResult<()>;
 
+    /// 以内存中的 JSON IQ 内容更新 IQ 参数。
+    ///
+    /// SDK 只能按路径加载 IQ 文件：内容先经解析检查（需启用 `json` 特性），再写入私有的临时目录，以 `update_iq`
+    /// 加载后删除。内容无法解析时返回 `XCAM_RETURN_ERROR_PARAM`，不调用 `update_iq`；
+    /// 写入临时文件失败时返回 `XCAM_RETURN_ERROR_FILE`。
+    fn update_iq_from_buffer(&self, iq_data: &[u8]) -> XCamResult<()> {
+        let calib = TempCalib::write("buffer_iq_calib", iq_data)?;
+        let path = calib.path();
+        let path = path
+            .to_str()
+            .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FILE))?;
+        self.update_iq(path)
+    }
+
     fn get_crop(&self) -> XCamResult<Rect>;
 
     fn set_crop(&self, crop: Rect) -> XCamResult<()>;
+
+    /// 获取最新一帧的 3A 统计信息。
+    ///
+    /// 统计队列为空时阻塞至新一帧的统计到达，最多等待 `timeout`（按毫秒计），
+    /// 仍未到达时返回 `XCamError::Timeout`。正常出流时最长阻塞约一个帧周期。
+    ///
+    /// `Context` 开启 `set_stats_averaging_window` 后部分字段为最近若干帧的平均，见 `average` 模块。
+    fn get_3a_stats(&self, timeout: Duration) -> XCamResult<IspStats>;
+
+    /// 获取最新一帧的 3A 统计信息，统计队列为空时立即返回 `XCamError::Busy`。
+    fn try_get_3a_stats(&self) -> XCamResult<IspStats> {
+        self.get_3a_stats(Duration::ZERO).map_err(|e| match e {
+            XCamError::Timeout => XCamError::Busy,
+            e => e,
+        })
+    }
+
+    /// 获取按网格划分的画面亮度，来源与归一化见 `luma` 模块。
+    ///
+    /// 亮度取自 `get_3a_stats`，最多阻塞 `LUMA_STATS_TIMEOUT`。
+    fn get_luma_map(&self) -> XCamResult<LumaMap> {
+        luma::luma_map_from_stats(&self.get_3a_stats(LUMA_STATS_TIMEOUT)?)
+    }
+
+    /// 获取自上次清除以来累积的 ISP 错误标志。
+    ///
+    /// # Note
+    ///
+    /// SDK 错误回调不携带上下文指针，因此标志为进程内全部上下文共享。
+    fn get_isp_error_flags(&self) -> XCamResult<IspErrorFlags>;
+
+    /// 清除已累积的 ISP 错误标志。
+    fn clear_isp_error_flags(&self) -> XCamResult<()>;
+
+    /// 获取平台允许的 ISP 时钟频率，单位：MHz，按升序排列。
+    ///
+    /// 本库绑定的各版本 SDK 都没有 ISP 时钟接口：rkisp 驱动按出流分辨率从设备树的频率表中
+    /// 自行选择时钟，用户态无法修改。默认返回 `XCamError::Unsupported`，由能够控制时钟的实现
+    /// 覆盖 `get_isp_clock_rates`、`get_isp_clock` 与 `set_isp_clock`。
+    fn get_isp_clock_rates(&self) -> XCamResult<Vec<u32>> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取当前 ISP 时钟频率，单位：MHz，见 `get_isp_clock_rates`。
+    fn get_isp_clock(&self) -> XCamResult<u32> {
+        Err(XCamError::Unsupported)
+    }
+
+    /// 设置 ISP 时钟频率，单位：MHz，见 `get_isp_clock_rates`。
+    ///
+    /// 降低时钟可降低功耗与发热，代价是处理吞吐下降：ISP 须在一个帧周期内处理完一帧的全部像素，
+    /// 时钟低于 `宽 × 高 × 帧率` 所需的像素率时最高帧率随之受限，甚至丢帧；HDR 模式每帧处理
+    /// 多次，所需时钟成倍增加。`mhz` 不是平台允许的频率时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`，
+    /// 见 `check_isp_clock`。
+    fn set_isp_clock(&self, mhz: u32) -> XCamResult<()> {
+        let _ = mhz;
+        Err(XCamError::Unsupported)
+    }
+
+    /// 获取最近一帧的帧号，尚未收到元数据回调时返回 `None`。
+    ///
+    /// # Note
+    ///
+    /// 元数据回调不携带上下文指针，因此帧号为进程内全部上下文共享。
+    fn get_frame_id(&self) -> Option<u32>;
+
+    /// 阻塞直到帧号到达 `frame_id`，用于确定性的测试与抓拍时机。
+    ///
+    /// 每隔约 2ms 检查一次 `get_frame_id`，帧号按回绕差值比较，见 `frame_reached`：
+    /// 目标帧号已经过去时立即返回。超过 `timeout` 仍未到达（包括始终未收到元数据回调）时
+    /// 返回 `XCamError::Timeout`。
+    fn wait_for_frame(&self, frame_id: u32, timeout: Duration) -> XCamResult<()> {
+        wait_for_frame_with(self, frame_id, timeout, &SystemClock)
+    }
+
+    /// 一次设置全部算法的使能状态。
+    ///
+    /// 先按依赖关系的逆序禁用 `mask` 之外的算法，再按依赖顺序使能 `mask` 中的算法，
+    /// 均使用 SDK 内置算法库。某个算法切换失败时继续处理其余算法，
+    /// 最后在错误中报告全部失败的标志；已切换的算法不会回滚。
+    fn set_algo_mask(&self, mask: AlgoMask) -> Result<(), AlgoMaskError> {
+        let disabled = disable_algos(self, !mask);
+        let enabled = restore_algos(self, mask);
+        match (disabled, enabled) {
+            (Ok(()), r) | (r, Ok(())) => r,
+            (Err(first), Err(second)) => Err(AlgoMaskError {
+                failed: first.failed | second.failed,
+                error: first.error,
+            }),
+        }
+    }
+
+    /// 获取全部算法的使能状态，一个标志对应的算法全部使能时才置位。
+    fn get_algo_mask(&self) -> AlgoMask {
+        AlgoMask::algo_types()
+            .iter()
+            .filter(|(_, types)| {
+                types
+                    .iter()
+                    .all(|&t| self.is_ax_lib_enabled(t, DEFAULT_ALGO_LIB_ID))
+            })
+            .fold(AlgoMask::empty(), |acc, (f, _)| acc | *f)
+    }
+
+    /// 禁用自动曝光、自动白平衡与自动对焦，直至返回的守卫释放。
+    ///
+    /// 只禁用当前处于使能状态的算法，守卫释放时恢复为冻结前的状态。
+    /// 某个算法禁用失败时恢复已禁用的算法并返回该错误。
+    ///
+    /// # Note
+    ///
+    /// 冻结期间 ISP 保持最后一帧的 3A 结果。`set_wb_mode`、`set_exp_mode` 等模式设置接口
+    /// 仍会修改算法属性，但冻结期间不生效，守卫释放后算法按新属性运行；
+    /// 手动增益、手动曝光同样由对应算法下发，也要等到释放后才生效。
+    fn freeze_3a(&self) -> XCamResult<Frozen3aGuard<'_, Self>>
+    where
+        Self: Sized,
+    {
+        let prior = self.get_algo_mask() & FROZEN_3A;
+        let mut disabled = AlgoMask::empty();
+        for (flag, types) in AlgoMask::algo_types().iter().rev() {
+            if !prior.contains(*flag) {
+                continue;
+            }
+            for &t in types.iter() {
+                if let Err(e) = self.disable_ax_lib(t, DEFAULT_ALGO_LIB_ID) {
+                    let _ = restore_algos(self, disabled | *flag);
+                    return Err(e);
+                }
+            }
+            disabled |= *flag;
+        }
+        Ok(Frozen3aGuard {
+            ctx: self,
+            prior,
+            released: false,
+        })
+    }
+
+    /// 在 `start` 之后等待 3A 收敛。
+    ///
+    /// 每到一个新帧检查一次自动曝光、自动白平衡与自动对焦是否已稳定，
+    /// 全部稳定或已等待 `frames` 帧后返回。等待按帧号计数而不是按时间计算；
+    /// 帧号超过 `WARMUP_STALL_TIMEOUT` 未增长时返回超时错误。
+    ///
+    /// # Parameters
+    /// * `frames` - 最多等待的帧数。
+    fn warmup(&self, frames: u32) -> XCamResult<()>
+    where
+        Self: AutoExposure + AutoWhiteBalance + AutoFocus + Sized,
+    {
+        let mut tracker = WarmupTracker::default();
+        let mut waited: u32 = 0;
+        let mut last = self.get_frame_id();
+        let mut last_change = Instant::now();
+        while waited < frames {
+            let id = self.get_frame_id();
+            if id == last {
+                if last_change.elapsed() >= WARMUP_STALL_TIMEOUT {
+                    return Err(XCamError::Timeout);
+                }
+                thread::sleep(WARMUP_POLL_INTERVAL);
+                continue;
+            }
+            waited = waited.saturating_add(match (last, id) {
+                (Some(l), Some(i)) => i.wrapping_sub(l),
+                _ => 1,
+            });
+            last = id;
+            last_change = Instant::now();
+            let exp = self.query_exp_info()?;
+            let gain = self.get_mwb_gain()?;
+            let mode = self.get_focus_mode()?;
+            let state = match mode {
+                OpMode::Auto => self.get_af_search_state()?,
+                _ => AfSearchState::Done,
+            };
+            if tracker.update(&exp, gain, (mode, state)) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 检查 `mhz` 是否为平台允许的 ISP 时钟频率之一，否则返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+///
+/// 供实现 `SystemControl::set_isp_clock` 时使用，`rates` 为 `get_isp_clock_rates` 的结果。
+pub fn check_isp_clock(mhz: u32, rates: &[u32]) -> XCamResult<()> {
+    if rates.contains(&mhz) {
+        Ok(())
+    } else {
+        Err(XCamError::from(
+            ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+        ))
+    }
+}
+
+/// 一个描述传感器模拟增益寄存器编码方式的枚举。
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AgainCode {
+    /// 每个码值对应固定的分贝数，倍数为 `10^(reg × 步长 / 20)`，如索尼 STARVIS 系列的 0.3 dB。
+    Decibel(f32),
+    /// 倒数编码，倍数为 `n / (n - reg)`，如 IMX219 的 256。
+    Reciprocal(u32),
+    /// 定点编码，倍数为 `reg / one`，如 OV5647 的 16。
+    Fixed(u32),
+}
+
+impl AgainCode {
+    /// 将寄存器值换算为增益倍数，寄存器值超出编码范围时返回 NaN。
+    pub fn multiplier(self, reg: u32) -> f32 {
+        match self {
+            Self::Decibel(step) => 10f32.powf(reg as f32 * step / 20.0),
+            Self::Reciprocal(n) if reg < n => n as f32 / (n - reg) as f32,
+            Self::Fixed(one) if one > 0 => reg as f32 / one as f32,
+            _ => f32::NAN,
+        }
+    }
+}
+
+/// 已知传感器型号及其模拟增益编码，型号取自各传感器的数据手册。
+const AGAIN_CODES: &[(&str, AgainCode)] = &[
+    ("imx307", AgainCode::Decibel(0.3)),
+    ("imx327", AgainCode::Decibel(0.3)),
+    ("imx335", AgainCode::Decibel(0.3)),
+    ("imx415", AgainCode::Decibel(0.3)),
+    ("imx462", AgainCode::Decibel(0.3)),
+    ("imx219", AgainCode::Reciprocal(256)),
+    ("imx258", AgainCode::Reciprocal(512)),
+    ("imx378", AgainCode::Reciprocal(1024)),
+    ("imx477", AgainCode::Reciprocal(1024)),
+    ("ov5647", AgainCode::Fixed(16)),
+];
+
+/// 按传感器实体名称（如 `m00_b_imx415 3-001a`）查找模拟增益编码，不区分大小写，未知型号返回 `None`。
+pub fn again_code_for_sensor(sensor: &str) -> Option<AgainCode> {
+    let sensor = sensor.to_ascii_lowercase();
+    AGAIN_CODES
+        .iter()
+        .find(|(model, _)| sensor.contains(model))
+        .map(|&(_, code)| code)
+}
+
+/// 将传感器模拟增益寄存器值换算为增益倍数。
+///
+/// `sensor` 为传感器实体名称或型号，编码方式见 `again_code_for_sensor`；
+/// 未知型号或寄存器值超出编码范围时返回 NaN。
+pub fn again_register_to_multiplier(reg: u32, sensor: &str) -> f32 {
+    again_code_for_sensor(sensor).map_or(f32::NAN, |code| code.multiplier(reg))
+}
+
+impl Context {
+    /// 获取最近一次 `prepare` 实际配置的输出格式。
+    ///
+    /// 分辨率可能因传感器模式选择、合并读出而与请求不同，见 `PrepareResult`。
+    /// 尚未 `prepare` 时返回 `XCAM_RETURN_ERROR_ORDER`。
+    pub fn negotiated_format(&self) -> XCamResult<NegotiatedFormat> {
+        self.prepared
+            .lock()
+            .unwrap()
+            .map(NegotiatedFormat::from)
+            .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))
+    }
+
+    /// 设置 ISP 主通路（`media::MAINPATH_ENTITY`）输出的像素格式。
+    ///
+    /// AIQ 不经手图像缓冲区，输出格式由主通路 video 节点的 V4L2 格式决定，本接口在媒体拓扑中
+    /// 查找该节点，再以 `VIDIOC_S_FMT` 设置格式，须在出流前、下游打开节点前调用。
+    /// 支持的格式取决于 ISP 而非传感器：Bayer 传感器经 ISP 处理后，主通路支持 NV12、NV21、
+    /// NV16、NV61、YUYV、UYVY 与 GREY；RGB888 仅部分 ISP 支持；RAW 格式只能是传感器本身的
+    /// 输出格式（见 `negotiated_format`），且只在驱动允许主通路输出 RAW 时有效。
+    ///
+    /// 出流期间返回 `XCAM_RETURN_ERROR_ORDER`；驱动不支持该格式、改为其他格式时返回
+    /// `XCAM_RETURN_ERROR_OUTOFRANGE`；访问节点失败时返回 `XCAM_RETURN_ERROR_IOCTL`；由
+    /// `from_raw_parts` 接管的上下文不知道传感器实体名称时返回 `XCamError::Unsupported`。
+    pub fn set_output_format(&self, fmt: PixelFormat) -> XCamResult<()> {
+        let sensor = self.negotiated_format().ok().and_then(|f| f.raw_format());
+        set_output_format_with(self, fmt, sensor, |fmt| {
+            let node = self.mainpath_video_node()?;
+            if media::set_video_format(&node, fmt.fourcc())? != fmt.fourcc() {
+                return Err(XCamError::from(
+                    ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+                ));
+            }
+            Ok(())
+        })
+    }
+
+    /// 获取 ISP 主通路输出的像素格式，见 `set_output_format`。
+    ///
+    /// 节点报告的格式不在 `PixelFormat` 中时返回 `XCAM_RETURN_ERROR_FAILED`。
+    pub fn get_output_format(&self) -> XCamResult<PixelFormat> {
+        let (_, _, code) = media::get_video_format(&self.mainpath_video_node()?)?;
+        PixelFormat::from_fourcc(code)
+            .ok_or_else(|| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))
+    }
+
+    /// 设置传感器的合并与裁剪读出方式。
+    ///
+    /// SDK 没有读出方式的接口，本接口在媒体拓扑中查找传感器的子设备节点，以
+    /// `VIDIOC_SUBDEV_S_SELECTION` 设置裁剪区域、以 `VIDIOC_SUBDEV_S_FMT` 选择合并后的输出尺寸，
+    /// 见 `media::set_sensor_readout`。AIQ 在 `prepare` 时读取传感器模式，因此修改须按 `stop`、
+    /// `set_sensor_readout`、`prepare`、`start` 的顺序进行，`prepare` 的宽高应与合并、裁剪后的
+    /// 输出尺寸一致。
+    ///
+    /// 出流期间返回 `XCAM_RETURN_ERROR_ORDER`；裁剪区域无效时返回 `XCAM_RETURN_ERROR_PARAM`；
+    /// 驱动不支持该裁剪区域或合并尺寸时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`；访问节点失败时返回
+    /// `XCAM_RETURN_ERROR_IOCTL`；由 `from_raw_parts` 接管的上下文不知道传感器实体名称时返回
+    /// `XCamError::Unsupported`。
+    pub fn set_sensor_readout(&self, cfg: ReadoutConfig) -> XCamResult<()> {
+        set_sensor_readout_with(self, cfg, |cfg| {
+            if self.sns_ent_name.is_empty() {
+                return Err(XCamError::Unsupported);
+            }
+            media::set_sensor_readout(&media::sensor_subdev_node(&self.sns_ent_name)?, cfg)
+        })
+    }
+
+    pub(crate) fn mainpath_video_node(&self) -> XCamResult<String> {
+        if self.sns_ent_name.is_empty() {
+            return Err(XCamError::Unsupported);
+        }
+        media::mainpath_video_node(&self.sns_ent_name)
+    }
+
+    /// 启动，遇到暂时性错误时按 `policy` 重试。
+    ///
+    /// 每次重试前输出一条警告日志；非暂时性错误或重试次数用尽时返回最后一次的错误。
+    /// 传入 `RetryPolicy::none()` 可恢复不重试的行为。
+    /// 启动成功后写入当前工作模式对应的手动 Gamma 曲线（见 `set_gamma_curve`），
+    /// 再应用 `configure` 延后的设置（见 `take_configure_results`）。
+    pub fn start_with_policy(&self, policy: RetryPolicy) -> XCamResult<()> {
+        schedule::start_then_flush(self, &self.scheduler, || {
+            retry(&policy, "start", || {
+                uapi_call!(ffi::rk_aiq_uapi2_sysctl_start(self))
+            })?;
+            self.streaming.store(true, Ordering::Release);
+            self.apply_gamma_curve();
+            Ok(())
+        })
+    }
+
+    /// 停止，遇到暂时性错误时按 `policy` 重试，见 `start_with_policy`。
+    pub fn stop_with_policy(&self, keep_ext_hw_st: bool, policy: RetryPolicy) -> XCamResult<()> {
+        retry(&policy, "stop", || {
+            uapi_call!(ffi::rk_aiq_uapi2_sysctl_stop(self, keep_ext_hw_st))
+        })?;
+        self.streaming.store(false, Ordering::Release);
+        Ok(())
+    }
+
+    /// 获取 AIQ 最近一次写入传感器模拟增益寄存器的码值，用于传感器特性测量。
+    ///
+    /// 码值取自曝光查询结果中的传感器参数，是 AIQ 交给传感器驱动写入寄存器的原始值，
+    /// 不经过 AIQ 的增益换算；可由 `again_register_to_multiplier` 换算为倍数。
+    /// 只反映线性曝光，HDR 模式下各帧的增益须另行查询。
+    pub fn get_sensor_again_register(&self) -> XCamResult<u32> {
+        let mut info = ffi::Uapi_ExpQueryInfo_t::default();
+        #[cfg(any(feature = "v1_0", feature = "v2_0", feature = "v3_0"))]
+        uapi_call!(ffi::rk_aiq_user_api_ae_queryExpResInfo(self, &mut info))?;
+        #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+        uapi_call!(ffi::rk_aiq_user_api2_ae_queryExpResInfo(self, &mut info))?;
+        Ok(info
+            .CurExpInfo
+            .LinearExp
+            .exp_sensor_params
+            .analog_gain_code_global as u32)
+    }
+
+    /// 设置 3A 的运行间隔，每 `frames` 帧运行一次自动曝光、自动白平衡与自动对焦，用于降低功耗。
+    ///
+    /// SDK 没有设置算法运行间隔的接口，由本库在 `tick_3a_update` 中按帧号切换算法的使能状态：
+    /// 运行帧使能设置时处于使能状态的 3A 算法，其余帧禁用，禁用期间 ISP 保持最近一次的结果，
+    /// 与 `freeze_3a` 相同。间隔越大功耗越低，但光照变化后收敛所需的时间也按比例变长，
+    /// 快速变化的场景中可能出现明显的曝光或色彩滞后。
+    ///
+    /// # Parameters
+    /// * `frames` - 运行间隔，单位：帧，最小为 1（每帧运行），0 按 1 处理。
+    ///   设为 1 时立即恢复暂停中的算法。
+    pub fn set_3a_update_interval(&self, frames: u8) -> XCamResult<()> {
+        set_3a_update_interval(self, &mut self.update_interval.lock().unwrap(), frames)
+    }
+
+    /// 获取 3A 的运行间隔，单位：帧，默认为 1。
+    pub fn get_3a_update_interval(&self) -> u8 {
+        self.update_interval.lock().unwrap().frames
+    }
+
+    /// 按当前帧号暂停或恢复 3A，见 `set_3a_update_interval`，返回本帧 3A 是否运行。
+    ///
+    /// 应在每帧的处理循环中调用；间隔为 1 时不做任何操作。
+    pub fn tick_3a_update(&self) -> XCamResult<bool> {
+        tick_3a_update(self, &mut self.update_interval.lock().unwrap())
+    }
 }
 
 impl SystemControl for Context {
-    fn prepare(&self, width: u32, height: u32, mode: WorkingMode) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_sysctl_prepare(
-                self.internal.as_ptr(),
+    fn prepare(&self, width: u32, height: u32, mode: WorkingMode) -> XCamResult<PrepareResult> {
+        uapi_call!(ffi::rk_aiq_uapi2_sysctl_prepare(
+            self,
+            width,
+            height,
+            mode.into()
+        ))?;
+        // 准备已成功，传感器描述读取失败时按请求值报告
+        let mut des = SensorDescriptor::default();
+        let r = uapi_call!(ffi::rk_aiq_uapi2_sysctl_getSensorDiscrib(self, &mut des));
+        let result = match r {
+            Ok(_) => PrepareResult::from_descriptor(&des, mode),
+            Err(_) => PrepareResult {
                 width,
                 height,
-                mode.into(),
-            ))
-            .ok()
-        }
+                mode,
+                format: 0,
+            },
+        };
+        *self.prepared.lock().unwrap() = Some(result);
+        Ok(result)
     }
 
+    /// 启动，设备忙时按 `RetryPolicy::default()` 重试，见 `Context::start_with_policy`。
     fn start(&self) -> XCamResult<()> {
-        unsafe { XCamError::from(ffi::rk_aiq_uapi2_sysctl_start(self.internal.as_ptr())).ok() }
+        self.start_with_policy(RetryPolicy::default())
     }
 
+    /// 停止，设备忙时按 `RetryPolicy::default()` 重试，见 `Context::stop_with_policy`。
     fn stop(&self, keep_ext_hw_st: bool) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_sysctl_stop(
-                self.internal.as_ptr(),
-                keep_ext_hw_st,
-            ))
-            .ok()
-        }
+        self.stop_with_policy(keep_ext_hw_st, RetryPolicy::default())
+    }
+
+    fn is_streaming(&self) -> bool {
+        self.streaming.load(Ordering::Acquire)
     }
 
     fn enable_module<T: Into<ModuleId>>(&self, id: T) -> XCamResult<()> {
-        unsafe {
+        self.guard_device(|| unsafe {
             XCamError::from(ffi::rk_aiq_uapi2_sysctl_setModuleCtl(
                 self.internal.as_ptr(),
                 id.into(),
                 true,
             ))
-            .ok()
-        }
+            .ok_or_bypass()
+        })
     }
 
     fn disable_module<T: Into<ModuleId>>(&self, id: T) -> XCamResult<()> {
-        unsafe {
+        self.guard_device(|| unsafe {
             XCamError::from(ffi::rk_aiq_uapi2_sysctl_setModuleCtl(
                 self.internal.as_ptr(),
                 id.into(),
                 false,
             ))
-            .ok()
-        }
+            .ok_or_bypass()
+        })
     }
 
     fn is_module_enabled<T: Into<ModuleId>>(&self, id: T) -> bool {
         let mut enabled = false;
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_sysctl_getModuleCtl(
-                self.internal.as_ptr(),
-                id.into(),
-                &mut enabled,
-            ))
-            .ok()
-            .map_or(false, |_| enabled)
-        }
+        uapi_call!(ffi::rk_aiq_uapi2_sysctl_getModuleCtl(
+            self,
+            id.into(),
+            &mut enabled
+        ))
+        .map_or(false, |_| enabled)
     }
 
     fn register_lib(&self, mut algo_lib_des: AlgoDescComm) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_sysctl_regLib(
-                self.internal.as_ptr(),
-                &mut algo_lib_des,
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi_sysctl_regLib(self, &mut algo_lib_des))
     }
 
     fn unregister_lib(&self, algo_type: i32, lib_id: i32) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_sysctl_unRegLib(
-                self.internal.as_ptr(),
-                algo_type,
-                lib_id,
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi_sysctl_unRegLib(self, algo_type, lib_id))
     }
 
     fn enable_ax_lib(&self, algo_type: i32, lib_id: i32) -> XCamResult<()> {
-        unsafe {
+        self.guard_device(|| unsafe {
             XCamError::from(ffi::rk_aiq_uapi2_sysctl_enableAxlib(
                 self.internal.as_ptr(),
                 algo_type,
                 lib_id,
                 true,
             ))
-            .ok()
-        }
+            .ok_or_bypass()
+        })
     }
 
     fn disable_ax_lib(&self, algo_type: i32, lib_id: i32) -> XCamResult<()> {
-        unsafe {
+        self.guard_device(|| unsafe {
             XCamError::from(ffi::rk_aiq_uapi2_sysctl_enableAxlib(
                 self.internal.as_ptr(),
                 algo_type,
                 lib_id,
                 false,
             ))
-            .ok()
-        }
+            .ok_or_bypass()
+        })
     }
 
     fn is_ax_lib_enabled(&self, algo_type: i32, lib_id: i32) -> bool {
-        unsafe { ffi::rk_aiq_uapi_sysctl_getAxlibStatus(self.internal.as_ptr(), algo_type, lib_id) }
+        // 掉线后不再调用 SDK，按未使能报告。
+        !self.device.is_lost()
+            && unsafe {
+                ffi::rk_aiq_uapi_sysctl_getAxlibStatus(self.internal.as_ptr(), algo_type, lib_id)
+            }
     }
 
     unsafe fn get_enabled_ax_lib_ctx(&self, algo_type: i32) -> *const AlgoContext {
@@ -202,70 +1178,64 @@ impl SystemControl for Context {
 
     fn get_cps_lt_info(&self) -> XCamResult<CpslInfo> {
         let mut info = CpslInfo::default();
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_sysctl_getCpsLtInfo(
-                self.internal.as_ptr(),
-                &mut info,
-            ))
-            .ok()
-            .map(|_| info)
-        }
+        uapi_call!(ffi::rk_aiq_uapi2_sysctl_getCpsLtInfo(self, &mut info)).map(|_| info)
     }
 
     fn query_cps_lt_cap(&self) -> XCamResult<CpslCap> {
         let mut cap = CpslCap::default();
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_sysctl_queryCpsLtCap(
-                self.internal.as_ptr(),
-                &mut cap,
-            ))
-            .ok()
-            .map(|_| cap)
-        }
+        uapi_call!(ffi::rk_aiq_uapi2_sysctl_queryCpsLtCap(self, &mut cap)).map(|_| cap)
     }
 
     fn set_cps_lt_cfg<T: Into<CpslCfg>>(&self, cfg: T) -> XCamResult<()> {
         let mut cfg = cfg.into();
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_sysctl_setCpsLtCfg(
-                self.internal.as_ptr(),
-                &mut cfg,
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi2_sysctl_setCpsLtCfg(self, &mut cfg))
     }
 
     fn update_iq<T: Into<Vec<u8>>>(&self, iq_file: T) -> XCamResult<()> {
         let iq_file = CString::new(iq_file).unwrap();
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_sysctl_updateIq(
-                self.internal.as_ptr(),
-                iq_file.as_ptr() as *mut _,
-            ))
-            .ok()
-        }
+        uapi_call!(ffi::rk_aiq_uapi2_sysctl_updateIq(
+            self,
+            iq_file.as_ptr() as *mut _
+        ))
     }
 
     fn get_crop(&self) -> XCamResult<Rect> {
         let mut crop = Rect::default();
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi2_sysctl_getCrop(
-                self.internal.as_ptr(),
-                &mut crop,
-            ))
-            .ok()
-            .map(|_| crop)
-        }
+        uapi_call!(ffi::rk_aiq_uapi2_sysctl_getCrop(self, &mut crop)).map(|_| crop)
     }
 
     fn set_crop(&self, crop: Rect) -> XCamResult<()> {
-        unsafe {
-            XCamError::from(ffi::rk_aiq_uapi_sysctl_setCrop(
-                self.internal.as_ptr(),
-                crop,
-            ))
-            .ok()
+        uapi_call!(ffi::rk_aiq_uapi_sysctl_setCrop(self, crop))
+    }
+
+    fn get_3a_stats(&self, timeout: Duration) -> XCamResult<IspStats> {
+        let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        let mut stats: *mut IspStats = std::ptr::null_mut();
+        uapi_call!(ffi::rk_aiq_uapi2_sysctl_get3AStatsBlk(
+            self, &mut stats, timeout_ms
+        ))?;
+        if stats.is_null() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED));
         }
+        let out = unsafe {
+            let out = *stats;
+            ffi::rk_aiq_uapi2_sysctl_release3AStatsRef(self.internal.as_ptr(), stats);
+            out
+        };
+        Ok(self.stats_avg.lock().unwrap().apply(out))
+    }
+
+    fn get_isp_error_flags(&self) -> XCamResult<IspErrorFlags> {
+        Ok(context::isp_error_flags())
+    }
+
+    fn clear_isp_error_flags(&self) -> XCamResult<()> {
+        context::clear_isp_error_flags();
+        Ok(())
+    }
+
+    fn get_frame_id(&self) -> Option<u32> {
+        context::last_frame_id()
     }
 }
 
@@ -299,6 +1269,35 @@ pub fn get_static_metas<T: Into<Vec<u8>>>(vd: T) -> XCamResult<StaticInfo> {
     }
 }
 
+/// 取出传感器的默认模式 `(分辨率, 帧率)`。
+///
+/// 默认模式为传感器驱动所列输出格式中的第一个线性格式，即驱动上电后的初始模式；
+/// 没有有效的线性格式时返回 `XCamError::Unsupported`。
+pub fn default_mode_from_static_info(info: &StaticInfo) -> XCamResult<(Resolution, f32)> {
+    let sensor = &info.sensor_info;
+    let count = (sensor.num as usize).min(sensor.support_fmt.len());
+    sensor.support_fmt[..count]
+        .iter()
+        .find(|fmt| {
+            HdrReadout::from_sensor_hdr_mode(fmt.hdr_mode) == Some(HdrReadout::Linear)
+                && fmt.width > 0
+                && fmt.height > 0
+        })
+        .map(|fmt| {
+            let resolution = Resolution {
+                width: fmt.width as u32,
+                height: fmt.height as u32,
+            };
+            (resolution, fmt.fps as f32)
+        })
+        .ok_or(XCamError::Unsupported)
+}
+
+/// 查询传感器 `sensor` 的默认模式，见 `default_mode_from_static_info`。
+pub fn default_mode_for(sensor: &str) -> XCamResult<(Resolution, f32)> {
+    default_mode_from_static_info(&get_static_metas(sensor)?)
+}
+
 /// 枚举 AIQ 获取到的静态信息。
 ///
 /// # Parameters
@@ -388,12 +1387,554 @@ mod tests {
         let r = Context::new("m00_b_ov5695 4-0036-1", "/etc/iqfiles");
         assert_eq!(r.is_ok(), true);
         if let Ok(ctx) = r {
-            assert_eq!(ctx.prepare(2592, 1944, WorkingMode::Normal), Ok(()));
+            assert!(ctx.prepare(2592, 1944, WorkingMode::Normal).is_ok());
             assert_eq!(ctx.start(), Ok(()));
             assert_eq!(ctx.stop(false), Ok(()));
         }
     }
 
+    #[test]
+    fn test_isp_error_flags_from_err_code() {
+        use ffi::XCamReturn::*;
+        assert_eq!(
+            IspErrorFlags::from_err_code(XCAM_RETURN_NO_ERROR as i32),
+            IspErrorFlags::empty()
+        );
+        assert_eq!(
+            IspErrorFlags::from_err_code(XCAM_RETURN_ERROR_ISP as i32),
+            IspErrorFlags::ISP
+        );
+        assert_eq!(
+            IspErrorFlags::from_err_code(XCAM_RETURN_ERROR_SENSOR as i32),
+            IspErrorFlags::SENSOR
+        );
+        assert_eq!(
+            IspErrorFlags::from_err_code(XCAM_RETURN_ERROR_TIMEOUT as i32),
+            IspErrorFlags::TIMEOUT
+        );
+        assert_eq!(IspErrorFlags::from_err_code(-100), IspErrorFlags::OTHER);
+    }
+
+    #[test]
+    fn test_clear_isp_error_flags() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().isp_error_flags = IspErrorFlags::ISP | IspErrorFlags::SENSOR;
+        assert_eq!(
+            ctx.get_isp_error_flags(),
+            Ok(IspErrorFlags::ISP | IspErrorFlags::SENSOR)
+        );
+        assert_eq!(ctx.clear_isp_error_flags(), Ok(()));
+        assert_eq!(ctx.calls().last(), Some(&"clear_isp_error_flags"));
+        assert_eq!(ctx.get_isp_error_flags(), Ok(IspErrorFlags::empty()));
+    }
+
+    #[test]
+    fn test_stability_checks() {
+        let gain = WbGain {
+            rgain: 1.8,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 2.1,
+        };
+        assert!(awb_stable(&gain, &gain));
+        let drifted = WbGain { rgain: 1.9, ..gain };
+        assert!(!awb_stable(&gain, &drifted));
+        assert!(af_stable(OpMode::Manual, AfSearchState::Running));
+        assert!(!af_stable(OpMode::Auto, AfSearchState::Running));
+        assert!(af_stable(OpMode::Auto, AfSearchState::Done));
+
+        let mut tracker = WarmupTracker::default();
+        let exp = ExpInfo {
+            converged: true,
+            ..Default::default()
+        };
+        let focus = (OpMode::Auto, AfSearchState::Done);
+        // 第一帧没有可比较的白平衡增益。
+        assert!(!tracker.update(&exp, gain, focus));
+        assert!(tracker.update(&exp, gain, focus));
+        assert!(!tracker.update(&exp, drifted, focus));
+    }
+
+    #[test]
+    fn test_warmup_until_converged() {
+        use crate::mock::{MockContext, MockState};
+        // AE 在第 4 帧收敛，AF 在第 3 帧完成，AWB 增益在第 6 帧后不再变化。
+        fn on_frame(id: u32, state: &mut MockState) {
+            state.exp_info.converged = id >= 4;
+            state.af_search_state = if id >= 3 {
+                AfSearchState::Done
+            } else {
+                AfSearchState::Running
+            };
+            state.wb_gain.rgain = 1.0 + 0.1 * 6u32.saturating_sub(id) as f32;
+        }
+        let ctx = MockContext::new();
+        {
+            let mut state = ctx.state.borrow_mut();
+            state.frame_step = 1;
+            state.on_frame = Some(on_frame);
+        }
+        assert_eq!(ctx.warmup(30), Ok(()));
+        assert_eq!(ctx.state.borrow().frame_id, Some(7));
+    }
+
+    #[test]
+    fn test_warmup_frame_budget() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().frame_step = 1;
+        assert_eq!(ctx.warmup(10), Ok(()));
+        assert_eq!(ctx.state.borrow().frame_id, Some(10));
+
+        // 跳过的帧同样计入预算。
+        let ctx = MockContext::new();
+        ctx.state.borrow_mut().frame_step = 3;
+        assert_eq!(ctx.warmup(10), Ok(()));
+        assert_eq!(ctx.state.borrow().frame_id, Some(12));
+        assert_eq!(ctx.warmup(0), Ok(()));
+    }
+
+    #[test]
+    fn test_freeze_3a_restores_prior_mask() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        let before = AlgoMask::all() - AlgoMask::AF - AlgoMask::ADHAZ;
+        assert_eq!(ctx.set_algo_mask(before), Ok(()));
+        {
+            let guard = ctx.freeze_3a().unwrap();
+            assert_eq!(guard.prior(), AlgoMask::AEC | AlgoMask::AWB);
+            assert_eq!(ctx.get_algo_mask(), before - FROZEN_3A);
+        }
+        assert_eq!(ctx.get_algo_mask(), before);
+
+        // 释放时的错误通过 `release` 返回。
+        let guard = ctx.freeze_3a().unwrap();
+        ctx.fail("enable_ax_lib");
+        let err = guard.release().unwrap_err();
+        assert_eq!(err.failed, AlgoMask::AEC | AlgoMask::AWB);
+        assert_eq!(ctx.get_algo_mask(), before - FROZEN_3A);
+    }
+
+    #[test]
+    fn test_set_algo_mask() {
+        use crate::mock::MockContext;
+        use ffi::RkAiqAlgoType_t::*;
+        let ctx = MockContext::new();
+        let mask = AlgoMask::AWB | AlgoMask::ACCM;
+        assert_eq!(ctx.set_algo_mask(mask), Ok(()));
+        let toggles = ctx.state.borrow().ax_libs.clone();
+        let (disabled, enabled): (Vec<_>, Vec<_>) =
+            toggles.iter().copied().partition(|(_, on)| !on);
+        // 先禁用其余算法，再按依赖顺序使能。
+        assert!(toggles.iter().skip(disabled.len()).all(|(_, on)| *on));
+        assert_eq!(
+            enabled,
+            vec![
+                (RK_AIQ_ALGO_TYPE_AWB as i32, true),
+                (RK_AIQ_ALGO_TYPE_ACCM as i32, true)
+            ]
+        );
+        assert_eq!(
+            disabled.first(),
+            Some(&(RK_AIQ_ALGO_TYPE_AGAMMA as i32, false))
+        );
+        assert!(!disabled
+            .iter()
+            .any(|(t, _)| *t == RK_AIQ_ALGO_TYPE_AWB as i32 || *t == RK_AIQ_ALGO_TYPE_ACCM as i32));
+        assert_eq!(ctx.get_algo_mask(), mask);
+
+        // 部分失败时继续处理其余算法。
+        ctx.fail("enable_ax_lib");
+        let err = ctx
+            .set_algo_mask(AlgoMask::AEC | AlgoMask::AWB)
+            .unwrap_err();
+        assert_eq!(err.failed, AlgoMask::AEC | AlgoMask::AWB);
+        assert_eq!(ctx.get_algo_mask(), AlgoMask::AWB);
+    }
+
+    #[test]
+    fn test_output_format_streaming_guard() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        let mut applied = Vec::new();
+        let mut apply = |fmt: PixelFormat| -> XCamResult<()> {
+            applied.push(fmt);
+            Ok(())
+        };
+        ctx.start().unwrap();
+        assert_eq!(
+            set_output_format_with(&ctx, PixelFormat::Nv12, None, &mut apply),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))
+        );
+        ctx.stop(false).unwrap();
+        assert_eq!(
+            set_output_format_with(&ctx, PixelFormat::Nv12, None, &mut apply),
+            Ok(())
+        );
+
+        // RAW 格式须与传感器输出一致。
+        let sensor = RawFormat {
+            bayer: BayerPattern::Rggb,
+            depth: RawBitDepth::Bits10,
+        };
+        let raw12 = PixelFormat::Raw(RawFormat {
+            depth: RawBitDepth::Bits12,
+            ..sensor
+        });
+        assert_eq!(
+            set_output_format_with(&ctx, raw12, Some(sensor), &mut apply),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        assert_eq!(
+            set_output_format_with(&ctx, PixelFormat::Raw(sensor), Some(sensor), &mut apply),
+            Ok(())
+        );
+        assert_eq!(applied, [PixelFormat::Nv12, PixelFormat::Raw(sensor)]);
+    }
+
+    #[test]
+    fn test_binning_conversion() {
+        for b in Binning::ALL {
+            assert_eq!(b.to_string().parse::<Binning>(), Ok(b));
+        }
+        assert_eq!(Binning::default(), Binning::None);
+        assert_eq!(Binning::None.output_size((3840, 2160)), (3840, 2160));
+        assert_eq!(Binning::V2H2.output_size((3840, 2160)), (1920, 1080));
+        assert_eq!(Binning::V4H4.output_size((3840, 2160)), (960, 540));
+    }
+
+    #[test]
+    fn test_sensor_readout_streaming_guard() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        let mut applied = Vec::new();
+        let mut apply = |cfg: ReadoutConfig| -> XCamResult<()> {
+            applied.push(cfg);
+            Ok(())
+        };
+        let cfg = ReadoutConfig {
+            binning: Binning::V2H2,
+            crop: None,
+        };
+        ctx.start().unwrap();
+        assert_eq!(
+            set_sensor_readout_with(&ctx, cfg, &mut apply),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_ORDER))
+        );
+        ctx.stop(false).unwrap();
+        assert_eq!(set_sensor_readout_with(&ctx, cfg, &mut apply), Ok(()));
+
+        // 裁剪区域须在画面内且非空。
+        let crop = |left, top, width, height| ReadoutConfig {
+            binning: Binning::None,
+            crop: Some(Rect {
+                left,
+                top,
+                width,
+                height,
+            }),
+        };
+        for bad in [crop(-8, 0, 1920, 1080), crop(0, 0, 0, 1080)] {
+            assert_eq!(
+                set_sensor_readout_with(&ctx, bad, &mut apply),
+                Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+            );
+        }
+        assert_eq!(
+            set_sensor_readout_with(&ctx, crop(960, 540, 1920, 1080), &mut apply),
+            Ok(())
+        );
+        assert_eq!(applied, [cfg, crop(960, 540, 1920, 1080)]);
+    }
+
+    #[test]
+    fn test_3a_update_interval() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        let before = AlgoMask::all() - AlgoMask::AF;
+        assert_eq!(ctx.set_algo_mask(before), Ok(()));
+        let mut state = UpdateInterval::default();
+        set_3a_update_interval(&ctx, &mut state, 0).unwrap();
+        assert_eq!(state.frames, 1);
+        assert_eq!(tick_3a_update(&ctx, &mut state), Ok(true));
+
+        set_3a_update_interval(&ctx, &mut state, 3).unwrap();
+        assert_eq!(state.frames, 3);
+        let mut runs = Vec::new();
+        let paused_types: Vec<i32> = AlgoMask::algo_types()[..2]
+            .iter()
+            .flat_map(|(_, types)| types.iter().copied())
+            .collect();
+        for id in 10..17 {
+            ctx.state.borrow_mut().frame_id = Some(id);
+            let written = ctx.state.borrow().ax_libs.len();
+            runs.push(tick_3a_update(&ctx, &mut state).unwrap());
+            let expected = if runs[runs.len() - 1] {
+                before
+            } else {
+                before - AlgoMask::AEC - AlgoMask::AWB
+            };
+            assert_eq!(ctx.get_algo_mask(), expected);
+
+            // 只在暂停与运行之间切换时写入，且只写入被暂停的算法。
+            let switched = runs.len() > 1 && runs[runs.len() - 1] != runs[runs.len() - 2];
+            let st = ctx.state.borrow();
+            let writes = &st.ax_libs[written..];
+            assert_eq!(writes.len(), if switched { paused_types.len() } else { 0 });
+            assert!(writes.iter().all(|(t, _)| paused_types.contains(t)));
+        }
+        assert_eq!(runs, [true, false, false, true, false, false, true]);
+
+        // 恢复为每帧运行时重新使能暂停中的算法。
+        ctx.state.borrow_mut().frame_id = Some(17);
+        assert_eq!(tick_3a_update(&ctx, &mut state), Ok(false));
+        set_3a_update_interval(&ctx, &mut state, 1).unwrap();
+        assert_eq!(state.frames, 1);
+        assert_eq!(ctx.get_algo_mask(), before);
+    }
+
+    #[test]
+    fn test_try_get_3a_stats() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        assert_eq!(ctx.try_get_3a_stats(), Err(XCamError::Busy));
+        assert_eq!(
+            ctx.get_3a_stats(Duration::from_millis(40)),
+            Err(XCamError::Timeout)
+        );
+        ctx.state.borrow_mut().stats = Some(IspStats::default());
+        assert_eq!(ctx.try_get_3a_stats(), Ok(IspStats::default()));
+
+        ctx.fail("get_3a_stats");
+        assert_eq!(
+            ctx.try_get_3a_stats(),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED))
+        );
+    }
+
+    #[test]
+    fn test_again_register_to_multiplier() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-3;
+        // 索尼 0.3 dB 步长：20 个码值为 6 dB，约 2 倍。
+        assert_eq!(again_register_to_multiplier(0, "m00_b_imx415 3-001a"), 1.0);
+        assert!(close(
+            again_register_to_multiplier(20, "m00_b_imx415 3-001a"),
+            1.9953
+        ));
+        assert!(close(again_register_to_multiplier(200, "IMX335"), 1000.0));
+        // IMX219：256 / (256 - reg)。
+        assert_eq!(
+            again_register_to_multiplier(128, "m01_f_imx219 1-0010"),
+            2.0
+        );
+        assert!(close(again_register_to_multiplier(232, "imx219"), 10.6667));
+        assert!(again_register_to_multiplier(256, "imx219").is_nan());
+        // OV5647：reg / 16。
+        assert_eq!(again_register_to_multiplier(40, "ov5647"), 2.5);
+        assert!(again_register_to_multiplier(16, "m00_b_ov5695 4-0036-1").is_nan());
+        assert_eq!(again_code_for_sensor("unknown"), None);
+    }
+
+    #[test]
+    fn test_prepare_negotiated() {
+        use crate::mock::{MockContext, MOCK_PIXEL_FORMAT};
+        let ctx = MockContext::new();
+        let r = ctx.prepare(1920, 1080, WorkingMode::Normal).unwrap();
+        assert!(r.matches(1920, 1080));
+
+        // 传感器没有 1080p 模式，选择了 2x2 合并后的 1296x972。
+        ctx.state.borrow_mut().negotiated = Some((1296, 972));
+        let r = ctx.prepare(1920, 1080, WorkingMode::IspHdr2).unwrap();
+        assert_eq!(
+            r,
+            PrepareResult {
+                width: 1296,
+                height: 972,
+                mode: WorkingMode::IspHdr2,
+                format: MOCK_PIXEL_FORMAT,
+            }
+        );
+        assert!(!r.matches(1920, 1080));
+
+        let des = SensorDescriptor {
+            sensor_output_width: 2592,
+            sensor_output_height: 1944,
+            sensor_pixelformat: MOCK_PIXEL_FORMAT,
+            ..Default::default()
+        };
+        let r = PrepareResult::from_descriptor(&des, WorkingMode::Normal);
+        assert!(r.matches(2592, 1944));
+        assert_eq!(r.format, MOCK_PIXEL_FORMAT);
+    }
+
+    #[test]
+    fn test_default_mode_from_static_info() {
+        // 驱动先列出 HDR 模式，默认模式为其后的第一个线性模式。
+        let mut info = StaticInfo::default();
+        assert_eq!(
+            default_mode_from_static_info(&info),
+            Err(XCamError::Unsupported)
+        );
+        let modes = [
+            (2688, 1520, 25, 5),
+            (2688, 1520, 30, 0),
+            (1920, 1080, 60, 0),
+        ];
+        for (fmt, (width, height, fps, hdr_mode)) in
+            info.sensor_info.support_fmt.iter_mut().zip(modes)
+        {
+            fmt.width = width;
+            fmt.height = height;
+            fmt.fps = fps;
+            fmt.hdr_mode = hdr_mode;
+        }
+        info.sensor_info.num = modes.len() as _;
+        let (resolution, fps) = default_mode_from_static_info(&info).unwrap();
+        assert_eq!(
+            resolution,
+            Resolution {
+                width: 2688,
+                height: 1520
+            }
+        );
+        assert_eq!(resolution.to_string(), "2688x1520");
+        assert_eq!(fps, 30.0);
+
+        info.sensor_info.num = 1;
+        assert_eq!(
+            default_mode_from_static_info(&info),
+            Err(XCamError::Unsupported)
+        );
+    }
+
+    #[test]
+    fn test_wait_for_frame() {
+        use crate::mock::{MockClock, MockContext};
+        let ctx = MockContext::new();
+        let clock = MockClock::new();
+        // 尚未收到元数据回调时等满超时。
+        assert_eq!(
+            wait_for_frame_with(&ctx, 3, Duration::from_millis(9), &clock),
+            Err(XCamError::Timeout)
+        );
+        assert_eq!(clock.elapsed(), Duration::from_millis(9));
+
+        // 每次读取帧号前进一帧，目标帧号跨越回绕。
+        {
+            let mut state = ctx.state.borrow_mut();
+            state.frame_id = Some(u32::MAX - 2);
+            state.frame_step = 1;
+        }
+        wait_for_frame_with(&ctx, 2, Duration::from_secs(1), &clock).unwrap();
+        assert_eq!(ctx.state.borrow().frame_id, Some(2));
+        // 目标帧号已经过去时立即返回。
+        let before = clock.elapsed();
+        wait_for_frame_with(&ctx, u32::MAX, Duration::from_secs(1), &clock).unwrap();
+        assert_eq!(clock.elapsed(), before);
+
+        // 帧号停止增长时超时。
+        ctx.state.borrow_mut().frame_step = 0;
+        assert_eq!(
+            wait_for_frame_with(&ctx, 100, Duration::from_millis(50), &clock),
+            Err(XCamError::Timeout)
+        );
+        assert!(!frame_reached(5, 6));
+        assert!(frame_reached(u32::MAX, u32::MAX - 1));
+        assert!(frame_reached(1, u32::MAX));
+    }
+
+    #[test]
+    fn test_negotiated_format() {
+        assert_eq!(
+            bayer_pattern_from_bus_code(0x3007),
+            Some(BayerPattern::Bggr)
+        );
+        assert_eq!(
+            bayer_pattern_from_bus_code(0x3001),
+            Some(BayerPattern::Bggr)
+        );
+        assert_eq!(
+            bayer_pattern_from_bus_code(0x300a),
+            Some(BayerPattern::Grbg)
+        );
+        assert_eq!(
+            bayer_pattern_from_bus_code(0x3010),
+            Some(BayerPattern::Gbrg)
+        );
+        assert_eq!(
+            bayer_pattern_from_bus_code(0x3020),
+            Some(BayerPattern::Rggb)
+        );
+        // YUYV8_2X8 不是 Bayer 格式。
+        assert_eq!(bayer_pattern_from_bus_code(0x2008), None);
+        assert_eq!(bayer_pattern_from_bus_code(0), None);
+
+        let f = NegotiatedFormat::from(PrepareResult {
+            width: 1296,
+            height: 972,
+            mode: WorkingMode::Normal,
+            format: 0x300f,
+        });
+        assert_eq!((f.width, f.height, f.format), (1296, 972, 0x300f));
+        assert_eq!(f.bayer, Some(BayerPattern::Rggb));
+        assert_eq!(f.bayer.unwrap().to_string(), "rggb");
+        assert_eq!(
+            f.raw_format(),
+            Some(RawFormat {
+                bayer: BayerPattern::Rggb,
+                depth: RawBitDepth::Bits10,
+            })
+        );
+        // V4L2_PIX_FMT_SGBRG12
+        let f = NegotiatedFormat::from(PrepareResult {
+            width: 1920,
+            height: 1080,
+            mode: WorkingMode::Normal,
+            format: 0x3231_4247,
+        });
+        assert_eq!(f.bayer, Some(BayerPattern::Gbrg));
+        assert_eq!(f.depth, Some(RawBitDepth::Bits12));
+    }
+
+    #[test]
+    fn test_update_iq_from_buffer() {
+        use crate::mock::MockContext;
+        let iq = include_bytes!("../tests/fixtures/ov5695_buffer_iq.json");
+        let ctx = MockContext::new();
+        ctx.update_iq_from_buffer(iq).unwrap();
+        assert_eq!(ctx.calls(), ["update_iq"]);
+        assert_eq!(ctx.state.borrow().iq_loaded.as_deref(), Some(&iq[..]));
+
+        // 无法解析的内容不会交给 SDK。
+        assert_eq!(
+            ctx.update_iq_from_buffer(&iq[..iq.len() / 2]),
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        assert_eq!(ctx.calls().len(), 1);
+    }
+
+    #[test]
+    fn test_isp_clock() {
+        use crate::mock::MockContext;
+        let ctx = MockContext::new();
+        assert_eq!(ctx.get_isp_clock(), Err(XCamError::Unsupported));
+        assert_eq!(ctx.set_isp_clock(300), Err(XCamError::Unsupported));
+
+        ctx.state.borrow_mut().isp_clock = Some((vec![200, 300, 400], 400));
+        assert_eq!(ctx.get_isp_clock_rates(), Ok(vec![200, 300, 400]));
+        ctx.set_isp_clock(200).unwrap();
+        assert_eq!(ctx.get_isp_clock(), Ok(200));
+        // 平台不支持的频率被拒绝，当前频率不变。
+        assert_eq!(
+            ctx.set_isp_clock(250),
+            Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE
+            ))
+        );
+        assert_eq!(ctx.get_isp_clock(), Ok(200));
+    }
+
     #[test]
     fn test_get_binded_sensor_entity_name() {
         let r = get_binded_sensor_entity_name("/dev/video0");
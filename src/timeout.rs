@@ -0,0 +1,287 @@
+//! 操作超时
+//!
+//! 驱动异常时部分 SDK 接口可能一直阻塞。本模块在工作线程中执行调用，
+//! 等待超时后立即返回 `XCamError::Timeout`。
+//!
+//! 注意：超时并不会中止原调用，它仍可能在工作线程中继续运行直至自行返回。
+//!
+//! 属性读取与信息查询接口需要获取对应算法的配置锁，分析线程处理一帧期间持有该锁，
+//! 因此正常出流时这些接口最长阻塞约一个帧周期，驱动异常时可能一直阻塞。
+//! SDK 提供非阻塞方式的接口有对应的 `try_*` 版本，未就绪时返回 `XCamError::Busy`；
+//! 其余接口可通过 `Context::with_timeout` 限制等待时间。
+//!
+//! 传感器接线错误时 `rk_aiq_uapi2_sysctl_init` 与 `prepare` 可能探测硬件数秒之久，
+//! `ContextBuilder::build_with_timeout` 在工作线程中完成创建，并可通过 `CancelToken` 在两步之间取消。
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::sysctl::SystemControl;
+use super::types::XCamResult;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// 在工作线程中执行 `f`，超过 `d` 仍未完成时返回 `XCamError::Timeout`。
+///
+/// 超时后 `f` 仍在工作线程中运行，其结果被丢弃。
+pub fn with_timeout<T, F>(d: Duration, f: F) -> XCamResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> XCamResult<T> + Send + 'static,
+{
+    let (tx, rx) = mpsc::sync_channel(1);
+    thread::Builder::new()
+        .name("rkaiq-timeout".into())
+        .spawn(move || {
+            let _ = tx.send(f());
+        })
+        .map_err(|_| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_THREAD))?;
+    match rx.recv_timeout(d) {
+        Ok(r) => r,
+        Err(RecvTimeoutError::Timeout) => Err(XCamError::Timeout),
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_THREAD))
+        }
+    }
+}
+
+/// 一个用于取消 `ContextBuilder::build_with_timeout` 的句柄，可跨线程克隆。
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 请求取消。已开始的 SDK 调用不会被中止，取消在下一步骤开始前生效。
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// 是否已请求取消。
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// 在工作线程中依次执行 `init` 与 `prepare`，见 `ContextBuilder::build_with_timeout`。
+///
+/// 每一步开始前检查 `cancel`，已取消时返回 `XCamError::Cancelled`。`init` 的结果在取消、
+/// `prepare` 失败或等待超时后由工作线程丢弃，即在原调用返回时释放。
+pub(crate) fn init_with_timeout<T, I, P>(
+    d: Duration,
+    cancel: Option<CancelToken>,
+    init: I,
+    prepare: P,
+) -> XCamResult<T>
+where
+    T: Send + 'static,
+    I: FnOnce() -> XCamResult<T> + Send + 'static,
+    P: FnOnce(&T) -> XCamResult<()> + Send + 'static,
+{
+    with_timeout(d, move || {
+        let cancelled = || cancel.as_ref().is_some_and(CancelToken::is_cancelled);
+        if cancelled() {
+            return Err(XCamError::Cancelled);
+        }
+        let ctx = init()?;
+        if cancelled() {
+            return Err(XCamError::Cancelled);
+        }
+        prepare(&ctx)?;
+        Ok(ctx)
+    })
+}
+
+/// 一个以超时调用上下文接口的类型，由 `Context::with_timeout` 创建。
+pub struct Timed<C> {
+    ctx: Arc<C>,
+    timeout: Duration,
+}
+
+impl<C: Send + Sync + 'static> Timed<C> {
+    pub fn new(ctx: &Arc<C>, timeout: Duration) -> Self {
+        Self {
+            ctx: Arc::clone(ctx),
+            timeout,
+        }
+    }
+
+    /// 在工作线程中执行 `f`，超时后返回 `XCamError::Timeout`。
+    ///
+    /// 工作线程持有上下文的引用，超时后上下文在原调用返回前不会被释放。
+    pub fn call<T, F>(&self, f: F) -> XCamResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&C) -> XCamResult<T> + Send + 'static,
+    {
+        let ctx = Arc::clone(&self.ctx);
+        with_timeout(self.timeout, move || f(&ctx))
+    }
+}
+
+impl Context {
+    /// 返回以超时 `d` 调用本上下文接口的包装，例如：
+    ///
+    /// ```ignore
+    /// let info = ctx
+    ///     .with_timeout(Duration::from_millis(50))
+    ///     .call(|ctx| ctx.query_exp_info())?;
+    /// ```
+    pub fn with_timeout(self: &Arc<Self>, d: Duration) -> Timed<Self> {
+        Timed::new(self, d)
+    }
+
+    /// 创建上下文，超过 `d` 仍未完成时返回 `io::ErrorKind::TimedOut`。
+    ///
+    /// 超时后初始化仍在后台进行，完成后创建的上下文会被立即释放。
+    pub fn new_with_timeout(
+        sns_ent_name: &str,
+        iq_file_dir: &str,
+        d: Duration,
+    ) -> Result<Self, io::Error> {
+        let sns_ent_name = sns_ent_name.to_owned();
+        let iq_file_dir = iq_file_dir.to_owned();
+        with_timeout(d, move || Ok(Self::new(&sns_ent_name, &iq_file_dir)))
+            .map_err(|e| io::Error::new(io::ErrorKind::TimedOut, e))?
+    }
+
+    /// 启动 AIQ，超过 `d` 仍未完成时返回 `XCamError::Timeout`。
+    ///
+    /// 工作线程持有上下文的引用，超时后上下文在原调用返回前不会被释放。
+    pub fn start_with_timeout(self: &Arc<Self>, d: Duration) -> XCamResult<()> {
+        let ctx = Arc::clone(self);
+        with_timeout(d, move || ctx.start())
+    }
+
+    /// 停止 AIQ，超过 `d` 仍未完成时返回 `XCamError::Timeout`。
+    ///
+    /// 工作线程持有上下文的引用，超时后上下文在原调用返回前不会被释放。
+    pub fn stop_with_timeout(
+        self: &Arc<Self>,
+        keep_ext_hw_st: bool,
+        d: Duration,
+    ) -> XCamResult<()> {
+        let ctx = Arc::clone(self);
+        with_timeout(d, move || ctx.stop(keep_ext_hw_st))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_timeout_slow_call() {
+        let r = with_timeout(Duration::from_millis(20), || {
+            thread::sleep(Duration::from_millis(500));
+            Ok(())
+        });
+        assert_eq!(r, Err(XCamError::Timeout));
+    }
+
+    #[test]
+    fn test_timed_call() {
+        let timed = Timed::new(&Arc::new(3), Duration::from_millis(20));
+        assert_eq!(timed.call(|v| Ok(*v * 2)), Ok(6));
+        assert_eq!(
+            timed.call(|_| {
+                thread::sleep(Duration::from_millis(500));
+                Ok(())
+            }),
+            Err(XCamError::Timeout)
+        );
+    }
+
+    /// 析构时置位标志，模拟释放 SDK 上下文。
+    struct Tracked(Arc<AtomicBool>);
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Release);
+        }
+    }
+
+    #[test]
+    fn test_init_with_timeout() {
+        let prepared = Arc::new(AtomicBool::new(false));
+        let dropped = Arc::new(AtomicBool::new(false));
+        let (p, d) = (Arc::clone(&prepared), Arc::clone(&dropped));
+        let r = init_with_timeout(
+            Duration::from_secs(1),
+            None,
+            move || Ok(Tracked(d)),
+            move |_| {
+                p.store(true, Ordering::Release);
+                Ok(())
+            },
+        );
+        assert!(r.is_ok());
+        assert!(prepared.load(Ordering::Acquire));
+        assert!(!dropped.load(Ordering::Acquire));
+
+        // 超时后初始化结果在原调用返回时释放。
+        let dropped = Arc::new(AtomicBool::new(false));
+        let d = Arc::clone(&dropped);
+        let r = init_with_timeout(
+            Duration::from_millis(20),
+            None,
+            move || {
+                thread::sleep(Duration::from_millis(100));
+                Ok(Tracked(d))
+            },
+            |_| Ok(()),
+        );
+        assert_eq!(r.err(), Some(XCamError::Timeout));
+        assert!(!dropped.load(Ordering::Acquire));
+        for _ in 0..100 {
+            if dropped.load(Ordering::Acquire) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(dropped.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_init_with_timeout_cancel() {
+        let token = CancelToken::new();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let (t, d) = (token.clone(), Arc::clone(&dropped));
+        // 初始化期间取消，`prepare` 不再执行，初始化结果被释放。
+        let r = init_with_timeout(
+            Duration::from_secs(1),
+            Some(token.clone()),
+            move || {
+                t.cancel();
+                Ok(Tracked(d))
+            },
+            |_| panic!("prepare after cancel"),
+        );
+        assert_eq!(r.err(), Some(XCamError::Cancelled));
+        assert!(dropped.load(Ordering::Acquire));
+
+        // 已取消时不再初始化。
+        let r = init_with_timeout(
+            Duration::from_secs(1),
+            Some(token),
+            || -> XCamResult<()> { panic!("init after cancel") },
+            |_| Ok(()),
+        );
+        assert_eq!(r, Err(XCamError::Cancelled));
+    }
+
+    #[test]
+    fn test_with_timeout_passes_result() {
+        assert_eq!(with_timeout(Duration::from_secs(1), || Ok(7)), Ok(7));
+        let failed = || XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED);
+        assert_eq!(
+            with_timeout(Duration::from_secs(1), move || Err::<(), _>(failed())),
+            Err(failed())
+        );
+    }
+}
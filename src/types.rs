@@ -1,3 +1,4 @@
+use super::display::enum_str;
 use super::error::XCamError;
 use super::ffi;
 
@@ -8,6 +9,10 @@ pub type AsdAttrib = ffi::asd_attrib_t;
 pub type CpslCfg = ffi::rk_aiq_cpsl_cfg_t;
 pub type CpslCap = ffi::rk_aiq_cpsl_cap_t;
 pub type CpslInfo = ffi::rk_aiq_cpsl_info_t;
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+pub type DegammaAttr = ffi::rk_aiq_degamma_attrib_t;
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+pub type DrcAttr = ffi::drc_attrib_t;
 pub type ExpPwrLineFreq = ffi::expPwrLineFreq_t;
 pub type FrameRateInfo = ffi::frameRateInfo_t;
 #[cfg(feature = "v2_0")]
@@ -35,9 +40,15 @@ pub type GammaMode = ffi::rk_aiq_gamma_op_mode_t;
 pub type GammaMode = ffi::rk_aiq_gamma_op_mode_t;
 pub type GammaOpMode = ffi::rk_aiq_gamma_op_mode_t;
 pub type GrayMode = ffi::rk_aiq_gray_mode_t;
+pub type IspStats = ffi::rk_aiq_isp_stats_t;
 pub type ModuleId = ffi::rk_aiq_module_id_t;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub type NrInfo = ffi::rk_aiq_bayernr_info_v2_t;
 pub type PaRange = ffi::paRange_t;
 pub type Rect = ffi::rk_aiq_rect_t;
+pub type SensorDescriptor = ffi::rk_aiq_exposure_sensor_descriptor;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+pub type SharpInfo = ffi::rk_aiq_sharp_info_v3_t;
 pub type StaticInfo = ffi::rk_aiq_static_info_t;
 pub type WbGain = ffi::rk_aiq_wb_gain_t;
 pub type WbScene = ffi::rk_aiq_wb_scene_t;
@@ -45,6 +56,11 @@ pub type XCamResult<T> = Result<T, XCamError>;
 
 /// 一个描述自动手动模式的枚举。
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum OpMode {
     Auto,
     Manual,
@@ -54,6 +70,31 @@ pub enum OpMode {
     Invalid,
 }
 
+impl OpMode {
+    /// 当前 SDK 版本支持的全部有效模式，不含 `Invalid`。
+    pub fn all() -> &'static [OpMode] {
+        &[
+            OpMode::Auto,
+            OpMode::Manual,
+            OpMode::SemiAuto,
+            #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+            OpMode::RegManual,
+        ]
+    }
+
+    /// 模式名称，与 `Display` 的输出相同。
+    pub fn name(&self) -> &'static str {
+        match self {
+            OpMode::Auto => "auto",
+            OpMode::Manual => "manual",
+            OpMode::SemiAuto => "semi_auto",
+            #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+            OpMode::RegManual => "reg_manual",
+            OpMode::Invalid => "invalid",
+        }
+    }
+}
+
 impl From<ffi::opMode_t> for OpMode {
     fn from(val: ffi::opMode_t) -> Self {
         use ffi::opMode_t::*;
@@ -82,6 +123,380 @@ impl From<OpMode> for ffi::opMode_t {
     }
 }
 
+enum_str!(OpMode, "operation mode" {
+    Auto => "auto",
+    Manual => "manual",
+    SemiAuto => "semi_auto",
+    #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+    RegManual => "reg_manual",
+    Invalid => "invalid",
+});
+
+/// 一个代表强度百分比的类型，取值范围：[0,100]。
+///
+/// 只能通过 `Strength::new` 或 `TryFrom<u8>` 构造，超出范围时构造失败，
+/// 因此持有的值总是有效的，接受该类型的接口无需再检查范围。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "u8", into = "u8")
+)]
+pub struct Strength(u8);
+
+impl Strength {
+    /// 最小强度。
+    pub const MIN: Self = Self(0);
+    /// 最大强度。
+    pub const MAX: Self = Self(100);
+
+    /// 创建一个强度值，超出 [0,100] 时返回 `None`。
+    pub const fn new(val: u8) -> Option<Self> {
+        if val <= Self::MAX.0 {
+            Some(Self(val))
+        } else {
+            None
+        }
+    }
+
+    /// 返回强度值。
+    pub const fn get(self) -> u8 {
+        self.0
+    }
+
+    /// 将 SDK 返回的强度值限制到有效范围内。
+    pub(crate) fn saturating(val: u32) -> Self {
+        Self(val.min(Self::MAX.0 as u32) as u8)
+    }
+
+    /// 将 SDK 算法状态中的强度比例（1.0 为满强度）转换为强度值，超出范围时截断。
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    pub(crate) fn from_ratio(ratio: f32) -> Self {
+        Self::saturating((ratio.max(0.0) * Self::MAX.0 as f32).round() as u32)
+    }
+}
+
+impl TryFrom<u8> for Strength {
+    type Error = StrengthOutOfRange;
+
+    fn try_from(val: u8) -> Result<Self, Self::Error> {
+        Self::new(val).ok_or(StrengthOutOfRange(val))
+    }
+}
+
+impl From<Strength> for u8 {
+    fn from(val: Strength) -> Self {
+        val.0
+    }
+}
+
+impl From<Strength> for u32 {
+    fn from(val: Strength) -> Self {
+        val.0 as u32
+    }
+}
+
+impl std::fmt::Display for Strength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// 一个代表强度值超出范围的错误类型。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StrengthOutOfRange(pub u8);
+
+impl std::fmt::Display for StrengthOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "strength {} out of range [0,100]", self.0)
+    }
+}
+
+impl std::error::Error for StrengthOutOfRange {}
+
+/// 一个描述分辨率的类型。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// 一个描述 Bayer 排列的枚举，按左上角 2×2 像素的颜色顺序命名。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum BayerPattern {
+    Bggr,
+    Gbrg,
+    Grbg,
+    Rggb,
+}
+
+enum_str!(BayerPattern, "Bayer pattern" {
+    Bggr => "bggr",
+    Gbrg => "gbrg",
+    Grbg => "grbg",
+    Rggb => "rggb",
+});
+
+/// 一个描述 Bayer 通道的枚举，`Gr` 为与 R 同行的 G，`Gb` 为与 B 同行的 G。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BayerChannel {
+    R,
+    Gr,
+    Gb,
+    B,
+}
+
+impl BayerPattern {
+    /// 左上角 2×2 像素的通道，按左上、右上、左下、右下排列。
+    pub const fn cell(self) -> [BayerChannel; 4] {
+        use BayerChannel::*;
+        match self {
+            Self::Bggr => [B, Gb, Gr, R],
+            Self::Gbrg => [Gb, B, R, Gr],
+            Self::Grbg => [Gr, R, B, Gb],
+            Self::Rggb => [R, Gr, Gb, B],
+        }
+    }
+
+    /// 传感器坐标 `(x, y)` 处像素的通道，坐标以有效像素区域的左上角为原点。
+    pub const fn channel_at(self, x: u32, y: u32) -> BayerChannel {
+        self.cell()[((y & 1) * 2 + (x & 1)) as usize]
+    }
+}
+
+/// 一个描述 RAW 数据位深的枚举。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RawBitDepth {
+    Bits8,
+    Bits10,
+    Bits12,
+    Bits14,
+    Bits16,
+}
+
+enum_str!(RawBitDepth, "raw bit depth" {
+    Bits8 => "8bit",
+    Bits10 => "10bit",
+    Bits12 => "12bit",
+    Bits14 => "14bit",
+    Bits16 => "16bit",
+});
+
+impl RawBitDepth {
+    /// 位数。
+    pub const fn bits(self) -> u32 {
+        match self {
+            Self::Bits8 => 8,
+            Self::Bits10 => 10,
+            Self::Bits12 => 12,
+            Self::Bits14 => 14,
+            Self::Bits16 => 16,
+        }
+    }
+
+    /// 像素的最大值，即 `2^位数 - 1`。
+    pub const fn max_value(self) -> u32 {
+        (1 << self.bits()) - 1
+    }
+
+    /// 检查以本位深表示的黑电平，超过最大值时返回 `XCAM_RETURN_ERROR_OUTOFRANGE`。
+    pub fn check_black_level(self, level: u32) -> XCamResult<()> {
+        if level <= self.max_value() {
+            Ok(())
+        } else {
+            Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE,
+            ))
+        }
+    }
+}
+
+/// 一个描述传感器 RAW 输出格式的类型。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RawFormat {
+    pub bayer: BayerPattern,
+    pub depth: RawBitDepth,
+}
+
+/// V4L2 media bus 代码（`MEDIA_BUS_FMT_S*_1X*`）与 RAW 格式的对照表。
+const BUS_CODE_FORMATS: [(u32, BayerPattern, RawBitDepth); 20] = {
+    use BayerPattern::*;
+    use RawBitDepth::*;
+    [
+        (0x3001, Bggr, Bits8),
+        (0x3013, Gbrg, Bits8),
+        (0x3002, Grbg, Bits8),
+        (0x3014, Rggb, Bits8),
+        (0x3007, Bggr, Bits10),
+        (0x300e, Gbrg, Bits10),
+        (0x300a, Grbg, Bits10),
+        (0x300f, Rggb, Bits10),
+        (0x3008, Bggr, Bits12),
+        (0x3010, Gbrg, Bits12),
+        (0x3011, Grbg, Bits12),
+        (0x3012, Rggb, Bits12),
+        (0x3019, Bggr, Bits14),
+        (0x301a, Gbrg, Bits14),
+        (0x301b, Grbg, Bits14),
+        (0x301c, Rggb, Bits14),
+        (0x301d, Bggr, Bits16),
+        (0x301e, Gbrg, Bits16),
+        (0x301f, Grbg, Bits16),
+        (0x3020, Rggb, Bits16),
+    ]
+};
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    (code[0] as u32) | ((code[1] as u32) << 8) | ((code[2] as u32) << 16) | ((code[3] as u32) << 24)
+}
+
+/// V4L2 像素格式（`V4L2_PIX_FMT_S*`）与 RAW 格式的对照表。
+const FOURCC_FORMATS: [(u32, BayerPattern, RawBitDepth); 20] = {
+    use BayerPattern::*;
+    use RawBitDepth::*;
+    [
+        (fourcc(b"BA81"), Bggr, Bits8),
+        (fourcc(b"GBRG"), Gbrg, Bits8),
+        (fourcc(b"GRBG"), Grbg, Bits8),
+        (fourcc(b"RGGB"), Rggb, Bits8),
+        (fourcc(b"BG10"), Bggr, Bits10),
+        (fourcc(b"GB10"), Gbrg, Bits10),
+        (fourcc(b"BA10"), Grbg, Bits10),
+        (fourcc(b"RG10"), Rggb, Bits10),
+        (fourcc(b"BG12"), Bggr, Bits12),
+        (fourcc(b"GB12"), Gbrg, Bits12),
+        (fourcc(b"BA12"), Grbg, Bits12),
+        (fourcc(b"RG12"), Rggb, Bits12),
+        (fourcc(b"BG14"), Bggr, Bits14),
+        (fourcc(b"GB14"), Gbrg, Bits14),
+        (fourcc(b"GR14"), Grbg, Bits14),
+        (fourcc(b"RG14"), Rggb, Bits14),
+        (fourcc(b"BYR2"), Bggr, Bits16),
+        (fourcc(b"GB16"), Gbrg, Bits16),
+        (fourcc(b"GR16"), Grbg, Bits16),
+        (fourcc(b"RG16"), Rggb, Bits16),
+    ]
+};
+
+fn lookup_raw_format(table: &[(u32, BayerPattern, RawBitDepth)], code: u32) -> Option<RawFormat> {
+    table
+        .iter()
+        .find(|(c, _, _)| *c == code)
+        .map(|&(_, bayer, depth)| RawFormat { bayer, depth })
+}
+
+impl RawFormat {
+    /// 由 V4L2 media bus 代码得到 RAW 格式，非 Bayer 格式返回 `None`。
+    pub fn from_bus_code(code: u32) -> Option<Self> {
+        lookup_raw_format(&BUS_CODE_FORMATS, code)
+    }
+
+    /// 由 V4L2 像素格式（fourcc）得到 RAW 格式，非 Bayer 格式返回 `None`。
+    pub fn from_fourcc(code: u32) -> Option<Self> {
+        lookup_raw_format(&FOURCC_FORMATS, code)
+    }
+
+    /// 由传感器描述中的 `sensor_pixelformat` 得到 RAW 格式。
+    ///
+    /// 各 SDK 版本及驱动在该字段中报告的编码不同，有的为 media bus 代码，有的为 V4L2 像素格式。
+    /// 两种编码的取值互不重叠，依次按两张表查找。
+    pub fn from_sensor_format(code: u32) -> Option<Self> {
+        Self::from_bus_code(code).or_else(|| Self::from_fourcc(code))
+    }
+
+    /// 由传感器描述得到 RAW 格式，见 `from_sensor_format`。
+    pub fn from_descriptor(des: &SensorDescriptor) -> Option<Self> {
+        Self::from_sensor_format(des.sensor_pixelformat)
+    }
+}
+
+/// 一个描述 ISP 输出像素格式的枚举，与 V4L2 像素格式（fourcc）一一对应。
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// YUV 4:2:0 半平面，UV 交错（`NV12`）。
+    Nv12,
+    /// YUV 4:2:0 半平面，VU 交错（`NV21`）。
+    Nv21,
+    /// YUV 4:2:2 半平面，UV 交错（`NV16`）。
+    Nv16,
+    /// YUV 4:2:2 半平面，VU 交错（`NV61`）。
+    Nv61,
+    /// YUV 4:2:2 打包（`YUYV`）。
+    Yuyv,
+    /// YUV 4:2:2 打包（`UYVY`）。
+    Uyvy,
+    /// 8 位灰度（`GREY`）。
+    Grey,
+    /// 24 位 RGB 打包（`RGB3`）。
+    Rgb888,
+    /// 传感器 RAW 数据，fourcc 见 `RawFormat::from_fourcc`。
+    Raw(RawFormat),
+}
+
+/// 非 RAW 像素格式与 V4L2 像素格式的对照表。
+const PIXEL_FORMATS: [(u32, PixelFormat); 8] = [
+    (fourcc(b"NV12"), PixelFormat::Nv12),
+    (fourcc(b"NV21"), PixelFormat::Nv21),
+    (fourcc(b"NV16"), PixelFormat::Nv16),
+    (fourcc(b"NV61"), PixelFormat::Nv61),
+    (fourcc(b"YUYV"), PixelFormat::Yuyv),
+    (fourcc(b"UYVY"), PixelFormat::Uyvy),
+    (fourcc(b"GREY"), PixelFormat::Grey),
+    (fourcc(b"RGB3"), PixelFormat::Rgb888),
+];
+
+impl PixelFormat {
+    /// 由 V4L2 像素格式（fourcc）得到像素格式，未知格式返回 `None`。
+    pub fn from_fourcc(code: u32) -> Option<Self> {
+        PIXEL_FORMATS
+            .iter()
+            .find(|(c, _)| *c == code)
+            .map(|&(_, f)| f)
+            .or_else(|| RawFormat::from_fourcc(code).map(Self::Raw))
+    }
+
+    /// V4L2 像素格式（fourcc）。
+    pub fn fourcc(self) -> u32 {
+        match self {
+            Self::Raw(raw) => FOURCC_FORMATS
+                .iter()
+                .find(|&&(_, bayer, depth)| RawFormat { bayer, depth } == raw)
+                .map(|&(c, _, _)| c)
+                .unwrap_or_default(),
+            _ => PIXEL_FORMATS
+                .iter()
+                .find(|(_, f)| *f == self)
+                .map(|&(c, _)| c)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// 输出 fourcc 的四个字符，例如 `NV12`。
+impl std::fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = self.fourcc().to_le_bytes();
+        f.write_str(&String::from_utf8_lossy(&code))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum WorkingMode {
     Normal,
     IspHdr2,
@@ -110,6 +525,36 @@ impl From<WorkingMode> for ffi::rk_aiq_working_mode_t {
     }
 }
 
+enum_str!(WorkingMode, "working mode" {
+    Normal => "normal",
+    IspHdr2 => "isp_hdr2",
+    IspHdr3 => "isp_hdr3",
+});
+
+/// 一个描述画面中矩形区域的类型，坐标与尺寸均为相对画面宽高的比例，范围：[0,1]。
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Roi {
+    /// 左边界。
+    pub x: f32,
+    /// 上边界。
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Roi {
+    /// 由像素坐标创建区域，`frame` 为画面的 `(宽, 高)`。
+    pub fn from_pixels(x: u32, y: u32, width: u32, height: u32, frame: (u32, u32)) -> Self {
+        let (w, h) = (frame.0.max(1) as f32, frame.1.max(1) as f32);
+        Self {
+            x: x as f32 / w,
+            y: y as f32 / h,
+            width: width as f32 / w,
+            height: height as f32 / h,
+        }
+    }
+}
+
 /// 一个代表摄像头朝向的枚举。
 #[derive(Copy, Clone, Debug, Default)]
 pub enum CameraFacing {
@@ -197,3 +642,216 @@ pub enum CameraModuleInfoParseError {
     NotMatched,
     RegexError,
 }
+
+/// 将 SDK 结构体中以 NUL 结尾的定长字符数组复制为字符串。
+///
+/// 没有 NUL 时使用整个数组，非 UTF-8 字节以替换字符表示。
+pub(crate) fn string_from_c_chars(buf: &[std::os::raw::c_char]) -> String {
+    let bytes: Vec<u8> = buf
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strength_range() {
+        assert_eq!(Strength::new(0), Some(Strength::MIN));
+        assert_eq!(Strength::new(100), Some(Strength::MAX));
+        assert_eq!(Strength::new(101), None);
+        assert_eq!(Strength::new(255), None);
+        assert_eq!(Strength::try_from(42).map(u8::from), Ok(42));
+        assert_eq!(Strength::try_from(150), Err(StrengthOutOfRange(150)));
+        assert_eq!(Strength::saturating(1000), Strength::MAX);
+    }
+
+    #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+    #[test]
+    fn test_strength_from_ratio() {
+        assert_eq!(Strength::from_ratio(0.426), Strength::new(43).unwrap());
+        assert_eq!(Strength::from_ratio(1.5), Strength::MAX);
+        assert_eq!(Strength::from_ratio(-0.2), Strength::MIN);
+        assert_eq!(Strength::from_ratio(f32::NAN), Strength::MIN);
+    }
+
+    #[test]
+    fn test_bayer_channel_at() {
+        use BayerChannel::*;
+        assert_eq!(BayerPattern::Rggb.channel_at(0, 0), R);
+        assert_eq!(BayerPattern::Rggb.channel_at(1, 0), Gr);
+        assert_eq!(BayerPattern::Rggb.channel_at(0, 1), Gb);
+        assert_eq!(BayerPattern::Rggb.channel_at(3, 5), B);
+        assert_eq!(BayerPattern::Bggr.channel_at(0, 0), B);
+        assert_eq!(BayerPattern::Bggr.channel_at(1, 1), R);
+        assert_eq!(BayerPattern::Grbg.channel_at(2, 0), Gr);
+        assert_eq!(BayerPattern::Gbrg.channel_at(1, 0), B);
+        // 每种排列的 2×2 单元恰好各含一个通道。
+        for p in [
+            BayerPattern::Bggr,
+            BayerPattern::Gbrg,
+            BayerPattern::Grbg,
+            BayerPattern::Rggb,
+        ] {
+            for ch in [R, Gr, Gb, B] {
+                assert_eq!(p.cell().iter().filter(|&&c| c == ch).count(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_raw_bit_depth() {
+        assert_eq!(RawBitDepth::Bits8.max_value(), 255);
+        assert_eq!(RawBitDepth::Bits12.max_value(), 4095);
+        assert_eq!(RawBitDepth::Bits16.max_value(), 65535);
+        assert_eq!(RawBitDepth::Bits10.check_black_level(64), Ok(()));
+        assert_eq!(RawBitDepth::Bits10.check_black_level(1023), Ok(()));
+        assert_eq!(
+            RawBitDepth::Bits10.check_black_level(1024),
+            Err(XCamError::from(
+                ffi::XCamReturn::XCAM_RETURN_ERROR_OUTOFRANGE
+            ))
+        );
+        assert_eq!("12bit".parse(), Ok(RawBitDepth::Bits12));
+    }
+
+    #[test]
+    fn test_raw_format_tables() {
+        let fmt = |bayer, depth| Some(RawFormat { bayer, depth });
+        use BayerPattern::*;
+        use RawBitDepth::*;
+        // MEDIA_BUS_FMT_SBGGR10_1X10、MEDIA_BUS_FMT_SGRBG12_1X12、MEDIA_BUS_FMT_SRGGB16_1X16
+        assert_eq!(RawFormat::from_bus_code(0x3007), fmt(Bggr, Bits10));
+        assert_eq!(RawFormat::from_bus_code(0x3011), fmt(Grbg, Bits12));
+        assert_eq!(RawFormat::from_bus_code(0x3020), fmt(Rggb, Bits16));
+        // MEDIA_BUS_FMT_YUYV8_2X8
+        assert_eq!(RawFormat::from_bus_code(0x2008), None);
+        // V4L2_PIX_FMT_SBGGR10 = 'BG10'、V4L2_PIX_FMT_SGRBG10 = 'BA10'、V4L2_PIX_FMT_SBGGR16 = 'BYR2'
+        assert_eq!(RawFormat::from_fourcc(0x3031_4742), fmt(Bggr, Bits10));
+        assert_eq!(RawFormat::from_fourcc(0x3031_4142), fmt(Grbg, Bits10));
+        assert_eq!(RawFormat::from_fourcc(0x3252_5942), fmt(Bggr, Bits16));
+        // V4L2_PIX_FMT_YUYV
+        assert_eq!(RawFormat::from_fourcc(0x5659_5559), None);
+        // 两张表互不重叠，每张表的代码各不相同。
+        for table in [&BUS_CODE_FORMATS, &FOURCC_FORMATS] {
+            for (i, a) in table.iter().enumerate() {
+                assert!(table[i + 1..].iter().all(|b| b.0 != a.0));
+                assert_eq!(RawFormat::from_sensor_format(a.0), fmt(a.1, a.2));
+            }
+        }
+        assert!(BUS_CODE_FORMATS
+            .iter()
+            .all(|a| FOURCC_FORMATS.iter().all(|b| a.0 != b.0)));
+        assert_eq!(RawFormat::from_sensor_format(0), None);
+    }
+
+    #[test]
+    fn test_pixel_format_fourcc() {
+        // V4L2_PIX_FMT_NV12 = 'NV12'
+        assert_eq!(PixelFormat::Nv12.fourcc(), 0x3231_564e);
+        assert_eq!(PixelFormat::Nv16.to_string(), "NV16");
+        let raw10 = PixelFormat::Raw(RawFormat {
+            bayer: BayerPattern::Grbg,
+            depth: RawBitDepth::Bits10,
+        });
+        assert_eq!(raw10.to_string(), "BA10");
+        let formats = PIXEL_FORMATS.iter().map(|&(_, f)| f).chain(
+            FOURCC_FORMATS
+                .iter()
+                .map(|&(_, bayer, depth)| PixelFormat::Raw(RawFormat { bayer, depth })),
+        );
+        for f in formats {
+            assert_eq!(PixelFormat::from_fourcc(f.fourcc()), Some(f));
+        }
+        assert_eq!(PixelFormat::from_fourcc(0), None);
+    }
+
+    #[test]
+    fn test_enum_str() {
+        use crate::display::ParseEnumError;
+        assert_eq!(OpMode::SemiAuto.to_string(), "semi_auto");
+        assert_eq!("Semi-Auto".parse(), Ok(OpMode::SemiAuto));
+        assert_eq!(" manual ".parse(), Ok(OpMode::Manual));
+        assert_eq!(format!("{:>8}", OpMode::Auto), "    auto");
+        assert_eq!(
+            "hdr".parse::<WorkingMode>(),
+            Err(ParseEnumError {
+                kind: "working mode",
+                input: "hdr".to_owned(),
+            })
+        );
+        for mode in [
+            WorkingMode::Normal,
+            WorkingMode::IspHdr2,
+            WorkingMode::IspHdr3,
+        ] {
+            assert_eq!(mode.to_string().parse(), Ok(mode));
+        }
+    }
+
+    #[test]
+    fn test_op_mode_all() {
+        for &mode in OpMode::all() {
+            assert_ne!(mode, OpMode::Invalid);
+            assert_eq!(OpMode::from(ffi::opMode_t::from(mode)), mode);
+            assert_eq!(mode.name(), mode.to_string());
+            assert_eq!(mode.name().parse::<OpMode>(), Ok(mode));
+        }
+        assert_eq!(OpMode::Invalid.name(), OpMode::Invalid.to_string());
+        #[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+        assert!(OpMode::all().contains(&OpMode::RegManual));
+    }
+
+    #[test]
+    fn test_string_from_c_chars() {
+        use std::os::raw::c_char;
+        let buf = [
+            b'D' as c_char,
+            b'6' as c_char,
+            b'5' as c_char,
+            0,
+            b'x' as c_char,
+        ];
+        assert_eq!(string_from_c_chars(&buf), "D65");
+        assert_eq!(string_from_c_chars(&[0 as c_char; 4]), "");
+        assert_eq!(string_from_c_chars(&[b'A' as c_char, b'B' as c_char]), "AB");
+    }
+
+    #[test]
+    fn test_wb_scene_order() {
+        use ffi::rk_aiq_wb_scene_t::*;
+        let mut scenes = vec![
+            RK_AIQ_WBCT_SHADE,
+            RK_AIQ_WBCT_DAYLIGHT,
+            RK_AIQ_WBCT_TWILIGHT,
+            RK_AIQ_WBCT_INCANDESCENT,
+            RK_AIQ_WBCT_CLOUDY_DAYLIGHT,
+            RK_AIQ_WBCT_FLUORESCENT,
+            RK_AIQ_WBCT_WARM_FLUORESCENT,
+        ];
+        scenes.sort();
+        assert_eq!(
+            scenes,
+            [
+                RK_AIQ_WBCT_INCANDESCENT,
+                RK_AIQ_WBCT_WARM_FLUORESCENT,
+                RK_AIQ_WBCT_FLUORESCENT,
+                RK_AIQ_WBCT_DAYLIGHT,
+                RK_AIQ_WBCT_CLOUDY_DAYLIGHT,
+                RK_AIQ_WBCT_SHADE,
+                RK_AIQ_WBCT_TWILIGHT,
+            ]
+        );
+        assert!(scenes
+            .windows(2)
+            .all(|w| w[0].nominal_ct() < w[1].nominal_ct()));
+        assert_eq!(
+            RK_AIQ_WBCT_DAYLIGHT.cmp(&RK_AIQ_WBCT_DAYLIGHT),
+            std::cmp::Ordering::Equal
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! SDK 调用
+//!
+//! 封装“取上下文指针—调用 SDK 函数—转换返回值”的固定写法：
+//!
+//! ```ignore
+//! fn set_mwb_ct(&self, ct: u32) -> XCamResult<()> {
+//!     uapi_call!(ffi::rk_aiq_uapi_setMWBCT(self, ct))
+//! }
+//!
+//! fn get_mwb_ct(&self) -> XCamResult<u32> {
+//!     uapi_get!(ffi::rk_aiq_uapi_getMWBCT(self) -> u32)
+//! }
+//! ```
+use super::context::Context;
+use super::ffi;
+use super::logging::log_warn;
+use super::types::XCamResult;
+
+/// 调用以上下文指针为首个参数的 SDK 函数，返回 `XCamResult<()>`。
+///
+/// 写作 `uapi_call!(函数路径(上下文, 参数...))`，上下文须实现 `UapiContext`，
+/// 调用经由其 `guard` 执行；末尾加 `; log` 时调用失败会连同上下文的标签输出到日志。
+///
+/// 上下文与各参数按书写顺序在 `unsafe` 块之外求值并绑定到局部变量，`unsafe` 块内只有
+/// SDK 函数调用本身，参数表达式中的不安全操作仍须自行标注 `unsafe`。
+macro_rules! uapi_call {
+    (@bind $ctx:ident, [$($f:ident)::+], [$($bound:ident)*], $arg:expr, $($rest:expr,)*) => {{
+        let arg = $arg;
+        $crate::uapi::uapi_call!(@bind $ctx, [$($f)::+], [$($bound)* arg], $($rest,)*)
+    }};
+    (@bind $ctx:ident, [$($f:ident)::+], [$($bound:ident)*],) => {
+        $crate::uapi::UapiContext::guard($ctx, || {
+            let raw = $crate::uapi::UapiContext::raw($ctx);
+            // SAFETY: `raw` 来自存活的上下文，参数由调用方按 SDK 函数的约定提供。
+            let ret = unsafe { $($f)::+(raw $(, $bound)*) };
+            $crate::error::XCamError::from(ret).ok()
+        })
+    };
+    ($($f:ident)::+($ctx:expr $(, $arg:expr)* $(,)?)) => {{
+        let ctx = $ctx;
+        $crate::uapi::uapi_call!(@bind ctx, [$($f)::+], [], $($arg,)*)
+    }};
+    ($($f:ident)::+($ctx:expr $(, $arg:expr)* $(,)?); log) => {
+        $crate::uapi::log_failure(
+            $crate::uapi::UapiContext::label($ctx).as_deref(),
+            stringify!($($f)::+),
+            $crate::uapi::uapi_call!($($f)::+($ctx $(, $arg)*)),
+        )
+    };
+}
+
+/// 调用以输出参数返回结果的 SDK 函数，返回 `XCamResult<类型>`。
+///
+/// 写作 `uapi_get!(函数路径(上下文, 参数...) -> 类型)`，输出参数为最后一个参数，
+/// 以 `Default::default()` 初始化；`; log` 同 `uapi_call!`。
+macro_rules! uapi_get {
+    (
+        $($f:ident)::+($ctx:expr $(, $arg:expr)* $(,)?) -> $ty:ty $(; $log:ident)?
+    ) => {{
+        let mut out = <$ty as ::std::default::Default>::default();
+        $crate::uapi::uapi_call!($($f)::+($ctx $(, $arg)*, &mut out) $(; $log)?).map(|_| out)
+    }};
+}
+
+pub(crate) use {uapi_call, uapi_get};
+
+/// 一个可由 `uapi_call!`、`uapi_get!` 调用 SDK 的上下文。
+pub(crate) trait UapiContext {
+    /// SDK 上下文的类型。
+    type Raw;
+
+    /// SDK 上下文指针。
+    fn raw(&self) -> *mut Self::Raw;
+
+    /// 执行一次 SDK 调用 `f`，可在调用前后检查上下文状态，默认直接调用。
+    fn guard<T, F>(&self, f: F) -> XCamResult<T>
+    where
+        F: FnOnce() -> XCamResult<T>,
+    {
+        f()
+    }
+
+    /// 输出调用失败日志时用于区分上下文的标签，默认没有。
+    fn label(&self) -> Option<String> {
+        None
+    }
+}
+
+impl UapiContext for Context {
+    type Raw = ffi::rk_aiq_sys_ctx_t;
+
+    fn raw(&self) -> *mut Self::Raw {
+        self.internal.as_ptr()
+    }
+
+    fn guard<T, F>(&self, f: F) -> XCamResult<T>
+    where
+        F: FnOnce() -> XCamResult<T>,
+    {
+        self.guard_device(f)
+    }
+
+    fn label(&self) -> Option<String> {
+        Some(Context::label(self))
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// 本线程 `log_failure` 输出的信息。
+    static LOGGED: std::cell::RefCell<Vec<String>> = std::cell::RefCell::new(Vec::new());
+}
+
+/// 调用失败时以 `warn` 级别输出上下文标签、SDK 函数名与错误，原样返回结果，见 `logging` 模块。
+///
+/// 输出形如 `[cam-left] rk_aiq_uapi_setMWBGain failed: ...`，没有标签时省略方括号一段。
+pub(crate) fn log_failure<T>(label: Option<&str>, name: &str, r: XCamResult<T>) -> XCamResult<T> {
+    if let Err(e) = &r {
+        let name = name.rsplit("::").next().unwrap_or(name).trim();
+        let msg = match label {
+            Some(label) => format!("[{}] {} failed: {}", label, name, e),
+            None => format!("{} failed: {}", name, e),
+        };
+        log_warn!("{}", msg);
+        #[cfg(test)]
+        LOGGED.with(|l| l.borrow_mut().push(msg));
+    }
+    r
+}
+
+/// 取出并清空本线程 `log_failure` 输出的信息。
+#[cfg(test)]
+pub(crate) fn take_logged() -> Vec<String> {
+    LOGGED.with(|l| std::mem::take(&mut *l.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{take_logged, UapiContext};
+    use crate::error::XCamError;
+    use crate::ffi::XCamReturn;
+    use std::ptr::NonNull;
+
+    struct Fake {
+        internal: NonNull<u32>,
+    }
+
+    impl UapiContext for Fake {
+        type Raw = u32;
+
+        fn raw(&self) -> *mut u32 {
+            self.internal.as_ptr()
+        }
+    }
+
+    struct Labeled(Fake, &'static str);
+
+    impl UapiContext for Labeled {
+        type Raw = u32;
+
+        fn raw(&self) -> *mut u32 {
+            self.0.raw()
+        }
+
+        fn label(&self) -> Option<String> {
+            Some(self.1.to_owned())
+        }
+    }
+
+    mod sdk {
+        use crate::ffi::XCamReturn;
+
+        pub unsafe fn set_level(ctx: *mut u32, level: u32) -> XCamReturn {
+            if level > 10 {
+                return XCamReturn::XCAM_RETURN_ERROR_PARAM;
+            }
+            *ctx = level;
+            XCamReturn::XCAM_RETURN_NO_ERROR
+        }
+
+        pub unsafe fn get_level(ctx: *mut u32, scale: u32, out: *mut u32) -> XCamReturn {
+            *out = *ctx * scale;
+            XCamReturn::XCAM_RETURN_NO_ERROR
+        }
+
+        pub unsafe fn get_timeout(_ctx: *mut u32, out: *mut u32) -> XCamReturn {
+            *out = 1;
+            XCamReturn::XCAM_RETURN_ERROR_TIMEOUT
+        }
+    }
+
+    #[test]
+    fn test_uapi_call_matches_hand_written() {
+        let mut level = 0u32;
+        let ctx = Fake {
+            internal: NonNull::from(&mut level),
+        };
+        for value in [3, 11] {
+            let hand =
+                unsafe { XCamError::from(sdk::set_level(ctx.internal.as_ptr(), value)).ok() };
+            assert_eq!(uapi_call!(sdk::set_level(&ctx, value)), hand);
+            assert_eq!(uapi_call!(sdk::set_level(&ctx, value); log), hand);
+        }
+        assert_eq!(
+            uapi_call!(sdk::set_level(&ctx, 11)),
+            Err(XCamError::from(XCamReturn::XCAM_RETURN_ERROR_PARAM))
+        );
+        assert_eq!(unsafe { *ctx.internal.as_ptr() }, 3);
+    }
+
+    #[test]
+    fn test_uapi_get_matches_hand_written() {
+        let mut level = 4u32;
+        let ctx = Fake {
+            internal: NonNull::from(&mut level),
+        };
+        let mut out = 0u32;
+        let hand = unsafe {
+            XCamError::from(sdk::get_level(ctx.internal.as_ptr(), 2, &mut out))
+                .ok()
+                .map(|_| out)
+        };
+        assert_eq!(uapi_get!(sdk::get_level(&ctx, 2) -> u32), hand);
+        assert_eq!(hand, Ok(8));
+
+        let mut out = 0u32;
+        let hand = unsafe {
+            XCamError::from(sdk::get_timeout(ctx.internal.as_ptr(), &mut out))
+                .ok()
+                .map(|_| out)
+        };
+        assert_eq!(uapi_get!(sdk::get_timeout(&ctx) -> u32; log), hand);
+        assert_eq!(hand, Err(XCamError::Timeout));
+    }
+
+    #[test]
+    fn test_log_failure_label() {
+        let mut level = 0u32;
+        let ctx = Labeled(
+            Fake {
+                internal: NonNull::from(&mut level),
+            },
+            "cam-left",
+        );
+        take_logged();
+        let _ = uapi_call!(sdk::set_level(&ctx, 3); log);
+        assert!(take_logged().is_empty());
+        let _ = uapi_call!(sdk::set_level(&ctx, 11); log);
+        let err = XCamError::from(XCamReturn::XCAM_RETURN_ERROR_PARAM);
+        assert_eq!(
+            take_logged(),
+            [format!("[cam-left] set_level failed: {}", err)]
+        );
+        let _ = uapi_call!(sdk::set_level(&ctx.0, 11); log);
+        assert_eq!(take_logged(), [format!("set_level failed: {}", err)]);
+    }
+
+    #[test]
+    fn test_uapi_call_evaluates_arguments_once_in_order() {
+        let mut level = 0u32;
+        let ctx = Fake {
+            internal: NonNull::from(&mut level),
+        };
+        let mut order = Vec::new();
+        let mut next = |v: u32| {
+            order.push(v);
+            v
+        };
+        let mut out = 0u32;
+        assert_eq!(uapi_call!(sdk::get_level(&ctx, next(2), &mut out)), Ok(()));
+        assert_eq!(uapi_call!(sdk::set_level(&ctx, next(5),)), Ok(()));
+        assert_eq!(order, [2, 5]);
+        assert_eq!(unsafe { *ctx.internal.as_ptr() }, 5);
+    }
+}
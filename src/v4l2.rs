@@ -0,0 +1,917 @@
+//! V4L2 与媒体控制器的内核接口
+//!
+//! 以 ioctl 直接访问媒体节点与 video 节点，不依赖 `media-ctl`、`v4l2-ctl` 等外部工具。
+//! 结构体布局与请求码取自内核头文件 `linux/media.h` 与 `linux/videodev2.h`，只包含本库用到的
+//! 部分；请求码按 ARM、AArch64 使用的通用编码计算。拓扑通过 `MEDIA_IOC_G_TOPOLOGY` 获取，需要 4.19 及以上的内核。
+//!
+//! video 节点只按多平面采集（`V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE`）访问，与 rkisp 各通路的
+//! 驱动一致。子设备节点只访问传感器的输出 pad，配置取自 `linux/v4l2-subdev.h`。
+use std::fs::{self, File};
+use std::io;
+use std::mem;
+use std::ops::Range;
+use std::os::fd::AsRawFd;
+use std::ptr;
+use std::time::{Duration, Instant};
+
+const IOC_WRITE: u32 = 1;
+const IOC_READ: u32 = 2;
+
+/// 按通用编码计算 ioctl 请求码，即内核的 `_IOC(dir, type, nr, size)`。
+const fn ioc(dir: u32, ty: u8, nr: u8, size: usize) -> u32 {
+    (dir << 30) | ((size as u32) << 16) | ((ty as u32) << 8) | nr as u32
+}
+
+/// 执行一次 ioctl，被信号中断时重试。
+///
+/// # Safety
+/// `arg` 须指向与 `request` 编码的大小一致、可由内核读写的结构体。
+unsafe fn ioctl<T>(file: &File, request: u32, arg: *mut T) -> io::Result<()> {
+    loop {
+        if libc::ioctl(file.as_raw_fd(), request as _, arg) >= 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+/// 取 C 字符串数组中第一个 NUL 之前的内容。
+fn c_string(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+/// 传感器实体的功能（`MEDIA_ENT_F_CAM_SENSOR`）。
+pub(crate) const MEDIA_ENT_F_CAM_SENSOR: u32 = 0x0002_0001;
+/// V4L2 video 设备接口（`MEDIA_INTF_T_V4L_VIDEO`）。
+pub(crate) const MEDIA_INTF_T_V4L_VIDEO: u32 = 0x0000_0200;
+/// V4L2 子设备接口（`MEDIA_INTF_T_V4L_SUBDEV`）。
+pub(crate) const MEDIA_INTF_T_V4L_SUBDEV: u32 = 0x0000_0203;
+const MEDIA_LNK_FL_LINK_TYPE: u32 = 0xf << 28;
+const MEDIA_LNK_FL_INTERFACE_LINK: u32 = 1 << 28;
+
+/// `struct media_device_info`
+#[repr(C)]
+struct MediaDeviceInfo {
+    driver: [u8; 16],
+    model: [u8; 32],
+    serial: [u8; 40],
+    bus_info: [u8; 32],
+    media_version: u32,
+    hw_revision: u32,
+    driver_version: u32,
+    reserved: [u32; 31],
+}
+
+/// `struct media_v2_topology`
+#[repr(C)]
+#[derive(Default)]
+struct MediaV2Topology {
+    topology_version: u64,
+    num_entities: u32,
+    reserved1: u32,
+    ptr_entities: u64,
+    num_interfaces: u32,
+    reserved2: u32,
+    ptr_interfaces: u64,
+    num_pads: u32,
+    reserved3: u32,
+    ptr_pads: u64,
+    num_links: u32,
+    reserved4: u32,
+    ptr_links: u64,
+}
+
+/// `struct media_v2_entity`
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct MediaV2Entity {
+    id: u32,
+    name: [u8; 64],
+    function: u32,
+    flags: u32,
+    reserved: [u32; 5],
+}
+
+/// `struct media_v2_interface`，联合体中只使用设备节点号 `devnode`。
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct MediaV2Interface {
+    id: u32,
+    intf_type: u32,
+    flags: u32,
+    reserved: [u32; 9],
+    devnode: [u32; 2],
+    raw: [u32; 14],
+}
+
+/// `struct media_v2_link`
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct MediaV2Link {
+    id: u32,
+    source_id: u32,
+    sink_id: u32,
+    flags: u32,
+    reserved: [u32; 6],
+}
+
+const MEDIA_IOC_DEVICE_INFO: u32 = ioc(
+    IOC_READ | IOC_WRITE,
+    b'|',
+    0x00,
+    mem::size_of::<MediaDeviceInfo>(),
+);
+const MEDIA_IOC_G_TOPOLOGY: u32 = ioc(
+    IOC_READ | IOC_WRITE,
+    b'|',
+    0x04,
+    mem::size_of::<MediaV2Topology>(),
+);
+
+/// 拓扑在两次读取之间变化时的最大尝试次数。
+const TOPOLOGY_ATTEMPTS: usize = 3;
+
+/// 媒体图中的一个实体。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Entity {
+    pub(crate) id: u32,
+    pub(crate) name: String,
+    /// 实体功能，例如 `MEDIA_ENT_F_CAM_SENSOR`。
+    pub(crate) function: u32,
+}
+
+/// 媒体图中的一个设备接口。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Interface {
+    pub(crate) id: u32,
+    /// 接口类型，例如 `MEDIA_INTF_T_V4L_VIDEO`。
+    pub(crate) intf_type: u32,
+    /// 设备节点的 `(主设备号, 次设备号)`。
+    pub(crate) devnode: (u32, u32),
+}
+
+/// 接口与实体之间的链接，即接口 `interface` 是实体 `entity` 的设备节点。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct InterfaceLink {
+    pub(crate) interface: u32,
+    pub(crate) entity: u32,
+}
+
+/// 一个媒体节点的拓扑，只保留实体、接口及二者之间的链接。
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Topology {
+    pub(crate) entities: Vec<Entity>,
+    pub(crate) interfaces: Vec<Interface>,
+    pub(crate) links: Vec<InterfaceLink>,
+}
+
+impl Topology {
+    /// 功能为 `function` 的实体。
+    pub(crate) fn entities_with(&self, function: u32) -> impl Iterator<Item = &Entity> {
+        self.entities.iter().filter(move |e| e.function == function)
+    }
+
+    /// 实体 `name` 类型为 `intf_type` 的设备节点号。
+    pub(crate) fn devnode(&self, name: &str, intf_type: u32) -> Option<(u32, u32)> {
+        let entity = self.entities.iter().find(|e| e.name == name)?;
+        self.links
+            .iter()
+            .filter(|l| l.entity == entity.id)
+            .filter_map(|l| self.interfaces.iter().find(|i| i.id == l.interface))
+            .find(|i| i.intf_type == intf_type)
+            .map(|i| i.devnode)
+    }
+}
+
+/// 读取媒体节点的型号，例如 `rkisp1`。
+pub(crate) fn media_model(file: &File) -> io::Result<String> {
+    let mut info: MediaDeviceInfo = unsafe { mem::zeroed() };
+    unsafe { ioctl(file, MEDIA_IOC_DEVICE_INFO, &mut info)? };
+    Ok(c_string(&info.model))
+}
+
+/// 读取媒体节点的拓扑。
+///
+/// 先读取各类对象的数量，再按数量分配缓冲区读取内容；两次读取之间拓扑变化时重新读取，
+/// 最多尝试 `TOPOLOGY_ATTEMPTS` 次，仍不一致时返回 `EAGAIN`。
+pub(crate) fn media_topology(file: &File) -> io::Result<Topology> {
+    for _ in 0..TOPOLOGY_ATTEMPTS {
+        let mut topo = MediaV2Topology::default();
+        unsafe { ioctl(file, MEDIA_IOC_G_TOPOLOGY, &mut topo)? };
+        let version = topo.topology_version;
+        let mut entities: Vec<MediaV2Entity> =
+            vec![unsafe { mem::zeroed() }; topo.num_entities as usize];
+        let mut interfaces: Vec<MediaV2Interface> =
+            vec![unsafe { mem::zeroed() }; topo.num_interfaces as usize];
+        let mut links: Vec<MediaV2Link> = vec![unsafe { mem::zeroed() }; topo.num_links as usize];
+        topo.ptr_entities = entities.as_mut_ptr() as u64;
+        topo.ptr_interfaces = interfaces.as_mut_ptr() as u64;
+        topo.ptr_links = links.as_mut_ptr() as u64;
+        topo.num_pads = 0;
+        topo.ptr_pads = 0;
+        match unsafe { ioctl(file, MEDIA_IOC_G_TOPOLOGY, &mut topo) } {
+            // 两次读取之间新增了对象，缓冲区不足。
+            Err(e) if e.raw_os_error() == Some(libc::ENOSPC) => continue,
+            r => r?,
+        }
+        if topo.topology_version != version {
+            continue;
+        }
+        entities.truncate(topo.num_entities as usize);
+        interfaces.truncate(topo.num_interfaces as usize);
+        links.truncate(topo.num_links as usize);
+        return Ok(Topology {
+            entities: entities
+                .iter()
+                .map(|e| Entity {
+                    id: e.id,
+                    name: c_string(&e.name),
+                    function: e.function,
+                })
+                .collect(),
+            interfaces: interfaces
+                .iter()
+                .map(|i| Interface {
+                    id: i.id,
+                    intf_type: i.intf_type,
+                    devnode: (i.devnode[0], i.devnode[1]),
+                })
+                .collect(),
+            links: links
+                .iter()
+                .filter(|l| l.flags & MEDIA_LNK_FL_LINK_TYPE == MEDIA_LNK_FL_INTERFACE_LINK)
+                .map(|l| InterfaceLink {
+                    interface: l.source_id,
+                    entity: l.sink_id,
+                })
+                .collect(),
+        });
+    }
+    Err(io::Error::from_raw_os_error(libc::EAGAIN))
+}
+
+/// 由设备号查找设备节点路径，例如 `(81, 0)` 为 `/dev/video0`。
+///
+/// 设备名取自 `/sys/dev/char/<主>:<次>/uevent` 中的 `DEVNAME`。
+pub(crate) fn devnode_path((major, minor): (u32, u32)) -> io::Result<String> {
+    let uevent = fs::read_to_string(format!("/sys/dev/char/{}:{}/uevent", major, minor))?;
+    devname_from_uevent(&uevent)
+        .map(|name| format!("/dev/{}", name))
+        .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+}
+
+/// 取 uevent 内容中的 `DEVNAME`。
+fn devname_from_uevent(uevent: &str) -> Option<&str> {
+    uevent
+        .lines()
+        .find_map(|l| l.strip_prefix("DEVNAME="))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+}
+
+/// 多平面视频采集（`V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE`）。
+const V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE: u32 = 9;
+const VIDEO_MAX_PLANES: usize = 8;
+
+/// `struct v4l2_plane_pix_format`
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+struct V4l2PlanePixFormat {
+    sizeimage: u32,
+    bytesperline: u32,
+    reserved: [u16; 6],
+}
+
+/// `struct v4l2_pix_format_mplane`
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+struct V4l2PixFormatMplane {
+    width: u32,
+    height: u32,
+    pixelformat: u32,
+    field: u32,
+    colorspace: u32,
+    plane_fmt: [V4l2PlanePixFormat; VIDEO_MAX_PLANES],
+    num_planes: u8,
+    flags: u8,
+    ycbcr_enc: u8,
+    quantization: u8,
+    xfer_func: u8,
+    reserved: [u8; 7],
+}
+
+/// `struct v4l2_format` 中的联合体，内核中含指针成员，按指针对齐。
+#[repr(C)]
+#[derive(Copy, Clone)]
+union V4l2FormatData {
+    pix_mp: V4l2PixFormatMplane,
+    raw_data: [u8; 200],
+    align: *mut libc::c_void,
+}
+
+/// `struct v4l2_format`
+#[repr(C)]
+struct V4l2Format {
+    type_: u32,
+    fmt: V4l2FormatData,
+}
+
+const VIDIOC_G_FMT: u32 = ioc(IOC_READ | IOC_WRITE, b'V', 4, mem::size_of::<V4l2Format>());
+const VIDIOC_S_FMT: u32 = ioc(IOC_READ | IOC_WRITE, b'V', 5, mem::size_of::<V4l2Format>());
+
+/// video 节点的图像格式。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PixFormat {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// 像素格式的 fourcc。
+    pub(crate) pixelformat: u32,
+}
+
+impl From<&V4l2Format> for PixFormat {
+    fn from(f: &V4l2Format) -> Self {
+        // 由内核填充的联合体，多平面类型下 `pix_mp` 有效。
+        let pix = unsafe { f.fmt.pix_mp };
+        PixFormat {
+            width: pix.width,
+            height: pix.height,
+            pixelformat: pix.pixelformat,
+        }
+    }
+}
+
+/// 以 `VIDIOC_G_FMT` 读取 video 节点的格式。
+fn g_fmt(file: &File) -> io::Result<V4l2Format> {
+    let mut f: V4l2Format = unsafe { mem::zeroed() };
+    f.type_ = V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE;
+    unsafe { ioctl(file, VIDIOC_G_FMT, &mut f)? };
+    Ok(f)
+}
+
+/// 读取 video 节点的格式。
+pub(crate) fn get_format(file: &File) -> io::Result<PixFormat> {
+    g_fmt(file).map(|f| PixFormat::from(&f))
+}
+
+/// 读取 video 节点各平面的行宽（`bytesperline`），单位：字节。
+pub(crate) fn get_plane_strides(file: &File) -> io::Result<Vec<u32>> {
+    // `g_fmt` 按多平面类型读取，`pix_mp` 有效。
+    let pix = unsafe { g_fmt(file)?.fmt.pix_mp };
+    let plane_fmt = pix.plane_fmt;
+    Ok(plane_fmt
+        .iter()
+        .take((pix.num_planes as usize).min(VIDEO_MAX_PLANES))
+        .map(|p| p.bytesperline)
+        .collect())
+}
+
+/// 设置 video 节点的像素格式，分辨率等其他参数保持不变，返回驱动实际采用的格式。
+///
+/// 驱动不支持 `pixelformat` 时按 V4L2 约定改为其他格式而不报错，调用者须检查返回值。
+pub(crate) fn set_pixel_format(file: &File, pixelformat: u32) -> io::Result<PixFormat> {
+    let mut f = g_fmt(file)?;
+    // `g_fmt` 按多平面类型读取，`pix_mp` 有效。
+    f.fmt.pix_mp.pixelformat = pixelformat;
+    unsafe { ioctl(file, VIDIOC_S_FMT, &mut f)? };
+    Ok(PixFormat::from(&f))
+}
+
+/// 子设备当前生效的配置（`V4L2_SUBDEV_FORMAT_ACTIVE`）。
+const V4L2_SUBDEV_FORMAT_ACTIVE: u32 = 1;
+/// 裁剪区域（`V4L2_SEL_TGT_CROP`）。
+const V4L2_SEL_TGT_CROP: u32 = 0x0000;
+/// 裁剪区域的上限（`V4L2_SEL_TGT_CROP_BOUNDS`）。
+const V4L2_SEL_TGT_CROP_BOUNDS: u32 = 0x0002;
+/// 传感器子设备的输出 pad。
+const SENSOR_SOURCE_PAD: u32 = 0;
+
+/// `struct v4l2_mbus_framefmt`
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct V4l2MbusFramefmt {
+    width: u32,
+    height: u32,
+    code: u32,
+    field: u32,
+    colorspace: u32,
+    ycbcr_enc: u16,
+    quantization: u16,
+    xfer_func: u16,
+    flags: u16,
+    reserved: [u16; 10],
+}
+
+/// `struct v4l2_subdev_format`
+#[repr(C)]
+struct V4l2SubdevFormat {
+    which: u32,
+    pad: u32,
+    format: V4l2MbusFramefmt,
+    stream: u32,
+    reserved: [u32; 7],
+}
+
+/// `struct v4l2_rect`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct V4l2Rect {
+    pub(crate) left: i32,
+    pub(crate) top: i32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+/// `struct v4l2_subdev_selection`
+#[repr(C)]
+struct V4l2SubdevSelection {
+    which: u32,
+    pad: u32,
+    target: u32,
+    flags: u32,
+    r: V4l2Rect,
+    stream: u32,
+    reserved: [u32; 7],
+}
+
+const VIDIOC_SUBDEV_G_FMT: u32 = ioc(
+    IOC_READ | IOC_WRITE,
+    b'V',
+    4,
+    mem::size_of::<V4l2SubdevFormat>(),
+);
+const VIDIOC_SUBDEV_S_FMT: u32 = ioc(
+    IOC_READ | IOC_WRITE,
+    b'V',
+    5,
+    mem::size_of::<V4l2SubdevFormat>(),
+);
+const VIDIOC_SUBDEV_G_SELECTION: u32 = ioc(
+    IOC_READ | IOC_WRITE,
+    b'V',
+    61,
+    mem::size_of::<V4l2SubdevSelection>(),
+);
+const VIDIOC_SUBDEV_S_SELECTION: u32 = ioc(
+    IOC_READ | IOC_WRITE,
+    b'V',
+    62,
+    mem::size_of::<V4l2SubdevSelection>(),
+);
+
+/// 传感器输出 pad 上目标为 `target` 的生效选择区域描述。
+fn selection(target: u32, r: V4l2Rect) -> V4l2SubdevSelection {
+    let mut sel: V4l2SubdevSelection = unsafe { mem::zeroed() };
+    sel.which = V4L2_SUBDEV_FORMAT_ACTIVE;
+    sel.pad = SENSOR_SOURCE_PAD;
+    sel.target = target;
+    sel.r = r;
+    sel
+}
+
+/// 以 `VIDIOC_SUBDEV_G_SELECTION` 读取传感器子设备裁剪区域的上限，即可读出的像素阵列。
+pub(crate) fn get_crop_bounds(file: &File) -> io::Result<V4l2Rect> {
+    let mut sel = selection(V4L2_SEL_TGT_CROP_BOUNDS, V4l2Rect::default());
+    unsafe { ioctl(file, VIDIOC_SUBDEV_G_SELECTION, &mut sel)? };
+    Ok(sel.r)
+}
+
+/// 以 `VIDIOC_SUBDEV_S_SELECTION` 设置传感器子设备的裁剪区域，返回驱动按对齐要求调整后的区域。
+///
+/// 驱动不支持裁剪时返回 `ENOTTY` 或 `EINVAL`。
+pub(crate) fn set_crop(file: &File, r: V4l2Rect) -> io::Result<V4l2Rect> {
+    let mut sel = selection(V4L2_SEL_TGT_CROP, r);
+    unsafe { ioctl(file, VIDIOC_SUBDEV_S_SELECTION, &mut sel)? };
+    Ok(sel.r)
+}
+
+/// 设置传感器子设备输出 pad 的尺寸，media bus 代码等其他参数保持不变，返回驱动实际采用的尺寸。
+///
+/// 驱动按 V4L2 约定选择最接近的传感器模式而不报错，调用者须检查返回值。
+pub(crate) fn set_subdev_size(file: &File, width: u32, height: u32) -> io::Result<(u32, u32)> {
+    let mut f: V4l2SubdevFormat = unsafe { mem::zeroed() };
+    f.which = V4L2_SUBDEV_FORMAT_ACTIVE;
+    f.pad = SENSOR_SOURCE_PAD;
+    unsafe { ioctl(file, VIDIOC_SUBDEV_G_FMT, &mut f)? };
+    f.format.width = width;
+    f.format.height = height;
+    unsafe { ioctl(file, VIDIOC_SUBDEV_S_FMT, &mut f)? };
+    Ok((f.format.width, f.format.height))
+}
+
+const V4L2_MEMORY_MMAP: u32 = 1;
+const V4L2_BUF_FLAG_ERROR: u32 = 0x0040;
+/// 抓取时申请的缓冲区数量，驱动可能按其下限上调。
+const CAPTURE_BUFFERS: u32 = 2;
+/// 连续采集时申请的缓冲区数量，驱动可能按其下限上调。
+pub(crate) const STREAM_BUFFERS: u32 = 4;
+
+/// `struct v4l2_requestbuffers`
+#[repr(C)]
+struct V4l2RequestBuffers {
+    count: u32,
+    type_: u32,
+    memory: u32,
+    reserved: [u32; 2],
+}
+
+/// `struct v4l2_plane` 中的联合体
+#[repr(C)]
+#[derive(Copy, Clone)]
+union V4l2PlaneMem {
+    mem_offset: u32,
+    userptr: libc::c_ulong,
+    fd: i32,
+}
+
+/// `struct v4l2_plane`
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct V4l2Plane {
+    bytesused: u32,
+    length: u32,
+    m: V4l2PlaneMem,
+    data_offset: u32,
+    reserved: [u32; 11],
+}
+
+/// `struct v4l2_timecode`
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct V4l2Timecode {
+    type_: u32,
+    flags: u32,
+    frames: u8,
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    userbits: [u8; 4],
+}
+
+/// `struct v4l2_buffer` 中的联合体，多平面类型下使用 `planes`。
+#[repr(C)]
+#[derive(Copy, Clone)]
+union V4l2BufferMem {
+    offset: u32,
+    userptr: libc::c_ulong,
+    planes: *mut V4l2Plane,
+    fd: i32,
+}
+
+/// `struct v4l2_buffer`
+#[repr(C)]
+struct V4l2Buffer {
+    index: u32,
+    type_: u32,
+    bytesused: u32,
+    flags: u32,
+    field: u32,
+    timestamp: libc::timeval,
+    timecode: V4l2Timecode,
+    sequence: u32,
+    memory: u32,
+    m: V4l2BufferMem,
+    length: u32,
+    reserved2: u32,
+    request_fd: i32,
+}
+
+const VIDIOC_REQBUFS: u32 = ioc(
+    IOC_READ | IOC_WRITE,
+    b'V',
+    8,
+    mem::size_of::<V4l2RequestBuffers>(),
+);
+const VIDIOC_QUERYBUF: u32 = ioc(IOC_READ | IOC_WRITE, b'V', 9, mem::size_of::<V4l2Buffer>());
+const VIDIOC_QBUF: u32 = ioc(IOC_READ | IOC_WRITE, b'V', 15, mem::size_of::<V4l2Buffer>());
+const VIDIOC_DQBUF: u32 = ioc(IOC_READ | IOC_WRITE, b'V', 17, mem::size_of::<V4l2Buffer>());
+const VIDIOC_STREAMON: u32 = ioc(IOC_WRITE, b'V', 18, mem::size_of::<libc::c_int>());
+const VIDIOC_STREAMOFF: u32 = ioc(IOC_WRITE, b'V', 19, mem::size_of::<libc::c_int>());
+
+/// 申请 `count` 个 mmap 缓冲区，`count` 为 0 时归还全部缓冲区。
+fn request_buffers(file: &File, count: u32) -> io::Result<u32> {
+    let mut req = V4l2RequestBuffers {
+        count,
+        type_: V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE,
+        memory: V4L2_MEMORY_MMAP,
+        reserved: [0; 2],
+    };
+    unsafe { ioctl(file, VIDIOC_REQBUFS, &mut req)? };
+    Ok(req.count)
+}
+
+/// 以 `planes` 为平面数组的多平面 mmap 缓冲区描述。
+fn buffer(index: u32, planes: &mut [V4l2Plane; VIDEO_MAX_PLANES]) -> V4l2Buffer {
+    let mut buf: V4l2Buffer = unsafe { mem::zeroed() };
+    buf.index = index;
+    buf.type_ = V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE;
+    buf.memory = V4L2_MEMORY_MMAP;
+    buf.m.planes = planes.as_mut_ptr();
+    buf.length = VIDEO_MAX_PLANES as u32;
+    buf
+}
+
+/// 平面中有效数据的范围：`data_offset` 至 `bytesused`，不超过映射长度 `len`。
+fn plane_range(bytesused: u32, data_offset: u32, len: usize) -> Range<usize> {
+    let end = (bytesused as usize).min(len);
+    (data_offset as usize).min(end)..end
+}
+
+/// 一个已映射的缓冲区平面。
+struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+/// 一个 video 节点上的 mmap 采集会话，释放时停止采集、解除映射并归还缓冲区。
+pub(crate) struct Capture {
+    file: File,
+    buffers: Vec<Vec<Mapping>>,
+    /// 各缓冲区最近一次出队时各平面有效数据的范围。
+    ranges: Vec<Vec<Range<usize>>>,
+    streaming: bool,
+}
+
+// 映射只经由会话访问，会话可整体移交给采集线程。
+unsafe impl Send for Capture {}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        if self.streaming {
+            let mut ty = V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE as libc::c_int;
+            let _ = unsafe { ioctl(&self.file, VIDIOC_STREAMOFF, &mut ty) };
+        }
+        for m in self.buffers.iter().flatten() {
+            unsafe { libc::munmap(m.ptr, m.len) };
+        }
+        let _ = request_buffers(&self.file, 0);
+    }
+}
+
+impl Capture {
+    /// 在 video 节点上申请 `count` 个 mmap 缓冲区，映射并全部入队后开始采集。
+    ///
+    /// 节点已被其他进程采集时 `VIDIOC_REQBUFS` 返回 `EBUSY`。
+    pub(crate) fn start(file: File, count: u32) -> io::Result<Self> {
+        let count = request_buffers(&file, count)?;
+        let mut cap = Capture {
+            file,
+            buffers: Vec::new(),
+            ranges: Vec::new(),
+            streaming: false,
+        };
+        if count == 0 {
+            return Err(io::Error::from_raw_os_error(libc::ENOMEM));
+        }
+        for index in 0..count {
+            let mut planes = [unsafe { mem::zeroed::<V4l2Plane>() }; VIDEO_MAX_PLANES];
+            let mut buf = buffer(index, &mut planes);
+            unsafe { ioctl(&cap.file, VIDIOC_QUERYBUF, &mut buf)? };
+            cap.buffers.push(Vec::new());
+            cap.ranges.push(Vec::new());
+            for plane in &planes[..(buf.length as usize).min(VIDEO_MAX_PLANES)] {
+                let len = plane.length as usize;
+                // QUERYBUF 对 mmap 缓冲区填充 `mem_offset`。
+                let offset = unsafe { plane.m.mem_offset };
+                let ptr = unsafe {
+                    libc::mmap(
+                        ptr::null_mut(),
+                        len,
+                        libc::PROT_READ | libc::PROT_WRITE,
+                        libc::MAP_SHARED,
+                        cap.file.as_raw_fd(),
+                        offset as libc::off_t,
+                    )
+                };
+                if ptr == libc::MAP_FAILED {
+                    return Err(io::Error::last_os_error());
+                }
+                cap.buffers.last_mut().unwrap().push(Mapping { ptr, len });
+            }
+            unsafe { ioctl(&cap.file, VIDIOC_QBUF, &mut buf)? };
+        }
+
+        let mut ty = V4L2_BUF_TYPE_VIDEO_CAPTURE_MPLANE as libc::c_int;
+        unsafe { ioctl(&cap.file, VIDIOC_STREAMON, &mut ty)? };
+        cap.streaming = true;
+        Ok(cap)
+    }
+
+    /// 取出第一个无错误标记的缓冲区，返回 `(缓冲区序号, 帧序号)`；帧序号即驱动填充的 `sequence`。
+    ///
+    /// 带错误标记的缓冲区重新入队后继续等待；`timeout` 内没有取到时返回 `io::ErrorKind::TimedOut`。
+    pub(crate) fn dequeue(&mut self, timeout: Duration) -> io::Result<(u32, u32)> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let mut pfd = libc::pollfd {
+                fd: self.file.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let ms = left.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+            match unsafe { libc::poll(&mut pfd, 1, ms) } {
+                0 => return Err(io::ErrorKind::TimedOut.into()),
+                n if n < 0 => {
+                    let e = io::Error::last_os_error();
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            let mut planes = [unsafe { mem::zeroed::<V4l2Plane>() }; VIDEO_MAX_PLANES];
+            let mut buf = buffer(0, &mut planes);
+            unsafe { ioctl(&self.file, VIDIOC_DQBUF, &mut buf)? };
+            if buf.flags & V4L2_BUF_FLAG_ERROR != 0 {
+                // 数据不完整的帧归还后继续等待下一帧。
+                unsafe { ioctl(&self.file, VIDIOC_QBUF, &mut buf)? };
+                continue;
+            }
+            let maps = self
+                .buffers
+                .get(buf.index as usize)
+                .ok_or_else(|| io::Error::from_raw_os_error(libc::EIO))?;
+            self.ranges[buf.index as usize] = planes
+                .iter()
+                .zip(maps)
+                .take(buf.length as usize)
+                .map(|(plane, m)| plane_range(plane.bytesused, plane.data_offset, m.len))
+                .collect();
+            return Ok((buf.index, buf.sequence));
+        }
+    }
+
+    /// 已出队的缓冲区 `index` 各平面的有效数据。
+    ///
+    /// # Safety
+    /// 缓冲区须已由 `dequeue` 取出，返回的切片须在 `requeue` 归还该缓冲区之前释放，
+    /// 之后驱动会重新写入。
+    pub(crate) unsafe fn planes(&self, index: u32) -> Vec<&[u8]> {
+        let index = index as usize;
+        self.buffers[index]
+            .iter()
+            .zip(&self.ranges[index])
+            .map(|(m, range)| {
+                let bytes = std::slice::from_raw_parts(m.ptr as *const u8, m.len);
+                &bytes[range.clone()]
+            })
+            .collect()
+    }
+
+    /// 将缓冲区 `index` 重新入队，交还驱动写入。
+    pub(crate) fn requeue(&self, index: u32) -> io::Result<()> {
+        let mut planes = [unsafe { mem::zeroed::<V4l2Plane>() }; VIDEO_MAX_PLANES];
+        let mut buf = buffer(index, &mut planes);
+        unsafe { ioctl(&self.file, VIDIOC_QBUF, &mut buf) }
+    }
+}
+
+/// 从 video 节点取一帧：申请并映射缓冲区、开始采集、取出第一个无错误标记的缓冲区复制其各平面
+/// 数据，再停止采集并归还缓冲区。
+///
+/// 节点已被其他进程采集时 `VIDIOC_REQBUFS` 返回 `EBUSY`；`timeout` 内没有取到帧时返回
+/// `io::ErrorKind::TimedOut`。
+pub(crate) fn capture_frame(file: File, timeout: Duration) -> io::Result<Vec<u8>> {
+    let mut cap = Capture::start(file, CAPTURE_BUFFERS)?;
+    let (index, _) = cap.dequeue(timeout)?;
+    // 缓冲区已出队，`cap` 释放前驱动不再写入。
+    Ok(unsafe { cap.planes(index) }.concat())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_media_abi() {
+        // 与内核头文件中的结构体大小一致，请求码随之一致。
+        assert_eq!(mem::size_of::<MediaDeviceInfo>(), 256);
+        assert_eq!(mem::size_of::<MediaV2Topology>(), 72);
+        assert_eq!(mem::size_of::<MediaV2Entity>(), 96);
+        assert_eq!(mem::size_of::<MediaV2Interface>(), 112);
+        assert_eq!(mem::size_of::<MediaV2Link>(), 40);
+        assert_eq!(MEDIA_IOC_DEVICE_INFO, 0xc100_7c00);
+        assert_eq!(MEDIA_IOC_G_TOPOLOGY, 0xc048_7c04);
+        assert_eq!(c_string(b"rkisp1\0\0garbage"), "rkisp1");
+        assert_eq!(c_string(b"full"), "full");
+    }
+
+    #[test]
+    fn test_video_abi() {
+        assert_eq!(mem::size_of::<V4l2PlanePixFormat>(), 20);
+        assert_eq!(mem::size_of::<V4l2PixFormatMplane>(), 192);
+        if cfg!(target_pointer_width = "64") {
+            assert_eq!(mem::size_of::<V4l2Format>(), 208);
+            assert_eq!(VIDIOC_G_FMT, 0xc0d0_5604);
+            assert_eq!(VIDIOC_S_FMT, 0xc0d0_5605);
+        } else {
+            assert_eq!(mem::size_of::<V4l2Format>(), 204);
+            assert_eq!(VIDIOC_G_FMT, 0xc0cc_5604);
+            assert_eq!(VIDIOC_S_FMT, 0xc0cc_5605);
+        }
+
+        assert_eq!(mem::size_of::<V4l2RequestBuffers>(), 20);
+        assert_eq!(VIDIOC_REQBUFS, 0xc014_5608);
+        assert_eq!(VIDIOC_STREAMON, 0x4004_5612);
+        assert_eq!(VIDIOC_STREAMOFF, 0x4004_5613);
+        if cfg!(target_pointer_width = "64") {
+            assert_eq!(mem::size_of::<V4l2Plane>(), 64);
+            assert_eq!(mem::size_of::<V4l2Buffer>(), 88);
+            assert_eq!(VIDIOC_QUERYBUF, 0xc058_5609);
+            assert_eq!(VIDIOC_QBUF, 0xc058_560f);
+            assert_eq!(VIDIOC_DQBUF, 0xc058_5611);
+        }
+    }
+
+    #[test]
+    fn test_subdev_abi() {
+        assert_eq!(mem::size_of::<V4l2MbusFramefmt>(), 48);
+        assert_eq!(mem::size_of::<V4l2SubdevFormat>(), 88);
+        assert_eq!(mem::size_of::<V4l2Rect>(), 16);
+        assert_eq!(mem::size_of::<V4l2SubdevSelection>(), 64);
+        assert_eq!(VIDIOC_SUBDEV_G_FMT, 0xc058_5604);
+        assert_eq!(VIDIOC_SUBDEV_S_FMT, 0xc058_5605);
+        assert_eq!(VIDIOC_SUBDEV_G_SELECTION, 0xc040_563d);
+        assert_eq!(VIDIOC_SUBDEV_S_SELECTION, 0xc040_563e);
+    }
+
+    #[test]
+    fn test_plane_range() {
+        assert_eq!(plane_range(3_110_400, 0, 3_112_960), 0..3_110_400);
+        assert_eq!(plane_range(4096, 128, 4096), 128..4096);
+        // 驱动报告的长度超出映射时截断，偏移超出有效长度时为空。
+        assert_eq!(plane_range(8192, 0, 4096), 0..4096);
+        assert_eq!(plane_range(64, 128, 4096), 64..64);
+    }
+
+    #[test]
+    fn test_topology_devnode() {
+        let topo = Topology {
+            entities: vec![
+                Entity {
+                    id: 6,
+                    name: "rkisp_mainpath".to_owned(),
+                    function: 0x0001_0001,
+                },
+                Entity {
+                    id: 63,
+                    name: "m00_b_imx415 3-001a".to_owned(),
+                    function: MEDIA_ENT_F_CAM_SENSOR,
+                },
+            ],
+            interfaces: vec![
+                Interface {
+                    id: 7,
+                    intf_type: MEDIA_INTF_T_V4L_VIDEO,
+                    devnode: (81, 0),
+                },
+                Interface {
+                    id: 64,
+                    intf_type: MEDIA_INTF_T_V4L_SUBDEV,
+                    devnode: (81, 5),
+                },
+            ],
+            links: vec![
+                InterfaceLink {
+                    interface: 7,
+                    entity: 6,
+                },
+                InterfaceLink {
+                    interface: 64,
+                    entity: 63,
+                },
+            ],
+        };
+        assert_eq!(
+            topo.entities_with(MEDIA_ENT_F_CAM_SENSOR)
+                .map(|e| e.name.as_str())
+                .collect::<Vec<_>>(),
+            ["m00_b_imx415 3-001a"]
+        );
+        assert_eq!(
+            topo.devnode("rkisp_mainpath", MEDIA_INTF_T_V4L_VIDEO),
+            Some((81, 0))
+        );
+        // 传感器的设备节点是子设备接口，不是 video 接口。
+        assert_eq!(
+            topo.devnode("m00_b_imx415 3-001a", MEDIA_INTF_T_V4L_VIDEO),
+            None
+        );
+        assert_eq!(
+            topo.devnode("m00_b_imx415 3-001a", MEDIA_INTF_T_V4L_SUBDEV),
+            Some((81, 5))
+        );
+        assert_eq!(topo.devnode("rkisp_selfpath", MEDIA_INTF_T_V4L_VIDEO), None);
+
+        assert_eq!(
+            devname_from_uevent("MAJOR=81\nMINOR=0\nDEVNAME=video0\n"),
+            Some("video0")
+        );
+        assert_eq!(devname_from_uevent("MAJOR=81\n"), None);
+    }
+}
@@ -0,0 +1,107 @@
+//! 只读视图
+//!
+//! `ContextView` 借用 `Context`，只提供各模块契定中的读取接口，用于把 3A 状态交给界面、
+//! 统计等只需读取的组件，而不必担心其修改参数。视图不实现任何模块契定，也不能取回
+//! `&Context`，因此无法通过视图调用设置接口，见 `tests/ui/fail/view_setter.rs`。
+//!
+//! 读取接口与 `Context` 上的同名，视图不需要导入对应的契定。会消耗统计队列的读取接口
+//! （如 `get_focus_sharpness`）及依赖调用历史的接口（如 `get_focus_score`）不属于只读接口，
+//! 不在视图中提供。
+use super::acm::AutoColorManagment;
+use super::ae::AutoExposure;
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+use super::ae::LinExpAttr;
+use super::af::{AfSearchState, AfZoneWeights, AutoFocus};
+use super::awb::AutoWhiteBalance;
+use super::context::Context;
+use super::defog::Defog;
+use super::hdr::HighDynamicRange;
+use super::misc::Miscellaneous;
+use super::nr::{NoiseRemoval, NrState};
+use super::settings::CommonSettings;
+use super::sharpen::Sharpen;
+use super::sysctl::{IspErrorFlags, SystemControl};
+use super::types::{FrameRateInfo, GrayMode, OpMode, Rect, Strength, WbGain, WbScene, XCamResult};
+
+/// 一个只提供读取接口的 `Context` 借用。
+#[derive(Copy, Clone)]
+pub struct ContextView<'a> {
+    ctx: &'a Context,
+}
+
+/// 生成转发到模块契定的读取接口。
+macro_rules! view_getters {
+    ($($(#[$m:meta])* $trait:ident::$name:ident -> $ret:ty;)*) => {
+        $(
+            $(#[$m])*
+            #[doc = concat!("见 `", stringify!($trait), "::", stringify!($name), "`。")]
+            pub fn $name(&self) -> $ret {
+                $trait::$name(self.ctx)
+            }
+        )*
+    };
+}
+
+impl<'a> ContextView<'a> {
+    /// 创建 `ctx` 的只读视图。
+    pub fn new(ctx: &'a Context) -> Self {
+        Self { ctx }
+    }
+
+    /// 传感器实体名称。
+    pub fn sns_ent_name(&self) -> &'a str {
+        &self.ctx.sns_ent_name
+    }
+
+    /// 一次读取常用参数的当前值，见 `CommonSettings::query`。
+    pub fn get_common_settings(&self) -> XCamResult<CommonSettings> {
+        CommonSettings::query(self.ctx)
+    }
+
+    view_getters! {
+        AutoExposure::get_exp_mode -> XCamResult<OpMode>;
+        AutoExposure::get_exp_gain_range -> XCamResult<(f32, f32)>;
+        AutoExposure::get_exp_time_range -> XCamResult<(f32, f32)>;
+        AutoExposure::get_effective_exposure -> XCamResult<(f32, f32)>;
+        AutoExposure::get_base_iso -> u32;
+        AutoExposure::get_iso -> XCamResult<u32>;
+        #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+        AutoExposure::get_lin_exp_attr -> XCamResult<LinExpAttr>;
+        #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+        AutoExposure::get_exposure_compensation -> XCamResult<f32>;
+        #[cfg(any(feature = "v4_0", feature = "v5_0"))]
+        AutoExposure::get_ae_target_luma -> XCamResult<u8>;
+        AutoWhiteBalance::get_wb_mode -> XCamResult<OpMode>;
+        AutoWhiteBalance::get_mwb_scene -> XCamResult<WbScene>;
+        AutoWhiteBalance::get_mwb_gain -> XCamResult<WbGain>;
+        AutoWhiteBalance::get_mwb_ct -> XCamResult<u32>;
+        AutoFocus::get_focus_mode -> XCamResult<OpMode>;
+        AutoFocus::get_af_zone_weights -> XCamResult<AfZoneWeights>;
+        AutoFocus::get_af_search_state -> XCamResult<AfSearchState>;
+        NoiseRemoval::get_nr_mode -> XCamResult<OpMode>;
+        NoiseRemoval::get_anr_strength -> XCamResult<Strength>;
+        NoiseRemoval::get_effective_nr -> XCamResult<NrState>;
+        Sharpen::get_sharpness -> XCamResult<Strength>;
+        Sharpen::get_effective_sharpness -> XCamResult<u8>;
+        AutoColorManagment::get_brightness -> XCamResult<u32>;
+        AutoColorManagment::get_contrast -> XCamResult<u32>;
+        AutoColorManagment::get_saturation -> XCamResult<Strength>;
+        AutoColorManagment::get_hue -> XCamResult<u32>;
+        Defog::get_dhz_mode -> XCamResult<OpMode>;
+        HighDynamicRange::get_hdr_mode -> XCamResult<OpMode>;
+        Miscellaneous::get_gray_mode -> GrayMode;
+        Miscellaneous::get_frame_rate -> XCamResult<FrameRateInfo>;
+        Miscellaneous::get_mirror_flip -> XCamResult<(bool, bool)>;
+        SystemControl::is_streaming -> bool;
+        SystemControl::get_crop -> XCamResult<Rect>;
+        SystemControl::get_isp_error_flags -> XCamResult<IspErrorFlags>;
+        SystemControl::get_frame_id -> Option<u32>;
+    }
+}
+
+impl Context {
+    /// 返回只读视图，见 `ContextView`。
+    pub fn view(&self) -> ContextView<'_> {
+        ContextView::new(self)
+    }
+}
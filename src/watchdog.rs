@@ -0,0 +1,250 @@
+//! 流水线看门狗
+//!
+//! 长时间运行时 3A 分析器偶尔会停止更新，表现为元数据回调的帧号不再变化，只有停止后重新启动
+//! 才能恢复。看门狗在独立线程中定期检查帧号，出流期间帧号保持不变超过阈值即判定为停滞，
+//! 执行配置的恢复操作，并通过通道报告每次停滞与恢复的结果。
+use super::context::Context;
+use super::error::XCamError;
+use super::ffi;
+use super::settings::CameraSettings;
+use super::sysctl::SystemControl;
+use super::types::XCamResult;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// 停滞后的恢复操作。
+pub enum RecoveryAction {
+    /// 只调用回调，由调用者自行处理，回调的结果作为恢复结果报告。
+    Callback(Box<dyn FnMut(&Context) -> XCamResult<()> + Send>),
+    /// 依次停止、启动，再重新应用参数快照（若有）。
+    Restart(Option<CameraSettings>),
+}
+
+/// 一个描述看门狗事件的枚举。
+#[derive(Clone, Debug, PartialEq)]
+pub enum WatchdogEvent {
+    /// 检测到停滞。
+    Stalled {
+        /// 停滞时的帧号，出流后从未收到元数据回调时为 `None`。
+        frame_id: Option<u32>,
+        /// 帧号保持不变的时长。
+        stalled_for: Duration,
+    },
+    /// 第 `attempt` 次恢复成功，从 1 开始计数。
+    Recovered { attempt: u32 },
+    /// 第 `attempt` 次恢复失败。
+    RecoveryFailed { attempt: u32, error: XCamError },
+}
+
+/// 按帧号变化判断停滞。
+#[derive(Debug)]
+pub(crate) struct StallDetector {
+    threshold: Duration,
+    /// 最近一次变化后的帧号及其首次出现的时间。
+    last: Option<(Option<u32>, Instant)>,
+}
+
+impl StallDetector {
+    pub(crate) fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            last: None,
+        }
+    }
+
+    /// 记录一次检查，停滞时返回帧号保持不变的时长。
+    ///
+    /// 未出流时不判定停滞；帧号变化时重新计时。
+    pub(crate) fn update(
+        &mut self,
+        running: bool,
+        frame_id: Option<u32>,
+        now: Instant,
+    ) -> Option<Duration> {
+        if !running {
+            self.last = None;
+            return None;
+        }
+        match self.last {
+            Some((last, since)) if last == frame_id => {
+                let stalled_for = now.saturating_duration_since(since);
+                (stalled_for >= self.threshold).then_some(stalled_for)
+            }
+            _ => {
+                self.last = Some((frame_id, now));
+                None
+            }
+        }
+    }
+
+    /// 恢复后从 `now` 重新计时。
+    pub(crate) fn reset(&mut self, now: Instant) {
+        if let Some((_, since)) = self.last.as_mut() {
+            *since = now;
+        }
+    }
+}
+
+/// 停止后重新启动，再调用 `reapply` 重新应用参数。
+pub(crate) fn restart<C, F>(ctx: &C, reapply: F) -> XCamResult<()>
+where
+    C: SystemControl + ?Sized,
+    F: FnOnce(&C) -> XCamResult<()>,
+{
+    ctx.stop(false)?;
+    ctx.start()?;
+    reapply(ctx)
+}
+
+/// 一个在独立线程中运行的流水线看门狗，由 `Context::spawn_watchdog` 创建。
+///
+/// 句柄被释放时停止线程；工作线程只持有上下文的弱引用，上下文释放后同样退出。
+pub struct PipelineWatchdog {
+    stop: Arc<AtomicBool>,
+    events: Receiver<WatchdogEvent>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl PipelineWatchdog {
+    /// 看门狗事件的接收端。
+    pub fn events(&self) -> &Receiver<WatchdogEvent> {
+        &self.events
+    }
+}
+
+impl Drop for PipelineWatchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Context {
+    /// 启动流水线看门狗。
+    ///
+    /// 每隔 `check_interval` 检查一次元数据回调的帧号，出流期间帧号保持不变达到
+    /// `stale_threshold` 时报告 `WatchdogEvent::Stalled` 并执行 `action`，随后报告恢复结果，
+    /// 并从恢复完成时重新计时。两个时间须大于 0，否则返回 `XCAM_RETURN_ERROR_PARAM`。
+    ///
+    /// # Note
+    ///
+    /// 元数据回调的帧号为进程内全部上下文共享，多个上下文同时出流时只有全部停滞才会被检出。
+    pub fn spawn_watchdog(
+        self: &Arc<Self>,
+        check_interval: Duration,
+        stale_threshold: Duration,
+        action: RecoveryAction,
+    ) -> XCamResult<PipelineWatchdog> {
+        if check_interval.is_zero() || stale_threshold.is_zero() {
+            return Err(XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_PARAM));
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, events) = mpsc::channel();
+        let ctx = Arc::downgrade(self);
+        let flag = Arc::clone(&stop);
+        let handle = thread::Builder::new()
+            .name("rkaiq-watchdog".into())
+            .spawn(move || run_watchdog(ctx, flag, tx, check_interval, stale_threshold, action))
+            .map_err(|_| XCamError::from(ffi::XCamReturn::XCAM_RETURN_ERROR_THREAD))?;
+        Ok(PipelineWatchdog {
+            stop,
+            events,
+            handle: Some(handle),
+        })
+    }
+}
+
+fn run_watchdog(
+    ctx: Weak<Context>,
+    stop: Arc<AtomicBool>,
+    tx: Sender<WatchdogEvent>,
+    check_interval: Duration,
+    stale_threshold: Duration,
+    mut action: RecoveryAction,
+) {
+    let mut detector = StallDetector::new(stale_threshold);
+    let mut attempt = 0;
+    while !stop.load(Ordering::Acquire) {
+        let Some(ctx) = ctx.upgrade() else { break };
+        let frame_id = ctx.get_frame_id();
+        if let Some(stalled_for) = detector.update(ctx.is_streaming(), frame_id, Instant::now()) {
+            let _ = tx.send(WatchdogEvent::Stalled {
+                frame_id,
+                stalled_for,
+            });
+            attempt += 1;
+            let r = match &mut action {
+                RecoveryAction::Callback(f) => f(&ctx),
+                RecoveryAction::Restart(settings) => restart(&*ctx, |c| match settings {
+                    Some(s) => s.apply(c),
+                    None => Ok(()),
+                }),
+            };
+            let _ = tx.send(match r {
+                Ok(_) => WatchdogEvent::Recovered { attempt },
+                Err(error) => WatchdogEvent::RecoveryFailed { attempt, error },
+            });
+            detector.reset(Instant::now());
+        }
+        drop(ctx);
+        thread::park_timeout(check_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ae::AutoExposure;
+    use crate::mock::MockContext;
+
+    #[test]
+    fn test_stall_detector() {
+        let t0 = Instant::now();
+        let at = |ms| t0 + Duration::from_millis(ms);
+        let mut d = StallDetector::new(Duration::from_millis(500));
+        assert_eq!(d.update(true, Some(1), at(0)), None);
+        assert_eq!(d.update(true, Some(1), at(400)), None);
+        assert_eq!(d.update(true, Some(2), at(450)), None);
+        assert_eq!(d.update(true, Some(2), at(900)), None);
+        assert_eq!(
+            d.update(true, Some(2), at(1000)),
+            Some(Duration::from_millis(550))
+        );
+
+        d.reset(at(1000));
+        assert_eq!(d.update(true, Some(2), at(1400)), None);
+        assert!(d.update(true, Some(2), at(1500)).is_some());
+
+        // 停止出流后不判定停滞，重新出流时重新计时。
+        assert_eq!(d.update(false, Some(2), at(2000)), None);
+        assert_eq!(d.update(true, Some(2), at(2100)), None);
+        assert_eq!(d.update(true, Some(2), at(2500)), None);
+
+        // 出流后从未收到帧号同样视为停滞。
+        let mut d = StallDetector::new(Duration::from_millis(500));
+        assert_eq!(d.update(true, None, at(0)), None);
+        assert!(d.update(true, None, at(600)).is_some());
+    }
+
+    #[test]
+    fn test_restart() {
+        let ctx = MockContext::new();
+        let mut reapplied = false;
+        restart(&ctx, |c| {
+            reapplied = true;
+            c.set_exp_mode(crate::types::OpMode::Manual)
+        })
+        .unwrap();
+        assert!(reapplied);
+        assert_eq!(ctx.calls(), ["stop", "start", "set_exp_mode"]);
+
+        ctx.fail("start");
+        assert!(restart(&ctx, |_| Ok(())).is_err());
+    }
+}
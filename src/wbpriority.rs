@@ -0,0 +1,200 @@
+//! 白平衡与曝光的优先级
+//!
+//! 在强烈的有色光源下，AWB 为还原色彩会大幅提高较弱通道的增益，这些通道更早饱和，
+//! 相当于占用了 AE 的曝光余量；AE 按亮度收敛后，AWB 的增益又会推高饱和通道。
+//! `Context::set_wb_priority` 同时调整 AWB 的色温范围与 AE 的目标亮度，明确取舍方向。
+//!
+//! 各优先级只修改以下两个字段，均通过已有的接口设置：
+//!
+//! | 优先级 | AWB 色温范围（`set_awb_ct_range`） | AE 目标亮度（`set_ae_target_luma`） |
+//! |---|---|---|
+//! | `ColorAccurate` | [`AWB_CT_MIN`, `AWB_CT_MAX`]，完全校正 | 原值 × `COLOR_ACCURATE_LUMA_SCALE` |
+//! | `Balanced` | 原值 | 原值 |
+//! | `ExposurePriority` | `EXPOSURE_PRIORITY_CT_RANGE`，保留色偏 | 原值 |
+//!
+//! 原值在首次离开 `Balanced` 时捕获，切换到 `Balanced` 时恢复；在其他优先级之间切换
+//! 总是以原值为基准，不会累积。处于非 `Balanced` 期间通过其他接口修改这两个字段，
+//! 会在恢复时被覆盖。
+//!
+//! 仅 v4_0、v5_0 支持。
+use super::ae::AutoExposure;
+use super::awb::{AutoWhiteBalance, AWB_CT_MAX, AWB_CT_MIN};
+use super::context::Context;
+use super::display::enum_str;
+use super::types::XCamResult;
+
+/// 一个描述色彩与亮度取舍的枚举。
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WbPriority {
+    /// 优先还原色彩，降低目标亮度为增益留出余量。
+    ColorAccurate,
+    /// 标定文件中的默认取舍。
+    #[default]
+    Balanced,
+    /// 优先保证亮度，限制 AWB 对极端色温的校正。
+    ExposurePriority,
+}
+
+enum_str!(WbPriority, "white balance priority" {
+    ColorAccurate => "color_accurate",
+    Balanced => "balanced",
+    ExposurePriority => "exposure_priority",
+});
+
+/// `ColorAccurate` 时目标亮度相对原值的比例，约降低 0.25 EV。
+pub const COLOR_ACCURATE_LUMA_SCALE: f32 = 0.85;
+
+/// `ExposurePriority` 时的 AWB 色温范围，单位：K，超出范围的光源不再完全校正。
+pub const EXPOSURE_PRIORITY_CT_RANGE: (u32, u32) = (3000, 7000);
+
+/// 首次离开 `Balanced` 前的色温范围与目标亮度。
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct WbPriorityState {
+    priority: WbPriority,
+    ct_range: Option<(u32, u32)>,
+    target_luma: u8,
+}
+
+impl WbPriorityState {
+    fn capture<C: AutoExposure + AutoWhiteBalance>(ctx: &C) -> XCamResult<Self> {
+        Ok(Self {
+            priority: WbPriority::Balanced,
+            ct_range: ctx.get_awb_ct_range()?,
+            target_luma: ctx.get_ae_target_luma()?,
+        })
+    }
+
+    /// 恢复原值，两步都会执行，返回遇到的第一个错误。
+    fn restore<C: AutoExposure + AutoWhiteBalance>(&self, ctx: &C) -> XCamResult<()> {
+        let ct = match self.ct_range {
+            Some((min, max)) => ctx.set_awb_ct_range(min, max),
+            None => ctx.reset_awb_ct_range(),
+        };
+        let luma = ctx.set_ae_target_luma(self.target_luma);
+        ct.and(luma)
+    }
+
+    /// 以原值为基准写入 `priority` 的设置。
+    fn apply<C: AutoExposure + AutoWhiteBalance>(
+        &self,
+        ctx: &C,
+        priority: WbPriority,
+    ) -> XCamResult<()> {
+        match priority {
+            WbPriority::ColorAccurate => {
+                ctx.set_awb_ct_range(AWB_CT_MIN, AWB_CT_MAX)?;
+                let luma = (self.target_luma as f32 * COLOR_ACCURATE_LUMA_SCALE).round();
+                ctx.set_ae_target_luma(luma as u8)
+            }
+            WbPriority::Balanced => self.restore(ctx),
+            WbPriority::ExposurePriority => {
+                let (min, max) = EXPOSURE_PRIORITY_CT_RANGE;
+                ctx.set_awb_ct_range(min, max)?;
+                ctx.set_ae_target_luma(self.target_luma)
+            }
+        }
+    }
+}
+
+/// 切换优先级，`saved` 保存首次离开 `Balanced` 前的原值。
+///
+/// 设置失败时恢复原值并返回该错误，此后处于 `Balanced`。
+pub(crate) fn switch_wb_priority<C: AutoExposure + AutoWhiteBalance>(
+    ctx: &C,
+    saved: &mut Option<WbPriorityState>,
+    priority: WbPriority,
+) -> XCamResult<()> {
+    if priority == WbPriority::Balanced {
+        if let Some(state) = saved.take() {
+            state.restore(ctx)?;
+        }
+        return Ok(());
+    }
+    let state = match *saved {
+        Some(state) => state,
+        None => WbPriorityState::capture(ctx)?,
+    };
+    match state.apply(ctx, priority) {
+        Ok(_) => {
+            *saved = Some(WbPriorityState { priority, ..state });
+            Ok(())
+        }
+        Err(e) => {
+            let _ = state.restore(ctx);
+            *saved = None;
+            Err(e)
+        }
+    }
+}
+
+impl Context {
+    /// 设置色彩与亮度的取舍，各优先级修改的字段见模块说明。
+    pub fn set_wb_priority(&self, priority: WbPriority) -> XCamResult<()> {
+        let mut saved = self.wb_priority.lock().unwrap();
+        switch_wb_priority(self, &mut saved, priority)
+    }
+
+    /// 获取当前的色彩与亮度取舍。
+    pub fn get_wb_priority(&self) -> WbPriority {
+        self.wb_priority
+            .lock()
+            .unwrap()
+            .map_or(WbPriority::Balanced, |s| s.priority)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::XCamError;
+    use crate::mock::MockContext;
+
+    #[test]
+    fn test_wb_priority_restores() {
+        let ctx = MockContext::new();
+        ctx.set_ae_target_luma(100).unwrap();
+        assert_eq!(ctx.get_awb_ct_range(), Ok(None));
+
+        let mut saved = None;
+        switch_wb_priority(&ctx, &mut saved, WbPriority::ColorAccurate).unwrap();
+        assert_eq!(ctx.get_awb_ct_range(), Ok(Some((AWB_CT_MIN, AWB_CT_MAX))));
+        assert_eq!(ctx.get_ae_target_luma(), Ok(85));
+
+        // 在非 Balanced 之间切换以原值为基准。
+        switch_wb_priority(&ctx, &mut saved, WbPriority::ExposurePriority).unwrap();
+        assert_eq!(ctx.get_awb_ct_range(), Ok(Some(EXPOSURE_PRIORITY_CT_RANGE)));
+        assert_eq!(ctx.get_ae_target_luma(), Ok(100));
+        switch_wb_priority(&ctx, &mut saved, WbPriority::ColorAccurate).unwrap();
+        assert_eq!(ctx.get_ae_target_luma(), Ok(85));
+        assert_eq!(saved.map(|s| s.priority), Some(WbPriority::ColorAccurate));
+
+        switch_wb_priority(&ctx, &mut saved, WbPriority::Balanced).unwrap();
+        assert_eq!(saved, None);
+        assert_eq!(ctx.get_awb_ct_range(), Ok(None));
+        assert_eq!(ctx.get_ae_target_luma(), Ok(100));
+
+        // 原先设置过的色温范围同样恢复。
+        ctx.set_awb_ct_range(2500, 6500).unwrap();
+        switch_wb_priority(&ctx, &mut saved, WbPriority::ExposurePriority).unwrap();
+        switch_wb_priority(&ctx, &mut saved, WbPriority::Balanced).unwrap();
+        assert_eq!(ctx.get_awb_ct_range(), Ok(Some((2500, 6500))));
+    }
+
+    #[test]
+    fn test_wb_priority_rolls_back_on_failure() {
+        let ctx = MockContext::new();
+        ctx.set_ae_target_luma(100).unwrap();
+        ctx.fail("set_lin_exp_attr");
+
+        let mut saved = None;
+        assert_eq!(
+            switch_wb_priority(&ctx, &mut saved, WbPriority::ColorAccurate),
+            Err(XCamError::from(
+                crate::ffi::XCamReturn::XCAM_RETURN_ERROR_FAILED
+            ))
+        );
+        assert_eq!(saved, None);
+        assert_eq!(ctx.get_awb_ct_range(), Ok(None));
+        assert_eq!(ctx.get_ae_target_luma(), Ok(100));
+    }
+}
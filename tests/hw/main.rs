@@ -0,0 +1,163 @@
+//! 硬件集成测试
+//!
+//! 在开发板上以 `RKK_TEST_SENSOR=<传感器实体名称> cargo test --test hw` 运行，环境变量见
+//! `support` 模块；未设置时全部用例跳过。用例对每个封装的控制接口设置后读回，同一文件在
+//! 任一 SDK 版本特性下均可编译，仅部分版本支持的接口以 `cfg` 限定。新增接口时在此补充用例。
+mod support;
+
+use rkaiq::prelude::*;
+use rkaiq::types::{OpMode, Strength, WbGain};
+use support::{round_trip, with_context};
+
+fn strength(val: u8) -> Strength {
+    Strength::new(val).unwrap()
+}
+
+#[test]
+fn hw_exposure() {
+    with_context("hw_exposure", |ctx| {
+        round_trip!(ctx, set_exp_mode(OpMode::Manual), get_exp_mode() => OpMode::Manual);
+        round_trip!(ctx, set_exp_mode(OpMode::Auto), get_exp_mode() => OpMode::Auto);
+        round_trip!(
+            ctx,
+            set_exp_gain_range(1.0, 8.0),
+            get_exp_gain_range() => (1.0, 8.0),
+            tol = 0.01
+        );
+        round_trip!(
+            ctx,
+            set_exp_time_range(0.001, 0.03),
+            get_exp_time_range() => (0.001, 0.03),
+            tol = 1e-4
+        );
+        round_trip!(ctx, set_dark_area_boost_strth(2), get_dark_area_boost_strth() => 2);
+    });
+}
+
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+#[test]
+fn hw_exposure_v4() {
+    with_context("hw_exposure_v4", |ctx| {
+        round_trip!(
+            ctx,
+            set_exposure_compensation(1.0),
+            get_exposure_compensation() => 1.0,
+            tol = 0.01
+        );
+        round_trip!(
+            ctx,
+            set_exposure_compensation(0.0),
+            get_exposure_compensation() => 0.0,
+            tol = 0.01
+        );
+        round_trip!(ctx, set_ae_target_luma(60), get_ae_target_luma() => 60);
+        round_trip!(ctx, set_ae_deadband(8), get_ae_deadband() => 8);
+        round_trip!(
+            ctx,
+            set_global_digital_gain_cap(4.0),
+            get_global_digital_gain_cap() => Some(4.0)
+        );
+    });
+}
+
+#[test]
+fn hw_white_balance() {
+    with_context("hw_white_balance", |ctx| {
+        let gain = WbGain {
+            rgain: 1.8,
+            grgain: 1.0,
+            gbgain: 1.0,
+            bgain: 2.1,
+        };
+        round_trip!(ctx, set_mwb_gain(gain), get_mwb_gain() => gain, tol = 0.01);
+        round_trip!(ctx, set_mwb_ct(5000), get_mwb_ct() => 5000, tol = 50.0);
+        round_trip!(ctx, set_wb_mode(OpMode::Auto), get_wb_mode() => OpMode::Auto);
+    });
+}
+
+#[cfg(any(feature = "v4_0", feature = "v5_0"))]
+#[test]
+fn hw_white_balance_v4() {
+    with_context("hw_white_balance_v4", |ctx| {
+        round_trip!(
+            ctx,
+            set_awb_ct_range(2500, 7500),
+            get_awb_ct_range() => Some((2500, 7500))
+        );
+        round_trip!(ctx, set_awb_speed(0.5), get_awb_speed() => 0.5, tol = 0.01);
+    });
+}
+
+#[test]
+fn hw_picture() {
+    with_context("hw_picture", |ctx| {
+        round_trip!(ctx, set_brightness(60), get_brightness() => 60, tol = 1.0);
+        round_trip!(ctx, set_contrast(40), get_contrast() => 40, tol = 1.0);
+        round_trip!(
+            ctx,
+            set_saturation(strength(70)),
+            get_saturation() => strength(70),
+            tol = 1.0
+        );
+        round_trip!(ctx, set_hue(50), get_hue() => 50, tol = 1.0);
+        round_trip!(
+            ctx,
+            set_sharpness(strength(30)),
+            get_sharpness() => strength(30),
+            tol = 1.0
+        );
+        round_trip!(
+            ctx,
+            set_anr_strength(strength(40)),
+            get_anr_strength() => strength(40),
+            tol = 1.0
+        );
+    });
+}
+
+#[test]
+fn hw_modes() {
+    with_context("hw_modes", |ctx| {
+        round_trip!(ctx, set_nr_mode(OpMode::Auto), get_nr_mode() => OpMode::Auto);
+        round_trip!(ctx, set_dhz_mode(OpMode::Auto), get_dhz_mode() => OpMode::Auto);
+        round_trip!(ctx, set_hdr_mode(OpMode::Auto), get_hdr_mode() => OpMode::Auto);
+    });
+}
+
+#[cfg(any(feature = "v3_0", feature = "v4_0", feature = "v5_0"))]
+#[test]
+fn hw_dehaze_v3() {
+    with_context("hw_dehaze_v3", |ctx| {
+        round_trip!(
+            ctx,
+            set_dhz_strength(strength(50)),
+            get_dhz_strength() => strength(50),
+            tol = 1.0
+        );
+    });
+}
+
+#[test]
+fn hw_misc() {
+    with_context("hw_misc", |ctx| {
+        round_trip!(
+            ctx,
+            set_mirror_flip(true, false, 0),
+            get_mirror_flip() => (true, false)
+        );
+        round_trip!(
+            ctx,
+            set_mirror_flip(false, false, 0),
+            get_mirror_flip() => (false, false)
+        );
+    });
+}
+
+#[test]
+fn hw_streaming() {
+    with_context("hw_streaming", |ctx| {
+        assert!(ctx.is_streaming());
+        assert!(ctx.negotiated_format().is_ok());
+        assert!(ctx.get_isp_error_flags().is_ok());
+    });
+}
@@ -0,0 +1,146 @@
+//! 硬件测试支持
+//!
+//! 所有用例共用一个已出流的 `Context`，首次使用时按环境变量创建：
+//!
+//! * `RKK_TEST_SENSOR` - 传感器实体名称，未设置时跳过全部用例。
+//! * `RKK_TEST_IQ_DIR` - IQ 文件目录，默认为 `/etc/iqfiles`。
+//! * `RKK_TEST_SIZE` - 出流分辨率，写作 `宽x高`，默认为 `1920x1080`。
+//!
+//! 用例之间互斥执行，但不恢复修改过的参数，每个用例只应断言自己设置的参数。
+use rkaiq::prelude::*;
+use rkaiq::types::{Strength, WbGain, WorkingMode, XCamResult};
+use std::env;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+/// 传感器实体名称的环境变量。
+pub const SENSOR_ENV: &str = "RKK_TEST_SENSOR";
+/// IQ 文件目录的环境变量。
+pub const IQ_DIR_ENV: &str = "RKK_TEST_IQ_DIR";
+/// 出流分辨率的环境变量。
+pub const SIZE_ENV: &str = "RKK_TEST_SIZE";
+
+const DEFAULT_IQ_DIR: &str = "/etc/iqfiles";
+const DEFAULT_SIZE: (u32, u32) = (1920, 1080);
+
+static CONTEXT: OnceLock<Option<Mutex<Context>>> = OnceLock::new();
+
+fn parse_size(s: &str) -> Option<(u32, u32)> {
+    let (w, h) = s.split_once('x')?;
+    Some((w.trim().parse().ok()?, h.trim().parse().ok()?))
+}
+
+/// 按环境变量创建并启动上下文，未设置传感器时返回 `None`，创建失败时 panic。
+fn open() -> Option<Context> {
+    let sensor = env::var(SENSOR_ENV).ok()?;
+    let iq_dir = env::var(IQ_DIR_ENV).unwrap_or_else(|_| DEFAULT_IQ_DIR.into());
+    let (width, height) = match env::var(SIZE_ENV) {
+        Ok(s) => parse_size(&s).unwrap_or_else(|| panic!("invalid {}: {}", SIZE_ENV, s)),
+        Err(_) => DEFAULT_SIZE,
+    };
+    let ctx = Context::new(&sensor, &iq_dir)
+        .unwrap_or_else(|e| panic!("failed to open {}: {}", sensor, e));
+    ctx.prepare(width, height, WorkingMode::Normal)
+        .expect("prepare");
+    ctx.start().expect("start");
+    Some(ctx)
+}
+
+/// 以共用的上下文执行用例 `name`，未设置 `RKK_TEST_SENSOR` 时跳过。
+pub fn with_context<F: FnOnce(&Context)>(name: &str, f: F) {
+    let Some(ctx) = CONTEXT.get_or_init(|| open().map(Mutex::new)) else {
+        eprintln!("{}: skipped, {} is not set", name, SENSOR_ENV);
+        return;
+    };
+    // 前一个用例断言失败时锁被污染，上下文本身仍可用
+    let ctx = ctx.lock().unwrap_or_else(PoisonError::into_inner);
+    f(&ctx);
+}
+
+/// 一个可在容差内比较的类型。
+pub trait Close {
+    fn close(&self, other: &Self, tol: f32) -> bool;
+}
+
+impl Close for f32 {
+    fn close(&self, other: &Self, tol: f32) -> bool {
+        (self - other).abs() <= tol
+    }
+}
+
+impl Close for u32 {
+    fn close(&self, other: &Self, tol: f32) -> bool {
+        self.abs_diff(*other) as f32 <= tol
+    }
+}
+
+impl Close for Strength {
+    fn close(&self, other: &Self, tol: f32) -> bool {
+        self.get().abs_diff(other.get()) as f32 <= tol
+    }
+}
+
+impl<A: Close, B: Close> Close for (A, B) {
+    fn close(&self, other: &Self, tol: f32) -> bool {
+        self.0.close(&other.0, tol) && self.1.close(&other.1, tol)
+    }
+}
+
+impl Close for WbGain {
+    fn close(&self, other: &Self, tol: f32) -> bool {
+        self.rgain.close(&other.rgain, tol)
+            && self.grgain.close(&other.grgain, tol)
+            && self.gbgain.close(&other.gbgain, tol)
+            && self.bgain.close(&other.bgain, tol)
+    }
+}
+
+/// 取出用例中一次调用的结果：`XCamError::Unsupported` 时打印并返回 `None`，其余错误 panic。
+pub fn checked<T>(name: &str, r: XCamResult<T>) -> Option<T> {
+    match r {
+        Ok(v) => Some(v),
+        Err(XCamError::Unsupported) => {
+            eprintln!("{}: skipped, unsupported", name);
+            None
+        }
+        Err(e) => panic!("{}: {}", name, e),
+    }
+}
+
+/// 设置后读回并断言与期望值相等；`tol = 容差` 时按 `Close` 比较。
+///
+/// 写作 `round_trip!(上下文, 设置接口(参数...), 读取接口(参数...) => 期望值)`，
+/// 错误的处理见 `checked`。
+macro_rules! round_trip {
+    (
+        $ctx:expr, $set:ident($($sarg:expr),* $(,)?), $get:ident($($garg:expr),* $(,)?)
+        => $want:expr $(,)?
+    ) => {{
+        let ctx = $ctx;
+        let name = concat!(stringify!($set), " -> ", stringify!($get));
+        let r = ctx.$set($($sarg),*).and_then(|_| ctx.$get($($garg),*));
+        if let Some(got) = $crate::support::checked(name, r) {
+            assert_eq!(got, $want, "{}", name);
+        }
+    }};
+    (
+        $ctx:expr, $set:ident($($sarg:expr),* $(,)?), $get:ident($($garg:expr),* $(,)?)
+        => $want:expr, tol = $tol:expr $(,)?
+    ) => {{
+        let ctx = $ctx;
+        let name = concat!(stringify!($set), " -> ", stringify!($get));
+        let r = ctx.$set($($sarg),*).and_then(|_| ctx.$get($($garg),*));
+        if let Some(got) = $crate::support::checked(name, r) {
+            let want = $want;
+            assert!(
+                $crate::support::Close::close(&got, &want, $tol),
+                "{}: {:?} != {:?} (tol {})",
+                name,
+                got,
+                want,
+                $tol
+            );
+        }
+    }};
+}
+
+pub(crate) use round_trip;
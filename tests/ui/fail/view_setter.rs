@@ -0,0 +1,9 @@
+// 视图不实现模块契定，即使契定已导入也不能调用设置接口。
+use rkaiq::prelude::*;
+use rkaiq::view::ContextView;
+
+fn disable_defog(view: ContextView<'_>) {
+    let _ = view.enable_dhz();
+}
+
+fn main() {}
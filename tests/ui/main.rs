@@ -0,0 +1,9 @@
+//! 编译期检查
+//!
+//! 以 `cargo test --test ui` 运行，检查应被类型系统拒绝的用法：`fail` 目录中的每个用例须编译
+//! 失败，且报错与同名的 `.stderr` 文件一致。报错文本随编译器版本变化，升级工具链后以
+//! `TRYBUILD=overwrite cargo test --test ui` 重新生成并检查差异。
+#[test]
+fn ui() {
+    trybuild::TestCases::new().compile_fail("tests/ui/fail/*.rs");
+}